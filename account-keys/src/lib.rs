@@ -13,6 +13,7 @@ mod burn_address;
 mod domain_separators;
 mod error;
 mod identity;
+mod view_only_bundle;
 
 pub use crate::{
     account_keys::{
@@ -22,4 +23,5 @@ pub use crate::{
     burn_address::{burn_address, burn_address_view_private, BURN_ADDRESS_VIEW_PRIVATE_BYTES},
     error::{Error, Result},
     identity::{RootEntropy, RootIdentity},
+    view_only_bundle::{KeyImageCheckpoint, ViewOnlyAccountBundle},
 };
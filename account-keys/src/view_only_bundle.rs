@@ -0,0 +1,265 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! A bundle of everything needed to watch an account without being able to
+//! spend from it.
+//!
+//! The view private key and spend public key are enough to find the account's
+//! outputs, but not to tell when they are spent, since computing an output's
+//! key image needs the spend private key. So the bundle also carries a
+//! checkpoint: the key images of the account's unspent outputs as of some
+//! block. A watch-only tool can then detect spends of those outputs, and
+//! starts scanning for new outputs after the checkpoint.
+//!
+//! mc-api encodes the bundle into a b58 string with the other printable
+//! messages, so that it can be copied between tools.
+
+use crate::account_keys::{AccountKey, ViewAccountKey};
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use mc_crypto_keys::{RistrettoPrivate, RistrettoPublic};
+use mc_crypto_ring_signature::KeyImage;
+#[cfg(feature = "prost")]
+use prost::Message;
+use zeroize::Zeroize;
+
+/// The key images of an account's unspent outputs, as of a block.
+#[derive(Clone, Eq, PartialEq, Zeroize)]
+#[cfg_attr(feature = "prost", derive(Message))]
+#[cfg_attr(not(feature = "prost"), derive(Debug))]
+pub struct KeyImageCheckpoint {
+    /// The number of blocks the checkpoint covers. Outputs received in blocks
+    /// with this index or later are not included.
+    #[cfg_attr(feature = "prost", prost(uint64, tag = "1"))]
+    pub block_index: u64,
+
+    /// The key images of the account's outputs which were unspent as of
+    /// `block_index`.
+    #[cfg_attr(feature = "prost", prost(message, repeated, tag = "2"))]
+    pub key_images: Vec<KeyImage>,
+}
+
+impl KeyImageCheckpoint {
+    /// A checkpoint of the given key images, as of `block_index`.
+    pub fn new(block_index: u64, key_images: Vec<KeyImage>) -> Self {
+        Self {
+            block_index,
+            key_images,
+        }
+    }
+}
+
+/// A view-only export of an account: its view private key, spend public key,
+/// fog info and, optionally, a key image checkpoint.
+///
+/// The fog info tells a watch-only tool which fog service the account uses.
+/// It can't be used to create fog subaddresses, since signing them needs the
+/// spend private key.
+#[derive(Clone, Zeroize)]
+#[cfg_attr(feature = "prost", derive(Message))]
+#[zeroize(drop)]
+pub struct ViewOnlyAccountBundle {
+    /// Private key 'a' used for view-key matching.
+    #[cfg_attr(feature = "prost", prost(message, required, tag = "1"))]
+    view_private_key: RistrettoPrivate,
+
+    /// Public key `B` used for generating Public Addresses.
+    #[cfg_attr(feature = "prost", prost(message, required, tag = "2"))]
+    spend_public_key: RistrettoPublic,
+
+    /// Fog Report server url (if user has Fog service), empty string otherwise
+    #[cfg_attr(feature = "prost", prost(string, tag = "3"))]
+    fog_report_url: String,
+
+    /// Fog Report Key (if user has Fog service), empty otherwise
+    #[cfg_attr(feature = "prost", prost(string, tag = "4"))]
+    fog_report_id: String,
+
+    /// Fog Authority Key Fingerprint (if user has Fog service), empty otherwise
+    #[cfg_attr(feature = "prost", prost(bytes, tag = "5"))]
+    fog_authority_spki: Vec<u8>,
+
+    /// The key images of the account's unspent outputs, if known.
+    #[cfg_attr(feature = "prost", prost(message, optional, tag = "6"))]
+    key_image_checkpoint: Option<KeyImageCheckpoint>,
+}
+
+// Note: like ViewAccountKey, PartialEq compares the keys by the default
+// subaddress, to avoid leaking private key details over side-channels.
+impl PartialEq for ViewOnlyAccountBundle {
+    fn eq(&self, other: &Self) -> bool {
+        self.view_account_key() == other.view_account_key()
+            && self.fog_report_url == other.fog_report_url
+            && self.fog_report_id == other.fog_report_id
+            && self.fog_authority_spki == other.fog_authority_spki
+            && self.key_image_checkpoint == other.key_image_checkpoint
+    }
+}
+
+impl Eq for ViewOnlyAccountBundle {}
+
+impl ViewOnlyAccountBundle {
+    /// A bundle for an account without a fog service.
+    ///
+    /// # Arguments
+    /// * `view_account_key` - The account's view private and spend public keys.
+    /// * `key_image_checkpoint` - The key images of its unspent outputs, if
+    ///   known.
+    pub fn new(
+        view_account_key: &ViewAccountKey,
+        key_image_checkpoint: Option<KeyImageCheckpoint>,
+    ) -> Self {
+        Self {
+            view_private_key: *view_account_key.view_private_key(),
+            spend_public_key: *view_account_key.spend_public_key(),
+            fog_report_url: Default::default(),
+            fog_report_id: Default::default(),
+            fog_authority_spki: Default::default(),
+            key_image_checkpoint,
+        }
+    }
+
+    /// A bundle for an account with a fog service.
+    ///
+    /// # Arguments
+    /// * `view_account_key` - The account's view private and spend public keys.
+    /// * `fog_report_url` - User's fog report server url.
+    /// * `fog_report_id` - User's fog report id.
+    /// * `fog_authority_spki` - Fog authority subject public key info.
+    /// * `key_image_checkpoint` - The key images of its unspent outputs, if
+    ///   known.
+    pub fn new_with_fog(
+        view_account_key: &ViewAccountKey,
+        fog_report_url: impl ToString,
+        fog_report_id: String,
+        fog_authority_spki: impl AsRef<[u8]>,
+        key_image_checkpoint: Option<KeyImageCheckpoint>,
+    ) -> Self {
+        Self {
+            view_private_key: *view_account_key.view_private_key(),
+            spend_public_key: *view_account_key.spend_public_key(),
+            fog_report_url: fog_report_url.to_string(),
+            fog_report_id,
+            fog_authority_spki: fog_authority_spki.as_ref().to_vec(),
+            key_image_checkpoint,
+        }
+    }
+
+    /// A bundle for the view-only part of `account_key`, including its fog
+    /// info.
+    pub fn from_account_key(
+        account_key: &AccountKey,
+        key_image_checkpoint: Option<KeyImageCheckpoint>,
+    ) -> Self {
+        Self::new_with_fog(
+            &ViewAccountKey::from(account_key),
+            account_key.fog_report_url().unwrap_or_default(),
+            account_key.fog_report_id().unwrap_or_default().into(),
+            account_key.fog_authority_spki().unwrap_or_default(),
+            key_image_checkpoint,
+        )
+    }
+
+    /// Get the view private key.
+    pub fn view_private_key(&self) -> &RistrettoPrivate {
+        &self.view_private_key
+    }
+
+    /// Get the spend public key.
+    pub fn spend_public_key(&self) -> &RistrettoPublic {
+        &self.spend_public_key
+    }
+
+    /// The keys needed to find the account's outputs.
+    pub fn view_account_key(&self) -> ViewAccountKey {
+        ViewAccountKey::new(self.view_private_key, self.spend_public_key)
+    }
+
+    /// Access the fog url (if it exists).
+    pub fn fog_report_url(&self) -> Option<&str> {
+        if self.fog_report_url.is_empty() {
+            None
+        } else {
+            Some(&self.fog_report_url)
+        }
+    }
+
+    /// Access the fog report key (if it exists).
+    pub fn fog_report_id(&self) -> Option<&str> {
+        if self.fog_report_id.is_empty() {
+            None
+        } else {
+            Some(&self.fog_report_id)
+        }
+    }
+
+    /// Access the fog authority subject public key info (if it exists).
+    pub fn fog_authority_spki(&self) -> Option<&[u8]> {
+        if self.fog_authority_spki.is_empty() {
+            None
+        } else {
+            Some(&self.fog_authority_spki)
+        }
+    }
+
+    /// Access the key image checkpoint (if it exists).
+    pub fn key_image_checkpoint(&self) -> Option<&KeyImageCheckpoint> {
+        self.key_image_checkpoint.as_ref()
+    }
+}
+
+#[cfg(all(test, feature = "prost"))]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    // Deserializing should recover a serialized ViewOnlyAccountBundle.
+    fn mc_util_serial_prost_roundtrip_view_only_account_bundle() {
+        mc_util_test_helper::run_with_several_seeds(|mut rng| {
+            let checkpoint = KeyImageCheckpoint::new(
+                1234,
+                vec![KeyImage::from(1), KeyImage::from(2), KeyImage::from(3)],
+            );
+            for account_key in [
+                AccountKey::random(&mut rng),
+                AccountKey::random_with_fog(&mut rng),
+            ] {
+                for checkpoint in [None, Some(checkpoint.clone())] {
+                    let bundle = ViewOnlyAccountBundle::from_account_key(&account_key, checkpoint);
+                    let ser = mc_util_serial::encode(&bundle);
+                    let result: ViewOnlyAccountBundle = mc_util_serial::decode(&ser).unwrap();
+                    assert_eq!(bundle, result);
+                }
+            }
+        });
+    }
+
+    #[test]
+    // A bundle should carry the view-only part of the account key it was made
+    // from.
+    fn bundle_matches_account_key() {
+        mc_util_test_helper::run_with_several_seeds(|mut rng| {
+            let account_key = AccountKey::random_with_fog(&mut rng);
+            let bundle = ViewOnlyAccountBundle::from_account_key(&account_key, None);
+
+            assert_eq!(
+                bundle.view_account_key(),
+                ViewAccountKey::from(&account_key)
+            );
+            assert_eq!(bundle.fog_report_url(), account_key.fog_report_url());
+            assert_eq!(bundle.fog_report_id(), account_key.fog_report_id());
+            assert_eq!(
+                bundle.fog_authority_spki(),
+                account_key.fog_authority_spki()
+            );
+            assert!(bundle.key_image_checkpoint().is_none());
+
+            let bundle =
+                ViewOnlyAccountBundle::from_account_key(&AccountKey::random(&mut rng), None);
+            assert_eq!(bundle.fog_report_url(), None);
+            assert_eq!(bundle.fog_authority_spki(), None);
+        });
+    }
+}
@@ -22,6 +22,7 @@ impl From<&BlockMetadataContents> for blockchain::BlockMetadataContents {
             }
         }
         proto.set_responder_id(src.responder_id().to_string());
+        proto.set_labels(src.labels().into());
         proto
     }
 }
@@ -49,12 +50,9 @@ impl TryFrom<&blockchain::BlockMetadataContents> for BlockMetadataContents {
         };
         let responder_id = ResponderId::from_str(&src.responder_id)
             .map_err(|_| ConversionError::InvalidContents)?;
-        Ok(BlockMetadataContents::new(
-            block_id,
-            quorum_set,
-            attestation_evidence,
-            responder_id,
-        ))
+        BlockMetadataContents::new(block_id, quorum_set, attestation_evidence, responder_id)
+            .with_labels(src.get_labels().to_vec())
+            .map_err(|_| ConversionError::InvalidContents)
     }
 }
 
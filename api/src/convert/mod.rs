@@ -58,6 +58,7 @@ mod watcher;
 
 // printable
 mod tx_out_gift_code;
+mod view_only_account_bundle;
 
 // error
 mod error;
@@ -0,0 +1,141 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Convert to/from printable::ViewOnlyAccountBundle
+
+use crate::{external, printable, ConversionError};
+use mc_account_keys::{KeyImageCheckpoint, ViewAccountKey, ViewOnlyAccountBundle};
+use mc_crypto_keys::{RistrettoPrivate, RistrettoPublic};
+use mc_transaction_core::ring_signature::KeyImage;
+use protobuf::RepeatedField;
+
+/// Convert KeyImageCheckpoint --> printable::KeyImageCheckpoint.
+impl From<&KeyImageCheckpoint> for printable::KeyImageCheckpoint {
+    fn from(src: &KeyImageCheckpoint) -> Self {
+        let mut checkpoint = printable::KeyImageCheckpoint::new();
+        checkpoint.set_block_index(src.block_index);
+        checkpoint.set_key_images(RepeatedField::from_vec(
+            src.key_images
+                .iter()
+                .map(external::KeyImage::from)
+                .collect(),
+        ));
+        checkpoint
+    }
+}
+
+/// Convert printable::KeyImageCheckpoint --> KeyImageCheckpoint.
+impl TryFrom<&printable::KeyImageCheckpoint> for KeyImageCheckpoint {
+    type Error = ConversionError;
+
+    fn try_from(src: &printable::KeyImageCheckpoint) -> Result<Self, Self::Error> {
+        let key_images = src
+            .get_key_images()
+            .iter()
+            .map(KeyImage::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::new(src.get_block_index(), key_images))
+    }
+}
+
+/// Convert ViewOnlyAccountBundle --> printable::ViewOnlyAccountBundle.
+impl From<&ViewOnlyAccountBundle> for printable::ViewOnlyAccountBundle {
+    fn from(src: &ViewOnlyAccountBundle) -> Self {
+        let mut bundle = printable::ViewOnlyAccountBundle::new();
+        bundle.set_view_private_key(external::RistrettoPrivate::from(src.view_private_key()));
+        bundle.set_spend_public_key(external::CompressedRistretto::from(src.spend_public_key()));
+
+        if let Some(url) = src.fog_report_url() {
+            bundle.set_fog_report_url(url.to_string());
+        }
+
+        if let Some(key) = src.fog_report_id() {
+            bundle.set_fog_report_id(key.to_string());
+        }
+
+        if let Some(spki) = src.fog_authority_spki() {
+            bundle.set_fog_authority_spki(spki.to_vec());
+        }
+
+        if let Some(checkpoint) = src.key_image_checkpoint() {
+            bundle.set_key_image_checkpoint(checkpoint.into());
+        }
+
+        bundle
+    }
+}
+
+/// Convert printable::ViewOnlyAccountBundle --> ViewOnlyAccountBundle.
+impl TryFrom<&printable::ViewOnlyAccountBundle> for ViewOnlyAccountBundle {
+    type Error = ConversionError;
+
+    fn try_from(src: &printable::ViewOnlyAccountBundle) -> Result<Self, Self::Error> {
+        let view_private_key = RistrettoPrivate::try_from(src.get_view_private_key())?;
+        let spend_public_key = RistrettoPublic::try_from(src.get_spend_public_key())?;
+        let view_account_key = ViewAccountKey::new(view_private_key, spend_public_key);
+
+        let key_image_checkpoint = src
+            .key_image_checkpoint
+            .as_ref()
+            .map(KeyImageCheckpoint::try_from)
+            .transpose()?;
+
+        Ok(Self::new_with_fog(
+            &view_account_key,
+            src.get_fog_report_url(),
+            src.get_fog_report_id().to_string(),
+            src.get_fog_authority_spki(),
+            key_image_checkpoint,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mc_account_keys::AccountKey;
+    use mc_util_serial::{decode, encode};
+    use protobuf::Message;
+    use rand::rngs::StdRng;
+    use rand_core::SeedableRng;
+
+    #[test]
+    // test conversion between ViewOnlyAccountBundle <-->
+    // printable::ViewOnlyAccountBundle.
+    fn test_view_only_account_bundle_serialization() {
+        let mut rng: StdRng = SeedableRng::from_seed([1u8; 32]);
+        let checkpoint = KeyImageCheckpoint::new(
+            1234,
+            vec![KeyImage::from(1), KeyImage::from(2), KeyImage::from(3)],
+        );
+
+        for source in [
+            ViewOnlyAccountBundle::from_account_key(&AccountKey::random(&mut rng), None),
+            ViewOnlyAccountBundle::from_account_key(
+                &AccountKey::random_with_fog(&mut rng),
+                Some(checkpoint),
+            ),
+        ] {
+            // Roundtrip from protobuf should return the same object
+            {
+                let external = printable::ViewOnlyAccountBundle::from(&source);
+                let recovered = ViewOnlyAccountBundle::try_from(&external).unwrap();
+                assert_eq!(source, recovered);
+            }
+
+            // Encoding with prost, decoding with protobuf should produce the same object
+            {
+                let bytes = encode(&source);
+                let recovered = printable::ViewOnlyAccountBundle::parse_from_bytes(&bytes).unwrap();
+                assert_eq!(recovered, printable::ViewOnlyAccountBundle::from(&source));
+            }
+
+            // Encoding with protobuf, decoding with prost should produce the same object
+            {
+                let external = printable::ViewOnlyAccountBundle::from(&source);
+                let bytes = external.write_to_bytes().unwrap();
+                let recovered: ViewOnlyAccountBundle = decode(&bytes).unwrap();
+                assert_eq!(source, recovered);
+            }
+        }
+    }
+}
@@ -76,7 +76,10 @@ mod display_tests {
     use super::Error;
     use crate::{
         external,
-        printable::{PaymentRequest, PrintableWrapper, TransferPayload},
+        printable::{
+            KeyImageCheckpoint, PaymentRequest, PrintableWrapper, TransferPayload,
+            ViewOnlyAccountBundle,
+        },
     };
     use mc_test_vectors_b58_encodings::{
         B58EncodePublicAddressWithFog, B58EncodePublicAddressWithoutFog,
@@ -210,6 +213,25 @@ mod display_tests {
         assert_eq!(wrapper, decoded);
     }
 
+    #[test]
+    fn test_view_only_account_bundle_roundtrip() {
+        let mut checkpoint = KeyImageCheckpoint::new();
+        checkpoint.set_block_index(100);
+        checkpoint.mut_key_images().push(vec![3u8; 32].into());
+
+        let mut bundle = ViewOnlyAccountBundle::new();
+        bundle.mut_view_private_key().set_data(vec![1u8; 32]);
+        bundle.mut_spend_public_key().set_data(vec![2u8; 32]);
+        bundle.set_fog_report_url("mob://fog.example.com".to_string());
+        bundle.set_key_image_checkpoint(checkpoint);
+
+        let mut wrapper = PrintableWrapper::new();
+        wrapper.set_view_only_account_bundle(bundle);
+        let encoded = wrapper.b58_encode().unwrap();
+        let decoded = PrintableWrapper::b58_decode(encoded).unwrap();
+        assert_eq!(wrapper, decoded);
+    }
+
     #[test]
     fn test_bad_checksum() {
         let public_address = sample_public_address();
@@ -0,0 +1,43 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Canonical JSON representations of the external API types.
+//!
+//! These follow the proto3 JSON mapping: field names are lowerCamelCase,
+//! bytes are base64, 64-bit integers are strings, and fields with default
+//! values are omitted. REST gateways and debugging tools should use these
+//! rather than converting the types by hand, so that every tool renders the
+//! same object the same way.
+
+use crate::{blockchain, external};
+use displaydoc::Display;
+use protobuf::{json, Message};
+
+/// JSON encoding / decoding errors
+#[derive(Clone, Debug, Eq, PartialEq, Display)]
+pub enum JsonError {
+    /// JSON printing error: {0}
+    Print(String),
+
+    /// JSON parsing error: {0}
+    Parse(String),
+}
+
+impl std::error::Error for JsonError {}
+
+/// A type with a canonical JSON representation.
+pub trait CanonicalJson: Message + Sized {
+    /// Print this object as canonical JSON
+    fn to_canonical_json(&self) -> Result<String, JsonError> {
+        json::print_to_string(self).map_err(|err| JsonError::Print(format!("{err:?}")))
+    }
+
+    /// Parse an object from its canonical JSON. Unknown fields are rejected.
+    fn from_canonical_json(json: &str) -> Result<Self, JsonError> {
+        json::parse_from_str(json).map_err(|err| JsonError::Parse(format!("{err:?}")))
+    }
+}
+
+impl CanonicalJson for external::TxOut {}
+impl CanonicalJson for external::Receipt {}
+impl CanonicalJson for external::PublicAddress {}
+impl CanonicalJson for blockchain::BlockContents {}
@@ -16,5 +16,6 @@ mod autogenerated_code {
 mod convert;
 
 pub mod display;
+pub mod json;
 
 pub use crate::{autogenerated_code::*, convert::*};
@@ -0,0 +1,126 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Tests that the canonical JSON of the external types matches the golden
+//! files in tests/data/json, and round-trips.
+
+use mc_api::{
+    blockchain,
+    external::{
+        CompressedRistretto, EncryptedFogHint, EncryptedMemo, KeyImage, MaskedAmount,
+        PublicAddress, Receipt, TxOut, TxOutConfirmationNumber,
+    },
+    json::{CanonicalJson, JsonError},
+};
+use serde_json::Value;
+
+fn compressed_ristretto(byte: u8) -> CompressedRistretto {
+    let mut point = CompressedRistretto::new();
+    point.set_data(vec![byte; 32]);
+    point
+}
+
+fn tx_out(seed: u8) -> TxOut {
+    let mut masked_amount = MaskedAmount::new();
+    masked_amount.set_commitment(compressed_ristretto(seed));
+    masked_amount.set_masked_value(1000 + seed as u64);
+    masked_amount.set_masked_token_id(vec![seed + 1; 8]);
+
+    let mut e_fog_hint = EncryptedFogHint::new();
+    e_fog_hint.set_data(vec![seed + 4; 84]);
+    let mut e_memo = EncryptedMemo::new();
+    e_memo.set_data(vec![seed + 5; 66]);
+
+    let mut tx_out = TxOut::new();
+    tx_out.set_masked_amount_v2(masked_amount);
+    tx_out.set_target_key(compressed_ristretto(seed + 2));
+    tx_out.set_public_key(compressed_ristretto(seed + 3));
+    tx_out.set_e_fog_hint(e_fog_hint);
+    tx_out.set_e_memo(e_memo);
+    tx_out
+}
+
+fn receipt() -> Receipt {
+    let mut confirmation = TxOutConfirmationNumber::new();
+    confirmation.set_hash(vec![7; 32]);
+    let mut masked_amount = MaskedAmount::new();
+    masked_amount.set_commitment(compressed_ristretto(8));
+    masked_amount.set_masked_value(12345);
+
+    let mut receipt = Receipt::new();
+    receipt.set_public_key(compressed_ristretto(4));
+    receipt.set_confirmation(confirmation);
+    receipt.set_tombstone_block(100);
+    receipt.set_masked_amount_v1(masked_amount);
+    receipt
+}
+
+fn public_address() -> PublicAddress {
+    let mut public_address = PublicAddress::new();
+    public_address.set_view_public_key(compressed_ristretto(9));
+    public_address.set_spend_public_key(compressed_ristretto(10));
+    public_address.set_fog_report_url("fog://fog.example.com".to_owned());
+    public_address.set_fog_authority_sig(vec![11; 64]);
+    public_address
+}
+
+fn block_contents() -> blockchain::BlockContents {
+    let key_images = [12, 13]
+        .into_iter()
+        .map(|byte| {
+            let mut key_image = KeyImage::new();
+            key_image.set_data(vec![byte; 32]);
+            key_image
+        })
+        .collect();
+
+    let mut block_contents = blockchain::BlockContents::new();
+    block_contents.set_key_images(key_images);
+    block_contents.set_outputs(vec![tx_out(1), tx_out(20)].into());
+    block_contents
+}
+
+/// Check that an object prints as its golden file, up to whitespace, and
+/// that the golden file parses back to the object.
+fn assert_matches_golden<M: CanonicalJson + PartialEq + std::fmt::Debug>(object: &M, golden: &str) {
+    let printed = object.to_canonical_json().unwrap();
+    assert_eq!(
+        serde_json::from_str::<Value>(&printed).unwrap(),
+        serde_json::from_str::<Value>(golden).unwrap(),
+        "printed: {printed}"
+    );
+    assert_eq!(&M::from_canonical_json(golden).unwrap(), object);
+}
+
+#[test]
+fn tx_out_matches_golden() {
+    assert_matches_golden(&tx_out(1), include_str!("data/json/tx_out.json"));
+}
+
+#[test]
+fn receipt_matches_golden() {
+    assert_matches_golden(&receipt(), include_str!("data/json/receipt.json"));
+}
+
+#[test]
+fn public_address_matches_golden() {
+    assert_matches_golden(
+        &public_address(),
+        include_str!("data/json/public_address.json"),
+    );
+}
+
+#[test]
+fn block_contents_matches_golden() {
+    assert_matches_golden(
+        &block_contents(),
+        include_str!("data/json/block_contents.json"),
+    );
+}
+
+#[test]
+fn unknown_fields_are_rejected() {
+    assert!(matches!(
+        Receipt::from_canonical_json(r#"{"tombstoneBlock": "100", "memo": "hi"}"#),
+        Err(JsonError::Parse(_))
+    ));
+}
@@ -0,0 +1,284 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Negotiation of the cipher suite used by client-to-node (NX) handshakes.
+//!
+//! The noise protocol name, and with it the AEAD and hash used by a session,
+//! is fixed when the handshake state is created. Negotiation therefore works
+//! as follows:
+//!
+//! 1. The client picks a suite it believes the responder supports, starts the
+//!    handshake with it, and lists it first in a [CipherSuiteOffer], followed
+//!    by any other suites it supports. The offer is the payload of the first
+//!    handshake message, which is otherwise empty, so it is bound to the
+//!    handshake hash.
+//! 2. The responder reads the offer with [offered_cipher_suites] before
+//!    creating its own handshake state, and continues with the first offered
+//!    suite if it supports it.
+//! 3. The responder appends the suites it supports to its attestation evidence
+//!    in the second handshake message, so the client can pick a better suite
+//!    for later sessions.
+//!
+//! Clients predating negotiation send an empty payload, which is treated as
+//! offering only [CipherSuite::Aes256GcmSha512]. Both the offer and the
+//! responder's list are encoded such that older peers ignore them.
+//!
+//! The same two messages also carry application capabilities, which are
+//! opaque to this crate: the client offers its own with the cipher suites,
+//! and the responder, having read them with [offered_capabilities], sends back
+//! the ones it agrees to with its supported suites. Because the offer is bound
+//! to the handshake hash and the responder's answer is encrypted and
+//! authenticated by the attested identity key, neither can be tampered with by
+//! the untrusted host. Peers which don't know about capabilities send none.
+
+use crate::Error;
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter, Result as FmtResult};
+use mc_crypto_keys::{Kex, ReprBytes};
+use prost::Message;
+
+/// A combination of AEAD and hash function which can be used by a session.
+///
+/// The key exchange is always X25519, and the KDF is HKDF over the hash.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[repr(u32)]
+pub enum CipherSuite {
+    /// AES-256-GCM with SHA-512. This is the suite used before negotiation
+    /// was introduced, and the one every responder supports.
+    Aes256GcmSha512 = 0,
+    /// ChaCha20-Poly1305 with SHA-512, for hardware without AES acceleration.
+    ChaCha20Poly1305Sha512 = 1,
+}
+
+impl CipherSuite {
+    /// All suites known to this version of the protocol, most preferred first.
+    pub const ALL: [CipherSuite; 2] = [
+        CipherSuite::Aes256GcmSha512,
+        CipherSuite::ChaCha20Poly1305Sha512,
+    ];
+
+    /// The suite used by a handshake with the given noise protocol name, e.g.
+    /// `Noise_NX_25519_AESGCM_SHA512`.
+    pub fn from_protocol_name(protocol_name: &str) -> Option<Self> {
+        // The protocol name is Noise_<pattern>_<dh>_<cipher>_<hash>.
+        let mut parts = protocol_name.rsplitn(3, '_');
+        let hash = parts.next()?;
+        let cipher = parts.next()?;
+        match (cipher, hash) {
+            ("AESGCM", "SHA512") => Some(CipherSuite::Aes256GcmSha512),
+            ("ChaChaPoly", "SHA512") => Some(CipherSuite::ChaCha20Poly1305Sha512),
+            _ => None,
+        }
+    }
+
+    fn from_u32(value: u32) -> Option<Self> {
+        Self::ALL.into_iter().find(|suite| *suite as u32 == value)
+    }
+}
+
+impl Display for CipherSuite {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            CipherSuite::Aes256GcmSha512 => write!(f, "AESGCM_SHA512"),
+            CipherSuite::ChaCha20Poly1305Sha512 => write!(f, "ChaChaPoly_SHA512"),
+        }
+    }
+}
+
+/// The cipher suites offered by a client, carried as the payload of the first
+/// message of an NX handshake. The first suite is the one the handshake
+/// message was written with.
+#[derive(Clone, Eq, Message, PartialEq)]
+pub struct CipherSuiteOffer {
+    /// The offered suites, as `CipherSuite` discriminants
+    #[prost(uint32, repeated, tag = 1)]
+    pub cipher_suites: Vec<u32>,
+    /// The application capabilities the client offers, if any
+    #[prost(bytes, tag = 2)]
+    pub capabilities: Vec<u8>,
+}
+
+/// The cipher suites supported by a responder, appended to the attestation
+/// evidence in the second message of an NX handshake.
+///
+/// The tag must not collide with any field of the structures the evidence is
+/// decoded as (see `EvidenceKind::from_bytes`), so that older clients skip it.
+#[derive(Clone, Eq, Message, PartialEq)]
+pub(crate) struct SupportedCipherSuites {
+    #[prost(uint32, repeated, tag = 10)]
+    pub cipher_suites: Vec<u32>,
+    /// The application capabilities the responder agreed to, if any
+    #[prost(bytes, tag = 11)]
+    pub capabilities: Vec<u8>,
+}
+
+/// Convert wire values into suites, skipping the ones we don't know about.
+pub(crate) fn decode_cipher_suites(values: &[u32]) -> Vec<CipherSuite> {
+    values
+        .iter()
+        .filter_map(|value| CipherSuite::from_u32(*value))
+        .collect()
+}
+
+/// Convert suites into their wire values.
+pub(crate) fn encode_cipher_suites(suites: &[CipherSuite]) -> Vec<u32> {
+    suites.iter().map(|suite| *suite as u32).collect()
+}
+
+/// Parse the payload of the first NX handshake message into the offered
+/// suites and capabilities. An empty payload comes from a client predating
+/// negotiation.
+pub(crate) fn parse_offer(payload: &[u8]) -> Result<(Vec<CipherSuite>, Vec<u8>), Error> {
+    if payload.is_empty() {
+        return Ok(([CipherSuite::Aes256GcmSha512].into(), Vec::new()));
+    }
+    let offer =
+        CipherSuiteOffer::decode(payload).map_err(|_| Error::CipherSuiteOfferDeserialization)?;
+    Ok((
+        decode_cipher_suites(&offer.cipher_suites),
+        offer.capabilities,
+    ))
+}
+
+/// The unencrypted payload of the first message of an NX handshake.
+fn offer_payload<KexAlgo: Kex>(auth_request: &[u8]) -> Result<&[u8], Error> {
+    // The message is the client's ephemeral public key, followed by the
+    // unencrypted payload.
+    auth_request
+        .get(KexAlgo::Public::size()..)
+        .ok_or(Error::CipherSuiteOfferDeserialization)
+}
+
+/// The suites offered by a client in the first message of an NX handshake,
+/// in the client's order of preference.
+///
+/// This lets a responder choose which suite to create its handshake state
+/// with before reading the message. The first suite is the one the client
+/// used, so it is the only one the handshake can continue with.
+pub fn offered_cipher_suites<KexAlgo: Kex>(auth_request: &[u8]) -> Result<Vec<CipherSuite>, Error> {
+    parse_offer(offer_payload::<KexAlgo>(auth_request)?).map(|(suites, _)| suites)
+}
+
+/// The application capabilities offered by a client in the first message of
+/// an NX handshake, which are empty if it offered none.
+///
+/// This lets a responder decide which capabilities to agree to, with
+/// [ClientAuthRequestInput::with_capabilities], before reading the message.
+///
+/// [ClientAuthRequestInput::with_capabilities]:
+/// crate::ClientAuthRequestInput::with_capabilities
+pub fn offered_capabilities<KexAlgo: Kex>(auth_request: &[u8]) -> Result<Vec<u8>, Error> {
+    parse_offer(offer_payload::<KexAlgo>(auth_request)?).map(|(_, capabilities)| capabilities)
+}
+
+/// Choose the suite to use with a responder: the first of our own suites, in
+/// our order of preference, which the responder supports.
+pub fn select_cipher_suite(
+    preferred: &[CipherSuite],
+    supported: &[CipherSuite],
+) -> Option<CipherSuite> {
+    preferred
+        .iter()
+        .find(|suite| supported.contains(suite))
+        .copied()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mc_attest_verifier_types::{prost as verifier_prost, EvidenceKind};
+    use mc_crypto_keys::{X25519Public, X25519};
+
+    #[test]
+    fn from_protocol_name() {
+        assert_eq!(
+            CipherSuite::from_protocol_name("Noise_NX_25519_AESGCM_SHA512"),
+            Some(CipherSuite::Aes256GcmSha512)
+        );
+        assert_eq!(
+            CipherSuite::from_protocol_name("Noise_IX_25519_ChaChaPoly_SHA512"),
+            Some(CipherSuite::ChaCha20Poly1305Sha512)
+        );
+        assert_eq!(
+            CipherSuite::from_protocol_name("Noise_XX_25519_AESGCM_SHA256"),
+            None
+        );
+        assert_eq!(CipherSuite::from_protocol_name("bogus"), None);
+    }
+
+    #[test]
+    fn select_prefers_our_order() {
+        let preferred = [
+            CipherSuite::ChaCha20Poly1305Sha512,
+            CipherSuite::Aes256GcmSha512,
+        ];
+        assert_eq!(
+            select_cipher_suite(&preferred, &CipherSuite::ALL),
+            Some(CipherSuite::ChaCha20Poly1305Sha512)
+        );
+        assert_eq!(
+            select_cipher_suite(&preferred, &[CipherSuite::Aes256GcmSha512]),
+            Some(CipherSuite::Aes256GcmSha512)
+        );
+        assert_eq!(select_cipher_suite(&preferred, &[]), None);
+    }
+
+    #[test]
+    fn offered_cipher_suites_from_auth_request() {
+        let ephemeral_key = [7u8; 32];
+        assert_eq!(ephemeral_key.len(), X25519Public::size());
+
+        // A legacy client sends nothing after its ephemeral key.
+        assert_eq!(
+            offered_cipher_suites::<X25519>(&ephemeral_key).unwrap(),
+            [CipherSuite::Aes256GcmSha512]
+        );
+
+        // Unknown suites are skipped.
+        let offer = CipherSuiteOffer {
+            cipher_suites: [1, 42, 0].into(),
+            capabilities: b"capabilities".to_vec(),
+        };
+        let mut auth_request = ephemeral_key.to_vec();
+        auth_request.extend(offer.encode_to_vec());
+        assert_eq!(
+            offered_cipher_suites::<X25519>(&auth_request).unwrap(),
+            [
+                CipherSuite::ChaCha20Poly1305Sha512,
+                CipherSuite::Aes256GcmSha512
+            ]
+        );
+        assert_eq!(
+            offered_capabilities::<X25519>(&auth_request).unwrap(),
+            b"capabilities"
+        );
+        assert!(offered_capabilities::<X25519>(&ephemeral_key)
+            .unwrap()
+            .is_empty());
+
+        assert_eq!(
+            offered_cipher_suites::<X25519>(&ephemeral_key[..16]),
+            Err(Error::CipherSuiteOfferDeserialization)
+        );
+    }
+
+    #[test]
+    fn supported_suites_are_ignored_by_evidence_decoding() {
+        let evidence = EvidenceKind::Dcap(verifier_prost::DcapEvidence::default());
+        let mut payload = evidence.into_bytes();
+        payload.extend(
+            SupportedCipherSuites {
+                cipher_suites: encode_cipher_suites(&CipherSuite::ALL),
+                capabilities: b"capabilities".to_vec(),
+            }
+            .encode_to_vec(),
+        );
+
+        assert_eq!(EvidenceKind::from_bytes(&payload).unwrap(), evidence);
+        let supported = SupportedCipherSuites::decode(payload.as_slice()).unwrap();
+        assert_eq!(
+            decode_cipher_suites(&supported.cipher_suites),
+            CipherSuite::ALL
+        );
+        assert_eq!(supported.capabilities, b"capabilities");
+    }
+}
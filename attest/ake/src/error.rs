@@ -3,7 +3,7 @@
 //! AKE Errors
 
 use displaydoc::Display;
-use mc_attest_verifier::Error as VerifierError;
+use mc_attest_verifier::{AttestationDiagnostic, Error as VerifierError};
 use mc_crypto_noise::{CipherError, HandshakeError};
 use serde::{Deserialize, Serialize};
 
@@ -34,10 +34,26 @@ pub enum Error {
     EncryptError(CipherError),
     /// The message could not be decrypted: {0}
     DecryptError(CipherError),
+    /// The initiator's cipher suite offer could not be deserialized
+    CipherSuiteOfferDeserialization,
+    /// The handshake does not use a known cipher suite
+    UnknownCipherSuite,
+    /// The initiator used a cipher suite other than the one we support
+    CipherSuiteMismatch,
     /// Unknown error while initiating a new AKE
     Unknown,
 }
 
+impl Error {
+    /// Why the peer's attestation evidence was rejected, if it was.
+    pub fn attestation_diagnostic(&self) -> Option<&AttestationDiagnostic> {
+        match self {
+            Error::AttestationEvidenceVerification(err) => err.diagnostic(),
+            _ => None,
+        }
+    }
+}
+
 impl From<VerifierError> for Error {
     fn from(src: VerifierError) -> Error {
         Error::AttestationEvidenceVerification(src)
@@ -2,7 +2,10 @@
 
 //!  data structures not defined elsewhere.
 
-use crate::mealy::{Input as MealyInput, Output as MealyOutput};
+use crate::{
+    cipher_suite::CipherSuite,
+    mealy::{Input as MealyInput, Output as MealyOutput},
+};
 use alloc::vec::Vec;
 use core::marker::PhantomData;
 use der::DateTime;
@@ -65,11 +68,45 @@ where
     Cipher: NoiseCipher,
     DigestAlgo: NoiseDigest,
 {
+    /// Other cipher suites to offer the responder, if any.
+    pub(crate) cipher_suites: Vec<CipherSuite>,
+
+    /// Application capabilities to offer the responder, if any.
+    pub(crate) capabilities: Vec<u8>,
+
     _kex: PhantomData<KexAlgo>,
     _cipher: PhantomData<Cipher>,
     _digest: PhantomData<DigestAlgo>,
 }
 
+impl<KexAlgo, Cipher, DigestAlgo> ClientInitiate<KexAlgo, Cipher, DigestAlgo>
+where
+    KexAlgo: Kex,
+    Cipher: NoiseCipher,
+    DigestAlgo: NoiseDigest,
+{
+    /// Create a new input event which tells the responder which cipher
+    /// suites we support, besides the one this handshake uses.
+    ///
+    /// The default input offers nothing, like clients predating cipher suite
+    /// negotiation.
+    pub fn with_cipher_suites(cipher_suites: Vec<CipherSuite>) -> Self {
+        Self {
+            cipher_suites,
+            ..Default::default()
+        }
+    }
+
+    /// Offer the responder the given application capabilities, which it may
+    /// agree to in its response. See [Ready::remote_capabilities].
+    ///
+    /// [Ready::remote_capabilities]: crate::Ready::remote_capabilities
+    pub fn with_capabilities(mut self, capabilities: Vec<u8>) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+}
+
 impl<KexAlgo, Cipher, DigestAlgo> Default for ClientInitiate<KexAlgo, Cipher, DigestAlgo>
 where
     KexAlgo: Kex,
@@ -78,6 +115,8 @@ where
 {
     fn default() -> Self {
         Self {
+            cipher_suites: Vec::new(),
+            capabilities: Vec::new(),
             _kex: PhantomData,
             _cipher: PhantomData,
             _digest: PhantomData,
@@ -180,6 +219,13 @@ where
 
     /// The auth request input, including payload, if any
     pub(crate) data: AuthRequestOutput<HandshakeNX, KexAlgo, Cipher, DigestAlgo>,
+
+    /// The cipher suites to advertise to the initiator. When empty, only the
+    /// one used by this handshake is advertised.
+    pub(crate) supported_cipher_suites: Vec<CipherSuite>,
+
+    /// The application capabilities to agree to, if any.
+    pub(crate) capabilities: Vec<u8>,
 }
 
 impl<KexAlgo, Cipher, DigestAlgo> MealyInput for ClientAuthRequestInput<KexAlgo, Cipher, DigestAlgo>
@@ -205,8 +251,26 @@ where
             local_identity,
             dcap_evidence,
             data,
+            supported_cipher_suites: Vec::new(),
+            capabilities: Vec::new(),
         }
     }
+
+    /// Advertise the given cipher suites to the initiator, telling it which
+    /// suites it may use for later sessions.
+    pub fn with_supported_cipher_suites(mut self, cipher_suites: Vec<CipherSuite>) -> Self {
+        self.supported_cipher_suites = cipher_suites;
+        self
+    }
+
+    /// Send the initiator the application capabilities we agree to, usually
+    /// chosen from those it offered, as read with [offered_capabilities].
+    ///
+    /// [offered_capabilities]: crate::offered_capabilities
+    pub fn with_capabilities(mut self, capabilities: Vec<u8>) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
 }
 
 /// An input used to transform a Start into a Ready for a node-to-node
@@ -3,14 +3,18 @@
 //! Initiator-specific transition functions
 
 use crate::{
-    AuthPending, AuthRequestOutput, AuthResponseInput, ClientInitiate, Error, NodeInitiate, Ready,
-    Start, Terminated, Transition, UnverifiedAttestationEvidence,
+    cipher_suite::{decode_cipher_suites, encode_cipher_suites, SupportedCipherSuites},
+    AuthPending, AuthRequestOutput, AuthResponseInput, CipherSuite, CipherSuiteOffer,
+    ClientInitiate, Error, NodeInitiate, Ready, Start, Terminated, Transition,
+    UnverifiedAttestationEvidence,
 };
 use ::prost::Message;
-use alloc::{string::ToString, vec::Vec};
+use alloc::{boxed::Box, string::ToString, vec, vec::Vec};
 use der::DateTime;
 use mc_attest_core::{EvidenceKind, ReportDataMask, VerificationReport};
-use mc_attest_verifier::{DcapVerifier, Error as VerifierError, Verifier, DEBUG_ENCLAVE};
+use mc_attest_verifier::{
+    AttestationDiagnostic, DcapVerifier, Error as VerifierError, Verifier, DEBUG_ENCLAVE,
+};
 use mc_attest_verifier_types::{prost, DcapEvidence};
 use mc_attestation_verifier::{Evidence, TrustedIdentity, VerificationTreeDisplay};
 use mc_crypto_keys::{Kex, KexPublic, ReprBytes};
@@ -63,7 +67,7 @@ where
     fn try_next<R: CryptoRng + RngCore>(
         self,
         csprng: &mut R,
-        _input: ClientInitiate<KexAlgo, Cipher, DigestAlgo>,
+        input: ClientInitiate<KexAlgo, Cipher, DigestAlgo>,
     ) -> Result<
         (
             AuthPending<KexAlgo, Cipher, DigestAlgo>,
@@ -71,9 +75,33 @@ where
         ),
         Self::Error,
     > {
+        let protocol_name = ProtocolName::<HandshakeNX, KexAlgo, Cipher, DigestAlgo>::default();
+
+        // Legacy clients send an empty payload, which responders take to mean
+        // the default cipher suite.
+        let payload = if input.cipher_suites.is_empty() && input.capabilities.is_empty() {
+            Vec::new()
+        } else {
+            let protocol_name_str: &str = protocol_name.as_ref();
+            let cipher_suite = CipherSuite::from_protocol_name(protocol_name_str)
+                .ok_or(Error::UnknownCipherSuite)?;
+            let mut cipher_suites = vec![cipher_suite];
+            cipher_suites.extend(
+                input
+                    .cipher_suites
+                    .into_iter()
+                    .filter(|suite| *suite != cipher_suite),
+            );
+            CipherSuiteOffer {
+                cipher_suites: encode_cipher_suites(&cipher_suites),
+                capabilities: input.capabilities,
+            }
+            .encode_to_vec()
+        };
+
         let handshake_state = HandshakeState::new(
             true,
-            ProtocolName::<HandshakeNX, KexAlgo, Cipher, DigestAlgo>::default(),
+            protocol_name,
             self.responder_id.as_ref(),
             None,
             None,
@@ -84,7 +112,7 @@ where
 
         parse_handshake_output(
             handshake_state
-                .write_message(csprng, &[])
+                .write_message(csprng, &payload)
                 .map_err(Error::HandshakeWrite)?,
         )
     }
@@ -173,11 +201,17 @@ where
                     input.time,
                     remote_identity,
                 )?;
+                // Responders predating cipher suite negotiation don't send
+                // this, and we don't hold that against them.
+                let supported =
+                    SupportedCipherSuites::decode(output.payload.as_slice()).unwrap_or_default();
                 Ok((
                     Ready {
                         writer: result.initiator_cipher,
                         reader: result.responder_cipher,
                         binding: result.channel_binding,
+                        remote_cipher_suites: decode_cipher_suites(&supported.cipher_suites),
+                        remote_capabilities: supported.capabilities,
                     },
                     remote_evidence,
                 ))
@@ -253,6 +287,7 @@ fn verify_dcap_evidence<PubKey: KexPublic>(
     }
 
     let verifier = DcapVerifier::new(identities, time, report_data);
+    let report_body = quote.app_report_body().clone();
     let evidence =
         Evidence::new(quote, collateral).map_err(|_| Error::AttestationEvidenceDeserialization)?;
     let verification_output = verifier.verify(&evidence);
@@ -260,8 +295,10 @@ fn verify_dcap_evidence<PubKey: KexPublic>(
         Ok(())
     } else {
         let display_tree = VerificationTreeDisplay::new(&verifier, verification_output);
+        let diagnostic =
+            AttestationDiagnostic::new(&report_body, identities, display_tree.to_string());
         Err(Error::AttestationEvidenceVerification(
-            VerifierError::Verification(display_tree.to_string()),
+            VerifierError::Rejected(Box::new(diagnostic)),
         ))
     }
 }
@@ -11,6 +11,7 @@
 #![no_std]
 extern crate alloc;
 
+mod cipher_suite;
 mod error;
 mod event;
 mod initiator;
@@ -20,6 +21,10 @@ mod shared;
 mod state;
 
 pub use crate::{
+    cipher_suite::{
+        offered_capabilities, offered_cipher_suites, select_cipher_suite, CipherSuite,
+        CipherSuiteOffer,
+    },
     error::Error,
     event::{
         AuthRequestOutput, AuthResponseInput, AuthResponseOutput, Ciphertext,
@@ -30,6 +35,9 @@ pub use crate::{
     state::{AuthPending, Ready, Start, Terminated},
 };
 
+#[cfg(feature = "chacha20poly1305")]
+pub use mc_crypto_noise::ChaChaPoly;
+
 #[cfg(test)]
 #[cfg(feature = "sgx-sim")]
 mod test {
@@ -146,4 +154,82 @@ mod test {
 
         assert_eq!(plaintext2.as_slice(), response.as_bytes());
     }
+
+    #[test]
+    fn nx_handshake_negotiates_cipher_suites() {
+        let mut csprng = Hc128Rng::seed_from_u64(0);
+        let identity = X25519Private::from_random(&mut csprng);
+        let pubkey = X25519Public::from(&identity);
+
+        let report_data = EnclaveReportDataContents::new([0x2au8; 16].into(), pubkey, [0x36u8; 32]);
+        let mut report = Report::default();
+        report.as_mut().body.report_data.d[..32].copy_from_slice(&report_data.sha256());
+
+        let quote = DcapQuotingEnclave::quote_report(&report).expect("Failed to create quote");
+        let collateral = DcapQuotingEnclave::collateral(&quote).expect("Failed to get collateral");
+        let attestation_evidence = DcapEvidence {
+            quote,
+            collateral,
+            report_data,
+        };
+
+        let report_body = attestation_evidence.quote.app_report_body();
+        let mr_signer = TrustedIdentity::from(TrustedMrSignerIdentity::new(
+            report_body.mr_signer(),
+            report_body.isv_product_id(),
+            report_body.isv_svn(),
+            [] as [&str; 0],
+            [] as [&str; 0],
+        ));
+
+        let initiator = Start::new(RESPONDER_ID_STR.into());
+        let responder = Start::new(RESPONDER_ID_STR.into());
+
+        let client_init = ClientInitiate::<X25519, Aes256Gcm, Sha512>::with_cipher_suites(
+            [CipherSuite::ChaCha20Poly1305Sha512].into(),
+        )
+        .with_capabilities(b"offered".to_vec());
+        let (initiator, auth_request_output) = initiator
+            .try_next(&mut csprng, client_init)
+            .expect("Initiator could not be initiated");
+
+        // The suite in use is offered first.
+        assert_eq!(
+            offered_cipher_suites::<X25519>(auth_request_output.as_ref()).unwrap(),
+            [
+                CipherSuite::Aes256GcmSha512,
+                CipherSuite::ChaCha20Poly1305Sha512
+            ]
+        );
+        assert_eq!(
+            offered_capabilities::<X25519>(auth_request_output.as_ref()).unwrap(),
+            b"offered"
+        );
+
+        let auth_request_input =
+            ClientAuthRequestInput::new(auth_request_output, identity, attestation_evidence)
+                .with_supported_cipher_suites(CipherSuite::ALL.into())
+                .with_capabilities(b"agreed".to_vec());
+        let (responder, auth_response_output) = responder
+            .try_next(&mut csprng, auth_request_input)
+            .expect("Responder could not process auth request");
+        let responder: Ready<Aes256Gcm> = responder;
+
+        let auth_response_input = AuthResponseInput::new(auth_response_output, [mr_signer], None);
+        let (initiator, _): (Ready<Aes256Gcm>, _) = initiator
+            .try_next(&mut csprng, auth_response_input)
+            .expect("Initiator could not process auth response");
+
+        assert_eq!(initiator.remote_cipher_suites(), CipherSuite::ALL);
+        assert_eq!(
+            responder.remote_cipher_suites(),
+            [
+                CipherSuite::Aes256GcmSha512,
+                CipherSuite::ChaCha20Poly1305Sha512
+            ]
+        );
+        assert_eq!(initiator.remote_capabilities(), b"agreed");
+        assert_eq!(responder.remote_capabilities(), b"offered");
+        assert_eq!(initiator.binding(), responder.binding());
+    }
 }
@@ -2,14 +2,15 @@
 
 //! Responder-specific transition functions
 use crate::{
+    cipher_suite::{encode_cipher_suites, parse_offer, CipherSuite, SupportedCipherSuites},
     error::Error,
     event::{AuthResponseOutput, ClientAuthRequestInput, NodeAuthRequestInput},
     mealy::Transition,
     state::{Ready, Start},
 };
 use ::prost::Message;
-use alloc::{string::ToString, vec::Vec};
-use mc_attest_verifier::DcapVerifier;
+use alloc::{boxed::Box, string::ToString, vec, vec::Vec};
+use mc_attest_verifier::{AttestationDiagnostic, DcapVerifier};
 use mc_attest_verifier_types::{prost, DcapEvidence, EvidenceKind};
 use mc_attestation_verifier::{Evidence, VerificationTreeDisplay};
 use mc_crypto_keys::Kex;
@@ -19,6 +20,19 @@ use mc_crypto_noise::{
 };
 use rand_core::{CryptoRng, RngCore};
 
+/// The cipher suite used by handshakes with the given parameters.
+fn cipher_suite_of<Handshake, KexAlgo, Cipher, DigestAlgo>() -> Option<CipherSuite>
+where
+    Handshake: HandshakePattern,
+    KexAlgo: Kex,
+    Cipher: NoiseCipher,
+    DigestAlgo: NoiseDigest,
+    ProtocolName<Handshake, KexAlgo, Cipher, DigestAlgo>: AsRef<str>,
+{
+    let protocol_name = ProtocolName::<Handshake, KexAlgo, Cipher, DigestAlgo>::default();
+    CipherSuite::from_protocol_name(protocol_name.as_ref())
+}
+
 /// A trait containing default implementations, used to tack repeatable chunks
 /// of code onto the "Start" state for use below.
 trait ResponderTransitionMixin {
@@ -38,6 +52,10 @@ trait ResponderTransitionMixin {
         csprng: &mut (impl CryptoRng + RngCore),
         handshake_state: HandshakeState<KexAlgo, Cipher, DigestAlgo>,
         dcap_evidence: DcapEvidence,
+        supported_cipher_suites: &[CipherSuite],
+        remote_cipher_suites: Vec<CipherSuite>,
+        capabilities: Vec<u8>,
+        remote_capabilities: Vec<u8>,
     ) -> Result<(Ready<Cipher>, AuthResponseOutput), Error>
     where
         KexAlgo: Kex,
@@ -84,6 +102,10 @@ impl ResponderTransitionMixin for Start {
         csprng: &mut (impl CryptoRng + RngCore),
         handshake_state: HandshakeState<KexAlgo, Cipher, DigestAlgo>,
         dcap_evidence: DcapEvidence,
+        supported_cipher_suites: &[CipherSuite],
+        remote_cipher_suites: Vec<CipherSuite>,
+        capabilities: Vec<u8>,
+        remote_capabilities: Vec<u8>,
     ) -> Result<(Ready<Cipher>, AuthResponseOutput), Error>
     where
         KexAlgo: Kex,
@@ -96,7 +118,15 @@ impl ResponderTransitionMixin for Start {
         // We need to send back EvidenceKind for backward compatibility with
         // the legacy `EvidenceKind::Epid` version
         let evidence = EvidenceKind::Dcap(prost_evidence);
-        let serialized_evidence = evidence.into_bytes();
+        let mut serialized_evidence = evidence.into_bytes();
+
+        // Older initiators skip over this when decoding the evidence.
+        SupportedCipherSuites {
+            cipher_suites: encode_cipher_suites(supported_cipher_suites),
+            capabilities,
+        }
+        .encode(&mut serialized_evidence)
+        .map_err(|_| Error::AttestationEvidenceSerialization)?;
 
         let output = handshake_state
             .write_message(csprng, &serialized_evidence)
@@ -109,6 +139,8 @@ impl ResponderTransitionMixin for Start {
                     writer: result.responder_cipher,
                     reader: result.initiator_cipher,
                     binding: result.channel_binding,
+                    remote_cipher_suites,
+                    remote_capabilities,
                 },
                 AuthResponseOutput::from(output.payload),
             )),
@@ -158,16 +190,32 @@ where
             report_data,
         } = dcap_evidence;
 
-        let verifier = DcapVerifier::new(identities, None, report_data);
+        let verifier = DcapVerifier::new(&identities, None, report_data);
+        let report_body = quote.app_report_body().clone();
         let evidence = Evidence::new(quote, collateral).map_err(mc_attest_verifier::Error::from)?;
 
         let verification = verifier.verify(&evidence);
         if verification.is_failure().into() {
             let display_tree = VerificationTreeDisplay::new(&verifier, verification);
-            return Err(mc_attest_verifier::Error::Verification(display_tree.to_string()).into());
+            let diagnostic =
+                AttestationDiagnostic::new(&report_body, &identities, display_tree.to_string());
+            return Err(mc_attest_verifier::Error::Rejected(Box::new(diagnostic)).into());
         }
 
-        Self::handle_response(csprng, handshake_state, input.dcap_evidence)
+        // Node-to-node handshakes don't negotiate, so we only tell the peer
+        // about the suite in use.
+        let cipher_suites = cipher_suite_of::<HandshakeIX, KexAlgo, Cipher, DigestAlgo>()
+            .map(|suite| vec![suite])
+            .unwrap_or_default();
+        Self::handle_response(
+            csprng,
+            handshake_state,
+            input.dcap_evidence,
+            &cipher_suites,
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+        )
     }
 }
 
@@ -191,11 +239,34 @@ where
         csprng: &mut R,
         input: ClientAuthRequestInput<KexAlgo, Cipher, DigestAlgo>,
     ) -> Result<(Ready<Cipher>, AuthResponseOutput), Error> {
-        let (handshake_state, _payload) = self
+        let (handshake_state, payload) = self
             .handle_request::<HandshakeNX, KexAlgo, Cipher, DigestAlgo>(
                 &input.data.data,
                 input.local_identity,
             )?;
-        Self::handle_response(csprng, handshake_state, input.dcap_evidence)
+
+        // The initiator's handshake uses the first suite it offers, which has
+        // to be ours for the rest of the handshake to succeed.
+        let cipher_suite = cipher_suite_of::<HandshakeNX, KexAlgo, Cipher, DigestAlgo>()
+            .ok_or(Error::UnknownCipherSuite)?;
+        let (offered_cipher_suites, offered_capabilities) = parse_offer(&payload)?;
+        if offered_cipher_suites.first() != Some(&cipher_suite) {
+            return Err(Error::CipherSuiteMismatch);
+        }
+
+        let supported_cipher_suites = if input.supported_cipher_suites.is_empty() {
+            vec![cipher_suite]
+        } else {
+            input.supported_cipher_suites
+        };
+        Self::handle_response(
+            csprng,
+            handshake_state,
+            input.dcap_evidence,
+            &supported_cipher_suites,
+            offered_cipher_suites,
+            input.capabilities,
+            offered_capabilities,
+        )
     }
 }
@@ -2,7 +2,7 @@
 
 //! Transducer states used by initiators and/or responders.
 
-use crate::mealy::State;
+use crate::{cipher_suite::CipherSuite, mealy::State};
 use alloc::{string::String, vec::Vec};
 use mc_crypto_keys::Kex;
 use mc_crypto_noise::{CipherError, CipherState, HandshakeState, NoiseCipher, NoiseDigest};
@@ -64,6 +64,8 @@ where
     pub(crate) writer: CipherState<Cipher>,
     pub(crate) reader: CipherState<Cipher>,
     pub(crate) binding: Vec<u8>,
+    pub(crate) remote_cipher_suites: Vec<CipherSuite>,
+    pub(crate) remote_capabilities: Vec<u8>,
 }
 
 impl<Cipher> Ready<Cipher>
@@ -75,6 +77,20 @@ where
         self.binding.as_ref()
     }
 
+    /// The cipher suites the other side of the connection supports, as far as
+    /// it told us. Responders predating cipher suite negotiation tell us
+    /// nothing, and clients predating it only use the default suite.
+    pub fn remote_cipher_suites(&self) -> &[CipherSuite] {
+        &self.remote_cipher_suites
+    }
+
+    /// The application capabilities the other side of the connection offered,
+    /// if it is an initiator, or agreed to, if it is a responder. These are
+    /// empty if it sent none.
+    pub fn remote_capabilities(&self) -> &[u8] {
+        &self.remote_capabilities
+    }
+
     /// Using the writer cipher, encrypt the given plaintext.
     pub fn encrypt(&mut self, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, CipherError> {
         self.writer.encrypt_with_ad(aad, plaintext)
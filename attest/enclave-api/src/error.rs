@@ -77,6 +77,9 @@ pub enum Error {
 
     /// Connection not found by node ID or session
     NotFound,
+
+    /// The measurement of the remote enclave has been blocked
+    BlockedMeasurement,
 }
 
 impl From<AkeError> for Error {
@@ -14,6 +14,7 @@ use alloc::{
 };
 use displaydoc::Display;
 use mc_attestation_verifier::TrustedIdentity;
+use mc_crypto_keys::{Ed25519Public, Ed25519Signature, Verifier};
 use serde::{Deserialize, Serialize};
 
 /// Defines a json schema for a "trusted-identities.json" file.
@@ -65,21 +66,109 @@ impl TrustedIdentitySet {
     }
 }
 
+/// The context string that a signature over a [SignedTrustedIdentitySet] also
+/// covers, so that nothing else signed by the same key can pass for one.
+pub const SIGNED_TRUSTED_IDENTITY_SET_CONTEXT: &[u8] = b"mc-signed-trusted-identity-set";
+
+/// Defines a json schema for a trusted identity set which is published along
+/// with an Ed25519 signature, e.g. a "signed-trusted-identities.json" file.
+/// See README.md for example.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SignedTrustedIdentitySet {
+    /// Incremented by the signer for each set it publishes. Clients refuse sets
+    /// with a lower sequence number than the last one they accepted, so an old
+    /// set can't be replayed to roll back their identities.
+    pub sequence: u64,
+
+    /// Seconds since the Unix epoch after which the set is no longer accepted.
+    pub expires_at: u64,
+
+    /// The trusted identities.
+    pub identities: TrustedIdentitySet,
+}
+
+impl SignedTrustedIdentitySet {
+    /// Parse a signed trusted identity set from json bytes, after checking that
+    /// `signature` is an Ed25519 signature by `signer` of the
+    /// [signing message](Self::signing_message) for exactly those bytes.
+    ///
+    /// This lets clients load new sets at runtime from an untrusted source,
+    /// e.g. a file downloaded along with its signature, while only trusting
+    /// whoever holds the signing key.
+    ///
+    /// Arguments:
+    /// * `last_accepted_sequence` - The sequence number of the last set the
+    ///   client accepted, if any. Sets with a lower sequence number are
+    ///   rejected.
+    /// * `now` - The current time, in seconds since the Unix epoch. Sets which
+    ///   expired before then are rejected.
+    pub fn from_signed_json(
+        json: &[u8],
+        signature: &Ed25519Signature,
+        signer: &Ed25519Public,
+        last_accepted_sequence: Option<u64>,
+        now: u64,
+    ) -> Result<Self, Error> {
+        signer
+            .verify(&Self::signing_message(json), signature)
+            .map_err(|_| Error::InvalidSignature)?;
+        let set: Self = serde_json::from_slice(json)?;
+
+        if let Some(last_accepted_sequence) = last_accepted_sequence {
+            if set.sequence < last_accepted_sequence {
+                return Err(Error::Rollback(set.sequence, last_accepted_sequence));
+            }
+        }
+        if set.expires_at <= now {
+            return Err(Error::Expired(set.expires_at));
+        }
+        Ok(set)
+    }
+
+    /// The message that the signer signs for a set serialized as `json`: the
+    /// [context string](SIGNED_TRUSTED_IDENTITY_SET_CONTEXT) followed by the
+    /// json.
+    pub fn signing_message(json: &[u8]) -> Vec<u8> {
+        [SIGNED_TRUSTED_IDENTITY_SET_CONTEXT, json].concat()
+    }
+}
+
 /// An error which can occur when trying to build an attestation verifier from a
 /// TrustedIdentitySet
 #[derive(Display, Debug)]
 pub enum Error {
     /// No identities found for enclave name "{0}"
     NoIdentitiesFound(String),
+
+    /// The signature of the trusted identity set is not valid
+    InvalidSignature,
+
+    /// Trusted identity set has sequence number {0}, but one with {1} was
+    /// already accepted
+    Rollback(u64, u64),
+
+    /// Trusted identity set expired at {0}
+    Expired(u64),
+
+    /// Invalid trusted identity set json: {0}
+    Json(serde_json::Error),
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(src: serde_json::Error) -> Self {
+        Self::Json(src)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use alloc::format;
     use assert_matches::assert_matches;
 
     use hex::FromHex;
     use mc_attestation_verifier::{TrustedMrEnclaveIdentity, TrustedMrSignerIdentity};
+    use mc_crypto_keys::{Ed25519Pair, Ed25519Private, Signer};
     use mc_sgx_core_types::{MrEnclave, MrSigner};
 
     const TEST_DATA: &str = r#"{
@@ -293,6 +382,114 @@ mod tests {
         ));
     }
 
+    fn signed_test_data(sequence: u64, expires_at: u64) -> String {
+        format!(
+            r#"{{"sequence": {sequence}, "expires_at": {expires_at}, "identities": {TEST_DATA}}}"#
+        )
+    }
+
+    #[test]
+    fn test_signed_json() {
+        let signer = Ed25519Pair::from(Ed25519Private::try_from(&[7u8; 32][..]).unwrap());
+        let json = signed_test_data(5, 2000);
+        let signature = signer.sign(&SignedTrustedIdentitySet::signing_message(json.as_bytes()));
+
+        let set = SignedTrustedIdentitySet::from_signed_json(
+            json.as_bytes(),
+            &signature,
+            &signer.public_key(),
+            Some(4),
+            1000,
+        )
+        .unwrap();
+        assert_eq!(set.sequence, 5);
+        assert_eq!(set.expires_at, 2000);
+        assert_eq!(set.identities.identities("fog-view").unwrap().len(), 2);
+
+        // The same set may be loaded again.
+        assert!(SignedTrustedIdentitySet::from_signed_json(
+            json.as_bytes(),
+            &signature,
+            &signer.public_key(),
+            Some(5),
+            1000,
+        )
+        .is_ok());
+
+        // The json must be exactly what was signed.
+        let tampered = json.replace("8c80a2b9", "0c80a2b9");
+        assert_matches!(
+            SignedTrustedIdentitySet::from_signed_json(
+                tampered.as_bytes(),
+                &signature,
+                &signer.public_key(),
+                None,
+                1000,
+            ),
+            Err(Error::InvalidSignature)
+        );
+
+        let other_signer = Ed25519Pair::from(Ed25519Private::try_from(&[8u8; 32][..]).unwrap());
+        assert_matches!(
+            SignedTrustedIdentitySet::from_signed_json(
+                json.as_bytes(),
+                &signature,
+                &other_signer.public_key(),
+                None,
+                1000,
+            ),
+            Err(Error::InvalidSignature)
+        );
+
+        // A signature over the json alone, without the context string, is rejected.
+        let signature_without_context = signer.sign(json.as_bytes());
+        assert_matches!(
+            SignedTrustedIdentitySet::from_signed_json(
+                json.as_bytes(),
+                &signature_without_context,
+                &signer.public_key(),
+                None,
+                1000,
+            ),
+            Err(Error::InvalidSignature)
+        );
+
+        // Signed data which isn't a signed trusted identity set is still rejected.
+        let signature = signer.sign(&SignedTrustedIdentitySet::signing_message(
+            TEST_DATA.as_bytes(),
+        ));
+        assert_matches!(
+            SignedTrustedIdentitySet::from_signed_json(
+                TEST_DATA.as_bytes(),
+                &signature,
+                &signer.public_key(),
+                None,
+                1000,
+            ),
+            Err(Error::Json(_))
+        );
+    }
+
+    #[test]
+    fn test_signed_json_rollback_and_expiry() {
+        let signer = Ed25519Pair::from(Ed25519Private::try_from(&[7u8; 32][..]).unwrap());
+        let json = signed_test_data(5, 2000);
+        let signature = signer.sign(&SignedTrustedIdentitySet::signing_message(json.as_bytes()));
+        let load = |last_accepted_sequence, now| {
+            SignedTrustedIdentitySet::from_signed_json(
+                json.as_bytes(),
+                &signature,
+                &signer.public_key(),
+                last_accepted_sequence,
+                now,
+            )
+        };
+
+        assert!(load(None, 1999).is_ok());
+        assert_matches!(load(Some(6), 1000), Err(Error::Rollback(5, 6)));
+        assert_matches!(load(None, 2000), Err(Error::Expired(2000)));
+    }
+
     #[test]
     fn test_expected_failures() {
         // Not enough hex characters
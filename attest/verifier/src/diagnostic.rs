@@ -0,0 +1,320 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Explains why attestation evidence was rejected.
+//!
+//! The verification tree says everything about a failed verification, but
+//! it is long and its interesting lines are buried among the checks which
+//! passed. An [AttestationDiagnostic] pulls out what an operator needs to fix
+//! the failure: the measurement the enclave actually has, how it differs from
+//! each trusted identity, and which checks failed, e.g. an advisory which
+//! isn't configured as mitigated, or a TCB level which isn't up to date.
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+use displaydoc::Display;
+use hex_fmt::HexFmt;
+use mc_attestation_verifier::TrustedIdentity;
+use mc_sgx_core_types::ReportBody;
+use serde::{Deserialize, Serialize};
+
+/// A way in which an enclave's measurement differs from a trusted identity.
+#[derive(Clone, Debug, Deserialize, Display, Eq, PartialEq, Serialize)]
+pub enum IdentityMismatch {
+    /// MRENCLAVE is {actual}, not {expected}
+    MrEnclave {
+        /// The trusted MRENCLAVE, hex-encoded
+        expected: String,
+        /// The enclave's MRENCLAVE, hex-encoded
+        actual: String,
+    },
+    /// MRSIGNER is {actual}, not {expected}
+    MrSigner {
+        /// The trusted MRSIGNER, hex-encoded
+        expected: String,
+        /// The enclave's MRSIGNER, hex-encoded
+        actual: String,
+    },
+    /// ISV product ID is {actual}, not {expected}
+    IsvProductId {
+        /// The trusted product ID
+        expected: u16,
+        /// The enclave's product ID
+        actual: u16,
+    },
+    /// ISV SVN {actual} is below the minimum of {minimum}
+    IsvSvn {
+        /// The minimum trusted SVN
+        minimum: u16,
+        /// The enclave's SVN
+        actual: u16,
+    },
+}
+
+/// How an enclave compares to one of the identities trusted by the verifier.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct IdentityDiagnostic {
+    /// The trusted identity
+    pub identity: String,
+    /// How the enclave's measurement differs from the identity. Empty if it
+    /// matches, in which case the evidence was rejected for another reason.
+    pub mismatches: Vec<IdentityMismatch>,
+}
+
+/// Why attestation evidence was rejected.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct AttestationDiagnostic {
+    /// The enclave's MRENCLAVE, hex-encoded
+    pub mr_enclave: String,
+    /// The enclave's MRSIGNER, hex-encoded
+    pub mr_signer: String,
+    /// The enclave's ISV product ID
+    pub isv_product_id: u16,
+    /// The enclave's ISV SVN
+    pub isv_svn: u16,
+    /// How the enclave compares to each trusted identity
+    pub identities: Vec<IdentityDiagnostic>,
+    /// The innermost checks of the evidence which failed
+    pub failed_checks: Vec<String>,
+    /// The full verification tree
+    pub verification_tree: String,
+}
+
+impl AttestationDiagnostic {
+    /// Diagnose the rejection of evidence for an enclave with `report_body`,
+    /// which was verified against `identities` as described by
+    /// `verification_tree`.
+    pub fn new<'a>(
+        report_body: &ReportBody,
+        identities: impl IntoIterator<Item = &'a TrustedIdentity>,
+        verification_tree: String,
+    ) -> Self {
+        Self {
+            mr_enclave: hex(report_body.mr_enclave().as_ref()),
+            mr_signer: hex(report_body.mr_signer().as_ref()),
+            isv_product_id: *report_body.isv_product_id().as_ref(),
+            isv_svn: *report_body.isv_svn().as_ref(),
+            identities: identities
+                .into_iter()
+                .map(|identity| diagnose_identity(report_body, identity))
+                .collect(),
+            failed_checks: failed_checks(&verification_tree),
+            verification_tree,
+        }
+    }
+
+    /// Whether the enclave's measurement matches any trusted identity, in
+    /// which case the evidence was rejected for another reason, e.g. its
+    /// advisories or TCB level.
+    pub fn measurement_matches(&self) -> bool {
+        self.identities
+            .iter()
+            .any(|identity| identity.mismatches.is_empty())
+    }
+}
+
+impl fmt::Display for AttestationDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "enclave MRENCLAVE {} MRSIGNER {} product ID {} SVN {}",
+            self.mr_enclave, self.mr_signer, self.isv_product_id, self.isv_svn
+        )?;
+        for identity in self.identities.iter() {
+            if identity.mismatches.is_empty() {
+                write!(f, "; matches trusted {}", identity.identity)?;
+            } else {
+                let mismatches = identity
+                    .mismatches
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>();
+                write!(
+                    f,
+                    "; differs from trusted {}: {}",
+                    identity.identity,
+                    mismatches.join(", ")
+                )?;
+            }
+        }
+        for check in self.failed_checks.iter() {
+            write!(f, "; failed: {check}")?;
+        }
+        write!(f, "\n{}", self.verification_tree)
+    }
+}
+
+fn hex(bytes: &[u8; 32]) -> String {
+    format!("{}", HexFmt(bytes))
+}
+
+fn diagnose_identity(report_body: &ReportBody, identity: &TrustedIdentity) -> IdentityDiagnostic {
+    let mut mismatches = Vec::new();
+    let identity = match identity {
+        TrustedIdentity::MrEnclave(mr_enclave_identity) => {
+            let expected = hex(mr_enclave_identity.mr_enclave().as_ref());
+            let actual = hex(report_body.mr_enclave().as_ref());
+            let description = format!("MRENCLAVE {expected}");
+            if expected != actual {
+                mismatches.push(IdentityMismatch::MrEnclave { expected, actual });
+            }
+            description
+        }
+        TrustedIdentity::MrSigner(mr_signer_identity) => {
+            let expected = hex(mr_signer_identity.mr_signer().as_ref());
+            let actual = hex(report_body.mr_signer().as_ref());
+            let product_id = *mr_signer_identity.isv_product_id().as_ref();
+            let minimum_svn = *mr_signer_identity.isv_svn().as_ref();
+            let description =
+                format!("MRSIGNER {expected} product ID {product_id} SVN {minimum_svn}");
+            if expected != actual {
+                mismatches.push(IdentityMismatch::MrSigner { expected, actual });
+            }
+            let actual_product_id = *report_body.isv_product_id().as_ref();
+            if actual_product_id != product_id {
+                mismatches.push(IdentityMismatch::IsvProductId {
+                    expected: product_id,
+                    actual: actual_product_id,
+                });
+            }
+            let actual_svn = *report_body.isv_svn().as_ref();
+            if actual_svn < minimum_svn {
+                mismatches.push(IdentityMismatch::IsvSvn {
+                    minimum: minimum_svn,
+                    actual: actual_svn,
+                });
+            }
+            description
+        }
+    };
+    IdentityDiagnostic {
+        identity,
+        mismatches,
+    }
+}
+
+/// The failed checks of a verification tree which have no failed checks
+/// nested under them.
+///
+/// Every failed check which combines others has a failed check nested under
+/// it, and since nested checks are listed right after the check they're
+/// nested under, the next failed check is nested under it iff it is indented
+/// further.
+fn failed_checks(verification_tree: &str) -> Vec<String> {
+    const FAILED: &str = "- [ ] ";
+    let failed = verification_tree
+        .lines()
+        .filter_map(|line| {
+            let check = line.trim_start();
+            let indent = line.len() - check.len();
+            check.strip_prefix(FAILED).map(|check| (indent, check))
+        })
+        .collect::<Vec<_>>();
+
+    failed
+        .iter()
+        .enumerate()
+        .filter(|(i, (indent, _))| {
+            failed
+                .get(i + 1)
+                .map_or(true, |(next_indent, _)| next_indent <= indent)
+        })
+        .map(|(_, (_, check))| check.trim_end_matches(':').to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mc_attestation_verifier::{TrustedMrEnclaveIdentity, TrustedMrSignerIdentity};
+    use mc_sgx_core_types::{IsvProductId, IsvSvn, MrEnclave, MrSigner, Report};
+
+    const TREE: &str = "\
+- [ ] DCAP evidence:
+  - [ ] Both of the following must be true:
+    - [x] all of the following must be true:
+      - [x] The quote was signed with the provided key
+    - [ ] Both of the following must be true:
+      - [x] The MRENCLAVE should be 0000
+      - [ ] The allowed advisories are IDs: (none) Status: UpToDate, but the actual advisories were IDs: {\"INTEL-SA-00334\"} Status: SWHardeningNeeded
+  - [ ] The ReportData hash should be CDA9 for:
+    - QuoteNonce: 0x4242
+    - [ ] The expected report data is 0xCDA9, but the actual report data was 0xCEA9
+";
+
+    #[test]
+    fn failed_checks_are_the_innermost_failures() {
+        assert_eq!(
+            failed_checks(TREE),
+            [
+                "The allowed advisories are IDs: (none) Status: UpToDate, but the actual advisories were IDs: {\"INTEL-SA-00334\"} Status: SWHardeningNeeded",
+                "The expected report data is 0xCDA9, but the actual report data was 0xCEA9",
+            ]
+        );
+        assert!(failed_checks(&TREE.replace("[ ]", "[x]")).is_empty());
+    }
+
+    #[test]
+    fn identities_report_their_mismatches() {
+        let mut report = Report::default();
+        let body = &mut report.as_mut().body;
+        body.mr_enclave.m = [1; 32];
+        body.mr_signer.m = [2; 32];
+        body.isv_prod_id = 3;
+        body.isv_svn = 4;
+        let report_body = report.body();
+
+        let identities: [TrustedIdentity; 3] = [
+            TrustedMrEnclaveIdentity::new(
+                MrEnclave::from([1; 32]),
+                [] as [&str; 0],
+                [] as [&str; 0],
+            )
+            .into(),
+            TrustedMrEnclaveIdentity::new(
+                MrEnclave::from([5; 32]),
+                [] as [&str; 0],
+                [] as [&str; 0],
+            )
+            .into(),
+            TrustedMrSignerIdentity::new(
+                MrSigner::from([2; 32]),
+                IsvProductId::from(7),
+                IsvSvn::from(6),
+                [] as [&str; 0],
+                [] as [&str; 0],
+            )
+            .into(),
+        ];
+        let diagnostic = AttestationDiagnostic::new(&report_body, &identities, TREE.to_string());
+
+        assert_eq!(diagnostic.isv_product_id, 3);
+        assert_eq!(diagnostic.isv_svn, 4);
+        assert!(diagnostic.identities[0].mismatches.is_empty());
+        assert!(diagnostic.measurement_matches());
+        assert_eq!(
+            diagnostic.identities[1].mismatches,
+            [IdentityMismatch::MrEnclave {
+                expected: hex(&[5; 32]),
+                actual: hex(&[1; 32]),
+            }]
+        );
+        assert_eq!(
+            diagnostic.identities[2].mismatches,
+            [
+                IdentityMismatch::IsvProductId {
+                    expected: 7,
+                    actual: 3
+                },
+                IdentityMismatch::IsvSvn {
+                    minimum: 6,
+                    actual: 4
+                },
+            ]
+        );
+        assert_eq!(diagnostic.failed_checks.len(), 2);
+    }
+}
@@ -13,11 +13,15 @@
 
 mod avr;
 mod dcap;
+mod diagnostic;
 mod ias;
 mod quote;
 mod report_body;
 mod status;
-pub use crate::dcap::DcapVerifier;
+pub use crate::{
+    dcap::DcapVerifier,
+    diagnostic::{AttestationDiagnostic, IdentityDiagnostic, IdentityMismatch},
+};
 
 extern crate alloc;
 
@@ -65,6 +69,7 @@ use crate::{
 };
 use alloc::{
     borrow::ToOwned,
+    boxed::Box,
     string::{String, ToString},
     vec::Vec,
 };
@@ -148,6 +153,22 @@ pub enum Error {
      * requirements:\n{0}
      */
     Verification(String),
+
+    /**
+     * The evidence was properly constructed, but did not meet security
+     * requirements: {0}
+     */
+    Rejected(Box<AttestationDiagnostic>),
+}
+
+impl Error {
+    /// Why the evidence was rejected, if it was rejected with a diagnostic.
+    pub fn diagnostic(&self) -> Option<&AttestationDiagnostic> {
+        match self {
+            Error::Rejected(diagnostic) => Some(diagnostic),
+            _ => None,
+        }
+    }
 }
 
 impl From<VerifyError> for Error {
@@ -5,6 +5,7 @@ use crate::{
     BlockID, QuorumSet, VerificationReport,
 };
 use ::prost::Message;
+use alloc::{string::String, vec::Vec};
 use displaydoc::Display;
 use mc_attest_verifier_types::prost;
 use mc_common::ResponderId;
@@ -36,6 +37,53 @@ impl From<prost::DcapEvidence> for AttestationEvidence {
     }
 }
 
+/// The most labels that a block's metadata may carry.
+pub const MAX_BLOCK_METADATA_LABELS: usize = 8;
+
+/// The longest that a block metadata label may be, in bytes.
+pub const MAX_BLOCK_METADATA_LABEL_LEN: usize = 64;
+
+/// An error in the labels of a block's metadata.
+#[derive(Clone, Debug, Display, Eq, PartialEq)]
+pub enum BlockMetadataLabelError {
+    /// Too many labels: {0}
+    TooMany(usize),
+    /// Label is {0} bytes long
+    TooLong(usize),
+    /// Label is empty
+    Empty,
+    /// Label contains characters other than printable ASCII: {0:?}
+    InvalidCharacters(String),
+}
+
+/// Check that the given labels fit the limits on block metadata labels.
+///
+/// Each label must be 1 to [MAX_BLOCK_METADATA_LABEL_LEN] bytes of printable
+/// ASCII, without whitespace, and there may be at most
+/// [MAX_BLOCK_METADATA_LABELS] of them.
+pub fn validate_block_metadata_labels(labels: &[String]) -> Result<(), BlockMetadataLabelError> {
+    if labels.len() > MAX_BLOCK_METADATA_LABELS {
+        return Err(BlockMetadataLabelError::TooMany(labels.len()));
+    }
+    labels
+        .iter()
+        .try_for_each(|label| validate_block_metadata_label(label))
+}
+
+/// Check that a single label fits the limits on block metadata labels.
+pub fn validate_block_metadata_label(label: &str) -> Result<(), BlockMetadataLabelError> {
+    if label.is_empty() {
+        return Err(BlockMetadataLabelError::Empty);
+    }
+    if label.len() > MAX_BLOCK_METADATA_LABEL_LEN {
+        return Err(BlockMetadataLabelError::TooLong(label.len()));
+    }
+    if !label.bytes().all(|byte| byte.is_ascii_graphic()) {
+        return Err(BlockMetadataLabelError::InvalidCharacters(label.into()));
+    }
+    Ok(())
+}
+
 /// Metadata for a block.
 #[derive(Clone, Deserialize, Digestible, Display, Eq, Message, PartialEq, Serialize)]
 pub struct BlockMetadataContents {
@@ -55,6 +103,12 @@ pub struct BlockMetadataContents {
     /// Responder ID of the consensus node that externalized this block.
     #[prost(message, required, tag = 4)]
     responder_id: ResponderId,
+
+    /// Labels set by the operator of the consensus node, e.g.
+    /// "upgrade-epoch:5". Empty labels leave the digest unchanged, so
+    /// metadata signed before labels existed still verifies.
+    #[prost(string, repeated, tag = 6)]
+    labels: Vec<String>,
 }
 
 impl BlockMetadataContents {
@@ -70,9 +124,18 @@ impl BlockMetadataContents {
             quorum_set,
             attestation_evidence: Some(attestation_evidence),
             responder_id,
+            labels: Vec::new(),
         }
     }
 
+    /// Attach operator-defined labels to this metadata, checking them with
+    /// [validate_block_metadata_labels].
+    pub fn with_labels(mut self, labels: Vec<String>) -> Result<Self, BlockMetadataLabelError> {
+        validate_block_metadata_labels(&labels)?;
+        self.labels = labels;
+        Ok(self)
+    }
+
     /// Get the [BlockID].
     pub fn block_id(&self) -> &BlockID {
         &self.block_id
@@ -94,6 +157,11 @@ impl BlockMetadataContents {
     pub fn responder_id(&self) -> &ResponderId {
         &self.responder_id
     }
+
+    /// Get the operator-defined labels.
+    pub fn labels(&self) -> &[String] {
+        &self.labels
+    }
 }
 
 /// Signed metadata for a block.
@@ -165,6 +233,7 @@ mod test {
     use alloc::vec;
     use mc_blockchain_test_utils::test_node_id;
     use mc_crypto_digestible::MerlinTranscript;
+    use mc_util_from_random::FromRandom;
 
     /// Metadata contents used in block version 3
     #[derive(Clone, Deserialize, Digestible, Display, Eq, Message, PartialEq, Serialize)]
@@ -224,4 +293,64 @@ mod test {
             assert_eq!(block_v3_digest, block_v4_digest);
         })
     }
+
+    #[test]
+    fn labels_are_signed() {
+        mc_util_test_helper::run_with_several_seeds(|mut rng| {
+            let contents = BlockMetadataContents::new(
+                BlockID([1; 32]),
+                QuorumSet::new(1, vec![QuorumSetMember::Node(test_node_id(1))]),
+                mc_blockchain_test_utils::make_verification_report(&mut rng).into(),
+                ResponderId("hello".into()),
+            );
+            let unlabeled_digest = contents.digest32::<MerlinTranscript>(b"");
+
+            let contents = contents
+                .with_labels(vec!["upgrade-epoch:5".into()])
+                .unwrap();
+            assert_eq!(contents.labels(), &[String::from("upgrade-epoch:5")]);
+            assert_ne!(contents.digest32::<MerlinTranscript>(b""), unlabeled_digest);
+
+            let bytes = mc_util_serial::encode(&contents);
+            let decoded: BlockMetadataContents = mc_util_serial::decode(&bytes).unwrap();
+            assert_eq!(decoded, contents);
+
+            let key_pair = Ed25519Pair::from_random(&mut rng);
+            let metadata = BlockMetadata::from_contents_and_keypair(contents, &key_pair).unwrap();
+            assert!(metadata.verify().is_ok());
+
+            let mut tampered = metadata;
+            tampered.contents.labels = vec!["upgrade-epoch:6".into()];
+            assert!(tampered.verify().is_err());
+        })
+    }
+
+    #[test]
+    fn labels_are_limited() {
+        let too_many = vec![String::from("a"); MAX_BLOCK_METADATA_LABELS + 1];
+        assert_eq!(
+            validate_block_metadata_labels(&too_many),
+            Err(BlockMetadataLabelError::TooMany(
+                MAX_BLOCK_METADATA_LABELS + 1
+            ))
+        );
+        assert_eq!(
+            validate_block_metadata_label(&"a".repeat(MAX_BLOCK_METADATA_LABEL_LEN + 1)),
+            Err(BlockMetadataLabelError::TooLong(
+                MAX_BLOCK_METADATA_LABEL_LEN + 1
+            ))
+        );
+        assert_eq!(
+            validate_block_metadata_label(""),
+            Err(BlockMetadataLabelError::Empty)
+        );
+        assert_eq!(
+            validate_block_metadata_label("upgrade epoch"),
+            Err(BlockMetadataLabelError::InvalidCharacters(
+                "upgrade epoch".into()
+            ))
+        );
+        let largest = vec!["a".repeat(MAX_BLOCK_METADATA_LABEL_LEN); MAX_BLOCK_METADATA_LABELS];
+        assert!(validate_block_metadata_labels(&largest).is_ok());
+    }
 }
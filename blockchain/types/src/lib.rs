@@ -22,7 +22,11 @@ pub use crate::{
     block_contents::{BlockContents, BlockContentsHash},
     block_data::BlockData,
     block_id::BlockID,
-    block_metadata::{AttestationEvidence, BlockMetadata, BlockMetadataContents},
+    block_metadata::{
+        validate_block_metadata_label, validate_block_metadata_labels, AttestationEvidence,
+        BlockMetadata, BlockMetadataContents, BlockMetadataLabelError, MAX_BLOCK_METADATA_LABELS,
+        MAX_BLOCK_METADATA_LABEL_LEN,
+    },
     block_signature::BlockSignature,
     error::ConvertError,
 };
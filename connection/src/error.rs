@@ -7,8 +7,11 @@ pub use retry::Error as RetryError;
 use crate::traits::AttestationError;
 use displaydoc::Display;
 use grpcio::Error as GrpcError;
-use mc_blockchain_types::ConvertError;
-use mc_consensus_api::{consensus_common::ProposeTxResult, ConversionError};
+use mc_blockchain_types::{BlockIndex, ConvertError};
+use mc_consensus_api::{
+    consensus_common::{ProposeTxRejectionDetails, ProposeTxResult},
+    ConversionError,
+};
 use mc_crypto_noise::CipherError;
 use std::{array::TryFromSliceError, result::Result as StdResult};
 
@@ -31,7 +34,13 @@ pub enum Error {
     /// Attestation failure: {0}
     Attestation(Box<dyn AttestationError + 'static>),
     /// Transaction validation failure: {0:?}: {1}
-    TransactionValidation(ProposeTxResult, String),
+    TransactionValidation(ProposeTxResult, String, ProposeTxRejectionDetails),
+    /// Expected block {0}, but got block {1}
+    UnexpectedBlockIndex(BlockIndex, BlockIndex),
+    /// Block {0} has an invalid ID, or is not the child of the previous block
+    InvalidBlock(BlockIndex),
+    /// Network block version {0} is not supported by this client
+    UnsupportedBlockVersion(u32),
     /// Other error: {0}
     Other(String),
 }
@@ -42,7 +51,9 @@ impl Error {
         match self {
             Error::Grpc(_) => true,
             Error::Attestation(err) => err.should_retry(),
-            Error::TransactionValidation(ProposeTxResult::LedgerTxOutIndexOutOfBounds, _) => true,
+            Error::TransactionValidation(ProposeTxResult::LedgerTxOutIndexOutOfBounds, _, _) => {
+                true
+            }
             _ => false,
         }
     }
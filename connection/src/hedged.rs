@@ -0,0 +1,668 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! A connection wrapper which hedges blockchain reads across several nodes.
+//!
+//! Each read is sent to several nodes at once, and the first complete answer
+//! which passes verification is returned, without waiting for slower nodes.
+//! Nodes which return answers that fail verification are tracked, and stop
+//! being asked once they have done so too often.
+
+use crate::{
+    error::{Error, Result, RetryResult},
+    pipeline::BlockFetchPipeline,
+    sync::SyncConnection,
+    traits::{BlockInfo, BlockchainConnection, RetryableBlockchainConnection},
+};
+use mc_blockchain_types::{Block, BlockID, BlockIndex};
+use mc_common::logger::{log, Logger};
+use retry::{retry, OperationResult};
+use std::{
+    ops::Range,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The number of invalid answers after which a node is no longer asked,
+/// unless every node has been excluded.
+pub const MAX_INVALID_ANSWERS: u64 = 3;
+
+/// How long to keep waiting for a complete answer from the other nodes once
+/// a node has given a verified but partial one, e.g. fewer blocks than were
+/// requested because it is behind.
+pub const PARTIAL_ANSWER_GRACE_PERIOD: Duration = Duration::from_millis(250);
+
+/// How a node has answered hedged reads.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct NodeBehavior {
+    /// The number of reads for which this node gave the answer that was used
+    pub answers_used: u64,
+    /// The number of reads which failed on this node
+    pub errors: u64,
+    /// The number of answers which failed verification, e.g. blocks whose IDs
+    /// don't match their contents
+    pub invalid_answers: u64,
+}
+
+impl NodeBehavior {
+    /// Whether the node has misbehaved too often to keep asking it
+    pub fn is_excluded(&self) -> bool {
+        self.invalid_answers >= MAX_INVALID_ANSWERS
+    }
+}
+
+struct Node<C: BlockchainConnection> {
+    conn: SyncConnection<C>,
+    behavior: Mutex<NodeBehavior>,
+    /// Whether a hedged read thread is running for this node
+    busy: AtomicBool,
+}
+
+impl<C: BlockchainConnection> Node<C> {
+    fn update_behavior(&self, update: impl FnOnce(&mut NodeBehavior)) {
+        update(&mut self.behavior.lock().expect("NodeBehavior lock poisoned"));
+    }
+
+    fn behavior(&self) -> NodeBehavior {
+        *self.behavior.lock().expect("NodeBehavior lock poisoned")
+    }
+
+    /// Make a read on this node, and record how it went.
+    fn read<T>(
+        &self,
+        read: &(dyn Fn(&mut C) -> Result<T> + Send + Sync),
+        verify: &(dyn Fn(&T) -> bool + Send + Sync),
+    ) -> OperationResult<T, Error> {
+        match read(&mut self.conn.write()) {
+            Ok(value) if verify(&value) => OperationResult::Ok(value),
+            Ok(_) => {
+                self.update_behavior(|behavior| behavior.invalid_answers += 1);
+                // Other nodes may well answer correctly.
+                OperationResult::Retry(Error::Other(format!(
+                    "{} returned an answer which failed verification",
+                    self.conn
+                )))
+            }
+            Err(err) => {
+                self.update_behavior(|behavior| behavior.errors += 1);
+                if err.should_retry() {
+                    OperationResult::Retry(err)
+                } else {
+                    OperationResult::Err(err)
+                }
+            }
+        }
+    }
+}
+
+/// A node on which a hedged read thread is running, marked idle again when
+/// the thread is done with it.
+struct BusyNode<C: BlockchainConnection> {
+    nodes: Arc<[Node<C>]>,
+    index: usize,
+}
+
+impl<C: BlockchainConnection> BusyNode<C> {
+    /// Mark the node busy, unless a read thread is already running for it.
+    fn claim(nodes: &Arc<[Node<C>]>, index: usize) -> Option<Self> {
+        nodes[index]
+            .busy
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+            .ok()
+            .map(|_| Self {
+                nodes: nodes.clone(),
+                index,
+            })
+    }
+
+    fn node(&self) -> &Node<C> {
+        &self.nodes[self.index]
+    }
+}
+
+impl<C: BlockchainConnection> Drop for BusyNode<C> {
+    fn drop(&mut self) {
+        self.node().busy.store(false, Ordering::Release);
+    }
+}
+
+/// Sends blockchain reads to several nodes at once, and returns the first
+/// complete verified answer.
+///
+/// Blocks are verified to have IDs matching their contents, to be the ones
+/// requested, and to form a chain. A node which is behind may verifiably
+/// return fewer blocks than requested, so such an answer is only used if no
+/// other node returns more within [PARTIAL_ANSWER_GRACE_PERIOD]. Other
+/// answers can't be verified against a single node's response, so the first
+/// successful one is used.
+///
+/// At most one read thread runs per node. Nodes still busy with an earlier
+/// read, e.g. because they are slow to answer, are skipped.
+pub struct HedgedConnection<C: BlockchainConnection + 'static> {
+    nodes: Arc<[Node<C>]>,
+    /// The number of nodes each read is sent to
+    fanout: usize,
+    /// Rotates the nodes which are asked first, to spread load
+    next_node: Arc<AtomicUsize>,
+    logger: Logger,
+}
+
+impl<C: BlockchainConnection + 'static> Clone for HedgedConnection<C> {
+    fn clone(&self) -> Self {
+        Self {
+            nodes: self.nodes.clone(),
+            fanout: self.fanout,
+            next_node: self.next_node.clone(),
+            logger: self.logger.clone(),
+        }
+    }
+}
+
+impl<C: BlockchainConnection + 'static> HedgedConnection<C> {
+    /// Create a hedged connection sending each read to `fanout` of `conns`.
+    pub fn new(conns: Vec<SyncConnection<C>>, fanout: usize, logger: Logger) -> Self {
+        assert!(
+            !conns.is_empty(),
+            "HedgedConnection needs at least one node"
+        );
+        let nodes = conns
+            .into_iter()
+            .map(|conn| Node {
+                conn,
+                behavior: Mutex::new(NodeBehavior::default()),
+                busy: AtomicBool::new(false),
+            })
+            .collect();
+        Self {
+            nodes,
+            fanout: fanout.max(1),
+            next_node: Arc::new(AtomicUsize::new(0)),
+            logger,
+        }
+    }
+
+    /// How each node has answered so far.
+    pub fn node_behavior(&self) -> Vec<(C::Uri, NodeBehavior)> {
+        self.nodes
+            .iter()
+            .map(|node| (node.conn.uri(), node.behavior()))
+            .collect()
+    }
+
+    /// The nodes a read may be sent to, in the order to try them: all nodes
+    /// in turn, skipping excluded nodes while there are others.
+    fn candidate_nodes(&self) -> Vec<usize> {
+        let start = self.next_node.fetch_add(1, Ordering::Relaxed);
+        let in_turn = (0..self.nodes.len()).map(|offset| (start + offset) % self.nodes.len());
+        let candidates = in_turn
+            .clone()
+            .filter(|index| !self.nodes[*index].behavior().is_excluded())
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            in_turn.collect()
+        } else {
+            candidates
+        }
+    }
+
+    /// Send a read to up to `fanout` idle nodes, and wait for the first
+    /// verified answer of size `complete_size`, or the largest verified answer
+    /// given within the grace period after the first one. Slower nodes keep
+    /// running in the background, so that their behavior is still recorded.
+    ///
+    /// If every node is still busy with an earlier read, the read is made on
+    /// the first candidate node from this thread, once that node is free.
+    fn hedged_read<T: Send + 'static>(
+        &self,
+        read: Arc<dyn Fn(&mut C) -> Result<T> + Send + Sync>,
+        verify: Arc<dyn Fn(&T) -> bool + Send + Sync>,
+        size: Arc<dyn Fn(&T) -> u64 + Send + Sync>,
+        complete_size: u64,
+    ) -> OperationResult<T, Error> {
+        let candidates = self.candidate_nodes();
+        let (sender, receiver) = mpsc::channel();
+        let mut num_started = 0;
+        for index in candidates.iter().copied() {
+            if num_started == self.fanout {
+                break;
+            }
+            let Some(busy_node) = BusyNode::claim(&self.nodes, index) else {
+                continue;
+            };
+            let sender = sender.clone();
+            let read = read.clone();
+            let verify = verify.clone();
+            let spawned = thread::Builder::new()
+                .name("HedgedRead".to_string())
+                .spawn(move || {
+                    let result = busy_node.node().read(&*read, &*verify);
+                    let index = busy_node.index;
+                    // Free the node before answering, so that a retry can use
+                    // it again.
+                    drop(busy_node);
+                    // The receiver is gone if another node answered first.
+                    let _ = sender.send((index, result));
+                });
+            match spawned {
+                Ok(_) => num_started += 1,
+                Err(err) => {
+                    log::warn!(self.logger, "Could not start hedged read thread: {}", err)
+                }
+            }
+        }
+        if num_started == 0 {
+            if let Some(index) = candidates.first().copied() {
+                let _ = sender.send((index, self.nodes[index].read(&*read, &*verify)));
+            }
+        }
+        drop(sender);
+
+        let mut should_retry = false;
+        let mut last_error = None;
+        let mut best_partial: Option<(usize, T, u64)> = None;
+        let mut deadline = None;
+        loop {
+            let received = match deadline {
+                None => receiver.recv().ok(),
+                Some(deadline) => receiver
+                    .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                    .ok(),
+            };
+            let Some((index, result)) = received else {
+                break;
+            };
+            let err = match result {
+                OperationResult::Ok(value) => {
+                    let value_size = size(&value);
+                    if value_size >= complete_size {
+                        self.nodes[index].update_behavior(|behavior| behavior.answers_used += 1);
+                        return OperationResult::Ok(value);
+                    }
+                    if best_partial
+                        .as_ref()
+                        .map_or(true, |(_, _, best_size)| value_size > *best_size)
+                    {
+                        best_partial = Some((index, value, value_size));
+                    }
+                    deadline.get_or_insert_with(|| Instant::now() + PARTIAL_ANSWER_GRACE_PERIOD);
+                    continue;
+                }
+                OperationResult::Retry(err) => {
+                    should_retry = true;
+                    err
+                }
+                OperationResult::Err(err) => err,
+            };
+            log::debug!(
+                self.logger,
+                "Hedged read from {} failed: {}",
+                self.nodes[index].conn,
+                err
+            );
+            last_error = Some(err);
+        }
+
+        if let Some((index, value, _)) = best_partial {
+            self.nodes[index].update_behavior(|behavior| behavior.answers_used += 1);
+            return OperationResult::Ok(value);
+        }
+        let err = last_error
+            .unwrap_or_else(|| Error::Other("No node could be asked for a hedged read".into()));
+        if should_retry {
+            OperationResult::Retry(err)
+        } else {
+            OperationResult::Err(err)
+        }
+    }
+
+    /// Retry a hedged read of an answer which is complete as soon as it is
+    /// verified.
+    fn retry_hedged_read<T: Send + 'static>(
+        &self,
+        retry_iterator: impl IntoIterator<Item = Duration>,
+        read: impl Fn(&mut C) -> Result<T> + Send + Sync + 'static,
+        verify: impl Fn(&T) -> bool + Send + Sync + 'static,
+    ) -> RetryResult<T> {
+        self.retry_hedged_read_sized(retry_iterator, read, verify, |_| 0, 0)
+    }
+
+    /// Retry a hedged read of a list of up to `num_requested` items, which
+    /// nodes that are behind may verifiably answer only in part.
+    fn retry_hedged_read_list<T: Send + 'static>(
+        &self,
+        retry_iterator: impl IntoIterator<Item = Duration>,
+        read: impl Fn(&mut C) -> Result<Vec<T>> + Send + Sync + 'static,
+        verify: impl Fn(&Vec<T>) -> bool + Send + Sync + 'static,
+        num_requested: u64,
+    ) -> RetryResult<Vec<T>> {
+        self.retry_hedged_read_sized(
+            retry_iterator,
+            read,
+            verify,
+            |items| items.len() as u64,
+            num_requested,
+        )
+    }
+
+    fn retry_hedged_read_sized<T: Send + 'static>(
+        &self,
+        retry_iterator: impl IntoIterator<Item = Duration>,
+        read: impl Fn(&mut C) -> Result<T> + Send + Sync + 'static,
+        verify: impl Fn(&T) -> bool + Send + Sync + 'static,
+        size: impl Fn(&T) -> u64 + Send + Sync + 'static,
+        complete_size: u64,
+    ) -> RetryResult<T> {
+        let read: Arc<dyn Fn(&mut C) -> Result<T> + Send + Sync> = Arc::new(read);
+        let verify: Arc<dyn Fn(&T) -> bool + Send + Sync> = Arc::new(verify);
+        let size: Arc<dyn Fn(&T) -> u64 + Send + Sync> = Arc::new(size);
+        retry(retry_iterator.into_iter().map(retry::delay::jitter), || {
+            self.hedged_read(read.clone(), verify.clone(), size.clone(), complete_size)
+        })
+    }
+}
+
+/// Whether `blocks` are the blocks at the start of `range`, with valid IDs, and
+/// each the parent of the next. Nodes which are behind may return fewer blocks
+/// than requested.
+fn verify_blocks(range: &Range<BlockIndex>, blocks: &[Block]) -> bool {
+    let num_requested = range.end.saturating_sub(range.start);
+    blocks.len() as u64 <= num_requested
+        && blocks
+            .iter()
+            .zip(range.start..)
+            .all(|(block, index)| block.index == index && block.is_block_id_valid())
+        && blocks
+            .windows(2)
+            .all(|pair| pair[1].parent_id == pair[0].id)
+}
+
+impl<C: BlockchainConnection + 'static> RetryableBlockchainConnection for HedgedConnection<C> {
+    fn fetch_blocks(
+        &self,
+        range: Range<BlockIndex>,
+        retry_iterator: impl IntoIterator<Item = Duration>,
+    ) -> RetryResult<Vec<Block>> {
+        let read_range = range.clone();
+        let num_requested = range.end.saturating_sub(range.start);
+        self.retry_hedged_read_list(
+            retry_iterator,
+            move |conn| conn.fetch_blocks(read_range.clone()),
+            move |blocks| verify_blocks(&range, blocks),
+            num_requested,
+        )
+    }
+
+    fn fetch_blocks_pipelined(
+        &self,
+        range: Range<BlockIndex>,
+        parent: Option<&Block>,
+        pipeline: &BlockFetchPipeline,
+        retry_iterator: impl IntoIterator<Item = Duration>,
+    ) -> RetryResult<Vec<Block>> {
+        // Each node verifies the chain as it arrives, so a verified answer
+        // only needs to be for the right range.
+        let read_range = range.clone();
+        let parent = parent.cloned();
+        let pipeline = *pipeline;
+        let num_requested = range.end.saturating_sub(range.start);
+        self.retry_hedged_read_list(
+            retry_iterator,
+            move |conn| conn.fetch_blocks_pipelined(read_range.clone(), parent.as_ref(), &pipeline),
+            move |blocks| verify_blocks(&range, blocks),
+            num_requested,
+        )
+    }
+
+    fn fetch_block_ids(
+        &self,
+        range: Range<BlockIndex>,
+        retry_iterator: impl IntoIterator<Item = Duration>,
+    ) -> RetryResult<Vec<BlockID>> {
+        let num_requested = range.end.saturating_sub(range.start);
+        self.retry_hedged_read_list(
+            retry_iterator,
+            move |conn| conn.fetch_block_ids(range.clone()),
+            move |ids| ids.len() as u64 <= num_requested,
+            num_requested,
+        )
+    }
+
+    fn fetch_block_height(
+        &self,
+        retry_iterator: impl IntoIterator<Item = Duration>,
+    ) -> RetryResult<BlockIndex> {
+        self.retry_hedged_read(retry_iterator, |conn| conn.fetch_block_height(), |_| true)
+    }
+
+    fn fetch_block_info(
+        &self,
+        retry_iterator: impl IntoIterator<Item = Duration>,
+    ) -> RetryResult<BlockInfo> {
+        self.retry_hedged_read(retry_iterator, |conn| conn.fetch_block_info(), |_| true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{error::RetryError, traits::Connection};
+    use mc_blockchain_types::{BlockContents, BlockVersion};
+    use mc_common::logger::{test_with_logger, Logger};
+    use mc_util_uri::ConsensusClientUri;
+    use std::{
+        fmt::{Display, Formatter, Result as FmtResult},
+        str::FromStr,
+    };
+
+    #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+    enum Answer {
+        Honest,
+        Slow,
+        Delayed,
+        Behind,
+        Tampered,
+        Fails,
+    }
+
+    #[derive(Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+    struct FakeConnection {
+        uri: ConsensusClientUri,
+        answer: Answer,
+        blocks: Vec<Block>,
+    }
+
+    impl FakeConnection {
+        fn new(port: u16, answer: Answer, blocks: &[Block]) -> Self {
+            let uri = ConsensusClientUri::from_str(&format!("insecure-mc://node:{port}/"))
+                .expect("Could not parse uri");
+            Self {
+                uri,
+                answer,
+                blocks: blocks.to_vec(),
+            }
+        }
+    }
+
+    impl Display for FakeConnection {
+        fn fmt(&self, f: &mut Formatter) -> FmtResult {
+            write!(f, "{}", self.uri)
+        }
+    }
+
+    impl Connection for FakeConnection {
+        type Uri = ConsensusClientUri;
+
+        fn uri(&self) -> Self::Uri {
+            self.uri.clone()
+        }
+    }
+
+    impl BlockchainConnection for FakeConnection {
+        fn fetch_blocks(&mut self, range: Range<BlockIndex>) -> Result<Vec<Block>> {
+            let mut blocks = self.blocks[range.start as usize..range.end as usize].to_vec();
+            match self.answer {
+                Answer::Honest => {}
+                Answer::Slow => thread::sleep(Duration::from_millis(200)),
+                Answer::Delayed => thread::sleep(Duration::from_millis(20)),
+                Answer::Behind => blocks.truncate(1),
+                Answer::Tampered => blocks[0].cumulative_txo_count += 1,
+                Answer::Fails => return Err(Error::Other("node is down".into())),
+            }
+            Ok(blocks)
+        }
+
+        fn fetch_block_ids(&mut self, range: Range<BlockIndex>) -> Result<Vec<BlockID>> {
+            Ok(self
+                .fetch_blocks(range)?
+                .into_iter()
+                .map(|block| block.id)
+                .collect())
+        }
+
+        fn fetch_block_height(&mut self) -> Result<BlockIndex> {
+            Ok(self.blocks.len() as u64 - 1)
+        }
+
+        fn fetch_block_info(&mut self) -> Result<BlockInfo> {
+            Err(Error::NotFound)
+        }
+    }
+
+    fn blocks() -> Vec<Block> {
+        let mut blocks = vec![Block::new_origin_block(&[])];
+        for _ in 0..4 {
+            let block = Block::new_with_parent(
+                BlockVersion::MAX,
+                blocks.last().unwrap(),
+                &Default::default(),
+                &BlockContents::default(),
+            );
+            blocks.push(block);
+        }
+        blocks
+    }
+
+    fn hedged(
+        answers: &[Answer],
+        fanout: usize,
+        logger: &Logger,
+    ) -> HedgedConnection<FakeConnection> {
+        let blocks = blocks();
+        let conns = answers
+            .iter()
+            .enumerate()
+            .map(|(index, answer)| {
+                SyncConnection::new(
+                    FakeConnection::new(3000 + index as u16, *answer, &blocks),
+                    logger.clone(),
+                )
+            })
+            .collect();
+        HedgedConnection::new(conns, fanout, logger.clone())
+    }
+
+    #[test]
+    fn verify_blocks_checks_ids_indexes_and_chain() {
+        let blocks = blocks();
+        assert!(verify_blocks(&(1..4), &blocks[1..4]));
+        assert!(verify_blocks(&(1..4), &blocks[1..3]));
+        assert!(verify_blocks(&(1..4), &[]));
+        assert!(!verify_blocks(&(1..3), &blocks[1..4]));
+        assert!(!verify_blocks(&(0..3), &blocks[1..4]));
+        assert!(!verify_blocks(
+            &(1..3),
+            &[blocks[1].clone(), blocks[1].clone()]
+        ));
+
+        let mut tampered = blocks[1..4].to_vec();
+        tampered[1].cumulative_txo_count += 1;
+        assert!(!verify_blocks(&(1..4), &tampered));
+    }
+
+    #[test_with_logger]
+    fn fastest_verified_answer_is_used(logger: Logger) {
+        let conn = hedged(
+            &[Answer::Slow, Answer::Tampered, Answer::Honest],
+            3,
+            &logger,
+        );
+        let expected = blocks()[1..3].to_vec();
+
+        assert_eq!(conn.fetch_blocks(1..3, [Duration::ZERO]).unwrap(), expected);
+
+        let behavior = conn.node_behavior();
+        assert_eq!(behavior[0].1.answers_used, 0);
+        assert_eq!(behavior[1].1.answers_used, 0);
+        assert_eq!(behavior[2].1.answers_used, 1);
+    }
+
+    #[test_with_logger]
+    fn complete_answer_is_preferred_to_partial_one(logger: Logger) {
+        let conn = hedged(&[Answer::Behind, Answer::Delayed], 2, &logger);
+        let expected = blocks()[1..4].to_vec();
+
+        assert_eq!(conn.fetch_blocks(1..4, [Duration::ZERO]).unwrap(), expected);
+
+        let behavior = conn.node_behavior();
+        assert_eq!(behavior[0].1.answers_used, 0);
+        assert_eq!(behavior[1].1.answers_used, 1);
+    }
+
+    #[test_with_logger]
+    fn partial_answer_is_used_when_no_node_has_more(logger: Logger) {
+        let conn = hedged(&[Answer::Behind, Answer::Fails], 2, &logger);
+        let expected = blocks()[1..2].to_vec();
+
+        assert_eq!(conn.fetch_blocks(1..4, [Duration::ZERO]).unwrap(), expected);
+        assert_eq!(conn.node_behavior()[0].1.answers_used, 1);
+    }
+
+    #[test_with_logger]
+    fn busy_nodes_are_skipped(logger: Logger) {
+        let conn = hedged(&[Answer::Slow, Answer::Honest], 1, &logger);
+        assert!(BusyNode::claim(&conn.nodes, 0).is_some());
+        let _busy = BusyNode::claim(&conn.nodes, 0).unwrap();
+        assert!(BusyNode::claim(&conn.nodes, 0).is_none());
+
+        // Node 0 is first in turn, but busy.
+        let expected = blocks()[0..2].to_vec();
+        assert_eq!(conn.fetch_blocks(0..2, [Duration::ZERO]).unwrap(), expected);
+
+        let behavior = conn.node_behavior();
+        assert_eq!(behavior[0].1.answers_used, 0);
+        assert_eq!(behavior[1].1.answers_used, 1);
+    }
+
+    #[test_with_logger]
+    fn misbehaving_nodes_are_excluded(logger: Logger) {
+        let conn = hedged(&[Answer::Tampered, Answer::Honest], 1, &logger);
+        let expected = blocks()[0..2].to_vec();
+
+        for _ in 0..2 * MAX_INVALID_ANSWERS {
+            let retries = [Duration::ZERO; 2 * MAX_INVALID_ANSWERS as usize];
+            assert_eq!(conn.fetch_blocks(0..2, retries).unwrap(), expected);
+        }
+
+        let behavior = conn.node_behavior();
+        assert_eq!(behavior[0].1.invalid_answers, MAX_INVALID_ANSWERS);
+        assert!(behavior[0].1.is_excluded());
+        assert_eq!(behavior[0].1.answers_used, 0);
+        assert_eq!(behavior[1].1.answers_used, 2 * MAX_INVALID_ANSWERS);
+    }
+
+    #[test_with_logger]
+    fn errors_are_returned_when_no_node_answers(logger: Logger) {
+        let conn = hedged(&[Answer::Fails, Answer::Fails], 2, &logger);
+        assert!(conn.fetch_blocks(0..2, [Duration::ZERO]).is_err());
+        assert!(matches!(
+            conn.fetch_block_info([]),
+            Err(RetryError {
+                error: Error::NotFound,
+                ..
+            })
+        ));
+    }
+}
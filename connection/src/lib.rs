@@ -4,7 +4,10 @@
 
 mod credentials;
 mod error;
+mod hedged;
 mod manager;
+mod network_status;
+mod pipeline;
 mod sync;
 mod thick;
 mod traits;
@@ -15,7 +18,15 @@ pub use crate::{
         CredentialsProviderError, HardcodedCredentialsProvider, TokenBasicCredentialsProvider,
     },
     error::{Error, Result, RetryError, RetryResult},
+    hedged::{HedgedConnection, NodeBehavior, MAX_INVALID_ANSWERS},
     manager::ConnectionManager,
+    network_status::{
+        NetworkFeatures, NetworkStatus, NetworkStatusCache, DEFAULT_NETWORK_STATUS_TTL,
+    },
+    pipeline::{
+        BlockChainVerifier, BlockFetchPipeline, DEFAULT_BLOCKS_PER_REQUEST,
+        DEFAULT_MAX_OUTSTANDING_REQUESTS,
+    },
     sync::SyncConnection,
     thick::{ThickClient, ThickClientAttestationError},
     traits::{
@@ -25,5 +36,5 @@ pub use crate::{
 };
 
 pub use mc_common::trace_time as _trace_time;
-pub use mc_consensus_api::consensus_common::ProposeTxResult;
+pub use mc_consensus_api::consensus_common::{ProposeTxRejectionDetails, ProposeTxResult};
 pub use retry as _retry;
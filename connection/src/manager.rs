@@ -45,8 +45,8 @@ impl<C: Connection> ConnectionManager<C> {
                                 .host_and_port_responder_id()
                                 .unwrap_or_else(|err| {
                                     panic!(
-                                        "Could not create responder_id from {:?}: {}",
-                                        conn.uri().to_string(),
+                                        "Could not create responder_id from {}: {}",
+                                        conn.uri().redacted(),
                                         err
                                     )
                                 });
@@ -0,0 +1,198 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! The network's current block version, minimum fees and features, as
+//! clients building transactions need them.
+
+use crate::{
+    error::{Error, Result},
+    traits::{BlockInfo, BlockchainConnection},
+};
+use mc_blockchain_types::BlockIndex;
+use mc_transaction_core::{BlockVersion, FeeMap, FeeMapError, TokenId};
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+/// How long a [NetworkStatusCache] keeps a status by default.
+pub const DEFAULT_NETWORK_STATUS_TTL: Duration = Duration::from_secs(60);
+
+/// The features enabled on the network by its block version.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct NetworkFeatures {
+    /// Transaction outputs carry encrypted memos
+    pub encrypted_memos: bool,
+    /// Transaction outputs mask their token ids
+    pub masked_token_ids: bool,
+    /// Transactions may contain outputs of several token ids
+    pub mixed_transactions: bool,
+    /// Minting transactions are accepted
+    pub mint_transactions: bool,
+    /// Inputs may carry signed input rules, as signed contingent inputs do
+    pub signed_input_rules: bool,
+    /// Transaction outputs use version 2 of masked amounts
+    pub masked_amount_v2: bool,
+    /// Multisig signer sets may be nested
+    pub nested_multisigs: bool,
+}
+
+impl From<BlockVersion> for NetworkFeatures {
+    fn from(block_version: BlockVersion) -> Self {
+        Self {
+            encrypted_memos: block_version.e_memo_feature_is_supported(),
+            masked_token_ids: block_version.masked_token_id_feature_is_supported(),
+            mixed_transactions: block_version.mixed_transactions_are_supported(),
+            mint_transactions: block_version.mint_transactions_are_supported(),
+            signed_input_rules: block_version.signed_input_rules_are_supported(),
+            masked_amount_v2: block_version.masked_amount_v2_is_supported(),
+            nested_multisigs: block_version.nested_multisigs_are_supported(),
+        }
+    }
+}
+
+/// What a client needs to know about the network to build transactions which
+/// it will accept.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NetworkStatus {
+    /// The index of the last block
+    pub block_index: BlockIndex,
+
+    /// The block version new transactions should target. After the network
+    /// is upgraded, this may be higher than the block version of any block
+    /// in the ledger.
+    pub block_version: BlockVersion,
+
+    /// The minimum fee for each token id supported by the network
+    pub minimum_fees: BTreeMap<TokenId, u64>,
+
+    /// The features enabled by the block version
+    pub features: NetworkFeatures,
+}
+
+impl NetworkStatus {
+    /// The minimum fee for a token id, or None if the network doesn't
+    /// support the token.
+    pub fn minimum_fee(&self, token_id: &TokenId) -> Option<u64> {
+        match self.minimum_fees.get(token_id) {
+            None | Some(&0) => None,
+            Some(fee) => Some(*fee),
+        }
+    }
+
+    /// The fee map of the network, which transactions must commit to.
+    pub fn fee_map(&self) -> core::result::Result<FeeMap, FeeMapError> {
+        FeeMap::try_from(self.minimum_fees.clone())
+    }
+}
+
+impl TryFrom<BlockInfo> for NetworkStatus {
+    type Error = Error;
+
+    fn try_from(src: BlockInfo) -> Result<Self> {
+        let block_version = BlockVersion::try_from(src.network_block_version)
+            .map_err(|_| Error::UnsupportedBlockVersion(src.network_block_version))?;
+        Ok(Self {
+            block_index: src.block_index,
+            block_version,
+            minimum_fees: src.minimum_fees,
+            features: block_version.into(),
+        })
+    }
+}
+
+impl From<NetworkStatus> for BlockInfo {
+    fn from(src: NetworkStatus) -> Self {
+        Self {
+            block_index: src.block_index,
+            minimum_fees: src.minimum_fees,
+            network_block_version: *src.block_version,
+        }
+    }
+}
+
+/// Caches the status of the network for a while, so that a client building
+/// transactions doesn't ask consensus for it every time, but still notices
+/// when the network is upgraded or its fees change.
+#[derive(Clone, Debug)]
+pub struct NetworkStatusCache {
+    ttl: Duration,
+    cached: Option<(Instant, NetworkStatus)>,
+}
+
+impl Default for NetworkStatusCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_NETWORK_STATUS_TTL)
+    }
+}
+
+impl NetworkStatusCache {
+    /// Create a cache which keeps a status for `ttl`.
+    pub fn new(ttl: Duration) -> Self {
+        Self { ttl, cached: None }
+    }
+
+    /// Get the status of the network, asking `conn` for it if the cached
+    /// status is missing or expired.
+    pub fn get_network_status(
+        &mut self,
+        conn: &mut (impl BlockchainConnection + ?Sized),
+    ) -> Result<NetworkStatus> {
+        if let Some((fetched_at, status)) = self.cached.as_ref() {
+            if fetched_at.elapsed() < self.ttl {
+                return Ok(status.clone());
+            }
+        }
+
+        let status = conn.get_network_status()?;
+        self.cached = Some((Instant::now(), status.clone()));
+        Ok(status)
+    }
+
+    /// The cached status, even if it has expired.
+    pub fn cached(&self) -> Option<&NetworkStatus> {
+        self.cached.as_ref().map(|(_, status)| status)
+    }
+
+    /// Forget the cached status, e.g. after consensus rejected a transaction
+    /// because its fee map or block version was out of date.
+    pub fn invalidate(&mut self) {
+        self.cached = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mc_transaction_core::{tokens::Mob, Token};
+
+    fn block_info(network_block_version: u32) -> BlockInfo {
+        BlockInfo {
+            block_index: 10,
+            minimum_fees: [(Mob::ID, Mob::MINIMUM_FEE), (TokenId::from(1), 0)].into(),
+            network_block_version,
+        }
+    }
+
+    #[test]
+    fn network_status_from_block_info() {
+        let status = NetworkStatus::try_from(block_info(*BlockVersion::THREE)).unwrap();
+
+        assert_eq!(status.block_index, 10);
+        assert_eq!(status.block_version, BlockVersion::THREE);
+        assert_eq!(status.minimum_fee(&Mob::ID), Some(Mob::MINIMUM_FEE));
+        assert_eq!(status.minimum_fee(&TokenId::from(1)), None);
+        assert_eq!(status.minimum_fee(&TokenId::from(2)), None);
+        assert!(status.features.encrypted_memos);
+        assert!(status.features.mixed_transactions);
+        assert!(!status.features.masked_amount_v2);
+    }
+
+    #[test]
+    fn unknown_block_version_is_unsupported() {
+        let version = *BlockVersion::MAX + 1;
+        assert!(matches!(
+            NetworkStatus::try_from(block_info(version)),
+            Err(Error::UnsupportedBlockVersion(v)) if v == version
+        ));
+    }
+}
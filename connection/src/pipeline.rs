@@ -0,0 +1,184 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Support for downloading long ranges of blocks with several requests in
+//! flight at once, verifying each response as it arrives.
+
+use crate::error::{Error, Result};
+use mc_blockchain_types::{Block, BlockID, BlockIndex};
+use std::{cmp::min, ops::Range};
+
+/// The default number of blocks asked for in each request.
+pub const DEFAULT_BLOCKS_PER_REQUEST: u32 = 500;
+
+/// The default number of requests which may be outstanding at once.
+pub const DEFAULT_MAX_OUTSTANDING_REQUESTS: usize = 4;
+
+/// How a range of blocks is split into requests, and how many of those
+/// requests may be outstanding at once.
+///
+/// Keeping several requests outstanding hides the round trip time of each,
+/// which dominates initial sync over high-latency links.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct BlockFetchPipeline {
+    /// The number of blocks asked for in each request. This should be no more
+    /// than the nodes' page size, or the fetch stops after the first page.
+    pub blocks_per_request: u32,
+    /// The number of requests which may be outstanding at once. One means
+    /// fetching each range only after the previous one arrived.
+    pub max_outstanding_requests: usize,
+}
+
+impl Default for BlockFetchPipeline {
+    fn default() -> Self {
+        Self {
+            blocks_per_request: DEFAULT_BLOCKS_PER_REQUEST,
+            max_outstanding_requests: DEFAULT_MAX_OUTSTANDING_REQUESTS,
+        }
+    }
+}
+
+impl BlockFetchPipeline {
+    /// Split `range` into the ranges of each request, in order.
+    pub fn request_ranges(
+        &self,
+        range: Range<BlockIndex>,
+    ) -> impl Iterator<Item = Range<BlockIndex>> {
+        let step = u64::from(self.blocks_per_request.max(1));
+        (range.start..range.end)
+            .step_by(step as usize)
+            .map(move |start| start..min(start.saturating_add(step), range.end))
+    }
+
+    /// The number of requests which may be outstanding at once, which is at
+    /// least one.
+    pub fn window(&self) -> usize {
+        self.max_outstanding_requests.max(1)
+    }
+}
+
+/// Checks that consecutive batches of blocks form a chain: each block has the
+/// next index, a valid ID, and the previous block as its parent.
+pub struct BlockChainVerifier {
+    next_index: BlockIndex,
+    parent_id: Option<BlockID>,
+}
+
+impl BlockChainVerifier {
+    /// Verify a chain starting at `first_index`. If `parent` is given, the
+    /// first block must be its child.
+    pub fn new(first_index: BlockIndex, parent: Option<&Block>) -> Self {
+        Self {
+            next_index: first_index,
+            parent_id: parent.map(|block| block.id.clone()),
+        }
+    }
+
+    /// Verify the next batch of blocks, which continues the chain.
+    pub fn verify(&mut self, blocks: &[Block]) -> Result<()> {
+        for block in blocks {
+            if block.index != self.next_index {
+                return Err(Error::UnexpectedBlockIndex(self.next_index, block.index));
+            }
+            let is_child = self
+                .parent_id
+                .as_ref()
+                .map_or(true, |parent_id| *parent_id == block.parent_id);
+            if !is_child || !block.is_block_id_valid() {
+                return Err(Error::InvalidBlock(block.index));
+            }
+            self.next_index += 1;
+            self.parent_id = Some(block.id.clone());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mc_blockchain_types::{BlockContents, BlockVersion};
+    use mc_transaction_core::ring_signature::KeyImage;
+
+    fn child_of(parent: &Block, block_contents: &BlockContents) -> Block {
+        Block::new_with_parent(
+            BlockVersion::MAX,
+            parent,
+            &Default::default(),
+            block_contents,
+        )
+    }
+
+    fn blocks() -> Vec<Block> {
+        let mut blocks = vec![Block::new_origin_block(&[])];
+        for _ in 0..5 {
+            let block = child_of(blocks.last().unwrap(), &BlockContents::default());
+            blocks.push(block);
+        }
+        blocks
+    }
+
+    #[test]
+    fn ranges_are_split_into_requests() {
+        let pipeline = BlockFetchPipeline {
+            blocks_per_request: 3,
+            max_outstanding_requests: 2,
+        };
+        assert_eq!(
+            pipeline.request_ranges(5..13).collect::<Vec<_>>(),
+            vec![5..8, 8..11, 11..13]
+        );
+        assert_eq!(pipeline.request_ranges(5..5).count(), 0);
+        assert_eq!(
+            pipeline
+                .request_ranges(u64::MAX - 2..u64::MAX)
+                .collect::<Vec<_>>(),
+            vec![u64::MAX - 2..u64::MAX]
+        );
+    }
+
+    #[test]
+    fn batches_must_continue_the_chain() {
+        let blocks = blocks();
+
+        let mut verifier = BlockChainVerifier::new(1, Some(&blocks[0]));
+        verifier.verify(&blocks[1..3]).unwrap();
+        verifier.verify(&[]).unwrap();
+        verifier.verify(&blocks[3..6]).unwrap();
+
+        let mut verifier = BlockChainVerifier::new(0, None);
+        verifier.verify(&blocks[0..2]).unwrap();
+        assert!(matches!(
+            verifier.verify(&blocks[3..4]),
+            Err(Error::UnexpectedBlockIndex(2, 3))
+        ));
+
+        let mut tampered = blocks[2].clone();
+        tampered.cumulative_txo_count += 1;
+        let mut verifier = BlockChainVerifier::new(1, Some(&blocks[0]));
+        verifier.verify(&blocks[1..2]).unwrap();
+        assert!(matches!(
+            verifier.verify(&[tampered]),
+            Err(Error::InvalidBlock(2))
+        ));
+    }
+
+    #[test]
+    fn blocks_from_another_chain_are_rejected() {
+        let blocks = blocks();
+        let fork = child_of(
+            &blocks[0],
+            &BlockContents {
+                key_images: vec![KeyImage::from(1)],
+                ..Default::default()
+            },
+        );
+        let forked_child = child_of(&fork, &BlockContents::default());
+        assert!(forked_child.is_block_id_valid());
+
+        let mut verifier = BlockChainVerifier::new(2, Some(&blocks[1]));
+        assert!(matches!(
+            verifier.verify(&[forked_child]),
+            Err(Error::InvalidBlock(2))
+        ));
+    }
+}
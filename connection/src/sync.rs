@@ -4,6 +4,7 @@
 
 use crate::{
     error::RetryResult,
+    pipeline::BlockFetchPipeline,
     traits::{
         BlockInfo, BlockchainConnection, Connection, RetryableBlockchainConnection,
         RetryableUserTxConnection, UserTxConnection,
@@ -176,6 +177,20 @@ macro_rules! impl_sync_connection_retry {
             $crate::_retry_wrapper!($obj.$func($arg1, $arg2))
         })
     }};
+    ($obj:expr, $logger:expr, $func:ident, $iter:expr, $arg1:expr, $arg2:expr, $arg3:expr) => {{
+        $crate::_trace_time!(
+            $logger,
+            "SyncConnection.{}({}, {}, {}, {})",
+            stringify!($func),
+            stringify!($arg1),
+            stringify!($arg2),
+            stringify!($arg3),
+            stringify!($iter)
+        );
+        $crate::_retry::retry($iter.into_iter().map($crate::_retry::delay::jitter), || {
+            $crate::_retry_wrapper!($obj.$func($arg1, $arg2, $arg3))
+        })
+    }};
 }
 
 impl<BC: BlockchainConnection> RetryableBlockchainConnection for SyncConnection<BC> {
@@ -193,6 +208,24 @@ impl<BC: BlockchainConnection> RetryableBlockchainConnection for SyncConnection<
         )
     }
 
+    fn fetch_blocks_pipelined(
+        &self,
+        range: Range<BlockIndex>,
+        parent: Option<&Block>,
+        pipeline: &BlockFetchPipeline,
+        retry_iterator: impl IntoIterator<Item = Duration>,
+    ) -> RetryResult<Vec<Block>> {
+        impl_sync_connection_retry!(
+            self.write(),
+            self.logger,
+            fetch_blocks_pipelined,
+            retry_iterator,
+            range.clone(),
+            parent,
+            pipeline
+        )
+    }
+
     fn fetch_block_ids(
         &self,
         range: Range<BlockIndex>,
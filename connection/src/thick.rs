@@ -7,6 +7,7 @@
 use crate::{
     credentials::{AuthenticationError, CredentialsProvider, CredentialsProviderError},
     error::{Error, Result},
+    pipeline::{BlockChainVerifier, BlockFetchPipeline},
     traits::{
         AttestationError, AttestedConnection, BlockInfo, BlockchainConnection, Connection,
         UserTxConnection,
@@ -34,7 +35,7 @@ use mc_common::{
 };
 use mc_consensus_api::{
     consensus_client_grpc::ConsensusClientApiClient,
-    consensus_common::{BlocksRequest, ProposeTxResult},
+    consensus_common::{BlocksRequest, BlocksResponse, ProposeTxResult},
     consensus_common_grpc::BlockchainApiClient,
     empty::Empty,
 };
@@ -49,6 +50,7 @@ use secrecy::{ExposeSecret, SecretVec};
 use sha2::Sha512;
 use std::{
     cmp::Ordering,
+    collections::VecDeque,
     fmt::{Display, Formatter, Result as FmtResult},
     hash::{Hash, Hasher},
     ops::Range,
@@ -210,8 +212,16 @@ impl<CP: CredentialsProvider> ThickClient<CP> {
             self.handle_rpc_error(err);
         }
 
-        // Block on the call, and update cookies before passing on the response.
-        result?
+        self.receive_authenticated(result?)
+    }
+
+    /// Block on a call made with [Self::call_option], and update cookies
+    /// before passing on the response.
+    fn receive_authenticated<T>(
+        &mut self,
+        receiver: ClientUnaryReceiver<T>,
+    ) -> StdResult<T, ThickClientAttestationError> {
+        receiver
             .receive_sync()
             .map(|(header, response, trailer)| {
                 // Update cookies from server-sent metadata
@@ -235,6 +245,28 @@ impl<CP: CredentialsProvider> ThickClient<CP> {
             })
     }
 
+    /// Start an authenticated+attested GetBlocks call for `range`, without
+    /// waiting for the response.
+    fn start_get_blocks(
+        &mut self,
+        range: &Range<BlockIndex>,
+    ) -> StdResult<ClientUnaryReceiver<BlocksResponse>, ThickClientAttestationError> {
+        let mut request = BlocksRequest::new();
+        request.set_offset(range.start);
+        // Ranges from a BlockFetchPipeline always fit.
+        request.set_limit(u32::try_from(range.end - range.start).unwrap_or(u32::MAX));
+
+        let call_option = self.call_option()?;
+        let result = self.attested_call(|this| {
+            this.blockchain_api_client
+                .get_blocks_async_opt(&request, call_option)
+        });
+        if let Err(err) = &result {
+            self.handle_rpc_error(err);
+        }
+        result
+    }
+
     /// A convenience wrapper for performing authenticated+attested GRPC calls
     fn authenticated_attested_call<T>(
         &mut self,
@@ -369,6 +401,51 @@ impl<CP: CredentialsProvider> BlockchainConnection for ThickClient<CP> {
         .collect::<Result<Vec<Block>>>()
     }
 
+    fn fetch_blocks_pipelined(
+        &mut self,
+        range: Range<BlockIndex>,
+        parent: Option<&Block>,
+        pipeline: &BlockFetchPipeline,
+    ) -> Result<Vec<Block>> {
+        trace_time!(self.logger, "ThickClient::fetch_blocks_pipelined");
+
+        let mut verifier = BlockChainVerifier::new(range.start, parent);
+        let mut request_ranges = pipeline.request_ranges(range);
+        let mut outstanding = VecDeque::new();
+        let mut blocks = Vec::new();
+        loop {
+            while outstanding.len() < pipeline.window() {
+                let Some(request_range) = request_ranges.next() else {
+                    break;
+                };
+                let receiver = self.start_get_blocks(&request_range)?;
+                outstanding.push_back((request_range, receiver));
+            }
+
+            // Responses are verified in order, as soon as each arrives, so that
+            // a bad node is caught before the whole range has downloaded.
+            let Some((request_range, receiver)) = outstanding.pop_front() else {
+                break;
+            };
+            let response = self
+                .receive_authenticated(receiver)?
+                .get_blocks()
+                .iter()
+                .map(|proto_block| Block::try_from(proto_block).map_err(Error::from))
+                .collect::<Result<Vec<Block>>>()?;
+            verifier.verify(&response)?;
+
+            // If the node doesn't have the whole range, stop. Dropping the
+            // outstanding calls cancels them.
+            let is_complete = response.len() as u64 == request_range.end - request_range.start;
+            blocks.extend(response);
+            if !is_complete {
+                break;
+            }
+        }
+        Ok(blocks)
+    }
+
     fn fetch_block_ids(&mut self, range: Range<BlockIndex>) -> Result<Vec<BlockID>> {
         trace_time!(self.logger, "ThickClient::get_block_ids");
 
@@ -432,7 +509,7 @@ impl<CP: CredentialsProvider> UserTxConnection for ThickClient<CP> {
             enclave_connection.encrypt(&[], tx_plaintext.expose_secret().as_ref())?;
         msg.set_data(tx_ciphertext);
 
-        let resp = self.authenticated_attested_call(|this, call_option| {
+        let mut resp = self.authenticated_attested_call(|this, call_option| {
             this.consensus_client_api_client
                 .client_tx_propose_async_opt(&msg, call_option)
         })?;
@@ -443,6 +520,7 @@ impl<CP: CredentialsProvider> UserTxConnection for ThickClient<CP> {
             Err(Error::TransactionValidation(
                 resp.get_result(),
                 resp.get_err_msg().to_owned(),
+                resp.take_rejection_details(),
             ))
         }
     }
@@ -450,7 +528,7 @@ impl<CP: CredentialsProvider> UserTxConnection for ThickClient<CP> {
 
 impl<CP: CredentialsProvider> Display for ThickClient<CP> {
     fn fmt(&self, f: &mut Formatter) -> FmtResult {
-        write!(f, "{}", self.uri)
+        write!(f, "{}", self.uri.redacted())
     }
 }
 
@@ -2,7 +2,11 @@
 
 //! Traits which connection implementations can implement.
 
-use crate::error::{Result, RetryResult};
+use crate::{
+    error::{Error, Result, RetryResult},
+    network_status::NetworkStatus,
+    pipeline::{BlockChainVerifier, BlockFetchPipeline},
+};
 use grpcio::Error as GrpcError;
 use mc_attest_core::EvidenceKind;
 use mc_blockchain_types::{Block, BlockID, BlockIndex};
@@ -152,6 +156,46 @@ pub trait BlockchainConnection: Connection {
 
     /// Retrieve the consensus node's current block height and fee
     fn fetch_block_info(&mut self) -> Result<BlockInfo>;
+
+    /// Retrieve the network's current block version, minimum fees and the
+    /// features that block version enables.
+    fn get_network_status(&mut self) -> Result<NetworkStatus> {
+        NetworkStatus::try_from(self.fetch_block_info()?)
+    }
+
+    /// Retrieve the blocks in `range`, in requests split according to
+    /// `pipeline`, verifying each response as it arrives: every block must
+    /// have a valid ID and be the child of the block before it, the first
+    /// being the child of `parent` if given.
+    ///
+    /// The returned blocks stop early if the node doesn't have all of them.
+    ///
+    /// This default implementation makes one request at a time.
+    /// Implementations talking to a remote node should keep up to
+    /// `pipeline.max_outstanding_requests` requests in flight instead.
+    fn fetch_blocks_pipelined(
+        &mut self,
+        range: Range<BlockIndex>,
+        parent: Option<&Block>,
+        pipeline: &BlockFetchPipeline,
+    ) -> Result<Vec<Block>> {
+        let mut verifier = BlockChainVerifier::new(range.start, parent);
+        let mut blocks = Vec::new();
+        for request_range in pipeline.request_ranges(range) {
+            let response = match self.fetch_blocks(request_range.clone()) {
+                // The previous request ended exactly at the node's last block.
+                Err(Error::NotFound) if !blocks.is_empty() => break,
+                result => result?,
+            };
+            verifier.verify(&response)?;
+            let is_complete = response.len() as u64 == request_range.end - request_range.start;
+            blocks.extend(response);
+            if !is_complete {
+                break;
+            }
+        }
+        Ok(blocks)
+    }
 }
 
 /// A trait which supports supporting the submission of transactions to a node
@@ -174,6 +218,16 @@ pub trait RetryableBlockchainConnection {
         retry_iterator: impl IntoIterator<Item = Duration>,
     ) -> RetryResult<Vec<Block>>;
 
+    /// Retrieve a long range of blocks, with several requests in flight at
+    /// once. See [BlockchainConnection::fetch_blocks_pipelined].
+    fn fetch_blocks_pipelined(
+        &self,
+        range: Range<BlockIndex>,
+        parent: Option<&Block>,
+        pipeline: &BlockFetchPipeline,
+        retry_iterator: impl IntoIterator<Item = Duration>,
+    ) -> RetryResult<Vec<Block>>;
+
     /// Retrieve the BlockIDs (hashes) of the given blocks from the blockchain
     /// service.
     fn fetch_block_ids(
@@ -10,13 +10,14 @@
 
 use crate::{
     consensus_client::{MintValidationResult, MintValidationResultCode},
-    consensus_common::ProposeTxResult,
+    consensus_common::{ProposeTxRejectionDetails, ProposeTxResult},
     consensus_config,
 };
 use mc_api::ConversionError;
 use mc_transaction_core::{
-    mint::MintValidationError, validation::TransactionValidationError as Error, BlockVersion,
-    InputRuleError, RevealedTxOutError, TokenId,
+    mint::MintValidationError, ring_ct::Error as RingCtError,
+    validation::TransactionValidationError as Error, BlockVersion, InputRuleError,
+    RevealedTxOutError, TokenId,
 };
 
 /// Convert TransactionValidationError --> ProposeTxResult.
@@ -94,6 +95,30 @@ impl From<InputRuleError> for ProposeTxResult {
     }
 }
 
+/// Convert TransactionValidationError --> ProposeTxRejectionDetails.
+impl From<&Error> for ProposeTxRejectionDetails {
+    fn from(src: &Error) -> Self {
+        let mut details = Self {
+            result: ProposeTxResult::from(src.clone()),
+            ..Default::default()
+        };
+        match src {
+            Error::InvalidTransactionSignature(err) => {
+                if let RingCtError::MissingRangeProofs(expected, found) = err {
+                    details.expected_range_proofs = *expected as u64;
+                    details.found_range_proofs = *found as u64;
+                }
+                details.cause = err.to_string();
+            }
+            Error::Ledger(err) => details.cause = err.clone(),
+            Error::LedgerTxOutIndexOutOfBounds(index) => details.tx_out_index = *index,
+            Error::InputRule(err) => details.cause = err.to_string(),
+            _ => {}
+        }
+        details
+    }
+}
+
 /// Convert MintValidationError -> MintValidationResult.
 impl From<MintValidationError> for MintValidationResult {
     fn from(src: MintValidationError) -> Self {
@@ -371,4 +396,35 @@ mod conversion_tests {
             assert_eq!(source, recovered);
         }
     }
+
+    #[test]
+    fn test_convert_tx_rejection_details() {
+        let details = ProposeTxRejectionDetails::from(&Error::LedgerTxOutIndexOutOfBounds(42));
+        assert_eq!(details.result, ProposeTxResult::LedgerTxOutIndexOutOfBounds);
+        assert_eq!(details.tx_out_index, 42);
+        assert!(details.cause.is_empty());
+
+        let details = ProposeTxRejectionDetails::from(&Error::InvalidTransactionSignature(
+            RingCtError::MissingRangeProofs(3, 1),
+        ));
+        assert_eq!(details.result, ProposeTxResult::InvalidTransactionSignature);
+        assert_eq!(details.expected_range_proofs, 3);
+        assert_eq!(details.found_range_proofs, 1);
+        assert_eq!(
+            details.cause,
+            RingCtError::MissingRangeProofs(3, 1).to_string()
+        );
+
+        let details = ProposeTxRejectionDetails::from(&Error::InputRule(
+            InputRuleError::MissingRequiredOutput,
+        ));
+        assert_eq!(
+            details.result,
+            ProposeTxResult::InputRuleMissingRequiredOutput
+        );
+        assert_eq!(
+            details.cause,
+            InputRuleError::MissingRequiredOutput.to_string()
+        );
+    }
 }
@@ -17,6 +17,7 @@ pub use crate::{
 
 use base64::{engine::general_purpose::STANDARD as BASE64_ENGINE, Engine};
 use clap::Parser;
+use mc_blockchain_types::validate_block_metadata_label;
 use mc_common::{NodeID, ResponderId};
 use mc_crypto_keys::{DistinguishedEncoding, Ed25519Pair, Ed25519Private};
 use mc_transaction_core::BlockVersion;
@@ -127,6 +128,17 @@ pub struct Config {
     /// config setting to match.
     #[clap(long, default_value = "10000", env = "MC_CLIENT_TRACKING_CAPACITY")]
     pub client_tracking_capacity: usize,
+
+    /// Labels to attach to the signed metadata of blocks this node
+    /// externalizes, e.g. "upgrade-epoch:5". The fog ledger server exposes
+    /// them per block.
+    #[clap(
+        long = "block-metadata-label",
+        value_parser = parse_block_metadata_label,
+        use_value_delimiter = true,
+        env = "MC_BLOCK_METADATA_LABELS"
+    )]
+    pub block_metadata_labels: Vec<String>,
 }
 
 impl Config {
@@ -185,6 +197,12 @@ fn parse_block_version(s: &str) -> Result<BlockVersion, String> {
     BlockVersion::from_str(s).map_err(|e| e.to_string())
 }
 
+/// Helper for parsing a block metadata label
+fn parse_block_metadata_label(s: &str) -> Result<String, String> {
+    validate_block_metadata_label(s).map_err(|e| e.to_string())?;
+    Ok(s.to_owned())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -213,6 +231,7 @@ mod tests {
             tokens_path: None,
             block_version: BlockVersion::ZERO,
             client_tracking_capacity: 4096,
+            block_metadata_labels: vec![],
         };
 
         assert_eq!(
@@ -280,6 +299,7 @@ mod tests {
             tokens_path: None,
             block_version: BlockVersion::ZERO,
             client_tracking_capacity: 4096,
+            block_metadata_labels: vec![],
         };
 
         assert_eq!(
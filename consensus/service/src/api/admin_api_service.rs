@@ -0,0 +1,275 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Serves operator-facing gRPC requests on the admin port, for inspecting and
+//! managing the transactions this node holds.
+
+use crate::{
+    consensus_service::EvictTxCallback,
+    tx_manager::{EvictTxOutcome, TxManager},
+    SVC_COUNTERS,
+};
+use grpcio::{RpcContext, RpcStatus, UnarySink};
+use mc_common::logger::{log, Logger};
+use mc_consensus_api::{
+    consensus_admin::{
+        EvictTxRequest, EvictTxResponse, GetPendingTxsResponse, GetTxRejectionRequest,
+        GetTxRejectionResponse, PendingTx,
+    },
+    consensus_admin_grpc::ConsensusAdminApi,
+    empty::Empty,
+};
+use mc_transaction_core::tx::TxHash;
+use mc_util_grpc::{rpc_invalid_arg_error, rpc_logger, rpc_unavailable_error, send_result};
+use protobuf::RepeatedField;
+use std::{sync::Arc, time::UNIX_EPOCH};
+
+#[derive(Clone)]
+pub struct AdminApiService {
+    /// Transaction manager.
+    tx_manager: Arc<dyn TxManager + Send + Sync>,
+
+    /// Evicts transactions through ByzantineLedger, so that it stops
+    /// proposing them.
+    evict_tx_callback: EvictTxCallback,
+
+    /// Logger.
+    logger: Logger,
+}
+
+impl AdminApiService {
+    pub fn new(
+        tx_manager: Arc<dyn TxManager + Send + Sync>,
+        evict_tx_callback: EvictTxCallback,
+        logger: Logger,
+    ) -> Self {
+        Self {
+            tx_manager,
+            evict_tx_callback,
+            logger,
+        }
+    }
+
+    fn get_pending_txs_impl(&self) -> GetPendingTxsResponse {
+        let mut pending_txs = self
+            .tx_manager
+            .pending_txs()
+            .iter()
+            .map(|context| {
+                let mut pending_tx = PendingTx::new();
+                pending_tx.set_tx_hash(context.tx_hash().to_vec());
+                pending_tx.set_fee(context.priority());
+                pending_tx.set_tombstone_block(context.tombstone_block());
+                pending_tx
+            })
+            .collect::<Vec<_>>();
+        // Highest fee first, which is the order they are combined in.
+        pending_txs.sort_by(|a, b| b.get_fee().cmp(&a.get_fee()));
+
+        let mut response = GetPendingTxsResponse::new();
+        response.set_pending_txs(RepeatedField::from_vec(pending_txs));
+        response
+    }
+
+    fn get_tx_rejection_impl(
+        &self,
+        request: GetTxRejectionRequest,
+        logger: &Logger,
+    ) -> Result<GetTxRejectionResponse, RpcStatus> {
+        let tx_hash = parse_tx_hash("get_tx_rejection", request.get_tx_hash(), logger)?;
+
+        let mut response = GetTxRejectionResponse::new();
+        if let Some(rejection) = self.tx_manager.recent_rejection(&tx_hash) {
+            response.set_found(true);
+            response.set_reason(rejection.error.to_string());
+            response.set_rejected_at(
+                rejection
+                    .rejected_at
+                    .duration_since(UNIX_EPOCH)
+                    .map(|duration| duration.as_secs())
+                    .unwrap_or_default(),
+            );
+        }
+        Ok(response)
+    }
+
+    fn evict_tx_impl(
+        &self,
+        request: EvictTxRequest,
+        logger: &Logger,
+    ) -> Result<EvictTxResponse, RpcStatus> {
+        let tx_hash = parse_tx_hash("evict_tx", request.get_tx_hash(), logger)?;
+
+        let outcome = (self.evict_tx_callback)(tx_hash)
+            .ok_or_else(|| rpc_unavailable_error("evict_tx", "consensus is not running", logger))?;
+        log::info!(
+            logger,
+            "Admin request to evict tx {}: {:?}",
+            tx_hash,
+            outcome
+        );
+
+        let mut response = EvictTxResponse::new();
+        response.set_evicted(outcome == EvictTxOutcome::Evicted);
+        response.set_in_current_slot(outcome == EvictTxOutcome::InCurrentSlot);
+        Ok(response)
+    }
+}
+
+fn parse_tx_hash(context: &str, bytes: &[u8], logger: &Logger) -> Result<TxHash, RpcStatus> {
+    TxHash::try_from(bytes)
+        .map_err(|_| rpc_invalid_arg_error(context, "tx_hash must be 32 bytes", logger))
+}
+
+impl ConsensusAdminApi for AdminApiService {
+    fn get_pending_txs(
+        &mut self,
+        ctx: RpcContext,
+        _request: Empty,
+        sink: UnarySink<GetPendingTxsResponse>,
+    ) {
+        let _timer = SVC_COUNTERS.req(&ctx);
+        mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
+            send_result(ctx, sink, Ok(self.get_pending_txs_impl()), logger);
+        });
+    }
+
+    fn get_tx_rejection(
+        &mut self,
+        ctx: RpcContext,
+        request: GetTxRejectionRequest,
+        sink: UnarySink<GetTxRejectionResponse>,
+    ) {
+        let _timer = SVC_COUNTERS.req(&ctx);
+        mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
+            send_result(
+                ctx,
+                sink,
+                self.get_tx_rejection_impl(request, logger),
+                logger,
+            );
+        });
+    }
+
+    fn evict_tx(
+        &mut self,
+        ctx: RpcContext,
+        request: EvictTxRequest,
+        sink: UnarySink<EvictTxResponse>,
+    ) {
+        let _timer = SVC_COUNTERS.req(&ctx);
+        mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
+            send_result(ctx, sink, self.evict_tx_impl(request, logger), logger);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tx_manager::{MockTxManager, TxManagerError, TxRejection};
+    use mc_common::logger::test_with_logger;
+    use mc_consensus_enclave::WellFormedTxContext;
+    use mc_transaction_core::validation::TransactionValidationError;
+    use std::time::{Duration, SystemTime};
+
+    #[test_with_logger]
+    fn test_get_pending_txs_sorted_by_fee(logger: Logger) {
+        let mut tx_manager = MockTxManager::new();
+        tx_manager.expect_pending_txs().times(1).return_const(
+            [(1u8, 100u64), (2, 300), (3, 200)]
+                .into_iter()
+                .map(|(i, fee)| {
+                    Arc::new(WellFormedTxContext::new(
+                        fee,
+                        TxHash([i; 32]),
+                        50,
+                        Default::default(),
+                        Default::default(),
+                        Default::default(),
+                    ))
+                })
+                .collect::<Vec<_>>(),
+        );
+        let service = AdminApiService::new(Arc::new(tx_manager), Arc::new(|_| None), logger);
+
+        let response = service.get_pending_txs_impl();
+        let fees: Vec<_> = response
+            .get_pending_txs()
+            .iter()
+            .map(|tx| tx.get_fee())
+            .collect();
+        assert_eq!(fees, vec![300, 200, 100]);
+        assert_eq!(response.get_pending_txs()[0].get_tx_hash(), &[2u8; 32]);
+        assert_eq!(response.get_pending_txs()[0].get_tombstone_block(), 50);
+    }
+
+    #[test_with_logger]
+    fn test_get_tx_rejection(logger: Logger) {
+        let mut tx_manager = MockTxManager::new();
+        tx_manager
+            .expect_recent_rejection()
+            .times(1)
+            .return_const(Some(TxRejection {
+                error: TxManagerError::TransactionValidation(
+                    TransactionValidationError::TombstoneBlockExceeded,
+                ),
+                rejected_at: SystemTime::UNIX_EPOCH + Duration::from_secs(1234),
+            }));
+        let service =
+            AdminApiService::new(Arc::new(tx_manager), Arc::new(|_| None), logger.clone());
+
+        let mut request = GetTxRejectionRequest::new();
+        request.set_tx_hash(vec![7u8; 32]);
+        let response = service.get_tx_rejection_impl(request, &logger).unwrap();
+        assert!(response.get_found());
+        assert_eq!(response.get_rejected_at(), 1234);
+        assert!(response.get_reason().contains("tombstone block"));
+    }
+
+    #[test_with_logger]
+    fn test_evict_tx_rejects_malformed_hash(logger: Logger) {
+        // The tx manager should not be consulted.
+        let tx_manager = MockTxManager::new();
+        let service =
+            AdminApiService::new(Arc::new(tx_manager), Arc::new(|_| None), logger.clone());
+
+        let mut request = EvictTxRequest::new();
+        request.set_tx_hash(vec![1u8; 5]);
+        assert!(service.evict_tx_impl(request, &logger).is_err());
+    }
+
+    #[test_with_logger]
+    fn test_evict_tx_reports_outcome(logger: Logger) {
+        let tx_hash = TxHash([9u8; 32]);
+        let evict_tx = |outcome: Option<EvictTxOutcome>| {
+            let service = AdminApiService::new(
+                Arc::new(MockTxManager::new()),
+                Arc::new(move |requested| {
+                    assert_eq!(requested, tx_hash);
+                    outcome
+                }),
+                logger.clone(),
+            );
+            let mut request = EvictTxRequest::new();
+            request.set_tx_hash(tx_hash.to_vec());
+            service
+                .evict_tx_impl(request, &logger)
+                .map(|response| (response.get_evicted(), response.get_in_current_slot()))
+        };
+
+        assert_eq!(
+            evict_tx(Some(EvictTxOutcome::Evicted)).unwrap(),
+            (true, false)
+        );
+        assert_eq!(
+            evict_tx(Some(EvictTxOutcome::NotFound)).unwrap(),
+            (false, false)
+        );
+        assert_eq!(
+            evict_tx(Some(EvictTxOutcome::InCurrentSlot)).unwrap(),
+            (false, true)
+        );
+        // ByzantineLedger isn't running.
+        assert!(evict_tx(None).is_err());
+    }
+}
@@ -923,6 +923,10 @@ mod client_api_tests {
                     propose_tx_response.get_result(),
                     ProposeTxResult::InvalidRangeProof
                 );
+                assert_eq!(
+                    propose_tx_response.get_rejection_details().get_result(),
+                    ProposeTxResult::InvalidRangeProof
+                );
                 assert_eq!(propose_tx_response.get_block_count(), num_blocks);
             }
             Err(e) => panic!("Unexpected error: {e:?}"),
@@ -6,7 +6,7 @@ use grpcio::{RpcStatus, RpcStatusCode};
 use mc_common::logger::global_log;
 use mc_consensus_api::{
     consensus_client::{MintValidationResult, ProposeMintConfigTxResponse, ProposeMintTxResponse},
-    consensus_common::{ProposeTxResponse, ProposeTxResult},
+    consensus_common::{ProposeTxRejectionDetails, ProposeTxResponse, ProposeTxResult},
 };
 use mc_consensus_enclave::Error as EnclaveError;
 use mc_consensus_service_config::Error as ConfigError;
@@ -148,12 +148,17 @@ impl From<ConsensusGrpcError> for Result<ProposeTxResponse, RpcStatus> {
             ConsensusGrpcError::TransactionValidation(err) => {
                 let mut resp = ProposeTxResponse::new();
                 resp.set_err_msg(err.to_string());
+                resp.set_rejection_details(ProposeTxRejectionDetails::from(&err));
                 resp.set_result(ProposeTxResult::from(err));
                 Ok(resp)
             }
             ConsensusGrpcError::Enclave(EnclaveError::FeeMapDigestMismatch) => {
                 let mut resp = ProposeTxResponse::new();
                 resp.set_err_msg(EnclaveError::FeeMapDigestMismatch.to_string());
+                resp.set_rejection_details(ProposeTxRejectionDetails {
+                    result: ProposeTxResult::FeeMapDigestMismatch,
+                    ..Default::default()
+                });
                 resp.set_result(ProposeTxResult::FeeMapDigestMismatch);
                 Ok(resp)
             }
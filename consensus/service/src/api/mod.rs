@@ -3,6 +3,7 @@
 //! gRPC APIs
 #![allow(clippy::result_large_err)]
 
+mod admin_api_service;
 mod attested_api_service;
 mod blockchain_api_service;
 mod client_api_service;
@@ -10,6 +11,7 @@ mod grpc_error;
 mod peer_api_service;
 mod peer_service_error;
 
+pub use admin_api_service::AdminApiService;
 pub use attested_api_service::AttestedApiService;
 pub use blockchain_api_service::BlockchainApiService;
 pub use client_api_service::{ClientApiService, ClientSessionTracking};
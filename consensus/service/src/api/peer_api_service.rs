@@ -143,11 +143,14 @@ impl PeerApiService {
             PeerServiceError::InternalError
         })?;
 
-        // Handle each transaction.
-        for tx_context in tx_contexts {
-            let tx_hash = tx_context.tx_hash;
-
-            match self.tx_manager.insert(tx_context) {
+        // Check the transactions in parallel, then handle each result.
+        let tx_hashes = tx_contexts
+            .iter()
+            .map(|tx_context| tx_context.tx_hash)
+            .collect::<Vec<_>>();
+        let results = self.tx_manager.insert_many(tx_contexts);
+        for (tx_hash, result) in tx_hashes.into_iter().zip(results) {
+            match result {
                 Ok(tx_hash) => {
                     // Submit for consideration in next SCP slot.
                     (*self.scp_client_value_sender)(
@@ -5,6 +5,7 @@
 //! Entrypoint for the MobileCoin server.
 
 use mc_attest_verifier::DEBUG_ENCLAVE;
+use mc_blockchain_types::validate_block_metadata_labels;
 use mc_common::{
     logger::{create_app_logger, log, o},
     time::SystemTimeProvider,
@@ -30,6 +31,8 @@ use std::{
 fn main() -> Result<(), ConsensusServiceError> {
     let _sentry_guard = mc_common::sentry::init();
     let config = Config::parse();
+    validate_block_metadata_labels(&config.block_metadata_labels)
+        .expect("Invalid block metadata labels");
     let local_node_id = config.node_id();
     let fee_map = config.tokens().fee_map().expect("Could not parse fee map");
     let governors_map = config
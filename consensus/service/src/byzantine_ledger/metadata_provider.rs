@@ -10,12 +10,13 @@ use mc_ledger_sync::BlockMetadataProvider;
 use mc_sgx_report_cache_api::ReportableEnclave;
 
 /// A [BlockMetadataProvider] that builds metadata from the configured quorum
-/// set, enclave's AVR, and message signing key.
+/// set, enclave's AVR, message signing key and operator-defined labels.
 pub struct ConsensusMetadataProvider<E: ReportableEnclave> {
     responder_id: ResponderId,
     quorum_set: QuorumSet,
     enclave: E,
     msg_signer_key: Arc<Ed25519Pair>,
+    labels: Vec<String>,
 }
 
 impl<E: ReportableEnclave> ConsensusMetadataProvider<E> {
@@ -24,12 +25,14 @@ impl<E: ReportableEnclave> ConsensusMetadataProvider<E> {
         quorum_set: QuorumSet,
         enclave: E,
         msg_signer_key: Arc<Ed25519Pair>,
+        labels: Vec<String>,
     ) -> Self {
         Self {
             responder_id,
             quorum_set,
             enclave,
             msg_signer_key,
+            labels,
         }
     }
 }
@@ -47,7 +50,9 @@ impl<E: ReportableEnclave> BlockMetadataProvider for ConsensusMetadataProvider<E
             self.quorum_set.clone(),
             prost_evidence.into(),
             self.responder_id.clone(),
-        );
+        )
+        .with_labels(self.labels.clone())
+        .expect("invalid block metadata labels");
         Some(
             BlockMetadata::from_contents_and_keypair(contents, &self.msg_signer_key)
                 .expect("failed to sign metadata"),
@@ -16,7 +16,7 @@ use crate::{
     byzantine_ledger::{task_message::TaskMessage, worker::ByzantineLedgerWorker},
     counters,
     mint_tx_manager::{MintTxManager, MintTxManagerError},
-    tx_manager::{TxManager, TxManagerError},
+    tx_manager::{EvictTxOutcome, TxManager, TxManagerError},
 };
 use displaydoc::Display;
 use mc_common::{logger::Logger, NodeID, ResponderId};
@@ -29,13 +29,16 @@ use mc_ledger_sync::{LedgerSyncService, ReqwestTransactionsFetcher};
 use mc_peers::{
     Broadcast, ConsensusConnection, ConsensusMsg, ConsensusValue, VerifiedConsensusMsg,
 };
-use mc_transaction_core::mint::constants::{MAX_MINT_CONFIG_TXS_PER_BLOCK, MAX_MINT_TXS_PER_BLOCK};
+use mc_transaction_core::{
+    mint::constants::{MAX_MINT_CONFIG_TXS_PER_BLOCK, MAX_MINT_TXS_PER_BLOCK},
+    tx::TxHash,
+};
 use mc_util_metered_channel::Sender;
 use std::{
     path::PathBuf,
     sync::{
         atomic::{AtomicBool, AtomicU64, Ordering},
-        Arc, Mutex,
+        mpsc, Arc, Mutex,
     },
     thread,
     thread::JoinHandle,
@@ -124,6 +127,7 @@ impl ByzantineLedger {
         mint_tx_manager: Arc<MTXM>,
         broadcaster: Arc<Mutex<dyn Broadcast>>,
         msg_signer_key: Arc<Ed25519Pair>,
+        block_metadata_labels: Vec<String>,
         tx_source_urls: Vec<String>,
         scp_debug_dir: Option<PathBuf>,
         logger: Logger,
@@ -220,6 +224,7 @@ impl ByzantineLedger {
                     quorum_set,
                     enclave.clone(),
                     msg_signer_key.clone(),
+                    block_metadata_labels.clone(),
                 ),
                 ledger.clone(),
                 peer_manager.clone(),
@@ -231,6 +236,7 @@ impl ByzantineLedger {
                 enclave,
                 scp_node,
                 msg_signer_key,
+                block_metadata_labels,
                 ledger,
                 ledger_sync_service,
                 peer_manager,
@@ -284,6 +290,17 @@ impl ByzantineLedger {
             .expect("Could not send consensus msg");
     }
 
+    /// Evict a transaction from the tx manager's cache and the pending values,
+    /// unless the current slot may externalize it. Returns None if the worker
+    /// has stopped.
+    pub fn evict_tx(&self, tx_hash: TxHash) -> Option<EvictTxOutcome> {
+        let (sender, receiver) = mpsc::sync_channel(1);
+        self.task_sender
+            .send(TaskMessage::EvictTx(tx_hash, sender))
+            .ok()?;
+        receiver.recv().ok()
+    }
+
     pub fn stop(&mut self) {
         let _ = self.task_sender.send(TaskMessage::StopTrigger);
         self.join();
@@ -481,6 +498,7 @@ mod tests {
             broadcaster,
             msg_signer_key,
             Vec::new(),
+            Vec::new(),
             None,
             logger,
         );
@@ -574,6 +592,7 @@ mod tests {
             broadcaster,
             local_signer_key.clone(),
             Vec::new(),
+            Vec::new(),
             None,
             logger,
         );
@@ -957,6 +976,7 @@ mod tests {
             broadcaster,
             local_signer_key.clone(),
             Vec::new(),
+            Vec::new(),
             None,
             logger,
         );
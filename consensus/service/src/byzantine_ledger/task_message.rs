@@ -1,8 +1,10 @@
 // Copyright (c) 2018-2022 The MobileCoin Foundation
 
+use crate::tx_manager::EvictTxOutcome;
 use mc_common::ResponderId;
 use mc_peers::{ConsensusValue, VerifiedConsensusMsg};
-use std::time::Instant;
+use mc_transaction_core::tx::TxHash;
+use std::{sync::mpsc::SyncSender, time::Instant};
 
 #[derive(Debug)]
 pub enum TaskMessage {
@@ -14,6 +16,10 @@ pub enum TaskMessage {
     /// SCP Statement.
     ConsensusMsg(VerifiedConsensusMsg, ResponderId),
 
+    /// An operator's request to evict a transaction, and where to send the
+    /// outcome.
+    EvictTx(TxHash, SyncSender<EvictTxOutcome>),
+
     /// Stop trigger, used for notifying the worker thread to terminate.
     StopTrigger,
 }
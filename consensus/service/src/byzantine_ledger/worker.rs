@@ -7,7 +7,7 @@ use crate::{
     },
     counters,
     mint_tx_manager::MintTxManager,
-    tx_manager::TxManager,
+    tx_manager::{EvictTxOutcome, TxManager},
 };
 use mc_attest_verifier_types::prost;
 use mc_blockchain_types::{BlockData, BlockID, BlockMetadata, BlockMetadataContents};
@@ -31,10 +31,9 @@ use mc_peers::{
 use mc_transaction_core::tx::TxHash;
 use mc_util_metered_channel::Receiver;
 use mc_util_telemetry::{mark_span_as_active, start_block_span, tracer, Tracer};
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use std::{
     cmp::min,
-    collections::{BTreeSet, HashMap},
+    collections::{BTreeSet, HashMap, HashSet},
     sync::{
         atomic::{AtomicBool, AtomicU64, Ordering},
         Arc, Mutex,
@@ -63,6 +62,9 @@ pub struct ByzantineLedgerWorker<
     // SCP message signing key.
     msg_signer_key: Arc<Ed25519Pair>,
 
+    // Operator-defined labels for the metadata of blocks this node externalizes.
+    block_metadata_labels: Vec<String>,
+
     // Peer connections manager.
     connection_manager: ConnectionManager<PC>,
 
@@ -112,6 +114,11 @@ pub struct ByzantineLedgerWorker<
     // scp_node.
     need_nominate: bool,
 
+    // Hashes of the transactions the current slot may externalize: the ones this node proposed,
+    // and the ones in messages it handled for the slot. These must stay in the tx_manager's cache
+    // until the slot completes, since forming the block needs them.
+    current_slot_tx_hashes: HashSet<TxHash>,
+
     logger: Logger,
 }
 
@@ -129,6 +136,8 @@ impl<
     /// # Arguments
     /// * `scp_node` - The local SCP Node.
     /// * `msg_signer_key` - Signs consensus messages issued by this node.
+    /// * `block_metadata_labels` - Operator-defined labels for the metadata of
+    ///   blocks this node externalizes.
     /// * `ledger` - This node's ledger.
     /// * `ledger_sync_service` - LedgerSyncService
     /// * `connection_manager` - Manages connections to peers.
@@ -149,6 +158,7 @@ impl<
         enclave: E,
         scp_node: Box<dyn ScpNode<ConsensusValue>>,
         msg_signer_key: Arc<Ed25519Pair>,
+        block_metadata_labels: Vec<String>,
         ledger: L,
         ledger_sync_service: LS,
         connection_manager: ConnectionManager<PC>,
@@ -170,6 +180,7 @@ impl<
             tasks,
             scp_node,
             msg_signer_key,
+            block_metadata_labels,
             is_behind,
             highest_peer_block,
             highest_issued_msg,
@@ -183,6 +194,7 @@ impl<
             pending_consensus_msgs: Default::default(),
             pending_values: PendingValues::new(tx_manager, mint_tx_manager),
             need_nominate: false,
+            current_slot_tx_hashes: HashSet::default(),
             network_state,
             ledger_sync_service,
             ledger_sync_state: LedgerSyncState::InSync,
@@ -249,6 +261,7 @@ impl<
                 );
 
                 self.scp_node.reset_slot_index(self.current_slot_index);
+                self.current_slot_tx_hashes.clear();
                 // Clear any pending values that might no longer be valid.
                 self.pending_values.clear_invalid_values();
                 if !self.pending_values.is_empty() {
@@ -414,6 +427,13 @@ impl<
                         .push((consensus_msg, from_responder_id));
                 }
 
+                // Operator request to evict a transaction
+                TaskMessage::EvictTx(tx_hash, outcome_sender) => {
+                    let outcome = self.evict_tx(&tx_hash);
+                    // The requester may have given up waiting.
+                    let _ = outcome_sender.send(outcome);
+                }
+
                 // Request to stop thread
                 TaskMessage::StopTrigger => {
                     return false;
@@ -429,6 +449,22 @@ impl<
         true
     }
 
+    // Evict a transaction from the tx_manager's cache and the pending values,
+    // unless the current slot may externalize it.
+    fn evict_tx(&mut self, tx_hash: &TxHash) -> EvictTxOutcome {
+        if self.current_slot_tx_hashes.contains(tx_hash) {
+            return EvictTxOutcome::InCurrentSlot;
+        }
+
+        let value = ConsensusValue::TxHash(*tx_hash);
+        self.pending_values.retain(|pending| *pending != value);
+        if self.tx_manager.evict(tx_hash) {
+            EvictTxOutcome::Evicted
+        } else {
+            EvictTxOutcome::NotFound
+        }
+    }
+
     // Propose pending values for nomination in the current slot.
     fn propose_pending_values(&mut self) {
         assert!(!self.pending_values.is_empty());
@@ -436,12 +472,13 @@ impl<
         // Fairness heuristics:
         // * Values are proposed in the order that they were received.
         // * Each node limits the total number of values it proposes per slot.
-        let values = self
+        let values: BTreeSet<ConsensusValue> = self
             .pending_values
             .iter()
             .take(MAX_PENDING_VALUES_TO_NOMINATE)
             .cloned()
             .collect();
+        self.track_current_slot_values(&values);
 
         let msg_opt = self
             .scp_node
@@ -524,6 +561,11 @@ impl<
                 .into_iter()
                 .map(|(consensus_msg, _)| consensus_msg.scp_msg().clone())
                 .collect();
+            for scp_msg in &scp_msgs {
+                if scp_msg.slot_index == current_slot_index {
+                    self.track_current_slot_values(&scp_msg.values());
+                }
+            }
 
             match self.scp_node.handle_messages(scp_msgs) {
                 Ok(outgoing_msgs) => {
@@ -538,6 +580,15 @@ impl<
         }
     }
 
+    // Remember the transactions among values that the current slot may externalize.
+    fn track_current_slot_values(&mut self, values: &BTreeSet<ConsensusValue>) {
+        self.current_slot_tx_hashes
+            .extend(values.iter().filter_map(|value| match value {
+                ConsensusValue::TxHash(tx_hash) => Some(*tx_hash),
+                _ => None,
+            }));
+    }
+
     fn complete_current_slot(&mut self, externalized: Vec<ConsensusValue>) {
         let tracer = tracer!();
 
@@ -588,6 +639,7 @@ impl<
         counters::TX_EXTERNALIZED_COUNT.inc_by(externalized.len() as u64);
 
         // Update current slot index.
+        self.current_slot_tx_hashes.clear();
         self.current_slot_index = {
             let current_slot_index: SlotIndex = self.ledger.num_blocks().unwrap();
             assert_eq!(current_slot_index, self.current_slot_index + 1);
@@ -716,21 +768,16 @@ impl<
                         );
                         return false;
                     }
-                    tx_contexts.into_par_iter().for_each_with(
-                        (self.tx_manager.clone(), self.logger.clone()),
-                        move |(tx_manager, logger), tx_context| match tx_manager.insert(tx_context)
-                        {
-                            Ok(_) => {}
-                            Err(err) => {
-                                log::crit!(
-                                    logger,
-                                    "Received malformed transaction from node {}: {:?}",
-                                    from_responder_id,
-                                    err,
-                                );
-                            }
-                        },
-                    );
+                    for result in self.tx_manager.insert_many(tx_contexts) {
+                        if let Err(err) = result {
+                            log::crit!(
+                                self.logger,
+                                "Received malformed transaction from node {}: {:?}",
+                                from_responder_id,
+                                err,
+                            );
+                        }
+                    }
                 }
                 Err(RetryError {
                     error: PeerError::TxHashesNotInCache(tx_hashes),
@@ -899,7 +946,9 @@ impl<
             self.scp_node.quorum_set(),
             prost_evidence.into(),
             self.scp_node.node_id().responder_id,
-        );
+        )
+        .with_labels(self.block_metadata_labels.clone())
+        .unwrap_or_else(|err| panic!("Invalid block metadata labels: {err}"));
 
         BlockMetadata::from_contents_and_keypair(contents, &self.msg_signer_key).unwrap_or_else(
             |err| panic!("Failed to sign block metadata for block {block_id:?}: {err}"),
@@ -1032,6 +1081,7 @@ mod tests {
             enclave,
             Box::new(scp_node),
             msg_signer_key,
+            Vec::new(),
             ledger,
             ledger_sync,
             connection_manager,
@@ -1084,6 +1134,7 @@ mod tests {
             enclave,
             Box::new(scp_node),
             msg_signer_key,
+            Vec::new(),
             ledger,
             ledger_sync,
             connection_manager,
@@ -1201,6 +1252,7 @@ mod tests {
             enclave,
             Box::new(scp_node),
             msg_signer_key,
+            Vec::new(),
             ledger,
             ledger_sync,
             connection_manager,
@@ -1262,6 +1314,7 @@ mod tests {
             enclave,
             Box::new(scp_node),
             msg_signer_key,
+            Vec::new(),
             ledger,
             ledger_sync,
             connection_manager,
@@ -1363,6 +1416,7 @@ mod tests {
             enclave,
             Box::new(scp_node),
             msg_signer_key,
+            Vec::new(),
             ledger,
             ledger_sync,
             connection_manager,
@@ -1449,6 +1503,7 @@ mod tests {
             enclave,
             Box::new(scp_node),
             msg_signer_key,
+            Vec::new(),
             ledger,
             ledger_sync,
             connection_manager,
@@ -1546,6 +1601,7 @@ mod tests {
             enclave,
             Box::new(scp_node),
             msg_signer_key,
+            Vec::new(),
             ledger,
             ledger_sync,
             connection_manager,
@@ -1573,6 +1629,106 @@ mod tests {
         worker.propose_pending_values();
     }
 
+    #[test_with_logger]
+    // Eviction should drop a transaction from the pending values and the
+    // tx_manager, but leave alone transactions the current slot may externalize.
+    fn test_evict_tx(logger: Logger) {
+        let (node_id, _local_node_uri, msg_signer_key) = get_local_node_config(11);
+        let mut rng: StdRng = SeedableRng::from_seed([97u8; 32]);
+        let peers = get_peers(&[22, 33], &mut rng);
+        let quorum_set =
+            QuorumSet::new_with_node_ids(2, vec![peers[0].id.clone(), peers[1].id.clone()]);
+
+        let num_blocks = 12;
+        let (
+            enclave,
+            mut scp_node,
+            ledger,
+            ledger_sync,
+            mut tx_manager,
+            mint_tx_manager,
+            broadcast,
+        ) = get_mocks(&node_id, &quorum_set, num_blocks);
+        let connection_manager = get_connection_manager(&node_id, &peers, &logger);
+        let (task_sender, task_receiver) = get_channel();
+
+        let nominated = TxHash([1u8; 32]);
+        let pending = TxHash([2u8; 32]);
+        let unknown = TxHash([3u8; 32]);
+
+        tx_manager.expect_validate().return_const(Ok(()));
+        scp_node.expect_propose_values().return_const(Ok(None));
+        // The nominated transaction must not be evicted.
+        tx_manager
+            .expect_evict()
+            .with(eq(pending))
+            .times(1)
+            .return_const(true);
+        tx_manager
+            .expect_evict()
+            .with(eq(unknown))
+            .times(1)
+            .return_const(false);
+
+        let mut worker = ByzantineLedgerWorker::new(
+            enclave,
+            Box::new(scp_node),
+            msg_signer_key,
+            Vec::new(),
+            ledger,
+            ledger_sync,
+            connection_manager,
+            Arc::new(tx_manager),
+            Arc::new(mint_tx_manager),
+            Arc::new(Mutex::new(broadcast)),
+            task_receiver,
+            Arc::new(AtomicBool::new(false)),
+            Arc::new(AtomicU64::new(0)),
+            Arc::new(Mutex::new(Option::<ConsensusMsg>::None)),
+            logger,
+        );
+
+        worker.pending_values.push(nominated.into(), None);
+        worker.propose_pending_values();
+        worker.pending_values.push(pending.into(), None);
+
+        let mut evict = |tx_hash| {
+            let (sender, receiver) = std::sync::mpsc::sync_channel(1);
+            task_sender
+                .send(TaskMessage::EvictTx(tx_hash, sender))
+                .unwrap();
+            assert!(worker.receive_tasks());
+            let outcome = receiver.recv().unwrap();
+            let pending_values: Vec<_> = worker.pending_values.iter().cloned().collect();
+            (outcome, pending_values)
+        };
+
+        assert_eq!(
+            evict(nominated),
+            (
+                EvictTxOutcome::InCurrentSlot,
+                vec![
+                    ConsensusValue::TxHash(nominated),
+                    ConsensusValue::TxHash(pending)
+                ]
+            )
+        );
+        assert_eq!(
+            evict(pending),
+            (
+                EvictTxOutcome::Evicted,
+                vec![ConsensusValue::TxHash(nominated)]
+            )
+        );
+        assert_eq!(
+            evict(unknown),
+            (
+                EvictTxOutcome::NotFound,
+                vec![ConsensusValue::TxHash(nominated)]
+            )
+        );
+    }
+
     #[test_with_logger]
     fn test_complete_current_slot_forms_block_successfully(logger: Logger) {
         let mut rng: StdRng = SeedableRng::from_seed([77u8; 32]);
@@ -1695,6 +1851,7 @@ mod tests {
             enclave,
             Box::new(scp_node),
             msg_signer_key,
+            Vec::new(),
             ledger.clone(),
             ledger_sync,
             connection_manager,
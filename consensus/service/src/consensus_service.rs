@@ -4,15 +4,15 @@
 
 use crate::{
     api::{
-        AttestedApiService, BlockchainApiService, ClientApiService, ClientSessionTracking,
-        PeerApiService,
+        AdminApiService, AttestedApiService, BlockchainApiService, ClientApiService,
+        ClientSessionTracking, PeerApiService,
     },
     background_work_queue::BackgroundWorkQueue,
     byzantine_ledger::ByzantineLedger,
     counters,
     mint_tx_manager::MintTxManager,
     peer_keepalive::PeerKeepalive,
-    tx_manager::TxManager,
+    tx_manager::{EvictTxOutcome, TxManager},
 };
 use base64::{engine::general_purpose::URL_SAFE as URL_SAFE_BASE64_ENGINE, Engine};
 use displaydoc::Display;
@@ -26,13 +26,16 @@ use mc_common::{
     LruCache, NodeID, ResponderId,
 };
 use mc_connection::{Connection, ConnectionManager};
-use mc_consensus_api::{consensus_client_grpc, consensus_common_grpc, consensus_peer_grpc};
+use mc_consensus_api::{
+    consensus_admin_grpc, consensus_client_grpc, consensus_common_grpc, consensus_peer_grpc,
+};
 use mc_consensus_enclave::{ConsensusEnclave, Error as ConsensusEnclaveError};
 use mc_consensus_service_config::{Config, Error as ConfigError};
 use mc_crypto_keys::DistinguishedEncoding;
 use mc_ledger_db::{Error as LedgerDbError, Ledger, LedgerDB};
 use mc_peers::{ConsensusValue, PeerConnection, ThreadedBroadcaster, VerifiedConsensusMsg};
 use mc_sgx_report_cache_untrusted::{Error as ReportCacheError, ReportCacheThread};
+use mc_transaction_core::tx::TxHash;
 use mc_util_grpc::{
     AdminServer, AnonymousAuthenticator, Authenticator, BuildInfoService,
     ConnectionUriGrpcioServer, GetConfigJsonFn, HealthCheckStatus, HealthService,
@@ -105,6 +108,10 @@ pub struct IncomingConsensusMsg {
 pub type ProposeTxCallback =
     Arc<dyn Fn(ConsensusValue, Option<&NodeID>, Option<&ResponderId>) + Sync + Send>;
 
+/// A callback for evicting a transaction through ByzantineLedger, which returns
+/// None if ByzantineLedger is not running.
+pub type EvictTxCallback = Arc<dyn Fn(TxHash) -> Option<EvictTxOutcome> + Sync + Send>;
+
 pub struct ConsensusService<
     E: ConsensusEnclave + Clone + Send + Sync + 'static,
     TXM: TxManager + Clone + Send + Sync + 'static,
@@ -410,6 +417,13 @@ impl<
 
     fn start_admin_rpc_server(&mut self) -> Result<(), ConsensusServiceError> {
         if let Some(admin_listen_uri) = self.config.admin_listen_uri.as_ref() {
+            let consensus_admin_service =
+                consensus_admin_grpc::create_consensus_admin_api(AdminApiService::new(
+                    self.tx_manager.clone(),
+                    self.create_evict_tx_fn(),
+                    self.logger.clone(),
+                ));
+
             self.admin_rpc_server = Some(
                 AdminServer::start(
                     Some(self.env.clone()),
@@ -417,7 +431,7 @@ impl<
                     "Consensus Service".to_owned(),
                     self.config.peer_responder_id.to_string(),
                     Some(self.create_get_config_json_fn()),
-                    vec![],
+                    vec![consensus_admin_service],
                     self.logger.clone(),
                 )
                 .expect("Failed starting admin grpc server"),
@@ -525,6 +539,7 @@ impl<
                 self.mint_tx_manager.clone(),
                 self.broadcaster.clone(),
                 self.config.msg_signer_key.clone(),
+                self.config.block_metadata_labels.clone(),
                 self.config.network().tx_source_urls,
                 self.config.scp_debug_dump.clone(),
                 self.logger.clone(),
@@ -614,8 +629,8 @@ impl<
                         Err(_e) => {
                             log::warn!(
                                 self.logger,
-                                "Could not get responder_id from {:?}",
-                                uri.to_string()
+                                "Could not get responder_id from {}",
+                                uri.redacted()
                             );
                             None
                         }
@@ -683,6 +698,22 @@ impl<
         })
     }
 
+    /// Creates a function that evicts a transaction through ByzantineLedger,
+    /// which knows whether the current slot may still externalize it.
+    fn create_evict_tx_fn(&self) -> EvictTxCallback {
+        let byzantine_ledger = self
+            .byzantine_ledger
+            .as_ref()
+            .map(Arc::downgrade)
+            .expect("Server was not initialized");
+
+        Arc::new(move |tx_hash| {
+            byzantine_ledger
+                .upgrade()
+                .and_then(|ledger| ledger.get().and_then(|ledger| ledger.evict_tx(tx_hash)))
+        })
+    }
+
     /// Helper method for creating the get config json function needed by the
     /// GRPC admin service.
     fn create_get_config_json_fn(&self) -> GetConfigJsonFn {
@@ -12,7 +12,7 @@ use crate::counters;
 use mc_attest_enclave_api::{EnclaveMessage, PeerSession};
 use mc_common::{
     logger::{log, Logger},
-    HashMap, HashSet,
+    HashMap, HashSet, LruCache,
 };
 use mc_consensus_enclave::{
     ConsensusEnclave, TxContext, WellFormedEncryptedTx, WellFormedTxContext,
@@ -21,7 +21,11 @@ use mc_transaction_core::{
     constants::MAX_TRANSACTIONS_PER_BLOCK,
     tx::{TxHash, TxOutMembershipProof},
 };
-use std::sync::{Arc, Mutex, MutexGuard};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use std::{
+    sync::{Arc, Mutex, MutexGuard},
+    time::SystemTime,
+};
 
 mod error;
 mod tx_manager_trait;
@@ -34,6 +38,33 @@ pub use untrusted_interfaces::UntrustedInterfaces;
 #[cfg(test)]
 pub use tx_manager_trait::MockTxManager;
 
+/// How many rejected transactions to remember the reason for.
+pub const MAX_RECENT_REJECTIONS: usize = 10_000;
+
+/// Why, and when, a transaction was rejected.
+#[derive(Clone, Debug)]
+pub struct TxRejection {
+    /// The error the transaction was rejected with.
+    pub error: TxManagerError,
+
+    /// When the transaction was rejected.
+    pub rejected_at: SystemTime,
+}
+
+/// The outcome of an operator's request to evict a transaction.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EvictTxOutcome {
+    /// The transaction was removed from the cache and the pending values.
+    Evicted,
+
+    /// The transaction was not in the cache.
+    NotFound,
+
+    /// The transaction was left in the cache, because the slot being agreed
+    /// on may externalize it, and forming the block needs it.
+    InCurrentSlot,
+}
+
 struct CacheEntry {
     /// An encrypted transaction that has been found to be well-formed.
     encrypted_tx: WellFormedEncryptedTx,
@@ -64,6 +95,9 @@ pub struct TxManagerImpl<E: ConsensusEnclave + Send, UI: UntrustedInterfaces + S
     /// Well-formed transactions, keyed by hash.
     cache: Arc<Mutex<HashMap<TxHash, CacheEntry>>>,
 
+    /// The most recent rejections, keyed by transaction hash.
+    recent_rejections: Arc<Mutex<LruCache<TxHash, TxRejection>>>,
+
     /// Logger.
     logger: Logger,
 }
@@ -76,6 +110,7 @@ impl<E: ConsensusEnclave + Send, UI: UntrustedInterfaces + Send> TxManagerImpl<E
             untrusted,
             logger,
             cache: Arc::new(Mutex::new(HashMap::default())),
+            recent_rejections: Arc::new(Mutex::new(LruCache::new(MAX_RECENT_REJECTIONS))),
         }
     }
 
@@ -106,6 +141,17 @@ impl<E: ConsensusEnclave + Send, UI: UntrustedInterfaces + Send> TxManagerImpl<E
         self.cache.lock().expect("Lock poisoned")
     }
 
+    /// Remember that a transaction was rejected, and why.
+    fn record_rejection(&self, tx_hash: TxHash, error: &TxManagerError) {
+        self.recent_rejections.lock().expect("Lock poisoned").put(
+            tx_hash,
+            TxRejection {
+                error: error.clone(),
+                rejected_at: SystemTime::now(),
+            },
+        );
+    }
+
     /// A utility method for resolving a list of TxHashes into CacheEntries that
     /// errors if any hashes are missing.
     fn get_cache_entries<'a, 'b, I>(
@@ -139,7 +185,7 @@ impl<E: ConsensusEnclave + Send, UI: UntrustedInterfaces + Send> TxManagerImpl<E
     }
 }
 
-impl<E: ConsensusEnclave + Send, UI: UntrustedInterfaces + Send> TxManager
+impl<E: ConsensusEnclave + Send + Sync, UI: UntrustedInterfaces + Send> TxManager
     for TxManagerImpl<E, UI>
 {
     /// Insert a transaction into the cache. The transaction must be
@@ -155,7 +201,10 @@ impl<E: ConsensusEnclave + Send, UI: UntrustedInterfaces + Send> TxManager
             }
         }
 
-        let new_entry = self.is_well_formed(tx_context)?;
+        let new_entry = self.is_well_formed(tx_context).map_err(|err| {
+            self.record_rejection(tx_hash, &err);
+            err
+        })?;
 
         {
             let mut cache = self.lock_cache();
@@ -172,6 +221,71 @@ impl<E: ConsensusEnclave + Send, UI: UntrustedInterfaces + Send> TxManager
         Ok(tx_hash)
     }
 
+    /// Insert several transactions into the cache, checking them for
+    /// well-formedness in parallel. Returns the result of each insert, in the
+    /// order of `tx_contexts`.
+    fn insert_many(&self, tx_contexts: Vec<TxContext>) -> Vec<TxManagerResult<TxHash>> {
+        let mut results: Vec<Option<TxManagerResult<TxHash>>> = vec![None; tx_contexts.len()];
+
+        // Skip transactions which are already cached, and only check each new
+        // transaction once.
+        let mut to_check = Vec::new();
+        let mut duplicates = Vec::new();
+        {
+            let cache = self.lock_cache();
+            let mut first_seen = HashMap::<TxHash, usize>::default();
+            for (i, tx_context) in tx_contexts.into_iter().enumerate() {
+                if cache.contains_key(&tx_context.tx_hash) {
+                    results[i] = Some(Ok(tx_context.tx_hash));
+                } else if let Some(first) = first_seen.get(&tx_context.tx_hash) {
+                    duplicates.push((i, *first));
+                } else {
+                    first_seen.insert(tx_context.tx_hash, i);
+                    to_check.push((i, tx_context));
+                }
+            }
+        }
+
+        // The checks of different transactions are independent, so they are
+        // spread over rayon's work-stealing pool. This overlaps the untrusted
+        // ledger reads (membership proofs, ring members) of some transactions
+        // with the enclave checks of others.
+        let checked = to_check
+            .into_par_iter()
+            .map(|(i, tx_context)| (i, tx_context.tx_hash, self.is_well_formed(tx_context)))
+            .collect::<Vec<_>>();
+
+        let mut rejections = Vec::new();
+        {
+            let mut cache = self.lock_cache();
+            for (i, tx_hash, result) in checked {
+                results[i] = Some(match result {
+                    Ok(new_entry) => {
+                        cache.insert(tx_hash, new_entry);
+                        Ok(tx_hash)
+                    }
+                    Err(err) => {
+                        rejections.push((tx_hash, err.clone()));
+                        Err(err)
+                    }
+                });
+            }
+            counters::TX_CACHE_NUM_ENTRIES.set(cache.len() as i64);
+        }
+        for (tx_hash, err) in rejections {
+            self.record_rejection(tx_hash, &err);
+        }
+
+        for (i, first) in duplicates {
+            results[i] = results[first].clone();
+        }
+
+        results
+            .into_iter()
+            .map(|result| result.expect("every transaction has a result"))
+            .collect()
+    }
+
     /// Remove expired transactions from the cache and return their hashes.
     ///
     /// # Arguments
@@ -224,8 +338,11 @@ impl<E: ConsensusEnclave + Send, UI: UntrustedInterfaces + Send> TxManager
 
         if let Some(context) = context_opt {
             let _timer = counters::VALIDATE_TX_TIME.start_timer();
-            self.untrusted.is_valid(context)?;
-            Ok(())
+            self.untrusted.is_valid(context).map_err(|err| {
+                let err = TxManagerError::from(err);
+                self.record_rejection(*tx_hash, &err);
+                err
+            })
         } else {
             log::warn!(
                 self.logger,
@@ -330,6 +447,36 @@ impl<E: ConsensusEnclave + Send, UI: UntrustedInterfaces + Send> TxManager
             .get(tx_hash)
             .map(|entry| entry.encrypted_tx().clone())
     }
+
+    /// The contexts of all cached transactions, in no particular order.
+    fn pending_txs(&self) -> Vec<Arc<WellFormedTxContext>> {
+        self.lock_cache()
+            .values()
+            .map(|entry| entry.context.clone())
+            .collect()
+    }
+
+    /// Remove a transaction from the cache, returning true if it was present.
+    fn evict(&self, tx_hash: &TxHash) -> bool {
+        let mut cache = self.lock_cache();
+        let evicted = cache.remove(tx_hash).is_some();
+        counters::TX_CACHE_NUM_ENTRIES.set(cache.len() as i64);
+
+        if evicted {
+            log::info!(self.logger, "Evicted transaction {}", tx_hash);
+        }
+        evicted
+    }
+
+    /// Why the transaction with the given hash was most recently rejected, if
+    /// it was rejected recently enough to be remembered.
+    fn recent_rejection(&self, tx_hash: &TxHash) -> Option<TxRejection> {
+        self.recent_rejections
+            .lock()
+            .expect("Lock poisoned")
+            .peek(tx_hash)
+            .cloned()
+    }
 }
 
 #[cfg(test)]
@@ -337,8 +484,14 @@ mod tests {
     use super::*;
     use crate::tx_manager::untrusted_interfaces::MockUntrustedInterfaces;
     use mc_common::logger::test_with_logger;
+    use mc_consensus_enclave::LocallyEncryptedTx;
     use mc_consensus_enclave_mock::{Error as EnclaveError, MockConsensusEnclave};
     use mc_transaction_core::validation::TransactionValidationError;
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::{Duration, Instant},
+    };
+    use test::Bencher;
 
     #[test_with_logger]
     // Should return Ok when a well-formed Tx is inserted.
@@ -445,8 +598,20 @@ mod tests {
         let mock_enclave = MockConsensusEnclave::new();
 
         let tx_manager = TxManagerImpl::new(mock_enclave, mock_untrusted, logger);
-        assert!(tx_manager.insert(tx_context).is_err());
+        assert!(tx_manager.insert(tx_context.clone()).is_err());
         assert_eq!(tx_manager.num_entries(), 0);
+
+        // The rejection should be remembered.
+        match tx_manager.recent_rejection(&tx_context.tx_hash) {
+            Some(TxRejection {
+                error:
+                    TxManagerError::TransactionValidation(
+                        TransactionValidationError::ContainsSpentKeyImage,
+                    ),
+                ..
+            }) => {} // This is expected.
+            other => panic!("unexpected rejection {other:?}"),
+        }
     }
 
     #[test_with_logger]
@@ -536,6 +701,44 @@ mod tests {
         }
     }
 
+    #[test_with_logger]
+    // Evict should remove only the given transaction.
+    fn test_evict(logger: Logger) {
+        let mock_untrusted = MockUntrustedInterfaces::new();
+        let mock_enclave = MockConsensusEnclave::new();
+        let tx_manager = TxManagerImpl::new(mock_enclave, mock_untrusted, logger);
+
+        let tx_hashes: Vec<_> = (0..3u8).map(|i| TxHash([i; 32])).collect();
+        for (priority, tx_hash) in tx_hashes.iter().enumerate() {
+            let context = WellFormedTxContext::new(
+                priority as u64,
+                *tx_hash,
+                10,
+                Default::default(),
+                Default::default(),
+                Default::default(),
+            );
+            let cache_entry = CacheEntry {
+                encrypted_tx: Default::default(),
+                context: Arc::new(context),
+            };
+            tx_manager.lock_cache().insert(*tx_hash, cache_entry);
+        }
+        assert_eq!(tx_manager.pending_txs().len(), 3);
+
+        assert!(tx_manager.evict(&tx_hashes[1]));
+        assert!(!tx_manager.evict(&tx_hashes[1]));
+        assert!(!tx_manager.contains(&tx_hashes[1]));
+
+        let mut pending: Vec<_> = tx_manager
+            .pending_txs()
+            .iter()
+            .map(|context| (*context.tx_hash(), context.priority()))
+            .collect();
+        pending.sort();
+        assert_eq!(pending, vec![(tx_hashes[0], 0), (tx_hashes[2], 2)]);
+    }
+
     #[test_with_logger]
     // Should return Ok if the transaction is in the cache and is valid.
     fn test_validate_ok(logger: Logger) {
@@ -975,4 +1178,170 @@ mod tests {
         }
         assert_eq!(tx_manager.num_entries(), tx_hashes.len());
     }
+
+    /// A transaction context whose locally encrypted tx is its hash, so that
+    /// a mock enclave can tell transactions apart.
+    fn tx_context_with_hash(tx_hash: TxHash) -> TxContext {
+        TxContext {
+            locally_encrypted_tx: LocallyEncryptedTx(tx_hash.0.to_vec()),
+            tx_hash,
+            ..Default::default()
+        }
+    }
+
+    /// A mock enclave which finds every transaction well-formed.
+    fn accepting_enclave() -> MockConsensusEnclave {
+        let mut mock_enclave = MockConsensusEnclave::new();
+        mock_enclave
+            .expect_tx_is_well_formed()
+            .returning(|locally_encrypted_tx, _, _| {
+                let tx_hash = TxHash(locally_encrypted_tx.0.clone().try_into().unwrap());
+                Ok((
+                    WellFormedEncryptedTx(locally_encrypted_tx.0),
+                    WellFormedTxContext::new(
+                        0,
+                        tx_hash,
+                        Default::default(),
+                        Default::default(),
+                        Default::default(),
+                        Default::default(),
+                    ),
+                ))
+            });
+        mock_enclave
+    }
+
+    /// A mock untrusted interface whose well-formed check takes `delay`, and
+    /// which records the most checks it saw running at once.
+    fn slow_untrusted(
+        delay: Duration,
+        max_concurrent: Arc<AtomicUsize>,
+    ) -> MockUntrustedInterfaces {
+        let running = Arc::new(AtomicUsize::new(0));
+        let mut mock_untrusted = MockUntrustedInterfaces::new();
+        mock_untrusted
+            .expect_well_formed_check()
+            .returning(move |_| {
+                let now_running = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_concurrent.fetch_max(now_running, Ordering::SeqCst);
+                std::thread::sleep(delay);
+                running.fetch_sub(1, Ordering::SeqCst);
+                Ok((0, vec![]))
+            });
+        mock_untrusted
+    }
+
+    #[test_with_logger]
+    // Should check new transactions once each, and return results in order.
+    fn test_insert_many(logger: Logger) {
+        let cached = TxHash([1u8; 32]);
+        let new = TxHash([2u8; 32]);
+        let malformed = TxHash([3u8; 32]);
+
+        let mut mock_untrusted = MockUntrustedInterfaces::new();
+        // Called once for `new` and once for `malformed`.
+        mock_untrusted
+            .expect_well_formed_check()
+            .times(2)
+            .returning(move |tx_context| {
+                if tx_context.tx_hash == malformed {
+                    Err(TransactionValidationError::ContainsSpentKeyImage)
+                } else {
+                    Ok((0, vec![]))
+                }
+            });
+
+        let tx_manager = TxManagerImpl::new(accepting_enclave(), mock_untrusted, logger);
+        tx_manager.lock_cache().insert(
+            cached,
+            CacheEntry {
+                encrypted_tx: Default::default(),
+                context: Arc::new(WellFormedTxContext::new(
+                    0,
+                    cached,
+                    Default::default(),
+                    Default::default(),
+                    Default::default(),
+                    Default::default(),
+                )),
+            },
+        );
+
+        let results = tx_manager.insert_many(
+            [new, cached, malformed, new]
+                .into_iter()
+                .map(tx_context_with_hash)
+                .collect(),
+        );
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].as_ref().ok(), Some(&new));
+        assert_eq!(results[1].as_ref().ok(), Some(&cached));
+        assert!(matches!(
+            results[2],
+            Err(TxManagerError::TransactionValidation(
+                TransactionValidationError::ContainsSpentKeyImage
+            ))
+        ));
+        assert_eq!(results[3].as_ref().ok(), Some(&new));
+
+        assert_eq!(tx_manager.num_entries(), 2);
+        assert!(tx_manager.contains(&new));
+        assert!(tx_manager.recent_rejection(&malformed).is_some());
+    }
+
+    #[test_with_logger]
+    // A burst of transactions should be checked concurrently.
+    fn test_insert_many_load(logger: Logger) {
+        const NUM_TXS: usize = 256;
+        let max_concurrent = Arc::new(AtomicUsize::new(0));
+        let tx_manager = TxManagerImpl::new(
+            accepting_enclave(),
+            slow_untrusted(Duration::from_millis(5), max_concurrent.clone()),
+            logger,
+        );
+
+        let tx_contexts = (0..NUM_TXS)
+            .map(|i| {
+                let mut hash = [0u8; 32];
+                hash[..8].copy_from_slice(&(i as u64).to_le_bytes());
+                tx_context_with_hash(TxHash(hash))
+            })
+            .collect();
+
+        let start = Instant::now();
+        let results = tx_manager.insert_many(tx_contexts);
+        let elapsed = start.elapsed();
+
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(tx_manager.num_entries(), NUM_TXS);
+
+        let num_threads = rayon::current_num_threads();
+        if num_threads > 1 {
+            assert!(max_concurrent.load(Ordering::SeqCst) > 1);
+            // Checked one at a time, this would take at least NUM_TXS * 5ms.
+            assert!(elapsed < Duration::from_millis(5) * NUM_TXS as u32);
+        }
+    }
+
+    #[bench]
+    fn bench_insert_many(b: &mut Bencher) {
+        let logger = mc_common::logger::create_test_logger("bench_insert_many".to_owned());
+        let mut next_hash = 0u64;
+        b.iter(|| {
+            let tx_manager = TxManagerImpl::new(
+                accepting_enclave(),
+                slow_untrusted(Duration::from_micros(200), Default::default()),
+                logger.clone(),
+            );
+            let tx_contexts = (0..MAX_TRANSACTIONS_PER_BLOCK)
+                .map(|_| {
+                    next_hash += 1;
+                    let mut hash = [0u8; 32];
+                    hash[..8].copy_from_slice(&next_hash.to_le_bytes());
+                    tx_context_with_hash(TxHash(hash))
+                })
+                .collect();
+            tx_manager.insert_many(tx_contexts)
+        });
+    }
 }
@@ -1,10 +1,11 @@
 // Copyright (c) 2018-2022 The MobileCoin Foundation
 
-use crate::tx_manager::TxManagerResult;
+use crate::tx_manager::{TxManagerResult, TxRejection};
 use mc_attest_enclave_api::{EnclaveMessage, PeerSession};
 use mc_common::HashSet;
-use mc_consensus_enclave::{TxContext, WellFormedEncryptedTx};
+use mc_consensus_enclave::{TxContext, WellFormedEncryptedTx, WellFormedTxContext};
 use mc_transaction_core::tx::{TxHash, TxOutMembershipProof};
+use std::sync::Arc;
 
 #[cfg(test)]
 use mockall::*;
@@ -15,6 +16,11 @@ pub trait TxManager: Send {
     /// well-formed.
     fn insert(&self, tx_context: TxContext) -> TxManagerResult<TxHash>;
 
+    /// Insert several transactions into the cache, checking them for
+    /// well-formedness in parallel. Returns the result of each insert, in the
+    /// order of `tx_contexts`.
+    fn insert_many(&self, tx_contexts: Vec<TxContext>) -> Vec<TxManagerResult<TxHash>>;
+
     /// Remove expired transactions from the cache and return their hashes.
     ///
     /// # Arguments
@@ -61,4 +67,18 @@ pub trait TxManager: Send {
 
     /// Get the encrypted transaction corresponding to the given hash.
     fn get_encrypted_tx(&self, tx_hash: &TxHash) -> Option<WellFormedEncryptedTx>;
+
+    /// The contexts of all cached transactions, in no particular order.
+    fn pending_txs(&self) -> Vec<Arc<WellFormedTxContext>>;
+
+    /// Remove a transaction from the cache, returning true if it was present.
+    ///
+    /// Forming a block fails if a transaction it externalizes is missing, so
+    /// this should only be called through ByzantineLedger::evict_tx, which
+    /// leaves alone transactions the current slot may externalize.
+    fn evict(&self, tx_hash: &TxHash) -> bool;
+
+    /// Why the transaction with the given hash was most recently rejected, if
+    /// it was rejected recently enough to be remembered.
+    fn recent_rejection(&self, tx_hash: &TxHash) -> Option<TxRejection>;
 }
@@ -0,0 +1,43 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Client sessions, which may use any cipher suite a client can negotiate.
+
+use aes_gcm::Aes256Gcm;
+use alloc::vec::Vec;
+use mc_attest_ake::{ChaChaPoly, Ready};
+use mc_attest_enclave_api::Result;
+
+/// An established client session, using the cipher suite the client chose in
+/// its handshake.
+pub(crate) enum ClientReady {
+    /// A session using AES-256-GCM, which every client supports
+    Aes256Gcm(Ready<Aes256Gcm>),
+    /// A session using ChaCha20-Poly1305
+    ChaChaPoly(Ready<ChaChaPoly>),
+}
+
+impl ClientReady {
+    /// The channel binding of the session
+    pub fn binding(&self) -> &[u8] {
+        match self {
+            Self::Aes256Gcm(ready) => ready.binding(),
+            Self::ChaChaPoly(ready) => ready.binding(),
+        }
+    }
+
+    /// Encrypt a message for the client
+    pub fn encrypt(&mut self, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+        Ok(match self {
+            Self::Aes256Gcm(ready) => ready.encrypt(aad, plaintext)?,
+            Self::ChaChaPoly(ready) => ready.encrypt(aad, plaintext)?,
+        })
+    }
+
+    /// Decrypt a message from the client
+    pub fn decrypt(&mut self, aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>> {
+        Ok(match self {
+            Self::Aes256Gcm(ready) => ready.decrypt(aad, ciphertext)?,
+            Self::ChaChaPoly(ready) => ready.decrypt(aad, ciphertext)?,
+        })
+    }
+}
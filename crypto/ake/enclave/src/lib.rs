@@ -6,13 +6,15 @@ extern crate alloc;
 
 use aes_gcm::Aes256Gcm;
 use alloc::{borrow::ToOwned, string::ToString, vec::Vec};
+use client_session::ClientReady;
 use mc_attest_ake::{
-    AuthPending, AuthRequestOutput, AuthResponseInput, AuthResponseOutput, ClientAuthRequestInput,
-    ClientInitiate, NodeAuthRequestInput, NodeInitiate, Ready, Start, Transition,
+    offered_capabilities, offered_cipher_suites, AuthPending, AuthRequestOutput, AuthResponseInput,
+    AuthResponseOutput, ChaChaPoly, CipherSuite, ClientAuthRequestInput, ClientInitiate,
+    NodeAuthRequestInput, NodeInitiate, Ready, Start, Transition,
 };
 use mc_attest_core::{
-    DcapEvidence, EnclaveReportDataContents, EvidenceKind, IntelSealed, Nonce, QuoteNonce, Report,
-    ReportData, TargetInfo,
+    DcapEvidence, EnclaveReportDataContents, EvidenceKind, IntelSealed, MrEnclave, Nonce,
+    QuoteNonce, Report, ReportData, TargetInfo,
 };
 use mc_attest_enclave_api::{
     ClientAuthRequest, ClientAuthResponse, ClientSession, EnclaveMessage, Error, NonceAuthRequest,
@@ -61,6 +63,8 @@ const MAX_BACKEND_AUTH_PENDING_REQUESTS: usize = 100;
 /// a trait to allow extensions
 mod identity;
 
+mod client_session;
+
 pub use identity::{EnclaveIdentity, NullIdentity};
 
 /// State associated to Attested Authenticated Key Exchange held by an enclave,
@@ -102,8 +106,9 @@ pub struct AkeEnclaveState<EI: EnclaveIdentity> {
     /// A map of channel ID to inbound connection state.
     peer_inbound: Mutex<LruCache<PeerSession, Ready<Aes256Gcm>>>,
 
-    /// A map of channel ID to connection state
-    clients: Mutex<LruCache<ClientSession, Ready<Aes256Gcm>>>,
+    /// A map of channel ID to connection state, in the cipher suite each
+    /// client chose
+    clients: Mutex<LruCache<ClientSession, ClientReady>>,
 
     /// A map of inbound session IDs to connection states, for use by a
     /// store/router backend
@@ -112,6 +117,14 @@ pub struct AkeEnclaveState<EI: EnclaveIdentity> {
     /// A map of ResponderIds for each enclave that serves as a backend to the
     /// current enclave.
     backends: Mutex<LruCache<ResponderId, Ready<Aes256Gcm>>>,
+
+    /// The MRENCLAVE of each enclave that serves as a backend to the current
+    /// enclave.
+    backend_measurements: Mutex<LruCache<ResponderId, MrEnclave>>,
+
+    /// The MRENCLAVE values of backend enclaves which must not be connected
+    /// to, e.g. because the enclave build is known to be compromised.
+    blocked_backend_measurements: Mutex<Vec<MrEnclave>>,
 }
 
 impl<EI: EnclaveIdentity + Default> Default for AkeEnclaveState<EI> {
@@ -137,6 +150,8 @@ impl<EI: EnclaveIdentity> AkeEnclaveState<EI> {
             clients: Mutex::new(LruCache::new(MAX_CLIENT_SESSIONS)),
             frontends: Mutex::new(LruCache::new(MAX_FRONTEND_SESSIONS)),
             backends: Mutex::new(LruCache::new(MAX_BACKEND_SESSIONS)),
+            backend_measurements: Mutex::new(LruCache::new(MAX_BACKEND_SESSIONS)),
+            blocked_backend_measurements: Mutex::new(Vec::new()),
         }
     }
 
@@ -325,19 +340,84 @@ impl<EI: EnclaveIdentity> AkeEnclaveState<EI> {
             [self.trusted_identity()?],
             None,
         );
-        let (initiator, _verification_report) =
+        let (initiator, attestation_evidence) =
             initiator.try_next(&mut csprng, auth_response_event)?;
 
+        let blocked_measurements = self.blocked_backend_measurements.lock()?;
+        let mr_enclave = backend_mr_enclave(&attestation_evidence);
+        if !blocked_measurements.is_empty()
+            && mr_enclave
+                .as_ref()
+                .map_or(true, |mr_enclave| blocked_measurements.contains(mr_enclave))
+        {
+            return Err(Error::BlockedMeasurement);
+        }
+
+        let mut backend_measurements = self.backend_measurements.lock()?;
+        match mr_enclave {
+            Some(mr_enclave) => backend_measurements.put(backend_id.clone(), mr_enclave),
+            None => backend_measurements.pop(&backend_id),
+        };
         let mut backends = self.backends.lock()?;
         backends.put(backend_id, initiator);
 
         Ok(())
     }
 
+    /// Set the MRENCLAVE values of the backend enclaves which must not be
+    /// connected to, replacing any previous list.
+    ///
+    /// Sessions with backends which have a blocked MRENCLAVE are closed, and
+    /// the ids of those backends are returned. When the list is not empty,
+    /// backends whose MRENCLAVE cannot be determined are rejected as well.
+    pub fn set_blocked_backend_measurements(
+        &self,
+        measurements: Vec<MrEnclave>,
+    ) -> Result<Vec<ResponderId>> {
+        let mut blocked_measurements = self.blocked_backend_measurements.lock()?;
+        let mut backend_measurements = self.backend_measurements.lock()?;
+        let mut backends = self.backends.lock()?;
+
+        let closed = backends
+            .iter()
+            .map(|(backend_id, _)| backend_id.clone())
+            .filter(|backend_id| {
+                !measurements.is_empty()
+                    && backend_measurements
+                        .peek(backend_id)
+                        .map_or(true, |mr_enclave| measurements.contains(mr_enclave))
+            })
+            .collect::<Vec<_>>();
+        for backend_id in closed.iter() {
+            backends.pop(backend_id);
+            backend_measurements.pop(backend_id);
+        }
+
+        *blocked_measurements = measurements;
+        Ok(closed)
+    }
+
     /// Accept a client connection
     pub fn client_accept(
         &self,
         req: ClientAuthRequest,
+    ) -> Result<(ClientAuthResponse, ClientSession)> {
+        self.client_accept_with_capabilities(req, |_| Vec::new())
+    }
+
+    /// Accept a client connection, agreeing to the application capabilities
+    /// returned by `negotiate` for those the client offered in its request.
+    ///
+    /// The client's offer is empty if it made none, e.g. because it predates
+    /// capability negotiation, and agreeing to no capabilities sends none.
+    ///
+    /// The session uses the cipher suite the client started its handshake
+    /// with, and the client is told that we support all of
+    /// [CipherSuite::ALL].
+    pub fn client_accept_with_capabilities(
+        &self,
+        req: ClientAuthRequest,
+        negotiate: impl FnOnce(&[u8]) -> Vec<u8>,
     ) -> Result<(ClientAuthResponse, ClientSession)> {
         let local_identity = self.kex_identity.clone();
         let dcap_evidence = self.get_attestation_evidence()?;
@@ -345,23 +425,41 @@ impl<EI: EnclaveIdentity> AkeEnclaveState<EI> {
         // Create the state machine
         let responder = Start::new(self.get_client_self_id()?.to_string());
 
-        // Massage the request message into state machine input
-        let auth_request = {
-            let req: Vec<u8> = req.into();
-            ClientAuthRequestInput::<X25519, Aes256Gcm, Sha512>::new(
-                AuthRequestOutput::from(req),
-                local_identity,
-                dcap_evidence,
-            )
-        };
-
-        // Advance the state machine
+        // Massage the request message into state machine input, and advance
+        // the state machine with the suite the client's handshake uses
+        let req: Vec<u8> = req.into();
+        let capabilities = negotiate(&offered_capabilities::<X25519>(&req)?);
+        let cipher_suites = offered_cipher_suites::<X25519>(&req)?;
+        let auth_request = AuthRequestOutput::from(req);
         let mut csprng = McRng;
-        let (responder, auth_response) = responder.try_next(&mut csprng, auth_request)?;
-        let session_id = ClientSession::from(responder.binding());
+        let (session, auth_response) = match cipher_suites.first() {
+            Some(CipherSuite::ChaCha20Poly1305Sha512) => {
+                let auth_request = ClientAuthRequestInput::<X25519, ChaChaPoly, Sha512>::new(
+                    auth_request,
+                    local_identity,
+                    dcap_evidence,
+                )
+                .with_capabilities(capabilities)
+                .with_supported_cipher_suites(CipherSuite::ALL.into());
+                let (responder, auth_response) = responder.try_next(&mut csprng, auth_request)?;
+                (ClientReady::ChaChaPoly(responder), auth_response)
+            }
+            _ => {
+                let auth_request = ClientAuthRequestInput::<X25519, Aes256Gcm, Sha512>::new(
+                    auth_request,
+                    local_identity,
+                    dcap_evidence,
+                )
+                .with_capabilities(capabilities)
+                .with_supported_cipher_suites(CipherSuite::ALL.into());
+                let (responder, auth_response) = responder.try_next(&mut csprng, auth_request)?;
+                (ClientReady::Aes256Gcm(responder), auth_response)
+            }
+        };
+        let session_id = ClientSession::from(session.binding());
 
         // This session is established as far as we are concerned.
-        self.clients.lock()?.put(session_id.clone(), responder);
+        self.clients.lock()?.put(session_id.clone(), session);
 
         // Massage the state machine output into the response message
         let auth_response: Vec<u8> = auth_response.into();
@@ -520,7 +618,7 @@ impl<EI: EnclaveIdentity> AkeEnclaveState<EI> {
         clients
             .get_mut(&msg.channel_id)
             .ok_or(Error::NotFound)
-            .and_then(|session| Ok(session.decrypt(&msg.aad, &msg.data)?))
+            .and_then(|session| session.decrypt(&msg.aad, &msg.data))
     }
 
     /// Encrypt a message for a client
@@ -724,3 +822,14 @@ impl<EI: EnclaveIdentity> AkeEnclaveState<EI> {
         Err(Error::NotFound)
     }
 }
+
+/// The MRENCLAVE of a backend enclave, from the attestation evidence it
+/// presented, if the evidence is DCAP evidence.
+fn backend_mr_enclave(attestation_evidence: &EvidenceKind) -> Option<MrEnclave> {
+    match attestation_evidence {
+        EvidenceKind::Dcap(dcap_evidence) => DcapEvidence::try_from(dcap_evidence)
+            .ok()
+            .map(|dcap_evidence| dcap_evidence.quote.app_report_body().mr_enclave()),
+        EvidenceKind::Epid(_) => None,
+    }
+}
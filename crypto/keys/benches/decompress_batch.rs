@@ -0,0 +1,52 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Compares decompressing Ristretto points one at a time with decompressing
+//! them as a batch.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use mc_crypto_keys::{CompressedRistrettoPublic, RistrettoPublic};
+use mc_util_from_random::FromRandom;
+use rand_core::SeedableRng;
+use rand_hc::Hc128Rng;
+
+fn decompress_batch_benchmarks(c: &mut Criterion) {
+    let mut rng = Hc128Rng::seed_from_u64(0);
+    let mut group = c.benchmark_group("RistrettoPublic");
+
+    for num_points in [16, 256, 4096] {
+        let points = (0..num_points)
+            .map(|_| CompressedRistrettoPublic::from(RistrettoPublic::from_random(&mut rng)))
+            .collect::<Vec<_>>();
+
+        group.bench_with_input(
+            BenchmarkId::new("::try_from", num_points),
+            &points,
+            |b, points| {
+                b.iter(|| {
+                    black_box(
+                        points
+                            .iter()
+                            .map(RistrettoPublic::try_from)
+                            .collect::<Result<Vec<_>, _>>(),
+                    )
+                })
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("::try_decompress_batch", num_points),
+            &points,
+            |b, points| b.iter(|| black_box(RistrettoPublic::try_decompress_batch(points))),
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(100);
+    targets = decompress_batch_benchmarks
+}
+
+criterion_main!(benches);
@@ -438,6 +438,46 @@ impl TryFrom<&CompressedRistrettoPublic> for RistrettoPublic {
     }
 }
 
+/// Batches smaller than this are decompressed on the calling thread, since
+/// handing them to the thread pool costs more than it saves.
+#[cfg(feature = "rayon")]
+const MIN_PARALLEL_DECOMPRESSION_BATCH: usize = 64;
+
+#[cfg(feature = "alloc")]
+impl RistrettoPublic {
+    /// Decompress a batch of points, returning a result for each of them in
+    /// the same order.
+    ///
+    /// Decompression is by far the most expensive part of validating a public
+    /// key. With the `rayon` feature enabled, large batches are decompressed
+    /// in parallel.
+    pub fn decompress_batch(
+        points: &[CompressedRistrettoPublic],
+    ) -> alloc::vec::Vec<Result<Self, KeyError>> {
+        #[cfg(feature = "rayon")]
+        if points.len() >= MIN_PARALLEL_DECOMPRESSION_BATCH {
+            use rayon::prelude::*;
+            return points.par_iter().map(Self::try_from).collect();
+        }
+        points.iter().map(Self::try_from).collect()
+    }
+
+    /// Decompress a batch of points, failing if any of them is not a valid
+    /// point.
+    ///
+    /// See [RistrettoPublic::decompress_batch].
+    pub fn try_decompress_batch(
+        points: &[CompressedRistrettoPublic],
+    ) -> Result<alloc::vec::Vec<Self>, KeyError> {
+        #[cfg(feature = "rayon")]
+        if points.len() >= MIN_PARALLEL_DECOMPRESSION_BATCH {
+            use rayon::prelude::*;
+            return points.par_iter().map(Self::try_from).collect();
+        }
+        points.iter().map(Self::try_from).collect()
+    }
+}
+
 /// Shared Secret resulting from Key Exchange
 ///
 /// This is a (compressed) curve point on the ristretto curve, but we make it a
@@ -672,6 +712,51 @@ mod test {
     #[cfg(feature = "serde")]
     use super::*;
 
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_decompress_batch() {
+        use super::*;
+        use alloc::vec::Vec;
+
+        mc_util_test_helper::run_with_several_seeds(|mut rng| {
+            // Large enough to be decompressed in parallel with the rayon feature.
+            let pubkeys = (0..100)
+                .map(|_| RistrettoPublic::from_random(&mut rng))
+                .collect::<Vec<_>>();
+            let mut compressed = pubkeys
+                .iter()
+                .map(CompressedRistrettoPublic::from)
+                .collect::<Vec<_>>();
+
+            assert_eq!(
+                RistrettoPublic::try_decompress_batch(&compressed).unwrap(),
+                pubkeys
+            );
+
+            // This is not the encoding of a valid point.
+            compressed[42] = CompressedRistrettoPublic::try_from(&[0xffu8; 32]).unwrap();
+            assert_eq!(
+                RistrettoPublic::try_decompress_batch(&compressed),
+                Err(KeyError::InvalidPublicKey)
+            );
+
+            let results = RistrettoPublic::decompress_batch(&compressed);
+            assert_eq!(results.len(), pubkeys.len());
+            for (i, (result, pubkey)) in results.into_iter().zip(pubkeys.iter()).enumerate() {
+                if i == 42 {
+                    assert_eq!(result, Err(KeyError::InvalidPublicKey));
+                } else {
+                    assert_eq!(result.as_ref(), Ok(pubkey));
+                }
+            }
+
+            assert!(RistrettoPublic::decompress_batch(&compressed[..3])
+                .into_iter()
+                .all(|result| result.is_ok()));
+            assert_eq!(RistrettoPublic::try_decompress_batch(&[]), Ok(Vec::new()));
+        });
+    }
+
     // Test that mc-util-serial can serialize a pubkey
     #[test]
     #[cfg(feature = "serde")]
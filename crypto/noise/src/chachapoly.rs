@@ -0,0 +1,95 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! The ChaChaPoly cipher functions.
+
+use aead::{
+    consts::{U0, U12, U16, U32},
+    AeadCore, AeadInPlace, AeadMutInPlace, Error as AeadError, Key, KeyInit, KeySizeUser, Nonce,
+    Tag,
+};
+use chacha20poly1305::ChaCha20Poly1305;
+
+/// ChaCha20-Poly1305, as used by noise.
+///
+/// Noise encodes the nonce counter of ChaChaPoly as little-endian bytes, after
+/// 32 bits of zeroes, as described in
+/// [section 12.4](http://noiseprotocol.org/noise.html#the-chachapoly-cipher-functions)
+/// of the specification, where [NoiseCipher](crate::NoiseCipher) produces
+/// big-endian nonces for every cipher. This wrapper converts between the two,
+/// so that it can be used as a [NoiseCipher](crate::NoiseCipher) like any
+/// other AEAD.
+#[derive(Clone)]
+pub struct ChaChaPoly(ChaCha20Poly1305);
+
+impl ChaChaPoly {
+    /// Convert a nonce made by [NoiseCipher](crate::NoiseCipher) into the one
+    /// ChaChaPoly uses for the same counter.
+    fn noise_nonce(nonce: &Nonce<Self>) -> Nonce<ChaCha20Poly1305> {
+        let mut retval = *nonce;
+        retval[4..].reverse();
+        retval
+    }
+}
+
+impl KeySizeUser for ChaChaPoly {
+    type KeySize = U32;
+}
+
+impl KeyInit for ChaChaPoly {
+    fn new(key: &Key<Self>) -> Self {
+        Self(ChaCha20Poly1305::new(key))
+    }
+}
+
+impl AeadCore for ChaChaPoly {
+    type NonceSize = U12;
+    type TagSize = U16;
+    type CiphertextOverhead = U0;
+}
+
+impl AeadMutInPlace for ChaChaPoly {
+    fn encrypt_in_place_detached(
+        &mut self,
+        nonce: &Nonce<Self>,
+        associated_data: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<Tag<Self>, AeadError> {
+        self.0
+            .encrypt_in_place_detached(&Self::noise_nonce(nonce), associated_data, buffer)
+    }
+
+    fn decrypt_in_place_detached(
+        &mut self,
+        nonce: &Nonce<Self>,
+        associated_data: &[u8],
+        buffer: &mut [u8],
+        tag: &Tag<Self>,
+    ) -> Result<(), AeadError> {
+        self.0
+            .decrypt_in_place_detached(&Self::noise_nonce(nonce), associated_data, buffer, tag)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::NoiseCipher;
+    use aead::{Aead, AeadMut};
+
+    #[test]
+    fn nonce_is_little_endian() {
+        let key = Key::<ChaChaPoly>::from([7u8; 32]);
+        let mut cipher = ChaChaPoly::new(&key);
+        let nonce = ChaChaPoly::nonce_to_arr(0x0102_0304_0506_0708);
+        let ciphertext = AeadMut::encrypt(&mut cipher, &nonce, &b"hello"[..]).unwrap();
+
+        let expected_nonce = Nonce::<ChaCha20Poly1305>::from([0, 0, 0, 0, 8, 7, 6, 5, 4, 3, 2, 1]);
+        let expected = ChaCha20Poly1305::new(&key)
+            .encrypt(&expected_nonce, &b"hello"[..])
+            .unwrap();
+        assert_eq!(ciphertext, expected);
+
+        let plaintext = AeadMut::decrypt(&mut cipher, &nonce, ciphertext.as_slice()).unwrap();
+        assert_eq!(plaintext, b"hello");
+    }
+}
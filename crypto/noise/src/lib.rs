@@ -7,6 +7,8 @@
 
 extern crate alloc;
 
+#[cfg(feature = "chacha20poly1305")]
+mod chachapoly;
 mod cipher_state;
 mod handshake_hash;
 mod handshake_state;
@@ -21,3 +23,6 @@ pub use crate::{
     protocol_name::{ProtocolName, ProtocolNameError},
     symmetric_state::SymmetricOutput,
 };
+
+#[cfg(feature = "chacha20poly1305")]
+pub use crate::chachapoly::ChaChaPoly;
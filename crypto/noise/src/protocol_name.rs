@@ -3,6 +3,8 @@
 //! A set of static ZWTs designed to aid the handling of noise protocol strings.
 
 use crate::patterns::{HandshakeIX, HandshakeNX, HandshakePattern};
+#[cfg(feature = "chacha20poly1305")]
+use crate::ChaChaPoly;
 use aead::AeadMut;
 use aes_gcm::Aes256Gcm;
 use core::marker::PhantomData;
@@ -126,6 +128,12 @@ impl_protocol_names! {
     "Noise_NX_25519_AESGCM_SHA512", HandshakeNX, X25519, Aes256Gcm, Sha512;
 }
 
+#[cfg(feature = "chacha20poly1305")]
+impl_protocol_names! {
+    "Noise_IX_25519_ChaChaPoly_SHA512", HandshakeIX, X25519, ChaChaPoly, Sha512;
+    "Noise_NX_25519_ChaChaPoly_SHA512", HandshakeNX, X25519, ChaChaPoly, Sha512;
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -0,0 +1,137 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Compares verifying MLSAGs one at a time with verifying them as a batch.
+//!
+//! Rings draw their members from a shared pool, as decoys drawn from the same
+//! ledger do, so that the batch has repeated members to share work on.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use mc_crypto_keys::{CompressedRistrettoPublic, RistrettoPrivate, RistrettoPublic};
+use mc_crypto_ring_signature::{
+    generators, CompressedCommitment, MlsagBatchItem, ReducedTxOut, RingMLSAG, Scalar,
+};
+use mc_util_from_random::FromRandom;
+use mc_util_test_helper::{RngCore, RngType, SeedableRng};
+
+const RING_SIZE: usize = 11;
+const POOL_SIZE: usize = 64;
+
+struct Signed {
+    message: [u8; 32],
+    ring: Vec<ReducedTxOut>,
+    output_commitment: CompressedCommitment,
+    signature: RingMLSAG,
+}
+
+fn make_signatures(num_signatures: usize, rng: &mut RngType) -> Vec<Signed> {
+    let generator = generators(0);
+
+    let pool = (0..POOL_SIZE)
+        .map(|_| {
+            let onetime_private_key = RistrettoPrivate::from_random(rng);
+            let value = rng.next_u64();
+            let blinding = Scalar::random(rng);
+            let tx_out = ReducedTxOut {
+                public_key: CompressedRistrettoPublic::from_random(rng),
+                target_key: CompressedRistrettoPublic::from(RistrettoPublic::from(
+                    &onetime_private_key,
+                )),
+                commitment: CompressedCommitment::new(value, blinding, &generator),
+            };
+            (onetime_private_key, value, blinding, tx_out)
+        })
+        .collect::<Vec<_>>();
+
+    (0..num_signatures)
+        .map(|_| {
+            let mut message = [0u8; 32];
+            rng.fill_bytes(&mut message);
+
+            // Distinct members, the first of which is the real input.
+            let start = rng.next_u64() as usize;
+            let indices = (0..RING_SIZE)
+                .map(|i| (start + i * 5) % POOL_SIZE)
+                .collect::<Vec<_>>();
+            let ring = indices
+                .iter()
+                .map(|i| pool[*i].3.clone())
+                .collect::<Vec<_>>();
+            let (onetime_private_key, value, blinding, _) = &pool[indices[0]];
+
+            let output_blinding = Scalar::random(rng);
+            let output_commitment = CompressedCommitment::new(*value, output_blinding, &generator);
+            let signature = RingMLSAG::sign(
+                &message,
+                &ring,
+                0,
+                onetime_private_key,
+                *value,
+                blinding,
+                &output_blinding,
+                &generator,
+                rng,
+            )
+            .expect("signing failed");
+
+            Signed {
+                message,
+                ring,
+                output_commitment,
+                signature,
+            }
+        })
+        .collect()
+}
+
+fn verify_batch_benchmarks(c: &mut Criterion) {
+    let mut rng = RngType::from_seed([0u8; 32]);
+    let mut group = c.benchmark_group("RingMLSAG");
+
+    for num_signatures in [1, 16, 256] {
+        let signed = make_signatures(num_signatures, &mut rng);
+
+        group.bench_with_input(
+            BenchmarkId::new("::verify", num_signatures),
+            &signed,
+            |b, signed| {
+                b.iter(|| {
+                    black_box(signed.iter().try_for_each(|signed| {
+                        signed.signature.verify(
+                            &signed.message,
+                            &signed.ring,
+                            &signed.output_commitment,
+                        )
+                    }))
+                })
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("::verify_batch", num_signatures),
+            &signed,
+            |b, signed| {
+                b.iter(|| {
+                    let items = signed
+                        .iter()
+                        .map(|signed| MlsagBatchItem {
+                            signature: &signed.signature,
+                            message: &signed.message,
+                            ring: &signed.ring,
+                            output_commitment: &signed.output_commitment,
+                        })
+                        .collect::<Vec<_>>();
+                    black_box(RingMLSAG::verify_batch(&items))
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(20);
+    targets = verify_batch_benchmarks
+}
+criterion_main!(benches);
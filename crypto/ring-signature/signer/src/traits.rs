@@ -137,6 +137,8 @@ pub enum Error {
     RingSignature(RingSignatureError),
     /// No path to spend key (logic error)
     NoPathToSpendKey,
+    /// Multisig signing failed: {0}
+    Multisig(String),
     /// Unknown device / implementation Error
     Unknown,
 }
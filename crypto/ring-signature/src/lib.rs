@@ -23,11 +23,14 @@ pub mod proptest_fixtures;
 
 pub use amount::{Commitment, CompressedCommitment};
 pub use ring_signature::{
-    generators, CurveScalar, Error, KeyImage, PedersenGens, ReducedTxOut, Scalar,
+    generators, hash_to_point, CurveScalar, Error, KeyImage, PedersenGens, ReducedTxOut, Scalar,
+    B_BLINDING,
 };
 
 #[cfg(feature = "alloc")]
-pub use ring_signature::RingMLSAG;
+pub use ring_signature::{
+    BatchVerificationError, KeyNonceCommitments, MlsagBatchItem, PartialRingMLSAG, RingMLSAG,
+};
 
 #[cfg(feature = "internals")]
 pub use ring_signature::{MlsagSignCtx, MlsagSignParams, MlsagVerify, Ring};
@@ -3,6 +3,7 @@
 use mc_crypto_keys::{RistrettoPrivate, RistrettoPublic};
 
 use alloc::vec::Vec;
+use curve25519_dalek::ristretto::RistrettoPoint;
 use rand_core::CryptoRngCore;
 use zeroize::Zeroize;
 
@@ -16,12 +17,107 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     ring_signature::{
-        mlsag_sign::MlsagSignParams, mlsag_verify::MlsagVerify, CurveScalar, Error, KeyImage,
-        PedersenGens, Scalar,
+        challenge, hash_to_point, mlsag_sign::MlsagSignParams, mlsag_verify::MlsagVerify,
+        CurveScalar, Error, KeyImage, PedersenGens, Scalar, B_BLINDING,
     },
     Commitment, CompressedCommitment, ReducedTxOut,
 };
 
+/// Commitments to the nonce `alpha` used for the real input's onetime
+/// private key, when that key is held elsewhere. See
+/// [RingMLSAG::sign_with_external_key].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct KeyNonceCommitments {
+    /// `alpha * G`
+    pub L0: RistrettoPoint,
+    /// `alpha * Hp(P)`, where `P` is the real input's onetime public key
+    pub R0: RistrettoPoint,
+}
+
+/// An MLSAG made by [RingMLSAG::sign_with_external_key], which is only
+/// missing the response for the real input's onetime private key.
+///
+/// Whoever holds that key can recompute the challenge they are asked to
+/// respond to with [PartialRingMLSAG::real_input_challenge], rather than
+/// trusting the signer for it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct PartialRingMLSAG {
+    /// The initial challenge `c[0]`.
+    pub c_zero: CurveScalar,
+
+    /// Responses, as in [RingMLSAG], with `r_{real_index,0}` set to zero.
+    pub responses: Vec<CurveScalar>,
+
+    /// Key image "spent" by this signature.
+    pub key_image: KeyImage,
+}
+
+impl PartialRingMLSAG {
+    /// Recompute the challenge `c[real_index]` which the response for the real
+    /// input's onetime private key must answer, checking that every other
+    /// response closes the ring with the given nonce commitments.
+    ///
+    /// # Arguments
+    /// * `message` - Message being signed.
+    /// * `ring` - A ring of reduced TxOuts
+    /// * `real_index` - The index in the ring of the real input.
+    /// * `key_nonce_commitments` - Commitments to the nonce for the real
+    ///   input's onetime private key.
+    /// * `output_commitment` - Output amount commitment.
+    pub fn real_input_challenge(
+        &self,
+        message: &[u8],
+        ring: &[ReducedTxOut],
+        real_index: usize,
+        key_nonce_commitments: &KeyNonceCommitments,
+        output_commitment: &CompressedCommitment,
+    ) -> Result<Scalar, Error> {
+        let ring_size = ring.len();
+        if real_index >= ring_size {
+            return Err(Error::IndexOutOfBounds);
+        }
+        if self.responses.len() != 2 * ring_size {
+            return Err(Error::LengthMismatch(2 * ring_size, self.responses.len()));
+        }
+
+        let decompressed_ring = ring
+            .iter()
+            .map(<(RistrettoPublic, Commitment)>::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        let I = self
+            .key_image
+            .point
+            .decompress()
+            .ok_or(Error::InvalidKeyImage)?;
+        let output_commitment = Commitment::try_from(output_commitment)?;
+        let G = B_BLINDING;
+
+        // Iterate around the ring from c[0], as in MlsagVerify, using the nonce
+        // commitments in place of the missing response.
+        let mut c = self.c_zero.scalar;
+        let mut real_input_challenge = Scalar::ZERO;
+        for (i, (P_i, input_commitment)) in decompressed_ring.iter().enumerate() {
+            let r_1 = self.responses[2 * i + 1].scalar;
+            let L1 = r_1 * G + c * (output_commitment.point - input_commitment.point);
+            let (L0, R0) = if i == real_index {
+                real_input_challenge = c;
+                (key_nonce_commitments.L0, key_nonce_commitments.R0)
+            } else {
+                let r_0 = self.responses[2 * i].scalar;
+                let L0: RistrettoPoint = r_0 * G + c * P_i.as_ref();
+                (L0, r_0 * hash_to_point(P_i) + c * I)
+            };
+            c = challenge(message, &self.key_image, &L0, &R0, &L1);
+        }
+
+        if c != self.c_zero.scalar {
+            return Err(Error::InvalidSignature);
+        }
+        Ok(real_input_challenge)
+    }
+}
+
 /// MLSAG for a ring of public keys and amount commitments.
 /// Note: Serialize and Deserialize appear to be cruft left over from
 /// sdk_json_interface.
@@ -202,12 +298,130 @@ impl RingMLSAG {
 
         res
     }
+
+    /// Sign a ring of input addresses and amount commitments, without knowing
+    /// the real input's onetime private key `x`.
+    ///
+    /// Whoever holds `x`, possibly split between several parties, supplies
+    /// the key image `x * Hp(P)` and commitments to a nonce `alpha`, and
+    /// `respond` is called with the real input's challenge `c`, and the rest
+    /// of the signature to check it against, to get the response
+    /// `alpha - c * x`. The amount commitment part of the signature is made
+    /// here, as in [RingMLSAG::sign]. The signature is verified before it is
+    /// returned, so a wrong key image or response is an error.
+    ///
+    /// # Arguments
+    /// * `message` - Message to be signed.
+    /// * `ring` - A ring of reduced TxOuts
+    /// * `real_index` - The index in the ring of the real input.
+    /// * `key_image` - The real input's key image.
+    /// * `key_nonce_commitments` - Commitments to the nonce for the real
+    ///   input's onetime private key.
+    /// * `value` - Value of the real input.
+    /// * `blinding` - Blinding of the real input.
+    /// * `output_blinding` - The output amount's blinding factor.
+    /// * `generator` - The pedersen generator to use for this commitment and
+    ///   signature
+    /// * `rng` - Randomness.
+    /// * `respond` - Computes the response for the real input's onetime private
+    ///   key, given its challenge and the partial signature.
+    pub fn sign_with_external_key(
+        message: &[u8],
+        ring: &[ReducedTxOut],
+        real_index: usize,
+        key_image: &KeyImage,
+        key_nonce_commitments: &KeyNonceCommitments,
+        value: u64,
+        blinding: &Scalar,
+        output_blinding: &Scalar,
+        generator: &PedersenGens,
+        rng: &mut dyn CryptoRngCore,
+        respond: impl FnOnce(&Scalar, &PartialRingMLSAG) -> Result<Scalar, Error>,
+    ) -> Result<Self, Error> {
+        let ring_size = ring.len();
+        if real_index >= ring_size {
+            return Err(Error::IndexOutOfBounds);
+        }
+
+        let decompressed_ring = ring
+            .iter()
+            .map(<(RistrettoPublic, Commitment)>::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        let I = key_image.point.decompress().ok_or(Error::InvalidKeyImage)?;
+        let G = B_BLINDING;
+
+        let output_commitment = Commitment::new(value, *output_blinding, generator);
+        let z: Scalar = *output_blinding - *blinding;
+        let (_, real_commitment) = decompressed_ring[real_index];
+        if output_commitment.point - real_commitment.point != z * G {
+            return Err(Error::ValueNotConserved);
+        }
+
+        let mut rng = rng;
+        let alpha_1 = Scalar::random(&mut rng);
+        let mut responses = alloc::vec![CurveScalar::from(Scalar::ZERO); 2 * ring_size];
+        let mut c_zero = Scalar::ZERO;
+
+        // Iterate around the ring, starting at real_index, as in MlsagSignCtx. After
+        // the last step, `c` is the real input's challenge.
+        let mut c = Scalar::ZERO;
+        for n in 0..ring_size {
+            let i = (real_index + n) % ring_size;
+            let (P_i, input_commitment) = &decompressed_ring[i];
+
+            let (L0, R0, L1) = if n == 0 {
+                (
+                    key_nonce_commitments.L0,
+                    key_nonce_commitments.R0,
+                    alpha_1 * G,
+                )
+            } else {
+                let r_0 = Scalar::random(&mut rng);
+                let r_1 = Scalar::random(&mut rng);
+                responses[2 * i] = CurveScalar::from(r_0);
+                responses[2 * i + 1] = CurveScalar::from(r_1);
+
+                let L0: RistrettoPoint = r_0 * G + c * P_i.as_ref();
+                let R0 = r_0 * hash_to_point(P_i) + c * I;
+                let L1 = r_1 * G + c * (output_commitment.point - input_commitment.point);
+                (L0, R0, L1)
+            };
+
+            c = challenge(message, key_image, &L0, &R0, &L1);
+            if (i + 1) % ring_size == 0 {
+                c_zero = c;
+            }
+        }
+
+        // "Close the loop" by computing responses for the real index, the one for
+        // the onetime private key last.
+        responses[2 * real_index + 1] = CurveScalar::from(alpha_1 - c * z);
+        let mut partial_signature = PartialRingMLSAG {
+            c_zero: CurveScalar::from(c_zero),
+            responses,
+            key_image: *key_image,
+        };
+        let response = respond(&c, &partial_signature)?;
+        partial_signature.responses[2 * real_index] = CurveScalar::from(response);
+
+        let signature = RingMLSAG {
+            c_zero: partial_signature.c_zero,
+            responses: partial_signature.responses,
+            key_image: partial_signature.key_image,
+        };
+        signature.verify(
+            message,
+            ring,
+            &CompressedCommitment::from(&output_commitment),
+        )?;
+        Ok(signature)
+    }
 }
 
 #[cfg(test)]
 mod mlsag_tests {
     use super::*;
-    use crate::generators;
+    use crate::{generators, BatchVerificationError, MlsagBatchItem};
     use curve25519_dalek::ristretto::CompressedRistretto;
     use mc_crypto_keys::{CompressedRistrettoPublic, RistrettoPrivate, RistrettoPublic};
     use mc_util_from_random::FromRandom;
@@ -321,6 +535,66 @@ mod mlsag_tests {
     proptest! {
         #![proptest_config(ProptestConfig::with_cases(6))]
 
+        #[test]
+        // A signature made with an external key should verify, and a wrong
+        // response should be rejected.
+        fn test_sign_with_external_key(
+            num_mixins in 1..17usize,
+            seed in any::<[u8; 32]>(),
+        ) {
+            let mut rng: RngType = SeedableRng::from_seed(seed);
+            let pseudo_output_blinding = Scalar::random(&mut rng);
+            let params = RingMLSAGParameters::random(num_mixins, pseudo_output_blinding, &mut rng);
+
+            let x = *params.onetime_private_key.as_ref();
+            let P = RistrettoPublic::from(&params.onetime_private_key);
+            let alpha = Scalar::random(&mut rng);
+            let key_nonce_commitments = KeyNonceCommitments {
+                L0: alpha * B_BLINDING,
+                R0: alpha * hash_to_point(&P),
+            };
+            let key_image = KeyImage::from(&params.onetime_private_key);
+
+            let sign = |response_offset: Scalar, rng: &mut RngType| {
+                RingMLSAG::sign_with_external_key(
+                    &params.message,
+                    &params.ring,
+                    params.real_index,
+                    &key_image,
+                    &key_nonce_commitments,
+                    params.value,
+                    &params.blinding,
+                    &params.pseudo_output_blinding,
+                    &params.generator,
+                    rng,
+                    |c, partial_signature| {
+                        let output_commitment = CompressedCommitment::new(params.value, params.pseudo_output_blinding, &params.generator);
+                        // The key holder can recompute the challenge, and a
+                        // tampered partial signature gives a different one.
+                        assert_eq!(
+                            partial_signature.real_input_challenge(&params.message, &params.ring, params.real_index, &key_nonce_commitments, &output_commitment),
+                            Ok(*c)
+                        );
+                        let mut tampered = partial_signature.clone();
+                        let decoy_index = (params.real_index + 1) % params.ring.len();
+                        tampered.responses[2 * decoy_index].scalar += Scalar::ONE;
+                        assert_eq!(
+                            tampered.real_input_challenge(&params.message, &params.ring, params.real_index, &key_nonce_commitments, &output_commitment),
+                            Err(Error::InvalidSignature)
+                        );
+                        Ok(alpha - c * x + response_offset)
+                    },
+                )
+            };
+
+            let signature = sign(Scalar::ZERO, &mut rng).unwrap();
+            assert_eq!(signature.key_image, key_image);
+            let output_commitment = CompressedCommitment::new(params.value, params.pseudo_output_blinding, &params.generator);
+            assert!(signature.verify(&params.message, &params.ring, &output_commitment).is_ok());
+
+            assert!(matches!(sign(Scalar::ONE, &mut rng), Err(Error::InvalidSignature)));
+        }
+
         #[test]
         // `sign` should return a signature with 2*ring_size responses.
         fn test_signature_responses_has_correct_length(
@@ -684,6 +958,106 @@ mod mlsag_tests {
             }
         }
 
+        #[test]
+        // `verify_batch` should accept a batch of valid signatures, including
+        // signatures whose rings share members.
+        fn test_verify_batch_accepts_valid_signatures(
+            num_mixins in 1..17usize,
+            seed in any::<[u8; 32]>(),
+        ) {
+            let mut rng: RngType = SeedableRng::from_seed(seed);
+            let params = (0..3)
+                .map(|_| {
+                    let pseudo_output_blinding = Scalar::random(&mut rng);
+                    RingMLSAGParameters::random(num_mixins, pseudo_output_blinding, &mut rng)
+                })
+                .collect::<Vec<_>>();
+            // Sign the last ring twice, so that the batch repeats its members.
+            let signed = params
+                .iter()
+                .chain(params.last())
+                .map(|params| {
+                    let signature = params.sign(&mut rng).unwrap();
+                    let output_commitment = CompressedCommitment::new(
+                        params.value,
+                        params.pseudo_output_blinding,
+                        &params.generator,
+                    );
+                    (params, signature, output_commitment)
+                })
+                .collect::<Vec<_>>();
+
+            let items = signed
+                .iter()
+                .map(|(params, signature, output_commitment)| MlsagBatchItem {
+                    signature,
+                    message: &params.message,
+                    ring: &params.ring,
+                    output_commitment,
+                })
+                .collect::<Vec<_>>();
+
+            assert_eq!(RingMLSAG::verify_batch(&items), Ok(()));
+            assert_eq!(RingMLSAG::verify_batch(&[]), Ok(()));
+        }
+
+        #[test]
+        // `verify_batch` should report the first signature that `verify` rejects.
+        fn test_verify_batch_reports_first_invalid_signature(
+            num_mixins in 1..17usize,
+            seed in any::<[u8; 32]>(),
+        ) {
+            let mut rng: RngType = SeedableRng::from_seed(seed);
+            let signed = (0..3)
+                .map(|_| {
+                    let pseudo_output_blinding = Scalar::random(&mut rng);
+                    let params =
+                        RingMLSAGParameters::random(num_mixins, pseudo_output_blinding, &mut rng);
+                    let signature = params.sign(&mut rng).unwrap();
+                    let output_commitment = CompressedCommitment::new(
+                        params.value,
+                        params.pseudo_output_blinding,
+                        &params.generator,
+                    );
+                    (params, signature, output_commitment)
+                })
+                .collect::<Vec<_>>();
+
+            let wrong_message = [0u8; 32];
+            let mut truncated = signed[2].1.clone();
+            truncated.responses.pop();
+            let mut items = signed
+                .iter()
+                .map(|(params, signature, output_commitment)| MlsagBatchItem {
+                    signature,
+                    message: &params.message,
+                    ring: &params.ring,
+                    output_commitment,
+                })
+                .collect::<Vec<_>>();
+            items[1].message = &wrong_message;
+            items[2].signature = &truncated;
+
+            let sequential_error = items[1]
+                .signature
+                .verify(items[1].message, items[1].ring, items[1].output_commitment)
+                .unwrap_err();
+            assert_eq!(
+                RingMLSAG::verify_batch(&items),
+                Err(BatchVerificationError {
+                    index: 1,
+                    error: sequential_error,
+                })
+            );
+            assert_eq!(
+                RingMLSAG::verify_batch(&items[2..]),
+                Err(BatchVerificationError {
+                    index: 0,
+                    error: Error::LengthMismatch(2 * (num_mixins + 1), 2 * (num_mixins + 1) - 1),
+                })
+            );
+        }
+
         #[test]
         #[cfg(feature = "prost")]
         // decode(encode(&signature)) should be the identity function.
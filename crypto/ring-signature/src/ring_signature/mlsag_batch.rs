@@ -0,0 +1,150 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Batch verification of RingMLSAG signatures.
+//!
+//! MLSAG challenges are chained through a hash, so unlike Schnorr signatures
+//! a batch cannot be checked with a single multiscalar multiplication. What
+//! can be shared is the work that depends only on the rings: every distinct
+//! ring member is decompressed and hashed to a point once per batch, however
+//! many rings it appears in. Decoys are sampled from the same ledger, so
+//! members recur across the inputs of a transaction and of a block.
+//!
+//! Everything a verifier handles is public, so the batch also uses
+//! variable-time scalar multiplication, which is considerably faster than the
+//! constant-time arithmetic the signer needs.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use curve25519_dalek::{ristretto::RistrettoPoint, traits::VartimeMultiscalarMul};
+use displaydoc::Display;
+use mc_crypto_keys::RistrettoPublic;
+
+use crate::{
+    ring_signature::{challenge, hash_to_point, Error, RingMLSAG},
+    Commitment, CompressedCommitment, ReducedTxOut,
+};
+
+/// One signature to verify as part of a batch, with the data it signs.
+#[derive(Clone, Copy)]
+pub struct MlsagBatchItem<'a> {
+    /// The signature.
+    pub signature: &'a RingMLSAG,
+    /// Signed message.
+    pub message: &'a [u8],
+    /// A ring of input onetime addresses and amount commitments.
+    pub ring: &'a [ReducedTxOut],
+    /// Output amount commitment.
+    pub output_commitment: &'a CompressedCommitment,
+}
+
+/// Signature {index} of the batch is invalid: {error}
+#[derive(Clone, Debug, Display, Eq, PartialEq)]
+pub struct BatchVerificationError {
+    /// The position of the first invalid signature in the batch.
+    pub index: usize,
+    /// Why the signature is invalid.
+    pub error: Error,
+}
+
+/// Identifies a ring member by its compressed onetime address and amount
+/// commitment.
+type RingMemberKey = ([u8; 32], [u8; 32]);
+
+/// A decompressed ring member, with the hash of its onetime address.
+struct RingMember {
+    target_key: RistrettoPoint,
+    hashed_target_key: RistrettoPoint,
+    commitment: RistrettoPoint,
+}
+
+impl RingMLSAG {
+    /// Verify several MLSAG signatures, sharing the decompression and hashing
+    /// of ring members between them.
+    ///
+    /// This accepts exactly the batches in which every signature passes
+    /// [RingMLSAG::verify], and otherwise reports the first signature that
+    /// fails.
+    pub fn verify_batch(items: &[MlsagBatchItem]) -> Result<(), BatchVerificationError> {
+        let mut members = BTreeMap::<RingMemberKey, RingMember>::new();
+
+        items.iter().enumerate().try_for_each(|(index, item)| {
+            verify_item(item, &mut members).map_err(|error| BatchVerificationError { index, error })
+        })
+    }
+}
+
+fn verify_item(
+    item: &MlsagBatchItem,
+    members: &mut BTreeMap<RingMemberKey, RingMember>,
+) -> Result<(), Error> {
+    let signature = item.signature;
+    let ring_size = item.ring.len();
+
+    // `responses` must contain `2 * ring_size` elements.
+    if signature.responses.len() != 2 * ring_size {
+        return Err(Error::LengthMismatch(
+            2 * ring_size,
+            signature.responses.len(),
+        ));
+    }
+    if ring_size == 0 {
+        return Err(Error::IndexOutOfBounds);
+    }
+
+    // The key image must decompress.
+    let I = signature
+        .key_image
+        .point
+        .decompress()
+        .ok_or(Error::InvalidKeyImage)?;
+
+    // Output commitment must decompress.
+    let output_commitment = Commitment::try_from(item.output_commitment)?;
+
+    // Ring must decompress, reusing members seen earlier in the batch.
+    let keys = item.ring.iter().map(member_key).collect::<Vec<_>>();
+    for (tx_out, key) in item.ring.iter().zip(&keys) {
+        if !members.contains_key(key) {
+            let (target_key, commitment): (RistrettoPublic, Commitment) = tx_out.try_into()?;
+            members.insert(
+                *key,
+                RingMember {
+                    target_key: *target_key.as_ref(),
+                    hashed_target_key: hash_to_point(&target_key),
+                    commitment: commitment.point,
+                },
+            );
+        }
+    }
+
+    // Recompute challenges, as in MlsagVerify::verify.
+    let mut c_i = signature.c_zero.scalar;
+    for (i, key) in keys.iter().enumerate() {
+        let member = &members[key];
+        let r_0 = signature.responses[2 * i].scalar;
+        let r_1 = signature.responses[2 * i + 1].scalar;
+
+        let L0 =
+            RistrettoPoint::vartime_double_scalar_mul_basepoint(&c_i, &member.target_key, &r_0);
+        let R0 = RistrettoPoint::vartime_multiscalar_mul([r_0, c_i], [member.hashed_target_key, I]);
+        let L1 = RistrettoPoint::vartime_double_scalar_mul_basepoint(
+            &c_i,
+            &(output_commitment.point - member.commitment),
+            &r_1,
+        );
+
+        c_i = challenge(item.message, &signature.key_image, &L0, &R0, &L1);
+    }
+
+    if c_i == signature.c_zero.scalar {
+        Ok(())
+    } else {
+        Err(Error::InvalidSignature)
+    }
+}
+
+fn member_key(tx_out: &ReducedTxOut) -> RingMemberKey {
+    (
+        *tx_out.target_key.as_bytes(),
+        tx_out.commitment.point.to_bytes(),
+    )
+}
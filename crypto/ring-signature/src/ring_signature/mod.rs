@@ -24,9 +24,14 @@ mod mlsag_verify;
 
 #[cfg(feature = "alloc")]
 mod mlsag;
+#[cfg(feature = "alloc")]
+mod mlsag_batch;
 
 #[cfg(feature = "alloc")]
-pub use self::mlsag::RingMLSAG;
+pub use self::{
+    mlsag::{KeyNonceCommitments, PartialRingMLSAG, RingMLSAG},
+    mlsag_batch::{BatchVerificationError, MlsagBatchItem},
+};
 
 pub use self::{curve_scalar::CurveScalar, error::Error, key_image::KeyImage};
 
@@ -50,6 +50,7 @@ fn main() {
             "ingest_peer.proto",
             "kex_rng.proto",
             "ledger.proto",
+            "overseer.proto",
             "view.proto",
         ],
     );
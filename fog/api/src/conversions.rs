@@ -11,6 +11,7 @@ use mc_attest_enclave_api::{EnclaveMessage, NonceSession};
 use mc_crypto_keys::CompressedRistrettoPublic;
 use mc_fog_types::{common, common::BlockRange, view::MultiViewStoreQueryResponseStatus};
 use mc_fog_uri::{ConnectionUri, FogViewStoreUri};
+use mc_util_grpc::InFlightRequestInfo;
 use std::str::FromStr;
 
 impl From<Vec<EnclaveMessage<NonceSession>>> for MultiViewStoreQueryRequest {
@@ -61,6 +62,22 @@ impl From<&common::BlockRange> for fog_common::BlockRange {
     }
 }
 
+impl From<&InFlightRequestInfo> for fog_common::InFlightQuery {
+    fn from(info: &InFlightRequestInfo) -> fog_common::InFlightQuery {
+        let mut query = fog_common::InFlightQuery::new();
+        query.set_id(info.id);
+        query.set_method(info.method.clone());
+        query.set_peer(info.peer.clone());
+        query.set_age_ms(info.age.as_millis() as u64);
+        query.set_num_requests(info.num_requests);
+        query.set_bytes_received(info.bytes_received);
+        query.set_bytes_sent(info.bytes_sent);
+        query.set_num_shards(info.num_shards);
+        query.set_state(info.state.clone());
+        query
+    }
+}
+
 impl From<fog_common::BlockRange> for common::BlockRange {
     fn from(proto_block_range: fog_common::BlockRange) -> common::BlockRange {
         common::BlockRange::new(proto_block_range.start_block, proto_block_range.end_block)
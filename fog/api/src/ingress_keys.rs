@@ -0,0 +1,78 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! The request header with which clients tell a fog view router the ingress
+//! keys their fog reports were issued under.
+//!
+//! When several wallet providers share fog, each with its own ingress key,
+//! the router only sends a query to the view stores which serve the client's
+//! ingress keys, and to stores which serve every key. The header is not
+//! covered by attestation, but a client which lies about its keys only stops
+//! itself from finding its TxOuts.
+
+use grpcio::Metadata;
+use mc_crypto_keys::CompressedRistrettoPublic;
+use std::collections::BTreeSet;
+
+/// The name of the header which carries the client's ingress keys, as comma
+/// separated hex strings.
+pub const INGRESS_KEYS_HEADER: &str = "x-mc-fog-ingress-keys";
+
+/// Encode ingress keys as the value of the [INGRESS_KEYS_HEADER].
+pub fn encode_ingress_keys<'a>(
+    ingress_keys: impl IntoIterator<Item = &'a CompressedRistrettoPublic>,
+) -> String {
+    ingress_keys
+        .into_iter()
+        .map(hex::encode)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Decode the value of the [INGRESS_KEYS_HEADER].
+pub fn decode_ingress_keys(value: &[u8]) -> Result<BTreeSet<CompressedRistrettoPublic>, String> {
+    let value = core::str::from_utf8(value).map_err(|err| err.to_string())?;
+    value
+        .split(',')
+        .map(|key| {
+            let bytes = hex::decode(key.trim()).map_err(|err| format!("{key}: {err}"))?;
+            CompressedRistrettoPublic::try_from(&bytes[..]).map_err(|err| format!("{key}: {err}"))
+        })
+        .collect()
+}
+
+/// Find the ingress keys in a set of request headers, if the client sent any.
+pub fn ingress_keys_from_metadata(
+    headers: &Metadata,
+) -> Result<Option<BTreeSet<CompressedRistrettoPublic>>, String> {
+    for (header, value) in headers.iter() {
+        if header == INGRESS_KEYS_HEADER {
+            return decode_ingress_keys(value).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ingress_key(byte: u8) -> CompressedRistrettoPublic {
+        CompressedRistrettoPublic::try_from(&[byte; 32]).unwrap()
+    }
+
+    #[test]
+    fn ingress_keys_round_trip() {
+        let ingress_keys = BTreeSet::from([ingress_key(1), ingress_key(2)]);
+
+        let value = encode_ingress_keys(&ingress_keys);
+
+        assert_eq!(decode_ingress_keys(value.as_bytes()).unwrap(), ingress_keys);
+    }
+
+    #[test]
+    fn decode_rejects_invalid_keys() {
+        assert!(decode_ingress_keys(b"").is_err());
+        assert!(decode_ingress_keys(b"not-hex").is_err());
+        assert!(decode_ingress_keys(b"0102").is_err());
+    }
+}
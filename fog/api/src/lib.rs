@@ -21,6 +21,10 @@ pub mod report_parse;
 
 pub mod conversions;
 
+pub mod ingress_keys;
+
+pub mod versioning;
+
 use grpcio::{CallOption, Metadata, Result as GrpcResult};
 use mc_fog_uri::{IngestPeerUri, UriParseError};
 use std::{collections::BTreeSet, str::FromStr};
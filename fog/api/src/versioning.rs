@@ -0,0 +1,45 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Versions of the fog view and fog ledger protocols.
+//!
+//! The messages clients encrypt for, or decrypt from, the view and ledger
+//! enclaves are versioned together per protocol. Servers and enclaves must
+//! keep accepting requests from, and answering in a way understood by, SDKs
+//! built against the previous version. To keep that possible:
+//!
+//! * Only add fields. Removed fields must have their tags `reserved`, and tags
+//!   must never be reused with a different meaning.
+//! * Added fields must have a default value which means the old behavior, since
+//!   older SDKs will never set them.
+//! * When a change to a client-facing message is released, bump the version
+//!   below, and record the encodings of the new version in
+//!   `tests/golden/{view,ledger}/v{N}`, by running the `proto_compatibility`
+//!   tests with `MC_UPDATE_PROTO_GOLDENS=1`. Goldens of older versions must not
+//!   be rewritten.
+//!
+//! The `proto_compatibility` tests check that the current types still encode
+//! to the current goldens, that goldens of the previous version decode to the
+//! same values with the new fields defaulted, and that the current encodings
+//! decode with the previous version's types.
+
+/// The version of the fog view client protocol.
+///
+/// * 1: The original protocol.
+/// * 2: Adds `QueryRequest.fast_forward_rngs`, so the enclave can generate the
+///   search keys itself.
+/// * 3: Adds `QueryRequest.pad_response`, so that only clients which ask for it
+///   get padded responses.
+pub const FOG_VIEW_API_VERSION: u32 = 3;
+
+/// The version of the fog ledger client protocol.
+///
+/// * 1: The original protocol.
+/// * 2: Adds `response_padding_bucket` to requests, and `padding` to responses,
+///   so that response sizes don't reveal how many results were found.
+/// * 3: Adds `ledger_num_blocks` and `serving_degraded` to key image responses,
+///   so that clients can tell key images which are not spent from ones the
+///   server has not ingested yet.
+/// * 4: Removes `response_padding_bucket` from requests. Responses are padded
+///   to the operator's `LedgerCapabilities.padding_buckets`, which clients also
+///   pad their requests to.
+pub const FOG_LEDGER_API_VERSION: u32 = 4;
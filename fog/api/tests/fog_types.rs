@@ -33,6 +33,19 @@ fn fog_view_query_request_round_trip() {
             get_txos: (0..num_txos as usize)
                 .map(|_| <[u8; 32]>::sample(&mut rng).to_vec())
                 .collect(),
+            fast_forward_rngs: (0..num_txos % 4)
+                .map(|_| mc_fog_types::view::RngFastForward {
+                    rng: StoredRng {
+                        secret: <[u8; 32]>::sample(&mut rng).to_vec(),
+                        buffer: <[u8; 32]>::sample(&mut rng).to_vec(),
+                        counter: rng.next_u64(),
+                        version: rng.next_u32(),
+                    },
+                    start_index: rng.next_u64(),
+                    num_search_keys: rng.next_u32(),
+                })
+                .collect(),
+            pad_response: rng.next_u32() % 2 == 0,
         };
         round_trip_message::<mc_fog_types::view::QueryRequest, mc_fog_api::view::QueryRequest>(
             &test_val,
@@ -51,6 +64,19 @@ fn fog_view_query_request_protobuf_round_trip() {
                 .get_txos
                 .push(<[u8; 32]>::sample(&mut rng).to_vec());
         }
+        for _ in 0..3 {
+            let mut stored_rng = kex_rng::StoredRng::new();
+            stored_rng.set_secret(<[u8; 32]>::sample(&mut rng).to_vec());
+            stored_rng.set_buffer(<[u8; 32]>::sample(&mut rng).to_vec());
+            stored_rng.set_counter(rng.next_u64());
+
+            let mut fast_forward = mc_fog_api::view::RngFastForward::new();
+            fast_forward.set_rng(stored_rng);
+            fast_forward.set_start_index(rng.next_u64());
+            fast_forward.set_num_search_keys(rng.next_u32());
+            test_val.fast_forward_rngs.push(fast_forward);
+        }
+        test_val.set_pad_response(rng.next_u32() % 2 == 0);
         round_trip_protobuf_object::<
             mc_fog_api::view::QueryRequest,
             mc_fog_types::view::QueryRequest,
@@ -73,6 +99,7 @@ fn fog_view_query_request_aad_round_trip() {
         let test_val = mc_fog_types::view::QueryRequestAAD {
             start_from_user_event_id: rng.next_u64() as i64,
             start_from_block_index: rng.next_u64(),
+            idempotency_token: rng.next_u64().to_le_bytes().to_vec(),
         };
         round_trip_message::<mc_fog_types::view::QueryRequestAAD, mc_fog_api::view::QueryRequestAAD>(
             &test_val,
@@ -99,6 +126,7 @@ fn fog_view_query_request_aad_protobuf_round_trip() {
         let mut test_val = mc_fog_api::view::QueryRequestAAD::new();
         test_val.start_from_user_event_id = rng.next_u64() as i64;
         test_val.start_from_block_index = rng.next_u64();
+        test_val.idempotency_token = rng.next_u64().to_le_bytes().to_vec();
         round_trip_protobuf_object::<
             mc_fog_api::view::QueryRequestAAD,
             mc_fog_types::view::QueryRequestAAD,
@@ -266,6 +294,10 @@ fn check_key_images_response_round_trip() {
         let mut test_val = mc_fog_types::ledger::CheckKeyImagesResponse {
             num_blocks: rng.next_u32() as u64,
             global_txo_count: rng.next_u32() as u64,
+            padding: vec![0; (rng.next_u32() % 64) as usize],
+            ledger_num_blocks: rng.next_u32() as u64,
+            serving_degraded: rng.next_u32() % 2 == 0,
+            signature: vec![0x55; 64],
             ..Default::default()
         };
         for _ in 0..20 {
@@ -281,6 +313,32 @@ fn check_key_images_response_round_trip() {
     });
 }
 
+/// Test that many random instances of prosty LedgerCapabilities round trip
+/// with protobufy LedgerCapabilities
+#[test]
+fn ledger_capabilities_round_trip() {
+    round_trip_message::<
+        mc_fog_types::ledger::LedgerCapabilities,
+        mc_fog_api::ledger::LedgerCapabilities,
+    >(&mc_fog_types::ledger::LedgerCapabilities::default());
+
+    run_with_several_seeds(|mut rng| {
+        let test_val = mc_fog_types::ledger::LedgerCapabilities {
+            version: rng.next_u32(),
+            query_types: (0..rng.next_u32() % 4).map(|_| rng.next_u32()).collect(),
+            max_key_images_per_request: rng.next_u32(),
+            max_outputs_per_request: rng.next_u32(),
+            padding_modes: (0..rng.next_u32() % 4).map(|_| rng.next_u32()).collect(),
+            padding_buckets: (0..rng.next_u32() % 4).map(|_| rng.next_u32()).collect(),
+        };
+
+        round_trip_message::<
+            mc_fog_types::ledger::LedgerCapabilities,
+            mc_fog_api::ledger::LedgerCapabilities,
+        >(&test_val);
+    });
+}
+
 /// Test that .proto enum values match what is in
 /// src/fog/recovery_db_iface/src/types.rs
 #[test]
@@ -324,6 +382,40 @@ fn test_key_image_result_code_enum_values() {
     );
 }
 
+/// Test that .proto enum values match what is in src/fog_types/ledger.rs
+#[test]
+fn test_output_result_code_enum_values() {
+    assert_eq!(
+        mc_fog_types::ledger::OutputResultCode::DoesNotExist as u32,
+        mc_fog_api::ledger::OutputResultCode::DoesNotExist as u32
+    );
+    assert_eq!(
+        mc_fog_types::ledger::OutputResultCode::Exists as u32,
+        mc_fog_api::ledger::OutputResultCode::Exists as u32
+    );
+    assert_eq!(
+        mc_fog_types::ledger::OutputResultCode::OutputDatabaseError as u32,
+        mc_fog_api::ledger::OutputResultCode::OutputDatabaseError as u32
+    );
+}
+
+/// Test that .proto enum values match what is in src/fog_types/ledger.rs
+#[test]
+fn test_ledger_capability_enum_values() {
+    assert_eq!(
+        mc_fog_types::ledger::LedgerQueryType::CheckKeyImages as u32,
+        mc_fog_api::ledger::LedgerQueryType::CheckKeyImages as u32
+    );
+    assert_eq!(
+        mc_fog_types::ledger::LedgerQueryType::GetOutputs as u32,
+        mc_fog_api::ledger::LedgerQueryType::GetOutputs as u32
+    );
+    assert_eq!(
+        mc_fog_types::ledger::LedgerPaddingMode::SizeBuckets as u32,
+        mc_fog_api::ledger::LedgerPaddingMode::SizeBuckets as u32
+    );
+}
+
 // Test that KexRngPubkey is a subset of its proto
 #[test]
 fn test_kex_rng_pubkey_round_trip() {
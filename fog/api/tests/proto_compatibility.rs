@@ -0,0 +1,291 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+// Test that the fog view and ledger client messages stay compatible with SDKs
+// built against the previous protocol version, by checking them against the
+// encodings recorded for each version in tests/golden.
+//
+// Run with MC_UPDATE_PROTO_GOLDENS=1 to rewrite the goldens of the current
+// version, after bumping it. See mc_fog_api::versioning.
+
+use mc_fog_api::versioning::{FOG_LEDGER_API_VERSION, FOG_VIEW_API_VERSION};
+use mc_fog_kex_rng::StoredRng;
+use mc_fog_report_api_test_utils::round_trip_message;
+use mc_fog_types::{
+    ledger::{CheckKeyImagesRequest, CheckKeyImagesResponse, KeyImageQuery, KeyImageResult},
+    view::{QueryRequest, QueryRequestAAD, RngFastForward},
+};
+use mc_transaction_core::ring_signature::KeyImage;
+use prost::Message;
+use std::{env, fmt::Debug, fs, path::PathBuf};
+
+/// The view client messages of version 1, with only the fields they had then.
+mod v1 {
+    use prost::Message;
+
+    #[derive(Clone, Eq, PartialEq, Message)]
+    pub struct QueryRequestAAD {
+        #[prost(int64, tag = "1")]
+        pub start_from_user_event_id: i64,
+        #[prost(uint64, tag = "2")]
+        pub start_from_block_index: u64,
+    }
+}
+
+/// The view and ledger client messages of version 2, with only the fields
+/// they had then.
+mod v2 {
+    use mc_fog_types::view::RngFastForward;
+    use mc_transaction_core::ring_signature::KeyImage;
+    use prost::Message;
+
+    #[derive(Clone, Eq, PartialEq, Message)]
+    pub struct QueryRequest {
+        #[prost(bytes, repeated, tag = "1")]
+        pub get_txos: Vec<Vec<u8>>,
+        #[prost(message, repeated, tag = "2")]
+        pub fast_forward_rngs: Vec<RngFastForward>,
+    }
+
+    #[derive(Clone, Eq, PartialEq, Message)]
+    pub struct CheckKeyImagesRequest {
+        #[prost(message, repeated, tag = "1")]
+        pub queries: Vec<KeyImageQuery>,
+        #[prost(uint32, tag = "2")]
+        pub response_padding_bucket: u32,
+    }
+
+    #[derive(Clone, Eq, PartialEq, Message)]
+    pub struct KeyImageQuery {
+        #[prost(message, required, tag = "1")]
+        pub key_image: KeyImage,
+        #[prost(fixed64, tag = "2")]
+        pub start_block: u64,
+    }
+
+    #[derive(Clone, Eq, PartialEq, Message)]
+    pub struct CheckKeyImagesResponse {
+        #[prost(uint64, tag = "1")]
+        pub num_blocks: u64,
+        #[prost(uint64, tag = "2")]
+        pub global_txo_count: u64,
+        #[prost(message, repeated, tag = "3")]
+        pub results: Vec<KeyImageResult>,
+        #[prost(uint32, tag = "4")]
+        pub latest_block_version: u32,
+        #[prost(uint32, tag = "5")]
+        pub max_block_version: u32,
+        #[prost(bytes, tag = "6")]
+        pub padding: Vec<u8>,
+    }
+
+    #[derive(Clone, Eq, PartialEq, Message)]
+    pub struct KeyImageResult {
+        #[prost(message, required, tag = "1")]
+        pub key_image: KeyImage,
+        #[prost(fixed64, tag = "2")]
+        pub spent_at: u64,
+        #[prost(fixed64, tag = "3")]
+        pub timestamp: u64,
+        #[prost(fixed32, tag = "4")]
+        pub timestamp_result_code: u32,
+        #[prost(fixed32, tag = "5")]
+        pub key_image_result_code: u32,
+    }
+}
+
+fn golden_path(api: &str, version: u32, name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(api)
+        .join(format!("v{version}"))
+        .join(format!("{name}.hex"))
+}
+
+fn read_golden(api: &str, version: u32, name: &str) -> Vec<u8> {
+    let path = golden_path(api, version, name);
+    let contents = fs::read_to_string(&path)
+        .unwrap_or_else(|err| panic!("Could not read golden {}: {err}", path.display()));
+    hex::decode(contents.trim())
+        .unwrap_or_else(|err| panic!("Golden {} is not hex: {err}", path.display()))
+}
+
+/// Check that `value` encodes to the golden of the current version, or record
+/// it as that golden when MC_UPDATE_PROTO_GOLDENS is set.
+fn check_current_golden<M: Message>(api: &str, version: u32, name: &str, value: &M) {
+    let bytes = mc_util_serial::encode(value);
+    if env::var_os("MC_UPDATE_PROTO_GOLDENS").is_some() {
+        let path = golden_path(api, version, name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, hex::encode(&bytes) + "\n").unwrap();
+        return;
+    }
+    assert_eq!(
+        hex::encode(&bytes),
+        hex::encode(read_golden(api, version, name)),
+        "The encoding of {api} {name} changed without bumping the protocol version"
+    );
+}
+
+/// Check that the golden of the previous version decodes to `expected`.
+fn check_previous_golden<M: Message + Default + Debug + PartialEq>(
+    api: &str,
+    version: u32,
+    name: &str,
+    expected: &M,
+) {
+    let decoded: M = mc_util_serial::decode(&read_golden(api, version - 1, name))
+        .unwrap_or_else(|err| panic!("Could not decode {api} v{} {name}: {err}", version - 1));
+    assert_eq!(&decoded, expected);
+}
+
+/// Check that the current encoding of `value` decodes with the previous
+/// version's types to `expected`.
+fn check_previous_decodes<M: Message, Old: Message + Default + Debug + PartialEq>(
+    value: &M,
+    expected: &Old,
+) {
+    let decoded: Old = mc_util_serial::decode(&mc_util_serial::encode(value))
+        .expect("Previous version could not decode the current encoding");
+    assert_eq!(&decoded, expected);
+}
+
+fn key_image(byte: u8) -> KeyImage {
+    KeyImage::try_from([byte; 32]).unwrap()
+}
+
+fn key_image_queries() -> Vec<KeyImageQuery> {
+    vec![
+        KeyImageQuery {
+            key_image: key_image(7),
+            start_block: 100,
+        },
+        KeyImageQuery {
+            key_image: key_image(8),
+            start_block: 0,
+        },
+    ]
+}
+
+#[test]
+fn check_key_images_request_is_compatible() {
+    let current = CheckKeyImagesRequest {
+        queries: key_image_queries(),
+    };
+    let previous = v2::CheckKeyImagesRequest {
+        queries: current
+            .queries
+            .iter()
+            .map(|query| v2::KeyImageQuery {
+                key_image: query.key_image,
+                start_block: query.start_block,
+            })
+            .collect(),
+        response_padding_bucket: 0,
+    };
+
+    let name = "check_key_images_request";
+    check_current_golden("ledger", FOG_LEDGER_API_VERSION, name, &current);
+    round_trip_message::<_, mc_fog_api::ledger::CheckKeyImagesRequest>(&current);
+    check_previous_golden("ledger", FOG_LEDGER_API_VERSION, name, &current);
+    check_previous_decodes(&current, &previous);
+}
+
+#[test]
+fn check_key_images_response_is_compatible() {
+    let current = CheckKeyImagesResponse {
+        num_blocks: 1000,
+        global_txo_count: 5000,
+        results: vec![KeyImageResult {
+            key_image: key_image(7),
+            spent_at: 900,
+            timestamp: 1700000000,
+            timestamp_result_code: 1,
+            key_image_result_code: 1,
+        }],
+        latest_block_version: 3,
+        max_block_version: 4,
+        padding: vec![0; 8],
+        ledger_num_blocks: 1200,
+        serving_degraded: true,
+        signature: vec![],
+    };
+    let previous = v2::CheckKeyImagesResponse {
+        num_blocks: current.num_blocks,
+        global_txo_count: current.global_txo_count,
+        results: current
+            .results
+            .iter()
+            .map(|result| v2::KeyImageResult {
+                key_image: result.key_image,
+                spent_at: result.spent_at,
+                timestamp: result.timestamp,
+                timestamp_result_code: result.timestamp_result_code,
+                key_image_result_code: result.key_image_result_code,
+            })
+            .collect(),
+        latest_block_version: current.latest_block_version,
+        max_block_version: current.max_block_version,
+        padding: current.padding.clone(),
+    };
+
+    let name = "check_key_images_response";
+    check_current_golden("ledger", FOG_LEDGER_API_VERSION, name, &current);
+    round_trip_message::<_, mc_fog_api::ledger::CheckKeyImagesResponse>(&current);
+    check_previous_golden("ledger", FOG_LEDGER_API_VERSION, name, &current);
+    check_previous_decodes(&current, &previous);
+}
+
+#[test]
+fn view_query_request_is_compatible() {
+    let current = QueryRequest {
+        get_txos: vec![vec![0x11; 32], vec![0x12; 32]],
+        fast_forward_rngs: vec![RngFastForward {
+            rng: StoredRng {
+                secret: vec![0x22; 32],
+                buffer: vec![0x33; 32],
+                counter: 5,
+                version: 1,
+            },
+            start_index: 100,
+            num_search_keys: 10,
+        }],
+        pad_response: true,
+    };
+    let previous = v2::QueryRequest {
+        get_txos: current.get_txos.clone(),
+        fast_forward_rngs: current.fast_forward_rngs.clone(),
+    };
+
+    let name = "query_request";
+    check_current_golden("view", FOG_VIEW_API_VERSION, name, &current);
+    round_trip_message::<_, mc_fog_api::view::QueryRequest>(&current);
+    check_previous_golden(
+        "view",
+        FOG_VIEW_API_VERSION,
+        name,
+        &QueryRequest {
+            pad_response: false,
+            ..current.clone()
+        },
+    );
+    check_previous_decodes(&current, &previous);
+}
+
+#[test]
+fn view_query_request_aad_is_compatible() {
+    let current = QueryRequestAAD {
+        start_from_user_event_id: 42,
+        start_from_block_index: 7,
+        idempotency_token: vec![],
+    };
+    let previous = v1::QueryRequestAAD {
+        start_from_user_event_id: 42,
+        start_from_block_index: 7,
+    };
+
+    let name = "query_request_aad";
+    check_current_golden("view", FOG_VIEW_API_VERSION, name, &current);
+    round_trip_message::<_, mc_fog_api::view::QueryRequestAAD>(&current);
+    check_previous_golden("view", FOG_VIEW_API_VERSION, name, &current);
+    check_previous_decodes(&current, &previous);
+}
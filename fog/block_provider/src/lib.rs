@@ -11,7 +11,7 @@ use dyn_clone::DynClone;
 use mc_blockchain_types::{Block, BlockData, BlockIndex};
 use mc_crypto_keys::CompressedRistrettoPublic;
 use mc_fog_api::ledger::TxOutResult;
-use mc_transaction_core::tx::{TxOut, TxOutMembershipProof};
+use mc_transaction_core::tx::{TxOut, TxOutMembershipElement, TxOutMembershipProof};
 use mc_watcher_api::TimestampResultCode;
 use std::time::Duration;
 
@@ -48,6 +48,65 @@ pub trait BlockProvider: DynClone + Send + Sync {
         tx_out_pub_keys: &[CompressedRistrettoPublic],
     ) -> Result<TxOutInfoByPublicKeyResponse, Error>;
 
+    /// Get the cumulative TxOut counts of multiple blocks by block number, and
+    /// in addition get information about the latest block.
+    ///
+    /// The default implementation fetches the full block data, providers
+    /// which can read block headers alone should override it.
+    fn get_cumulative_txo_counts(
+        &self,
+        block_indices: &[BlockIndex],
+    ) -> Result<CumulativeTxoCountsResponse, Error> {
+        let BlocksDataResponse {
+            results,
+            latest_block,
+        } = self.get_blocks_data(block_indices)?;
+
+        let results = results
+            .into_iter()
+            .map(|result| result.map(|b| b.block_data.block().cumulative_txo_count))
+            .collect();
+
+        Ok(CumulativeTxoCountsResponse {
+            results,
+            latest_block,
+        })
+    }
+
+    /// Get the root elements of multiple blocks by block number, and in
+    /// addition get information about the latest block.
+    ///
+    /// The default implementation fetches the full block data, providers
+    /// which can read block headers alone should override it.
+    fn get_root_elements(
+        &self,
+        block_indices: &[BlockIndex],
+    ) -> Result<RootElementsResponse, Error> {
+        let BlocksDataResponse {
+            results,
+            latest_block,
+        } = self.get_blocks_data(block_indices)?;
+
+        let results = results
+            .into_iter()
+            .map(|result| {
+                result.map(|b| {
+                    let block = b.block_data.block();
+                    BlockRootElement {
+                        root_element: block.root_element.clone(),
+                        txo_count: block.cumulative_txo_count
+                            - b.block_data.contents().outputs.len() as u64,
+                    }
+                })
+            })
+            .collect();
+
+        Ok(RootElementsResponse {
+            results,
+            latest_block,
+        })
+    }
+
     /// Convenience method to get a single block data by block number.
     fn get_block_data(&self, block_index: BlockIndex) -> Result<BlockDataResponse, Error> {
         let BlocksDataResponse {
@@ -96,6 +155,34 @@ pub struct BlockDataResponse {
     pub latest_block: Block,
 }
 
+#[derive(Clone, Debug)]
+pub struct CumulativeTxoCountsResponse {
+    /// The cumulative TxOut count of each block, if it is in the ledger.
+    pub results: Vec<Option<u64>>,
+
+    /// The latest block.
+    pub latest_block: Block,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BlockRootElement {
+    /// The root element of the TxOut membership tree the block was validated
+    /// against.
+    pub root_element: TxOutMembershipElement,
+
+    /// The number of TxOuts in that tree, i.e. in the blocks before the block.
+    pub txo_count: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct RootElementsResponse {
+    /// The root element of each block, if it is in the ledger.
+    pub results: Vec<Option<BlockRootElement>>,
+
+    /// The latest block.
+    pub latest_block: Block,
+}
+
 #[derive(Clone, Debug)]
 pub struct TxOutInfoByPublicKeyResponse {
     /// Reuslts.
@@ -1,7 +1,8 @@
 // Copyright (c) 2018-2023 The MobileCoin Foundation
 
 use crate::{
-    BlockDataWithTimestamp, BlockProvider, BlocksDataResponse, Error, TxOutInfoByPublicKeyResponse,
+    BlockDataWithTimestamp, BlockProvider, BlockRootElement, BlocksDataResponse,
+    CumulativeTxoCountsResponse, Error, RootElementsResponse, TxOutInfoByPublicKeyResponse,
 };
 use mc_blockchain_types::{Block, BlockIndex};
 use mc_crypto_keys::CompressedRistrettoPublic;
@@ -124,6 +125,60 @@ impl<L: Ledger + Clone + Sync> BlockProvider for LocalBlockProvider<L> {
         })
     }
 
+    fn get_cumulative_txo_counts(
+        &self,
+        block_indices: &[BlockIndex],
+    ) -> Result<CumulativeTxoCountsResponse, Error> {
+        let latest_block = self.ledger.get_latest_block()?;
+
+        let results = block_indices
+            .iter()
+            .map(|block_index| match self.ledger.get_block(*block_index) {
+                Ok(block) => Ok(Some(block.cumulative_txo_count)),
+                Err(LedgerError::NotFound) => Ok(None),
+                Err(err) => Err(err),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(CumulativeTxoCountsResponse {
+            results,
+            latest_block,
+        })
+    }
+
+    fn get_root_elements(
+        &self,
+        block_indices: &[BlockIndex],
+    ) -> Result<RootElementsResponse, Error> {
+        let latest_block = self.ledger.get_latest_block()?;
+
+        let results = block_indices
+            .iter()
+            .map(|block_index| match self.ledger.get_block(*block_index) {
+                Ok(block) => {
+                    // The tree holds the TxOuts of the blocks before this one.
+                    let txo_count = match block_index.checked_sub(1) {
+                        Some(parent_index) => {
+                            self.ledger.get_block(parent_index)?.cumulative_txo_count
+                        }
+                        None => 0,
+                    };
+                    Ok(Some(BlockRootElement {
+                        root_element: block.root_element,
+                        txo_count,
+                    }))
+                }
+                Err(LedgerError::NotFound) => Ok(None),
+                Err(err) => Err(err),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(RootElementsResponse {
+            results,
+            latest_block,
+        })
+    }
+
     fn poll_block_timestamp(&self, block_index: BlockIndex, watcher_timeout: Duration) -> u64 {
         self.watcher
             .as_ref()
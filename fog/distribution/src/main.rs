@@ -637,6 +637,7 @@ fn submit_tx(
                 if let ConnectionError::TransactionValidation(
                     ProposeTxResult::TombstoneBlockExceeded,
                     _,
+                    _,
                 ) = error
                 {
                     log::debug!(
@@ -647,6 +648,7 @@ fn submit_tx(
                 if let ConnectionError::TransactionValidation(
                     ProposeTxResult::ContainsSpentKeyImage,
                     _,
+                    _,
                 ) = error
                 {
                     log::info!(
@@ -1,18 +1,26 @@
 // Copyright (c) 2018-2022 The MobileCoin Foundation
 
+use crate::TransportError;
 use displaydoc::Display;
 use grpcio::RpcStatusCode;
 use mc_attest_ake::Error as AkeError;
+use mc_attest_verifier::AttestationDiagnostic;
 use mc_connection::AttestationError;
 use mc_crypto_noise::CipherError;
+use mc_util_grpc::MessageTooLarge;
 use mc_util_serial::DecodeError;
 use mc_util_uri::UriConversionError;
+use std::time::Duration;
 
 /// An error that can occur when using EnclaveConnection
 #[derive(Display, Debug)]
 pub enum Error {
     /// gRPC Error: {0}
     Rpc(grpcio::Error),
+    /// gRPC message size limit: {0}
+    MessageTooLarge(MessageTooLarge),
+    /// Transport error: {0}
+    Transport(TransportError),
     /// Attestation AKE error: {0}
     Ake(AkeError),
     /// mc-crypto-noise cipher error: {0}
@@ -23,32 +31,116 @@ pub enum Error {
     ProtoDecode(DecodeError),
     /// Other: {0}
     Other(String),
+    /// {error} ({context})
+    InContext {
+        /// The underlying error
+        error: Box<Error>,
+        /// Where and when the failed request was made
+        context: ErrorContext,
+    },
+}
+
+/// endpoint {endpoint}, attempt {attempt}, after {elapsed:?}
+#[derive(Clone, Debug, Display, Eq, PartialEq)]
+pub struct ErrorContext {
+    /// The host:port the request was sent to. This leaves out the rest of the
+    /// URI, which may hold credentials.
+    pub endpoint: String,
+    /// Which attempt at the request failed, starting from 1
+    pub attempt: u64,
+    /// The time from the start of the first attempt to this failure
+    pub elapsed: Duration,
+}
+
+impl Error {
+    /// Attach the context of the request that failed, replacing any context
+    /// attached before.
+    pub fn with_context(self, context: ErrorContext) -> Self {
+        Self::InContext {
+            error: Box::new(self.into_inner()),
+            context,
+        }
+    }
+
+    /// The context of the request that failed, if known.
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Self::InContext { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// The error without its context.
+    pub fn inner(&self) -> &Error {
+        match self {
+            Self::InContext { error, .. } => error,
+            other => other,
+        }
+    }
+
+    /// Why the enclave's attestation evidence was rejected, if that is why
+    /// the request failed. This shows which measurement, advisory or TCB
+    /// level the client's trusted identities didn't accept.
+    pub fn attestation_diagnostic(&self) -> Option<&AttestationDiagnostic> {
+        match self.inner() {
+            Self::Ake(err) => err.attestation_diagnostic(),
+            _ => None,
+        }
+    }
+
+    /// The error without its context.
+    pub fn into_inner(self) -> Error {
+        match self {
+            Self::InContext { error, .. } => *error,
+            other => other,
+        }
+    }
 }
 
 impl AttestationError for Error {
     fn should_reattest(&self) -> bool {
-        matches!(self, Self::Rpc(_) | Self::Ake(_) | Self::Cipher(_))
+        matches!(
+            self.inner(),
+            Self::Rpc(_)
+                | Self::MessageTooLarge(_)
+                | Self::Transport(_)
+                | Self::Ake(_)
+                | Self::Cipher(_)
+        )
     }
 
     fn should_retry(&self) -> bool {
-        match self {
+        match self.inner() {
             Error::Rpc(grpcio::Error::RpcFailure(rpc_status)) => {
-                // Retry but only if the error code is not RESOURCE_EXHAUSTED, which is what is
-                // returned when the response size is too large
+                // Retry but only if the error code is not RESOURCE_EXHAUSTED, e.g. from a
+                // rate limit
                 rpc_status.code() != RpcStatusCode::RESOURCE_EXHAUSTED
             }
+            // The same message would be rejected again
+            Error::MessageTooLarge(_) => false,
             Error::Rpc(_) | Error::Cipher(_) | Error::ProtoDecode(_) => true,
+            Error::Transport(err) => err.is_retriable(),
             Error::Ake(AkeError::AttestationEvidenceVerification(_)) => false,
             Error::Ake(_) => true,
             Error::InvalidUri(_) => false,
             Error::Other(_) => false,
+            Error::InContext { error, .. } => error.should_retry(),
         }
     }
 }
 
 impl From<grpcio::Error> for Error {
     fn from(err: grpcio::Error) -> Self {
-        Error::Rpc(err)
+        match MessageTooLarge::from_grpc_error(&err) {
+            Some(too_large) => Error::MessageTooLarge(too_large),
+            None => Error::Rpc(err),
+        }
+    }
+}
+
+impl From<TransportError> for Error {
+    fn from(err: TransportError) -> Self {
+        Error::Transport(err)
     }
 }
 
@@ -75,3 +167,57 @@ impl From<DecodeError> for Error {
         Error::ProtoDecode(src)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn context_is_attached_once() {
+        let context = |attempt| ErrorContext {
+            endpoint: "localhost:3223".to_owned(),
+            attempt,
+            elapsed: Duration::from_millis(40),
+        };
+        let err = Error::from(TransportError::retriable("connection reset"))
+            .with_context(context(1))
+            .with_context(context(2));
+
+        assert_eq!(err.context(), Some(&context(2)));
+        assert!(matches!(err.inner(), Error::Transport(_)));
+        assert!(err.should_retry());
+        assert!(err.should_reattest());
+        assert_eq!(
+            err.to_string(),
+            "Transport error: connection reset \
+             (endpoint localhost:3223, attempt 2, after 40ms)"
+        );
+    }
+    #[test]
+    fn attestation_diagnostic_survives_context() {
+        let diagnostic = AttestationDiagnostic {
+            mr_enclave: "01".repeat(32),
+            mr_signer: "02".repeat(32),
+            isv_product_id: 1,
+            isv_svn: 2,
+            identities: vec![],
+            failed_checks: vec!["The allowed advisories are IDs: (none)".to_owned()],
+            verification_tree: String::new(),
+        };
+        let err = Error::from(AkeError::AttestationEvidenceVerification(
+            mc_attest_verifier::Error::Rejected(Box::new(diagnostic.clone())),
+        ))
+        .with_context(ErrorContext {
+            endpoint: "localhost:3223".to_owned(),
+            attempt: 1,
+            elapsed: Duration::from_millis(40),
+        });
+
+        assert_eq!(err.attestation_diagnostic(), Some(&diagnostic));
+        assert!(!err.should_retry());
+        assert_eq!(
+            Error::Other("unrelated".to_owned()).attestation_diagnostic(),
+            None
+        );
+    }
+}
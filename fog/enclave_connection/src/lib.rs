@@ -2,14 +2,13 @@
 
 #![allow(clippy::result_large_err)]
 use aes_gcm::Aes256Gcm;
-use cookie::CookieJar;
+use cookie::{Cookie, CookieJar};
 use core::{
     cmp::Ordering,
     fmt::{Display, Formatter, Result as FmtResult},
     hash::{Hash, Hasher},
 };
 use der::DateTime;
-use grpcio::{CallOption, Metadata, MetadataBuilder, Result as GrpcResult};
 use mc_attest_ake::{AuthResponseInput, ClientInitiate, Ready, Start, Transition};
 use mc_attest_api::attest::{AuthMessage, Message};
 use mc_attest_core::EvidenceKind;
@@ -22,59 +21,58 @@ use mc_common::{
 use mc_connection::{AttestationError, AttestedConnection, Connection};
 use mc_crypto_keys::X25519;
 use mc_rand::McRng;
-use mc_util_grpc::{BasicCredentials, GrpcCookieStore, CHAIN_ID_GRPC_HEADER};
+use mc_util_grpc::{BasicCredentials, CHAIN_ID_GRPC_HEADER};
 use mc_util_uri::ConnectionUri;
 use retry::OperationResult;
 use sha2::Sha512;
+use shaping::ConstantRateShaper;
+use std::{thread, time::Instant};
 
 mod error;
-pub use error::Error;
-
-/// Abstracts the auth and enclave_request aspects of a grpc channel used for
-/// attested connections
-///
-/// These calls:
-/// - Take a message type appropriate to the service
-/// - Take a CallOption object containing credentials info and cookies
-/// - Return a message type appropriate to the service, as well as two metadata
-///   objects, the first one containing grpc headers, and second one containing
-///   grpc trailers.
-pub trait EnclaveGrpcChannel: Send + Sync {
-    fn auth(
-        &mut self,
-        msg: &AuthMessage,
-        call_option: CallOption,
-    ) -> GrpcResult<(Metadata, AuthMessage, Metadata)>;
-    fn enclave_request(
-        &mut self,
-        ciphertext: &Message,
-        call_option: CallOption,
-    ) -> GrpcResult<(Metadata, Message, Metadata)>;
-}
+mod shaping;
+mod transport;
+
+pub use error::{Error, ErrorContext};
+pub use shaping::ConstantRateConfig;
+pub use transport::{
+    AttestedTransport, EnclaveGrpcChannel, Headers, TransportError, TransportResponse,
+};
 
 /// A generic object representing an attested connection to a remote enclave
-pub struct EnclaveConnection<U: ConnectionUri, G: EnclaveGrpcChannel> {
+pub struct EnclaveConnection<U: ConnectionUri, T: AttestedTransport> {
     /// Chain id, ignored if empty
     chain_id: String,
     /// The URI we are connecting to, and which provides the ResponderId
     uri: U,
-    /// Abstraction of one or more grpc connections
-    grpc: G,
+    /// Abstraction of one or more grpc (or other RPC) connections
+    transport: T,
     /// The AKE state machine object, if one is available.
     attest_cipher: Option<Ready<Aes256Gcm>>,
+    /// The evidence the enclave presented for the current session, and the
+    /// time it was verified at.
+    attestation: Option<(EvidenceKind, DateTime)>,
     /// The identities that a fog node's attestation evidence must match, one of
     identities: Vec<TrustedIdentity>,
-    /// Credentials to use for all GRPC calls (this allows authentication
+    /// Credentials to use for all calls (this allows authentication
     /// username/password to go through, if provided).
     creds: BasicCredentials,
-    /// A hash map of metadata to set on outbound requests, filled by inbound
-    /// `Set-Cookie` metadata
+    /// Cookies to send with outbound requests, filled by inbound `Set-Cookie`
+    /// headers
     cookies: CookieJar,
+    /// Constant-rate traffic shaping, if enabled
+    shaper: Option<ConstantRateShaper>,
+    /// Additional headers to send with every request
+    extra_headers: Vec<(String, String)>,
+    /// Application capabilities to offer the enclave when attesting
+    capabilities: Vec<u8>,
+    /// Pads the plaintext of each request, given the application capabilities
+    /// the enclave agreed to
+    request_padding: Option<fn(&[u8], &mut Vec<u8>)>,
     /// Logger
     logger: Logger,
 }
 
-impl<U: ConnectionUri, G: EnclaveGrpcChannel> Connection for EnclaveConnection<U, G> {
+impl<U: ConnectionUri, T: AttestedTransport> Connection for EnclaveConnection<U, T> {
     type Uri = U;
 
     fn uri(&self) -> Self::Uri {
@@ -82,7 +80,7 @@ impl<U: ConnectionUri, G: EnclaveGrpcChannel> Connection for EnclaveConnection<U
     }
 }
 
-impl<U: ConnectionUri, G: EnclaveGrpcChannel> AttestedConnection for EnclaveConnection<U, G> {
+impl<U: ConnectionUri, T: AttestedTransport> AttestedConnection for EnclaveConnection<U, T> {
     type Error = Error;
 
     fn is_attested(&self) -> bool {
@@ -98,25 +96,15 @@ impl<U: ConnectionUri, G: EnclaveGrpcChannel> AttestedConnection for EnclaveConn
 
         let initiator = Start::new(self.uri.responder_id()?.to_string());
 
-        let init_input = ClientInitiate::<X25519, Aes256Gcm, Sha512>::default();
+        let init_input = ClientInitiate::<X25519, Aes256Gcm, Sha512>::default()
+            .with_capabilities(self.capabilities.clone());
         let (initiator, auth_request_output) = initiator.try_next(&mut csprng, init_input)?;
 
         // Make the auth request with the server
-        let call_opt = self.call_option();
-        let (header, auth_response_msg, trailer) =
-            self.grpc.auth(&auth_request_output.into(), call_opt)?;
-
-        // Update cookies from server-sent metadata
-        if let Err(e) = self
-            .cookies
-            .update_from_server_metadata(Some(&header), Some(&trailer))
-        {
-            log::warn!(
-                self.logger,
-                "Could not update cookies from gRPC metadata: {}",
-                e
-            )
-        }
+        let headers = self.request_headers();
+        let response = self.transport.auth(&auth_request_output.into(), &headers)?;
+        self.update_cookies(&response.headers);
+        let auth_response_msg = response.message;
 
         let epoch_time = SystemTimeProvider
             .since_epoch()
@@ -129,6 +117,7 @@ impl<U: ConnectionUri, G: EnclaveGrpcChannel> AttestedConnection for EnclaveConn
         let (initiator, evidence) = initiator.try_next(&mut csprng, auth_response_event)?;
 
         self.attest_cipher = Some(initiator);
+        self.attestation = Some((evidence.clone(), time));
 
         Ok(evidence)
     }
@@ -140,16 +129,17 @@ impl<U: ConnectionUri, G: EnclaveGrpcChannel> AttestedConnection for EnclaveConn
                 "Tearing down existing attested connection and clearing cookies."
             );
             self.attest_cipher = None;
+            self.attestation = None;
             self.cookies = CookieJar::default();
         }
     }
 }
 
-impl<U: ConnectionUri, G: EnclaveGrpcChannel> EnclaveConnection<U, G> {
+impl<U: ConnectionUri, T: AttestedTransport> EnclaveConnection<U, T> {
     pub fn new(
         chain_id: String,
         uri: U,
-        grpc: G,
+        transport: T,
         identities: impl Into<Vec<TrustedIdentity>>,
         logger: Logger,
     ) -> Self {
@@ -159,45 +149,112 @@ impl<U: ConnectionUri, G: EnclaveGrpcChannel> EnclaveConnection<U, G> {
         Self {
             chain_id,
             uri,
-            grpc,
+            transport,
             attest_cipher: None,
+            attestation: None,
             identities: identities.into(),
             creds,
             cookies,
+            shaper: None,
+            extra_headers: Vec::new(),
+            capabilities: Vec::new(),
+            request_padding: None,
             logger,
         }
     }
 
-    /// Produce a "call option" object appropriate for this grpc connection.
-    /// This includes the http headers needed for credentials and cookies.
-    pub fn call_option(&mut self) -> CallOption {
-        let retval = CallOption::default();
+    /// Send an additional header with every request on this connection.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.extra_headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Offer the enclave these application capabilities, encoded in a way
+    /// the enclave understands, whenever this connection attests. What it
+    /// agrees to can be read with [EnclaveConnection::remote_capabilities].
+    pub fn with_capabilities(mut self, capabilities: Vec<u8>) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Pad the plaintext of each request with `pad`, which is given the
+    /// application capabilities the enclave agreed to, e.g. to pad requests to
+    /// the sizes the enclave asked for. Constant-rate shaping pads the result.
+    pub fn with_request_padding(mut self, pad: fn(&[u8], &mut Vec<u8>)) -> Self {
+        self.request_padding = Some(pad);
+        self
+    }
+
+    /// Send requests on this connection at a constant rate, padded to a fixed
+    /// block size, so that a network observer can't easily tell when the
+    /// client is active. See [ConstantRateConfig].
+    pub fn with_constant_rate(mut self, config: ConstantRateConfig) -> Self {
+        self.shaper = Some(ConstantRateShaper::new(config));
+        self
+    }
+
+    /// The evidence the enclave presented for the current session, and the
+    /// time it was verified at, if the connection is attested.
+    pub fn attestation(&self) -> Option<(&EvidenceKind, DateTime)> {
+        self.attestation
+            .as_ref()
+            .map(|(evidence, time)| (evidence, *time))
+    }
+
+    /// The application capabilities the enclave agreed to for the current
+    /// session, if the connection is attested. These are empty if it sent
+    /// none.
+    pub fn remote_capabilities(&self) -> Option<&[u8]> {
+        self.attest_cipher
+            .as_ref()
+            .map(|cipher| cipher.remote_capabilities())
+    }
 
-        // Create metadata from cookies and credentials
-        let mut metadata_builder = self
-            .cookies
-            .to_client_metadata()
-            .unwrap_or_else(|_| MetadataBuilder::new());
+    /// Produce the headers to send with requests on this connection.
+    /// This includes the headers needed for credentials and cookies.
+    pub fn request_headers(&self) -> Headers {
+        let mut headers = Headers::new();
+        for cookie in self.cookies.iter() {
+            headers.add("Cookie", cookie.to_string());
+        }
         if !self.creds.username().is_empty() && !self.creds.password().is_empty() {
-            metadata_builder
-                .add_str("Authorization", &self.creds.authorization_header())
-                .expect("Error setting authorization header");
+            headers.add("Authorization", self.creds.authorization_header());
         }
 
         // Add the chain id header if we have a chain id specified
         if !self.chain_id.is_empty() {
-            metadata_builder
-                .add_str(CHAIN_ID_GRPC_HEADER, &self.chain_id)
-                .expect("Could not add chain-id header");
+            headers.add(CHAIN_ID_GRPC_HEADER, &self.chain_id);
         }
 
-        retval.headers(metadata_builder.build())
+        for (name, value) in self.extra_headers.iter() {
+            headers.add(name, value);
+        }
+
+        headers
+    }
+
+    /// Add the cookies set by a response to the ones we send with requests.
+    fn update_cookies(&mut self, headers: &Headers) {
+        for value in headers.get_all("set-cookie") {
+            match Cookie::parse(value.to_owned()) {
+                Ok(cookie) => self.cookies.add(cookie),
+                Err(err) => log::warn!(
+                    self.logger,
+                    "Could not parse Set-Cookie header {:?}: {}",
+                    value,
+                    err
+                ),
+            }
+        }
     }
 
     /// Make an attested request to the enclave, given the plaintext to go to
     /// enclave, and any aad data, which will be nonmalleable, but visible
     /// to untrusted. Returns the decrypted and deserialized response
     /// object.
+    ///
+    /// If constant-rate shaping is enabled, this first waits for the next
+    /// request slot.
     pub fn encrypted_enclave_request<
         RequestMessage: mc_util_serial::Message,
         ResponseMessage: mc_util_serial::Message + Default,
@@ -205,6 +262,48 @@ impl<U: ConnectionUri, G: EnclaveGrpcChannel> EnclaveConnection<U, G> {
         &mut self,
         plaintext_request: &RequestMessage,
         aad: &[u8],
+    ) -> Result<ResponseMessage, Error> {
+        if let Some(shaper) = self.shaper.as_mut() {
+            thread::sleep(shaper.reserve_slot(Instant::now()));
+        }
+        self.send_encrypted_request(plaintext_request, aad)
+    }
+
+    /// If constant-rate shaping is enabled and no request has used the current
+    /// slot, send a cover request in it and discard the response. Clients
+    /// should call this about once per interval, with a request that looks
+    /// like their real ones to the server but has no effect.
+    ///
+    /// Returns whether a cover request was sent. Cover requests are not sent
+    /// before the connection is attested, or beyond the bandwidth budget.
+    pub fn encrypted_cover_request<
+        RequestMessage: mc_util_serial::Message,
+        ResponseMessage: mc_util_serial::Message + Default,
+    >(
+        &mut self,
+        plaintext_request: &RequestMessage,
+        aad: &[u8],
+    ) -> Result<bool, Error> {
+        if !self.is_attested() {
+            return Ok(false);
+        }
+        let len = plaintext_request.encoded_len() + aad.len();
+        match self.shaper.as_mut() {
+            Some(shaper) if shaper.take_idle_slot(Instant::now(), len) => {}
+            _ => return Ok(false),
+        }
+        let _response: ResponseMessage = self.send_encrypted_request(plaintext_request, aad)?;
+        Ok(true)
+    }
+
+    /// Encrypt and send a request without waiting for a slot.
+    fn send_encrypted_request<
+        RequestMessage: mc_util_serial::Message,
+        ResponseMessage: mc_util_serial::Message + Default,
+    >(
+        &mut self,
+        plaintext_request: &RequestMessage,
+        aad: &[u8],
     ) -> Result<ResponseMessage, Error> {
         if !self.is_attested() {
             let _verification_report = self.attest()?;
@@ -221,49 +320,62 @@ impl<U: ConnectionUri, G: EnclaveGrpcChannel> EnclaveConnection<U, G> {
             msg.set_channel_id(Vec::from(attest_cipher.binding()));
             msg.set_aad(aad.to_vec());
 
-            let plaintext_bytes = mc_util_serial::encode(plaintext_request);
+            // Pad inside the encrypted payload, so that the padding can't be
+            // told apart from the request.
+            let mut plaintext_bytes = mc_util_serial::encode(plaintext_request);
+            if let Some(pad) = self.request_padding {
+                pad(attest_cipher.remote_capabilities(), &mut plaintext_bytes);
+            }
+            if let Some(shaper) = self.shaper.as_ref() {
+                mc_util_serial::pad_encoded(&mut plaintext_bytes, shaper.padding_block_size());
+            }
 
             let request_ciphertext = attest_cipher.encrypt(aad, &plaintext_bytes)?;
             msg.set_data(request_ciphertext);
             msg
         };
 
-        // make an attested call to EnclaveGrpcChannel::enclave_request,
-        // and handle cookies
-        let message = self.attested_call(|this| {
-            let call_opt = this.call_option();
-            let (header, message, trailer) = this.grpc.enclave_request(&msg, call_opt)?;
-
-            // Update cookies from server-sent metadata
-            if let Err(e) = this
-                .cookies
-                .update_from_server_metadata(Some(&header), Some(&trailer))
-            {
-                log::warn!(
-                    this.logger,
-                    "Could not update cookies from gRPC metadata: {}",
-                    e
-                )
+        // Make the call with AttestedTransport::enclave_request, and handle
+        // cookies. As in AttestedConnection::attested_call, an error which
+        // AttestationError::should_reattest says may have broken the channel
+        // tears down the session, so the next request attests again.
+        let headers = self.request_headers();
+        let result = self
+            .transport
+            .enclave_request(&msg, &headers)
+            .and_then(|response| {
+                self.update_cookies(&response.headers);
+                self.decrypt_response(&response.message)
+            });
+        if let Err(err) = &result {
+            if err.should_reattest() {
+                self.deattest();
             }
-
-            Ok(message)
-        })?;
-
-        // Decrypt request, scope attest_cipher borrow
-        {
-            let attest_cipher = self
-                .attest_cipher
-                .as_mut()
-                .expect("no enclave_connection even though attest succeeded");
-
-            let plaintext_bytes = attest_cipher.decrypt(message.get_aad(), message.get_data())?;
-            let plaintext_response: ResponseMessage = mc_util_serial::decode(&plaintext_bytes)?;
-            Ok(plaintext_response)
         }
+        result
+    }
+
+    /// Decrypt and deserialize a response from the enclave.
+    fn decrypt_response<ResponseMessage: mc_util_serial::Message + Default>(
+        &mut self,
+        message: &Message,
+    ) -> Result<ResponseMessage, Error> {
+        let attest_cipher = self
+            .attest_cipher
+            .as_mut()
+            .expect("no enclave_connection even though attest succeeded");
+
+        let plaintext_bytes = attest_cipher.decrypt(message.get_aad(), message.get_data())?;
+        let plaintext_response: ResponseMessage = mc_util_serial::decode(&plaintext_bytes)?;
+        Ok(plaintext_response)
     }
 
     /// Same as encrypted_enclave_request, but convert result to an
-    /// OperationResult for use with the retry crate
+    /// OperationResult for use with the retry crate.
+    ///
+    /// `attempt` counts the attempts at this request, starting from 1, and
+    /// `started_at` is when the first one started. Errors carry both, along
+    /// with this connection's address, as an [ErrorContext].
     pub fn retriable_encrypted_enclave_request<
         RequestMessage: mc_util_serial::Message,
         ResponseMessage: mc_util_serial::Message + Default,
@@ -271,10 +383,17 @@ impl<U: ConnectionUri, G: EnclaveGrpcChannel> EnclaveConnection<U, G> {
         &mut self,
         plaintext_request: &RequestMessage,
         aad: &[u8],
+        attempt: u64,
+        started_at: Instant,
     ) -> OperationResult<ResponseMessage, Error> {
         match self.encrypted_enclave_request(plaintext_request, aad) {
             Ok(value) => OperationResult::Ok(value),
             Err(err) => {
+                let err = err.with_context(ErrorContext {
+                    endpoint: self.uri.addr(),
+                    attempt,
+                    elapsed: started_at.elapsed(),
+                });
                 if err.should_retry() {
                     log::debug!(self.logger, "retriable enclave connection error: {}", err);
                     OperationResult::Retry(err)
@@ -288,33 +407,33 @@ impl<U: ConnectionUri, G: EnclaveGrpcChannel> EnclaveConnection<U, G> {
 
 // boilerplate
 
-impl<U: ConnectionUri, G: EnclaveGrpcChannel> Display for EnclaveConnection<U, G> {
+impl<U: ConnectionUri, T: AttestedTransport> Display for EnclaveConnection<U, T> {
     fn fmt(&self, f: &mut Formatter) -> FmtResult {
-        write!(f, "{}", self.uri)
+        write!(f, "{}", self.uri.redacted())
     }
 }
 
-impl<U: ConnectionUri, G: EnclaveGrpcChannel> Eq for EnclaveConnection<U, G> {}
+impl<U: ConnectionUri, T: AttestedTransport> Eq for EnclaveConnection<U, T> {}
 
-impl<U: ConnectionUri, G: EnclaveGrpcChannel> Hash for EnclaveConnection<U, G> {
+impl<U: ConnectionUri, T: AttestedTransport> Hash for EnclaveConnection<U, T> {
     fn hash<H: Hasher>(&self, hasher: &mut H) {
         self.uri.addr().hash(hasher);
     }
 }
 
-impl<U: ConnectionUri, G: EnclaveGrpcChannel> PartialEq for EnclaveConnection<U, G> {
+impl<U: ConnectionUri, T: AttestedTransport> PartialEq for EnclaveConnection<U, T> {
     fn eq(&self, other: &Self) -> bool {
         self.uri.addr() == other.uri.addr()
     }
 }
 
-impl<U: ConnectionUri, G: EnclaveGrpcChannel> Ord for EnclaveConnection<U, G> {
+impl<U: ConnectionUri, T: AttestedTransport> Ord for EnclaveConnection<U, T> {
     fn cmp(&self, other: &Self) -> Ordering {
         self.uri.addr().cmp(&other.uri.addr())
     }
 }
 
-impl<U: ConnectionUri, G: EnclaveGrpcChannel> PartialOrd for EnclaveConnection<U, G> {
+impl<U: ConnectionUri, T: AttestedTransport> PartialOrd for EnclaveConnection<U, T> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
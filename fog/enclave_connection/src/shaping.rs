@@ -0,0 +1,182 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Optional constant-rate traffic shaping for an
+//! [EnclaveConnection](crate::EnclaveConnection).
+//!
+//! Without shaping, a network observer sees a burst of requests, whose sizes
+//! depend on how much the wallet is looking for, whenever the wallet wakes up,
+//! and can try to correlate that with on-chain events. With shaping:
+//! - Requests are only sent at fixed intervals. A request made before its slot
+//!   waits for it.
+//! - Request plaintexts are padded up to a multiple of a fixed block size
+//!   before they are encrypted, so the padding is indistinguishable from the
+//!   request.
+//! - Slots which no request used can be filled with cover requests, within a
+//!   bandwidth budget.
+//!
+//! Enclaves pad their responses separately, when the request asks them to,
+//! e.g. fog view query responses with `QueryRequest.pad_response` set are
+//! padded to `QUERY_RESPONSE_PADDING_BLOCK_SIZE`.
+
+use std::time::{Duration, Instant};
+
+/// Configuration for constant-rate traffic shaping.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ConstantRateConfig {
+    /// The time between request slots.
+    pub interval: Duration,
+    /// Request plaintexts are padded up to a multiple of this many bytes.
+    pub padding_block_size: usize,
+    /// The most bytes per second to spend on cover requests. Cover requests
+    /// which would exceed this are not sent.
+    pub bandwidth_budget: u64,
+}
+
+impl Default for ConstantRateConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(5),
+            padding_block_size: 4096,
+            bandwidth_budget: 16 * 1024,
+        }
+    }
+}
+
+/// Tracks request slots and the cover traffic budget for one connection.
+#[derive(Clone, Debug)]
+pub(crate) struct ConstantRateShaper {
+    config: ConstantRateConfig,
+    /// The earliest time at which the next request may be sent, if any
+    /// request has been sent yet.
+    next_slot: Option<Instant>,
+    /// The start of the current one second budget window.
+    window_start: Option<Instant>,
+    /// The bytes of cover traffic sent in the current budget window.
+    window_spent: u64,
+}
+
+impl ConstantRateShaper {
+    pub fn new(config: ConstantRateConfig) -> Self {
+        Self {
+            config,
+            next_slot: None,
+            window_start: None,
+            window_spent: 0,
+        }
+    }
+
+    /// Reserve the next slot for a request made at `now`, and return how long
+    /// to wait before sending it.
+    pub fn reserve_slot(&mut self, now: Instant) -> Duration {
+        let slot = self.next_slot.map_or(now, |slot| slot.max(now));
+        self.next_slot = Some(slot + self.config.interval);
+        slot - now
+    }
+
+    /// Take the current slot for a cover request of `len` bytes, if no request
+    /// has used it and the bandwidth budget allows.
+    pub fn take_idle_slot(&mut self, now: Instant, len: usize) -> bool {
+        if self.next_slot.map_or(false, |slot| slot > now) {
+            return false;
+        }
+
+        let len = (len + self.padding_len(len)) as u64;
+        if self.window_start.map_or(true, |start| {
+            now.duration_since(start) >= Duration::from_secs(1)
+        }) {
+            self.window_start = Some(now);
+            self.window_spent = 0;
+        }
+        if self.window_spent + len > self.config.bandwidth_budget {
+            return false;
+        }
+
+        self.window_spent += len;
+        self.next_slot = Some(now + self.config.interval);
+        true
+    }
+
+    /// The block size which request plaintexts are padded to.
+    pub fn padding_block_size(&self) -> usize {
+        self.config.padding_block_size
+    }
+
+    /// The number of padding bytes to add to a request of `len` bytes.
+    pub fn padding_len(&self, len: usize) -> usize {
+        let block_size = self.config.padding_block_size;
+        if block_size == 0 {
+            return 0;
+        }
+        (block_size - len % block_size) % block_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shaper() -> ConstantRateShaper {
+        ConstantRateShaper::new(ConstantRateConfig {
+            interval: Duration::from_secs(2),
+            padding_block_size: 100,
+            bandwidth_budget: 250,
+        })
+    }
+
+    #[test]
+    fn requests_wait_for_their_slot() {
+        let mut shaper = shaper();
+        let now = Instant::now();
+
+        assert_eq!(shaper.reserve_slot(now), Duration::ZERO);
+        assert_eq!(shaper.reserve_slot(now), Duration::from_secs(2));
+        assert_eq!(
+            shaper.reserve_slot(now + Duration::from_secs(1)),
+            Duration::from_secs(3)
+        );
+        // After an idle period, the next request goes straight away.
+        assert_eq!(
+            shaper.reserve_slot(now + Duration::from_secs(60)),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn cover_requests_only_use_idle_slots() {
+        let mut shaper = shaper();
+        let now = Instant::now();
+
+        shaper.reserve_slot(now);
+        assert!(!shaper.take_idle_slot(now + Duration::from_secs(1), 10));
+        assert!(shaper.take_idle_slot(now + Duration::from_secs(2), 10));
+        assert!(!shaper.take_idle_slot(now + Duration::from_secs(3), 10));
+        // A real request after a cover request waits for the following slot.
+        assert_eq!(
+            shaper.reserve_slot(now + Duration::from_secs(3)),
+            Duration::from_secs(1)
+        );
+    }
+
+    #[test]
+    fn cover_requests_respect_bandwidth_budget() {
+        let mut shaper = ConstantRateShaper::new(ConstantRateConfig {
+            interval: Duration::ZERO,
+            ..shaper().config
+        });
+        let now = Instant::now();
+
+        // Each padded request is 200 bytes, so only one fits in the budget.
+        assert!(shaper.take_idle_slot(now, 150));
+        assert!(!shaper.take_idle_slot(now, 150));
+        assert!(shaper.take_idle_slot(now + Duration::from_secs(1), 150));
+    }
+
+    #[test]
+    fn padding_rounds_up_to_block_size() {
+        let shaper = shaper();
+        assert_eq!(shaper.padding_len(0), 0);
+        assert_eq!(shaper.padding_len(1), 99);
+        assert_eq!(shaper.padding_len(100), 0);
+        assert_eq!(shaper.padding_len(250), 50);
+    }
+}
@@ -0,0 +1,212 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! The transport an [EnclaveConnection](crate::EnclaveConnection) uses to reach
+//! a remote enclave.
+//!
+//! The connection itself only deals in attest messages and [Headers], so that
+//! it can run over any RPC stack which implements [AttestedTransport]. Any
+//! [EnclaveGrpcChannel] is such a transport.
+
+use crate::Error;
+use core::fmt::{Display, Formatter, Result as FmtResult};
+use grpcio::{CallOption, Metadata, MetadataBuilder, Result as GrpcResult};
+use mc_attest_api::attest::{AuthMessage, Message};
+
+/// Metadata sent with a request, or received with a response, as a list of
+/// (name, value) pairs. Names are compared case-insensitively.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Headers(Vec<(String, String)>);
+
+impl Headers {
+    /// Create an empty list of headers
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a header
+    pub fn add(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.0.push((name.into(), value.into()));
+    }
+
+    /// Iterate over all (name, value) pairs, in the order they were added
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// Iterate over the values of all headers with the given name
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.iter()
+            .filter(move |(header_name, _)| header_name.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+}
+
+/// A message received from a remote enclave, along with the response headers
+/// and trailers sent with it.
+#[derive(Clone, Debug)]
+pub struct TransportResponse<T> {
+    /// The response message
+    pub message: T,
+    /// The response headers, followed by the trailers if the transport has
+    /// any
+    pub headers: Headers,
+}
+
+/// An error from an [AttestedTransport] which is not built on grpcio.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TransportError {
+    message: String,
+    retriable: bool,
+}
+
+impl TransportError {
+    /// An error which may go away if the request is retried, e.g. a timeout
+    /// or a dropped connection.
+    pub fn retriable(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            retriable: true,
+        }
+    }
+
+    /// An error which retrying will not fix, e.g. a response which is too
+    /// large.
+    pub fn fatal(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            retriable: false,
+        }
+    }
+
+    /// Whether retrying the request may succeed
+    pub fn is_retriable(&self) -> bool {
+        self.retriable
+    }
+}
+
+impl Display for TransportError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Sends the auth and enclave_request calls of an attested connection to a
+/// remote enclave.
+///
+/// These calls:
+/// - Take a message type appropriate to the service
+/// - Take the headers to send, containing credentials info and cookies
+/// - Return a message type appropriate to the service, along with all headers
+///   received with it
+///
+/// Transports which are not built on grpcio should report their failures as
+/// [Error::Transport].
+pub trait AttestedTransport: Send + Sync {
+    fn auth(
+        &mut self,
+        msg: &AuthMessage,
+        headers: &Headers,
+    ) -> Result<TransportResponse<AuthMessage>, Error>;
+    fn enclave_request(
+        &mut self,
+        ciphertext: &Message,
+        headers: &Headers,
+    ) -> Result<TransportResponse<Message>, Error>;
+}
+
+/// Abstracts the auth and enclave_request aspects of a grpc channel used for
+/// attested connections
+///
+/// These calls:
+/// - Take a message type appropriate to the service
+/// - Take a CallOption object containing credentials info and cookies
+/// - Return a message type appropriate to the service, as well as two metadata
+///   objects, the first one containing grpc headers, and second one containing
+///   grpc trailers.
+pub trait EnclaveGrpcChannel: Send + Sync {
+    fn auth(
+        &mut self,
+        msg: &AuthMessage,
+        call_option: CallOption,
+    ) -> GrpcResult<(Metadata, AuthMessage, Metadata)>;
+    fn enclave_request(
+        &mut self,
+        ciphertext: &Message,
+        call_option: CallOption,
+    ) -> GrpcResult<(Metadata, Message, Metadata)>;
+}
+
+impl<G: EnclaveGrpcChannel> AttestedTransport for G {
+    fn auth(
+        &mut self,
+        msg: &AuthMessage,
+        headers: &Headers,
+    ) -> Result<TransportResponse<AuthMessage>, Error> {
+        let (header, message, trailer) =
+            EnclaveGrpcChannel::auth(self, msg, call_option(headers)?)?;
+        Ok(TransportResponse {
+            message,
+            headers: headers_from_metadata(&[&header, &trailer]),
+        })
+    }
+
+    fn enclave_request(
+        &mut self,
+        ciphertext: &Message,
+        headers: &Headers,
+    ) -> Result<TransportResponse<Message>, Error> {
+        let (header, message, trailer) =
+            EnclaveGrpcChannel::enclave_request(self, ciphertext, call_option(headers)?)?;
+        Ok(TransportResponse {
+            message,
+            headers: headers_from_metadata(&[&header, &trailer]),
+        })
+    }
+}
+
+fn call_option(headers: &Headers) -> GrpcResult<CallOption> {
+    let mut builder = MetadataBuilder::new();
+    for (name, value) in headers.iter() {
+        builder.add_str(name, value)?;
+    }
+    Ok(CallOption::default().headers(builder.build()))
+}
+
+/// Convert grpc metadata to headers. Values which are not UTF-8, such as those
+/// of binary (`-bin`) metadata, are skipped, since the connection only looks at
+/// text headers.
+fn headers_from_metadata(metadata: &[&Metadata]) -> Headers {
+    let mut headers = Headers::new();
+    for (name, value) in metadata.iter().flat_map(|metadata| metadata.iter()) {
+        if let Ok(value) = core::str::from_utf8(value) {
+            headers.add(name, value);
+        }
+    }
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headers_from_grpc_metadata() {
+        let mut builder = MetadataBuilder::new();
+        builder.add_str("set-cookie", "a=1").unwrap();
+        builder.add_bytes("data-bin", &[0xff, 0xfe]).unwrap();
+        let header = builder.build();
+
+        let mut builder = MetadataBuilder::new();
+        builder.add_str("Set-Cookie", "b=2").unwrap();
+        let trailer = builder.build();
+
+        let headers = headers_from_metadata(&[&header, &trailer]);
+        assert_eq!(
+            headers.get_all("Set-Cookie").collect::<Vec<_>>(),
+            ["a=1", "b=2"]
+        );
+        assert_eq!(headers.get_all("data-bin").count(), 0);
+    }
+}
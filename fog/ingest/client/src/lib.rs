@@ -116,7 +116,11 @@ impl FogIngestGrpcClient {
     }
 
     pub fn activate(&self) -> ClientResult<IngestSummary> {
-        log::info!(self.logger, "Activating Fog Ingest node {}", self.uri);
+        log::info!(
+            self.logger,
+            "Activating Fog Ingest node {}",
+            self.uri.redacted()
+        );
         retry(self.get_retries(), || -> Result<_, Error> {
             Ok(self
                 .ingest_api_client
@@ -129,7 +129,10 @@ impl<OSC: ORAMStorageCreator<StorageDataSize, StorageMetaSize>> SgxIngestEnclave
             if let Ok(decompressed_view_pubkey) =
                 RistrettoPublic::try_from(aligned_view_pubkey.as_slice())
             {
-                // Get the next rng output for this user
+                // Get the next rng output for this user. This depends only on the
+                // fog hint and the kex rng version, never on the block version, so
+                // the same search keys are produced for TxOuts of every block
+                // version.
                 use mc_crypto_keys::KexReusablePrivate;
                 let shared_secret = egress_key.key_exchange(&decompressed_view_pubkey);
                 let (overflow, rng_output) = rng_store.next_rng_output(shared_secret.as_ref());
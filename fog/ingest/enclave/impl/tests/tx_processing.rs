@@ -61,7 +61,7 @@ fn test_ingest_enclave(logger: Logger) {
                     let e_fog_hint =
                         FogHint::from(&bob_public_address).encrypt(&fog_pubkey, &mut rng);
                     TxOut::new(
-                        BlockVersion::MAX,
+                        block_version,
                         Amount {
                             value: 10,
                             token_id,
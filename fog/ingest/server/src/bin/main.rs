@@ -3,24 +3,26 @@
 
 //! Fog Ingest target
 
-use mc_common::logger::{log, o};
+use mc_common::logger::{log, o, Logger};
 use mc_fog_block_provider::{BlockProvider, LocalBlockProvider, MobilecoindBlockProvider};
-use mc_fog_ingest_enclave::ENCLAVE_FILE;
+use mc_fog_ingest_enclave::{IngestEnclave, IngestSgxEnclave, ENCLAVE_FILE};
 use mc_fog_ingest_server::{
     config::IngestConfig,
+    dry_run::dry_run as dry_run_blocks,
     server::{IngestServer, IngestServerConfig},
     state_file::StateFile,
+    tenant::load_tenants,
 };
 use mc_fog_sql_recovery_db::SqlRecoveryDb;
 use mc_ledger_db::LedgerDB;
-use mc_util_cli::ParserWithBuildInfo;
+use mc_util_cli::LayeredConfig;
 use mc_util_grpc::AdminServer;
 use mc_watcher::watcher_db::WatcherDB;
-use std::{env, sync::Arc};
+use std::{env, io::ErrorKind, path::PathBuf, sync::Arc};
 
 fn main() {
     let _sentry_guard = mc_common::sentry::init();
-    let config = IngestConfig::parse();
+    let config = IngestConfig::load();
     let (logger, _global_logger_guard) = mc_common::logger::create_app_logger(
         o!("mc.local_node_id" => config.local_node_id.to_string()),
     );
@@ -47,18 +49,6 @@ fn main() {
         .with_file_name(ENCLAVE_FILE);
     log::info!(logger, "Enclave path is: {:?}", enclave_path);
 
-    // Open databases.
-    let database_url =
-        std::env::var("DATABASE_URL").expect("DATABASE_URL environment variable missing");
-    let recovery_db = SqlRecoveryDb::new_from_url(
-        &database_url,
-        config.postgres_config.clone(),
-        logger.clone(),
-    )
-    .unwrap_or_else(|err| {
-        panic!("fog-ingest cannot connect to database '{database_url}': {err:?}")
-    });
-
     let (block_provider, ledger_db) = match (
         config.ledger_db.as_ref(),
         config.watcher_db.as_ref(),
@@ -83,6 +73,29 @@ fn main() {
         _ => panic!("invalid configuration, need either ledger_db+watcher_db or mobilecoind_uri"),
     };
 
+    if config.dry_run {
+        dry_run(
+            &config,
+            state_file_path,
+            enclave_path,
+            &*block_provider,
+            &logger,
+        );
+        return;
+    }
+
+    // Open databases.
+    let database_url =
+        std::env::var("DATABASE_URL").expect("DATABASE_URL environment variable missing");
+    let recovery_db = SqlRecoveryDb::new_from_url(
+        &database_url,
+        config.postgres_config.clone(),
+        logger.clone(),
+    )
+    .unwrap_or_else(|err| {
+        panic!("fog-ingest cannot connect to database '{database_url}': {err:?}")
+    });
+
     // Start ingest server.
     let server_config = IngestServerConfig {
         max_transactions: config.max_transactions,
@@ -100,10 +113,47 @@ fn main() {
         poll_interval: config.poll_interval,
     };
 
-    let mut server = IngestServer::new(server_config, recovery_db, block_provider, logger.clone());
+    // Load additional tenants before starting anything, so that a bad tenants
+    // file fails fast.
+    let tenants = config
+        .tenants
+        .as_ref()
+        .map(|path| load_tenants(path, &server_config).expect("Invalid tenants file"))
+        .unwrap_or_default();
+
+    let mut server = IngestServer::new(
+        server_config.clone(),
+        recovery_db.clone(),
+        block_provider.clone(),
+        logger.clone(),
+    );
 
     server.start().expect("Failed starting Ingest Service");
 
+    // Start a server, with its own enclave and ingress key, for each additional
+    // tenant.
+    let _tenant_servers = tenants
+        .iter()
+        .map(|tenant| {
+            let tenant_logger = logger.new(o!("mc.fog_report_id" => tenant.fog_report_id.clone()));
+            log::info!(
+                tenant_logger,
+                "Starting ingest for tenant '{}'",
+                tenant.fog_report_id
+            );
+            let mut tenant_server = IngestServer::new(
+                tenant.server_config(&server_config),
+                recovery_db.clone(),
+                block_provider.clone(),
+                tenant_logger,
+            );
+            tenant_server
+                .start()
+                .expect("Failed starting Ingest Service for tenant");
+            tenant_server
+        })
+        .collect::<Vec<_>>();
+
     // Start admin server.
     let config_json = serde_json::to_string(&config).expect("failed to serialize config to JSON");
     let get_config_json = Arc::new(move || Ok(config_json.clone()));
@@ -133,3 +183,58 @@ fn main() {
         std::thread::sleep(std::time::Duration::from_millis(1000));
     }
 }
+
+/// Process blocks with an enclave of our own, without touching the recovery
+/// database or starting any servers, and print what would have been written.
+fn dry_run(
+    config: &IngestConfig,
+    state_file_path: PathBuf,
+    enclave_path: PathBuf,
+    block_provider: &dyn BlockProvider,
+    logger: &Logger,
+) {
+    // Use the ingress key from the state file, if there is one. The state file
+    // is only read, so that a running server's state is not disturbed.
+    let sealed_key = match StateFile::new(state_file_path).read() {
+        Ok(state_data) => Some(state_data.sealed_ingress_key),
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            log::info!(logger, "No state file, dry run will use a new ingress key");
+            None
+        }
+        Err(err) => panic!("Could not read state file: {err}"),
+    };
+
+    let enclave = IngestSgxEnclave::new(
+        enclave_path,
+        &config.local_node_id,
+        &sealed_key,
+        config.user_capacity,
+        logger,
+    )
+    .expect("Could not create ingest enclave");
+    let ingress_pubkey = enclave
+        .get_ingress_pubkey()
+        .expect("Failed to get ingress pubkey");
+    log::info!(
+        logger,
+        "Starting dry run at block {} with ingress key {:?}",
+        config.dry_run_start_block,
+        ingress_pubkey
+    );
+
+    let report = dry_run_blocks(
+        &enclave,
+        block_provider,
+        config.dry_run_start_block,
+        config.dry_run_num_blocks,
+        config.max_transactions,
+        logger,
+    )
+    .expect("Dry run failed");
+
+    log::info!(logger, "Dry run finished: {}", report);
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&report).expect("failed to serialize dry run report")
+    );
+}
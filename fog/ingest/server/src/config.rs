@@ -4,6 +4,7 @@
 //! Configuration parameters for the Fog Ingest Node
 
 use clap::Parser;
+use mc_blockchain_types::BlockIndex;
 use mc_common::ResponderId;
 use mc_fog_sql_recovery_db::SqlRecoveryDbConnectionConfig;
 use mc_fog_uri::{FogIngestUri, IngestPeerUri};
@@ -75,8 +76,9 @@ pub struct IngestConfig {
     pub user_capacity: u64,
 
     /// Max number of transactions ingest can eat at one time.  This is mostly
-    /// determined by SGX memory allocation limits, so it must be configurable
-    #[clap(long, default_value = "100000", env = "MC_MAX_TRANSACTIONS")]
+    /// determined by SGX memory allocation limits, so it must be configurable.
+    /// Must be at least 1.
+    #[clap(long, default_value = "100000", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..), env = "MC_MAX_TRANSACTIONS")]
     pub max_transactions: usize,
 
     /// The amount we add to current block height to compute pubkey_expiry in
@@ -103,6 +105,11 @@ pub struct IngestConfig {
     #[clap(long, env = "MC_STATE_FILE")]
     pub state_file: Option<PathBuf>,
 
+    /// File listing additional tenants of this server, each with its own
+    /// ingress key, as a JSON array. See [crate::tenant].
+    #[clap(long, env = "MC_TENANTS")]
+    pub tenants: Option<PathBuf>,
+
     /// Postgres config
     #[clap(flatten)]
     pub postgres_config: SqlRecoveryDbConnectionConfig,
@@ -110,6 +117,35 @@ pub struct IngestConfig {
     /// How many milliseconds to wait between polling.
     #[clap(long = "poll_interval_ms", default_value = "250", value_parser = parse_duration_in_millis, env = "MC_POLL_INTERVAL_MS")]
     pub poll_interval: Duration,
+
+    /// Process blocks without writing to the recovery database, report what
+    /// would have been written, and exit.
+    ///
+    /// The ingress key is loaded from the state file if there is one, so this
+    /// can be used to check a key or an enclave build against existing blocks.
+    /// The state file is not modified.
+    #[clap(long, env = "MC_DRY_RUN")]
+    pub dry_run: bool,
+
+    /// The first block to process in a dry run. Defaults to the first block
+    /// of the ledger.
+    #[clap(
+        long,
+        default_value = "0",
+        requires = "dry_run",
+        env = "MC_DRY_RUN_START_BLOCK"
+    )]
+    pub dry_run_start_block: BlockIndex,
+
+    /// The number of blocks to process in a dry run. The dry run stops early
+    /// if it reaches the end of the ledger.
+    #[clap(
+        long,
+        default_value = "1000",
+        requires = "dry_run",
+        env = "MC_DRY_RUN_NUM_BLOCKS"
+    )]
+    pub dry_run_num_blocks: u64,
 }
 
 #[cfg(test)]
@@ -129,5 +165,50 @@ mod tests {
       "--admin-listen-uri", "insecure-mca://127.0.0.1:8003/",
       "--pubkey-expiry-window", "100"]).expect("Could not parse command line arguments");
         assert_eq!(config.peers.len(), 2);
+        assert!(!config.dry_run);
+    }
+
+    #[test]
+    fn ingest_server_dry_run_config() {
+        let config = IngestConfig::try_parse_from([
+            "/usr/bin/fog_ingest_server",
+            "--ledger-db",
+            "/fog-data/ledger",
+            "--watcher-db",
+            "/fog-data/watcher",
+            "--client-listen-uri",
+            "insecure-fog-ingest://0.0.0.0:3226/",
+            "--peer-listen-uri",
+            "insecure-igp://0.0.0.0:8090/",
+            "--local-node-id",
+            "fogingest2.buildtest.svc.cluster.local:443",
+            "--dry-run",
+            "--dry-run-start-block",
+            "1500",
+        ])
+        .expect("Could not parse command line arguments");
+        assert!(config.dry_run);
+        assert_eq!(config.dry_run_start_block, 1500);
+        assert_eq!(config.dry_run_num_blocks, 1000);
+    }
+
+    #[test]
+    fn ingest_server_rejects_zero_max_transactions() {
+        let result = IngestConfig::try_parse_from([
+            "/usr/bin/fog_ingest_server",
+            "--ledger-db",
+            "/fog-data/ledger",
+            "--watcher-db",
+            "/fog-data/watcher",
+            "--client-listen-uri",
+            "insecure-fog-ingest://0.0.0.0:3226/",
+            "--peer-listen-uri",
+            "insecure-igp://0.0.0.0:8090/",
+            "--local-node-id",
+            "fogingest2.buildtest.svc.cluster.local:443",
+            "--max-transactions",
+            "0",
+        ]);
+        assert!(result.is_err());
     }
 }
@@ -38,7 +38,10 @@ use mc_util_uri::ConnectionUri;
 use std::{
     collections::{BTreeMap, BTreeSet},
     io::ErrorKind,
-    sync::{Arc, Mutex, MutexGuard},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
 };
 
 /// The ingest controller sits under the grpc / networking layer, and implements
@@ -81,6 +84,10 @@ where
     /// The last sealed key. We don't bother asking the enclave for the sealed
     /// key again unless the private key changes.
     last_sealed_key: Arc<Mutex<Option<(Vec<u8>, CompressedRistrettoPublic)>>>,
+    /// The number of TxOuts we currently hand to the enclave at a time. This
+    /// is config.max_transactions, unless the enclave recently could not fit
+    /// a chunk in its rng store, in which case we back off to smaller chunks.
+    chunk_size: AtomicUsize,
     /// Logger object
     logger: Logger,
 }
@@ -189,6 +196,8 @@ where
                 .build(),
         );
 
+        counters::INGEST_CHUNK_SIZE.set(config.max_transactions as i64);
+
         // Make controller object
         let result = Self {
             config: config.clone(),
@@ -198,6 +207,7 @@ where
             report_cache,
             grpc_env,
             last_sealed_key: Arc::new(Mutex::new(None)),
+            chunk_size: AtomicUsize::new(config.max_transactions),
             logger: logger.clone(),
         };
 
@@ -397,12 +407,18 @@ where
     ///   database, there will be gaps in the RNG sequences (some of the entries
     ///   won't make it to database), and the client's won't be able to perform
     ///   balance checks successfully then.
+    ///
+    /// If the enclave fails to ingest a chunk of the block, the block is not
+    /// processed and an error is returned, so that the caller can pause and
+    /// hand us the same block again. When the failure is an rng store
+    /// overflow, later attempts use smaller chunks, so that a block with more
+    /// TxOuts than the enclave can hold at once is still processed.
     pub fn process_next_block(
         &self,
         block: &Block,
         block_contents: &BlockContents,
         timestamp: u64,
-    ) {
+    ) -> Result<(), Error> {
         let _process_next_block_timer = counters::PROCESS_NEXT_BLOCK_TIME.start_timer();
 
         let ingress_pubkey: CompressedRistrettoPublic = self
@@ -457,7 +473,7 @@ where
                                 state.set_idle();
                                 self.new_egress_key(&mut state)
                                     .expect("Failure to rotate egress key can't be recovered from");
-                                return;
+                                return Ok(());
                             }
                             break;
                         }
@@ -520,7 +536,8 @@ where
         // tx_rows are records containing tx outs, encrypted for the users.
         // there is typically (and at most) one tx row per tx out that comes in.
         let mut tx_rows = Vec::with_capacity(block_contents.outputs.len());
-        let chunks = block_contents.outputs.chunks(self.config.max_transactions);
+        let chunk_size = self.chunk_size.load(Ordering::SeqCst);
+        let chunks = block_contents.outputs.chunks(chunk_size);
         let num_chunks = chunks.len();
         for (chunk_index, chunk) in chunks.enumerate() {
             log::trace!(
@@ -543,8 +560,16 @@ where
             let (new_tx_rows, maybe_kex_rng_pubkey) = match self.enclave.ingest_txs(txs_chunk) {
                 Ok(pair) => pair,
                 Err(err) => {
-                    log::error!(self.logger, "Failed ingesting txs: {}", err);
-                    return;
+                    log::error!(
+                        self.logger,
+                        "Failed ingesting chunk {}/{} of block #{}: {}",
+                        chunk_index + 1,
+                        num_chunks,
+                        block.index,
+                        err
+                    );
+                    self.abandon_block(&err, chunk_size);
+                    return Err(err.into());
                 }
             };
             drop(ingest_txs_timer);
@@ -660,7 +685,48 @@ where
         counters::LAST_PROCESSED_BLOCK_INDEX.set(block.index as i64);
         counters::BLOCKS_PROCESSED_COUNT.inc();
 
+        // Grow the chunk size back after a successful block, if we backed off
+        if chunk_size < self.config.max_transactions {
+            let new_chunk_size = chunk_size
+                .saturating_mul(2)
+                .min(self.config.max_transactions);
+            self.chunk_size.store(new_chunk_size, Ordering::SeqCst);
+            counters::INGEST_CHUNK_SIZE.set(new_chunk_size as i64);
+        }
+
         self.write_state_file();
+        Ok(())
+    }
+
+    // Helper which cleans up after the enclave failed to ingest a chunk of a
+    // block, so that the block can be processed again from the start.
+    //
+    // The enclave already advanced its RNGs for the chunks before the failure,
+    // and there is no way to roll them back, so we nuke the egress key and
+    // decommission our ingest invocation, as when we lose the race to publish a
+    // block. The next attempt creates a fresh invocation.
+    //
+    // If the failure was an rng store overflow, we also halve the chunk size.
+    fn abandon_block(&self, err: &EnclaveError, chunk_size: usize) {
+        let mut state = self.get_state();
+        self.decommission_ingest_invocation_id(&mut state);
+        self.enclave
+            .new_egress_key()
+            .expect("Failure to rotate egress key after we can't publish data isn't recoverable, the RNGs would have gaps that the clients can't deal with");
+        *self.last_sealed_key.lock().unwrap() = None;
+        self.write_state_file_inner(&state);
+
+        if let EnclaveError::ChunkTooBig(..) = err {
+            let new_chunk_size = (chunk_size / 2).max(1);
+            log::warn!(
+                self.logger,
+                "Enclave rng store overflowed, reducing chunk size from {} to {} TxOuts",
+                chunk_size,
+                new_chunk_size
+            );
+            self.chunk_size.store(new_chunk_size, Ordering::SeqCst);
+            counters::INGEST_CHUNK_SIZE.set(new_chunk_size as i64);
+        }
     }
 
     /// Attempt to put this server safely in the active mode
@@ -729,7 +795,7 @@ where
                             log::error!(
                                 self.logger,
                                 "Could not activate: peer {} is already active",
-                                uri
+                                uri.redacted()
                             );
                             return Err(PeerBackupError::AnotherActivePeer(uri).into());
                         }
@@ -757,7 +823,11 @@ where
                 .expect("Could not get pubkey from our enclave"),
         );
         for (conn, summary) in peer_connections.iter_mut().zip(summaries.iter()) {
-            log::info!(self.logger, "activate: check on peer {}", conn.uri());
+            log::info!(
+                self.logger,
+                "activate: check on peer {}",
+                conn.uri().redacted()
+            );
             // Allow updates to our peers but don't log if they don't match initially
             self.confirm_backup(
                 conn,
@@ -1348,7 +1418,7 @@ where
                     log::error!(
                         self.logger,
                         "Our peer uri: {} did not have a valid responder id: {}",
-                        peer_uri,
+                        peer_uri.redacted(),
                         err
                     );
                     continue;
@@ -1360,7 +1430,7 @@ where
                 }
             }
 
-            log::debug!(self.logger, "Checking on peer: {}", peer_uri);
+            log::debug!(self.logger, "Checking on peer: {}", peer_uri.redacted());
             // Build a peer connection
             let mut conn = PeerConnection::<IngestSgxEnclave>::new(
                 self.enclave.clone(),
@@ -1375,7 +1445,11 @@ where
             // if it is in the wrong state.
             match self.confirm_backup(&mut conn, None, None, None, true, true) {
                 Ok(()) => {
-                    log::debug!(self.logger, "Peer backup {} was confirmed", peer_uri);
+                    log::debug!(
+                        self.logger,
+                        "Peer backup {} was confirmed",
+                        peer_uri.redacted()
+                    );
                 }
                 Err(
                     err @ PeerBackupError::Connection(ConnectionError::UnexpectedKeyInEnclave(_)),
@@ -1393,7 +1467,7 @@ where
                     log::warn!(
                         self.logger,
                         "Could not ensure backup with peer {}: {}",
-                        peer_uri,
+                        peer_uri.redacted(),
                         err
                     );
                 }
@@ -1483,7 +1557,7 @@ where
                             CompressedRistrettoPublic::try_from(summary.get_ingress_pubkey())?;
                         if peer_pubkey != our_pubkey {
                             let uri = conn.uri();
-                            log::error!(self.logger, "Tried to send our ingress key to peer {}, but despite successful status the key is still wrong! Expected: {}, Found: {}", uri, our_pubkey, peer_pubkey);
+                            log::error!(self.logger, "Tried to send our ingress key to peer {}, but despite successful status the key is still wrong! Expected: {}, Found: {}", uri.redacted(), our_pubkey, peer_pubkey);
                             return Err(PeerBackupError::FailedRemoteKeyBackup(uri));
                         }
                     }
@@ -1522,7 +1596,7 @@ where
                         let new_sorted_remote_peers = new_summary.get_sorted_peers()?;
                         if new_sorted_remote_peers != our_peers {
                             let uri = conn.uri();
-                            log::error!(self.logger, "Tried to send our peer list to peer {}, but despite successful status the peer list is still wrong! Expected: {}, Found: {}", uri,
+                            log::error!(self.logger, "Tried to send our peer list to peer {}, but despite successful status the peer list is still wrong! Expected: {}, Found: {}", uri.redacted(),
                                 SeqDisplay(our_peers.iter()),
                                 SeqDisplay(new_sorted_remote_peers.iter()),
                             );
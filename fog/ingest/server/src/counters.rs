@@ -24,6 +24,18 @@ lazy_static::lazy_static! {
     // Time it takes to perform the enclave ingest_txs call.
     pub static ref INGEST_TXS_TIME: Histogram = OP_COUNTERS.histogram("ingest_txs_time");
 
+    // Number of TxOuts handed to the enclave per ingest_txs call. This drops below max_transactions after the enclave's rng store overflows.
+    pub static ref INGEST_CHUNK_SIZE: IntGauge = OP_COUNTERS.gauge("ingest_chunk_size");
+
+    // Whether the ingest worker is currently paused, waiting to process a block the enclave failed to ingest.
+    pub static ref INGEST_STALLED: IntGauge = OP_COUNTERS.gauge("ingest_stalled");
+
+    // Number of times the ingest worker paused because the enclave failed to ingest a block.
+    pub static ref INGEST_STALL_COUNT: IntCounter = OP_COUNTERS.counter("ingest_stall_count");
+
+    // Time the ingest worker spent paused on a block before processing it.
+    pub static ref INGEST_STALL_TIME: Histogram = OP_COUNTERS.histogram("ingest_stall_time");
+
     // Time it takes to perform the db add_block_data call.
     pub static ref DB_ADD_BLOCK_DATA_TIME: Histogram = OP_COUNTERS.histogram("db_add_block_data_time");
 
@@ -0,0 +1,188 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Dry-run ingestion of blocks.
+//!
+//! A dry run feeds blocks through the ingest enclave exactly the way the
+//! ingest worker does, but discards the resulting ETxOutRecords instead of
+//! writing them to the recovery database, and reports on what would have been
+//! written. This lets operators check a new ingress key or enclave build
+//! against production blocks without affecting any users.
+
+use crate::error::IngestServiceError as Error;
+use mc_blockchain_types::BlockIndex;
+use mc_common::logger::{log, Logger};
+use mc_fog_block_provider::{BlockProvider, Error as BlockProviderError};
+use mc_fog_ingest_enclave::IngestEnclave;
+use mc_fog_types::{ingest::TxsForIngest, ETxOutRecord};
+use mc_util_serial::Message;
+use serde::Serialize;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// What a dry run would have written to the recovery database.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct DryRunReport {
+    /// The index of the first block processed.
+    pub start_block: BlockIndex,
+
+    /// The number of blocks processed. This is smaller than requested if the
+    /// end of the ledger was reached.
+    pub num_blocks: u64,
+
+    /// The number of TxOuts in the processed blocks.
+    pub num_tx_outs: u64,
+
+    /// The number of ETxOutRecords the enclave produced. The enclave produces
+    /// one for every TxOut, whether or not its fog hint is addressed to our
+    /// ingress key, so this doesn't tell how many TxOuts belong to our users.
+    pub num_tx_out_records: u64,
+
+    /// The number of times the enclave's RNG table overflowed, so that it
+    /// started a new egress key and all users got new RNGs.
+    pub num_kex_rng_rotations: u64,
+
+    /// The total encoded size of the ETxOutRecords, in bytes.
+    pub total_record_bytes: u64,
+
+    /// The encoded size of the largest ETxOutRecord, in bytes.
+    pub max_record_bytes: u64,
+}
+
+impl DryRunReport {
+    /// Account for a chunk of TxOuts fed to the enclave, and the records the
+    /// enclave produced for them.
+    fn add_chunk(&mut self, num_tx_outs: usize, records: &[ETxOutRecord], rotated_kex_rng: bool) {
+        self.num_tx_outs += num_tx_outs as u64;
+        self.num_tx_out_records += records.len() as u64;
+        if rotated_kex_rng {
+            self.num_kex_rng_rotations += 1;
+        }
+        for record in records {
+            let record_bytes = record.encoded_len() as u64;
+            self.total_record_bytes += record_bytes;
+            self.max_record_bytes = self.max_record_bytes.max(record_bytes);
+        }
+    }
+}
+
+impl Display for DryRunReport {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(
+            f,
+            "blocks [{}, {}): {} TxOuts, {} records, {} kex rng rotations, {} record bytes (max {})",
+            self.start_block,
+            self.start_block + self.num_blocks,
+            self.num_tx_outs,
+            self.num_tx_out_records,
+            self.num_kex_rng_rotations,
+            self.total_record_bytes,
+            self.max_record_bytes,
+        )
+    }
+}
+
+/// Ingest blocks without writing anything to the recovery database.
+///
+/// Blocks are processed starting at `start_block`, until `num_blocks` blocks
+/// have been processed or the block provider has no more blocks. Blocks are
+/// fed to the enclave in chunks of at most `max_transactions` TxOuts, like the
+/// ingest worker does.
+///
+/// This does not wait for the watcher to provide timestamps. The timestamps
+/// only affect the contents of the records, not their number or size.
+///
+/// Note that this advances the RNGs inside the enclave, so the enclave must not
+/// be used to ingest blocks for real afterwards.
+pub fn dry_run(
+    enclave: &impl IngestEnclave,
+    block_provider: &dyn BlockProvider,
+    start_block: BlockIndex,
+    num_blocks: u64,
+    max_transactions: usize,
+    logger: &Logger,
+) -> Result<DryRunReport, Error> {
+    let mut report = DryRunReport {
+        start_block,
+        ..Default::default()
+    };
+
+    for block_index in start_block..start_block.saturating_add(num_blocks) {
+        let result = match block_provider.get_block_data(block_index) {
+            Ok(response) => response.result,
+            Err(BlockProviderError::NotFound) => {
+                log::info!(
+                    logger,
+                    "Block {} is not in the ledger yet, stopping dry run",
+                    block_index
+                );
+                break;
+            }
+            Err(err) => return Err(Error::BlockProvider(err)),
+        };
+        let block = result.block_data.block();
+        let outputs = &result.block_data.contents().outputs;
+
+        // TxsForIngest expects global_txo_index to be the index of the first TxOut in
+        // the block handed to it.
+        let mut global_txo_index = block.cumulative_txo_count - outputs.len() as u64;
+        for chunk in outputs.chunks(max_transactions) {
+            let txs_chunk = TxsForIngest {
+                block_index,
+                global_txo_index,
+                redacted_txs: chunk.to_vec(),
+                timestamp: result.block_timestamp,
+            };
+            let (records, maybe_kex_rng_pubkey) =
+                enclave.ingest_txs(txs_chunk).map_err(Error::Enclave)?;
+            report.add_chunk(chunk.len(), &records, maybe_kex_rng_pubkey.is_some());
+            global_txo_index += chunk.len() as u64;
+        }
+
+        report.num_blocks += 1;
+        log::debug!(logger, "Dry run so far: {}", report);
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(payload_len: usize) -> ETxOutRecord {
+        ETxOutRecord {
+            search_key: vec![1; 16],
+            payload: vec![2; payload_len],
+        }
+    }
+
+    #[test]
+    fn report_accumulates_chunks() {
+        let mut report = DryRunReport {
+            start_block: 10,
+            ..Default::default()
+        };
+        let records = [record(100), record(300)];
+        report.add_chunk(5, &records, false);
+        report.add_chunk(3, &[], true);
+        report.add_chunk(1, &records[..1], false);
+        report.num_blocks = 2;
+
+        let record_bytes = records
+            .iter()
+            .map(|record| record.encoded_len() as u64)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            report,
+            DryRunReport {
+                start_block: 10,
+                num_blocks: 2,
+                num_tx_outs: 9,
+                num_tx_out_records: 3,
+                num_kex_rng_rotations: 1,
+                total_record_bytes: 2 * record_bytes[0] + record_bytes[1],
+                max_record_bytes: record_bytes[1],
+            }
+        );
+        assert!(report.to_string().starts_with("blocks [10, 12): 9 TxOuts"));
+    }
+}
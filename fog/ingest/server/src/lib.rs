@@ -18,11 +18,13 @@ pub mod config;
 pub mod connection;
 pub mod connection_error;
 pub mod connection_traits;
+pub mod dry_run;
 pub mod error;
 pub mod ingest_peer_service;
 pub mod ingest_service;
 pub mod server;
 pub mod state_file;
+pub mod tenant;
 
 mod attested_api_service;
 mod controller;
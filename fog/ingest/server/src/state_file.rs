@@ -7,7 +7,7 @@ use protobuf::Message;
 use std::{
     fs,
     io::{Error, ErrorKind, Result, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
 /// State file.
@@ -23,6 +23,11 @@ impl StateFile {
         Self { file_path }
     }
 
+    /// The state file's path.
+    pub fn path(&self) -> &Path {
+        &self.file_path
+    }
+
     /// Read the data from the state file on disk
     pub fn read(&self) -> Result<IngestStateFile> {
         let file_data = fs::read(&self.file_path)?;
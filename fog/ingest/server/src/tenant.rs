@@ -0,0 +1,212 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Additional tenants of an ingest server.
+//!
+//! Wallet providers that share fog infrastructure must not share an ingress
+//! key, since whoever holds a fog report for the key can address TxOuts to the
+//! users of every provider using it. An ingest server can serve several
+//! providers with a key each, by running one ingest enclave per tenant, each
+//! with its own ingress key, report id, peers and state file, all scanning
+//! blocks from the same ledger into the same recovery database.
+//!
+//! The records of each tenant are tagged with the ingest invocation of its
+//! enclave, which the recovery database associates with its ingress key, so
+//! fog view serves every tenant's users without telling the tenants apart.
+//!
+//! The tenant given by the command line is the primary one. Additional tenants
+//! are listed, as a JSON array of [TenantConfig], in the file given by
+//! `--tenants`. Each tenant needs its own listen URIs, and its peers are the
+//! ingest servers of the other nodes of the cluster for the same tenant. To
+//! fog overseer, each tenant is a separate ingest cluster.
+//!
+//! The metrics of the ingest server are shared by all of its tenants.
+
+use crate::{server::IngestServerConfig, state_file::StateFile};
+use mc_common::ResponderId;
+use mc_fog_uri::{FogIngestUri, IngestPeerUri};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The configuration of an additional tenant of an ingest server.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct TenantConfig {
+    /// The report id of the tenant's fog reports, which appears in the public
+    /// addresses of its users.
+    pub fog_report_id: String,
+
+    /// The responder id of this node for the tenant. This must be the
+    /// responder id of one of `peers`.
+    pub local_node_id: ResponderId,
+
+    /// gRPC listening URI for the tenant's client requests.
+    pub client_listen_uri: FogIngestUri,
+
+    /// gRPC listening URI for the tenant's peer requests.
+    pub peer_listen_uri: IngestPeerUri,
+
+    /// The tenant's ingest peers.
+    pub peers: Vec<IngestPeerUri>,
+
+    /// The tenant's state file, which holds its sealed ingress key.
+    pub state_file: PathBuf,
+}
+
+impl TenantConfig {
+    /// The server configuration for the tenant, taking the settings which are
+    /// not specific to a tenant from the primary tenant's configuration.
+    pub fn server_config(&self, primary: &IngestServerConfig) -> IngestServerConfig {
+        IngestServerConfig {
+            local_node_id: self.local_node_id.clone(),
+            client_listen_uri: self.client_listen_uri.clone(),
+            peer_listen_uri: self.peer_listen_uri.clone(),
+            peers: self.peers.iter().cloned().collect(),
+            fog_report_id: self.fog_report_id.clone(),
+            state_file: Some(StateFile::new(self.state_file.clone())),
+            ..primary.clone()
+        }
+    }
+}
+
+/// Read the additional tenants from a tenants file, and check that their
+/// report ids, listen URIs and state files are distinct from each other and
+/// from the primary tenant's.
+pub fn load_tenants(
+    path: &Path,
+    primary: &IngestServerConfig,
+) -> Result<Vec<TenantConfig>, String> {
+    let contents =
+        fs::read_to_string(path).map_err(|err| format!("Could not read {path:?}: {err}"))?;
+    let tenants: Vec<TenantConfig> = serde_json::from_str(&contents)
+        .map_err(|err| format!("Could not parse {path:?}: {err}"))?;
+
+    let mut report_ids = BTreeSet::from([primary.fog_report_id.clone()]);
+    let mut listen_addrs = BTreeSet::from([
+        primary.client_listen_uri.to_string(),
+        primary.peer_listen_uri.to_string(),
+    ]);
+    let mut state_files = BTreeSet::new();
+    if let Some(state_file) = primary.state_file.as_ref() {
+        state_files.insert(state_file.path().to_path_buf());
+    }
+
+    for tenant in tenants.iter() {
+        if !report_ids.insert(tenant.fog_report_id.clone()) {
+            return Err(format!(
+                "Report id '{}' is used by more than one tenant",
+                tenant.fog_report_id
+            ));
+        }
+        for uri in [
+            tenant.client_listen_uri.to_string(),
+            tenant.peer_listen_uri.to_string(),
+        ] {
+            if !listen_addrs.insert(uri.clone()) {
+                return Err(format!("Listen uri {uri} is used by more than one tenant"));
+            }
+        }
+        if !state_files.insert(tenant.state_file.clone()) {
+            return Err(format!(
+                "State file {:?} is used by more than one tenant",
+                tenant.state_file
+            ));
+        }
+    }
+
+    Ok(tenants)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{str::FromStr, time::Duration};
+
+    fn primary_config() -> IngestServerConfig {
+        IngestServerConfig {
+            max_transactions: 10_000,
+            omap_capacity: 1000,
+            local_node_id: ResponderId::from_str("ingest1:3227").unwrap(),
+            client_listen_uri: FogIngestUri::from_str("insecure-fog-ingest://0.0.0.0:3226/")
+                .unwrap(),
+            peer_listen_uri: IngestPeerUri::from_str("insecure-igp://0.0.0.0:3227/").unwrap(),
+            peers: Default::default(),
+            peer_checkup_period: None,
+            pubkey_expiry_window: 100,
+            watcher_timeout: Duration::from_secs(5),
+            fog_report_id: "".to_owned(),
+            state_file: Some(StateFile::new("/fog-data/ingest-state".into())),
+            enclave_path: "ingest-enclave.css".into(),
+            poll_interval: Duration::from_millis(250),
+        }
+    }
+
+    fn write_tenants(dir: &Path, tenants: &str) -> PathBuf {
+        let path = dir.join("tenants.json");
+        fs::write(&path, tenants).unwrap();
+        path
+    }
+
+    #[test]
+    fn loads_tenants() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = write_tenants(
+            temp_dir.path(),
+            r#"[{
+                "fog_report_id": "wallet-b",
+                "local_node_id": "ingest1:4227",
+                "client_listen_uri": "insecure-fog-ingest://0.0.0.0:4226/",
+                "peer_listen_uri": "insecure-igp://0.0.0.0:4227/",
+                "peers": ["insecure-igp://ingest1:4227/", "insecure-igp://ingest2:4227/"],
+                "state_file": "/fog-data/ingest-state-wallet-b"
+            }]"#,
+        );
+
+        let primary = primary_config();
+        let tenants = load_tenants(&path, &primary).unwrap();
+        assert_eq!(tenants.len(), 1);
+
+        let config = tenants[0].server_config(&primary);
+        assert_eq!(config.fog_report_id, "wallet-b");
+        assert_eq!(config.peers.len(), 2);
+        assert_eq!(config.max_transactions, primary.max_transactions);
+    }
+
+    #[test]
+    fn rejects_shared_report_id() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = write_tenants(
+            temp_dir.path(),
+            r#"[{
+                "fog_report_id": "",
+                "local_node_id": "ingest1:4227",
+                "client_listen_uri": "insecure-fog-ingest://0.0.0.0:4226/",
+                "peer_listen_uri": "insecure-igp://0.0.0.0:4227/",
+                "peers": ["insecure-igp://ingest1:4227/"],
+                "state_file": "/fog-data/ingest-state-wallet-b"
+            }]"#,
+        );
+
+        assert!(load_tenants(&path, &primary_config()).is_err());
+    }
+
+    #[test]
+    fn rejects_shared_listen_uri() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = write_tenants(
+            temp_dir.path(),
+            r#"[{
+                "fog_report_id": "wallet-b",
+                "local_node_id": "ingest1:3227",
+                "client_listen_uri": "insecure-fog-ingest://0.0.0.0:4226/",
+                "peer_listen_uri": "insecure-igp://0.0.0.0:3227/",
+                "peers": ["insecure-igp://ingest1:3227/"],
+                "state_file": "/fog-data/ingest-state-wallet-b"
+            }]"#,
+        );
+
+        assert!(load_tenants(&path, &primary_config()).is_err());
+    }
+}
@@ -1,9 +1,10 @@
 // Copyright (c) 2018-2022 The MobileCoin Foundation
 
-use crate::{controller::IngestController, error::IngestServiceError};
+use crate::{controller::IngestController, counters, error::IngestServiceError};
 use mc_blockchain_types::BlockIndex;
 use mc_common::logger::{log, Logger};
 use mc_fog_block_provider::{BlockDataResponse, BlockProvider, Error as BlockProviderError};
+use mc_fog_ingest_enclave::Error as EnclaveError;
 use mc_fog_recovery_db_iface::{RecoveryDb, ReportDb};
 use mc_sgx_report_cache_untrusted::REPORT_REFRESH_INTERVAL;
 use mc_util_telemetry::{
@@ -68,6 +69,8 @@ impl IngestWorker {
             stop_requested: stop_requested.clone(),
             thread: Some(std::thread::spawn(move || {
                 let mut last_not_found_log: Option<LastNotFound> = None;
+                // When we first failed to process the block we are stuck on, if any
+                let mut stalled_since: Option<Instant> = None;
                 loop {
                     let (next_block_index, is_idle) = controller.get_next_block_index();
 
@@ -150,13 +153,55 @@ impl IngestWorker {
                                 })
                             };
 
-                            tracer.in_span("process_next_block", |_cx| {
+                            let process_result = tracer.in_span("process_next_block", |_cx| {
                                 controller.process_next_block(
                                     result.block_data.block(),
                                     result.block_data.contents(),
                                     timestamp,
-                                );
+                                )
                             });
+
+                            // If the enclave could not ingest the block, pause and
+                            // then try the same block again. The controller has
+                            // already reset the enclave so that this is safe, and
+                            // backs off to smaller chunks if the rng store overflowed.
+                            match process_result {
+                                Ok(()) => {
+                                    if let Some(since) = stalled_since.take() {
+                                        let stall_time = since.elapsed();
+                                        log::info!(
+                                            logger,
+                                            "Resumed ingest at block {} after pausing for {:?}",
+                                            next_block_index,
+                                            stall_time
+                                        );
+                                        counters::INGEST_STALL_TIME
+                                            .observe(stall_time.as_secs_f64());
+                                        counters::INGEST_STALLED.set(0);
+                                    }
+                                }
+                                Err(err) => {
+                                    if stalled_since.is_none() {
+                                        stalled_since = Some(Instant::now());
+                                        counters::INGEST_STALL_COUNT.inc();
+                                        counters::INGEST_STALLED.set(1);
+                                    }
+                                    log::warn!(
+                                        logger,
+                                        "Pausing ingest at block {}: {}",
+                                        next_block_index,
+                                        err
+                                    );
+                                    if let IngestServiceError::Enclave(EnclaveError::ChunkTooBig(
+                                        ..,
+                                    )) = err
+                                    {
+                                        std::thread::sleep(poll_interval);
+                                    } else {
+                                        std::thread::sleep(Self::ERROR_RETRY_FREQUENCY);
+                                    }
+                                }
+                            }
                         }
                     }
                 }
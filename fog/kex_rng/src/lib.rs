@@ -180,6 +180,34 @@ mod tests {
         })
     }
 
+    // Test that seeking a VersionedKexRng lands where advancing it would
+    #[test]
+    fn test_versioned_seek() {
+        mc_util_test_helper::run_with_several_seeds(|mut rng| {
+            let a_sec = RistrettoPrivate::from_random(&mut rng);
+            let a_pub = RistrettoPublic::from(&a_sec);
+
+            let (pubkey, _) = LatestKexRng::new_from_ephemeral_static(&mut rng, &a_pub);
+            let mut rng1 = VersionedKexRng::try_from_kex_pubkey(&pubkey, &a_sec).unwrap();
+            let mut rng2 = rng1.clone();
+
+            for _ in 0..10 {
+                rng1.advance();
+            }
+            rng2.seek(10);
+            assert_eq!(rng1.index(), rng2.index());
+            assert_eq!(rng1.peek(), rng2.peek());
+
+            // Seeking backwards works too
+            rng1.seek(3);
+            let mut rng3 = VersionedKexRng::try_from_kex_pubkey(&pubkey, &a_sec).unwrap();
+            for _ in 0..3 {
+                rng3.advance();
+            }
+            assert_eq!(rng1.next(), rng3.next());
+        })
+    }
+
     // Test vectors
     #[test]
     fn test_vectors() {
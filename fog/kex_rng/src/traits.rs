@@ -72,6 +72,9 @@ pub trait BufferedRng: Clone + Into<StoredRng> + TryFrom<StoredRng> {
     fn peek(&self) -> &[u8];
     /// Increment the index compute the next value
     fn advance(&mut self);
+    /// Jump to the index'th output of the RNG, without computing the outputs
+    /// in between
+    fn seek(&mut self, index: u64);
     /// Return the version_id number
     fn version_id(&self) -> u32;
 }
@@ -40,6 +40,10 @@ where
         self.counter += 1;
         self.buffer = Core::prf(&self.secret, &self.counter);
     }
+    fn seek(&mut self, index: u64) {
+        self.counter = index;
+        self.buffer = Core::prf(&self.secret, &self.counter);
+    }
     fn index(&self) -> u64 {
         self.counter
     }
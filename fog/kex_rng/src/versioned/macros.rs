@@ -83,6 +83,11 @@ macro_rules! impl_multiversion_kex_rng_enum {
                     $(Self::$rng_name(ref mut inner) => inner.advance(),)+
                 }
             }
+            fn seek(&mut self, index: u64) {
+                match self {
+                    $(Self::$rng_name(ref mut inner) => inner.seek(index),)+
+                }
+            }
             fn index(&self) -> u64 {
                 match self {
                     $(Self::$rng_name(ref inner) => inner.index(),)+
@@ -0,0 +1,258 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! The client side of an attested session with a ledger enclave, without any
+//! transport.
+
+use crate::Error;
+use aes_gcm::Aes256Gcm;
+use alloc::{borrow::ToOwned, vec::Vec};
+use der::DateTime;
+use mc_attest_ake::{
+    AuthPending, AuthResponseInput, AuthResponseOutput, ClientInitiate, Ready, Start, Transition,
+};
+use mc_attest_core::EvidenceKind;
+use mc_attestation_verifier::TrustedIdentity;
+use mc_crypto_keys::X25519;
+use mc_fog_types::ledger::{pad_request, strip_padding, LedgerCapabilities, PaddedResponse};
+use mc_util_serial::Message;
+use rand_core::{CryptoRng, RngCore};
+use sha2::Sha512;
+
+/// An encrypted request, ready to be placed into an `attest::Message` (or an
+/// equivalent structure) by the transport.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct EncryptedRequest {
+    /// The channel id (binding) of the attested session
+    pub channel_id: Vec<u8>,
+    /// The additional authenticated data sent alongside the ciphertext
+    pub aad: Vec<u8>,
+    /// The encrypted request
+    pub data: Vec<u8>,
+}
+
+/// The attested session state machine for a ledger client.
+///
+/// Attestation happens in two steps so that the caller can perform the network
+/// round trip in between: [AttestedClientCore::begin_attest] produces the
+/// bytes of an auth request, and [AttestedClientCore::finish_attest] consumes
+/// the bytes of the server's auth response.
+pub struct AttestedClientCore {
+    /// The identities that the enclave's attestation evidence must match, one
+    /// of
+    identities: Vec<TrustedIdentity>,
+
+    /// An attestation which has been started but not finished
+    pending: Option<AuthPending<X25519, Aes256Gcm, Sha512>>,
+
+    /// The established session, if any
+    cipher: Option<Ready<Aes256Gcm>>,
+
+    /// The evidence the enclave presented for the established session, and
+    /// the time it was verified at
+    attestation: Option<(EvidenceKind, DateTime)>,
+
+    /// The capabilities offered to the enclave when attesting
+    offered_capabilities: LedgerCapabilities,
+
+    /// The capabilities agreed to for the established session
+    capabilities: Option<LedgerCapabilities>,
+}
+
+impl AttestedClientCore {
+    /// Create a new, unattested, client core.
+    pub fn new(identities: impl Into<Vec<TrustedIdentity>>) -> Self {
+        Self {
+            identities: identities.into(),
+            pending: None,
+            cipher: None,
+            attestation: None,
+            offered_capabilities: LedgerCapabilities::current(),
+            capabilities: None,
+        }
+    }
+
+    /// Offer the enclave these capabilities when attesting, instead of
+    /// everything this version of the API supports, e.g. to tell it the most
+    /// key images we will check in one request.
+    pub fn set_offered_capabilities(&mut self, capabilities: LedgerCapabilities) {
+        self.offered_capabilities = capabilities;
+    }
+
+    /// The capabilities the enclave agreed to for the established session, if
+    /// there is one. Enclaves predating negotiation agree to
+    /// [LedgerCapabilities::legacy].
+    pub fn capabilities(&self) -> Option<&LedgerCapabilities> {
+        self.capabilities.as_ref()
+    }
+
+    /// The identities that the enclave's evidence is checked against.
+    pub fn identities(&self) -> &[TrustedIdentity] {
+        &self.identities
+    }
+
+    /// True if an attested session is established.
+    pub fn is_attested(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    /// Tear down any existing or in-progress session.
+    pub fn deattest(&mut self) {
+        self.pending = None;
+        self.cipher = None;
+        self.attestation = None;
+        self.capabilities = None;
+    }
+
+    /// The evidence the enclave presented for the established session, and
+    /// the time it was verified at, if there is one.
+    pub fn attestation(&self) -> Option<(&EvidenceKind, DateTime)> {
+        self.attestation
+            .as_ref()
+            .map(|(evidence, time)| (evidence, *time))
+    }
+
+    /// Start a new attestation, discarding any existing session, and return
+    /// the auth request bytes to send to the server.
+    ///
+    /// The responder id is the host:port the client uses to reach the server,
+    /// which the enclave's evidence must be bound to.
+    pub fn begin_attest<R: CryptoRng + RngCore>(
+        &mut self,
+        responder_id: &str,
+        rng: &mut R,
+    ) -> Result<Vec<u8>, Error> {
+        self.deattest();
+
+        let initiator = Start::new(responder_id.to_owned());
+        let init_input = ClientInitiate::<X25519, Aes256Gcm, Sha512>::default()
+            .with_capabilities(mc_util_serial::encode(&self.offered_capabilities));
+        let (pending, auth_request_output) = initiator.try_next(rng, init_input)?;
+        self.pending = Some(pending);
+
+        Ok(auth_request_output.into())
+    }
+
+    /// Complete an attestation started with [AttestedClientCore::begin_attest]
+    /// using the server's auth response bytes, verifying the enclave's
+    /// evidence against our identities at the given time, and reading the
+    /// capabilities the enclave agreed to.
+    pub fn finish_attest<R: CryptoRng + RngCore>(
+        &mut self,
+        rng: &mut R,
+        auth_response: Vec<u8>,
+        time: DateTime,
+    ) -> Result<EvidenceKind, Error> {
+        let pending = self.pending.take().ok_or(Error::NoPendingAttestation)?;
+
+        let auth_response_event = AuthResponseInput::new(
+            AuthResponseOutput::from(auth_response),
+            self.identities.clone(),
+            time,
+        );
+        let (cipher, attestation_evidence): (Ready<Aes256Gcm>, _) =
+            pending.try_next(rng, auth_response_event)?;
+        let capabilities = match cipher.remote_capabilities() {
+            [] => LedgerCapabilities::legacy(),
+            capabilities => mc_util_serial::decode(capabilities)?,
+        };
+        self.capabilities = Some(capabilities);
+        self.cipher = Some(cipher);
+        self.attestation = Some((attestation_evidence.clone(), time));
+
+        Ok(attestation_evidence)
+    }
+
+    /// Serialize and encrypt a request for the enclave, padded to the
+    /// padding buckets the enclave agreed to.
+    pub fn encrypt_request<T: Message>(
+        &mut self,
+        aad: &[u8],
+        request: &T,
+    ) -> Result<EncryptedRequest, Error> {
+        let cipher = self.cipher.as_mut().ok_or(Error::NotAttested)?;
+
+        let mut plaintext_bytes = mc_util_serial::encode(request);
+        if let Some(capabilities) = self.capabilities.as_ref() {
+            pad_request(&mut plaintext_bytes, &capabilities.padding_buckets);
+        }
+        let data = cipher.encrypt(aad, &plaintext_bytes)?;
+
+        Ok(EncryptedRequest {
+            channel_id: Vec::from(cipher.binding()),
+            aad: aad.to_vec(),
+            data,
+        })
+    }
+
+    /// Decrypt and deserialize a response from the enclave.
+    pub fn decrypt_response<T: Message + Default>(
+        &mut self,
+        aad: &[u8],
+        data: &[u8],
+    ) -> Result<T, Error> {
+        let cipher = self.cipher.as_mut().ok_or(Error::NotAttested)?;
+
+        let plaintext_bytes = cipher.decrypt(aad, data)?;
+        Ok(mc_util_serial::decode(&plaintext_bytes)?)
+    }
+
+    /// Decrypt and deserialize a response from the enclave, and strip the
+    /// padding the enclave added to it.
+    pub fn decrypt_padded_response<T: PaddedResponse + Default>(
+        &mut self,
+        aad: &[u8],
+        data: &[u8],
+    ) -> Result<T, Error> {
+        let mut response: T = self.decrypt_response(aad, data)?;
+        strip_padding(&mut response);
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use mc_fog_types::ledger::CheckKeyImagesRequest;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn requests_require_attestation() {
+        let mut client = AttestedClientCore::new(Vec::new());
+        assert!(!client.is_attested());
+        assert!(client.attestation().is_none());
+        assert!(client.capabilities().is_none());
+
+        let request = CheckKeyImagesRequest::default();
+        assert!(matches!(
+            client.encrypt_request(&[], &request),
+            Err(Error::NotAttested)
+        ));
+        assert!(matches!(
+            client.decrypt_response::<CheckKeyImagesRequest>(&[], &[]),
+            Err(Error::NotAttested)
+        ));
+    }
+
+    #[test]
+    fn finish_attest_requires_begin_attest() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut client = AttestedClientCore::new(Vec::new());
+        let time = DateTime::from_unix_duration(Default::default()).unwrap();
+
+        assert!(matches!(
+            client.finish_attest(&mut rng, Vec::new(), time),
+            Err(Error::NoPendingAttestation)
+        ));
+
+        let auth_request = client
+            .begin_attest("ledger.example.com:443", &mut rng)
+            .unwrap();
+        assert!(!auth_request.is_empty());
+        assert!(!client.is_attested());
+
+        // A garbage response fails the handshake and leaves us unattested.
+        assert!(client.finish_attest(&mut rng, vec![0u8; 8], time).is_err());
+        assert!(!client.is_attested());
+    }
+}
@@ -0,0 +1,41 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Errors produced by the fog ledger client core.
+
+use displaydoc::Display;
+use mc_attest_ake::Error as AttestAkeError;
+use mc_crypto_noise::CipherError;
+use mc_util_serial::DecodeError;
+
+/// An error in the attested session or in decoding a response
+#[derive(Debug, Display)]
+pub enum Error {
+    /// Attestation error: {0}
+    Attestation(AttestAkeError),
+    /// Cipher error: {0}
+    Cipher(CipherError),
+    /// Decode error: {0}
+    Decode(DecodeError),
+    /// No attested session exists, attest first
+    NotAttested,
+    /// No attestation is in progress, begin attestation first
+    NoPendingAttestation,
+}
+
+impl From<AttestAkeError> for Error {
+    fn from(src: AttestAkeError) -> Self {
+        Self::Attestation(src)
+    }
+}
+
+impl From<CipherError> for Error {
+    fn from(src: CipherError) -> Self {
+        Self::Cipher(src)
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(src: DecodeError) -> Self {
+        Self::Decode(src)
+    }
+}
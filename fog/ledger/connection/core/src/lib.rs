@@ -0,0 +1,27 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Transport-agnostic core of the fog ledger client.
+//!
+//! This crate contains everything a fog ledger client needs except the
+//! transport: building request payloads, driving the attested key exchange,
+//! encrypting requests, decrypting responses and interpreting the results.
+//! Callers are responsible for moving the resulting bytes to and from the
+//! server, e.g. over grpcio, grpc-web or an FFI boundary.
+
+#![no_std]
+#![deny(missing_docs)]
+
+extern crate alloc;
+
+mod attested;
+mod error;
+mod request;
+mod result;
+
+pub use attested::{AttestedClientCore, EncryptedRequest};
+pub use error::Error;
+pub use request::{check_key_images_request, get_outputs_request, pad_request_to_capabilities};
+pub use result::{
+    CheckKeyImagesResponseExtension, KeyImageQueryError, KeyImageResultExtension,
+    KeyImageSpendStatus, OutputError, OutputResultExtension,
+};
@@ -0,0 +1,86 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Construction of the plaintext request payloads sent to the ledger enclave.
+
+use alloc::vec::Vec;
+use mc_fog_types::ledger::{
+    pad_request, CheckKeyImagesRequest, GetOutputsRequest, KeyImageQuery, LedgerCapabilities,
+};
+use mc_transaction_core::ring_signature::KeyImage;
+
+/// Build a request checking whether each of the given key images is spent.
+pub fn check_key_images_request(key_images: &[KeyImage]) -> CheckKeyImagesRequest {
+    CheckKeyImagesRequest {
+        queries: key_images
+            .iter()
+            .map(|key_image| KeyImageQuery {
+                key_image: *key_image,
+                start_block: 0,
+            })
+            .collect(),
+    }
+}
+
+/// Build a request for TxOuts and membership proofs at the given global
+/// indices, with proofs relative to `merkle_root_block`.
+pub fn get_outputs_request(indices: Vec<u64>, merkle_root_block: u64) -> GetOutputsRequest {
+    GetOutputsRequest {
+        indices,
+        merkle_root_block,
+    }
+}
+
+/// Pad the encoded plaintext of a request to the padding buckets in the
+/// encoded capabilities an enclave agreed to, for transports which only see
+/// the capabilities as bytes. Requests to an enclave which sent no
+/// capabilities, or ones which can't be decoded, are not padded.
+pub fn pad_request_to_capabilities(capabilities: &[u8], request: &mut Vec<u8>) {
+    if let Ok(capabilities) = mc_util_serial::decode::<LedgerCapabilities>(capabilities) {
+        pad_request(request, &capabilities.padding_buckets);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn check_key_images_request_preserves_order() {
+        let key_images = [KeyImage::from(1u64), KeyImage::from(2u64)];
+        let request = check_key_images_request(&key_images);
+
+        let queried = request
+            .queries
+            .iter()
+            .map(|query| query.key_image)
+            .collect::<Vec<_>>();
+        assert_eq!(queried, key_images);
+        assert!(request.queries.iter().all(|query| query.start_block == 0));
+    }
+
+    #[test]
+    fn get_outputs_request_round_trips() {
+        let request = get_outputs_request(vec![3, 1, 4], 10);
+        let bytes = mc_util_serial::encode(&request);
+        let decoded: GetOutputsRequest = mc_util_serial::decode(&bytes).unwrap();
+        assert_eq!(decoded, request);
+    }
+
+    #[test]
+    fn pad_request_to_capabilities_uses_padding_buckets() {
+        let request = get_outputs_request(vec![3, 1, 4], 10);
+        let capabilities =
+            mc_util_serial::encode(&LedgerCapabilities::current().with_padding_buckets(&[256]));
+
+        let mut bytes = mc_util_serial::encode(&request);
+        pad_request_to_capabilities(&capabilities, &mut bytes);
+        assert_eq!(bytes.len(), 256);
+        let decoded: GetOutputsRequest = mc_util_serial::decode(&bytes).unwrap();
+        assert_eq!(decoded, request);
+
+        let mut bytes = mc_util_serial::encode(&request);
+        pad_request_to_capabilities(&[], &mut bytes);
+        assert_eq!(bytes, mc_util_serial::encode(&request));
+    }
+}
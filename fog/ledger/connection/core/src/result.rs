@@ -0,0 +1,205 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Interpretation of the per-query result codes in ledger enclave responses.
+
+use displaydoc::Display;
+use mc_blockchain_types::BlockIndex;
+use mc_fog_types::ledger::{
+    CheckKeyImagesResponse, KeyImageResult, KeyImageResultCode, OutputResult, OutputResultCode,
+};
+use mc_transaction_core::tx::{TxOut, TxOutMembershipProof};
+
+/// An extension trait that adds a convenience method to check the status of a
+/// key image result.
+pub trait KeyImageResultExtension {
+    /// Check the status of a key image query. A `None` value indicates the key
+    /// image has not been found. Some(spent_at) indicates the key image
+    /// appeared at block index `spent_at`.
+    fn status(&self) -> Result<Option<BlockIndex>, KeyImageQueryError>;
+}
+
+impl KeyImageResultExtension for KeyImageResult {
+    /// Map the protobuf KeyImageResult type to a more idiomatic rust Result
+    /// type
+    fn status(&self) -> Result<Option<BlockIndex>, KeyImageQueryError> {
+        match KeyImageResultCode::try_from(self.key_image_result_code) {
+            Ok(KeyImageResultCode::Spent) => Ok(Some(self.spent_at)),
+            Ok(KeyImageResultCode::NotSpent) => Ok(None),
+            Ok(KeyImageResultCode::KeyImageError) => Err(KeyImageQueryError::KeyImageError),
+            Err(()) => Err(KeyImageQueryError::UnknownStatus(
+                self.key_image_result_code,
+            )),
+        }
+    }
+}
+
+/// Whether a key image is spent, taking into account how far the server has
+/// ingested the ledger.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum KeyImageSpendStatus {
+    /// The key image appeared at this block index.
+    Spent(BlockIndex),
+    /// The key image does not appear in the ledger.
+    NotSpent,
+    /// The key image was not found in the blocks the server has ingested, but
+    /// the ledger has more blocks, so it may still be spent.
+    NotYetIngested {
+        /// The number of blocks the server has ingested.
+        num_blocks: u64,
+        /// The number of blocks in the ledger.
+        ledger_num_blocks: u64,
+    },
+}
+
+/// An extension trait that adds convenience methods to interpret a check key
+/// images response while the server is still ingesting blocks.
+pub trait CheckKeyImagesResponseExtension {
+    /// Whether the server is behind the ledger, so that "not spent" results
+    /// only hold for the blocks it has ingested.
+    fn is_degraded(&self) -> bool;
+
+    /// Check the status of a key image result from this response, telling
+    /// key images which are not spent from ones the server has not ingested
+    /// yet.
+    fn spend_status(
+        &self,
+        result: &KeyImageResult,
+    ) -> Result<KeyImageSpendStatus, KeyImageQueryError>;
+}
+
+impl CheckKeyImagesResponseExtension for CheckKeyImagesResponse {
+    fn is_degraded(&self) -> bool {
+        self.serving_degraded || self.ledger_num_blocks > self.num_blocks
+    }
+
+    fn spend_status(
+        &self,
+        result: &KeyImageResult,
+    ) -> Result<KeyImageSpendStatus, KeyImageQueryError> {
+        Ok(match result.status()? {
+            Some(spent_at) => KeyImageSpendStatus::Spent(spent_at),
+            None if self.is_degraded() => KeyImageSpendStatus::NotYetIngested {
+                num_blocks: self.num_blocks,
+                ledger_num_blocks: self.ledger_num_blocks.max(self.num_blocks),
+            },
+            None => KeyImageSpendStatus::NotSpent,
+        })
+    }
+}
+
+/// Errors that occur from an individual check key image query
+#[derive(Display, Debug, Eq, PartialEq)]
+pub enum KeyImageQueryError {
+    /// Nonspecific server error handling the request
+    // FIXME: The server should at least seperate "invalid key image", "rate
+    // limited", "database", from other error types
+    KeyImageError,
+    /// Unknown status code: {0}
+    UnknownStatus(u32),
+}
+
+/// An extension trait that adds a convenience method to check that status of an
+/// output result.
+pub trait OutputResultExtension {
+    /// Check the status of an output query.
+    /// A none status indicates that the result was not found
+    /// An Error indicates that something went wrong resolving the query
+    fn status(&self) -> Result<Option<(TxOut, TxOutMembershipProof)>, OutputError>;
+}
+
+impl OutputResultExtension for OutputResult {
+    /// Map the protobuf OutputResult type to a more idiomatic rust Result type
+    fn status(&self) -> Result<Option<(TxOut, TxOutMembershipProof)>, OutputError> {
+        match OutputResultCode::try_from(self.result_code) {
+            Ok(OutputResultCode::Exists) => Ok(Some((self.output.clone(), self.proof.clone()))),
+            Ok(OutputResultCode::DoesNotExist) => Ok(None),
+            Ok(OutputResultCode::OutputDatabaseError) => Err(OutputError::DatabaseError),
+            Err(()) => Err(OutputError::UnknownError(self.result_code)),
+        }
+    }
+}
+
+/// Errors that occur in regards to an individual GetOutput query.
+#[derive(Clone, Display, Debug, Eq, PartialEq)]
+pub enum OutputError {
+    /// The server reported a database error
+    DatabaseError,
+    /// The server returned an unknown output status code
+    UnknownError(u32),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mc_transaction_core::ring_signature::KeyImage;
+
+    fn key_image_result(key_image_result_code: u32) -> KeyImageResult {
+        KeyImageResult {
+            key_image: KeyImage::from(7u64),
+            spent_at: 12,
+            timestamp: 0,
+            timestamp_result_code: 0,
+            key_image_result_code,
+        }
+    }
+
+    #[test]
+    fn key_image_status_maps_result_codes() {
+        assert_eq!(
+            key_image_result(KeyImageResultCode::Spent as u32).status(),
+            Ok(Some(12))
+        );
+        assert_eq!(
+            key_image_result(KeyImageResultCode::NotSpent as u32).status(),
+            Ok(None)
+        );
+        assert_eq!(
+            key_image_result(KeyImageResultCode::KeyImageError as u32).status(),
+            Err(KeyImageQueryError::KeyImageError)
+        );
+        assert_eq!(
+            key_image_result(0).status(),
+            Err(KeyImageQueryError::UnknownStatus(0))
+        );
+    }
+
+    #[test]
+    fn spend_status_reports_keys_not_yet_ingested() {
+        let spent = key_image_result(KeyImageResultCode::Spent as u32);
+        let not_spent = key_image_result(KeyImageResultCode::NotSpent as u32);
+
+        let mut response = CheckKeyImagesResponse {
+            num_blocks: 20,
+            ledger_num_blocks: 20,
+            ..Default::default()
+        };
+        assert!(!response.is_degraded());
+        assert_eq!(
+            response.spend_status(&spent),
+            Ok(KeyImageSpendStatus::Spent(12))
+        );
+        assert_eq!(
+            response.spend_status(&not_spent),
+            Ok(KeyImageSpendStatus::NotSpent)
+        );
+
+        response.ledger_num_blocks = 30;
+        response.serving_degraded = true;
+        assert!(response.is_degraded());
+        assert_eq!(
+            response.spend_status(&spent),
+            Ok(KeyImageSpendStatus::Spent(12))
+        );
+        assert_eq!(
+            response.spend_status(&not_spent),
+            Ok(KeyImageSpendStatus::NotYetIngested {
+                num_blocks: 20,
+                ledger_num_blocks: 30,
+            })
+        );
+        assert_eq!(
+            response.spend_status(&key_image_result(0)),
+            Err(KeyImageQueryError::UnknownStatus(0))
+        );
+    }
+}
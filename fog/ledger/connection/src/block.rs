@@ -55,6 +55,6 @@ impl FogBlockGrpcClient {
                 self.blocks_client
                     .get_blocks_opt(&request, self.creds.call_option()?)
             })
-            .map_err(|grpcio_error| Error::Grpc(self.uri.clone(), grpcio_error))
+            .map_err(|grpcio_error| Error::grpc(self.uri.clone(), grpcio_error))
     }
 }
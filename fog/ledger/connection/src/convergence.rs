@@ -0,0 +1,238 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Waiting for fog ledger to reach a ledger height.
+//!
+//! Fog ledger answers queries while it is still loading blocks, and reports
+//! how many it has loaded in each response. A client which needs answers as
+//! of some height, e.g. a test which just added blocks, or a wallet which
+//! just submitted a transaction, retries its queries until the responses
+//! report at least that many blocks.
+
+use crate::{Error, FogKeyImageGrpcClient, FogMerkleProofGrpcClient};
+use mc_fog_types::ledger::{CheckKeyImagesResponse, GetOutputsResponse};
+use mc_transaction_core::ring_signature::KeyImage;
+use std::{
+    cmp::min,
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+/// The default for how long to retry queries before giving up.
+pub const DEFAULT_CONVERGENCE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The default delay before the first retry.
+pub const DEFAULT_CONVERGENCE_INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+
+/// The default for the longest delay between retries.
+pub const DEFAULT_CONVERGENCE_MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Retries fog ledger queries until their responses report at least a given
+/// number of blocks.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct NumBlocksConvergence {
+    /// The number of blocks the responses must report, at least.
+    pub min_num_blocks: u64,
+    /// How long to retry queries before giving up.
+    pub timeout: Duration,
+    /// The delay before the first retry, which doubles with each retry.
+    pub initial_backoff: Duration,
+    /// The longest delay between retries.
+    pub max_backoff: Duration,
+}
+
+impl NumBlocksConvergence {
+    /// Wait for responses reporting at least `min_num_blocks` blocks, with the
+    /// default timeout and backoff.
+    pub fn new(min_num_blocks: u64) -> Self {
+        Self {
+            min_num_blocks,
+            timeout: DEFAULT_CONVERGENCE_TIMEOUT,
+            initial_backoff: DEFAULT_CONVERGENCE_INITIAL_BACKOFF,
+            max_backoff: DEFAULT_CONVERGENCE_MAX_BACKOFF,
+        }
+    }
+
+    /// Make a query until its response reports enough blocks, according to
+    /// `num_blocks`.
+    ///
+    /// Errors of the query are returned as they are, since the clients
+    /// already retry connection errors. If the timeout passes first,
+    /// [Error::NumBlocksNotReached] is returned.
+    pub fn wait_for<T>(
+        &self,
+        mut query: impl FnMut() -> Result<T, Error>,
+        num_blocks: impl Fn(&T) -> u64,
+    ) -> Result<T, Error> {
+        self.retry_until(&mut query, |response| {
+            self.check_num_blocks(num_blocks(response))
+        })
+    }
+
+    /// Check key images once fog ledger has loaded enough blocks.
+    pub fn check_key_images(
+        &self,
+        client: &mut FogKeyImageGrpcClient,
+        key_images: &[KeyImage],
+    ) -> Result<CheckKeyImagesResponse, Error> {
+        self.wait_for(
+            || client.check_key_images(key_images),
+            |response| response.num_blocks,
+        )
+    }
+
+    /// Get TxOuts and their membership proofs once fog ledger has loaded
+    /// enough blocks.
+    pub fn get_outputs(
+        &self,
+        client: &mut FogMerkleProofGrpcClient,
+        indices: &[u64],
+        merkle_root_block: u64,
+    ) -> Result<GetOutputsResponse, Error> {
+        self.wait_for(
+            || client.get_outputs(indices.to_vec(), merkle_root_block),
+            |response| response.num_blocks,
+        )
+    }
+
+    /// Check key images and get TxOuts once both fog ledger services have
+    /// loaded enough blocks, and report the same number of blocks, so the
+    /// responses describe the same ledger.
+    ///
+    /// If the timeout passes while both report enough blocks, but different
+    /// numbers of them, [Error::NumBlocksDiverged] is returned.
+    pub fn check_key_images_and_get_outputs(
+        &self,
+        key_image_client: &mut FogKeyImageGrpcClient,
+        merkle_proof_client: &mut FogMerkleProofGrpcClient,
+        key_images: &[KeyImage],
+        indices: &[u64],
+        merkle_root_block: u64,
+    ) -> Result<(CheckKeyImagesResponse, GetOutputsResponse), Error> {
+        self.retry_until(
+            &mut || {
+                Ok((
+                    key_image_client.check_key_images(key_images)?,
+                    merkle_proof_client.get_outputs(indices.to_vec(), merkle_root_block)?,
+                ))
+            },
+            |(key_images_response, outputs_response)| {
+                self.check_converged(key_images_response.num_blocks, outputs_response.num_blocks)
+            },
+        )
+    }
+
+    fn check_num_blocks(&self, num_blocks: u64) -> Result<(), Error> {
+        if num_blocks < self.min_num_blocks {
+            return Err(Error::NumBlocksNotReached(self.min_num_blocks, num_blocks));
+        }
+        Ok(())
+    }
+
+    fn check_converged(
+        &self,
+        key_images_num_blocks: u64,
+        outputs_num_blocks: u64,
+    ) -> Result<(), Error> {
+        self.check_num_blocks(min(key_images_num_blocks, outputs_num_blocks))?;
+        if key_images_num_blocks != outputs_num_blocks {
+            return Err(Error::NumBlocksDiverged(
+                key_images_num_blocks,
+                outputs_num_blocks,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Make a query until `check` accepts its response, returning the error
+    /// of the last check if the timeout passes first.
+    fn retry_until<T>(
+        &self,
+        query: &mut impl FnMut() -> Result<T, Error>,
+        check: impl Fn(&T) -> Result<(), Error>,
+    ) -> Result<T, Error> {
+        let deadline = Instant::now() + self.timeout;
+        let mut backoff = self.initial_backoff;
+        loop {
+            let response = query()?;
+            let Err(err) = check(&response) else {
+                return Ok(response);
+            };
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(err);
+            }
+            sleep(min(backoff, deadline - now));
+            backoff = min(backoff * 2, self.max_backoff);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convergence(min_num_blocks: u64, timeout: Duration) -> NumBlocksConvergence {
+        NumBlocksConvergence {
+            timeout,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(4),
+            ..NumBlocksConvergence::new(min_num_blocks)
+        }
+    }
+
+    #[test]
+    fn retries_until_enough_blocks() {
+        let mut num_blocks = 0;
+        let response = convergence(5, Duration::from_secs(10))
+            .wait_for(
+                || {
+                    num_blocks += 1;
+                    Ok(num_blocks)
+                },
+                |num_blocks| *num_blocks,
+            )
+            .unwrap();
+        assert_eq!(response, 5);
+
+        // Responses ahead of the height are fine too.
+        let response = convergence(5, Duration::from_secs(10))
+            .wait_for(|| Ok(7), |num_blocks| *num_blocks)
+            .unwrap();
+        assert_eq!(response, 7);
+    }
+
+    #[test]
+    fn gives_up_after_the_timeout() {
+        let started_at = Instant::now();
+        let result = convergence(5, Duration::from_millis(20)).wait_for(|| Ok(3), |n| *n);
+        assert!(matches!(result, Err(Error::NumBlocksNotReached(5, 3))));
+        assert!(started_at.elapsed() >= Duration::from_millis(20));
+
+        let mut attempts = 0;
+        let result = convergence(5, Duration::from_secs(10)).wait_for(
+            || -> Result<u64, Error> {
+                attempts += 1;
+                Err(Error::DeserializationFailed)
+            },
+            |n| *n,
+        );
+        assert!(matches!(result, Err(Error::DeserializationFailed)));
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn converged_responses_report_the_same_blocks() {
+        let convergence = convergence(5, Duration::ZERO);
+        assert!(convergence.check_converged(5, 5).is_ok());
+        assert!(convergence.check_converged(8, 8).is_ok());
+        assert!(matches!(
+            convergence.check_converged(4, 6),
+            Err(Error::NumBlocksNotReached(5, 4))
+        ));
+        assert!(matches!(
+            convergence.check_converged(6, 5),
+            Err(Error::NumBlocksDiverged(6, 5))
+        ));
+    }
+}
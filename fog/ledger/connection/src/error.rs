@@ -6,8 +6,13 @@ use retry::Error as RetryError;
 
 use mc_api::ConversionError;
 
+use mc_crypto_keys::CompressedRistrettoPublic;
+
 use mc_fog_enclave_connection::Error as EnclaveConnectionError;
 use mc_fog_uri::FogLedgerUri;
+use mc_util_grpc::MessageTooLarge;
+use mc_util_serial::DecodeError;
+use mc_util_uri::UriConversionError;
 
 /// Error type returned by LedgerServerConn
 #[derive(Debug, Display)]
@@ -22,6 +27,36 @@ pub enum Error {
     Conversion(ConversionError),
     /// grpcio error ({0}): {1}
     Grpc(FogLedgerUri, RetryError<grpcio::Error>),
+    /// gRPC message size limit ({0}): {1}
+    MessageTooLarge(FogLedgerUri, MessageTooLarge),
+    /// Uri conversion error: {0}
+    UriConversion(UriConversionError),
+    /// The ledger kept growing from {0} to {1} blocks while a split key image
+    /// query was answered
+    InconsistentNumBlocks(u64, u64),
+    /// The ledger server failed to look up TxOut {0}
+    TxOutLookupFailed(CompressedRistrettoPublic),
+    /// The ledger server returned {1} TxOut results for {0} TxOuts
+    TxOutResultCount(usize, usize),
+    /// The ledger server reported {1} blocks, not at least {0}, before the
+    /// timeout
+    NumBlocksNotReached(u64, u64),
+    /// The key image service reported {0} blocks, but the merkle proof
+    /// service {1}, until the timeout
+    NumBlocksDiverged(u64, u64),
+    /// The capabilities the enclave agreed to could not be decoded: {0}
+    Capabilities(DecodeError),
+}
+
+impl Error {
+    /// Wrap the error of a failed grpc call to `uri`, recognizing message size
+    /// violations.
+    pub fn grpc(uri: FogLedgerUri, err: RetryError<grpcio::Error>) -> Self {
+        match MessageTooLarge::from_grpc_error(&err.error) {
+            Some(too_large) => Error::MessageTooLarge(uri, too_large),
+            None => Error::Grpc(uri, err),
+        }
+    }
 }
 
 impl From<ProtobufError> for Error {
@@ -30,6 +65,12 @@ impl From<ProtobufError> for Error {
     }
 }
 
+impl From<UriConversionError> for Error {
+    fn from(err: UriConversionError) -> Self {
+        Error::UriConversion(err)
+    }
+}
+
 impl From<ConversionError> for Error {
     fn from(err: ConversionError) -> Self {
         Error::Conversion(err)
@@ -1,28 +1,36 @@
 // Copyright (c) 2018-2022 The MobileCoin Foundation
 
-use super::Error;
-use displaydoc::Display;
+use super::{Error, SpentKeyImageCache, VerificationBundle};
 use grpcio::{ChannelBuilder, Environment};
 use mc_attestation_verifier::TrustedIdentity;
-use mc_blockchain_types::BlockIndex;
 use mc_common::{
-    logger::{o, Logger},
+    logger::{log, o, Logger},
     trace_time,
 };
-use mc_fog_api::{ledger::KeyImageResultCode, ledger_grpc::FogKeyImageApiClient};
+use mc_fog_api::ledger_grpc::FogKeyImageApiClient;
 use mc_fog_enclave_connection::EnclaveConnection;
-use mc_fog_types::ledger::{
-    CheckKeyImagesRequest, CheckKeyImagesResponse, KeyImageQuery, KeyImageResult,
-};
+use mc_fog_ledger_connection_core::{check_key_images_request, pad_request_to_capabilities};
+use mc_fog_types::ledger::{strip_padding, CheckKeyImagesResponse, LedgerCapabilities};
 use mc_fog_uri::FogLedgerUri;
 use mc_transaction_core::ring_signature::KeyImage;
 use mc_util_grpc::{ConnectionUriGrpcioChannel, GrpcRetryConfig};
-use std::sync::Arc;
+use mc_util_uri::ConnectionUri;
+use std::{sync::Arc, time::Instant};
+
+/// The default for the most key images sent to the enclave in one request.
+/// Larger queries are split across several requests.
+pub const DEFAULT_MAX_KEY_IMAGES_PER_REQUEST: usize = 1000;
+
+/// How many times a split query is restarted because the ledger grew while
+/// its requests were being answered.
+const MAX_SPLIT_QUERY_ATTEMPTS: usize = 3;
 
 /// An attested connection to the Fog Key Image service.
 pub struct FogKeyImageGrpcClient {
     conn: EnclaveConnection<FogLedgerUri, FogKeyImageApiClient>,
     grpc_retry_config: GrpcRetryConfig,
+    max_key_images_per_request: usize,
+    spent_key_image_cache: Option<Arc<SpentKeyImageCache>>,
     uri: FogLedgerUri,
     logger: Logger,
 }
@@ -59,74 +67,273 @@ impl FogKeyImageGrpcClient {
                 grpc_client,
                 identities,
                 logger.clone(),
-            ),
+            )
+            .with_capabilities(mc_util_serial::encode(&LedgerCapabilities::current()))
+            .with_request_padding(pad_request_to_capabilities),
             grpc_retry_config,
+            max_key_images_per_request: DEFAULT_MAX_KEY_IMAGES_PER_REQUEST,
+            spent_key_image_cache: None,
             uri,
             logger,
         }
     }
 
+    /// Send at most this many key images to the enclave in one request,
+    /// splitting larger queries across several requests so that they stay
+    /// under the enclave's message size limits. Zero is treated as one.
+    pub fn set_max_key_images_per_request(&mut self, max_key_images_per_request: usize) {
+        self.max_key_images_per_request = max_key_images_per_request.max(1);
+    }
+
+    /// The capabilities the enclave agreed to for the current attested
+    /// session, if there is one. Enclaves predating negotiation agree to
+    /// [LedgerCapabilities::legacy].
+    pub fn capabilities(&self) -> Result<Option<LedgerCapabilities>, Error> {
+        self.conn
+            .remote_capabilities()
+            .map(decode_capabilities)
+            .transpose()
+    }
+
+    /// The most key images to send in one request: the configured limit, or
+    /// the one agreed to by the enclave if that is lower.
+    fn max_key_images_per_request(&self) -> Result<usize, Error> {
+        Ok(match self.capabilities()? {
+            Some(capabilities) if capabilities.max_key_images_per_request > 0 => self
+                .max_key_images_per_request
+                .min(capabilities.max_key_images_per_request as usize),
+            _ => self.max_key_images_per_request,
+        })
+    }
+
+    /// Answer queries about key images which are known to be spent from a
+    /// local cache, and only send the other key images to fog ledger.
+    pub fn set_spent_key_image_cache(&mut self, cache: Option<Arc<SpentKeyImageCache>>) {
+        self.spent_key_image_cache = cache;
+    }
+
     /// Make a private request to check the validity of several key images
+    ///
+    /// If a spent key image cache is set, key images in it are not sent to fog
+    /// ledger, but their cached results are still part of the response. The
+    /// request is made even if every key image is cached, so that the ledger
+    /// metadata in the response is current.
     pub fn check_key_images(
         &mut self,
         key_images: &[KeyImage],
     ) -> Result<CheckKeyImagesResponse, Error> {
         trace_time!(self.logger, "FogKeyImageGrpcClient::check_key_images");
 
-        let request = CheckKeyImagesRequest {
-            queries: key_images
-                .iter()
-                .map(|key_image| KeyImageQuery {
-                    key_image: *key_image,
-                    start_block: 0,
-                })
-                .collect(),
-        };
+        match self.spent_key_image_cache.clone() {
+            Some(cache) => {
+                let (cached, uncached) = cache.split_query(key_images);
+                let mut response = self.query_key_images(&uncached)?;
+                cache.complete_response(&mut response, cached);
+                Ok(response)
+            }
+            None => self.query_key_images(key_images),
+        }
+    }
+
+    /// Query the key images in requests of at most
+    /// `max_key_images_per_request` each, and merge the responses. Until the
+    /// first request attests, the limit agreed to by the enclave is not known
+    /// yet, so only the configured one applies.
+    ///
+    /// The requests are made one after another, because the attested session
+    /// encrypts each request with the next nonce, and the enclave must receive
+    /// them in that order. If the ledger grows between two of the requests,
+    /// the query is restarted, so that every result is relative to the same
+    /// ledger state.
+    fn query_key_images(
+        &mut self,
+        key_images: &[KeyImage],
+    ) -> Result<CheckKeyImagesResponse, Error> {
+        if key_images.len() <= self.max_key_images_per_request()? {
+            return self.query_key_images_chunk(key_images);
+        }
+
+        let mut num_blocks = (0, 0);
+        for _ in 0..MAX_SPLIT_QUERY_ATTEMPTS {
+            let responses = key_images
+                .chunks(self.max_key_images_per_request()?)
+                .map(|chunk| self.query_key_images_chunk(chunk))
+                .collect::<Result<Vec<_>, _>>()?;
+            match merge_responses(responses) {
+                Ok(response) => return Ok(response),
+                Err(mismatch) => {
+                    log::debug!(
+                        self.logger,
+                        "Ledger grew from {} to {} blocks during a split key image query, restarting it",
+                        mismatch.0,
+                        mismatch.1
+                    );
+                    num_blocks = mismatch;
+                }
+            }
+        }
+        Err(Error::InconsistentNumBlocks(num_blocks.0, num_blocks.1))
+    }
+
+    fn query_key_images_chunk(
+        &mut self,
+        key_images: &[KeyImage],
+    ) -> Result<CheckKeyImagesResponse, Error> {
+        let request = check_key_images_request(key_images);
 
         let retry_config = self.grpc_retry_config;
 
-        let response: CheckKeyImagesResponse = retry_config
-            .retry(|| self.conn.retriable_encrypted_enclave_request(&request, &[]))
+        let started_at = Instant::now();
+        let mut response: CheckKeyImagesResponse = retry_config
+            .retry_with_index(|attempt| {
+                self.conn
+                    .retriable_encrypted_enclave_request(&request, &[], attempt, started_at)
+            })
             .map_err(|err| Error::Connection(self.uri.clone(), err))?;
+        strip_padding(&mut response);
 
         Ok(response)
     }
+
+    /// Check several key images, and also return a [VerificationBundle]
+    /// recording the attestation of the enclave which answered and its
+    /// signature over the answer.
+    ///
+    /// The key images are sent in one request, without using the spent key
+    /// image cache, so that the response is the one the enclave signed.
+    pub fn check_key_images_with_bundle(
+        &mut self,
+        key_images: &[KeyImage],
+    ) -> Result<(CheckKeyImagesResponse, VerificationBundle), Error> {
+        let response = self.query_key_images_chunk(key_images)?;
+        let (evidence, attested_at) = self
+            .conn
+            .attestation()
+            .expect("connection is not attested even though the request succeeded");
+        let bundle = VerificationBundle::from_key_image_response(
+            self.uri.responder_id()?.to_string(),
+            evidence.clone(),
+            attested_at,
+            &response,
+        );
+        Ok((response, bundle))
+    }
 }
 
-/// An extension trait that adds a convenience method to check the status of a
-/// key image result.
-pub trait KeyImageResultExtension {
-    /// Check the status of a key image query. A `None` value indicates the key
-    /// image has not been found. Some(spent_at) indicates the key image
-    /// appeared at block index `spent_at`.
-    fn status(&self) -> Result<Option<BlockIndex>, KeyImageQueryError>;
+/// Decode the capabilities an enclave agreed to. Enclaves predating
+/// negotiation send none, and agree to [LedgerCapabilities::legacy].
+pub(crate) fn decode_capabilities(capabilities: &[u8]) -> Result<LedgerCapabilities, Error> {
+    match capabilities {
+        [] => Ok(LedgerCapabilities::legacy()),
+        capabilities => mc_util_serial::decode(capabilities).map_err(Error::Capabilities),
+    }
 }
 
-impl KeyImageResultExtension for KeyImageResult {
-    /// Map the protobuf KeyImageResult type to a more idiomatic rust Result
-    /// type
-    fn status(&self) -> Result<Option<BlockIndex>, KeyImageQueryError> {
-        if self.key_image_result_code == KeyImageResultCode::Spent as u32 {
-            Ok(Some(self.spent_at))
-        } else if self.key_image_result_code == KeyImageResultCode::NotSpent as u32 {
-            Ok(None)
-        } else if self.key_image_result_code == KeyImageResultCode::KeyImageError as u32 {
-            Err(KeyImageQueryError::KeyImageError)
-        } else {
-            Err(KeyImageQueryError::UnknownStatus(
-                self.key_image_result_code,
-            ))
+/// Merge the responses to the requests of a split query, in order. Fails with
+/// the first two different numbers of blocks if the responses don't agree on
+/// the number of blocks in the ledger.
+fn merge_responses(
+    responses: Vec<CheckKeyImagesResponse>,
+) -> Result<CheckKeyImagesResponse, (u64, u64)> {
+    let mut responses = responses.into_iter();
+    let mut merged = responses.next().unwrap_or_default();
+    for response in responses {
+        if response.num_blocks != merged.num_blocks {
+            return Err((merged.num_blocks, response.num_blocks));
         }
+        merged.ledger_num_blocks = merged.ledger_num_blocks.max(response.ledger_num_blocks);
+        merged.serving_degraded |= response.serving_degraded;
+        merged.results.extend(response.results);
+        // The enclave signed each response separately.
+        merged.signature.clear();
     }
+    Ok(merged)
 }
 
-/// Errors that occur from an individual check key image query
-#[derive(Display, Debug, Eq, PartialEq)]
-pub enum KeyImageQueryError {
-    /// Nonspecific server error handling the request
-    // FIXME: The server should at least seperate "invalid key image", "rate
-    // limited", "database", from other error types
-    KeyImageError,
-    /// Unknown status code: {0}
-    UnknownStatus(u32),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mc_fog_types::ledger::{KeyImageResult, KeyImageResultCode};
+
+    fn response(num_blocks: u64, key_images: &[u64]) -> CheckKeyImagesResponse {
+        CheckKeyImagesResponse {
+            num_blocks,
+            global_txo_count: num_blocks * 2,
+            results: key_images
+                .iter()
+                .map(|key_image| KeyImageResult {
+                    key_image: KeyImage::from(*key_image),
+                    spent_at: 1,
+                    timestamp: 0,
+                    timestamp_result_code: 0,
+                    key_image_result_code: KeyImageResultCode::Spent as u32,
+                })
+                .collect(),
+            latest_block_version: 3,
+            max_block_version: 3,
+            padding: vec![],
+            ledger_num_blocks: num_blocks,
+            serving_degraded: false,
+            signature: vec![],
+        }
+    }
+
+    #[test]
+    fn decode_capabilities_rejects_undecodable_capabilities() {
+        assert_eq!(
+            decode_capabilities(&[]).unwrap(),
+            LedgerCapabilities::legacy()
+        );
+        let current = LedgerCapabilities::current();
+        assert_eq!(
+            decode_capabilities(&mc_util_serial::encode(&current)).unwrap(),
+            current
+        );
+        assert!(matches!(
+            decode_capabilities(&[0xff]),
+            Err(Error::Capabilities(_))
+        ));
+    }
+
+    #[test]
+    fn merge_responses_concatenates_results_in_order() {
+        let merged = merge_responses(vec![
+            response(10, &[1, 2]),
+            response(10, &[3]),
+            response(10, &[4]),
+        ])
+        .unwrap();
+        assert_eq!(merged.num_blocks, 10);
+        assert_eq!(merged.global_txo_count, 20);
+        let key_images = merged
+            .results
+            .iter()
+            .map(|result| result.key_image)
+            .collect::<Vec<_>>();
+        assert_eq!(key_images, [1u64, 2, 3, 4].map(KeyImage::from).to_vec());
+    }
+
+    #[test]
+    fn merge_responses_rejects_different_num_blocks() {
+        assert_eq!(
+            merge_responses(vec![
+                response(10, &[1]),
+                response(10, &[2]),
+                response(11, &[3])
+            ])
+            .unwrap_err(),
+            (10, 11)
+        );
+    }
+
+    #[test]
+    fn merge_responses_keeps_degraded_status() {
+        let mut degraded = response(10, &[2]);
+        degraded.ledger_num_blocks = 15;
+        degraded.serving_degraded = true;
+
+        let merged = merge_responses(vec![response(10, &[1]), degraded]).unwrap();
+        assert_eq!(merged.ledger_num_blocks, 15);
+        assert!(merged.serving_degraded);
+    }
 }
@@ -7,17 +7,40 @@
 mod block;
 pub use block::FogBlockGrpcClient;
 
+mod convergence;
+pub use convergence::{
+    NumBlocksConvergence, DEFAULT_CONVERGENCE_INITIAL_BACKOFF, DEFAULT_CONVERGENCE_MAX_BACKOFF,
+    DEFAULT_CONVERGENCE_TIMEOUT,
+};
+
 mod error;
 pub use error::Error;
 
 mod key_image;
-pub use key_image::{FogKeyImageGrpcClient, KeyImageQueryError, KeyImageResultExtension};
+pub use key_image::{FogKeyImageGrpcClient, DEFAULT_MAX_KEY_IMAGES_PER_REQUEST};
 
 mod merkle_proof;
-pub use merkle_proof::{FogMerkleProofGrpcClient, OutputError, OutputResultExtension};
+pub use merkle_proof::FogMerkleProofGrpcClient;
+
+mod receipt;
+pub use receipt::{receipt_status, ReceiptStatus, SenderReceipt};
+
+pub use mc_fog_ledger_connection_core::{
+    CheckKeyImagesResponseExtension, KeyImageQueryError, KeyImageResultExtension,
+    KeyImageSpendStatus, OutputError, OutputResultExtension,
+};
 
 mod untrusted;
 pub use untrusted::FogUntrustedLedgerGrpcClient;
 
 mod router_client;
 pub use router_client::LedgerGrpcClient;
+
+mod router_failover;
+pub use router_failover::{EndpointStatus, FailoverLedgerGrpcClient, DEFAULT_QUARANTINE_DURATION};
+
+mod spent_key_image_cache;
+pub use spent_key_image_cache::{SpentKeyImageCache, SpentKeyImageCacheError};
+
+mod verification;
+pub use verification::{VerificationBundle, VerificationError};
@@ -1,7 +1,6 @@
 // Copyright (c) 2018-2022 The MobileCoin Foundation
 
-use super::Error;
-use displaydoc::Display;
+use super::{key_image::decode_capabilities, Error};
 use grpcio::{ChannelBuilder, Environment};
 use mc_attestation_verifier::TrustedIdentity;
 use mc_common::{
@@ -10,11 +9,11 @@ use mc_common::{
 };
 use mc_fog_api::ledger_grpc::FogMerkleProofApiClient;
 use mc_fog_enclave_connection::EnclaveConnection;
-use mc_fog_types::ledger::{GetOutputsRequest, GetOutputsResponse, OutputResult};
+use mc_fog_ledger_connection_core::{get_outputs_request, pad_request_to_capabilities};
+use mc_fog_types::ledger::{strip_padding, GetOutputsResponse, LedgerCapabilities};
 use mc_fog_uri::FogLedgerUri;
-use mc_transaction_core::tx::{TxOut, TxOutMembershipProof};
 use mc_util_grpc::{ConnectionUriGrpcioChannel, GrpcRetryConfig};
-use std::sync::Arc;
+use std::{sync::Arc, time::Instant};
 
 /// A high level object for making requests to the Fog Merkle Proof service.
 pub struct FogMerkleProofGrpcClient {
@@ -60,13 +59,25 @@ impl FogMerkleProofGrpcClient {
                 grpc_client,
                 identities,
                 logger.clone(),
-            ),
+            )
+            .with_capabilities(mc_util_serial::encode(&LedgerCapabilities::current()))
+            .with_request_padding(pad_request_to_capabilities),
             grpc_retry_config,
             uri,
             logger,
         }
     }
 
+    /// The capabilities the enclave agreed to for the current attested
+    /// session, if there is one. Enclaves predating negotiation agree to
+    /// [LedgerCapabilities::legacy].
+    pub fn capabilities(&self) -> Result<Option<LedgerCapabilities>, Error> {
+        self.conn
+            .remote_capabilities()
+            .map(decode_capabilities)
+            .transpose()
+    }
+
     /// Make a private request for membership proofs for given TxOuts
     pub fn get_outputs(
         &mut self,
@@ -75,54 +86,19 @@ impl FogMerkleProofGrpcClient {
     ) -> Result<GetOutputsResponse, Error> {
         trace_time!(self.logger, "FogMerkeProofGrpcClient::get_outputs");
 
-        let request = GetOutputsRequest {
-            indices,
-            merkle_root_block,
-        };
+        let request = get_outputs_request(indices, merkle_root_block);
 
         let retry_config = self.grpc_retry_config;
 
-        let response: GetOutputsResponse = retry_config
-            .retry(|| self.conn.retriable_encrypted_enclave_request(&request, &[]))
+        let started_at = Instant::now();
+        let mut response: GetOutputsResponse = retry_config
+            .retry_with_index(|attempt| {
+                self.conn
+                    .retriable_encrypted_enclave_request(&request, &[], attempt, started_at)
+            })
             .map_err(|err| Error::Connection(self.uri.clone(), err))?;
+        strip_padding(&mut response);
 
         Ok(response)
     }
 }
-
-/// An extension trait that adds a convenience method to check that status of an
-/// output result.
-pub trait OutputResultExtension {
-    /// Check the status of an output query.
-    /// A none status indicates that the result was not found
-    /// An Error indicates that something went wrong resolving the query
-    fn status(&self) -> Result<Option<(TxOut, TxOutMembershipProof)>, OutputError>;
-}
-
-impl OutputResultExtension for OutputResult {
-    /// Map the protobuf OutputResult type to a more idiomatic rust Result type
-    fn status(&self) -> Result<Option<(TxOut, TxOutMembershipProof)>, OutputError> {
-        // Rust does not allow the left side of match expression to a be `Foo as u32`.
-        const OUTPUT_RESULT_CODE_EXISTS: u32 = mc_fog_api::ledger::OutputResultCode::Exists as u32;
-        const OUTPUT_RESULT_CODE_DOES_NOT_EXIST: u32 =
-            mc_fog_api::ledger::OutputResultCode::DoesNotExist as u32;
-        const OUTPUT_RESULT_CODE_DATABASE_ERROR: u32 =
-            mc_fog_api::ledger::OutputResultCode::OutputDatabaseError as u32;
-
-        match self.result_code {
-            OUTPUT_RESULT_CODE_EXISTS => Ok(Some((self.output.clone(), self.proof.clone()))),
-            OUTPUT_RESULT_CODE_DOES_NOT_EXIST => Ok(None),
-            OUTPUT_RESULT_CODE_DATABASE_ERROR => Err(OutputError::DatabaseError),
-            other => Err(OutputError::UnknownError(other)),
-        }
-    }
-}
-
-/// Errors that occur in regards to an individual GetOutput query.
-#[derive(Clone, Display, Debug, Eq, PartialEq)]
-pub enum OutputError {
-    /// The server reported a database error
-    DatabaseError,
-    /// The server returned an unknown output status code
-    UnknownError(u32),
-}
@@ -0,0 +1,188 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Checking a sender's receipt for a payment against the ledger.
+//!
+//! When a sender pays someone, they can give the recipient a receipt with the
+//! public key of the TxOut they created, its confirmation number and the
+//! tombstone block of the transaction. The recipient looks the TxOut up with
+//! the untrusted fog ledger API, and checks the confirmation number with their
+//! view private key. Only whoever created the TxOut could have computed it, so
+//! a valid confirmation number shows that the sender made the payment, rather
+//! than having seen someone else's TxOut on the ledger.
+
+use crate::Error;
+use mc_blockchain_types::BlockIndex;
+use mc_crypto_keys::{CompressedRistrettoPublic, RistrettoPrivate, RistrettoPublic};
+use mc_fog_api::ledger::{TxOutResult, TxOutResultCode};
+use mc_transaction_extra::TxOutConfirmationNumber;
+use mc_watcher_api::TimestampResultCode;
+use serde::{Deserialize, Serialize};
+
+/// A receipt which the sender of a payment gives its recipient.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SenderReceipt {
+    /// The public key of the TxOut paying the recipient
+    pub tx_out_public_key: CompressedRistrettoPublic,
+
+    /// The confirmation number of the TxOut
+    pub confirmation_number: TxOutConfirmationNumber,
+
+    /// The tombstone block of the transaction. The transaction can only be in
+    /// blocks with a lower index.
+    pub tombstone_block: BlockIndex,
+}
+
+/// The status of a payment, according to the ledger.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ReceiptStatus {
+    /// The TxOut is in the ledger, and the confirmation number is valid.
+    Confirmed {
+        /// The index of the block with the TxOut
+        block_index: BlockIndex,
+        /// The timestamp of the block, in seconds since the Unix epoch, if it
+        /// is known
+        timestamp: Option<u64>,
+    },
+
+    /// The TxOut is in the ledger, but the confirmation number doesn't match
+    /// it, so the receipt doesn't show that its sender created it.
+    InvalidConfirmationNumber,
+
+    /// The TxOut is not in the ledger, and never will be, since the ledger has
+    /// reached the tombstone block.
+    Tombstoned,
+
+    /// The TxOut is not in the ledger yet, but may still be added.
+    Pending,
+}
+
+impl ReceiptStatus {
+    /// Whether the status can no longer change.
+    pub fn is_final(&self) -> bool {
+        !matches!(self, Self::Pending)
+    }
+}
+
+/// Determine the status of a receipt from the ledger's result for its TxOut.
+///
+/// Arguments:
+/// * receipt: The receipt to check
+/// * result: The result of looking up the receipt's TxOut
+/// * num_blocks: The number of blocks in the ledger when it was looked up
+/// * view_private_key: The recipient's view private key
+pub fn receipt_status(
+    receipt: &SenderReceipt,
+    result: &TxOutResult,
+    num_blocks: u64,
+    view_private_key: &RistrettoPrivate,
+) -> Result<ReceiptStatus, Error> {
+    match result.result_code {
+        TxOutResultCode::Found => {
+            let confirmed = RistrettoPublic::try_from(&receipt.tx_out_public_key)
+                .map(|tx_out_public_key| {
+                    receipt
+                        .confirmation_number
+                        .validate(&tx_out_public_key, view_private_key)
+                })
+                .unwrap_or(false);
+            if !confirmed {
+                return Ok(ReceiptStatus::InvalidConfirmationNumber);
+            }
+
+            let timestamp = (TimestampResultCode::try_from(result.timestamp_result_code)
+                == Ok(TimestampResultCode::TimestampFound))
+            .then_some(result.timestamp);
+            Ok(ReceiptStatus::Confirmed {
+                block_index: result.block_index,
+                timestamp,
+            })
+        }
+        TxOutResultCode::NotFound => {
+            if num_blocks >= receipt.tombstone_block {
+                Ok(ReceiptStatus::Tombstoned)
+            } else {
+                Ok(ReceiptStatus::Pending)
+            }
+        }
+        _ => Err(Error::TxOutLookupFailed(receipt.tx_out_public_key)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mc_rand::McRng;
+    use mc_transaction_core::get_tx_out_shared_secret;
+    use mc_util_from_random::FromRandom;
+
+    fn receipt(view_private_key: &RistrettoPrivate, tombstone_block: BlockIndex) -> SenderReceipt {
+        let tx_out_public_key = RistrettoPublic::from_random(&mut McRng);
+        let shared_secret = get_tx_out_shared_secret(view_private_key, &tx_out_public_key);
+        SenderReceipt {
+            tx_out_public_key: CompressedRistrettoPublic::from(&tx_out_public_key),
+            confirmation_number: TxOutConfirmationNumber::from(&shared_secret),
+            tombstone_block,
+        }
+    }
+
+    fn found(block_index: BlockIndex) -> TxOutResult {
+        let mut result = TxOutResult::new();
+        result.set_result_code(TxOutResultCode::Found);
+        result.block_index = block_index;
+        result.timestamp = 1_700_000_000;
+        result.timestamp_result_code = TimestampResultCode::TimestampFound as u32;
+        result
+    }
+
+    #[test]
+    fn found_with_valid_confirmation_number_is_confirmed() {
+        let view_private_key = RistrettoPrivate::from_random(&mut McRng);
+        let receipt = receipt(&view_private_key, 20);
+
+        assert_eq!(
+            receipt_status(&receipt, &found(12), 15, &view_private_key).unwrap(),
+            ReceiptStatus::Confirmed {
+                block_index: 12,
+                timestamp: Some(1_700_000_000),
+            }
+        );
+    }
+
+    #[test]
+    fn found_with_wrong_confirmation_number_is_invalid() {
+        let view_private_key = RistrettoPrivate::from_random(&mut McRng);
+        let other_view_private_key = RistrettoPrivate::from_random(&mut McRng);
+        let receipt = receipt(&other_view_private_key, 20);
+
+        assert_eq!(
+            receipt_status(&receipt, &found(12), 15, &view_private_key).unwrap(),
+            ReceiptStatus::InvalidConfirmationNumber
+        );
+    }
+
+    #[test]
+    fn not_found_is_pending_until_tombstone_block() {
+        let view_private_key = RistrettoPrivate::from_random(&mut McRng);
+        let receipt = receipt(&view_private_key, 20);
+        let not_found = TxOutResult::new();
+
+        assert_eq!(
+            receipt_status(&receipt, &not_found, 19, &view_private_key).unwrap(),
+            ReceiptStatus::Pending
+        );
+        assert_eq!(
+            receipt_status(&receipt, &not_found, 20, &view_private_key).unwrap(),
+            ReceiptStatus::Tombstoned
+        );
+    }
+
+    #[test]
+    fn database_error_is_an_error() {
+        let view_private_key = RistrettoPrivate::from_random(&mut McRng);
+        let receipt = receipt(&view_private_key, 20);
+        let mut result = TxOutResult::new();
+        result.set_result_code(TxOutResultCode::DatabaseError);
+
+        assert!(receipt_status(&receipt, &result, 15, &view_private_key).is_err());
+    }
+}
@@ -1,12 +1,10 @@
 // Copyright (c) 2018-2023 The MobileCoin Foundation
 
-use aes_gcm::Aes256Gcm;
+use crate::{SpentKeyImageCache, VerificationBundle};
 use der::DateTime;
 use futures::{executor::block_on, SinkExt, TryStreamExt};
 use grpcio::{ChannelBuilder, ClientDuplexReceiver, ClientDuplexSender, Environment};
-use mc_attest_ake::{
-    AuthResponseInput, ClientInitiate, Error as AttestAkeError, Ready, Start, Transition,
-};
+use mc_attest_ake::Error as AttestAkeError;
 use mc_attest_core::EvidenceKind;
 use mc_attestation_verifier::TrustedIdentity;
 use mc_common::{
@@ -14,21 +12,22 @@ use mc_common::{
     time::{SystemTimeProvider, TimeProvider},
     trace_time,
 };
-use mc_crypto_keys::X25519;
 use mc_crypto_noise::CipherError;
 use mc_fog_api::{
     attest::{AuthMessage, Message},
     ledger::{LedgerRequest, LedgerResponse},
     ledger_grpc::LedgerApiClient,
 };
-use mc_fog_types::ledger::{CheckKeyImagesRequest, CheckKeyImagesResponse, KeyImageQuery};
+use mc_fog_ledger_connection_core::{
+    check_key_images_request, AttestedClientCore, Error as CoreError,
+};
+use mc_fog_types::ledger::{CheckKeyImagesResponse, LedgerCapabilities};
 use mc_fog_uri::FogLedgerUri;
 use mc_rand::McRng;
 use mc_transaction_core::ring_signature::KeyImage;
-use mc_util_grpc::ConnectionUriGrpcioChannel;
+use mc_util_grpc::{BasicCredentials, ConnectionUriGrpcioChannel, MessageTooLarge};
 use mc_util_serial::DecodeError;
 use mc_util_uri::{ConnectionUri, UriConversionError};
-use sha2::Sha512;
 use std::sync::Arc;
 
 /// A high-level object mediating requests to the fog ledger router service
@@ -39,11 +38,12 @@ pub struct LedgerGrpcClient {
     /// The URI of the router to communicate with
     uri: FogLedgerUri,
 
-    /// The identities that a fog node's attestation evidence must match, one of
-    identities: Vec<TrustedIdentity>,
+    /// The transport-agnostic attested session, which holds the identities
+    /// that a fog node's attestation evidence must match, one of
+    core: AttestedClientCore,
 
-    /// The AKE state machine object, if one is available.
-    attest_cipher: Option<Ready<Aes256Gcm>>,
+    /// Cache of key images known to be spent, which we don't ask about
+    spent_key_image_cache: Option<Arc<SpentKeyImageCache>>,
 
     /// Sends requests to the fog ledger router
     request_sender: ClientDuplexSender<LedgerRequest>,
@@ -74,23 +74,39 @@ impl LedgerGrpcClient {
 
         let ch = ChannelBuilder::default_channel_builder(env).connect_to_uri(&uri, &logger);
         let client = LedgerApiClient::new(ch);
+        // The router authenticates the stream when it is opened.
+        let call_option = BasicCredentials::new(&uri.username(), &uri.password())
+            .call_option()
+            .expect("Could not create call credentials");
         let (request_sender, response_receiver) = client
-            .request()
+            .request_opt(call_option)
             .expect("Could not retrieve grpc sender and receiver.");
 
         Self {
             logger,
-            attest_cipher: None,
+            core: AttestedClientCore::new(identities),
+            spent_key_image_cache: None,
             _client: client,
             request_sender,
             response_receiver,
             uri,
-            identities: identities.into(),
         }
     }
 
+    /// Answer queries about key images which are known to be spent from a
+    /// local cache, and only send the other key images to the router.
+    pub fn set_spent_key_image_cache(&mut self, cache: Option<Arc<SpentKeyImageCache>>) {
+        self.spent_key_image_cache = cache;
+    }
+
+    /// The capabilities the enclave agreed to for the current attested
+    /// session, if there is one.
+    pub fn capabilities(&self) -> Option<&LedgerCapabilities> {
+        self.core.capabilities()
+    }
+
     fn is_attested(&self) -> bool {
-        self.attest_cipher.is_some()
+        self.core.is_attested()
     }
 
     async fn attest(&mut self) -> Result<EvidenceKind, Error> {
@@ -99,12 +115,11 @@ impl LedgerGrpcClient {
 
         let mut csprng = McRng;
 
-        let initiator = Start::new(self.uri.responder_id()?.to_string());
-
-        let init_input = ClientInitiate::<X25519, Aes256Gcm, Sha512>::default();
-        let (initiator, auth_request_output) = initiator.try_next(&mut csprng, init_input)?;
+        let responder_id = self.uri.responder_id()?.to_string();
+        let auth_request = self.core.begin_attest(&responder_id, &mut csprng)?;
 
-        let attested_message: AuthMessage = auth_request_output.into();
+        let mut attested_message = AuthMessage::new();
+        attested_message.set_data(auth_request);
         let mut request = LedgerRequest::new();
         request.set_auth(attested_message);
         self.request_sender
@@ -116,7 +131,7 @@ impl LedgerGrpcClient {
             .try_next()
             .await?
             .ok_or(Error::ResponseNotReceived)?;
-        let auth_response_msg = response.take_auth();
+        let mut auth_response_msg = response.take_auth();
 
         let epoch_time = SystemTimeProvider
             .since_epoch()
@@ -125,12 +140,9 @@ impl LedgerGrpcClient {
             .map_err(|_| Error::Other("Time out of range".to_owned()))?;
 
         // Process server response, check if key exchange is successful
-        let auth_response_event =
-            AuthResponseInput::new(auth_response_msg.into(), self.identities.clone(), time);
-        let (initiator, attestation_evidence) =
-            initiator.try_next(&mut csprng, auth_response_event)?;
-
-        self.attest_cipher = Some(initiator);
+        let attestation_evidence =
+            self.core
+                .finish_attest(&mut csprng, auth_response_msg.take_data(), time)?;
 
         Ok(attestation_evidence)
     }
@@ -138,77 +150,197 @@ impl LedgerGrpcClient {
     fn deattest(&mut self) {
         if self.is_attested() {
             log::trace!(self.logger, "Tearing down existing attested connection.");
-            self.attest_cipher = None;
         }
+        self.core.deattest();
     }
 
     /// Check one or more key images against the ledger router service
+    ///
+    /// If a spent key image cache is set, key images in it are not sent to the
+    /// router, but their cached results are still part of the response. The
+    /// request is made even if every key image is cached, so that the ledger
+    /// metadata in the response is current.
     pub async fn check_key_images(
         &mut self,
         key_images: &[KeyImage],
     ) -> Result<CheckKeyImagesResponse, Error> {
         trace_time!(self.logger, "LedgerGrpcClient::check_key_images");
 
+        self.check_key_images_impl(key_images, None).await
+    }
+
+    /// Check one or more key images, asking the router to stream partial
+    /// results for large batches.
+    ///
+    /// Each time another shard responds, `on_partial` is called with the
+    /// results of the shards which have responded so far. Key images a partial
+    /// response reports as spent are spent, but ones it reports as not spent
+    /// may still turn out to be spent in the final response, which is
+    /// returned.
+    pub async fn check_key_images_streaming(
+        &mut self,
+        key_images: &[KeyImage],
+        mut on_partial: impl FnMut(CheckKeyImagesResponse),
+    ) -> Result<CheckKeyImagesResponse, Error> {
+        trace_time!(self.logger, "LedgerGrpcClient::check_key_images_streaming");
+
+        self.check_key_images_impl(key_images, Some(&mut on_partial))
+            .await
+    }
+
+    async fn check_key_images_impl(
+        &mut self,
+        key_images: &[KeyImage],
+        on_partial: Option<&mut dyn FnMut(CheckKeyImagesResponse)>,
+    ) -> Result<CheckKeyImagesResponse, Error> {
+        match self.spent_key_image_cache.clone() {
+            Some(cache) => {
+                let (cached, uncached) = cache.split_query(key_images);
+                let mut on_partial = on_partial.map(|on_partial| {
+                    let cache = cache.clone();
+                    let cached = cached.clone();
+                    move |mut response: CheckKeyImagesResponse| {
+                        cache.complete_response(&mut response, cached.clone());
+                        on_partial(response)
+                    }
+                });
+                let mut response = self
+                    .query_key_images(
+                        &uncached,
+                        on_partial
+                            .as_mut()
+                            .map(|f| f as &mut dyn FnMut(CheckKeyImagesResponse)),
+                    )
+                    .await?;
+                cache.complete_response(&mut response, cached);
+                Ok(response)
+            }
+            None => self.query_key_images(key_images, on_partial).await,
+        }
+    }
+
+    async fn query_key_images(
+        &mut self,
+        key_images: &[KeyImage],
+        mut on_partial: Option<&mut dyn FnMut(CheckKeyImagesResponse)>,
+    ) -> Result<CheckKeyImagesResponse, Error> {
         if !self.is_attested() {
             let verification_report = self.attest().await;
             verification_report?;
         }
 
-        let key_images_queries = key_images
-            .iter()
-            .map(|&key_image| KeyImageQuery {
-                key_image,
-                start_block: 0,
-            })
-            .collect();
-        let key_images_request = CheckKeyImagesRequest {
-            queries: key_images_queries,
-        };
+        let msg = self.encrypt_key_images_request(key_images)?;
+        let mut request = LedgerRequest::new();
+        request.set_check_key_images(msg);
+        request.stream_partial_results = on_partial.is_some();
+
+        self.request_sender
+            .send((request.clone(), grpcio::WriteFlags::default()))
+            .await?;
+
+        loop {
+            let mut response = self
+                .response_receiver
+                .try_next()
+                .await?
+                .ok_or(Error::ResponseNotReceived)?;
+            let message = response.take_check_key_image_response();
+
+            let plaintext_response: CheckKeyImagesResponse = self
+                .core
+                .decrypt_padded_response(message.get_aad(), message.get_data())?;
+            match on_partial.as_mut() {
+                Some(on_partial) if response.partial => on_partial(plaintext_response),
+                _ => return Ok(plaintext_response),
+            }
+        }
+    }
+
+    fn encrypt_key_images_request(&mut self, key_images: &[KeyImage]) -> Result<Message, Error> {
+        let key_images_request = check_key_images_request(key_images);
 
         // No authenticated data associated with ledger query
         let aad = vec![];
 
-        let msg = {
-            let attest_cipher = self
-                .attest_cipher
-                .as_mut()
-                .expect("no enclave_connection even though attest succeeded");
+        let encrypted = self.core.encrypt_request(&aad, &key_images_request)?;
 
-            let mut msg = Message::new();
-            msg.set_channel_id(Vec::from(attest_cipher.binding()));
-            msg.set_aad(aad.clone());
+        let mut msg = Message::new();
+        msg.set_channel_id(encrypted.channel_id);
+        msg.set_aad(encrypted.aad);
+        msg.set_data(encrypted.data);
+        Ok(msg)
+    }
 
-            let plaintext_bytes = mc_util_serial::encode(&key_images_request);
+    /// Subscribe to one or more key images, returning their current state.
+    ///
+    /// After each block added to the ledger, the router checks the key images
+    /// again, and the results can be read with
+    /// [LedgerGrpcClient::next_key_images_update]. Subscribing again replaces
+    /// the key images of the subscription. Once subscribed, this client can
+    /// only be used for subscriptions, and the spent key image cache is not
+    /// used.
+    pub async fn subscribe_key_images(
+        &mut self,
+        key_images: &[KeyImage],
+    ) -> Result<CheckKeyImagesResponse, Error> {
+        trace_time!(self.logger, "LedgerGrpcClient::subscribe_key_images");
 
-            let request_ciphertext = attest_cipher.encrypt(&aad, &plaintext_bytes)?;
-            msg.set_data(request_ciphertext);
-            msg
-        };
+        if !self.is_attested() {
+            self.attest().await?;
+        }
+
+        let msg = self.encrypt_key_images_request(key_images)?;
         let mut request = LedgerRequest::new();
-        request.set_check_key_images(msg);
+        request.set_subscribe_key_images(msg);
 
         self.request_sender
-            .send((request.clone(), grpcio::WriteFlags::default()))
+            .send((request, grpcio::WriteFlags::default()))
             .await?;
 
-        let message = self
+        self.next_key_images_update().await
+    }
+
+    /// Wait for the router to check the subscribed key images again, which it
+    /// does after each block added to the ledger, and return the results.
+    ///
+    /// An update is sent for every block whether or not any of the key images
+    /// were spent in it, so callers should look for newly spent key images in
+    /// each one.
+    pub async fn next_key_images_update(&mut self) -> Result<CheckKeyImagesResponse, Error> {
+        let mut response = self
             .response_receiver
             .try_next()
             .await?
-            .ok_or(Error::ResponseNotReceived)?
-            .take_check_key_image_response();
-
-        {
-            let attest_cipher = self
-                .attest_cipher
-                .as_mut()
-                .expect("no enclave_connection even though attest succeeded");
-
-            let plaintext_bytes = attest_cipher.decrypt(message.get_aad(), message.get_data())?;
-            let plaintext_response: CheckKeyImagesResponse =
-                mc_util_serial::decode(&plaintext_bytes)?;
-            Ok(plaintext_response)
-        }
+            .ok_or(Error::ResponseNotReceived)?;
+        let message = response.take_key_images_update();
+
+        Ok(self
+            .core
+            .decrypt_padded_response(message.get_aad(), message.get_data())?)
+    }
+
+    /// Check one or more key images, and also return a [VerificationBundle]
+    /// recording the attestation of the router which answered and its
+    /// signature over the answer.
+    ///
+    /// The spent key image cache is not used, so that the response is the one
+    /// the router signed.
+    pub async fn check_key_images_with_bundle(
+        &mut self,
+        key_images: &[KeyImage],
+    ) -> Result<(CheckKeyImagesResponse, VerificationBundle), Error> {
+        let response = self.query_key_images(key_images, None).await?;
+        let (evidence, attested_at) = self
+            .core
+            .attestation()
+            .expect("router client is not attested even though the request succeeded");
+        let bundle = VerificationBundle::from_key_image_response(
+            self.uri.responder_id()?.to_string(),
+            evidence.clone(),
+            attested_at,
+            &response,
+        );
+        Ok((response, bundle))
     }
 }
 
@@ -233,9 +365,15 @@ pub enum Error {
     /// Attestation errors.
     Attestation(AttestAkeError),
 
+    /// Other errors from the attested session, e.g. using it before attesting.
+    Session(CoreError),
+
     /// Grpc errors.
     Grpc(grpcio::Error),
 
+    /// A request or response exceeded a grpc message size limit.
+    MessageTooLarge(MessageTooLarge),
+
     /// Response not received
     ResponseNotReceived,
 
@@ -257,7 +395,10 @@ impl From<CipherError> for Error {
 
 impl From<grpcio::Error> for Error {
     fn from(err: grpcio::Error) -> Self {
-        Self::Grpc(err)
+        match MessageTooLarge::from_grpc_error(&err) {
+            Some(too_large) => Self::MessageTooLarge(too_large),
+            None => Self::Grpc(err),
+        }
     }
 }
 
@@ -267,6 +408,17 @@ impl From<UriConversionError> for Error {
     }
 }
 
+impl From<CoreError> for Error {
+    fn from(err: CoreError) -> Self {
+        match err {
+            CoreError::Attestation(err) => Self::Attestation(err),
+            CoreError::Cipher(err) => Self::Cipher(err),
+            CoreError::Decode(err) => Self::Decode(err),
+            other => Self::Session(other),
+        }
+    }
+}
+
 impl From<AttestAkeError> for Error {
     fn from(err: AttestAkeError) -> Self {
         Self::Attestation(err)
@@ -0,0 +1,301 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! A fog ledger router client which fails over between several endpoints.
+//!
+//! Routers are usually run as several instances behind a load balancer, and
+//! during a deployment some of them may still run an enclave the client no
+//! longer trusts, or one it doesn't trust yet. Rather than failing the whole
+//! query when the router it reached doesn't attest, [FailoverLedgerGrpcClient]
+//! records why, quarantines that endpoint for a while, and retries the query
+//! against the next one. Other errors are returned as is, since another
+//! endpoint is no more likely to avoid them.
+
+use crate::{router_client::Error, LedgerGrpcClient, SpentKeyImageCache, VerificationBundle};
+use grpcio::Environment;
+use mc_attestation_verifier::TrustedIdentity;
+use mc_common::logger::{log, Logger};
+use mc_fog_types::ledger::CheckKeyImagesResponse;
+use mc_fog_uri::{ConnectionUri, FogLedgerUri};
+use mc_transaction_core::ring_signature::KeyImage;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// The default time for which an endpoint which failed attestation is only
+/// tried after every other endpoint.
+pub const DEFAULT_QUARANTINE_DURATION: Duration = Duration::from_secs(300);
+
+/// What the client knows about one of its endpoints.
+#[derive(Clone, Debug)]
+pub struct EndpointStatus {
+    /// The endpoint's URI
+    pub uri: FogLedgerUri,
+    /// When the endpoint's quarantine ends, if it is quarantined
+    pub quarantined_until: Option<Instant>,
+    /// How many times attesting with the endpoint failed
+    pub attestation_failures: u64,
+    /// Why attesting with the endpoint last failed. When the evidence didn't
+    /// verify, this shows the measurements it presented.
+    pub last_attestation_failure: Option<String>,
+}
+
+impl EndpointStatus {
+    fn new(uri: FogLedgerUri) -> Self {
+        Self {
+            uri,
+            quarantined_until: None,
+            attestation_failures: 0,
+            last_attestation_failure: None,
+        }
+    }
+
+    fn is_quarantined(&self, now: Instant) -> bool {
+        self.quarantined_until
+            .is_some_and(|quarantined_until| now < quarantined_until)
+    }
+}
+
+/// Decides which endpoint to try next, and keeps track of their quarantines.
+struct EndpointSelector {
+    statuses: Vec<EndpointStatus>,
+    /// The endpoint which answered last, which is tried first
+    preferred: usize,
+    quarantine_duration: Duration,
+}
+
+impl EndpointSelector {
+    fn new(uris: Vec<FogLedgerUri>, quarantine_duration: Duration) -> Self {
+        Self {
+            statuses: uris.into_iter().map(EndpointStatus::new).collect(),
+            preferred: 0,
+            quarantine_duration,
+        }
+    }
+
+    /// The endpoints to try, in order: the preferred one, then the others in
+    /// the order they were given, and then the quarantined ones, in the order
+    /// their quarantines end.
+    fn order(&self, now: Instant) -> Vec<usize> {
+        let num_endpoints = self.statuses.len();
+        let (mut quarantined, mut order): (Vec<usize>, Vec<usize>) = (0..num_endpoints)
+            .map(|offset| (self.preferred + offset) % num_endpoints)
+            .partition(|index| self.statuses[*index].is_quarantined(now));
+        quarantined.sort_by_key(|index| self.statuses[*index].quarantined_until);
+        order.extend(quarantined);
+        order
+    }
+
+    fn record_attestation_failure(&mut self, index: usize, failure: String, now: Instant) {
+        let status = &mut self.statuses[index];
+        status.quarantined_until = Some(now + self.quarantine_duration);
+        status.attestation_failures += 1;
+        status.last_attestation_failure = Some(failure);
+    }
+
+    fn record_success(&mut self, index: usize) {
+        self.statuses[index].quarantined_until = None;
+        self.preferred = index;
+    }
+}
+
+/// A fog ledger router client which retries queries against another endpoint
+/// when attestation with one fails.
+///
+/// Endpoints are connected to lazily, and the connection to an endpoint which
+/// attested is kept for later queries.
+pub struct FailoverLedgerGrpcClient {
+    selector: EndpointSelector,
+    clients: Vec<Option<LedgerGrpcClient>>,
+    identities: Vec<TrustedIdentity>,
+    env: Arc<Environment>,
+    spent_key_image_cache: Option<Arc<SpentKeyImageCache>>,
+    logger: Logger,
+}
+
+impl FailoverLedgerGrpcClient {
+    /// Create a client for the routers at `uris`, which are tried in order.
+    ///
+    /// Arguments:
+    /// * uris: The endpoints to connect to
+    /// * identities: The identities that are allowed for attestation
+    /// * env: A grpc environment (thread pool) to use for the connections
+    /// * logger: For logging
+    pub fn new(
+        uris: Vec<FogLedgerUri>,
+        identities: impl Into<Vec<TrustedIdentity>>,
+        env: Arc<Environment>,
+        logger: Logger,
+    ) -> Self {
+        Self {
+            clients: uris.iter().map(|_| None).collect(),
+            selector: EndpointSelector::new(uris, DEFAULT_QUARANTINE_DURATION),
+            identities: identities.into(),
+            env,
+            spent_key_image_cache: None,
+            logger,
+        }
+    }
+
+    /// Only try an endpoint which failed attestation after every other
+    /// endpoint, for this long after the failure.
+    pub fn set_quarantine_duration(&mut self, quarantine_duration: Duration) {
+        self.selector.quarantine_duration = quarantine_duration;
+    }
+
+    /// Answer queries about key images which are known to be spent from a
+    /// local cache. See [LedgerGrpcClient::set_spent_key_image_cache].
+    pub fn set_spent_key_image_cache(&mut self, cache: Option<Arc<SpentKeyImageCache>>) {
+        for client in self.clients.iter_mut().flatten() {
+            client.set_spent_key_image_cache(cache.clone());
+        }
+        self.spent_key_image_cache = cache;
+    }
+
+    /// What the client knows about each endpoint, in the order they were
+    /// given.
+    pub fn endpoint_statuses(&self) -> &[EndpointStatus] {
+        &self.selector.statuses
+    }
+
+    /// Check one or more key images, failing over to another endpoint if
+    /// attestation fails. See [LedgerGrpcClient::check_key_images].
+    pub async fn check_key_images(
+        &mut self,
+        key_images: &[KeyImage],
+    ) -> Result<CheckKeyImagesResponse, Error> {
+        let (response, _bundle) = self.query(key_images, false).await?;
+        Ok(response)
+    }
+
+    /// Check one or more key images, failing over to another endpoint if
+    /// attestation fails, and also return a [VerificationBundle] recording
+    /// the attestation of the router which answered.
+    pub async fn check_key_images_with_bundle(
+        &mut self,
+        key_images: &[KeyImage],
+    ) -> Result<(CheckKeyImagesResponse, VerificationBundle), Error> {
+        let (response, bundle) = self.query(key_images, true).await?;
+        Ok((
+            response,
+            bundle.expect("a bundle was requested but not returned"),
+        ))
+    }
+
+    async fn query(
+        &mut self,
+        key_images: &[KeyImage],
+        with_bundle: bool,
+    ) -> Result<(CheckKeyImagesResponse, Option<VerificationBundle>), Error> {
+        let mut last_error = None;
+        for index in self.selector.order(Instant::now()) {
+            let client = self.client(index);
+            let result = if with_bundle {
+                client
+                    .check_key_images_with_bundle(key_images)
+                    .await
+                    .map(|(response, bundle)| (response, Some(bundle)))
+            } else {
+                client
+                    .check_key_images(key_images)
+                    .await
+                    .map(|response| (response, None))
+            };
+            match result {
+                Ok(result) => {
+                    self.selector.record_success(index);
+                    return Ok(result);
+                }
+                Err(Error::Attestation(err)) => {
+                    let uri = &self.selector.statuses[index].uri;
+                    log::warn!(
+                        self.logger,
+                        "Attestation with {} failed, trying another endpoint: {}",
+                        uri.redacted(),
+                        err
+                    );
+                    self.selector.record_attestation_failure(
+                        index,
+                        err.to_string(),
+                        Instant::now(),
+                    );
+                    // Reconnect next time, in case the load balancer sends us to another
+                    // instance.
+                    self.clients[index] = None;
+                    last_error = Some(Error::Attestation(err));
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| Error::Other("No fog ledger endpoints".to_owned())))
+    }
+
+    fn client(&mut self, index: usize) -> &mut LedgerGrpcClient {
+        let uri = &self.selector.statuses[index].uri;
+        self.clients[index].get_or_insert_with(|| {
+            let mut client = LedgerGrpcClient::new(
+                uri.clone(),
+                self.identities.clone(),
+                self.env.clone(),
+                self.logger.clone(),
+            );
+            client.set_spent_key_image_cache(self.spent_key_image_cache.clone());
+            client
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn selector(num_endpoints: usize) -> EndpointSelector {
+        EndpointSelector::new(
+            (0..num_endpoints)
+                .map(|index| {
+                    FogLedgerUri::from_str(&format!("fog-ledger://router-{index}.example.com"))
+                        .unwrap()
+                })
+                .collect(),
+            Duration::from_secs(60),
+        )
+    }
+
+    #[test]
+    fn endpoints_are_tried_in_order() {
+        let mut selector = selector(3);
+        let now = Instant::now();
+        assert_eq!(selector.order(now), vec![0, 1, 2]);
+
+        // The endpoint which answered last is tried first.
+        selector.record_success(1);
+        assert_eq!(selector.order(now), vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn quarantined_endpoints_are_tried_last() {
+        let mut selector = selector(3);
+        let now = Instant::now();
+
+        selector.record_attestation_failure(0, "MRENCLAVE mismatch".to_owned(), now);
+        selector.record_attestation_failure(
+            2,
+            "MRENCLAVE mismatch".to_owned(),
+            now - Duration::from_secs(10),
+        );
+        assert_eq!(selector.order(now), vec![1, 2, 0]);
+        assert_eq!(selector.statuses[0].attestation_failures, 1);
+        assert_eq!(
+            selector.statuses[0].last_attestation_failure.as_deref(),
+            Some("MRENCLAVE mismatch")
+        );
+
+        // Endpoints leave quarantine once it ends, or once they answer.
+        assert_eq!(selector.order(now + Duration::from_secs(61)), vec![0, 1, 2]);
+        selector.record_success(0);
+        assert_eq!(selector.order(now), vec![0, 1, 2]);
+        assert!(selector.statuses[0].quarantined_until.is_none());
+        assert_eq!(selector.statuses[0].attestation_failures, 1);
+    }
+}
@@ -0,0 +1,245 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! A local, persistent cache of key images which fog ledger reported as spent.
+//!
+//! A key image which has been spent stays spent, so once fog ledger has told
+//! us so, and has given the final timestamp of the block it was spent in,
+//! there is no point in asking again. Wallets with long histories ask about
+//! many such key images on every sync, so the clients in this crate can
+//! consult the cache first, and only send the other key images over the
+//! network. Results for key images which are not spent are never cached.
+//!
+//! The cache is an LMDB database in a directory chosen by the caller. It
+//! records which key images belong to the wallet, so it should be protected
+//! like the wallet's other local data.
+
+use displaydoc::Display;
+use lmdb::{Database, DatabaseFlags, Environment, Transaction, WriteFlags};
+use mc_common::logger::{log, Logger};
+use mc_fog_ledger_connection_core::KeyImageResultExtension;
+use mc_fog_types::ledger::{CheckKeyImagesResponse, KeyImageResult};
+use mc_transaction_core::ring_signature::KeyImage;
+use mc_util_serial::DecodeError;
+use mc_watcher_api::TimestampResultCode;
+use std::path::Path;
+
+// LMDB Constants
+const MAX_LMDB_FILE_SIZE: usize = 1 << 30; // 1 GB
+
+// LMDB Database Names
+const SPENT_KEY_IMAGES_DB_NAME: &str = "fog_ledger_connection:spent_key_images";
+
+/// An error accessing the spent key image cache
+#[derive(Debug, Display)]
+pub enum SpentKeyImageCacheError {
+    /// LMDB: {0}
+    Lmdb(lmdb::Error),
+    /// Could not decode a cached result: {0}
+    Decode(DecodeError),
+}
+
+impl From<lmdb::Error> for SpentKeyImageCacheError {
+    fn from(err: lmdb::Error) -> Self {
+        Self::Lmdb(err)
+    }
+}
+
+impl From<DecodeError> for SpentKeyImageCacheError {
+    fn from(err: DecodeError) -> Self {
+        Self::Decode(err)
+    }
+}
+
+/// A persistent map from spent key images to the results fog ledger returned
+/// for them.
+pub struct SpentKeyImageCache {
+    env: Environment,
+    spent_key_images: Database,
+    logger: Logger,
+}
+
+impl SpentKeyImageCache {
+    /// Open the cache in the given directory, creating it if it is empty.
+    pub fn open(path: impl AsRef<Path>, logger: Logger) -> Result<Self, SpentKeyImageCacheError> {
+        let env = Environment::new()
+            .set_max_dbs(1)
+            .set_map_size(MAX_LMDB_FILE_SIZE)
+            .open(path.as_ref())?;
+        let spent_key_images =
+            env.create_db(Some(SPENT_KEY_IMAGES_DB_NAME), DatabaseFlags::empty())?;
+
+        Ok(Self {
+            env,
+            spent_key_images,
+            logger,
+        })
+    }
+
+    /// Get the cached result for a key image, if it is known to be spent.
+    pub fn get(
+        &self,
+        key_image: &KeyImage,
+    ) -> Result<Option<KeyImageResult>, SpentKeyImageCacheError> {
+        let db_txn = self.env.begin_ro_txn()?;
+        match db_txn.get(self.spent_key_images, key_image) {
+            Ok(bytes) => Ok(Some(mc_util_serial::decode(bytes)?)),
+            Err(lmdb::Error::NotFound) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Add the final results among the given ones to the cache, and return how
+    /// many there were.
+    pub fn insert(&self, results: &[KeyImageResult]) -> Result<usize, SpentKeyImageCacheError> {
+        let mut db_txn = self.env.begin_rw_txn()?;
+        let mut num_inserted = 0;
+        for result in results.iter().filter(|result| is_final(result)) {
+            db_txn.put(
+                self.spent_key_images,
+                &result.key_image,
+                &mc_util_serial::encode(result),
+                WriteFlags::empty(),
+            )?;
+            num_inserted += 1;
+        }
+        db_txn.commit()?;
+        Ok(num_inserted)
+    }
+
+    /// Split a query into the results we have cached, and the key images we
+    /// still need to ask fog ledger about.
+    ///
+    /// The cache is only an optimization, so a key image we fail to look up is
+    /// asked about again.
+    pub(crate) fn split_query(
+        &self,
+        key_images: &[KeyImage],
+    ) -> (Vec<KeyImageResult>, Vec<KeyImage>) {
+        let mut cached = Vec::new();
+        let mut uncached = Vec::new();
+        for key_image in key_images {
+            match self.get(key_image) {
+                Ok(Some(result)) => cached.push(result),
+                Ok(None) => uncached.push(*key_image),
+                Err(err) => {
+                    log::warn!(
+                        self.logger,
+                        "Could not look up key image in spent key image cache: {}",
+                        err
+                    );
+                    uncached.push(*key_image);
+                }
+            }
+        }
+        (cached, uncached)
+    }
+
+    /// Cache the final results of a response from fog ledger, and then add the
+    /// cached results of the same query to it. The enclave did not sign the
+    /// cached results, so a response they are added to loses its signature.
+    pub(crate) fn complete_response(
+        &self,
+        response: &mut CheckKeyImagesResponse,
+        cached: Vec<KeyImageResult>,
+    ) {
+        if let Err(err) = self.insert(&response.results) {
+            log::warn!(
+                self.logger,
+                "Could not add results to spent key image cache: {}",
+                err
+            );
+        }
+        if !cached.is_empty() {
+            response.signature.clear();
+            response.results.extend(cached);
+        }
+    }
+}
+
+/// Whether a result can never change: the key image is spent, and the
+/// timestamp of the block it was spent in is either known or will never be.
+fn is_final(result: &KeyImageResult) -> bool {
+    let timestamp_is_final = matches!(
+        TimestampResultCode::try_from(result.timestamp_result_code),
+        Ok(TimestampResultCode::TimestampFound | TimestampResultCode::Unavailable)
+    );
+    matches!(result.status(), Ok(Some(_))) && timestamp_is_final
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mc_common::logger::test_with_logger;
+    use mc_fog_types::ledger::KeyImageResultCode;
+
+    fn result(
+        key_image: u64,
+        code: KeyImageResultCode,
+        timestamp_code: TimestampResultCode,
+    ) -> KeyImageResult {
+        KeyImageResult {
+            key_image: KeyImage::from(key_image),
+            spent_at: 10,
+            timestamp: 1000,
+            timestamp_result_code: timestamp_code as u32,
+            key_image_result_code: code as u32,
+        }
+    }
+
+    #[test_with_logger]
+    fn caches_only_final_spent_results(logger: Logger) {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = SpentKeyImageCache::open(dir.path(), logger).unwrap();
+
+        let spent = result(
+            1,
+            KeyImageResultCode::Spent,
+            TimestampResultCode::TimestampFound,
+        );
+        let not_spent = result(
+            2,
+            KeyImageResultCode::NotSpent,
+            TimestampResultCode::TimestampFound,
+        );
+        let watcher_behind = result(
+            3,
+            KeyImageResultCode::Spent,
+            TimestampResultCode::WatcherBehind,
+        );
+        let results = [spent.clone(), not_spent, watcher_behind];
+        assert_eq!(cache.insert(&results).unwrap(), 1);
+
+        let key_images = results
+            .iter()
+            .map(|result| result.key_image)
+            .collect::<Vec<_>>();
+        let (cached, uncached) = cache.split_query(&key_images);
+        assert_eq!(cached, vec![spent.clone()]);
+        assert_eq!(uncached, key_images[1..]);
+
+        let mut response = CheckKeyImagesResponse {
+            num_blocks: 20,
+            ..Default::default()
+        };
+        cache.complete_response(&mut response, cached);
+        assert_eq!(response.results, vec![spent]);
+    }
+
+    #[test_with_logger]
+    fn cache_persists(logger: Logger) {
+        let dir = tempfile::tempdir().unwrap();
+        let spent = result(
+            1,
+            KeyImageResultCode::Spent,
+            TimestampResultCode::Unavailable,
+        );
+        SpentKeyImageCache::open(dir.path(), logger.clone())
+            .unwrap()
+            .insert(&[spent.clone()])
+            .unwrap();
+
+        let cache = SpentKeyImageCache::open(dir.path(), logger).unwrap();
+        assert_eq!(cache.get(&spent.key_image).unwrap(), Some(spent));
+        assert_eq!(cache.get(&KeyImage::from(2)).unwrap(), None);
+    }
+}
@@ -1,10 +1,10 @@
 // Copyright (c) 2018-2022 The MobileCoin Foundation
 
-use super::Error;
+use super::{receipt_status, Error, ReceiptStatus, SenderReceipt};
 use grpcio::{ChannelBuilder, Environment};
 use mc_blockchain_types::BlockIndex;
 use mc_common::{logger::Logger, trace_time};
-use mc_crypto_keys::CompressedRistrettoPublic;
+use mc_crypto_keys::{CompressedRistrettoPublic, RistrettoPrivate};
 use mc_fog_api::{fog_common::BlockRange, ledger, ledger_grpc};
 use mc_fog_uri::FogLedgerUri;
 use mc_util_grpc::{BasicCredentials, ConnectionUriGrpcioChannel, GrpcRetryConfig};
@@ -73,7 +73,31 @@ impl FogUntrustedLedgerGrpcClient {
                 self.blocks_client
                     .get_blocks_opt(&request, self.creds.call_option()?)
             })
-            .map_err(|grpcio_error| Error::Grpc(self.uri.clone(), grpcio_error))
+            .map_err(|grpcio_error| Error::grpc(self.uri.clone(), grpcio_error))
+    }
+
+    /// Make (non-private) request for the cumulative TxOut count after each
+    /// block of a range, e.g. to map global TxOut indices to blocks without
+    /// downloading the blocks.
+    pub fn get_txo_count_history(
+        &self,
+        block_range: Range<BlockIndex>,
+    ) -> Result<ledger::TxoCountHistoryResponse, Error> {
+        trace_time!(
+            self.logger,
+            "FogUntrustedLedgerGrpcClient::get_txo_count_history"
+        );
+
+        let mut request = ledger::TxoCountHistoryRequest::new();
+        request.mut_range().start_block = block_range.start;
+        request.mut_range().end_block = block_range.end;
+
+        self.grpc_retry_config
+            .retry(|| {
+                self.blocks_client
+                    .get_txo_count_history_opt(&request, self.creds.call_option()?)
+            })
+            .map_err(|grpcio_error| Error::grpc(self.uri.clone(), grpcio_error))
     }
 
     /// Make (non-private) request to check if particular TxOut public keys
@@ -99,6 +123,61 @@ impl FogUntrustedLedgerGrpcClient {
                 self.tx_out_client
                     .get_tx_outs_opt(&request, self.creds.call_option()?)
             })
-            .map_err(|grpcio_error| Error::Grpc(self.uri.clone(), grpcio_error))
+            .map_err(|grpcio_error| Error::grpc(self.uri.clone(), grpcio_error))
+    }
+
+    /// Make (non-private) request for the root element of the TxOut
+    /// membership tree which each block of a range was validated against, e.g.
+    /// to verify membership proofs fetched earlier against the root they were
+    /// made from.
+    pub fn get_merkle_root_history(
+        &self,
+        block_range: Range<BlockIndex>,
+    ) -> Result<ledger::MerkleRootHistoryResponse, Error> {
+        trace_time!(
+            self.logger,
+            "FogUntrustedLedgerGrpcClient::get_merkle_root_history"
+        );
+
+        let mut request = ledger::MerkleRootHistoryRequest::new();
+        request.mut_range().start_block = block_range.start;
+        request.mut_range().end_block = block_range.end;
+
+        self.grpc_retry_config
+            .retry(|| {
+                self.tx_out_client
+                    .get_merkle_root_history_opt(&request, self.creds.call_option()?)
+            })
+            .map_err(|grpcio_error| Error::grpc(self.uri.clone(), grpcio_error))
+    }
+
+    /// Check payment receipts against the ledger, returning the status of each
+    /// of them, in order. See [SenderReceipt].
+    ///
+    /// Arguments:
+    /// * receipts: The receipts given by the senders
+    /// * view_private_key: The recipient's view private key, which proves that
+    ///   the sender created each TxOut
+    pub fn check_receipts(
+        &self,
+        receipts: &[SenderReceipt],
+        view_private_key: &RistrettoPrivate,
+    ) -> Result<Vec<ReceiptStatus>, Error> {
+        let response =
+            self.get_tx_outs(receipts.iter().map(|receipt| receipt.tx_out_public_key))?;
+        if response.results.len() != receipts.len() {
+            return Err(Error::TxOutResultCount(
+                receipts.len(),
+                response.results.len(),
+            ));
+        }
+
+        receipts
+            .iter()
+            .zip(response.results.iter())
+            .map(|(receipt, result)| {
+                receipt_status(receipt, result, response.num_blocks, view_private_key)
+            })
+            .collect()
     }
 }
@@ -0,0 +1,192 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Records of fog ledger responses which can be persisted and re-verified
+//! later, e.g. by an auditor.
+
+use der::DateTime;
+use displaydoc::Display;
+use mc_attest_core::EvidenceKind;
+use mc_attest_verifier::DcapVerifier;
+use mc_attest_verifier_types::DcapEvidence;
+use mc_attestation_verifier::{Evidence, TrustedIdentity, VerificationTreeDisplay};
+use mc_crypto_keys::{Ed25519Public, Ed25519Signature, Verifier};
+use mc_fog_types::ledger::{
+    key_image_response_signed_message, CheckKeyImagesResponse, KeyImageResult,
+};
+use serde::{Deserialize, Serialize};
+
+/// A ledger enclave's signed answer to a key image query, along with the
+/// attestation evidence of the enclave.
+///
+/// The evidence binds the enclave's Ed25519 identity key, which signed the
+/// responder id, the chain and ingestion state and the results, so the bundle
+/// shows a third party that the attested enclave gave this answer. The
+/// evidence is re-verified as of a time the verifier chooses, so a bundle
+/// whose collateral or TCB has since expired no longer verifies.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct VerificationBundle {
+    /// The responder id the client attested to
+    pub responder_id: String,
+
+    /// The attestation evidence the enclave presented
+    pub attestation_evidence: EvidenceKind,
+
+    /// When the evidence was verified, in seconds since the Unix epoch
+    pub attested_at: u64,
+
+    /// The number of blocks in the ledger at the time of the response
+    pub num_blocks: u64,
+
+    /// The number of txos in the ledger at the time of the response
+    pub global_txo_count: u64,
+
+    /// The latest block version at the time of the response
+    pub latest_block_version: u32,
+
+    /// The number of blocks in the ledger the server ingests key images from
+    pub ledger_num_blocks: u64,
+
+    /// Whether the server had not yet ingested every block of its ledger
+    pub serving_degraded: bool,
+
+    /// The results of the key image checks
+    pub results: Vec<KeyImageResult>,
+
+    /// The enclave's signature over the responder id, the chain and ingestion
+    /// state and the results
+    pub signature: Vec<u8>,
+}
+
+impl VerificationBundle {
+    /// Record a key image check response, as the enclave signed it, along with
+    /// the attestation of the session it arrived over.
+    pub fn from_key_image_response(
+        responder_id: String,
+        attestation_evidence: EvidenceKind,
+        attested_at: DateTime,
+        response: &CheckKeyImagesResponse,
+    ) -> Self {
+        Self {
+            responder_id,
+            attestation_evidence,
+            attested_at: attested_at.unix_duration().as_secs(),
+            num_blocks: response.num_blocks,
+            global_txo_count: response.global_txo_count,
+            latest_block_version: response.latest_block_version,
+            ledger_num_blocks: response.ledger_num_blocks,
+            serving_degraded: response.serving_degraded,
+            results: response.results.clone(),
+            signature: response.signature.clone(),
+        }
+    }
+
+    /// Re-verify the attestation evidence against the given identities, as of
+    /// `time`, and check that the enclave it attests to signed the rest of
+    /// the bundle.
+    ///
+    /// `time` should come from the verifier's own clock, e.g. the current
+    /// time. The bundle's `attested_at` is not signed, so checking the
+    /// evidence as of it would let a replayed bundle pick the time its
+    /// collateral and TCB are checked at.
+    pub fn verify(
+        &self,
+        identities: &[TrustedIdentity],
+        time: DateTime,
+    ) -> Result<(), VerificationError> {
+        let prost_evidence = match &self.attestation_evidence {
+            EvidenceKind::Dcap(prost_evidence) => prost_evidence,
+            EvidenceKind::Epid(_) => return Err(VerificationError::UnsupportedEvidence),
+        };
+        let DcapEvidence {
+            quote,
+            collateral,
+            report_data,
+        } = DcapEvidence::try_from(prost_evidence)
+            .map_err(|_| VerificationError::Deserialization)?;
+        let signer = report_data
+            .custom_identity()
+            .and_then(|bytes| Ed25519Public::try_from(&bytes[..]).ok())
+            .ok_or(VerificationError::MissingIdentity)?;
+
+        let verifier = DcapVerifier::new(identities, time, report_data);
+        let evidence =
+            Evidence::new(quote, collateral).map_err(|_| VerificationError::Deserialization)?;
+        let verification_output = verifier.verify(&evidence);
+        if !bool::from(verification_output.is_success()) {
+            let display_tree = VerificationTreeDisplay::new(&verifier, verification_output);
+            return Err(VerificationError::Verification(display_tree.to_string()));
+        }
+
+        let signature = Ed25519Signature::try_from(&self.signature[..])
+            .map_err(|_| VerificationError::Signature)?;
+        let response = CheckKeyImagesResponse {
+            num_blocks: self.num_blocks,
+            global_txo_count: self.global_txo_count,
+            latest_block_version: self.latest_block_version,
+            ledger_num_blocks: self.ledger_num_blocks,
+            serving_degraded: self.serving_degraded,
+            results: self.results.clone(),
+            ..Default::default()
+        };
+        let message = key_image_response_signed_message(&self.responder_id, &response);
+        signer
+            .verify(&message, &signature)
+            .map_err(|_| VerificationError::Signature)
+    }
+}
+
+/// An error re-verifying a [VerificationBundle]
+#[derive(Clone, Debug, Display, Eq, PartialEq)]
+pub enum VerificationError {
+    /// Only DCAP evidence can be re-verified
+    UnsupportedEvidence,
+    /// The attestation evidence could not be decoded
+    Deserialization,
+    /// The attestation evidence failed verification: {0}
+    Verification(String),
+    /// The attestation evidence does not contain a signing key
+    MissingIdentity,
+    /// The enclave's signature over the response is missing or invalid
+    Signature,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mc_attest_verifier_types::prost;
+    use std::time::Duration;
+
+    #[test]
+    fn bundle_records_response() {
+        let response = CheckKeyImagesResponse {
+            num_blocks: 10,
+            global_txo_count: 100,
+            latest_block_version: 3,
+            ledger_num_blocks: 12,
+            serving_degraded: true,
+            signature: vec![0x55; 64],
+            ..Default::default()
+        };
+        let attested_at = DateTime::from_unix_duration(Duration::from_secs(1_700_000_000)).unwrap();
+        let bundle = VerificationBundle::from_key_image_response(
+            "ledger.example.com:443".to_owned(),
+            EvidenceKind::Dcap(prost::DcapEvidence::default()),
+            attested_at,
+            &response,
+        );
+
+        assert_eq!(bundle.attested_at, 1_700_000_000);
+        assert_eq!(bundle.num_blocks, 10);
+        assert_eq!(bundle.global_txo_count, 100);
+        assert_eq!(bundle.latest_block_version, 3);
+        assert_eq!(bundle.ledger_num_blocks, 12);
+        assert!(bundle.serving_degraded);
+        assert_eq!(bundle.signature, vec![0x55; 64]);
+
+        // Empty evidence can't be verified.
+        assert_eq!(
+            bundle.verify(&[], attested_at),
+            Err(VerificationError::Deserialization)
+        );
+    }
+}
@@ -40,6 +40,15 @@ pub enum Error {
 
     /// Prost decode error
     ProstDecode,
+
+    /// The request has {0} key images, but at most {1} are allowed
+    TooManyKeyImages(u64, u64),
+
+    /// The request has {0} outputs, but at most {1} are allowed
+    TooManyOutputs(u64, u64),
+
+    /// The query type {0} was not agreed to for this session
+    UnsupportedQuery(u32),
 }
 
 /// An error when something goes wrong with adding a record
@@ -62,6 +62,9 @@ pub struct UntrustedKeyImageQueryResponse {
 
     /// The (max of) latest_block_version and mc_transaction_core::BLOCK_VERSION
     pub max_block_version: u32,
+
+    /// The number of blocks in the ledger that key images are ingested from.
+    pub ledger_num_blocks: u64,
 }
 
 /// The API for interacting with a ledger node's enclave.
@@ -70,6 +73,11 @@ pub trait LedgerEnclave: ReportableEnclave {
     /// Perform one-time initialization upon enclave startup.
     fn enclave_init(&self, self_id: &ResponderId, desired_capacity: u64) -> Result<()>;
 
+    /// Pad responses to clients to these sizes in bytes, replacing any
+    /// previous ones. Clients are told them when they attest, and pad their
+    /// requests to them too.
+    fn set_padding_buckets(&self, padding_buckets: Vec<u32>) -> Result<()>;
+
     /// Retrieve the public identity of the enclave.
     fn get_identity(&self) -> Result<X25519Public>;
 
@@ -127,6 +135,15 @@ pub trait LedgerEnclave: ReportableEnclave {
         ledger_store_auth_response: NonceAuthResponse,
     ) -> Result<()>;
 
+    /// Set the MRENCLAVE values of Fog Ledger Store enclaves which must not be
+    /// connected to, replacing any previous list. Connections to stores with
+    /// a blocked MRENCLAVE are closed, and the ids of those stores are
+    /// returned.
+    fn set_blocked_store_measurements(
+        &self,
+        measurements: Vec<[u8; 32]>,
+    ) -> Result<Vec<ResponderId>>;
+
     /// Check to see if a particular key image is present on this key image
     /// store. Used by the store server in a router/store system to respond
     /// to requests from a ledger router.
@@ -136,12 +153,35 @@ pub trait LedgerEnclave: ReportableEnclave {
         response: UntrustedKeyImageQueryResponse,
     ) -> Result<EnclaveMessage<NonceSession>>;
 
+    /// Check several router queries against this key image store in one
+    /// call, sharing the same untrusted data.
+    ///
+    /// Each query gets its own result, in the same order, so a query that
+    /// fails to decrypt does not fail the rest of the batch. The default
+    /// implementation calls [LedgerEnclave::check_key_image_store] for each
+    /// query; SGX implementations override it to cross the enclave boundary
+    /// only once.
+    fn check_key_image_store_batch(
+        &self,
+        msgs: Vec<EnclaveMessage<NonceSession>>,
+        response: UntrustedKeyImageQueryResponse,
+    ) -> Result<Vec<Result<EnclaveMessage<NonceSession>>>> {
+        Ok(msgs
+            .into_iter()
+            .map(|msg| self.check_key_image_store(msg, response.clone()))
+            .collect())
+    }
+
     /// Decrypts a client query message and converts it into a
     /// SealedClientMessage which can be unsealed multiple times to
     /// construct the MultiKeyImageStoreRequest.
+    ///
+    /// Queries with more than `max_key_images` key images are rejected, unless
+    /// `max_key_images` is zero.
     fn decrypt_and_seal_query(
         &self,
         client_query: EnclaveMessage<ClientSession>,
+        max_key_images: usize,
     ) -> Result<SealedClientMessage>;
 
     /// Transforms a client query request into a list of query request data.
@@ -42,6 +42,14 @@ pub enum EnclaveCall {
     /// The [LedgerEnclave::enclave_init()] method.
     EnclaveInit(ResponderId, u64),
 
+    /// Keep at most about this many bytes of the key image store's ORAM in
+    /// enclave memory, and the rest in untrusted memory. This only affects
+    /// stores created afterwards, by [EnclaveCall::EnclaveInit].
+    SetOramMemoryBudget(u64),
+
+    /// The [LedgerEnclave::set_padding_buckets()] method.
+    SetPaddingBuckets(Vec<u32>),
+
     /// The [LedgerEnclave::client_accept()] method.
     ///
     /// Process a new inbound client connection.
@@ -112,11 +120,18 @@ pub enum EnclaveCall {
     /// initialized and discovers a new Fog Ledger Store.
     LedgerStoreConnect(ResponderId, NonceAuthResponse),
 
+    /// The [LedgerEnclave::set_blocked_store_measurements()] method.
+    ///
+    /// Set the MRENCLAVE values of Fog Ledger Stores which must not be
+    /// connected to, closing connections to any such stores.
+    SetBlockedStoreMeasurements(Vec<[u8; 32]>),
+
     /// The [LedgerEnclave::decrypt_and_seal_query()] method.
     ///
     /// Takes a client query message and returns a SealedClientMessage
-    /// sealed for the current enclave.
-    DecryptAndSealQuery(EnclaveMessage<ClientSession>),
+    /// sealed for the current enclave, if the query has no more key images
+    /// than the given limit.
+    DecryptAndSealQuery(EnclaveMessage<ClientSession>, usize),
 
     /// The [LedgerEnclave::create_multi_key_image_store_query()] method.
     ///
@@ -139,6 +154,14 @@ pub enum EnclaveCall {
     /// client.
     CheckKeyImageStore(EnclaveMessage<NonceSession>, UntrustedKeyImageQueryResponse),
 
+    /// The [LedgerEnclave::check_key_image_store_batch()] method.
+    ///
+    /// Check several router queries in a single ECALL.
+    CheckKeyImageStoreBatch(
+        Vec<EnclaveMessage<NonceSession>>,
+        UntrustedKeyImageQueryResponse,
+    ),
+
     /// The [LedgerEnclave::FrontendAccept()] method.
     /// Called by a Store accepting a Router's incoming
     /// connection.
@@ -0,0 +1,42 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Implementation of ed25519 signer identity for report
+
+use mc_crypto_ake_enclave::EnclaveIdentity;
+use mc_crypto_keys::{Ed25519Pair, Ed25519Public, Ed25519Signature, Signer};
+use mc_rand::McRng;
+use mc_util_from_random::FromRandom;
+
+/// An enclave identity based on an ed25519 key pair, which the enclave signs
+/// its key image check responses with, so that they can be shown to a third
+/// party along with its attestation evidence
+pub struct Ed25519Identity {
+    signing_keypair: Ed25519Pair,
+}
+
+impl Default for Ed25519Identity {
+    fn default() -> Self {
+        Self {
+            signing_keypair: Ed25519Pair::from_random(&mut McRng),
+        }
+    }
+}
+
+impl Ed25519Identity {
+    /// Get the associated public key
+    pub fn get_public_key(&self) -> Ed25519Public {
+        self.signing_keypair.public_key()
+    }
+
+    /// Sign a message with the identity key
+    pub fn sign(&self, message: &[u8]) -> Ed25519Signature {
+        self.signing_keypair.sign(message)
+    }
+}
+
+impl EnclaveIdentity for Ed25519Identity {
+    /// Get the bytes for the attestation evidence
+    fn get_bytes_for_report(&self) -> [u8; 32] {
+        *self.get_public_key().as_ref()
+    }
+}
@@ -12,11 +12,14 @@
 #![deny(missing_docs)]
 extern crate alloc;
 
+mod identity;
 mod key_image_store;
+pub use identity::Ed25519Identity;
+
 use alloc::{collections::BTreeMap, vec::Vec};
 use core::cmp::max;
 use key_image_store::{KeyImageStore, StorageDataSize, StorageMetaSize};
-use mc_attest_core::{DcapEvidence, EnclaveReportDataContents, Report, TargetInfo};
+use mc_attest_core::{DcapEvidence, EnclaveReportDataContents, MrEnclave, Report, TargetInfo};
 use mc_attest_enclave_api::{
     ClientAuthRequest, ClientAuthResponse, ClientSession, EnclaveMessage, NonceAuthRequest,
     NonceAuthResponse, NonceSession, SealedClientMessage,
@@ -24,9 +27,9 @@ use mc_attest_enclave_api::{
 use mc_blockchain_types::MAX_BLOCK_VERSION;
 use mc_common::{
     logger::{log, Logger},
-    ResponderId,
+    LruCache, ResponderId,
 };
-use mc_crypto_ake_enclave::{AkeEnclaveState, NullIdentity};
+use mc_crypto_ake_enclave::AkeEnclaveState;
 use mc_crypto_keys::X25519Public;
 use mc_fog_ledger_enclave_api::{
     Error, KeyImageData, KeyImageResult, LedgerEnclave, OutputContext, Result,
@@ -35,7 +38,9 @@ use mc_fog_ledger_enclave_api::{
 use mc_fog_types::{
     common::BlockRange,
     ledger::{
-        CheckKeyImagesRequest, CheckKeyImagesResponse, GetOutputsRequest, GetOutputsResponse,
+        key_image_response_signed_message, pad_response, CheckKeyImagesRequest,
+        CheckKeyImagesResponse, GetOutputsRequest, GetOutputsResponse, LedgerCapabilities,
+        LedgerQueryType,
     },
 };
 use mc_oblivious_traits::ORAMStorageCreator;
@@ -45,6 +50,22 @@ use serde::{Deserialize, Serialize};
 
 mod oblivious_utils;
 
+/// The maximum number of client sessions whose negotiated capabilities are
+/// remembered, which matches the number of sessions the AKE keeps.
+const MAX_CLIENT_SESSIONS: usize = 10_000;
+
+/// The capabilities to agree to with a client which offered the given ones,
+/// given our own. Clients which offered none, e.g. because they predate
+/// negotiation, or offered something we can't decode, are agreed to none.
+fn negotiate_capabilities(ours: &LedgerCapabilities, offered: &[u8]) -> Option<LedgerCapabilities> {
+    if offered.is_empty() {
+        return None;
+    }
+    mc_util_serial::decode::<LedgerCapabilities>(offered)
+        .ok()
+        .map(|offered| ours.negotiate(&offered))
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 /// Response from a shard enclave to a router enclave for the key image query
 struct ShardKeyImageResponse {
@@ -60,8 +81,16 @@ where
     /// The encrypted storage
     key_image_store: Mutex<Option<KeyImageStore<OSC>>>,
 
-    /// The enclave state
-    ake: AkeEnclaveState<NullIdentity>,
+    /// The enclave state, whose identity signs key image check responses
+    ake: AkeEnclaveState<Ed25519Identity>,
+
+    /// The capabilities offered to clients, including the padding buckets
+    /// configured by the operator
+    capabilities: Mutex<LedgerCapabilities>,
+
+    /// The capabilities agreed to with each client session which negotiated
+    /// them
+    session_capabilities: Mutex<LruCache<ClientSession, LedgerCapabilities>>,
 
     /// Logger object
     logger: Logger,
@@ -77,9 +106,73 @@ where
         Self {
             key_image_store: Mutex::new(None),
             ake: Default::default(),
+            capabilities: Mutex::new(LedgerCapabilities::current()),
+            session_capabilities: Mutex::new(LruCache::new(MAX_CLIENT_SESSIONS)),
             logger,
         }
     }
+
+    /// The padding buckets to pad responses to clients to.
+    fn padding_buckets(&self) -> Result<Vec<u32>> {
+        Ok(self.capabilities.lock()?.padding_buckets.clone())
+    }
+
+    /// The capabilities agreed to with a client session, which must include
+    /// `query_type`. Sessions which didn't negotiate get the legacy ones.
+    fn client_capabilities(
+        &self,
+        session: &ClientSession,
+        query_type: LedgerQueryType,
+    ) -> Result<LedgerCapabilities> {
+        let capabilities = self
+            .session_capabilities
+            .lock()?
+            .get(session)
+            .cloned()
+            .unwrap_or_else(LedgerCapabilities::legacy);
+        if !capabilities.supports_query(query_type) {
+            return Err(Error::UnsupportedQuery(query_type as u32));
+        }
+        Ok(capabilities)
+    }
+
+    /// Sign the chain state, ingestion status and results of a key image check
+    /// response, along with the responder id clients attest to, with the
+    /// identity key in our attestation evidence.
+    fn sign_response(&self, response: &mut CheckKeyImagesResponse) -> Result<()> {
+        let responder_id = self.ake.get_client_self_id()?;
+        let message = key_image_response_signed_message(&responder_id.to_string(), response);
+        response.signature = self.ake.get_identity().sign(&message).to_bytes().to_vec();
+        Ok(())
+    }
+
+    /// Decrypt and decode a key image query sent by a router to this store.
+    fn decrypt_key_image_store_query(
+        &self,
+        msg: EnclaveMessage<NonceSession>,
+    ) -> Result<(NonceSession, CheckKeyImagesRequest)> {
+        let channel_id = msg.channel_id.clone();
+        let user_plaintext = self.ake.frontend_decrypt(msg)?;
+
+        let req: CheckKeyImagesRequest = mc_util_serial::decode(&user_plaintext).map_err(|e| {
+            log::error!(self.logger, "Could not decode user request: {}", e);
+            Error::ProstDecode
+        })?;
+
+        Ok((channel_id, req))
+    }
+
+    /// Encrypt a store's key image query response for return to the router.
+    fn encrypt_key_image_store_response(
+        &self,
+        channel_id: &NonceSession,
+        resp: ShardKeyImageResponse,
+    ) -> Result<EnclaveMessage<NonceSession>> {
+        let response_plaintext_bytes = mc_util_serial::serialize(&resp)?;
+        Ok(self
+            .ake
+            .frontend_encrypt(channel_id, &[], &response_plaintext_bytes)?)
+    }
 }
 
 /// Implementation of the reportable enclave for sgxledger enclave
@@ -120,24 +213,62 @@ where
         Ok(())
     }
 
+    fn set_padding_buckets(&self, padding_buckets: Vec<u32>) -> Result<()> {
+        let mut capabilities = self.capabilities.lock()?;
+        *capabilities = capabilities.clone().with_padding_buckets(&padding_buckets);
+        Ok(())
+    }
+
     fn get_identity(&self) -> Result<X25519Public> {
         Ok(self.ake.get_kex_identity())
     }
 
     fn client_accept(&self, req: ClientAuthRequest) -> Result<(ClientAuthResponse, ClientSession)> {
-        Ok(self.ake.client_accept(req)?)
+        let capabilities = self.capabilities.lock()?.clone();
+        let mut negotiated = None;
+        let (response, session) = self.ake.client_accept_with_capabilities(req, |offered| {
+            negotiated = negotiate_capabilities(&capabilities, offered);
+            negotiated
+                .as_ref()
+                .map(mc_util_serial::encode)
+                .unwrap_or_default()
+        })?;
+
+        let mut session_capabilities = self.session_capabilities.lock()?;
+        match negotiated {
+            Some(negotiated) => {
+                session_capabilities.put(session.clone(), negotiated);
+            }
+            None => {
+                session_capabilities.pop(&session);
+            }
+        }
+
+        Ok((response, session))
     }
 
     fn client_close(&self, channel_id: ClientSession) -> Result<()> {
+        self.session_capabilities.lock()?.pop(&channel_id);
         Ok(self.ake.client_close(channel_id)?)
     }
 
     fn get_outputs(&self, msg: EnclaveMessage<ClientSession>) -> Result<OutputContext> {
+        let channel_id = msg.channel_id.clone();
         let request_bytes = self.ake.client_decrypt(msg)?;
 
         // Try and deserialize.
         let enclave_request: GetOutputsRequest = mc_util_serial::decode(&request_bytes)?;
 
+        let capabilities = self.client_capabilities(&channel_id, LedgerQueryType::GetOutputs)?;
+        let max_outputs = capabilities.max_outputs_per_request as usize;
+        let num_outputs = enclave_request.indices.len();
+        if max_outputs > 0 && num_outputs > max_outputs {
+            return Err(Error::TooManyOutputs(
+                num_outputs as u64,
+                max_outputs as u64,
+            ));
+        }
+
         let output_context = OutputContext {
             indexes: enclave_request.indices,
             merkle_root_block: enclave_request.merkle_root_block,
@@ -148,9 +279,11 @@ where
 
     fn get_outputs_data(
         &self,
-        response: GetOutputsResponse,
+        mut response: GetOutputsResponse,
         client: ClientSession,
     ) -> Result<EnclaveMessage<ClientSession>> {
+        pad_response(&mut response, &self.padding_buckets()?);
+
         // Serialize this for the client.
         let response_bytes = mc_util_serial::encode(&response);
 
@@ -171,6 +304,17 @@ where
             Error::ProstDecode
         })?;
 
+        let capabilities =
+            self.client_capabilities(&channel_id, LedgerQueryType::CheckKeyImages)?;
+        let max_key_images = capabilities.max_key_images_per_request as usize;
+        let num_key_images = req.queries.len();
+        if max_key_images > 0 && num_key_images > max_key_images {
+            return Err(Error::TooManyKeyImages(
+                num_key_images as u64,
+                max_key_images as u64,
+            ));
+        }
+
         let mut resp = CheckKeyImagesResponse {
             // `num_blocks` is a count, `end_block` is an exclusive index.
             // A block range of [0, 5) would have a count of 5 blocks.
@@ -182,7 +326,12 @@ where
                 .last_known_block_cumulative_txo_count,
             latest_block_version: untrusted_key_image_query_response.latest_block_version,
             max_block_version: untrusted_key_image_query_response.max_block_version,
+            padding: Default::default(),
+            ledger_num_blocks: Default::default(),
+            serving_degraded: Default::default(),
+            signature: Default::default(),
         };
+        set_ingestion_status(&mut resp, &untrusted_key_image_query_response);
 
         // Do the scope lock of keyimagetore
         {
@@ -196,6 +345,8 @@ where
                 .collect();
         }
 
+        self.sign_response(&mut resp)?;
+        pad_response(&mut resp, &self.padding_buckets()?);
         let response_plaintext_bytes = mc_util_serial::encode(&resp);
 
         let response = self
@@ -231,11 +382,47 @@ where
             .backend_connect(ledger_store_id, ledger_store_auth_response)?)
     }
 
+    fn set_blocked_store_measurements(
+        &self,
+        measurements: Vec<[u8; 32]>,
+    ) -> Result<Vec<ResponderId>> {
+        Ok(self.ake.set_blocked_backend_measurements(
+            measurements.into_iter().map(MrEnclave::from).collect(),
+        )?)
+    }
+
     fn decrypt_and_seal_query(
         &self,
         client_query: EnclaveMessage<ClientSession>,
+        max_key_images: usize,
     ) -> Result<SealedClientMessage> {
-        Ok(self.ake.decrypt_client_message_for_enclave(client_query)?)
+        let capabilities =
+            self.client_capabilities(&client_query.channel_id, LedgerQueryType::CheckKeyImages)?;
+        // Zero means no limit, so apply the tighter of the router's limit and
+        // the one agreed to with the client.
+        let max_key_images = match (max_key_images, capabilities.max_key_images_per_request) {
+            (0, limit) => limit as usize,
+            (limit, 0) => limit,
+            (ours, theirs) => ours.min(theirs as usize),
+        };
+
+        let sealed_query = self.ake.decrypt_client_message_for_enclave(client_query)?;
+        if max_key_images > 0 {
+            let client_query_plaintext = self.ake.unseal(&sealed_query)?;
+            let client_query_request: CheckKeyImagesRequest =
+                mc_util_serial::decode(&client_query_plaintext).map_err(|e| {
+                    log::error!(self.logger, "Could not decode client query request: {}", e);
+                    Error::ProstDecode
+                })?;
+            let num_key_images = client_query_request.queries.len();
+            if num_key_images > max_key_images {
+                return Err(Error::TooManyKeyImages(
+                    num_key_images as u64,
+                    max_key_images as u64,
+                ));
+            }
+        }
+        Ok(sealed_query)
     }
 
     fn create_multi_key_image_store_query_data(
@@ -294,13 +481,20 @@ where
             &plaintext_results,
         );
 
-        let client_query_response = CheckKeyImagesResponse {
+        let mut client_query_response = CheckKeyImagesResponse {
             num_blocks,
             global_txo_count,
             results: oblivious_results,
             latest_block_version,
             max_block_version,
+            padding: Default::default(),
+            ledger_num_blocks: Default::default(),
+            serving_degraded: Default::default(),
+            signature: Default::default(),
         };
+        set_ingestion_status(&mut client_query_response, &untrusted_response);
+        self.sign_response(&mut client_query_response)?;
+        pad_response(&mut client_query_response, &self.padding_buckets()?);
         let response_plaintext_bytes = mc_util_serial::encode(&client_query_response);
         let response =
             self.ake
@@ -314,37 +508,71 @@ where
         msg: EnclaveMessage<NonceSession>,
         untrusted_key_image_query_response: UntrustedKeyImageQueryResponse,
     ) -> Result<EnclaveMessage<NonceSession>> {
-        let channel_id = msg.channel_id.clone();
-        let user_plaintext = self.ake.frontend_decrypt(msg)?;
-
-        let req: CheckKeyImagesRequest = mc_util_serial::decode(&user_plaintext).map_err(|e| {
-            log::error!(self.logger, "Could not decode user request: {}", e);
-            Error::ProstDecode
-        })?;
+        let (channel_id, req) = self.decrypt_key_image_store_query(msg)?;
 
-        let mut resp = ShardKeyImageResponse {
-            untrusted_response: untrusted_key_image_query_response,
-            results: Default::default(),
-        };
-
-        {
+        let results = {
             let mut lk = self.key_image_store.lock()?;
             let store = lk.as_mut().ok_or(Error::EnclaveNotInitialized)?;
 
-            resp.results = req
-                .queries
+            req.queries
                 .iter() //  get the key images used to find the key image data using the oram
                 .map(|key| store.find_record(&key.key_image))
-                .collect();
-        }
+                .collect()
+        };
 
-        // Encrypt for return to router
-        let response_plaintext_bytes = mc_util_serial::serialize(&resp)?;
-        let response = self
-            .ake
-            .frontend_encrypt(&channel_id, &[], &response_plaintext_bytes)?;
+        self.encrypt_key_image_store_response(
+            &channel_id,
+            ShardKeyImageResponse {
+                untrusted_response: untrusted_key_image_query_response,
+                results,
+            },
+        )
+    }
 
-        Ok(response)
+    fn check_key_image_store_batch(
+        &self,
+        msgs: Vec<EnclaveMessage<NonceSession>>,
+        untrusted_key_image_query_response: UntrustedKeyImageQueryResponse,
+    ) -> Result<Vec<Result<EnclaveMessage<NonceSession>>>> {
+        // Decrypt every query up front, so that the store is only locked once
+        // for the whole batch.
+        let requests = msgs
+            .into_iter()
+            .map(|msg| self.decrypt_key_image_store_query(msg))
+            .collect::<Vec<_>>();
+
+        let results = {
+            let mut lk = self.key_image_store.lock()?;
+            let store = lk.as_mut().ok_or(Error::EnclaveNotInitialized)?;
+
+            requests
+                .into_iter()
+                .map(|request| {
+                    request.map(|(channel_id, req)| {
+                        let results = req
+                            .queries
+                            .iter()
+                            .map(|key| store.find_record(&key.key_image))
+                            .collect::<Vec<_>>();
+                        (channel_id, results)
+                    })
+                })
+                .collect::<Vec<_>>()
+        };
+
+        Ok(results
+            .into_iter()
+            .map(|result| {
+                let (channel_id, results) = result?;
+                self.encrypt_key_image_store_response(
+                    &channel_id,
+                    ShardKeyImageResponse {
+                        untrusted_response: untrusted_key_image_query_response.clone(),
+                        results,
+                    },
+                )
+            })
+            .collect())
     }
 
     fn frontend_accept(
@@ -363,6 +591,7 @@ fn merge_untrusted_responses<'a>(
         last_known_block_cumulative_txo_count: 0,
         latest_block_version: 0,
         max_block_version: 0,
+        ledger_num_blocks: 0,
     };
 
     let mut untrusted_responses = [default_response]
@@ -370,6 +599,15 @@ fn merge_untrusted_responses<'a>(
         .chain(untrusted_responses.into_iter().cloned())
         .collect::<Vec<_>>();
 
+    // Shards which stopped at the end of their block range don't see the
+    // ledger grow, so take the furthest tip any shard has seen, including
+    // shards whose ranges are dropped below.
+    let ledger_num_blocks = untrusted_responses
+        .iter()
+        .map(|r| r.ledger_num_blocks)
+        .max()
+        .unwrap_or_default();
+
     untrusted_responses.sort_by(|a, b| a.processed_block_range.cmp(&b.processed_block_range));
 
     // This logic will remove fully contained overlaps. For example:
@@ -394,9 +632,21 @@ fn merge_untrusted_responses<'a>(
 
     let mut untrusted_response = untrusted_responses[index].clone();
     untrusted_response.processed_block_range = block_range;
+    untrusted_response.ledger_num_blocks = ledger_num_blocks;
     untrusted_response
 }
 
+/// Tell the client how far the ledger has been ingested, and whether it is
+/// behind the ledger's tip, so that it can tell "not spent" from "not yet
+/// ingested".
+fn set_ingestion_status(
+    response: &mut CheckKeyImagesResponse,
+    untrusted_response: &UntrustedKeyImageQueryResponse,
+) {
+    response.ledger_num_blocks = max(untrusted_response.ledger_num_blocks, response.num_blocks);
+    response.serving_degraded = response.num_blocks < response.ledger_num_blocks;
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -534,6 +784,7 @@ mod tests {
                         last_known_block_cumulative_txo_count: *txo_count,
                         latest_block_version: *latest_block_version,
                         max_block_version: *max_block_version,
+                        ledger_num_blocks: 0,
                     }
                 },
             )
@@ -544,8 +795,57 @@ mod tests {
             last_known_block_cumulative_txo_count: expected_response.1,
             latest_block_version: expected_response.2,
             max_block_version: expected_response.3,
+            ledger_num_blocks: 0,
         };
 
         assert_eq!(merge_untrusted_responses(&untrusted_responses), expected);
     }
+
+    fn untrusted_response(
+        block_range: (u64, u64),
+        ledger_num_blocks: u64,
+    ) -> UntrustedKeyImageQueryResponse {
+        UntrustedKeyImageQueryResponse {
+            processed_block_range: BlockRange::new(block_range.0, block_range.1),
+            ledger_num_blocks,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn merged_responses_are_degraded_until_caught_up() {
+        // A new shard which is still catching up knows about a later tip than
+        // the shards which have stopped at the end of their ranges.
+        let merged = merge_untrusted_responses(&[
+            untrusted_response((0, 5), 5),
+            untrusted_response((5, 8), 12),
+        ]);
+        assert_eq!(merged.processed_block_range, BlockRange::new(0, 8));
+        assert_eq!(merged.ledger_num_blocks, 12);
+
+        // That holds even when the catching up shard's range is dropped.
+        let contained = merge_untrusted_responses(&[
+            untrusted_response((0, 10), 10),
+            untrusted_response((5, 8), 12),
+        ]);
+        assert_eq!(contained.processed_block_range, BlockRange::new(0, 10));
+        assert_eq!(contained.ledger_num_blocks, 12);
+
+        let mut response = CheckKeyImagesResponse {
+            num_blocks: merged.processed_block_range.end_block,
+            ..Default::default()
+        };
+        set_ingestion_status(&mut response, &merged);
+        assert_eq!(response.ledger_num_blocks, 12);
+        assert!(response.serving_degraded);
+
+        // Once the ledger has been ingested, responses are no longer degraded.
+        set_ingestion_status(&mut response, &untrusted_response((0, 12), 12));
+        assert!(!response.serving_degraded);
+
+        // Stores which don't know the ledger's tip are not degraded.
+        set_ingestion_status(&mut response, &untrusted_response((0, 12), 0));
+        assert_eq!(response.ledger_num_blocks, 12);
+        assert!(!response.serving_degraded);
+    }
 }
@@ -6,6 +6,8 @@
 
 extern crate mc_fog_ocall_oram_storage_untrusted;
 
+pub use mc_fog_ocall_oram_storage_untrusted::set_oram_spill_dir;
+
 pub use mc_fog_ledger_enclave_api::{
     CheckKeyImagesResponse, EnclaveCall, Error, GetOutputsResponse, KeyImageData, KeyImageResult,
     KeyImageResultCode, LedgerEnclave, LedgerEnclaveProxy, OutputContext, OutputResult, Result,
@@ -81,6 +83,24 @@ impl LedgerSgxEnclave {
         enclave_path: path::PathBuf,
         self_id: &ResponderId,
         desired_capacity: u64,
+        logger: Logger,
+    ) -> LedgerSgxEnclave {
+        Self::new_with_oram_memory_budget(enclave_path, self_id, desired_capacity, None, logger)
+    }
+
+    /// Create a new sgx ledger enclave, whose oblivious map keeps at most
+    /// about `oram_memory_budget` bytes in enclave memory, and the rest in
+    /// untrusted memory, or on disk if [set_oram_spill_dir] was called. The
+    /// default is 32 MB.
+    ///
+    /// The budget is rounded down to a power of two. A smaller budget serves
+    /// larger maps from the same instance, but makes each query slower, since
+    /// more of each ORAM access then crosses the enclave boundary.
+    pub fn new_with_oram_memory_budget(
+        enclave_path: path::PathBuf,
+        self_id: &ResponderId,
+        desired_capacity: u64,
+        oram_memory_budget: Option<u64>,
         _logger: Logger,
     ) -> LedgerSgxEnclave {
         let mut launch_token: sgx_launch_token_t = [0; 1024];
@@ -108,6 +128,14 @@ impl LedgerSgxEnclave {
             _enclave: Arc::new(enclave),
         };
 
+        if let Some(oram_memory_budget) = oram_memory_budget {
+            sgx_enclave
+                .set_oram_memory_budget(oram_memory_budget)
+                .unwrap_or_else(|e| {
+                    panic!("set_oram_memory_budget({oram_memory_budget}) failed: {e:?}")
+                });
+        }
+
         sgx_enclave
             .enclave_init(self_id, desired_capacity)
             .unwrap_or_else(|e| panic!("enclave_init({self_id}) failed: {e:?}"));
@@ -115,6 +143,12 @@ impl LedgerSgxEnclave {
         sgx_enclave
     }
 
+    fn set_oram_memory_budget(&self, max_bytes: u64) -> Result<()> {
+        let inbuf = mc_util_serial::serialize(&EnclaveCall::SetOramMemoryBudget(max_bytes))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mc_util_serial::deserialize(&outbuf[..])?
+    }
+
     /// Takes serialized data, and fires to the corresponding ECALL.
     fn enclave_call(&self, inbuf: &[u8]) -> StdResult<Vec<u8>, SgxError> {
         Ok(make_variable_length_ecall(
@@ -137,6 +171,12 @@ impl LedgerEnclave for LedgerSgxEnclave {
         mc_util_serial::deserialize(&outbuf[..])?
     }
 
+    fn set_padding_buckets(&self, padding_buckets: Vec<u32>) -> Result<()> {
+        let inbuf = mc_util_serial::serialize(&EnclaveCall::SetPaddingBuckets(padding_buckets))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mc_util_serial::deserialize(&outbuf[..])?
+    }
+
     fn get_identity(&self) -> Result<X25519Public> {
         let inbuf = mc_util_serial::serialize(&EnclaveCall::GetIdentity)?;
         let outbuf = self.enclave_call(&inbuf)?;
@@ -208,11 +248,25 @@ impl LedgerEnclave for LedgerSgxEnclave {
         mc_util_serial::deserialize(&outbuf[..])?
     }
 
+    fn set_blocked_store_measurements(
+        &self,
+        measurements: Vec<[u8; 32]>,
+    ) -> Result<Vec<ResponderId>> {
+        let inbuf =
+            mc_util_serial::serialize(&EnclaveCall::SetBlockedStoreMeasurements(measurements))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mc_util_serial::deserialize(&outbuf[..])?
+    }
+
     fn decrypt_and_seal_query(
         &self,
         client_query: EnclaveMessage<ClientSession>,
+        max_key_images: usize,
     ) -> Result<SealedClientMessage> {
-        let inbuf = mc_util_serial::serialize(&EnclaveCall::DecryptAndSealQuery(client_query))?;
+        let inbuf = mc_util_serial::serialize(&EnclaveCall::DecryptAndSealQuery(
+            client_query,
+            max_key_images,
+        ))?;
         let outbuf = self.enclave_call(&inbuf)?;
         mc_util_serial::deserialize(&outbuf[..])?
     }
@@ -251,6 +305,17 @@ impl LedgerEnclave for LedgerSgxEnclave {
         mc_util_serial::deserialize(&outbuf[..])?
     }
 
+    fn check_key_image_store_batch(
+        &self,
+        msgs: Vec<EnclaveMessage<NonceSession>>,
+        response: UntrustedKeyImageQueryResponse,
+    ) -> Result<Vec<Result<EnclaveMessage<NonceSession>>>> {
+        let inbuf =
+            mc_util_serial::serialize(&EnclaveCall::CheckKeyImageStoreBatch(msgs, response))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mc_util_serial::deserialize(&outbuf[..])?
+    }
+
     fn frontend_accept(
         &self,
         auth_request: NonceAuthRequest,
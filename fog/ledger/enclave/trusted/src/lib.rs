@@ -10,9 +10,9 @@ use alloc::vec::Vec;
 use core::slice;
 use lazy_static::lazy_static;
 use mc_enclave_boundary::trusted::RetryBuffer;
-use mc_fog_ledger_enclave_api::{EnclaveCall, LedgerEnclave};
+use mc_fog_ledger_enclave_api::{EnclaveCall, Error, LedgerEnclave};
 use mc_fog_ledger_enclave_impl::SgxLedgerEnclave;
-use mc_fog_ocall_oram_storage_trusted::OcallORAMStorageCreator;
+use mc_fog_ocall_oram_storage_trusted::{set_treetop_caching_budget, OcallORAMStorageCreator};
 use mc_sgx_compat::panic::catch_unwind;
 use mc_sgx_report_cache_api::ReportableEnclave;
 use mc_sgx_slog::default_logger;
@@ -39,6 +39,13 @@ pub fn ecall_dispatcher(inbuf: &[u8]) -> Result<Vec<u8>, sgx_status_t> {
         EnclaveCall::EnclaveInit(self_id, desired_capacity) => {
             serialize(&ENCLAVE.enclave_init(&self_id, desired_capacity))
         }
+        EnclaveCall::SetOramMemoryBudget(max_bytes) => {
+            set_treetop_caching_budget(max_bytes);
+            serialize(&Ok::<(), Error>(()))
+        }
+        EnclaveCall::SetPaddingBuckets(padding_buckets) => {
+            serialize(&ENCLAVE.set_padding_buckets(padding_buckets))
+        }
         // Node-to-Client Attestation
         EnclaveCall::ClientAccept(auth_msg) => serialize(&ENCLAVE.client_accept(auth_msg)),
         EnclaveCall::ClientClose(channel_id) => serialize(&ENCLAVE.client_close(channel_id)),
@@ -69,8 +76,11 @@ pub fn ecall_dispatcher(inbuf: &[u8]) -> Result<Vec<u8>, sgx_status_t> {
         EnclaveCall::LedgerStoreConnect(responder_id, client_auth_response) => {
             serialize(&ENCLAVE.ledger_store_connect(responder_id, client_auth_response))
         }
-        EnclaveCall::DecryptAndSealQuery(client_query) => {
-            serialize(&ENCLAVE.decrypt_and_seal_query(client_query))
+        EnclaveCall::SetBlockedStoreMeasurements(measurements) => {
+            serialize(&ENCLAVE.set_blocked_store_measurements(measurements))
+        }
+        EnclaveCall::DecryptAndSealQuery(client_query, max_key_images) => {
+            serialize(&ENCLAVE.decrypt_and_seal_query(client_query, max_key_images))
         }
         EnclaveCall::CreateMultiKeyImageStoreQueryData(msg) => {
             serialize(&ENCLAVE.create_multi_key_image_store_query_data(msg))
@@ -81,6 +91,9 @@ pub fn ecall_dispatcher(inbuf: &[u8]) -> Result<Vec<u8>, sgx_status_t> {
         EnclaveCall::CheckKeyImageStore(req, response) => {
             serialize(&ENCLAVE.check_key_image_store(req, response))
         }
+        EnclaveCall::CheckKeyImageStoreBatch(reqs, response) => {
+            serialize(&ENCLAVE.check_key_image_store_batch(reqs, response))
+        }
         EnclaveCall::FrontendAccept(auth_message) => {
             serialize(&ENCLAVE.frontend_accept(auth_message))
         }
@@ -0,0 +1,143 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! The responder side of the attested key exchange, without an enclave.
+//!
+//! The mock's attestation evidence is a quote from the simulated quoting
+//! enclave, so clients only accept it when they are built with the `sgx-sim`
+//! feature of `mc-attest-verifier`, as tests depending on this crate are.
+
+use aes_gcm::Aes256Gcm;
+use grpcio::{RpcStatus, RpcStatusCode};
+use mc_attest_ake::{AuthRequestOutput, ClientAuthRequestInput, Ready, Start, Transition};
+use mc_attest_api::attest;
+use mc_attest_core::Report;
+use mc_attest_untrusted::DcapQuotingEnclave;
+use mc_attest_verifier_types::{DcapEvidence, EnclaveReportDataContents};
+use mc_attestation_verifier::{TrustedIdentity, TrustedMrSignerIdentity};
+use mc_crypto_keys::{X25519Private, X25519Public, X25519};
+use mc_rand::McRng;
+use mc_util_from_random::FromRandom;
+use mc_util_test_helper::{RngType, SeedableRng};
+use sha2::Sha512;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+/// The identity the mock server attests with, and its clients' sessions.
+#[derive(Clone)]
+pub struct MockAttestation {
+    responder_id: String,
+    identity: X25519Private,
+    evidence: DcapEvidence,
+    sessions: Arc<Mutex<HashMap<Vec<u8>, Ready<Aes256Gcm>>>>,
+}
+
+impl MockAttestation {
+    /// Create an identity for a server with the given responder id. The
+    /// identity is derived from `seed`, so the same seed gives the same
+    /// identity.
+    pub fn new(responder_id: impl Into<String>, seed: u64) -> Self {
+        let identity = X25519Private::from_random(&mut RngType::seed_from_u64(seed));
+        let report_data = EnclaveReportDataContents::new(
+            [0x2au8; 16].into(),
+            X25519Public::from(&identity),
+            [0x36u8; 32],
+        );
+        let mut report = Report::default();
+        report.as_mut().body.report_data.d[..32].copy_from_slice(&report_data.sha256());
+
+        let quote = DcapQuotingEnclave::quote_report(&report).expect("Failed to create quote");
+        let collateral = DcapQuotingEnclave::collateral(&quote).expect("Failed to get collateral");
+        let evidence = DcapEvidence {
+            quote,
+            collateral,
+            report_data,
+        };
+
+        Self {
+            responder_id: responder_id.into(),
+            identity,
+            evidence,
+            sessions: Default::default(),
+        }
+    }
+
+    /// The identity clients should trust to attest to the mock server.
+    pub fn trusted_identity(&self) -> TrustedIdentity {
+        let report_body = self.evidence.quote.app_report_body();
+        TrustedMrSignerIdentity::new(
+            report_body.mr_signer(),
+            report_body.isv_product_id(),
+            report_body.isv_svn(),
+            [] as [&str; 0],
+            [] as [&str; 0],
+        )
+        .into()
+    }
+
+    /// Accept a client's auth request, returning the auth response.
+    pub fn accept(
+        &self,
+        mut auth_request: attest::AuthMessage,
+    ) -> Result<attest::AuthMessage, RpcStatus> {
+        let auth_request = ClientAuthRequestInput::<X25519, Aes256Gcm, Sha512>::new(
+            AuthRequestOutput::from(auth_request.take_data()),
+            self.identity.clone(),
+            self.evidence.clone(),
+        );
+        let (session, auth_response) = Start::new(self.responder_id.clone())
+            .try_next(&mut McRng, auth_request)
+            .map_err(|err| {
+                RpcStatus::with_message(RpcStatusCode::PERMISSION_DENIED, err.to_string())
+            })?;
+
+        self.sessions
+            .lock()
+            .expect("mutex poisoned")
+            .insert(session.binding().to_vec(), session);
+
+        let mut response = attest::AuthMessage::new();
+        response.set_data(auth_response.into());
+        Ok(response)
+    }
+
+    /// Decrypt a message from a client.
+    pub fn decrypt(&self, message: &attest::Message) -> Result<Vec<u8>, RpcStatus> {
+        let mut sessions = self.sessions.lock().expect("mutex poisoned");
+        let session = sessions.get_mut(message.get_channel_id()).ok_or_else(|| {
+            RpcStatus::with_message(
+                RpcStatusCode::PERMISSION_DENIED,
+                "Unknown channel id, attest first".to_owned(),
+            )
+        })?;
+        session
+            .decrypt(message.get_aad(), message.get_data())
+            .map_err(|err| {
+                RpcStatus::with_message(RpcStatusCode::INVALID_ARGUMENT, err.to_string())
+            })
+    }
+
+    /// Encrypt a message for the client of a session.
+    pub fn encrypt(
+        &self,
+        channel_id: &[u8],
+        plaintext: &[u8],
+    ) -> Result<attest::Message, RpcStatus> {
+        let mut sessions = self.sessions.lock().expect("mutex poisoned");
+        let session = sessions.get_mut(channel_id).ok_or_else(|| {
+            RpcStatus::with_message(
+                RpcStatusCode::PERMISSION_DENIED,
+                "Unknown channel id, attest first".to_owned(),
+            )
+        })?;
+        let data = session
+            .encrypt(&[], plaintext)
+            .map_err(|err| RpcStatus::with_message(RpcStatusCode::INTERNAL, err.to_string()))?;
+
+        let mut message = attest::Message::new();
+        message.set_channel_id(channel_id.to_vec());
+        message.set_data(data);
+        Ok(message)
+    }
+}
@@ -0,0 +1,383 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! The scriptable ledger behind the mock server.
+
+use grpcio::{RpcStatus, RpcStatusCode};
+use mc_blockchain_types::BlockIndex;
+use mc_crypto_keys::CompressedRistrettoPublic;
+use mc_fog_types::ledger::{
+    CheckKeyImagesRequest, CheckKeyImagesResponse, KeyImageResult, KeyImageResultCode,
+};
+use mc_transaction_core::{
+    ring_signature::KeyImage,
+    tx::{TxOut, TxOutMembershipElement},
+    BlockVersion,
+};
+use mc_watcher_api::TimestampResultCode;
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+/// The calls to the mock server, for injecting errors into them.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum MockLedgerMethod {
+    /// Attesting, on the streaming or the unary key image API
+    Auth,
+    /// Checking key images, on the streaming or the unary key image API
+    CheckKeyImages,
+    /// FogBlockAPI::GetBlocks
+    GetBlocks,
+    /// FogBlockAPI::GetTxoCountHistory
+    GetTxoCountHistory,
+    /// FogUntrustedTxOutApi::GetTxOuts
+    GetTxOuts,
+    /// FogUntrustedTxOutApi::GetMerkleRootHistory
+    GetMerkleRootHistory,
+}
+
+/// A block of the mock ledger.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MockBlock {
+    /// The TxOuts created in the block
+    pub outputs: Vec<TxOut>,
+    /// The number of TxOuts in the ledger up to and including this block
+    pub global_txo_count: u64,
+    /// The timestamp of the block, in seconds since the Unix epoch
+    pub timestamp: u64,
+}
+
+/// Where a TxOut is in the mock ledger.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct MockTxOutLocation {
+    /// The global index of the TxOut
+    pub global_index: u64,
+    /// The index of the block with the TxOut
+    pub block_index: BlockIndex,
+    /// The timestamp of the block with the TxOut
+    pub timestamp: u64,
+}
+
+#[derive(Default)]
+struct MockLedgerState {
+    blocks: BTreeMap<BlockIndex, MockBlock>,
+    num_blocks: u64,
+    global_txo_count: u64,
+    latest_block_version: u32,
+    spent_key_images: HashMap<KeyImage, (BlockIndex, u64)>,
+    tx_outs: HashMap<CompressedRistrettoPublic, MockTxOutLocation>,
+    root_elements: HashMap<BlockIndex, TxOutMembershipElement>,
+    errors: HashMap<MockLedgerMethod, VecDeque<RpcStatus>>,
+}
+
+/// The ledger served by a mock fog ledger server.
+///
+/// Everything the server answers with comes from here, so tests can set up
+/// exactly the ledger they need, and change it while the server is running.
+/// Clones share the same ledger.
+#[derive(Clone)]
+pub struct MockLedger {
+    state: Arc<Mutex<MockLedgerState>>,
+}
+
+impl Default for MockLedger {
+    fn default() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(MockLedgerState {
+                latest_block_version: *BlockVersion::MAX,
+                ..Default::default()
+            })),
+        }
+    }
+}
+
+impl MockLedger {
+    fn state(&self) -> MutexGuard<MockLedgerState> {
+        self.state.lock().expect("mutex poisoned")
+    }
+
+    /// Append a block with the given TxOuts, returning its index.
+    pub fn add_block(&self, outputs: Vec<TxOut>, timestamp: u64) -> BlockIndex {
+        let mut state = self.state();
+        let block_index = state.num_blocks;
+        for tx_out in &outputs {
+            let location = MockTxOutLocation {
+                global_index: state.global_txo_count,
+                block_index,
+                timestamp,
+            };
+            state.tx_outs.insert(tx_out.public_key, location);
+            state.global_txo_count += 1;
+        }
+        let global_txo_count = state.global_txo_count;
+        state.blocks.insert(
+            block_index,
+            MockBlock {
+                outputs,
+                global_txo_count,
+                timestamp,
+            },
+        );
+        state.num_blocks += 1;
+        block_index
+    }
+
+    /// Set the number of blocks reported, without adding their contents.
+    /// Blocks beyond the ones added with [MockLedger::add_block] are served
+    /// as empty.
+    pub fn set_num_blocks(&self, num_blocks: u64) {
+        self.state().num_blocks = num_blocks;
+    }
+
+    /// Set the number of TxOuts reported.
+    pub fn set_global_txo_count(&self, global_txo_count: u64) {
+        self.state().global_txo_count = global_txo_count;
+    }
+
+    /// Set the latest block version reported.
+    pub fn set_latest_block_version(&self, block_version: BlockVersion) {
+        self.state().latest_block_version = *block_version;
+    }
+
+    /// Set the root element of the TxOut membership tree which a block was
+    /// validated against. Blocks without one report the default element.
+    pub fn set_root_element(&self, block_index: BlockIndex, root_element: TxOutMembershipElement) {
+        self.state().root_elements.insert(block_index, root_element);
+    }
+
+    /// Mark a key image as spent in a block.
+    pub fn spend_key_image(&self, key_image: KeyImage, block_index: BlockIndex, timestamp: u64) {
+        self.state()
+            .spent_key_images
+            .insert(key_image, (block_index, timestamp));
+    }
+
+    /// Make the next call of `method` fail with `code`. Failures queue up, so
+    /// calling this twice fails the next two calls.
+    pub fn fail_next(&self, method: MockLedgerMethod, code: RpcStatusCode) {
+        self.fail_next_with(
+            method,
+            RpcStatus::with_message(code, "injected by mock fog ledger".to_owned()),
+        );
+    }
+
+    /// Make the next call of `method` fail with `status`.
+    pub fn fail_next_with(&self, method: MockLedgerMethod, status: RpcStatus) {
+        self.state()
+            .errors
+            .entry(method)
+            .or_default()
+            .push_back(status);
+    }
+
+    /// The number of blocks in the ledger.
+    pub fn num_blocks(&self) -> u64 {
+        self.state().num_blocks
+    }
+
+    /// The number of TxOuts in the ledger.
+    pub fn global_txo_count(&self) -> u64 {
+        self.state().global_txo_count
+    }
+
+    /// The latest block version of the ledger.
+    pub fn latest_block_version(&self) -> u32 {
+        self.state().latest_block_version
+    }
+
+    /// A block of the ledger, if it is in the ledger. Blocks without contents
+    /// are empty.
+    pub fn block(&self, block_index: BlockIndex) -> Option<MockBlock> {
+        let state = self.state();
+        if block_index >= state.num_blocks {
+            return None;
+        }
+        Some(state.blocks.get(&block_index).cloned().unwrap_or_else(|| {
+            let global_txo_count = state
+                .blocks
+                .range(..block_index)
+                .next_back()
+                .map_or(0, |(_, block)| block.global_txo_count);
+            MockBlock {
+                outputs: Vec::new(),
+                global_txo_count,
+                timestamp: 0,
+            }
+        }))
+    }
+
+    /// The root element a block was validated against, if the block is in
+    /// the ledger.
+    pub fn root_element(&self, block_index: BlockIndex) -> Option<TxOutMembershipElement> {
+        let state = self.state();
+        if block_index >= state.num_blocks {
+            return None;
+        }
+        Some(
+            state
+                .root_elements
+                .get(&block_index)
+                .cloned()
+                .unwrap_or_default(),
+        )
+    }
+
+    /// Where a TxOut is in the ledger, if it is.
+    pub fn tx_out_location(
+        &self,
+        public_key: &CompressedRistrettoPublic,
+    ) -> Option<MockTxOutLocation> {
+        self.state().tx_outs.get(public_key).copied()
+    }
+
+    /// Check key images the way the ledger enclave would.
+    ///
+    /// A key image is spent if it was spent in a block which is in the ledger.
+    pub fn check_key_images(&self, request: &CheckKeyImagesRequest) -> CheckKeyImagesResponse {
+        let state = self.state();
+        let results = request
+            .queries
+            .iter()
+            .map(|query| {
+                match state
+                    .spent_key_images
+                    .get(&query.key_image)
+                    .filter(|(block_index, _)| *block_index < state.num_blocks)
+                {
+                    Some((block_index, timestamp)) => KeyImageResult {
+                        key_image: query.key_image,
+                        spent_at: *block_index,
+                        timestamp: *timestamp,
+                        timestamp_result_code: TimestampResultCode::TimestampFound as u32,
+                        key_image_result_code: KeyImageResultCode::Spent as u32,
+                    },
+                    None => KeyImageResult {
+                        key_image: query.key_image,
+                        spent_at: 0,
+                        timestamp: 0,
+                        timestamp_result_code: TimestampResultCode::TimestampFound as u32,
+                        key_image_result_code: KeyImageResultCode::NotSpent as u32,
+                    },
+                }
+            })
+            .collect();
+
+        CheckKeyImagesResponse {
+            num_blocks: state.num_blocks,
+            global_txo_count: state.global_txo_count,
+            results,
+            latest_block_version: state.latest_block_version,
+            max_block_version: *BlockVersion::MAX,
+            ledger_num_blocks: state.num_blocks,
+            ..Default::default()
+        }
+    }
+
+    /// Take the error injected into the next call of `method`, if any.
+    pub(crate) fn take_error(&self, method: MockLedgerMethod) -> Result<(), RpcStatus> {
+        match self
+            .state()
+            .errors
+            .get_mut(&method)
+            .and_then(VecDeque::pop_front)
+        {
+            Some(status) => Err(status),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mc_fog_types::ledger::KeyImageQuery;
+
+    fn query(key_image: KeyImage) -> KeyImageQuery {
+        KeyImageQuery {
+            key_image,
+            start_block: 0,
+        }
+    }
+
+    #[test]
+    fn key_images_are_spent_once_their_block_is_in_the_ledger() {
+        let ledger = MockLedger::default();
+        let spent = KeyImage::from(1);
+        let unspent = KeyImage::from(2);
+        ledger.spend_key_image(spent, 3, 1_700_000_000);
+        let request = CheckKeyImagesRequest {
+            queries: vec![query(spent), query(unspent)],
+            ..Default::default()
+        };
+
+        ledger.set_num_blocks(3);
+        let response = ledger.check_key_images(&request);
+        assert_eq!(response.num_blocks, 3);
+        assert!(response
+            .results
+            .iter()
+            .all(|result| result.key_image_result_code == KeyImageResultCode::NotSpent as u32));
+
+        ledger.set_num_blocks(4);
+        let response = ledger.check_key_images(&request);
+        assert_eq!(
+            response.results[0].key_image_result_code,
+            KeyImageResultCode::Spent as u32
+        );
+        assert_eq!(response.results[0].spent_at, 3);
+        assert_eq!(
+            response.results[1].key_image_result_code,
+            KeyImageResultCode::NotSpent as u32
+        );
+    }
+
+    #[test]
+    fn injected_errors_fail_the_next_calls() {
+        let ledger = MockLedger::default();
+        ledger.fail_next(MockLedgerMethod::GetBlocks, RpcStatusCode::UNAVAILABLE);
+        ledger.fail_next(MockLedgerMethod::GetBlocks, RpcStatusCode::INTERNAL);
+
+        assert_eq!(
+            ledger
+                .take_error(MockLedgerMethod::GetTxOuts)
+                .map_err(|status| status.code()),
+            Ok(())
+        );
+        assert_eq!(
+            ledger
+                .take_error(MockLedgerMethod::GetBlocks)
+                .map_err(|status| status.code()),
+            Err(RpcStatusCode::UNAVAILABLE)
+        );
+        assert_eq!(
+            ledger
+                .take_error(MockLedgerMethod::GetBlocks)
+                .map_err(|status| status.code()),
+            Err(RpcStatusCode::INTERNAL)
+        );
+        assert!(ledger.take_error(MockLedgerMethod::GetBlocks).is_ok());
+    }
+
+    #[test]
+    fn added_blocks_index_their_tx_outs() {
+        let ledger = MockLedger::default();
+        let tx_out = TxOut::default();
+
+        ledger.add_block(vec![], 10);
+        let block_index = ledger.add_block(vec![tx_out.clone()], 20);
+
+        assert_eq!(block_index, 1);
+        assert_eq!(ledger.num_blocks(), 2);
+        assert_eq!(ledger.global_txo_count(), 1);
+        assert_eq!(
+            ledger.tx_out_location(&tx_out.public_key),
+            Some(MockTxOutLocation {
+                global_index: 0,
+                block_index: 1,
+                timestamp: 20,
+            })
+        );
+        assert_eq!(ledger.block(1).unwrap().outputs, vec![tx_out]);
+        assert!(ledger.block(2).is_none());
+    }
+}
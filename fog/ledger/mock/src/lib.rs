@@ -0,0 +1,23 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! A mock fog ledger server, for testing clients.
+//!
+//! The server implements the client-facing fog ledger gRPC APIs, answering
+//! from a [MockLedger] which tests script directly: which key images are
+//! spent, which blocks and TxOuts are in the ledger, and which calls should
+//! fail. It needs no ledger database, key image stores or enclave, so wallet
+//! SDK tests can use the real ledger clients against it.
+
+#![deny(missing_docs)]
+
+mod attestation;
+mod ledger;
+mod server;
+mod service;
+
+pub use crate::{
+    attestation::MockAttestation,
+    ledger::{MockBlock, MockLedger, MockLedgerMethod, MockTxOutLocation},
+    server::MockFogLedgerServer,
+    service::MockFogLedgerService,
+};
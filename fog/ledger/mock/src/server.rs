@@ -0,0 +1,85 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! A gRPC server serving a [MockLedger].
+
+use crate::{attestation::MockAttestation, ledger::MockLedger, service::MockFogLedgerService};
+use futures::executor::block_on;
+use grpcio::{EnvBuilder, Server, ServerBuilder};
+use mc_attestation_verifier::TrustedIdentity;
+use mc_common::logger::{log, Logger};
+use mc_fog_api::ledger_grpc;
+use mc_fog_uri::{ConnectionUri, FogLedgerUri};
+use mc_util_grpc::ConnectionUriGrpcioServer;
+use std::sync::Arc;
+
+/// The seed of the identity the server attests with, so that every mock
+/// server has the same identity.
+const ATTESTATION_SEED: u64 = 0;
+
+/// A fog ledger server which answers from a [MockLedger] instead of a ledger
+/// database and enclave.
+///
+/// It serves the streaming and unary key image APIs, the block API and the
+/// untrusted TxOut API. Merkle proofs and key image subscriptions are not
+/// supported. The server stops when it is dropped.
+pub struct MockFogLedgerServer {
+    server: Server,
+    uri: FogLedgerUri,
+    attestation: MockAttestation,
+    logger: Logger,
+}
+
+impl MockFogLedgerServer {
+    /// Start serving `ledger` on `uri`.
+    pub fn start(ledger: MockLedger, uri: FogLedgerUri, logger: Logger) -> Self {
+        let responder_id = uri
+            .responder_id()
+            .expect("Could not get responder id from uri");
+        let attestation = MockAttestation::new(responder_id.to_string(), ATTESTATION_SEED);
+        let service = MockFogLedgerService::new(ledger, attestation.clone(), logger.clone());
+
+        let env = Arc::new(
+            EnvBuilder::new()
+                .name_prefix("MockFogLedger-RPC".to_string())
+                .build(),
+        );
+        let mut server = ServerBuilder::new(env)
+            .register_service(ledger_grpc::create_ledger_api(service.clone()))
+            .register_service(ledger_grpc::create_fog_key_image_api(service.clone()))
+            .register_service(ledger_grpc::create_fog_block_api(service.clone()))
+            .register_service(ledger_grpc::create_fog_untrusted_tx_out_api(service))
+            .build_using_uri(&uri, logger.clone())
+            .expect("Could not build mock fog ledger server");
+        server.start();
+        log::info!(logger, "Mock fog ledger listening on {}", uri.addr());
+
+        Self {
+            server,
+            uri,
+            attestation,
+            logger,
+        }
+    }
+
+    /// The uri the server listens on.
+    pub fn uri(&self) -> &FogLedgerUri {
+        &self.uri
+    }
+
+    /// The identity clients should trust to attest to the server.
+    pub fn trusted_identity(&self) -> TrustedIdentity {
+        self.attestation.trusted_identity()
+    }
+}
+
+impl Drop for MockFogLedgerServer {
+    fn drop(&mut self) {
+        if let Err(err) = block_on(self.server.shutdown()) {
+            log::error!(
+                self.logger,
+                "Could not stop mock fog ledger server: {}",
+                err
+            );
+        }
+    }
+}
@@ -0,0 +1,279 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! The fog ledger gRPC services, answered from a [MockLedger].
+
+use crate::{
+    attestation::MockAttestation,
+    ledger::{MockLedger, MockLedgerMethod},
+};
+use futures::{FutureExt, SinkExt, TryFutureExt, TryStreamExt};
+use grpcio::{
+    DuplexSink, RequestStream, RpcContext, RpcStatus, RpcStatusCode, UnarySink, WriteFlags,
+};
+use mc_attest_api::attest::{AuthMessage, Message};
+use mc_common::logger::{log, Logger};
+use mc_crypto_keys::CompressedRistrettoPublic;
+use mc_fog_api::{
+    external,
+    ledger::{
+        BlockData, BlockMerkleRoot, BlockRequest, BlockResponse, BlockTxoCount, LedgerRequest,
+        LedgerRequest_oneof_request_data, LedgerResponse, MerkleRootHistoryRequest,
+        MerkleRootHistoryResponse, TxOutRequest, TxOutResponse, TxOutResult, TxOutResultCode,
+        TxoCountHistoryRequest, TxoCountHistoryResponse,
+    },
+    ledger_grpc::{FogBlockApi, FogKeyImageApi, FogUntrustedTxOutApi, LedgerApi},
+};
+use mc_fog_types::ledger::CheckKeyImagesRequest;
+use mc_util_grpc::{rpc_invalid_arg_error, rpc_logger, send_result};
+use mc_watcher_api::TimestampResultCode;
+
+/// Serves every fog ledger API the mock supports from the same ledger.
+#[derive(Clone)]
+pub struct MockFogLedgerService {
+    ledger: MockLedger,
+    attestation: MockAttestation,
+    logger: Logger,
+}
+
+impl MockFogLedgerService {
+    /// Create a service answering from `ledger`, attesting as `attestation`.
+    pub fn new(ledger: MockLedger, attestation: MockAttestation, logger: Logger) -> Self {
+        Self {
+            ledger,
+            attestation,
+            logger,
+        }
+    }
+
+    fn auth_impl(&self, request: AuthMessage) -> Result<AuthMessage, RpcStatus> {
+        self.ledger.take_error(MockLedgerMethod::Auth)?;
+        self.attestation.accept(request)
+    }
+
+    fn check_key_images_impl(&self, query: Message) -> Result<Message, RpcStatus> {
+        self.ledger.take_error(MockLedgerMethod::CheckKeyImages)?;
+
+        let plaintext = self.attestation.decrypt(&query)?;
+        let request: CheckKeyImagesRequest = mc_util_serial::decode(&plaintext)
+            .map_err(|err| rpc_invalid_arg_error("check_key_images", err, &self.logger))?;
+
+        let response = self.ledger.check_key_images(&request);
+        self.attestation
+            .encrypt(query.get_channel_id(), &mc_util_serial::encode(&response))
+    }
+
+    fn ledger_request_impl(&self, request: LedgerRequest) -> Result<LedgerResponse, RpcStatus> {
+        let mut response = LedgerResponse::new();
+        match request.request_data {
+            Some(LedgerRequest_oneof_request_data::auth(auth)) => {
+                response.set_auth(self.auth_impl(auth)?);
+            }
+            Some(LedgerRequest_oneof_request_data::check_key_images(query)) => {
+                response.set_check_key_image_response(self.check_key_images_impl(query)?);
+            }
+            Some(LedgerRequest_oneof_request_data::subscribe_key_images(_)) => {
+                return Err(RpcStatus::with_message(
+                    RpcStatusCode::UNIMPLEMENTED,
+                    "The mock fog ledger doesn't support key image subscriptions".to_owned(),
+                ));
+            }
+            None => {
+                return Err(rpc_invalid_arg_error(
+                    "Inavlid LedgerRequest request",
+                    "Neither the query nor auth fields were set".to_string(),
+                    &self.logger,
+                ));
+            }
+        }
+        Ok(response)
+    }
+
+    fn get_blocks_impl(&self, request: BlockRequest) -> Result<BlockResponse, RpcStatus> {
+        self.ledger.take_error(MockLedgerMethod::GetBlocks)?;
+
+        let mut response = BlockResponse::new();
+        response.num_blocks = self.ledger.num_blocks();
+        response.global_txo_count = self.ledger.global_txo_count();
+        response.blocks = request
+            .ranges
+            .iter()
+            .flat_map(|range| range.start_block..range.end_block)
+            .filter_map(|index| {
+                let block = self.ledger.block(index)?;
+                let mut result = BlockData::new();
+                result.index = index;
+                result.global_txo_count = block.global_txo_count;
+                result.outputs = block.outputs.iter().map(external::TxOut::from).collect();
+                result.timestamp = block.timestamp;
+                result.timestamp_result_code = TimestampResultCode::TimestampFound as u32;
+                Some(result)
+            })
+            .collect();
+
+        Ok(response)
+    }
+
+    fn get_txo_count_history_impl(
+        &self,
+        request: TxoCountHistoryRequest,
+    ) -> Result<TxoCountHistoryResponse, RpcStatus> {
+        self.ledger
+            .take_error(MockLedgerMethod::GetTxoCountHistory)?;
+
+        let range = request.get_range();
+        let mut response = TxoCountHistoryResponse::new();
+        response.num_blocks = self.ledger.num_blocks();
+        response.global_txo_count = self.ledger.global_txo_count();
+        response.blocks = (range.start_block..range.end_block)
+            .filter_map(|index| {
+                let block = self.ledger.block(index)?;
+                let mut result = BlockTxoCount::new();
+                result.index = index;
+                result.global_txo_count = block.global_txo_count;
+                Some(result)
+            })
+            .collect();
+
+        Ok(response)
+    }
+
+    fn get_tx_outs_impl(&self, request: TxOutRequest) -> Result<TxOutResponse, RpcStatus> {
+        self.ledger.take_error(MockLedgerMethod::GetTxOuts)?;
+
+        let mut response = TxOutResponse::new();
+        response.num_blocks = self.ledger.num_blocks();
+        response.global_txo_count = self.ledger.global_txo_count();
+        for tx_out_pubkey in request.tx_out_pubkeys.iter() {
+            let public_key = CompressedRistrettoPublic::try_from(tx_out_pubkey)
+                .map_err(|err| rpc_invalid_arg_error("tx_out_pubkey", err, &self.logger))?;
+
+            let mut result = TxOutResult::new();
+            result.set_tx_out_pubkey(tx_out_pubkey.clone());
+            match self.ledger.tx_out_location(&public_key) {
+                Some(location) => {
+                    result.set_result_code(TxOutResultCode::Found);
+                    result.tx_out_global_index = location.global_index;
+                    result.block_index = location.block_index;
+                    result.timestamp = location.timestamp;
+                    result.timestamp_result_code = TimestampResultCode::TimestampFound as u32;
+                }
+                None => result.set_result_code(TxOutResultCode::NotFound),
+            }
+            response.results.push(result);
+        }
+
+        Ok(response)
+    }
+
+    fn get_merkle_root_history_impl(
+        &self,
+        request: MerkleRootHistoryRequest,
+    ) -> Result<MerkleRootHistoryResponse, RpcStatus> {
+        self.ledger
+            .take_error(MockLedgerMethod::GetMerkleRootHistory)?;
+
+        let range = request.get_range();
+        let mut response = MerkleRootHistoryResponse::new();
+        response.num_blocks = self.ledger.num_blocks();
+        response.global_txo_count = self.ledger.global_txo_count();
+        response.roots = (range.start_block..range.end_block)
+            .filter_map(|index| {
+                let block = self.ledger.block(index)?;
+                let root_element = self.ledger.root_element(index)?;
+                let mut result = BlockMerkleRoot::new();
+                result.index = index;
+                result.set_root_element((&root_element).into());
+                result.txo_count = block.global_txo_count - block.outputs.len() as u64;
+                Some(result)
+            })
+            .collect();
+
+        Ok(response)
+    }
+}
+
+impl LedgerApi for MockFogLedgerService {
+    fn request(
+        &mut self,
+        ctx: RpcContext,
+        mut requests: RequestStream<LedgerRequest>,
+        mut responses: DuplexSink<LedgerResponse>,
+    ) {
+        let service = self.clone();
+        let logger = rpc_logger(&ctx, &self.logger);
+        let future = async move {
+            while let Some(request) = requests.try_next().await? {
+                match service.ledger_request_impl(request) {
+                    Ok(response) => responses.send((response, WriteFlags::default())).await?,
+                    Err(rpc_status) => return responses.fail(rpc_status).await,
+                }
+            }
+            responses.close().await
+        }
+        .map_err(move |err| log::error!(&logger, "failed to reply: {}", err))
+        .map(|_| ());
+
+        ctx.spawn(future)
+    }
+}
+
+impl FogKeyImageApi for MockFogLedgerService {
+    fn check_key_images(&mut self, ctx: RpcContext, request: Message, sink: UnarySink<Message>) {
+        let logger = rpc_logger(&ctx, &self.logger);
+        send_result(ctx, sink, self.check_key_images_impl(request), &logger)
+    }
+
+    fn auth(&mut self, ctx: RpcContext, request: AuthMessage, sink: UnarySink<AuthMessage>) {
+        let logger = rpc_logger(&ctx, &self.logger);
+        send_result(ctx, sink, self.auth_impl(request), &logger)
+    }
+}
+
+impl FogBlockApi for MockFogLedgerService {
+    fn get_blocks(
+        &mut self,
+        ctx: RpcContext,
+        request: BlockRequest,
+        sink: UnarySink<BlockResponse>,
+    ) {
+        let logger = rpc_logger(&ctx, &self.logger);
+        send_result(ctx, sink, self.get_blocks_impl(request), &logger)
+    }
+
+    fn get_txo_count_history(
+        &mut self,
+        ctx: RpcContext,
+        request: TxoCountHistoryRequest,
+        sink: UnarySink<TxoCountHistoryResponse>,
+    ) {
+        let logger = rpc_logger(&ctx, &self.logger);
+        send_result(ctx, sink, self.get_txo_count_history_impl(request), &logger)
+    }
+}
+
+impl FogUntrustedTxOutApi for MockFogLedgerService {
+    fn get_tx_outs(
+        &mut self,
+        ctx: RpcContext,
+        request: TxOutRequest,
+        sink: UnarySink<TxOutResponse>,
+    ) {
+        let logger = rpc_logger(&ctx, &self.logger);
+        send_result(ctx, sink, self.get_tx_outs_impl(request), &logger)
+    }
+
+    fn get_merkle_root_history(
+        &mut self,
+        ctx: RpcContext,
+        request: MerkleRootHistoryRequest,
+        sink: UnarySink<MerkleRootHistoryResponse>,
+    ) {
+        let logger = rpc_logger(&ctx, &self.logger);
+        send_result(
+            ctx,
+            sink,
+            self.get_merkle_root_history_impl(request),
+            &logger,
+        )
+    }
+}
@@ -0,0 +1,106 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Tests of the ledger clients against the mock server.
+
+use futures::executor::block_on;
+use grpcio::{EnvBuilder, RpcStatusCode};
+use mc_common::logger::{test_with_logger, Logger};
+use mc_crypto_keys::{CompressedRistrettoPublic, RistrettoPublic};
+use mc_fog_api::ledger::TxOutResultCode;
+use mc_fog_ledger_connection::{FogUntrustedLedgerGrpcClient, LedgerGrpcClient};
+use mc_fog_ledger_mock::{MockFogLedgerServer, MockLedger, MockLedgerMethod};
+use mc_fog_types::ledger::KeyImageResultCode;
+use mc_fog_uri::FogLedgerUri;
+use mc_rand::McRng;
+use mc_transaction_core::{ring_signature::KeyImage, tx::TxOut};
+use mc_util_from_random::FromRandom;
+use mc_util_grpc::GrpcRetryConfig;
+use std::{str::FromStr, sync::Arc};
+
+const GRPC_RETRY_CONFIG: GrpcRetryConfig = GrpcRetryConfig {
+    grpc_retry_count: 0,
+    grpc_retry_millis: 20,
+};
+
+fn start_server(ledger: MockLedger, logger: Logger) -> MockFogLedgerServer {
+    let uri = FogLedgerUri::from_str(&format!(
+        "insecure-fog-ledger://127.0.0.1:{}",
+        portpicker::pick_unused_port().expect("No free ports")
+    ))
+    .unwrap();
+    MockFogLedgerServer::start(ledger, uri, logger)
+}
+
+fn tx_out() -> TxOut {
+    let mut tx_out = TxOut::default();
+    tx_out.public_key = CompressedRistrettoPublic::from(&RistrettoPublic::from_random(&mut McRng));
+    tx_out
+}
+
+#[test_with_logger]
+fn untrusted_client_sees_scripted_ledger(logger: Logger) {
+    let ledger = MockLedger::default();
+    let server = start_server(ledger.clone(), logger.clone());
+    let client = FogUntrustedLedgerGrpcClient::new(
+        server.uri().clone(),
+        GRPC_RETRY_CONFIG,
+        Arc::new(EnvBuilder::new().build()),
+        logger,
+    );
+
+    let found = tx_out();
+    let missing = tx_out();
+    ledger.add_block(vec![tx_out()], 100);
+    ledger.add_block(vec![found.clone()], 200);
+
+    let response = client
+        .get_tx_outs([found.public_key, missing.public_key])
+        .unwrap();
+    assert_eq!(response.num_blocks, 2);
+    assert_eq!(response.global_txo_count, 2);
+    assert_eq!(response.results[0].result_code, TxOutResultCode::Found);
+    assert_eq!(response.results[0].block_index, 1);
+    assert_eq!(response.results[0].tx_out_global_index, 1);
+    assert_eq!(response.results[0].timestamp, 200);
+    assert_eq!(response.results[1].result_code, TxOutResultCode::NotFound);
+
+    let response = client.get_blocks(&[0..5]).unwrap();
+    assert_eq!(response.blocks.len(), 2);
+    assert_eq!(response.blocks[1].outputs.len(), 1);
+    assert_eq!(response.blocks[1].global_txo_count, 2);
+
+    ledger.fail_next(MockLedgerMethod::GetBlocks, RpcStatusCode::UNAVAILABLE);
+    assert!(client.get_blocks(&[0..5]).is_err());
+    assert!(client.get_blocks(&[0..5]).is_ok());
+}
+
+#[test_with_logger]
+fn attested_client_sees_spent_key_images(logger: Logger) {
+    let ledger = MockLedger::default();
+    let server = start_server(ledger.clone(), logger.clone());
+    let mut client = LedgerGrpcClient::new(
+        server.uri().clone(),
+        [server.trusted_identity()],
+        Arc::new(EnvBuilder::new().build()),
+        logger,
+    );
+
+    let spent = KeyImage::from(1);
+    let unspent = KeyImage::from(2);
+    ledger.set_num_blocks(10);
+    ledger.spend_key_image(spent, 7, 1_700_000_000);
+
+    let response = block_on(client.check_key_images(&[spent, unspent])).unwrap();
+    assert_eq!(response.num_blocks, 10);
+    assert_eq!(response.results.len(), 2);
+    assert_eq!(response.results[0].key_image, spent);
+    assert_eq!(
+        response.results[0].key_image_result_code,
+        KeyImageResultCode::Spent as u32
+    );
+    assert_eq!(response.results[0].spent_at, 7);
+    assert_eq!(
+        response.results[1].key_image_result_code,
+        KeyImageResultCode::NotSpent as u32
+    );
+}
@@ -0,0 +1,185 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Compares checking router queries against a key image store one ECALL at a
+//! time with checking them in a single batched ECALL.
+
+use aes_gcm::Aes256Gcm;
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use mc_attest_ake::{AuthResponseInput, ClientInitiate, Ready, Start, Transition};
+use mc_attest_api::attest;
+use mc_attest_enclave_api::{ClientSession, EnclaveMessage, NonceSession};
+use mc_blockchain_types::MAX_BLOCK_VERSION;
+use mc_common::{logger::create_null_logger, ResponderId};
+use mc_crypto_keys::X25519;
+use mc_fog_ledger_enclave::{KeyImageData, LedgerEnclave, LedgerSgxEnclave, ENCLAVE_FILE};
+use mc_fog_ledger_enclave_api::UntrustedKeyImageQueryResponse;
+use mc_fog_types::ledger::{CheckKeyImagesRequest, KeyImageQuery};
+use mc_util_test_helper::{Rng, RngType, SeedableRng};
+use sha2::Sha512;
+use std::str::FromStr;
+
+const OMAP_CAPACITY: u64 = 1024;
+const NUM_KEY_IMAGES: usize = 256;
+
+struct Context {
+    enclave: LedgerSgxEnclave,
+    client_session: ClientSession,
+    noise_connection: Ready<Aes256Gcm>,
+    key_images: Vec<KeyImageData>,
+    untrusted: UntrustedKeyImageQueryResponse,
+}
+
+impl Context {
+    fn new(rng: &mut RngType) -> Self {
+        let responder_id = ResponderId::from_str("localhost:3228").unwrap();
+        let enclave_path = std::env::current_exe()
+            .expect("Could not get the path of our executable")
+            .parent()
+            .expect("Failed to get parent of enclave path.")
+            .with_file_name(ENCLAVE_FILE);
+        let enclave = LedgerSgxEnclave::new(
+            enclave_path,
+            &responder_id,
+            OMAP_CAPACITY,
+            create_null_logger(),
+        );
+
+        // Connect the enclave to itself, acting as both router and store.
+        let auth_request = enclave.ledger_store_init(responder_id.clone()).unwrap();
+        let (auth_response, _) = enclave.frontend_accept(auth_request).unwrap();
+        enclave
+            .ledger_store_connect(responder_id.clone(), auth_response)
+            .unwrap();
+
+        let key_images = (0..NUM_KEY_IMAGES)
+            .map(|i| {
+                let mut key_image_bytes = [0u8; 32];
+                rng.fill(&mut key_image_bytes);
+                KeyImageData {
+                    key_image: key_image_bytes.try_into().unwrap(),
+                    block_index: i as u64,
+                    timestamp: 0,
+                }
+            })
+            .collect::<Vec<_>>();
+        enclave.add_key_image_data(key_images.clone()).unwrap();
+
+        // Connect a client to the router side.
+        let initiator = Start::new(responder_id.to_string());
+        let (initiator, auth_request_output) = initiator
+            .try_next(rng, ClientInitiate::<X25519, Aes256Gcm, Sha512>::default())
+            .unwrap();
+        let (client_auth_response, client_session) = enclave
+            .client_accept(attest::AuthMessage::from(auth_request_output).into())
+            .unwrap();
+        let identity = mc_fog_ledger_enclave_measurement::mr_signer_identity(None);
+        let auth_response_event = AuthResponseInput::new(
+            attest::AuthMessage::from(client_auth_response).into(),
+            [identity],
+            None,
+        );
+        let (noise_connection, _) = initiator.try_next(rng, auth_response_event).unwrap();
+
+        let untrusted = UntrustedKeyImageQueryResponse {
+            processed_block_range: Default::default(),
+            last_known_block_cumulative_txo_count: 0,
+            latest_block_version: *MAX_BLOCK_VERSION,
+            max_block_version: *MAX_BLOCK_VERSION,
+            ledger_num_blocks: 0,
+        };
+
+        Self {
+            enclave,
+            client_session,
+            noise_connection,
+            key_images,
+            untrusted,
+        }
+    }
+
+    /// Produce store queries the way a router does for client requests.
+    fn make_queries(&mut self, count: usize) -> Vec<EnclaveMessage<NonceSession>> {
+        (0..count)
+            .map(|i| {
+                let request = CheckKeyImagesRequest {
+                    queries: vec![KeyImageQuery {
+                        key_image: self.key_images[i % NUM_KEY_IMAGES].key_image,
+                        start_block: 0,
+                    }],
+                };
+                let data = self
+                    .noise_connection
+                    .encrypt(&[], &mc_util_serial::encode(&request))
+                    .unwrap();
+                let sealed_query = self
+                    .enclave
+                    .decrypt_and_seal_query(
+                        EnclaveMessage {
+                            aad: vec![],
+                            channel_id: self.client_session.clone(),
+                            data,
+                        },
+                        0,
+                    )
+                    .unwrap();
+                self.enclave
+                    .create_multi_key_image_store_query_data(sealed_query)
+                    .unwrap()
+                    .pop()
+                    .unwrap()
+            })
+            .collect()
+    }
+}
+
+fn key_image_store_batch_benchmarks(c: &mut Criterion) {
+    let mut rng = RngType::from_seed([7u8; 32]);
+    let mut context = Context::new(&mut rng);
+    let enclave = context.enclave.clone();
+    let untrusted = context.untrusted.clone();
+    let mut group = c.benchmark_group("check_key_image_store");
+
+    for num_queries in [1, 8, 32, 64] {
+        group.bench_with_input(
+            BenchmarkId::new("sequential", num_queries),
+            &num_queries,
+            |b, &num_queries| {
+                b.iter_batched(
+                    || context.make_queries(num_queries),
+                    |queries| {
+                        for query in queries {
+                            enclave
+                                .check_key_image_store(query, untrusted.clone())
+                                .unwrap();
+                        }
+                    },
+                    BatchSize::SmallInput,
+                )
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("batched", num_queries),
+            &num_queries,
+            |b, &num_queries| {
+                b.iter_batched(
+                    || context.make_queries(num_queries),
+                    |queries| {
+                        enclave
+                            .check_key_image_store_batch(queries, untrusted.clone())
+                            .unwrap()
+                    },
+                    BatchSize::SmallInput,
+                )
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(20);
+    targets = key_image_store_batch_benchmarks
+}
+criterion_main!(benches);
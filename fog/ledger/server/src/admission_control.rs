@@ -0,0 +1,217 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Admission control for client calls, decided before any other work is done
+//! for them.
+//!
+//! [CidrAdmissionControl] is the first layer of the router's client
+//! [InterceptorChain](mc_util_grpc::InterceptorChain), and admits or rejects
+//! callers by IP address, as configured on the command line. Operators
+//! embedding the router can shed load by region, or only serve known clients,
+//! by passing their own [Interceptor] to
+//! [crate::LedgerRouterServer::new_with_admission_control] instead.
+
+use crate::config::AdmissionControlConfig;
+use grpcio::{RpcStatus, RpcStatusCode};
+use mc_util_grpc::{InterceptedCall, Interceptor};
+use serde::{Serialize, Serializer};
+use std::{fmt, net::IpAddr, str::FromStr};
+
+/// Admits or rejects callers by IP address.
+///
+/// Callers in a denied range are rejected. If any ranges are allowed, only
+/// callers in them are admitted, which also rejects callers without an IP
+/// address. Otherwise every caller which isn't denied is admitted.
+#[derive(Clone, Debug, Default)]
+pub struct CidrAdmissionControl {
+    allow: Vec<IpCidr>,
+    deny: Vec<IpCidr>,
+}
+
+impl CidrAdmissionControl {
+    /// Admit only callers in `allow`, if it isn't empty, and reject callers in
+    /// `deny`.
+    pub fn new(allow: Vec<IpCidr>, deny: Vec<IpCidr>) -> Self {
+        Self { allow, deny }
+    }
+
+    /// Admit and reject callers as configured.
+    pub fn from_config(config: &AdmissionControlConfig) -> Self {
+        Self::new(
+            config.admission_allow.clone(),
+            config.admission_deny.clone(),
+        )
+    }
+
+    fn is_admitted(&self, ip: Option<IpAddr>) -> bool {
+        let contains =
+            |ranges: &[IpCidr]| ip.is_some_and(|ip| ranges.iter().any(|range| range.contains(ip)));
+        !contains(&self.deny) && (self.allow.is_empty() || contains(&self.allow))
+    }
+}
+
+impl Interceptor for CidrAdmissionControl {
+    fn name(&self) -> &'static str {
+        "admission_control"
+    }
+
+    fn intercept(&self, call: &InterceptedCall) -> Result<(), RpcStatus> {
+        if self.is_admitted(call.peer_ip()) {
+            Ok(())
+        } else {
+            Err(RpcStatus::with_message(
+                RpcStatusCode::PERMISSION_DENIED,
+                "Calls from this address are not served".to_owned(),
+            ))
+        }
+    }
+}
+
+/// A range of IP addresses, written like `10.0.0.0/8` or `2001:db8::/32`. A
+/// bare address is a range containing only that address.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct IpCidr {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpCidr {
+    /// The addresses whose first `prefix_len` bits match those of `addr`.
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Result<Self, String> {
+        let max_prefix_len = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        if prefix_len > max_prefix_len {
+            return Err(format!(
+                "Prefix length {prefix_len} is longer than the address {addr}"
+            ));
+        }
+        Ok(Self { addr, prefix_len })
+    }
+
+    /// Whether `ip` is in this range. IPv4 addresses are never in IPv6 ranges,
+    /// nor the other way round.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(addr), IpAddr::V4(ip)) => prefix_matches(
+                u32::from(addr).into(),
+                u32::from(ip).into(),
+                32,
+                self.prefix_len,
+            ),
+            (IpAddr::V6(addr), IpAddr::V6(ip)) => {
+                prefix_matches(addr.into(), ip.into(), 128, self.prefix_len)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Whether the first `prefix_len` of the low `bits` bits of `a` and `b` match.
+fn prefix_matches(a: u128, b: u128, bits: u8, prefix_len: u8) -> bool {
+    let ignored_bits = u32::from(bits - prefix_len);
+    (a ^ b).checked_shr(ignored_bits).unwrap_or(0) == 0
+}
+
+impl FromStr for IpCidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix_len) = match s.split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (s, None),
+        };
+        let addr =
+            IpAddr::from_str(addr).map_err(|err| format!("Invalid address {addr:?}: {err}"))?;
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len
+                .parse()
+                .map_err(|err| format!("Invalid prefix length {prefix_len:?}: {err}"))?,
+            None if addr.is_ipv4() => 32,
+            None => 128,
+        };
+        Self::new(addr, prefix_len)
+    }
+}
+
+impl fmt::Display for IpCidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+impl Serialize for IpCidr {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grpcio::MetadataBuilder;
+
+    fn cidrs(ranges: &[&str]) -> Vec<IpCidr> {
+        ranges
+            .iter()
+            .map(|range| IpCidr::from_str(range).unwrap())
+            .collect()
+    }
+
+    fn admit(admission_control: &CidrAdmissionControl, peer: &str) -> bool {
+        let headers = MetadataBuilder::new().build();
+        admission_control
+            .intercept(&InterceptedCall {
+                method: "/fog_ledger.FogKeyImageAPI/CheckKeyImages",
+                peer,
+                headers: &headers,
+            })
+            .is_ok()
+    }
+
+    #[test]
+    fn parse_cidrs() {
+        let range = IpCidr::from_str("10.1.0.0/16").unwrap();
+        assert!(range.contains("10.1.200.3".parse().unwrap()));
+        assert!(!range.contains("10.2.0.1".parse().unwrap()));
+        assert!(!range.contains("::ffff:10.1.0.1".parse().unwrap()));
+        assert_eq!(range.to_string(), "10.1.0.0/16");
+
+        let range = IpCidr::from_str("2001:db8::/32").unwrap();
+        assert!(range.contains("2001:db8:1::1".parse().unwrap()));
+        assert!(!range.contains("2001:db9::1".parse().unwrap()));
+
+        let range = IpCidr::from_str("192.168.1.1").unwrap();
+        assert!(range.contains("192.168.1.1".parse().unwrap()));
+        assert!(!range.contains("192.168.1.2".parse().unwrap()));
+
+        let range = IpCidr::from_str("0.0.0.0/0").unwrap();
+        assert!(range.contains("203.0.113.9".parse().unwrap()));
+
+        assert!(IpCidr::from_str("10.0.0.0/33").is_err());
+        assert!(IpCidr::from_str("10.0.0/8").is_err());
+        assert!(IpCidr::from_str("10.0.0.0/x").is_err());
+    }
+
+    #[test]
+    fn denied_ranges_are_rejected() {
+        let admission_control = CidrAdmissionControl::new(vec![], cidrs(&["203.0.113.0/24"]));
+        assert!(!admit(&admission_control, "ipv4:203.0.113.7:5123"));
+        assert!(admit(&admission_control, "ipv4:198.51.100.7:5123"));
+        assert!(admit(&admission_control, "unix:/tmp/router.sock"));
+    }
+
+    #[test]
+    fn only_allowed_ranges_are_admitted() {
+        let admission_control = CidrAdmissionControl::new(
+            cidrs(&["10.0.0.0/8", "2001:db8::/32"]),
+            cidrs(&["10.66.0.0/16"]),
+        );
+        assert!(admit(&admission_control, "ipv4:10.1.2.3:5123"));
+        assert!(admit(&admission_control, "ipv6:[2001:db8::5]:5123"));
+        // Denied ranges win over allowed ones.
+        assert!(!admit(&admission_control, "ipv4:10.66.2.3:5123"));
+        assert!(!admit(&admission_control, "ipv4:192.0.2.1:5123"));
+        assert!(!admit(&admission_control, "unix:/tmp/router.sock"));
+    }
+}
@@ -1,12 +1,15 @@
 // Copyright (c) 2018-2022 The MobileCoin Foundation
 
-use clap::Parser;
 use grpcio::{RpcStatus, RpcStatusCode};
 use mc_common::{logger::log, time::SystemTimeProvider};
+use mc_fog_api::ledger_grpc;
 use mc_fog_block_provider::{BlockProvider, LocalBlockProvider, MobilecoindBlockProvider};
-use mc_fog_ledger_enclave::{LedgerSgxEnclave, ENCLAVE_FILE};
-use mc_fog_ledger_server::{KeyImageStoreServer, LedgerStoreConfig, ShardingStrategy};
+use mc_fog_ledger_enclave::{set_oram_spill_dir, LedgerSgxEnclave, ENCLAVE_FILE};
+use mc_fog_ledger_server::{
+    KeyImageStoreAdminService, KeyImageStoreServer, LedgerStoreConfig, ShardingStrategy,
+};
 use mc_ledger_db::LedgerDB;
+use mc_util_cli::LayeredConfig;
 use mc_util_grpc::AdminServer;
 use mc_watcher::watcher_db::WatcherDB;
 
@@ -16,25 +19,11 @@ fn main() {
     let (logger, _global_logger_guard) =
         mc_common::logger::create_app_logger(mc_common::logger::o!());
     mc_common::setup_panic_handler();
-    let config = LedgerStoreConfig::parse();
+    let config = LedgerStoreConfig::load();
 
     let enclave_path = env::current_exe()
         .expect("Could not get the path of our executable")
         .with_file_name(ENCLAVE_FILE);
-    log::info!(
-        logger,
-        "enclave path {}, responder ID {}",
-        enclave_path
-            .to_str()
-            .expect("enclave path is not valid UTF-8"),
-        &config.client_responder_id
-    );
-    let enclave = LedgerSgxEnclave::new(
-        enclave_path,
-        &config.client_responder_id,
-        config.omap_capacity,
-        logger.clone(),
-    );
 
     let (block_provider, ledger_db) = match (
         config.ledger_db.as_ref(),
@@ -60,17 +49,52 @@ fn main() {
         _ => panic!("invalid configuration, need either ledger_db+watcher_db or mobilecoind_uri"),
     };
 
-    let mut store_server = match config.sharding_strategy.clone() {
-        ShardingStrategy::Epoch(sharding_strategy) => KeyImageStoreServer::new_from_config(
-            config.clone(),
-            enclave,
-            block_provider,
-            sharding_strategy,
-            SystemTimeProvider,
-            logger.clone(),
-        ),
-    };
-    store_server.start();
+    if let Some(oram_spill_dir) = config.oram_spill_dir.as_ref() {
+        log::info!(logger, "Spilling ORAM storage to {:?}", oram_spill_dir);
+        set_oram_spill_dir(Some(oram_spill_dir.clone()));
+    }
+
+    // Each epoch gets its own enclave, and so its own OMAP.
+    let store_servers = config
+        .store_configs()
+        .into_iter()
+        .map(|store_config| {
+            log::info!(
+                logger,
+                "enclave path {}, responder ID {}",
+                enclave_path
+                    .to_str()
+                    .expect("enclave path is not valid UTF-8"),
+                &store_config.client_responder_id
+            );
+            let enclave = LedgerSgxEnclave::new_with_oram_memory_budget(
+                enclave_path.clone(),
+                &store_config.client_responder_id,
+                store_config.omap_capacity,
+                store_config.oram_memory_budget,
+                logger.clone(),
+            );
+
+            let mut store_server = match store_config.sharding_strategy.clone() {
+                ShardingStrategy::Epoch(sharding_strategy) => KeyImageStoreServer::new_from_config(
+                    store_config,
+                    enclave,
+                    block_provider.clone(),
+                    sharding_strategy,
+                    SystemTimeProvider,
+                    logger.clone(),
+                ),
+            };
+            store_server.start();
+            store_server
+        })
+        .collect::<Vec<_>>();
+    let store_state_sources = config
+        .store_configs()
+        .iter()
+        .zip(&store_servers)
+        .map(|(store_config, store_server)| store_server.state_source(store_config.omap_capacity))
+        .collect();
 
     //Initialize the admin api
     let config2 = config.clone();
@@ -78,6 +102,9 @@ fn main() {
         serde_json::to_string(&config2)
             .map_err(|err| RpcStatus::with_message(RpcStatusCode::INTERNAL, format!("{err:?}")))
     });
+    let store_admin_service = ledger_grpc::create_key_image_store_admin_api(
+        KeyImageStoreAdminService::new(store_state_sources, logger.clone()),
+    );
     let _admin_server = config.admin_listen_uri.as_ref().map(|admin_listen_uri| {
         AdminServer::start(
             None,
@@ -85,7 +112,7 @@ fn main() {
             "Fog Ledger".to_owned(),
             config.client_responder_id.to_string(),
             Some(get_config_json),
-            vec![],
+            vec![store_admin_service],
             logger.clone(),
         )
         .expect("Failed starting admin server")
@@ -2,19 +2,19 @@
 
 use std::env;
 
-use clap::Parser;
 use mc_common::logger::log;
 use mc_fog_block_provider::{BlockProvider, LocalBlockProvider, MobilecoindBlockProvider};
 use mc_fog_ledger_enclave::{LedgerSgxEnclave, ENCLAVE_FILE};
 use mc_fog_ledger_server::{LedgerRouterConfig, LedgerRouterServer};
 use mc_ledger_db::LedgerDB;
+use mc_util_cli::LayeredConfig;
 use mc_watcher::watcher_db::WatcherDB;
 
 fn main() {
     let (logger, _global_logger_guard) =
         mc_common::logger::create_app_logger(mc_common::logger::o!());
     mc_common::setup_panic_handler();
-    let config = LedgerRouterConfig::parse();
+    let config = LedgerRouterConfig::load();
 
     let enclave_path = env::current_exe()
         .expect("Could not get the path of our executable")
@@ -78,6 +78,8 @@ fn main() {
     router_server.start();
 
     loop {
+        router_server.update_shard_coverage();
+
         if let Some(ledger_db) = ledger_db.as_ref() {
             // The ledger database is read by this service, but updated by another service.
             // In order to keep this service's metrics up to date, we need to update them
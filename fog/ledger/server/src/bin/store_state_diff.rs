@@ -0,0 +1,70 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Fetches the state of every Key Image Store from their admin APIs, and lists
+//! where the stores disagree, e.g. replicas of an epoch claiming different
+//! coverage, blocks no store has processed, or stores close to their ORAM
+//! capacity. Exits with a non-zero status if there are any discrepancies.
+
+use clap::Parser;
+use grpcio::{ChannelBuilder, EnvBuilder};
+use mc_common::logger::{create_app_logger, o};
+use mc_fog_api::ledger_grpc::KeyImageStoreAdminApiClient;
+use mc_fog_ledger_server::diff_store_states;
+use mc_util_grpc::{ConnectionUriGrpcioChannel, Empty};
+use mc_util_uri::AdminUri;
+use std::{process::exit, sync::Arc};
+
+/// Command line configuration for the store state diff tool
+#[derive(Parser)]
+#[clap(version)]
+struct Config {
+    /// Admin URIs of the Key Image Store processes to compare.
+    #[clap(
+        long,
+        use_value_delimiter = true,
+        env = "MC_ADMIN_URIS",
+        required = true
+    )]
+    admin_uris: Vec<AdminUri>,
+}
+
+fn main() {
+    let (logger, _global_logger_guard) = create_app_logger(o!());
+    let config = Config::parse();
+    let env = Arc::new(EnvBuilder::new().name_prefix("store-state-diff").build());
+
+    let mut states = Vec::new();
+    for admin_uri in &config.admin_uris {
+        let client = KeyImageStoreAdminApiClient::new(
+            ChannelBuilder::default_channel_builder(env.clone()).connect_to_uri(admin_uri, &logger),
+        );
+        let response = client
+            .get_store_states(&Empty::new())
+            .unwrap_or_else(|err| panic!("Could not get store states from {admin_uri}: {err}"));
+        for state in response.get_stores() {
+            println!(
+                "{admin_uri}: {} epoch [{},{}) processed [{},{}) key images {}/{} block version {} server {}",
+                state.get_responder_id(),
+                state.get_epoch_block_range().get_start_block(),
+                state.get_epoch_block_range().get_end_block(),
+                state.get_processed_block_range().get_start_block(),
+                state.get_processed_block_range().get_end_block(),
+                state.get_num_key_images(),
+                state.get_omap_capacity(),
+                state.get_latest_block_version(),
+                state.get_server_version(),
+            );
+        }
+        states.extend(response.stores.into_iter());
+    }
+
+    let discrepancies = diff_store_states(&states);
+    if discrepancies.is_empty() {
+        println!("The stores agree");
+        return;
+    }
+    for discrepancy in &discrepancies {
+        println!("{discrepancy}");
+    }
+    exit(1);
+}
@@ -1,39 +1,47 @@
 // Copyright (c) 2018-2022 The MobileCoin Foundation
 
-use crate::SVC_COUNTERS;
+use crate::{concurrency_limit::spawn_limited, ConcurrencyLimiter, SVC_COUNTERS};
 use grpcio::{RpcContext, RpcStatus, UnarySink};
 use mc_common::logger::Logger;
 use mc_fog_api::{
     external,
-    ledger::{BlockData, BlockRequest, BlockResponse},
+    ledger::{
+        BlockData, BlockRequest, BlockResponse, BlockTxoCount, TxoCountHistoryRequest,
+        TxoCountHistoryResponse,
+    },
     ledger_grpc::FogBlockApi,
 };
-use mc_fog_block_provider::{BlockProvider, BlocksDataResponse};
+use mc_fog_block_provider::{BlockProvider, BlocksDataResponse, CumulativeTxoCountsResponse};
 use mc_util_grpc::{
-    check_request_chain_id, rpc_database_err, rpc_logger, send_result, Authenticator,
+    rpc_database_err, rpc_invalid_arg_error, rpc_logger, send_result, InterceptorChain,
 };
+use mc_util_metrics::service_metrics;
 use std::sync::Arc;
 
+/// The most blocks a get_txo_count_history call may ask about.
+pub const MAX_TXO_COUNT_HISTORY_BLOCKS: u64 = 10_000;
+
 #[derive(Clone)]
 pub struct BlockService {
-    chain_id: String,
     block_provider: Box<dyn BlockProvider>,
-    authenticator: Arc<dyn Authenticator + Send + Sync>,
+    interceptors: InterceptorChain,
+    /// Limits how many get_blocks and get_txo_count_history calls are served
+    /// at once.
+    concurrency_limiter: Arc<ConcurrencyLimiter>,
     logger: Logger,
 }
 
 impl BlockService {
     pub fn new(
-        chain_id: String,
-
         block_provider: Box<dyn BlockProvider>,
-        authenticator: Arc<dyn Authenticator + Send + Sync>,
+        interceptors: InterceptorChain,
+        concurrency_limiter: Arc<ConcurrencyLimiter>,
         logger: Logger,
     ) -> Self {
         Self {
-            chain_id,
             block_provider,
-            authenticator,
+            interceptors,
+            concurrency_limiter,
             logger,
         }
     }
@@ -71,14 +79,63 @@ impl BlockService {
                 result.global_txo_count = b.block_data.block().cumulative_txo_count;
                 result.timestamp = b.block_timestamp;
                 result.timestamp_result_code = b.block_timestamp_result_code as u32;
+                if let Some(metadata) = b.block_data.metadata() {
+                    result.metadata_labels = metadata.contents().labels().into();
+                }
                 result
             })
             .collect();
 
         Ok(response)
     }
+
+    fn get_txo_count_history_impl(
+        &mut self,
+        request: TxoCountHistoryRequest,
+    ) -> Result<TxoCountHistoryResponse, RpcStatus> {
+        mc_common::trace_time!(self.logger, "Get Txo Count History");
+
+        let range = request.get_range();
+        let num_requested = range.end_block.saturating_sub(range.start_block);
+        if num_requested > MAX_TXO_COUNT_HISTORY_BLOCKS {
+            return Err(rpc_invalid_arg_error(
+                "get_txo_count_history",
+                format!(
+                    "{num_requested} blocks requested, at most {MAX_TXO_COUNT_HISTORY_BLOCKS} allowed"
+                ),
+                &self.logger,
+            ));
+        }
+        let block_indices = (range.start_block..range.end_block).collect::<Vec<_>>();
+
+        let CumulativeTxoCountsResponse {
+            results,
+            latest_block,
+        } = self
+            .block_provider
+            .get_cumulative_txo_counts(block_indices.as_slice())
+            .map_err(|err| rpc_database_err(err, &self.logger))?;
+
+        let mut response = TxoCountHistoryResponse::new();
+        response.num_blocks = latest_block.index + 1;
+        response.global_txo_count = latest_block.cumulative_txo_count;
+
+        response.blocks = block_indices
+            .into_iter()
+            .zip(results)
+            .filter_map(|(index, global_txo_count)| {
+                let mut result = BlockTxoCount::new();
+                result.index = index;
+                result.global_txo_count = global_txo_count?;
+                Some(result)
+            })
+            .collect();
+
+        Ok(response)
+    }
 }
 
+#[service_metrics(SVC_COUNTERS)]
 impl FogBlockApi for BlockService {
     fn get_blocks(
         &mut self,
@@ -86,17 +143,41 @@ impl FogBlockApi for BlockService {
         request: BlockRequest,
         sink: UnarySink<BlockResponse>,
     ) {
-        let _timer = SVC_COUNTERS.req(&ctx);
         mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
-            if let Err(err) = check_request_chain_id(&self.chain_id, &ctx) {
+            if let Err(err) = self.interceptors.check(&ctx) {
                 return send_result(ctx, sink, Err(err), logger);
             }
 
-            if let Err(err) = self.authenticator.authenticate_rpc(&ctx) {
-                return send_result(ctx, sink, err.into(), logger);
+            let mut service = self.clone();
+            spawn_limited(
+                &ctx,
+                sink,
+                self.concurrency_limiter.clone(),
+                logger.clone(),
+                move || service.get_blocks_impl(request),
+            )
+        })
+    }
+
+    fn get_txo_count_history(
+        &mut self,
+        ctx: RpcContext,
+        request: TxoCountHistoryRequest,
+        sink: UnarySink<TxoCountHistoryResponse>,
+    ) {
+        mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
+            if let Err(err) = self.interceptors.check(&ctx) {
+                return send_result(ctx, sink, Err(err), logger);
             }
 
-            send_result(ctx, sink, self.get_blocks_impl(request), logger)
+            let mut service = self.clone();
+            spawn_limited(
+                &ctx,
+                sink,
+                self.concurrency_limiter.clone(),
+                logger.clone(),
+                move || service.get_txo_count_history_impl(request),
+            )
         })
     }
 }
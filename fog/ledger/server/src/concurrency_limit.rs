@@ -0,0 +1,304 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Limits on the number of calls to a method which are served at once.
+//!
+//! All of the router's services share the same grpcio completion queues, so
+//! without a limit a burst of expensive attested key image checks can occupy
+//! every completion queue thread and starve the cheap, untrusted block reads.
+//! Each limited method gets a [ConcurrencyLimiter]: calls beyond its limit
+//! wait in a bounded queue, without blocking a thread, and calls beyond the
+//! queue are shed with an UNAVAILABLE status, which clients retry.
+
+use crate::{
+    metrics::{
+        CONCURRENCY_LIMIT_IN_FLIGHT, CONCURRENCY_LIMIT_QUEUED, CONCURRENCY_LIMIT_QUEUE_TIME,
+        CONCURRENCY_LIMIT_SHED,
+    },
+    SVC_COUNTERS,
+};
+use futures::{channel::oneshot, FutureExt, TryFutureExt};
+use grpcio::{RpcContext, RpcStatus, UnarySink};
+use mc_common::logger::{log, Logger};
+use mc_util_grpc::{rpc_unavailable_error, ResponseStatus};
+use mc_util_metrics::ServiceMetrics;
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+/// Limits how many calls to a method are served at once, and how many more
+/// may wait for their turn.
+pub struct ConcurrencyLimiter {
+    /// The method name, used in metrics and errors
+    method: &'static str,
+    /// How many calls may be served at once, or 0 for no limit
+    max_in_flight: usize,
+    /// How many calls may wait while `max_in_flight` calls are served
+    max_queued: usize,
+    state: Mutex<LimiterState>,
+}
+
+struct LimiterState {
+    /// The number of calls being served
+    in_flight: usize,
+    /// The calls waiting to be served, in arrival order
+    waiters: VecDeque<oneshot::Sender<()>>,
+}
+
+impl ConcurrencyLimiter {
+    /// Create a new limiter.
+    ///
+    /// # Arguments
+    /// * `method` - The name of the limited method, for metrics and errors
+    /// * `max_in_flight` - How many calls may be served at once. 0 disables the
+    ///   limit.
+    /// * `max_queued` - How many calls may wait for their turn
+    pub fn new(method: &'static str, max_in_flight: usize, max_queued: usize) -> Self {
+        Self {
+            method,
+            max_in_flight,
+            max_queued,
+            state: Mutex::new(LimiterState {
+                in_flight: 0,
+                waiters: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// Create a limiter which lets every call through.
+    pub fn unlimited(method: &'static str) -> Self {
+        Self::new(method, 0, 0)
+    }
+
+    /// Wait for a turn to serve a call, which lasts until the returned permit
+    /// is dropped.
+    ///
+    /// Fails immediately if the queue is full.
+    pub async fn acquire(
+        self: &Arc<Self>,
+        logger: &Logger,
+    ) -> Result<ConcurrencyPermit, RpcStatus> {
+        if self.max_in_flight == 0 {
+            return Ok(ConcurrencyPermit { limiter: None });
+        }
+
+        let receiver = {
+            let mut state = self.state.lock().expect("mutex poisoned");
+            if state.in_flight < self.max_in_flight {
+                state.in_flight += 1;
+                self.update_gauges(&state);
+                return Ok(self.permit());
+            }
+            if state.waiters.len() >= self.max_queued {
+                CONCURRENCY_LIMIT_SHED
+                    .with_label_values(&[self.method])
+                    .inc();
+                return Err(rpc_unavailable_error(
+                    self.method,
+                    format!(
+                        "Too many concurrent requests: {} in flight, {} queued",
+                        state.in_flight,
+                        state.waiters.len()
+                    ),
+                    logger,
+                ));
+            }
+            let (sender, receiver) = oneshot::channel();
+            state.waiters.push_back(sender);
+            self.update_gauges(&state);
+            receiver
+        };
+
+        let _timer = CONCURRENCY_LIMIT_QUEUE_TIME
+            .with_label_values(&[self.method])
+            .start_timer();
+        let mut waiter = Waiter {
+            limiter: self.clone(),
+            receiver: Some(receiver),
+        };
+        waiter.wait().await;
+        Ok(self.permit())
+    }
+
+    fn permit(self: &Arc<Self>) -> ConcurrencyPermit {
+        ConcurrencyPermit {
+            limiter: Some(self.clone()),
+        }
+    }
+
+    /// End a call, handing its turn to the first waiter still waiting.
+    fn release(&self, state: &mut LimiterState) {
+        while let Some(sender) = state.waiters.pop_front() {
+            if sender.send(()).is_ok() {
+                self.update_gauges(state);
+                return;
+            }
+        }
+        state.in_flight -= 1;
+        self.update_gauges(state);
+    }
+
+    fn update_gauges(&self, state: &LimiterState) {
+        CONCURRENCY_LIMIT_IN_FLIGHT
+            .with_label_values(&[self.method])
+            .set(state.in_flight as i64);
+        CONCURRENCY_LIMIT_QUEUED
+            .with_label_values(&[self.method])
+            .set(state.waiters.len() as i64);
+    }
+}
+
+/// A turn to serve a call, which ends when this is dropped.
+pub struct ConcurrencyPermit {
+    limiter: Option<Arc<ConcurrencyLimiter>>,
+}
+
+impl Drop for ConcurrencyPermit {
+    fn drop(&mut self) {
+        if let Some(limiter) = self.limiter.take() {
+            let mut state = limiter.state.lock().expect("mutex poisoned");
+            limiter.release(&mut state);
+        }
+    }
+}
+
+/// A call waiting in the queue.
+///
+/// If the call is cancelled while it waits, dropping this takes it out of the
+/// queue, and passes on the turn it may have been handed in the meantime.
+struct Waiter {
+    limiter: Arc<ConcurrencyLimiter>,
+    receiver: Option<oneshot::Receiver<()>>,
+}
+
+impl Waiter {
+    async fn wait(&mut self) {
+        let receiver = self.receiver.as_mut().expect("waited twice");
+        // The sender is only dropped after a successful send, or if the
+        // limiter is dropped, so either way the call can go ahead.
+        let _ = receiver.await;
+        self.receiver = None;
+    }
+}
+
+impl Drop for Waiter {
+    fn drop(&mut self) {
+        if let Some(mut receiver) = self.receiver.take() {
+            // Turns are handed out with the lock held, so once we hold it our
+            // receiver can no longer change.
+            let mut state = self.limiter.state.lock().expect("mutex poisoned");
+            receiver.close();
+            if let Ok(Some(())) = receiver.try_recv() {
+                self.limiter.release(&mut state);
+            } else {
+                state.waiters.retain(|sender| !sender.is_canceled());
+                self.limiter.update_gauges(&state);
+            }
+        }
+    }
+}
+
+/// Serve a unary call once the limiter gives it a turn, and send the
+/// handler's result.
+///
+/// This takes the place of [mc_util_grpc::send_result] for limited methods.
+pub fn spawn_limited<T, F>(
+    ctx: &RpcContext,
+    sink: UnarySink<T>,
+    limiter: Arc<ConcurrencyLimiter>,
+    logger: Logger,
+    handler: F,
+) where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, RpcStatus> + Send + 'static,
+{
+    let method_name = ServiceMetrics::get_method_name(ctx);
+    let future = async move {
+        let result = match limiter.acquire(&logger).await {
+            Ok(_permit) => handler(),
+            Err(rpc_status) => Err(rpc_status),
+        };
+
+        let response_status = ResponseStatus::from(&result);
+        SVC_COUNTERS.resp_impl(&method_name, response_status.is_success);
+        SVC_COUNTERS.status_code_impl(&method_name, response_status.code);
+
+        match result {
+            Ok(response) => sink.success(response).await,
+            Err(rpc_status) => sink.fail(rpc_status).await,
+        }
+        .map_err(move |err| log::error!(logger, "failed to reply: {}", err))
+    }
+    .map(|_| ());
+    ctx.spawn(future)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{executor::block_on, poll};
+    use grpcio::RpcStatusCode;
+    use mc_common::logger::test_with_logger;
+    use std::{pin::pin, task::Poll};
+
+    fn in_flight_and_queued(limiter: &ConcurrencyLimiter) -> (usize, usize) {
+        let state = limiter.state.lock().unwrap();
+        (state.in_flight, state.waiters.len())
+    }
+
+    #[test_with_logger]
+    fn calls_queue_then_shed(logger: Logger) {
+        let limiter = Arc::new(ConcurrencyLimiter::new("test_queue_then_shed", 1, 1));
+        block_on(async {
+            let first = limiter.acquire(&logger).await.unwrap();
+
+            let mut second = pin!(limiter.acquire(&logger));
+            assert!(matches!(poll!(second.as_mut()), Poll::Pending));
+            assert_eq!(in_flight_and_queued(&limiter), (1, 1));
+
+            let status = limiter.acquire(&logger).await.err().unwrap();
+            assert_eq!(status.code(), RpcStatusCode::UNAVAILABLE);
+
+            // Ending the first call hands its turn to the queued one.
+            drop(first);
+            assert_eq!(in_flight_and_queued(&limiter), (1, 0));
+            let second = second.await.unwrap();
+
+            drop(second);
+            assert_eq!(in_flight_and_queued(&limiter), (0, 0));
+        });
+    }
+
+    #[test_with_logger]
+    fn cancelled_waiters_leave_the_queue(logger: Logger) {
+        let limiter = Arc::new(ConcurrencyLimiter::new("test_cancelled_waiters", 1, 1));
+        block_on(async {
+            let first = limiter.acquire(&logger).await.unwrap();
+            {
+                let mut second = pin!(limiter.acquire(&logger));
+                assert!(matches!(poll!(second.as_mut()), Poll::Pending));
+            }
+            assert_eq!(in_flight_and_queued(&limiter), (1, 0));
+
+            // A waiter dropped after being handed a turn passes it on.
+            {
+                let mut second = pin!(limiter.acquire(&logger));
+                assert!(matches!(poll!(second.as_mut()), Poll::Pending));
+                drop(first);
+            }
+            assert_eq!(in_flight_and_queued(&limiter), (0, 0));
+        });
+    }
+
+    #[test_with_logger]
+    fn unlimited_limiter_lets_calls_through(logger: Logger) {
+        let limiter = Arc::new(ConcurrencyLimiter::unlimited("test_unlimited"));
+        block_on(async {
+            let permits = futures::future::try_join_all((0..10).map(|_| limiter.acquire(&logger)))
+                .await
+                .unwrap();
+            assert_eq!(permits.len(), 10);
+        });
+        assert_eq!(in_flight_and_queued(&limiter), (0, 0));
+    }
+}
@@ -4,11 +4,13 @@
 
 #![deny(missing_docs)]
 
-use crate::sharding_strategy::EpochShardingStrategy;
+use crate::{admission_control::IpCidr, sharding_strategy::EpochShardingStrategy};
 use clap::Parser;
 use mc_common::ResponderId;
+use mc_fog_types::ledger::MAX_PADDING_BUCKET;
 use mc_fog_uri::{FogLedgerUri, KeyImageStoreUri};
 use mc_mobilecoind_api::MobilecoindUri;
+use mc_util_grpc::{AuditLogConfig, InterceptorConfig, MessageSizeConfig};
 use mc_util_parse::{parse_duration_in_millis, parse_duration_in_seconds};
 use mc_util_uri::AdminUri;
 use serde::Serialize;
@@ -33,6 +35,16 @@ pub struct LedgerRouterConfig {
     #[clap(long, env = "MC_CLIENT_LISTEN_URI")]
     pub client_listen_uri: FogLedgerUri,
 
+    /// Further gRPC listening URIs for client requests, which serve the same
+    /// services as --client-listen-uri. This allows e.g. listening with TLS
+    /// for direct clients and insecurely on localhost for a sidecar proxy.
+    #[clap(
+        long = "additional-client-listen-uri",
+        use_value_delimiter = true,
+        env = "MC_ADDITIONAL_CLIENT_LISTEN_URIS"
+    )]
+    pub additional_client_listen_uris: Vec<FogLedgerUri>,
+
     /// gRPC listening URIs for preconfigured Key Image Stores.
     #[clap(long, use_value_delimiter = true, env = "MC_KEY_IMAGE_SHARD_URIS")]
     pub shard_uris: Vec<KeyImageStoreUri>,
@@ -49,6 +61,7 @@ pub struct LedgerRouterConfig {
     /// Enables authenticating client requests using Authorization tokens using
     /// the provided hex-encoded 32 bytes shared secret.
     #[clap(long, value_parser = mc_util_parse::parse_hex::<[u8; 32]>, env = "MC_CLIENT_AUTH_TOKEN_SECRET")]
+    #[serde(serialize_with = "mc_util_cli::redact_secret")]
     pub client_auth_token_secret: Option<[u8; 32]>,
 
     /// Maximal client authentication token lifetime, in seconds (only relevant
@@ -73,6 +86,149 @@ pub struct LedgerRouterConfig {
     /// Mobilecoind URI (to use instead of lmdb)
     #[clap(long, env = "MC_MOBILECOIND_URI")]
     pub mobilecoind_uri: Option<MobilecoindUri>,
+
+    /// Optional request interceptor layers for client-facing services.
+    #[clap(flatten)]
+    pub interceptors: InterceptorConfig,
+
+    /// Optional gRPC message size limits for client-facing services.
+    #[clap(flatten)]
+    pub message_size: MessageSizeConfig,
+
+    /// Optional audit log of administrative and attestation events.
+    #[clap(flatten)]
+    pub audit_log: AuditLogConfig,
+
+    /// What to do when the shards' block ranges do not cover every block in
+    /// the ledger: "refuse" fails key image queries, "degraded" serves them
+    /// anyway and only reports the gap in logs and metrics.
+    #[clap(long, default_value = "refuse", env = "MC_SHARD_COVERAGE_POLICY")]
+    pub shard_coverage_policy: ShardCoveragePolicy,
+
+    /// The number of recently requested TxOuts whose membership proofs are
+    /// cached, and recomputed as soon as each new block appears. 0 disables
+    /// the cache.
+    #[clap(long, default_value = "0", env = "MC_MERKLE_PROOF_CACHE_SIZE")]
+    pub merkle_proof_cache_size: usize,
+
+    /// How many milliseconds to wait between checks for new blocks to
+    /// recompute cached membership proofs at.
+    #[clap(long = "merkle-proof-cache-poll-interval-ms", default_value = "100", value_parser = parse_duration_in_millis, env = "MC_MERKLE_PROOF_CACHE_POLL_INTERVAL_MS")]
+    pub merkle_proof_cache_poll_interval: Duration,
+
+    /// Optional limits on the number of concurrent calls to client-facing
+    /// methods.
+    #[clap(flatten)]
+    pub concurrency_limits: ConcurrencyLimitConfig,
+
+    /// Which callers may call the router's client APIs.
+    #[clap(flatten)]
+    pub admission_control: AdmissionControlConfig,
+
+    /// The longest time, in seconds, to wait between attempts to attest with
+    /// a Key Image Store at startup. The router reports itself ready once it
+    /// has attested with every store in --shard-uris.
+    #[clap(long, default_value = "30", value_parser = parse_duration_in_seconds, env = "MC_STORE_ATTESTATION_MAX_BACKOFF")]
+    pub store_attestation_max_backoff: Duration,
+
+    /// The hex-encoded MRENCLAVE values of Key Image Store enclave builds the
+    /// router refuses to attest with, comma-separated. The list can be
+    /// replaced at runtime through the admin API.
+    #[clap(long, use_value_delimiter = true, value_parser = mc_util_parse::parse_hex::<[u8; 32]>, env = "MC_BLOCKED_STORE_MEASUREMENTS")]
+    pub blocked_store_measurements: Vec<[u8; 32]>,
+
+    /// How often, in milliseconds, to check for new blocks. The key images of
+    /// each stream subscribed to key images are checked again at that
+    /// interval until the key image stores have added the new blocks.
+    #[clap(long = "key-image-subscription-poll-interval-ms", default_value = "1000", value_parser = parse_duration_in_millis, env = "MC_KEY_IMAGE_SUBSCRIPTION_POLL_INTERVAL_MS")]
+    pub key_image_subscription_poll_interval: Duration,
+
+    /// The most streams which may be subscribed to key images at once.
+    #[clap(
+        long,
+        default_value = "10000",
+        env = "MC_KEY_IMAGE_SUBSCRIPTION_MAX_STREAMS"
+    )]
+    pub key_image_subscription_max_streams: usize,
+
+    /// The most key images one stream may be subscribed to. This is enforced
+    /// by the enclave, since the untrusted router can't see the key images.
+    #[clap(
+        long,
+        default_value = "1000",
+        env = "MC_KEY_IMAGE_SUBSCRIPTION_MAX_KEY_IMAGES"
+    )]
+    pub key_image_subscription_max_key_images: usize,
+
+    /// The sizes, in bytes, to pad responses to clients to, comma-separated.
+    /// Each response is padded to the smallest of these it fits in, or to a
+    /// multiple of the largest. Clients learn them when they attest, and pad
+    /// their requests to them too, so that message sizes say little about
+    /// queries. Empty disables padding.
+    #[clap(long, use_value_delimiter = true, value_parser = parse_padding_bucket, env = "MC_PADDING_BUCKETS")]
+    pub padding_buckets: Vec<u32>,
+}
+
+/// Parse a padding bucket, which must be positive and at most
+/// [MAX_PADDING_BUCKET].
+fn parse_padding_bucket(src: &str) -> Result<u32, String> {
+    let bucket = src.parse::<u32>().map_err(|err| err.to_string())?;
+    if bucket == 0 || bucket > MAX_PADDING_BUCKET {
+        return Err(format!(
+            "padding buckets must be between 1 and {MAX_PADDING_BUCKET} bytes"
+        ));
+    }
+    Ok(bucket)
+}
+
+/// Limits on how many calls to each expensive client-facing method the router
+/// serves at once, so that one method can't starve the others.
+///
+/// Calls beyond a limit wait in a queue, and calls beyond the queue are
+/// rejected with UNAVAILABLE.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Parser, Serialize)]
+pub struct ConcurrencyLimitConfig {
+    /// How many key image checks, over either the streaming or the unary API,
+    /// are served at once. 0 means unlimited.
+    #[clap(long, default_value = "0", env = "MC_CHECK_KEY_IMAGES_MAX_CONCURRENCY")]
+    pub check_key_images_max_concurrency: usize,
+
+    /// How many key image checks may wait for their turn.
+    #[clap(long, default_value = "0", env = "MC_CHECK_KEY_IMAGES_MAX_QUEUED")]
+    pub check_key_images_max_queued: usize,
+
+    /// How many get_blocks and get_txo_count_history calls are served at
+    /// once. 0 means unlimited.
+    #[clap(long, default_value = "0", env = "MC_GET_BLOCKS_MAX_CONCURRENCY")]
+    pub get_blocks_max_concurrency: usize,
+
+    /// How many get_blocks and get_txo_count_history calls may wait for their
+    /// turn.
+    #[clap(long, default_value = "0", env = "MC_GET_BLOCKS_MAX_QUEUED")]
+    pub get_blocks_max_queued: usize,
+}
+
+/// Which callers' addresses the router serves its client APIs to.
+///
+/// Behind a proxy, the address is the proxy's.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Parser, Serialize)]
+pub struct AdmissionControlConfig {
+    /// IP ranges, like 10.0.0.0/8 or 2001:db8::/32, to only serve callers
+    /// from. Every caller is served when none are given.
+    #[clap(
+        long = "admission-allow-cidr",
+        use_value_delimiter = true,
+        env = "MC_ADMISSION_ALLOW_CIDRS"
+    )]
+    pub admission_allow: Vec<IpCidr>,
+
+    /// IP ranges to never serve callers from, even if they are allowed.
+    #[clap(
+        long = "admission-deny-cidr",
+        use_value_delimiter = true,
+        env = "MC_ADMISSION_DENY_CIDRS"
+    )]
+    pub admission_deny: Vec<IpCidr>,
 }
 
 /// Configuration parameters for the Fog Ledger Store service.
@@ -118,6 +274,7 @@ pub struct LedgerStoreConfig {
     /// Enables authenticating client requests using Authorization tokens using
     /// the provided hex-encoded 32 bytes shared secret.
     #[clap(long, value_parser = mc_util_parse::parse_hex::<[u8; 32]>, env = "MC_CLIENT_AUTH_TOKEN_SECRET")]
+    #[serde(serialize_with = "mc_util_cli::redact_secret")]
     pub client_auth_token_secret: Option<[u8; 32]>,
 
     /// Maximal client authentication token lifetime, in seconds (only relevant
@@ -131,14 +288,28 @@ pub struct LedgerStoreConfig {
     /// The hash table will overflow when there are more Keyimages than this,
     /// and the server will have to be restarted with a larger number.
     ///
-    /// Note: At time of writing, the hash table will be allocated to use all
-    /// available SGX EPC memory, and then beyond that it will be allocated on
-    /// the heap in the untrusted side. Once the needed capacity exceeds RAM,
-    /// you will either get killed by OOM killer, or it will start being swapped
-    /// to disk by linux kernel.
+    /// Note: The top of the hash table's ORAM is kept in enclave memory, up to
+    /// --oram-memory-budget, and the rest on the heap in the untrusted side,
+    /// encrypted and authenticated. Once the needed capacity exceeds RAM, set
+    /// --oram-spill-dir, or you will either get killed by OOM killer, or it
+    /// will start being swapped to disk by linux kernel.
     #[clap(long, default_value = "1048576", env = "MC_OMAP_CAPACITY")]
     pub omap_capacity: u64,
 
+    /// How many bytes of each store's ORAM to keep in enclave memory, rounded
+    /// down to a power of two. Defaults to 32 MB. A smaller budget lets an
+    /// instance with less enclave memory serve larger epochs, but makes
+    /// queries slower.
+    #[clap(long, env = "MC_ORAM_MEMORY_BUDGET")]
+    pub oram_memory_budget: Option<u64>,
+
+    /// Directory in which to keep the part of each ORAM which is outside the
+    /// enclave, instead of on the heap. This lets the stores exceed RAM, at
+    /// the cost of a disk access per ORAM block they read or write. The
+    /// enclave encrypts and authenticates everything it stores there.
+    #[clap(long, env = "MC_ORAM_SPILL_DIR")]
+    pub oram_spill_dir: Option<PathBuf>,
+
     /// Determines which group of Key Images the Key Image Store instance will
     /// process.
     #[clap(long, default_value = "default", env = "MC_SHARDING_STRATEGY")]
@@ -147,6 +318,140 @@ pub struct LedgerStoreConfig {
     /// How many milliseconds to wait between polling.
     #[clap(long = "poll_interval_ms", default_value = "250", value_parser = parse_duration_in_millis, env = "MC_POLL_INTERVAL_MS")]
     pub poll_interval: Duration,
+
+    /// Further epochs to serve from this process, each with its own enclave
+    /// and OMAP, as
+    /// `responder-id=<id>;listen-uri=<uri>;block-range=<start>-<end>;
+    /// omap-capacity=<n>`, optionally followed by `;oram-memory-budget=<n>`.
+    /// This lets small historical epochs share a process instead of each
+    /// needing their own.
+    #[clap(
+        long = "additional-epoch",
+        use_value_delimiter = true,
+        env = "MC_ADDITIONAL_EPOCHS"
+    )]
+    pub additional_epochs: Vec<StoreEpochConfig>,
+
+    /// Directory in which to keep an export of the key image data added to
+    /// each epoch's enclave. At startup, the blocks found in the export are
+    /// checked against the ledger and added to the enclave before the ledger
+    /// is polled, so a new replica can be bootstrapped from a copy of an
+    /// existing store's export.
+    #[clap(long, env = "MC_KEY_IMAGE_EXPORT_DIR")]
+    pub key_image_export_dir: Option<PathBuf>,
+
+    /// Directory in which to keep a queue of each epoch's fetched blocks,
+    /// from which they are added to the enclave by a thread of their own, so
+    /// that fetching blocks doesn't wait for the enclave. Blocks left in the
+    /// queue at shutdown are added at the next startup, if they follow on
+    /// from the blocks in the key image export.
+    #[clap(long, env = "MC_INTAKE_QUEUE_DIR")]
+    pub intake_queue_dir: Option<PathBuf>,
+
+    /// The most fetched blocks to keep in each epoch's intake queue. Blocks
+    /// are not fetched while the queue is full.
+    #[clap(long, default_value = "1000", env = "MC_INTAKE_QUEUE_CAPACITY")]
+    pub intake_queue_capacity: u64,
+}
+
+impl LedgerStoreConfig {
+    /// The configuration of every store this process serves: this one, and
+    /// then one for each additional epoch.
+    pub fn store_configs(&self) -> Vec<LedgerStoreConfig> {
+        let primary = LedgerStoreConfig {
+            additional_epochs: vec![],
+            ..self.clone()
+        };
+        let additional = self
+            .additional_epochs
+            .iter()
+            .map(|epoch| LedgerStoreConfig {
+                client_responder_id: epoch.client_responder_id.clone(),
+                client_listen_uri: epoch.client_listen_uri.clone(),
+                sharding_strategy: ShardingStrategy::Epoch(epoch.sharding_strategy.clone()),
+                omap_capacity: epoch.omap_capacity,
+                oram_memory_budget: epoch.oram_memory_budget.or(self.oram_memory_budget),
+                // The admin API is served once, for the whole process.
+                admin_listen_uri: None,
+                ..primary.clone()
+            });
+        std::iter::once(primary.clone()).chain(additional).collect()
+    }
+}
+
+/// An additional epoch served by a key image store process.
+#[derive(Clone, Serialize)]
+pub struct StoreEpochConfig {
+    /// The ID with which to respond to client attestation requests for this
+    /// epoch.
+    pub client_responder_id: ResponderId,
+
+    /// gRPC listening URI for client requests for this epoch.
+    pub client_listen_uri: KeyImageStoreUri,
+
+    /// The blocks whose key images this epoch holds.
+    pub sharding_strategy: EpochShardingStrategy,
+
+    /// The capacity to build this epoch's OMAP with.
+    pub omap_capacity: u64,
+
+    /// How many bytes of this epoch's ORAM to keep in enclave memory, if not
+    /// the process's --oram-memory-budget.
+    pub oram_memory_budget: Option<u64>,
+}
+
+impl FromStr for StoreEpochConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut client_responder_id = None;
+        let mut client_listen_uri = None;
+        let mut sharding_strategy = None;
+        let mut omap_capacity = None;
+        let mut oram_memory_budget = None;
+        for field in s.split(';') {
+            let (key, value) = field
+                .split_once('=')
+                .ok_or_else(|| format!("Expected key=value, found {field:?}"))?;
+            let value = value.trim();
+            match key.trim() {
+                "responder-id" => {
+                    client_responder_id = Some(
+                        ResponderId::from_str(value)
+                            .map_err(|err| format!("Invalid responder-id {value:?}: {err}"))?,
+                    )
+                }
+                "listen-uri" => {
+                    client_listen_uri = Some(
+                        KeyImageStoreUri::from_str(value)
+                            .map_err(|err| format!("Invalid listen-uri {value:?}: {err}"))?,
+                    )
+                }
+                "block-range" => sharding_strategy = Some(EpochShardingStrategy::from_str(value)?),
+                "omap-capacity" => {
+                    omap_capacity = Some(
+                        value
+                            .parse()
+                            .map_err(|err| format!("Invalid omap-capacity {value:?}: {err}"))?,
+                    )
+                }
+                "oram-memory-budget" => {
+                    oram_memory_budget =
+                        Some(value.parse().map_err(|err| {
+                            format!("Invalid oram-memory-budget {value:?}: {err}")
+                        })?)
+                }
+                other => return Err(format!("Unknown epoch field {other:?}")),
+            }
+        }
+        Ok(Self {
+            client_responder_id: client_responder_id.ok_or("Missing responder-id")?,
+            client_listen_uri: client_listen_uri.ok_or("Missing listen-uri")?,
+            sharding_strategy: sharding_strategy.ok_or("Missing block-range")?,
+            omap_capacity: omap_capacity.ok_or("Missing omap-capacity")?,
+            oram_memory_budget,
+        })
+    }
 }
 
 /// Enum for parsing strategy from command line w/ clap
@@ -170,3 +475,217 @@ impl FromStr for ShardingStrategy {
         Err("Invalid sharding strategy config.".to_string())
     }
 }
+
+/// How the router treats blocks which no shard is responsible for.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+pub enum ShardCoveragePolicy {
+    /// Fail key image queries until every block is covered.
+    #[default]
+    Refuse,
+    /// Serve key image queries, reporting the gap in logs and metrics.
+    Degraded,
+}
+
+impl FromStr for ShardCoveragePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "refuse" => Ok(Self::Refuse),
+            "degraded" => Ok(Self::Degraded),
+            _ => Err(format!("Invalid shard coverage policy: {s}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sharding_strategy::ShardingStrategy as _;
+    use mc_fog_uri::ConnectionUri;
+
+    #[test]
+    fn parse_store_epoch_config() {
+        let epoch = StoreEpochConfig::from_str(
+            "responder-id=store-1.example.com:443; \
+             listen-uri=insecure-key-image-store://0.0.0.0:3230; \
+             block-range=100-200; omap-capacity=65536",
+        )
+        .unwrap();
+        assert_eq!(
+            epoch.client_responder_id,
+            ResponderId::from_str("store-1.example.com:443").unwrap()
+        );
+        assert_eq!(epoch.client_listen_uri.port(), 3230);
+        assert_eq!(epoch.omap_capacity, 65536);
+        assert_eq!(epoch.oram_memory_budget, None);
+        assert!(epoch.sharding_strategy.should_process_block(100));
+        assert!(!epoch.sharding_strategy.should_process_block(200));
+
+        let epoch = StoreEpochConfig::from_str(
+            "responder-id=store-1.example.com:443; \
+             listen-uri=insecure-key-image-store://0.0.0.0:3230; \
+             block-range=100-200; omap-capacity=65536; oram-memory-budget=16777216",
+        )
+        .unwrap();
+        assert_eq!(epoch.oram_memory_budget, Some(16777216));
+
+        assert!(StoreEpochConfig::from_str("block-range=100-200;omap-capacity=65536").is_err());
+        assert!(StoreEpochConfig::from_str(
+            "responder-id=a:1;listen-uri=insecure-key-image-store://0.0.0.0:3230;\
+             block-range=100-200;omap-capacity=lots"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn parse_additional_client_listen_uris() {
+        let config = LedgerRouterConfig::try_parse_from([
+            "ledger_router",
+            "--chain-id=local",
+            "--client-responder-id=router.example.com:443",
+            "--client-listen-uri=fog-ledger://router.example.com:443/?tls-chain=chain.pem&tls-key=key.pem",
+            "--additional-client-listen-uri=insecure-fog-ledger://127.0.0.1:3228,insecure-fog-ledger://127.0.0.1:3229",
+            "--admin-listen-uri=insecure-mca://127.0.0.1:8001",
+        ])
+        .unwrap();
+        assert!(config.client_listen_uri.use_tls());
+        assert_eq!(
+            config
+                .additional_client_listen_uris
+                .iter()
+                .map(|uri| uri.port())
+                .collect::<Vec<_>>(),
+            vec![3228, 3229]
+        );
+        assert_eq!(config.concurrency_limits, ConcurrencyLimitConfig::default());
+        assert_eq!(config.admission_control, AdmissionControlConfig::default());
+        assert_eq!(
+            config.store_attestation_max_backoff,
+            Duration::from_secs(30)
+        );
+        assert!(config.blocked_store_measurements.is_empty());
+        assert_eq!(
+            config.key_image_subscription_poll_interval,
+            Duration::from_secs(1)
+        );
+        assert_eq!(config.key_image_subscription_max_streams, 10000);
+        assert_eq!(config.key_image_subscription_max_key_images, 1000);
+        assert!(config.padding_buckets.is_empty());
+    }
+
+    #[test]
+    fn parse_padding_buckets() {
+        let config = LedgerRouterConfig::try_parse_from([
+            "ledger_router",
+            "--chain-id=local",
+            "--client-responder-id=router.example.com:443",
+            "--client-listen-uri=insecure-fog-ledger://127.0.0.1:3228",
+            "--admin-listen-uri=insecure-mca://127.0.0.1:8001",
+            "--padding-buckets=1024,4096,65536",
+        ])
+        .unwrap();
+        assert_eq!(config.padding_buckets, vec![1024, 4096, 65536]);
+
+        for buckets in ["0", "2097152", "1k"] {
+            assert!(LedgerRouterConfig::try_parse_from([
+                "ledger_router",
+                "--chain-id=local",
+                "--client-responder-id=router.example.com:443",
+                "--client-listen-uri=insecure-fog-ledger://127.0.0.1:3228",
+                "--admin-listen-uri=insecure-mca://127.0.0.1:8001",
+                &format!("--padding-buckets={buckets}"),
+            ])
+            .is_err());
+        }
+    }
+
+    #[test]
+    fn parse_blocked_store_measurements() {
+        let config = LedgerRouterConfig::try_parse_from([
+            "ledger_router",
+            "--chain-id=local",
+            "--client-responder-id=router.example.com:443",
+            "--client-listen-uri=insecure-fog-ledger://127.0.0.1:3228",
+            "--admin-listen-uri=insecure-mca://127.0.0.1:8001",
+            &format!(
+                "--blocked-store-measurements={},{}",
+                hex::encode([1u8; 32]),
+                hex::encode([2u8; 32])
+            ),
+        ])
+        .unwrap();
+        assert_eq!(
+            config.blocked_store_measurements,
+            vec![[1u8; 32], [2u8; 32]]
+        );
+
+        assert!(LedgerRouterConfig::try_parse_from([
+            "ledger_router",
+            "--chain-id=local",
+            "--client-responder-id=router.example.com:443",
+            "--client-listen-uri=insecure-fog-ledger://127.0.0.1:3228",
+            "--admin-listen-uri=insecure-mca://127.0.0.1:8001",
+            "--blocked-store-measurements=0102",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn parse_concurrency_limits() {
+        let config = LedgerRouterConfig::try_parse_from([
+            "ledger_router",
+            "--chain-id=local",
+            "--client-responder-id=router.example.com:443",
+            "--client-listen-uri=insecure-fog-ledger://127.0.0.1:3228",
+            "--admin-listen-uri=insecure-mca://127.0.0.1:8001",
+            "--check-key-images-max-concurrency=4",
+            "--check-key-images-max-queued=64",
+            "--get-blocks-max-concurrency=16",
+        ])
+        .unwrap();
+        assert_eq!(
+            config.concurrency_limits,
+            ConcurrencyLimitConfig {
+                check_key_images_max_concurrency: 4,
+                check_key_images_max_queued: 64,
+                get_blocks_max_concurrency: 16,
+                get_blocks_max_queued: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn parse_admission_control() {
+        let config = LedgerRouterConfig::try_parse_from([
+            "ledger_router",
+            "--chain-id=local",
+            "--client-responder-id=router.example.com:443",
+            "--client-listen-uri=insecure-fog-ledger://127.0.0.1:3228",
+            "--admin-listen-uri=insecure-mca://127.0.0.1:8001",
+            "--admission-allow-cidr=10.0.0.0/8,2001:db8::/32",
+            "--admission-deny-cidr=10.66.0.0/16",
+        ])
+        .unwrap();
+        assert_eq!(
+            config.admission_control,
+            AdmissionControlConfig {
+                admission_allow: vec![
+                    IpCidr::from_str("10.0.0.0/8").unwrap(),
+                    IpCidr::from_str("2001:db8::/32").unwrap(),
+                ],
+                admission_deny: vec![IpCidr::from_str("10.66.0.0/16").unwrap()],
+            }
+        );
+
+        assert!(LedgerRouterConfig::try_parse_from([
+            "ledger_router",
+            "--chain-id=local",
+            "--client-responder-id=router.example.com:443",
+            "--client-listen-uri=insecure-fog-ledger://127.0.0.1:3228",
+            "--admin-listen-uri=insecure-mca://127.0.0.1:8001",
+            "--admission-deny-cidr=10.66.0.0/40",
+        ])
+        .is_err());
+    }
+}
@@ -12,4 +12,8 @@ lazy_static::lazy_static! {
           pub static ref BLOCKS_ADDED_COUNT: IntCounter = OP_COUNTERS.counter("blocks_added_count");
           // Number of keyimages fetched (from the database) since startup.
           pub static ref KEY_IMAGES_FETCHED_COUNT: IntCounter = OP_COUNTERS.counter("keyimages_fetched_count");
+          // Number of TxOut membership proofs served from the merkle proof cache.
+          pub static ref MERKLE_PROOF_CACHE_HITS: IntCounter = OP_COUNTERS.counter("merkle_proof_cache_hits");
+          // Number of TxOut membership proofs the merkle proof cache did not have at the requested height.
+          pub static ref MERKLE_PROOF_CACHE_MISSES: IntCounter = OP_COUNTERS.counter("merkle_proof_cache_misses");
 }
@@ -3,7 +3,13 @@
 //! A background thread, in the server side, that continuously checks the
 //! LedgerDB for new blocks, then gets all the key images associated to those
 //! blocks and adds them to the enclave.
-use crate::{counters, sharding_strategy::ShardingStrategy, DbPollSharedState};
+//!
+//! With an intake queue, the thread only pushes the key images of each block
+//! to the queue, and a second thread adds them from the queue to the enclave.
+use crate::{
+    counters, intake_queue::IntakeQueue, key_image_export::KeyImageExport,
+    sharding_strategy::ShardingStrategy, DbPollSharedState,
+};
 use mc_blockchain_types::Block;
 use mc_common::{
     logger::{log, Logger},
@@ -36,6 +42,14 @@ const TELEMETRY_BLOCK_INDEX_KEY: Key = telemetry_static_key!("block-index");
 /// marked unready
 const BLOCKS_BEHIND: u64 = 100;
 
+/// The number of blocks read from the key image export and added to the
+/// enclave at once
+const EXPORT_IMPORT_BATCH_BLOCKS: usize = 1000;
+
+/// The number of blocks taken from the intake queue and added to the enclave
+/// at once
+const INTAKE_BATCH_BLOCKS: usize = 100;
+
 /// An object for managing background data fetches from the ledger database.
 pub struct DbFetcher<
     E: LedgerEnclaveProxy + Clone + Send + Sync + 'static,
@@ -86,6 +100,29 @@ impl<
         }
     }
 
+    /// Keep an export of the key image data added to the enclave, and start
+    /// by adding the blocks already in it.
+    ///
+    /// This must be called before the thread is started.
+    pub fn set_key_image_export(&mut self, key_image_export: KeyImageExport) {
+        self.thread
+            .as_mut()
+            .expect("DbFetcher thread already started")
+            .key_image_export = Some(key_image_export);
+    }
+
+    /// Push fetched blocks to the given intake queue, and add them from the
+    /// queue to the enclave in a separate thread, starting with the blocks
+    /// already queued.
+    ///
+    /// This must be called before the thread is started.
+    pub fn set_intake_queue(&mut self, intake_queue: IntakeQueue) {
+        self.thread
+            .as_mut()
+            .expect("DbFetcher thread already started")
+            .intake_queue = Some(Arc::new(intake_queue));
+    }
+
     /// Start running the DbFetcher thread.
     pub fn start(&mut self) {
         let thread = self
@@ -132,6 +169,10 @@ struct DbFetcherThread<
     db_poll_shared_state: Arc<Mutex<DbPollSharedState>>,
     readiness_indicator: ReadinessIndicator,
     poll_interval: Duration,
+    /// The export of the key image data added to the enclave, if kept
+    key_image_export: Option<KeyImageExport>,
+    /// The queue of blocks waiting to be added to the enclave, if used
+    intake_queue: Option<Arc<IntakeQueue>>,
     logger: Logger,
 }
 
@@ -143,6 +184,7 @@ impl<
     > DbFetcherThread<E, SS>
 {
     const ERROR_RETRY_FREQUENCY: Duration = Duration::from_millis(1000);
+    const INTAKE_QUEUE_FULL_RETRY_FREQUENCY: Duration = Duration::from_millis(100);
 
     pub fn new(
         block_provider: Box<dyn BlockProvider>,
@@ -162,6 +204,8 @@ impl<
             db_poll_shared_state,
             readiness_indicator,
             poll_interval,
+            key_image_export: None,
+            intake_queue: None,
             logger,
         }
     }
@@ -169,13 +213,39 @@ impl<
     pub fn run(mut self) {
         log::info!(self.logger, "Db fetcher thread started.");
         let block_range = self.sharding_strategy.get_block_range();
-        let mut next_block_index = block_range.start_block;
+        let mut next_block_index = self.import_key_image_export(&block_range);
+        let mut intake_thread = None;
+        if let Some(intake_queue) = self.intake_queue.clone() {
+            next_block_index = self.resume_intake_queue(&intake_queue, next_block_index);
+            intake_thread = Some(IntakeThread::start(
+                intake_queue,
+                self.enclave.clone(),
+                self.db_poll_shared_state.clone(),
+                block_range.clone(),
+                self.key_image_export.take(),
+                self.stop_requested.clone(),
+                self.logger.clone(),
+            ));
+        }
+
         loop {
+            if !block_range.contains(next_block_index) {
+                log::info!(self.logger, "Db fetcher thread reached end of block range.");
+                break;
+            }
+
             loop {
                 if self.stop_requested.load(Ordering::SeqCst) {
                     break;
                 }
 
+                if let Some(intake_queue) = self.intake_queue.as_ref() {
+                    if intake_queue.is_full() {
+                        std::thread::sleep(Self::INTAKE_QUEUE_FULL_RETRY_FREQUENCY);
+                        continue;
+                    }
+                }
+
                 let Some(num_blocks) = self.load_block_data(&mut next_block_index) else {
                     std::thread::sleep(Self::ERROR_RETRY_FREQUENCY);
                     continue;
@@ -183,7 +253,12 @@ impl<
 
                 let end = min(num_blocks, block_range.end_block);
 
-                if next_block_index < end.saturating_sub(BLOCKS_BEHIND) {
+                // Blocks still in the intake queue are not in the enclave yet.
+                let added_block_index = self
+                    .intake_queue
+                    .as_ref()
+                    .map_or(next_block_index, |queue| queue.queued_blocks().start_block);
+                if added_block_index < end.saturating_sub(BLOCKS_BEHIND) {
                     self.readiness_indicator.set_unready();
                 } else {
                     self.readiness_indicator.set_ready();
@@ -206,6 +281,170 @@ impl<
 
             std::thread::sleep(self.poll_interval);
         }
+
+        if let Some(intake_thread) = intake_thread {
+            intake_thread.finish();
+        }
+        if !block_range.contains(next_block_index) {
+            self.readiness_indicator.set_ready();
+        }
+    }
+
+    /// Resume adding the blocks left in the intake queue which follow on from
+    /// the blocks already added to the enclave.
+    ///
+    /// Returns the index of the first block which still has to be loaded from
+    /// the block provider.
+    fn resume_intake_queue(&self, intake_queue: &IntakeQueue, next_block_index: u64) -> u64 {
+        match intake_queue.resume(next_block_index) {
+            Ok(end_block) => {
+                if end_block > next_block_index {
+                    log::info!(
+                        self.logger,
+                        "Resuming with blocks {} from the intake queue",
+                        BlockRange::new(next_block_index, end_block)
+                    );
+                }
+                end_block
+            }
+            Err(err) => {
+                log::error!(
+                    self.logger,
+                    "Could not remove stale blocks from the intake queue: {}",
+                    err
+                );
+                intake_queue.queued_blocks().end_block
+            }
+        }
+    }
+
+    /// Add the blocks recorded in the key image export to the enclave.
+    ///
+    /// Returns the index of the first block which still has to be loaded from
+    /// the block provider.
+    fn import_key_image_export(&mut self, block_range: &BlockRange) -> u64 {
+        let start_block = block_range.start_block;
+        let Some(key_image_export) = self.key_image_export.take() else {
+            return start_block;
+        };
+
+        let mut next_block_index = start_block;
+        loop {
+            let blocks =
+                match key_image_export.read_blocks(next_block_index, EXPORT_IMPORT_BATCH_BLOCKS) {
+                    Ok(blocks) => blocks,
+                    Err(err) => {
+                        log::error!(
+                            self.logger,
+                            "Could not read the key image export at block {}: {}",
+                            next_block_index,
+                            err
+                        );
+                        break;
+                    }
+                };
+            let num_read = blocks.len();
+            let blocks = self.verify_export_blocks(blocks);
+            let Some((last_block_index, _)) = blocks.last() else {
+                break;
+            };
+            let imported_blocks = BlockRange::new(next_block_index, last_block_index + 1);
+            let all_verified = blocks.len() == num_read;
+            let records = blocks
+                .into_iter()
+                .flat_map(|(_, records)| records)
+                .collect();
+            self.add_records_to_enclave(&imported_blocks, records);
+            next_block_index = imported_blocks.end_block;
+            if !all_verified {
+                break;
+            }
+        }
+        self.key_image_export = Some(key_image_export);
+
+        if next_block_index == start_block {
+            return start_block;
+        }
+        log::info!(
+            self.logger,
+            "Imported blocks {} from the key image export",
+            BlockRange::new(start_block, next_block_index)
+        );
+
+        // Blocks are usually only imported long after they were added to the
+        // ledger, so the last one doesn't tell us about the ledger's tip.
+        loop {
+            match self.block_provider.get_latest_block() {
+                Ok(latest_block) => {
+                    let mut processed_block_range = block_range.clone();
+                    processed_block_range.end_block = next_block_index;
+                    self.update_db_poll_shared_state(&latest_block, Some(processed_block_range));
+                    break;
+                }
+                Err(err) => {
+                    log::error!(self.logger, "Could not get the latest block: {}", err);
+                    if self.stop_requested.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    std::thread::sleep(Self::ERROR_RETRY_FREQUENCY);
+                }
+            }
+        }
+        next_block_index
+    }
+
+    /// Check blocks read from the key image export against the ledger.
+    ///
+    /// Returns the leading blocks whose key images are exactly those of the
+    /// block in the ledger, with the ledger's timestamp where it has one, so
+    /// that an export which was tampered with or corrupted can neither add
+    /// key images to the enclave nor hide any. Blocks from the first one that
+    /// doesn't match on are left to be loaded from the ledger.
+    fn verify_export_blocks(
+        &self,
+        blocks: Vec<(u64, Vec<KeyImageData>)>,
+    ) -> Vec<(u64, Vec<KeyImageData>)> {
+        let block_indices: Vec<u64> = blocks.iter().map(|(block_index, _)| *block_index).collect();
+        let ledger_blocks = match self.block_provider.get_blocks_data(&block_indices) {
+            Ok(response) => response.results,
+            Err(err) => {
+                log::error!(
+                    self.logger,
+                    "Could not get blocks to verify the key image export against: {}",
+                    err
+                );
+                return Vec::new();
+            }
+        };
+
+        let mut verified = Vec::with_capacity(blocks.len());
+        for ((block_index, records), ledger_block) in blocks.into_iter().zip(ledger_blocks) {
+            let Some(ledger_block) = ledger_block else {
+                break;
+            };
+            let timestamp_found =
+                ledger_block.block_timestamp_result_code == TimestampResultCode::TimestampFound;
+            let matches = records.len() == ledger_block.block_data.contents().key_images.len()
+                && records
+                    .iter()
+                    .zip(&ledger_block.block_data.contents().key_images)
+                    .all(|(record, key_image)| {
+                        record.key_image == *key_image
+                            && record.block_index == block_index
+                            && (!timestamp_found
+                                || record.timestamp == ledger_block.block_timestamp)
+                    });
+            if !matches {
+                log::error!(
+                    self.logger,
+                    "Block {} in the key image export does not match the ledger, loading it and the following blocks from the ledger",
+                    block_index
+                );
+                break;
+            }
+            verified.push((block_index, records));
+        }
+        verified
     }
 
     /// Attempt to load the next block that we are aware of and tracking.
@@ -234,6 +473,10 @@ impl<
         };
 
         let latest_block = blocks.latest_block;
+        self.db_poll_shared_state
+            .lock()
+            .expect("mutex poisoned")
+            .ledger_num_blocks = latest_block.index + 1;
 
         if let Some(next_block) = blocks.results.get(0).and_then(|r| r.as_ref()) {
             let tracer = tracer!();
@@ -258,7 +501,7 @@ impl<
                 };
 
             // Add block to enclave.
-            let records = next_block
+            let records: Vec<_> = next_block
                 .block_data
                 .contents()
                 .key_images
@@ -270,72 +513,235 @@ impl<
                 })
                 .collect();
 
+            if let Some(intake_queue) = self.intake_queue.as_ref() {
+                let pushed = tracer.in_span("push_to_intake_queue", |_cx| {
+                    intake_queue.push(*next_block_index, &records)
+                });
+                if let Err(err) = pushed {
+                    log::error!(
+                        self.logger,
+                        "Could not add block {} to the intake queue: {}",
+                        next_block_index,
+                        err
+                    );
+                    return None;
+                }
+
+                *next_block_index += 1;
+                self.update_db_poll_shared_state(&latest_block, None);
+                return Some(latest_block.index + 1);
+            }
+
+            if let Some(key_image_export) = self.key_image_export.as_ref() {
+                if let Err(err) = key_image_export.write_block(*next_block_index, &records) {
+                    log::error!(
+                        self.logger,
+                        "Could not add block {} to the key image export: {}",
+                        next_block_index,
+                        err
+                    );
+                }
+            }
+
             tracer.in_span("add_records_to_enclave", |_cx| {
-                self.add_records_to_enclave(*next_block_index, records);
+                self.add_records_to_enclave(
+                    &BlockRange::new_from_length(*next_block_index, 1),
+                    records,
+                );
             });
 
             *next_block_index += 1;
             let mut processed_block_range = self.sharding_strategy.get_block_range();
             processed_block_range.end_block = *next_block_index;
-            self.update_db_poll_shared_state(&latest_block, processed_block_range);
+            self.update_db_poll_shared_state(&latest_block, Some(processed_block_range));
         }
         // Adding 1 as indices are 0 based, but "number of blocks" is 1 based.
         Some(latest_block.index + 1)
     }
 
+    /// Record the latest block, and the blocks added to the enclave, if they
+    /// are known to this thread rather than the intake thread.
     fn update_db_poll_shared_state(
         &mut self,
         latest_block: &Block,
-        processed_block_range: BlockRange,
+        processed_block_range: Option<BlockRange>,
     ) {
         tracer!().in_span("update_shared_state", |_cx| {
             let mut shared_state = self.db_poll_shared_state.lock().expect("mutex poisoned");
-            shared_state.processed_block_range = processed_block_range;
+            if let Some(processed_block_range) = processed_block_range {
+                shared_state.processed_block_range = processed_block_range;
+            }
             shared_state.last_known_block_cumulative_txo_count = latest_block.cumulative_txo_count;
             shared_state.latest_block_version = latest_block.version;
+            shared_state.ledger_num_blocks = latest_block.index + 1;
         });
     }
 
-    fn add_records_to_enclave(&mut self, block_index: u64, records: Vec<KeyImageData>) {
-        let num_records = records.len();
-
-        let _info = retry(delay::Fixed::from_millis(5000).map(delay::jitter), || {
-            trace_time!(
-                self.logger,
-                "Added {} records into the enclave",
-                num_records
-            );
-            let metrics_timer = counters::ENCLAVE_ADD_KEY_IMAGE_DATA_TIME.start_timer();
-
-            match self.enclave.add_key_image_data(records.clone()) {
-                Ok(info) => {
-                    // Update metrics
-                    counters::BLOCKS_ADDED_COUNT.inc();
-                    counters::KEY_IMAGES_FETCHED_COUNT.inc_by(num_records as u64);
-                    OperationResult::Ok(info)
-                }
-                Err(err) => {
-                    let _ = metrics_timer.stop_and_discard();
-                    // Failing to add records to the enclave is unrecoverable,
-                    // When we encounter this failure mode we will begin logging a high-priority log
-                    // message every ten minutes indefinitely.
-                    log::crit!(
-                        self.logger,
-                        "Failed adding {} keyimage_outs for {} into enclave: {}",
-                        num_records,
-                        block_index,
-                        err
+    fn add_records_to_enclave(&mut self, blocks: &BlockRange, records: Vec<KeyImageData>) {
+        add_records_to_enclave(
+            &self.enclave,
+            &self.db_poll_shared_state,
+            blocks,
+            records,
+            &self.logger,
+        );
+    }
+}
+
+/// Add the key image data of the given blocks to the enclave, retrying until
+/// it succeeds.
+fn add_records_to_enclave<E: LedgerEnclaveProxy>(
+    enclave: &E,
+    db_poll_shared_state: &Mutex<DbPollSharedState>,
+    blocks: &BlockRange,
+    records: Vec<KeyImageData>,
+    logger: &Logger,
+) {
+    let num_records = records.len();
+
+    let _info = retry(delay::Fixed::from_millis(5000).map(delay::jitter), || {
+        trace_time!(logger, "Added {} records into the enclave", num_records);
+        let metrics_timer = counters::ENCLAVE_ADD_KEY_IMAGE_DATA_TIME.start_timer();
+
+        match enclave.add_key_image_data(records.clone()) {
+            Ok(info) => {
+                // Update metrics
+                counters::BLOCKS_ADDED_COUNT.inc_by(blocks.len());
+                counters::KEY_IMAGES_FETCHED_COUNT.inc_by(num_records as u64);
+                OperationResult::Ok(info)
+            }
+            Err(err) => {
+                let _ = metrics_timer.stop_and_discard();
+                // Failing to add records to the enclave is unrecoverable,
+                // When we encounter this failure mode we will begin logging a high-priority log
+                // message every ten minutes indefinitely.
+                log::crit!(
+                    logger,
+                    "Failed adding {} keyimage_outs for blocks {} into enclave: {}",
+                    num_records,
+                    blocks,
+                    err
+                );
+                OperationResult::Retry(err)
+            }
+        }
+    });
+
+    db_poll_shared_state
+        .lock()
+        .expect("mutex poisoned")
+        .num_key_images += num_records as u64;
+
+    log::info!(
+        logger,
+        "Added {} keyimage outs for blocks {} into the enclave",
+        num_records,
+        blocks
+    );
+}
+
+/// A thread adding the blocks in an intake queue to the enclave, and removing
+/// them from the queue once they were added.
+struct IntakeThread {
+    join_handle: JoinHandle<()>,
+    /// Set when no more blocks will be pushed, so the thread stops once the
+    /// queue is empty.
+    finish_requested: Arc<AtomicBool>,
+}
+
+impl IntakeThread {
+    const POLL_INTERVAL: Duration = Duration::from_millis(100);
+    const ERROR_RETRY_FREQUENCY: Duration = Duration::from_millis(1000);
+
+    fn start<E: LedgerEnclaveProxy + Clone + Send + Sync + 'static>(
+        intake_queue: Arc<IntakeQueue>,
+        enclave: E,
+        db_poll_shared_state: Arc<Mutex<DbPollSharedState>>,
+        block_range: BlockRange,
+        key_image_export: Option<KeyImageExport>,
+        stop_requested: Arc<AtomicBool>,
+        logger: Logger,
+    ) -> Self {
+        let finish_requested = Arc::new(AtomicBool::new(false));
+        let thread_finish_requested = finish_requested.clone();
+        let join_handle = ThreadBuilder::new()
+            .name("LedgerIntake".to_owned())
+            .spawn(move || {
+                log::info!(logger, "Intake thread started.");
+                while !stop_requested.load(Ordering::SeqCst) {
+                    let blocks = match intake_queue
+                        .wait_for_blocks(INTAKE_BATCH_BLOCKS, Self::POLL_INTERVAL)
+                    {
+                        Ok(blocks) => blocks,
+                        Err(err) => {
+                            log::error!(logger, "Could not read the intake queue: {}", err);
+                            std::thread::sleep(Self::ERROR_RETRY_FREQUENCY);
+                            continue;
+                        }
+                    };
+                    let (Some(&(first_block_index, _)), Some(&(last_block_index, _))) =
+                        (blocks.first(), blocks.last())
+                    else {
+                        if thread_finish_requested.load(Ordering::SeqCst) {
+                            break;
+                        }
+                        continue;
+                    };
+                    let added_blocks = BlockRange::new(first_block_index, last_block_index + 1);
+
+                    if let Some(key_image_export) = key_image_export.as_ref() {
+                        for (block_index, records) in blocks.iter() {
+                            if let Err(err) = key_image_export.write_block(*block_index, records) {
+                                log::error!(
+                                    logger,
+                                    "Could not add block {} to the key image export: {}",
+                                    block_index,
+                                    err
+                                );
+                            }
+                        }
+                    }
+
+                    let records = blocks
+                        .into_iter()
+                        .flat_map(|(_, records)| records)
+                        .collect();
+                    add_records_to_enclave(
+                        &enclave,
+                        &db_poll_shared_state,
+                        &added_blocks,
+                        records,
+                        &logger,
                     );
-                    OperationResult::Retry(err)
+                    db_poll_shared_state
+                        .lock()
+                        .expect("mutex poisoned")
+                        .processed_block_range =
+                        BlockRange::new(block_range.start_block, added_blocks.end_block);
+
+                    if let Err(err) = intake_queue.pop(added_blocks.len()) {
+                        log::error!(
+                            logger,
+                            "Could not remove blocks {} from the intake queue: {}",
+                            added_blocks,
+                            err
+                        );
+                    }
                 }
-            }
-        });
+                log::info!(logger, "Intake thread stopped.");
+            })
+            .expect("Could not spawn thread");
 
-        log::info!(
-            self.logger,
-            "Added {} keyimage outs for block with index {} into the enclave",
-            num_records,
-            block_index
-        );
+        Self {
+            join_handle,
+            finish_requested,
+        }
+    }
+
+    /// Wait for the thread to add the blocks left in the queue to the enclave,
+    /// unless it is stopped first.
+    fn finish(self) {
+        self.finish_requested.store(true, Ordering::SeqCst);
+        let _ = self.join_handle.join();
     }
 }
@@ -0,0 +1,401 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! A bounded, persistent queue of the key image data of the blocks which a key
+//! image store has fetched from its block provider, but not yet added to its
+//! enclave.
+//!
+//! Without an intake queue, the thread polling the block provider adds each
+//! block to the enclave itself, so while adding to the enclave stalls, e.g.
+//! because the ECALL fails and is retried, no further blocks are fetched and
+//! no watcher timestamps are waited for. With one, the db fetcher fetches
+//! blocks into the queue until it is full, and a separate thread adds them
+//! from the queue to the enclave in batches. The depth of the queue and the
+//! next block to add are exported as metrics.
+//!
+//! The queue is kept in LMDB, so when a store which also keeps a key image
+//! export restarts, the blocks which were queued but not yet added to its
+//! enclave follow on from the blocks in the export, and are added from the
+//! queue instead of being fetched again.
+
+use crate::metrics;
+use displaydoc::Display;
+use lmdb::{Cursor, Database, DatabaseFlags, Environment, Transaction, WriteFlags};
+use mc_fog_ledger_enclave_api::KeyImageData;
+use mc_fog_types::common::BlockRange;
+use prometheus::IntGauge;
+use std::{
+    path::Path,
+    sync::{Condvar, Mutex},
+    time::Duration,
+};
+
+// LMDB Constants
+const MAX_LMDB_FILE_SIZE: usize = 1 << 40; // 1 TB
+
+// LMDB Database Names
+const BLOCKS_DB_NAME: &str = "intake_queue:blocks";
+const METADATA_DB_NAME: &str = "intake_queue:metadata";
+
+// Metadata keys
+const CHAIN_ID_KEY: &str = "chain_id";
+
+/// An error accessing an intake queue
+#[derive(Debug, Display)]
+pub enum IntakeQueueError {
+    /// LMDB: {0}
+    Lmdb(lmdb::Error),
+    /// IO: {0}
+    Io(std::io::Error),
+    /// Serialization: {0}
+    Serialization(mc_util_serial::encode::Error),
+    /// Deserialization: {0}
+    Deserialization(mc_util_serial::decode::Error),
+    /// The queue is for chain id {0}, not {1}
+    ChainIdMismatch(String, String),
+    /// The queue is full
+    Full,
+    /// Block {0} was pushed, but the next block in the queue is {1}
+    UnexpectedBlock(u64, u64),
+}
+
+impl From<lmdb::Error> for IntakeQueueError {
+    fn from(err: lmdb::Error) -> Self {
+        Self::Lmdb(err)
+    }
+}
+
+impl From<std::io::Error> for IntakeQueueError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<mc_util_serial::encode::Error> for IntakeQueueError {
+    fn from(err: mc_util_serial::encode::Error) -> Self {
+        Self::Serialization(err)
+    }
+}
+
+impl From<mc_util_serial::decode::Error> for IntakeQueueError {
+    fn from(err: mc_util_serial::decode::Error) -> Self {
+        Self::Deserialization(err)
+    }
+}
+
+/// The key image data of consecutive blocks of one epoch, waiting to be added
+/// to the enclave.
+pub struct IntakeQueue {
+    env: Environment,
+    blocks: Database,
+    /// The most blocks kept in the queue at once.
+    capacity: u64,
+    /// The blocks in the queue.
+    queued: Mutex<BlockRange>,
+    /// Notified when a block is pushed.
+    pushed: Condvar,
+    depth_gauge: IntGauge,
+    next_block_gauge: IntGauge,
+}
+
+impl IntakeQueue {
+    /// Open the intake queue of an epoch in the given directory, creating it
+    /// if there is none.
+    ///
+    /// # Arguments
+    /// * `dir` - The directory keeping the intake queues of a store process
+    /// * `chain_id` - The chain id of the network the store serves
+    /// * `block_range` - The epoch's block range
+    /// * `capacity` - The most blocks to keep in the queue at once
+    pub fn open(
+        dir: impl AsRef<Path>,
+        chain_id: &str,
+        block_range: &BlockRange,
+        capacity: u64,
+    ) -> Result<Self, IntakeQueueError> {
+        let path = dir.as_ref().join(format!(
+            "intake-{}-{}",
+            block_range.start_block, block_range.end_block
+        ));
+        std::fs::create_dir_all(&path)?;
+
+        let env = Environment::new()
+            .set_max_dbs(2)
+            .set_map_size(MAX_LMDB_FILE_SIZE)
+            .open(&path)?;
+        let blocks = env.create_db(Some(BLOCKS_DB_NAME), DatabaseFlags::empty())?;
+        let metadata = env.create_db(Some(METADATA_DB_NAME), DatabaseFlags::empty())?;
+
+        let mut db_txn = env.begin_rw_txn()?;
+        match db_txn.get(metadata, &CHAIN_ID_KEY) {
+            Ok(bytes) => {
+                let queue_chain_id = String::from_utf8_lossy(bytes);
+                if queue_chain_id != chain_id {
+                    return Err(IntakeQueueError::ChainIdMismatch(
+                        queue_chain_id.into_owned(),
+                        chain_id.to_owned(),
+                    ));
+                }
+            }
+            Err(lmdb::Error::NotFound) => {
+                db_txn.put(metadata, &CHAIN_ID_KEY, &chain_id, WriteFlags::empty())?;
+            }
+            Err(err) => return Err(err.into()),
+        }
+
+        // The queue is the consecutive blocks starting at the first one
+        // found, which are all there is unless it was last written by a
+        // process which crashed while removing blocks.
+        let block_indices = {
+            let mut cursor = db_txn.open_ro_cursor(blocks)?;
+            cursor
+                .iter_start()
+                .filter_map(Result::ok)
+                .filter_map(|(key_bytes, _)| key_to_block_index(key_bytes))
+                .collect::<Vec<_>>()
+        };
+        let start_block = block_indices
+            .first()
+            .copied()
+            .unwrap_or(block_range.start_block);
+        let mut queued = BlockRange::new(start_block, start_block);
+        for block_index in block_indices {
+            if block_index == queued.end_block {
+                queued.end_block += 1;
+            } else {
+                db_txn.del(blocks, &block_index.to_be_bytes(), None)?;
+            }
+        }
+        db_txn.commit()?;
+
+        let epoch = format!("{}-{}", block_range.start_block, block_range.end_block);
+        let queue = Self {
+            env,
+            blocks,
+            capacity,
+            queued: Mutex::new(queued.clone()),
+            pushed: Condvar::new(),
+            depth_gauge: metrics::INTAKE_QUEUE_DEPTH.with_label_values(&[&epoch]),
+            next_block_gauge: metrics::INTAKE_QUEUE_NEXT_BLOCK.with_label_values(&[&epoch]),
+        };
+        queue.update_gauges(&queued);
+        Ok(queue)
+    }
+
+    /// The blocks in the queue. The first of them is the next block to add to
+    /// the enclave.
+    pub fn queued_blocks(&self) -> BlockRange {
+        self.queued.lock().expect("mutex poisoned").clone()
+    }
+
+    /// Whether no more blocks can be pushed until some are popped.
+    pub fn is_full(&self) -> bool {
+        self.queued.lock().expect("mutex poisoned").len() >= self.capacity
+    }
+
+    /// Start adding blocks to an empty enclave, at `next_block`.
+    ///
+    /// The blocks queued before `next_block` are removed. If `next_block`
+    /// isn't queued, there would be a gap between the blocks already added
+    /// and the queued ones, so the queue is emptied.
+    ///
+    /// Returns the index of the first block which still has to be pushed.
+    pub fn resume(&self, next_block: u64) -> Result<u64, IntakeQueueError> {
+        let mut queued = self.queued.lock().expect("mutex poisoned");
+        let removed = if queued.contains(next_block) {
+            let removed = BlockRange::new(queued.start_block, next_block);
+            queued.start_block = next_block;
+            removed
+        } else {
+            let removed = queued.clone();
+            *queued = BlockRange::new(next_block, next_block);
+            removed
+        };
+        self.update_gauges(&queued);
+        let end_block = queued.end_block;
+        drop(queued);
+
+        self.delete(&removed)?;
+        Ok(end_block)
+    }
+
+    /// Add the key image data of the block after the last queued one.
+    pub fn push(&self, block_index: u64, records: &[KeyImageData]) -> Result<(), IntakeQueueError> {
+        let mut queued = self.queued.lock().expect("mutex poisoned");
+        if block_index != queued.end_block {
+            return Err(IntakeQueueError::UnexpectedBlock(
+                block_index,
+                queued.end_block,
+            ));
+        }
+        if queued.len() >= self.capacity {
+            return Err(IntakeQueueError::Full);
+        }
+
+        let value = mc_util_serial::serialize(records)?;
+        let mut db_txn = self.env.begin_rw_txn()?;
+        db_txn.put(
+            self.blocks,
+            &block_index.to_be_bytes(),
+            &value,
+            WriteFlags::empty(),
+        )?;
+        db_txn.commit()?;
+
+        queued.end_block += 1;
+        self.update_gauges(&queued);
+        self.pushed.notify_all();
+        Ok(())
+    }
+
+    /// Read the key image data of up to `max_blocks` blocks from the front of
+    /// the queue, waiting up to `timeout` for a block to be pushed if it is
+    /// empty.
+    ///
+    /// The blocks stay in the queue until they are popped.
+    pub fn wait_for_blocks(
+        &self,
+        max_blocks: usize,
+        timeout: Duration,
+    ) -> Result<Vec<(u64, Vec<KeyImageData>)>, IntakeQueueError> {
+        let queued = self.queued.lock().expect("mutex poisoned");
+        let (queued, _) = self
+            .pushed
+            .wait_timeout_while(queued, timeout, |queued| queued.is_empty())
+            .expect("mutex poisoned");
+        let start_block = queued.start_block;
+        let num_blocks = queued.len().min(max_blocks as u64);
+        drop(queued);
+
+        let db_txn = self.env.begin_ro_txn()?;
+        let blocks = (start_block..start_block + num_blocks)
+            .map(|block_index| -> Result<_, IntakeQueueError> {
+                let value = db_txn.get(self.blocks, &block_index.to_be_bytes())?;
+                Ok((block_index, mc_util_serial::deserialize(value)?))
+            })
+            .collect();
+        blocks
+    }
+
+    /// Remove the first `num_blocks` blocks, once they were added to the
+    /// enclave.
+    pub fn pop(&self, num_blocks: u64) -> Result<(), IntakeQueueError> {
+        let mut queued = self.queued.lock().expect("mutex poisoned");
+        let removed = BlockRange::new_from_length(queued.start_block, num_blocks.min(queued.len()));
+        queued.start_block = removed.end_block;
+        self.update_gauges(&queued);
+        drop(queued);
+
+        self.delete(&removed)
+    }
+
+    fn delete(&self, blocks: &BlockRange) -> Result<(), IntakeQueueError> {
+        if blocks.is_empty() {
+            return Ok(());
+        }
+        let mut db_txn = self.env.begin_rw_txn()?;
+        for block_index in blocks.start_block..blocks.end_block {
+            match db_txn.del(self.blocks, &block_index.to_be_bytes(), None) {
+                Ok(()) | Err(lmdb::Error::NotFound) => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        db_txn.commit()?;
+        Ok(())
+    }
+
+    fn update_gauges(&self, queued: &BlockRange) {
+        self.depth_gauge.set(queued.len() as i64);
+        self.next_block_gauge.set(queued.start_block as i64);
+    }
+}
+
+fn key_to_block_index(key_bytes: &[u8]) -> Option<u64> {
+    key_bytes.try_into().ok().map(u64::from_be_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mc_transaction_core::ring_signature::KeyImage;
+
+    fn records(block_index: u64) -> Vec<KeyImageData> {
+        (0..3)
+            .map(|i| KeyImageData {
+                key_image: KeyImage::from(block_index * 10 + i),
+                block_index,
+                timestamp: block_index * 1000,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn blocks_are_queued_in_order_up_to_capacity() {
+        let dir = tempfile::tempdir().unwrap();
+        let queue = IntakeQueue::open(dir.path(), "local", &BlockRange::new(10, 20), 3).unwrap();
+        assert_eq!(queue.queued_blocks(), BlockRange::new(10, 10));
+        assert_eq!(
+            queue.wait_for_blocks(10, Duration::from_millis(1)).unwrap(),
+            vec![]
+        );
+
+        assert!(matches!(
+            queue.push(11, &records(11)),
+            Err(IntakeQueueError::UnexpectedBlock(11, 10))
+        ));
+        for block_index in 10..13 {
+            queue.push(block_index, &records(block_index)).unwrap();
+        }
+        assert!(queue.is_full());
+        assert!(matches!(
+            queue.push(13, &records(13)),
+            Err(IntakeQueueError::Full)
+        ));
+
+        assert_eq!(
+            queue.wait_for_blocks(2, Duration::ZERO).unwrap(),
+            vec![(10, records(10)), (11, records(11))]
+        );
+        queue.pop(2).unwrap();
+        assert!(!queue.is_full());
+        queue.push(13, &records(13)).unwrap();
+        assert_eq!(queue.queued_blocks(), BlockRange::new(12, 14));
+        assert_eq!(
+            queue.wait_for_blocks(10, Duration::ZERO).unwrap(),
+            vec![(12, records(12)), (13, records(13))]
+        );
+    }
+
+    #[test]
+    fn queued_blocks_are_resumed_after_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let queue = IntakeQueue::open(dir.path(), "local", &BlockRange::new(0, 100), 10).unwrap();
+        for block_index in 0..5 {
+            queue.push(block_index, &records(block_index)).unwrap();
+        }
+        queue.pop(2).unwrap();
+        drop(queue);
+
+        let queue = IntakeQueue::open(dir.path(), "local", &BlockRange::new(0, 100), 10).unwrap();
+        assert_eq!(queue.queued_blocks(), BlockRange::new(2, 5));
+        // Blocks 0 to 2 were already added to the enclave, e.g. from an export.
+        assert_eq!(queue.resume(3).unwrap(), 5);
+        assert_eq!(
+            queue.wait_for_blocks(10, Duration::ZERO).unwrap(),
+            vec![(3, records(3)), (4, records(4))]
+        );
+        drop(queue);
+
+        // Without an export, the store starts again at the start of the epoch,
+        // which the queue doesn't reach.
+        let queue = IntakeQueue::open(dir.path(), "local", &BlockRange::new(0, 100), 10).unwrap();
+        assert_eq!(queue.resume(0).unwrap(), 0);
+        assert_eq!(queue.queued_blocks(), BlockRange::new(0, 0));
+        queue.push(0, &records(0)).unwrap();
+        drop(queue);
+
+        assert!(matches!(
+            IntakeQueue::open(dir.path(), "main", &BlockRange::new(0, 100), 10),
+            Err(IntakeQueueError::ChainIdMismatch(_, _))
+        ));
+    }
+}
@@ -0,0 +1,243 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! A local export of the key image data that a key image store has added to
+//! its enclave, from which a new replica of the same epoch can be bootstrapped.
+//!
+//! Reading every block of an epoch back from the ledger, and waiting for the
+//! watcher's timestamp of each, takes hours for a large epoch. A store with an
+//! export directory records the key image data of each block it adds to its
+//! enclave, and at startup adds the blocks already recorded there to its
+//! enclave in large batches, before it resumes polling the ledger. To bring up
+//! a replica, copy the export of an existing store of the same epoch (e.g.
+//! with `mdb_copy`, which works while the store is running) and start the
+//! replica with it.
+//!
+//! The export is not sealed. SGX sealing keys are specific to a CPU, so data
+//! sealed by one store could not be unsealed by a replica on another machine,
+//! and key images are public ledger data which the enclave already accepts
+//! from the untrusted side. Instead, the key images of every block read from
+//! the export are checked against the block's contents in the ledger before
+//! they are added to the enclave, which is much faster than loading the
+//! blocks one at a time since it doesn't wait on the watcher. Each epoch's
+//! export is also kept in its own subdirectory named after its block range,
+//! and is tagged with the chain id, so that exports of other epochs or
+//! networks are never imported.
+
+use displaydoc::Display;
+use lmdb::{Cursor, Database, DatabaseFlags, Environment, Transaction, WriteFlags};
+use mc_fog_ledger_enclave_api::KeyImageData;
+use mc_fog_types::common::BlockRange;
+use std::path::Path;
+
+// LMDB Constants
+const MAX_LMDB_FILE_SIZE: usize = 1 << 40; // 1 TB
+
+// LMDB Database Names
+const BLOCKS_DB_NAME: &str = "key_image_export:blocks";
+const METADATA_DB_NAME: &str = "key_image_export:metadata";
+
+// Metadata keys
+const CHAIN_ID_KEY: &str = "chain_id";
+
+/// An error accessing a key image export
+#[derive(Debug, Display)]
+pub enum KeyImageExportError {
+    /// LMDB: {0}
+    Lmdb(lmdb::Error),
+    /// IO: {0}
+    Io(std::io::Error),
+    /// Serialization: {0}
+    Serialization(mc_util_serial::encode::Error),
+    /// Deserialization: {0}
+    Deserialization(mc_util_serial::decode::Error),
+    /// The export is for chain id {0}, not {1}
+    ChainIdMismatch(String, String),
+}
+
+impl From<lmdb::Error> for KeyImageExportError {
+    fn from(err: lmdb::Error) -> Self {
+        Self::Lmdb(err)
+    }
+}
+
+impl From<std::io::Error> for KeyImageExportError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<mc_util_serial::encode::Error> for KeyImageExportError {
+    fn from(err: mc_util_serial::encode::Error) -> Self {
+        Self::Serialization(err)
+    }
+}
+
+impl From<mc_util_serial::decode::Error> for KeyImageExportError {
+    fn from(err: mc_util_serial::decode::Error) -> Self {
+        Self::Deserialization(err)
+    }
+}
+
+/// The key image data of the blocks of one epoch, by block index.
+pub struct KeyImageExport {
+    env: Environment,
+    blocks: Database,
+    block_range: BlockRange,
+}
+
+impl KeyImageExport {
+    /// Open the export of an epoch in the given directory, creating it if
+    /// there is none.
+    ///
+    /// # Arguments
+    /// * `dir` - The directory keeping the exports of a store process
+    /// * `chain_id` - The chain id of the network the store serves
+    /// * `block_range` - The epoch's block range
+    pub fn open(
+        dir: impl AsRef<Path>,
+        chain_id: &str,
+        block_range: &BlockRange,
+    ) -> Result<Self, KeyImageExportError> {
+        let path = dir.as_ref().join(format!(
+            "blocks-{}-{}",
+            block_range.start_block, block_range.end_block
+        ));
+        std::fs::create_dir_all(&path)?;
+
+        let env = Environment::new()
+            .set_max_dbs(2)
+            .set_map_size(MAX_LMDB_FILE_SIZE)
+            .open(&path)?;
+        let blocks = env.create_db(Some(BLOCKS_DB_NAME), DatabaseFlags::empty())?;
+        let metadata = env.create_db(Some(METADATA_DB_NAME), DatabaseFlags::empty())?;
+
+        let mut db_txn = env.begin_rw_txn()?;
+        match db_txn.get(metadata, &CHAIN_ID_KEY) {
+            Ok(bytes) => {
+                let export_chain_id = String::from_utf8_lossy(bytes);
+                if export_chain_id != chain_id {
+                    return Err(KeyImageExportError::ChainIdMismatch(
+                        export_chain_id.into_owned(),
+                        chain_id.to_owned(),
+                    ));
+                }
+            }
+            Err(lmdb::Error::NotFound) => {
+                db_txn.put(metadata, &CHAIN_ID_KEY, &chain_id, WriteFlags::empty())?;
+            }
+            Err(err) => return Err(err.into()),
+        }
+        db_txn.commit()?;
+
+        Ok(Self {
+            env,
+            blocks,
+            block_range: block_range.clone(),
+        })
+    }
+
+    /// Record the key image data of a block.
+    pub fn write_block(
+        &self,
+        block_index: u64,
+        records: &[KeyImageData],
+    ) -> Result<(), KeyImageExportError> {
+        let value = mc_util_serial::serialize(records)?;
+        let mut db_txn = self.env.begin_rw_txn()?;
+        db_txn.put(
+            self.blocks,
+            &block_index.to_be_bytes(),
+            &value,
+            WriteFlags::empty(),
+        )?;
+        db_txn.commit()?;
+        Ok(())
+    }
+
+    /// Read the key image data of up to `max_blocks` consecutive blocks of the
+    /// epoch, starting at `start_block`.
+    ///
+    /// Reading stops at the first block which was not recorded, so the
+    /// blocks returned can be added to an enclave as they are, and the rest
+    /// loaded from the ledger.
+    pub fn read_blocks(
+        &self,
+        start_block: u64,
+        max_blocks: usize,
+    ) -> Result<Vec<(u64, Vec<KeyImageData>)>, KeyImageExportError> {
+        let db_txn = self.env.begin_ro_txn()?;
+        let mut cursor = db_txn.open_ro_cursor(self.blocks)?;
+        let mut blocks = Vec::new();
+        let mut next_block = start_block;
+        for item in cursor.iter_from(start_block.to_be_bytes()) {
+            let (key_bytes, value_bytes) = item?;
+            if blocks.len() >= max_blocks
+                || !self.block_range.contains(next_block)
+                || key_bytes != next_block.to_be_bytes()
+            {
+                break;
+            }
+            blocks.push((next_block, mc_util_serial::deserialize(value_bytes)?));
+            next_block += 1;
+        }
+        Ok(blocks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mc_transaction_core::ring_signature::KeyImage;
+
+    fn records(block_index: u64) -> Vec<KeyImageData> {
+        (0..3)
+            .map(|i| KeyImageData {
+                key_image: KeyImage::from(block_index * 10 + i),
+                block_index,
+                timestamp: block_index * 1000,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reads_consecutive_blocks_in_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let export = KeyImageExport::open(dir.path(), "local", &BlockRange::new(10, 20)).unwrap();
+        for block_index in [10, 11, 12, 14, 20] {
+            export
+                .write_block(block_index, &records(block_index))
+                .unwrap();
+        }
+
+        assert_eq!(
+            export.read_blocks(10, 100).unwrap(),
+            vec![(10, records(10)), (11, records(11)), (12, records(12))]
+        );
+        assert_eq!(export.read_blocks(11, 1).unwrap(), vec![(11, records(11))]);
+        assert_eq!(export.read_blocks(13, 100).unwrap(), vec![]);
+        // Block 20 is past the end of the epoch.
+        assert_eq!(export.read_blocks(20, 100).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn exports_are_kept_per_epoch_and_chain() {
+        let dir = tempfile::tempdir().unwrap();
+        KeyImageExport::open(dir.path(), "local", &BlockRange::new(0, 10))
+            .unwrap()
+            .write_block(0, &records(0))
+            .unwrap();
+
+        let other_epoch =
+            KeyImageExport::open(dir.path(), "local", &BlockRange::new(0, 20)).unwrap();
+        assert_eq!(other_epoch.read_blocks(0, 100).unwrap(), vec![]);
+
+        let reopened = KeyImageExport::open(dir.path(), "local", &BlockRange::new(0, 10)).unwrap();
+        assert_eq!(reopened.read_blocks(0, 100).unwrap(), vec![(0, records(0))]);
+        drop(reopened);
+
+        assert!(matches!(
+            KeyImageExport::open(dir.path(), "main", &BlockRange::new(0, 10)),
+            Err(KeyImageExportError::ChainIdMismatch(_, _))
+        ));
+    }
+}
@@ -0,0 +1,286 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Groups router queries arriving concurrently at a key image store, so that
+//! they are checked with a single ECALL instead of one ECALL per query.
+
+use crate::metrics::STORE_QUERY_BATCH_SIZE;
+use mc_attest_enclave_api::{EnclaveMessage, NonceSession};
+use mc_fog_ledger_enclave::LedgerEnclaveProxy;
+use mc_fog_ledger_enclave_api::{Error as EnclaveError, UntrustedKeyImageQueryResponse};
+use std::{
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{
+        mpsc::{channel, Sender},
+        Mutex, PoisonError,
+    },
+};
+
+/// The default upper bound on the number of queries checked in one ECALL.
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 64;
+
+/// The results for one caller's queries, in the order the queries were given.
+pub type QueryResults =
+    Result<Vec<Result<EnclaveMessage<NonceSession>, EnclaveError>>, EnclaveError>;
+
+/// What a waiting caller is told by the batcher.
+enum BatcherMessage {
+    /// The results for the caller's queries.
+    Results(QueryResults),
+    /// The caller's queries are next in line, and it should make the next
+    /// ECALL itself.
+    Drain,
+}
+
+/// Queries from one caller waiting to be put into a batch.
+struct PendingQueries {
+    msgs: Vec<EnclaveMessage<NonceSession>>,
+    result_sender: Sender<BatcherMessage>,
+}
+
+#[derive(Default)]
+struct BatcherState {
+    pending: Vec<PendingQueries>,
+    /// Whether some caller is currently making ECALLs on behalf of the others.
+    draining: bool,
+}
+
+/// Batches router queries into calls to `check_key_image_store_batch`.
+///
+/// There is no background thread. The first caller to find the batcher idle
+/// makes one ECALL for a batch of pending queries, which always includes its
+/// own, and hands each caller in the batch its results. If queries are still
+/// pending afterwards, the caller at the front of the queue is told to make
+/// the next ECALL, so no caller makes more than one ECALL and none is held up
+/// serving other callers' queries indefinitely. Callers arriving in the
+/// meantime just queue their queries and wait, so under load each ECALL
+/// carries the queries that arrived while the previous one was running.
+pub struct KeyImageQueryBatcher<E: LedgerEnclaveProxy> {
+    enclave: E,
+    max_batch_size: usize,
+    state: Mutex<BatcherState>,
+}
+
+impl<E: LedgerEnclaveProxy> KeyImageQueryBatcher<E> {
+    /// Create a new batcher which makes ECALLs of at most `max_batch_size`
+    /// queries, unless a single caller submits more than that at once.
+    pub fn new(enclave: E, max_batch_size: usize) -> Self {
+        Self {
+            enclave,
+            max_batch_size: max_batch_size.max(1),
+            state: Default::default(),
+        }
+    }
+
+    /// Check a caller's queries, possibly batched with other callers'.
+    ///
+    /// # Arguments
+    /// * `msgs` - The queries to check
+    /// * `untrusted_query` - Produces the untrusted data for a batch. It is
+    ///   called when the batch is made, which may be by another caller.
+    pub fn check_key_image_store(
+        &self,
+        msgs: Vec<EnclaveMessage<NonceSession>>,
+        untrusted_query: impl Fn() -> UntrustedKeyImageQueryResponse,
+    ) -> QueryResults {
+        let (result_sender, result_receiver) = channel();
+        let mut should_drain = {
+            let mut state = self.state.lock().expect("mutex poisoned");
+            state.pending.push(PendingQueries {
+                msgs,
+                result_sender,
+            });
+            !std::mem::replace(&mut state.draining, true)
+        };
+
+        loop {
+            if should_drain {
+                self.drain_one_batch(&untrusted_query);
+            }
+            // Every batch is answered, even if making it panicked, so the
+            // sender is only dropped without an answer if something went
+            // badly wrong on another thread.
+            match result_receiver.recv() {
+                Ok(BatcherMessage::Results(results)) => return results,
+                Ok(BatcherMessage::Drain) => should_drain = true,
+                Err(_) => return Err(EnclaveError::Poison),
+            }
+        }
+    }
+
+    /// Make one ECALL for the queries at the front of the queue, then hand
+    /// draining off to the next waiting caller, if any.
+    fn drain_one_batch(&self, untrusted_query: impl Fn() -> UntrustedKeyImageQueryResponse) {
+        // Hands off even if the ECALL panics, so the batcher never gets stuck
+        // with `draining` set and nobody draining.
+        let _guard = DrainGuard { state: &self.state };
+        let batch = {
+            let mut state = self.state.lock().expect("mutex poisoned");
+            take_batch(&mut state.pending, self.max_batch_size)
+        };
+        self.run_batch(batch, untrusted_query);
+    }
+
+    fn run_batch(
+        &self,
+        batch: Vec<PendingQueries>,
+        untrusted_query: impl Fn() -> UntrustedKeyImageQueryResponse,
+    ) {
+        let mut senders = Vec::with_capacity(batch.len());
+        let mut msgs = Vec::new();
+        for pending in batch {
+            senders.push((pending.result_sender, pending.msgs.len()));
+            msgs.extend(pending.msgs);
+        }
+        STORE_QUERY_BATCH_SIZE.observe(msgs.len() as f64);
+
+        // If preparing the untrusted data or the ECALL panics, every caller in
+        // the batch gets an error, instead of waiting on a sender which was
+        // dropped during unwinding.
+        let results = catch_unwind(AssertUnwindSafe(|| {
+            self.enclave
+                .check_key_image_store_batch(msgs, untrusted_query())
+        }))
+        .unwrap_or(Err(EnclaveError::Poison));
+
+        match results {
+            Ok(results) => {
+                let mut results = results.into_iter();
+                for (sender, count) in senders {
+                    // The caller may have gone away, which is not our problem.
+                    let _ = sender.send(BatcherMessage::Results(Ok(results
+                        .by_ref()
+                        .take(count)
+                        .collect())));
+                }
+            }
+            Err(err) => {
+                for (sender, _) in senders {
+                    let _ = sender.send(BatcherMessage::Results(Err(err.clone())));
+                }
+            }
+        }
+    }
+}
+
+/// Passes the job of draining the batcher on when dropped: to the first
+/// pending caller still waiting, or back to idle if there is none.
+struct DrainGuard<'a> {
+    state: &'a Mutex<BatcherState>,
+}
+
+impl Drop for DrainGuard<'_> {
+    fn drop(&mut self) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        while let Some(next) = state.pending.first() {
+            if next.result_sender.send(BatcherMessage::Drain).is_ok() {
+                return;
+            }
+            // That caller is gone, so nobody is waiting for its results.
+            state.pending.remove(0);
+        }
+        state.draining = false;
+    }
+}
+
+/// Take pending callers off the front of the queue until the batch would
+/// exceed `max_batch_size` queries. At least one caller is always taken.
+fn take_batch(pending: &mut Vec<PendingQueries>, max_batch_size: usize) -> Vec<PendingQueries> {
+    let mut num_queries = 0;
+    let mut num_callers = 0;
+    for queries in pending.iter() {
+        if num_callers > 0 && num_queries + queries.msgs.len() > max_batch_size {
+            break;
+        }
+        num_queries += queries.msgs.len();
+        num_callers += 1;
+    }
+    pending.drain(..num_callers).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mc_fog_ledger_test_infra::MockEnclave;
+
+    fn pending(num_msgs: usize) -> PendingQueries {
+        let (result_sender, _) = channel();
+        PendingQueries {
+            msgs: (0..num_msgs)
+                .map(|i| EnclaveMessage {
+                    aad: vec![],
+                    channel_id: Default::default(),
+                    data: vec![i as u8],
+                })
+                .collect(),
+            result_sender,
+        }
+    }
+
+    #[test]
+    fn take_batch_respects_max_batch_size() {
+        let mut queue = vec![pending(3), pending(2), pending(4)];
+
+        let batch = take_batch(&mut queue, 5);
+        assert_eq!(batch.len(), 2);
+        assert_eq!(queue.len(), 1);
+
+        // A caller with more queries than the limit still gets a batch.
+        let batch = take_batch(&mut queue, 2);
+        assert_eq!(batch.len(), 1);
+        assert_eq!(batch[0].msgs.len(), 4);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn callers_get_an_error_when_the_ecall_panics() {
+        // The mock enclave panics when asked to check a query.
+        let batcher = KeyImageQueryBatcher::new(MockEnclave::default(), DEFAULT_MAX_BATCH_SIZE);
+        let result = batcher.check_key_image_store(pending(1).msgs, Default::default);
+        assert!(matches!(result, Err(EnclaveError::Poison)));
+
+        // The batcher is idle again, so the next caller can drain it.
+        assert!(!batcher.state.lock().unwrap().draining);
+    }
+
+    #[test]
+    fn drain_guard_hands_off_to_first_waiting_caller() {
+        let (gone_sender, gone_receiver) = channel();
+        drop(gone_receiver);
+        let (waiting_sender, waiting_receiver) = channel();
+        let state = Mutex::new(BatcherState {
+            pending: vec![
+                PendingQueries {
+                    msgs: vec![],
+                    result_sender: gone_sender,
+                },
+                PendingQueries {
+                    msgs: vec![],
+                    result_sender: waiting_sender,
+                },
+            ],
+            draining: true,
+        });
+
+        drop(DrainGuard { state: &state });
+
+        assert!(matches!(
+            waiting_receiver.try_recv(),
+            Ok(BatcherMessage::Drain)
+        ));
+        let state = state.lock().unwrap();
+        assert!(state.draining);
+        assert_eq!(state.pending.len(), 1);
+    }
+
+    #[test]
+    fn drain_guard_goes_idle_when_nothing_is_pending() {
+        let state = Mutex::new(BatcherState {
+            pending: vec![],
+            draining: true,
+        });
+
+        drop(DrainGuard { state: &state });
+
+        assert!(!state.lock().unwrap().draining);
+    }
+}
@@ -1,5 +1,9 @@
 // Copyright (c) 2018-2022 The MobileCoin Foundation
-use crate::{metrics::STORE_QUERY_REQUESTS, DbPollSharedState, SVC_COUNTERS};
+use crate::{
+    key_image_query_batcher::{KeyImageQueryBatcher, DEFAULT_MAX_BATCH_SIZE},
+    metrics::STORE_QUERY_REQUESTS,
+    DbPollSharedState, SVC_COUNTERS,
+};
 use grpcio::RpcStatus;
 use mc_attest_api::{attest, attest::AuthMessage};
 use mc_blockchain_types::MAX_BLOCK_VERSION;
@@ -13,7 +17,8 @@ use mc_fog_api::{
 use mc_fog_ledger_enclave::LedgerEnclaveProxy;
 use mc_fog_ledger_enclave_api::{Error as EnclaveError, UntrustedKeyImageQueryResponse};
 use mc_fog_uri::{ConnectionUri, KeyImageStoreUri};
-use mc_util_grpc::{rpc_logger, rpc_permissions_error, send_result, Authenticator};
+use mc_util_grpc::{rpc_logger, rpc_permissions_error, send_result, InterceptorChain};
+use mc_util_metrics::service_metrics;
 use std::{
     sync::{Arc, Mutex},
     time::Instant,
@@ -24,7 +29,9 @@ pub struct KeyImageService<E: LedgerEnclaveProxy> {
     /// The ClientListenUri for this Fog Ledger Service.
     client_listen_uri: KeyImageStoreUri,
     enclave: E,
-    authenticator: Arc<dyn Authenticator + Send + Sync>,
+    /// Groups concurrent router queries into batched ECALLs.
+    batcher: Arc<KeyImageQueryBatcher<E>>,
+    interceptors: InterceptorChain,
     logger: Logger,
     /// Shared state from db polling thread.
     db_poll_shared_state: Arc<Mutex<DbPollSharedState>>,
@@ -35,13 +42,17 @@ impl<E: LedgerEnclaveProxy> KeyImageService<E> {
         client_listen_uri: KeyImageStoreUri,
         enclave: E,
         db_poll_shared_state: Arc<Mutex<DbPollSharedState>>,
-        authenticator: Arc<dyn Authenticator + Send + Sync>,
+        interceptors: InterceptorChain,
         logger: Logger,
     ) -> Self {
         Self {
             client_listen_uri,
+            batcher: Arc::new(KeyImageQueryBatcher::new(
+                enclave.clone(),
+                DEFAULT_MAX_BATCH_SIZE,
+            )),
             enclave,
-            authenticator,
+            interceptors,
             logger,
             db_poll_shared_state,
         }
@@ -81,15 +92,20 @@ impl<E: LedgerEnclaveProxy> KeyImageService<E> {
     }
 
     /// Generate an UntrustedKeyImageQueryResponse
-    /// for use in [KeyImageService::check_key_images_auth()]
-    /// and [KeyImageService::check_key_image_store_auth()]
-    fn prepare_untrusted_query(&mut self) -> UntrustedKeyImageQueryResponse {
-        let (processed_block_range, last_known_block_cumulative_txo_count, latest_block_version) = {
+    /// for use in [KeyImageService::check_key_image_store_auth()]
+    fn prepare_untrusted_query(&self) -> UntrustedKeyImageQueryResponse {
+        let (
+            processed_block_range,
+            last_known_block_cumulative_txo_count,
+            latest_block_version,
+            ledger_num_blocks,
+        ) = {
             let shared_state = self.db_poll_shared_state.lock().expect("mutex poisoned");
             (
                 shared_state.processed_block_range.clone(),
                 shared_state.last_known_block_cumulative_txo_count,
                 shared_state.latest_block_version,
+                shared_state.ledger_num_blocks,
             )
         };
 
@@ -98,28 +114,33 @@ impl<E: LedgerEnclaveProxy> KeyImageService<E> {
             last_known_block_cumulative_txo_count,
             latest_block_version,
             max_block_version: latest_block_version.max(*MAX_BLOCK_VERSION),
+            ledger_num_blocks,
         }
     }
 
     /// Unwrap and forward to enclave
-    // self.enclave.check_key_images should take both a NonceMessage and an
+    // The enclave takes both the NonceMessages and an
     // UntrustedKeyImageQueryResponse object that contains any data that is
     // needed that isn't in the ORAM. This might be like "num_blocks" and similar
-    // stuff. self.enclave.check_key_images should return an AttestMessage that
-    // we send back to the user.
+    // stuff. The queries are batched with those of concurrent requests, and the
+    // enclave returns an AttestMessage per query that we send back to the
+    // router.
     fn check_key_image_store_auth(
-        &mut self,
-        request: attest::NonceMessage,
-    ) -> Result<attest::NonceMessage, EnclaveError> {
-        log::trace!(self.logger, "Getting encrypted request");
+        &self,
+        requests: Vec<attest::NonceMessage>,
+    ) -> Result<Vec<Result<attest::NonceMessage, EnclaveError>>, EnclaveError> {
+        log::trace!(self.logger, "Getting encrypted requests");
 
-        let untrusted_query_response = self.prepare_untrusted_query();
+        let responses = self
+            .batcher
+            .check_key_image_store(requests.into_iter().map(Into::into).collect(), || {
+                self.prepare_untrusted_query()
+            })?;
 
-        let response = self
-            .enclave
-            .check_key_image_store(request.into(), untrusted_query_response)?;
-
-        Ok(response.into())
+        Ok(responses
+            .into_iter()
+            .map(|response| response.map(Into::into))
+            .collect())
     }
 
     /// Handle MultiKeyImageStoreRequest contents sent by a router to this
@@ -134,13 +155,29 @@ impl<E: LedgerEnclaveProxy> KeyImageService<E> {
         response.set_store_uri(fog_ledger_store_uri.url().to_string());
         // Default status of AUTHENTICATION_ERROR in case of empty queries
         response.set_status(MultiKeyImageStoreResponseStatus::AUTHENTICATION_ERROR);
+        response.set_processed_block_range(
+            (&self
+                .db_poll_shared_state
+                .lock()
+                .expect("mutex poisoned")
+                .processed_block_range)
+                .into(),
+        );
+
+        let results = match self.check_key_image_store_auth(queries) {
+            Ok(results) => results,
+            Err(_) => {
+                response.set_status(MultiKeyImageStoreResponseStatus::UNKNOWN);
+                return response;
+            }
+        };
 
-        for query in queries.into_iter() {
+        for result in results.into_iter() {
             // Only one of the query messages in the multi-store query is intended for this
             // store. It's a bit of a broadcast model - all queries are sent to
             // all stores, and then the stores evaluate which message is meant
             // for them.
-            match self.check_key_image_store_auth(query) {
+            match result {
                 Ok(attested_message) => {
                     response.set_query_response(attested_message);
                     response.set_status(MultiKeyImageStoreResponseStatus::SUCCESS);
@@ -167,6 +204,7 @@ impl<E: LedgerEnclaveProxy> KeyImageService<E> {
     }
 }
 
+#[service_metrics(SVC_COUNTERS)]
 impl<E: LedgerEnclaveProxy> KeyImageStoreApi for KeyImageService<E> {
     fn auth(
         &mut self,
@@ -174,10 +212,9 @@ impl<E: LedgerEnclaveProxy> KeyImageStoreApi for KeyImageService<E> {
         req: AuthMessage,
         sink: grpcio::UnarySink<AuthMessage>,
     ) {
-        let _timer = SVC_COUNTERS.req(&ctx);
         mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
-            if let Err(err) = self.authenticator.authenticate_rpc(&ctx) {
-                return send_result(ctx, sink, err.into(), logger);
+            if let Err(err) = self.interceptors.check(&ctx) {
+                return send_result(ctx, sink, Err(err), logger);
             }
 
             match self.auth_store(req, logger) {
@@ -205,10 +242,9 @@ impl<E: LedgerEnclaveProxy> KeyImageStoreApi for KeyImageService<E> {
         req: MultiKeyImageStoreRequest,
         sink: grpcio::UnarySink<MultiKeyImageStoreResponse>,
     ) {
-        let _timer = SVC_COUNTERS.req(&ctx);
         mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
-            if let Err(err) = self.authenticator.authenticate_rpc(&ctx) {
-                return send_result(ctx, sink, err.into(), logger);
+            if let Err(err) = self.interceptors.check(&ctx) {
+                return send_result(ctx, sink, Err(err), logger);
             }
             let start_time = Instant::now();
 
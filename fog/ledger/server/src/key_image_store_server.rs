@@ -2,7 +2,8 @@
 
 use crate::{
     config::LedgerStoreConfig, counters, db_fetcher::DbFetcher,
-    sharding_strategy::ShardingStrategy, DbPollSharedState, KeyImageService,
+    sharding_strategy::ShardingStrategy, store_state::KeyImageStoreStateSource, DbPollSharedState,
+    IntakeQueue, KeyImageExport, KeyImageService,
 };
 use futures::executor::block_on;
 use mc_common::{
@@ -12,11 +13,12 @@ use mc_common::{
 use mc_fog_api::ledger_grpc;
 use mc_fog_block_provider::BlockProvider;
 use mc_fog_ledger_enclave::LedgerEnclaveProxy;
+use mc_fog_types::common::BlockRange;
 use mc_fog_uri::{ConnectionUri, KeyImageStoreUri};
 use mc_sgx_report_cache_untrusted::ReportCacheThread;
 use mc_util_grpc::{
-    AnonymousAuthenticator, Authenticator, ConnectionUriGrpcioServer, ReadinessIndicator,
-    TokenAuthenticator,
+    AnonymousAuthenticator, AuthInterceptor, Authenticator, ConnectionUriGrpcioServer,
+    InterceptorChain, ReadinessIndicator, TokenAuthenticator,
 };
 use std::{
     sync::{Arc, Mutex},
@@ -31,6 +33,8 @@ where
     server: grpcio::Server,
     client_listen_uri: KeyImageStoreUri,
     db_fetcher: DbFetcher<E, SS>,
+    db_poll_shared_state: Arc<Mutex<DbPollSharedState>>,
+    epoch_block_range: BlockRange,
     enclave: E,
     report_cache_thread: Option<ReportCacheThread>,
     logger: Logger,
@@ -61,7 +65,21 @@ where
                 Arc::new(AnonymousAuthenticator)
             };
 
-        Self::new(
+        let key_image_export = config.key_image_export_dir.as_ref().map(|dir| {
+            KeyImageExport::open(dir, &config.chain_id, &sharding_strategy.get_block_range())
+                .expect("Could not open key image export")
+        });
+        let intake_queue = config.intake_queue_dir.as_ref().map(|dir| {
+            IntakeQueue::open(
+                dir,
+                &config.chain_id,
+                &sharding_strategy.get_block_range(),
+                config.intake_queue_capacity,
+            )
+            .expect("Could not open intake queue")
+        });
+
+        let mut server = Self::new(
             client_authenticator,
             config.client_listen_uri,
             enclave,
@@ -69,7 +87,14 @@ where
             sharding_strategy,
             config.poll_interval,
             logger,
-        )
+        );
+        if let Some(key_image_export) = key_image_export {
+            server.set_key_image_export(key_image_export);
+        }
+        if let Some(intake_queue) = intake_queue {
+            server.set_intake_queue(intake_queue);
+        }
+        server
     }
 
     pub fn new(
@@ -94,7 +119,7 @@ where
             uri,
             enclave.clone(),
             shared_state,
-            client_authenticator,
+            InterceptorChain::new().with(AuthInterceptor::new(client_authenticator)),
             logger.clone(),
         );
         Self::new_from_service(
@@ -151,11 +176,13 @@ where
             .build_using_uri(&client_listen_uri, logger.clone())
             .expect("Could not build Key Image Store Server");
 
+        let db_poll_shared_state = key_image_service.get_db_poll_shared_state();
+        let epoch_block_range = sharding_strategy.get_block_range();
         let db_fetcher = DbFetcher::new(
             block_provider,
             enclave.clone(),
             sharding_strategy,
-            key_image_service.get_db_poll_shared_state(),
+            db_poll_shared_state.clone(),
             readiness_indicator,
             poll_interval,
             logger.clone(),
@@ -165,12 +192,44 @@ where
             server,
             client_listen_uri,
             db_fetcher,
+            db_poll_shared_state,
+            epoch_block_range,
             enclave,
             report_cache_thread: None,
             logger,
         }
     }
 
+    /// Keep an export of the key image data added to the enclave in the given
+    /// export, and bootstrap the enclave from the blocks already in it.
+    ///
+    /// This must be called before the server is started.
+    pub fn set_key_image_export(&mut self, key_image_export: KeyImageExport) {
+        self.db_fetcher.set_key_image_export(key_image_export);
+    }
+
+    /// Add fetched blocks to the enclave from the given intake queue, in a
+    /// thread of their own, so fetching doesn't wait for the enclave.
+    ///
+    /// This must be called before the server is started.
+    pub fn set_intake_queue(&mut self, intake_queue: IntakeQueue) {
+        self.db_fetcher.set_intake_queue(intake_queue);
+    }
+
+    /// A source of descriptions of this store's state, for the admin API.
+    /// `omap_capacity` is the capacity the enclave was created with.
+    pub fn state_source(&self, omap_capacity: u64) -> KeyImageStoreStateSource {
+        KeyImageStoreStateSource::new(
+            self.client_listen_uri
+                .responder_id()
+                .expect("Could not get store responder ID")
+                .to_string(),
+            omap_capacity,
+            self.epoch_block_range.clone(),
+            self.db_poll_shared_state.clone(),
+        )
+    }
+
     /// Starts the server
     pub fn start(&mut self) {
         self.report_cache_thread = Some(
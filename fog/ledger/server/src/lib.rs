@@ -1,30 +1,52 @@
 // Copyright (c) 2018-2022 The MobileCoin Foundation
 
 #![allow(clippy::result_large_err)]
-pub use block_service::BlockService;
-pub use config::{LedgerRouterConfig, LedgerStoreConfig, ShardingStrategy};
+pub use admission_control::{CidrAdmissionControl, IpCidr};
+pub use block_service::{BlockService, MAX_TXO_COUNT_HISTORY_BLOCKS};
+pub use concurrency_limit::{ConcurrencyLimiter, ConcurrencyPermit};
+pub use config::{
+    AdmissionControlConfig, ConcurrencyLimitConfig, LedgerRouterConfig, LedgerStoreConfig,
+    ShardCoveragePolicy, ShardingStrategy,
+};
+pub use intake_queue::{IntakeQueue, IntakeQueueError};
+pub use key_image_export::{KeyImageExport, KeyImageExportError};
 pub use key_image_service::KeyImageService;
 pub use key_image_store_server::KeyImageStoreServer;
 use mc_fog_types::common::BlockRange;
 pub use merkle_proof_service::MerkleProofService;
 pub use router_server::LedgerRouterServer;
-pub use untrusted_tx_out_service::UntrustedTxOutService;
+pub use store_state::{
+    diff_store_states, KeyImageStoreAdminService, KeyImageStoreStateSource, StoreStateDiscrepancy,
+};
+pub use untrusted_tx_out_service::{UntrustedTxOutService, MAX_MERKLE_ROOT_HISTORY_BLOCKS};
 
 pub mod sharding_strategy;
 
+mod admission_control;
 mod block_service;
+mod concurrency_limit;
 mod config;
 mod counters;
 mod db_fetcher;
 mod error;
+mod intake_queue;
+mod key_image_export;
+mod key_image_query_batcher;
 mod key_image_service;
 mod key_image_store_server;
+mod merkle_proof_cache;
 mod merkle_proof_service;
 mod metrics;
+mod new_block_notifier;
 mod router_admin_service;
 mod router_handlers;
 mod router_server;
 mod router_service;
+mod shard_coverage;
+mod shard_epoch;
+mod shard_handoff;
+mod store_attestation;
+mod store_state;
 mod untrusted_tx_out_service;
 
 use mc_util_metrics::ServiceMetrics;
@@ -45,4 +67,11 @@ pub struct DbPollSharedState {
 
     /// The latest value of `block_version` in the blockchain
     pub latest_block_version: u32,
+
+    /// The number of blocks in the ledger we load blocks from. Queries are
+    /// served degraded while this is ahead of `processed_block_range`.
+    pub ledger_num_blocks: u64,
+
+    /// The number of key images added to the enclave so far.
+    pub num_key_images: u64,
 }
@@ -0,0 +1,358 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! A cache of TxOut membership proofs, keyed by the number of blocks they were
+//! computed against.
+//!
+//! Membership proofs change with every block, so a cache of them is only
+//! useful for as long as the ledger doesn't grow. To keep it warm right after
+//! block boundaries, the [MerkleProofCacheThread] recomputes the proofs of the
+//! most recently requested TxOuts as soon as a new block appears.
+
+use crate::counters;
+use mc_common::{
+    logger::{log, Logger},
+    HashMap,
+};
+use mc_fog_block_provider::{BlockProvider, Error as BlockProviderError};
+use mc_transaction_core::tx::{TxOut, TxOutMembershipProof};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{Builder as ThreadBuilder, JoinHandle},
+    time::Duration,
+};
+
+/// The ledger height a cached proof is valid at.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ProofHeight {
+    /// The number of blocks in the ledger
+    pub num_blocks: u64,
+    /// The number of TxOuts in the ledger
+    pub global_txo_count: u64,
+}
+
+impl ProofHeight {
+    /// Whether `proof` was computed against a ledger of this height.
+    fn matches(&self, proof: &TxOutMembershipProof) -> bool {
+        proof.highest_index + 1 == self.global_txo_count
+    }
+}
+
+/// A cache of the membership proofs of recently requested TxOuts.
+pub struct MerkleProofCache {
+    /// The number of recently requested TxOuts to keep proofs for
+    capacity: usize,
+    state: Mutex<CacheState>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    /// The height all of the cached proofs are valid at
+    height: ProofHeight,
+    /// The cached TxOuts and proofs, by TxOut index
+    proofs: HashMap<u64, (TxOut, TxOutMembershipProof)>,
+    /// When each TxOut index was last requested, as a count of requests
+    last_requested: HashMap<u64, u64>,
+    /// The number of requests so far
+    num_requests: u64,
+}
+
+impl CacheState {
+    /// Note that `index` was requested, forgetting the least recently requested
+    /// indices once there are too many.
+    fn record_request(&mut self, index: u64, capacity: usize) {
+        self.num_requests += 1;
+        self.last_requested.insert(index, self.num_requests);
+        // Trimming only once there are twice as many as needed keeps the cost
+        // of sorting low per request.
+        if self.last_requested.len() > 2 * capacity {
+            self.last_requested = self.most_recently_requested(capacity).into_iter().collect();
+            let Self {
+                proofs,
+                last_requested,
+                ..
+            } = self;
+            proofs.retain(|index, _| last_requested.contains_key(index));
+        }
+    }
+
+    /// The `capacity` most recently requested indices, along with when they
+    /// were last requested.
+    fn most_recently_requested(&self, capacity: usize) -> Vec<(u64, u64)> {
+        let mut requested = self
+            .last_requested
+            .iter()
+            .map(|(index, request)| (*index, *request))
+            .collect::<Vec<_>>();
+        requested.sort_unstable_by_key(|(_, request)| std::cmp::Reverse(*request));
+        requested.truncate(capacity);
+        requested
+    }
+}
+
+impl MerkleProofCache {
+    /// Create a cache holding the proofs of up to `capacity` recently requested
+    /// TxOuts.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Default::default(),
+        }
+    }
+
+    /// Get the TxOut and proof at `index`, if a proof valid at `height` is
+    /// cached. This counts as a request for `index`.
+    pub fn get(&self, height: ProofHeight, index: u64) -> Option<(TxOut, TxOutMembershipProof)> {
+        let mut state = self.state.lock().expect("mutex poisoned");
+        state.record_request(index, self.capacity);
+        let result = if state.height == height {
+            state.proofs.get(&index).cloned()
+        } else {
+            None
+        };
+        if result.is_some() {
+            counters::MERKLE_PROOF_CACHE_HITS.inc();
+        } else {
+            counters::MERKLE_PROOF_CACHE_MISSES.inc();
+        }
+        result
+    }
+
+    /// Cache a TxOut and its proof, if the proof is valid at `height` and no
+    /// later height is cached already.
+    pub fn insert(
+        &self,
+        height: ProofHeight,
+        index: u64,
+        tx_out: TxOut,
+        proof: TxOutMembershipProof,
+    ) {
+        if !height.matches(&proof) {
+            return;
+        }
+        let mut state = self.state.lock().expect("mutex poisoned");
+        if state.height.num_blocks < height.num_blocks {
+            state.height = height;
+            state.proofs.clear();
+        }
+        if state.height == height && state.last_requested.contains_key(&index) {
+            state.proofs.insert(index, (tx_out, proof));
+        }
+    }
+
+    /// If the ledger has grown past the cached height, recompute the proofs of
+    /// the most recently requested TxOuts against it.
+    pub fn refresh(&self, block_provider: &dyn BlockProvider) -> Result<(), BlockProviderError> {
+        let latest_block = block_provider.get_latest_block()?;
+        let height = ProofHeight {
+            num_blocks: latest_block.index + 1,
+            global_txo_count: latest_block.cumulative_txo_count,
+        };
+
+        let indices = {
+            let state = self.state.lock().expect("mutex poisoned");
+            if state.height.num_blocks >= height.num_blocks {
+                return Ok(());
+            }
+            state.most_recently_requested(self.capacity)
+        };
+
+        // Compute the proofs without holding the lock, so that requests are
+        // not held up.
+        let mut proofs = HashMap::default();
+        for (index, _) in indices {
+            let (tx_out, proof) =
+                match block_provider.get_tx_out_and_membership_proof_by_index(index) {
+                    Ok(result) => result,
+                    Err(BlockProviderError::NotFound) => continue,
+                    Err(err) => return Err(err),
+                };
+            if !height.matches(&proof) {
+                // Another block arrived meanwhile, so the next refresh will
+                // compute proofs at it instead.
+                return Ok(());
+            }
+            proofs.insert(index, (tx_out, proof));
+        }
+
+        let mut state = self.state.lock().expect("mutex poisoned");
+        if state.height.num_blocks < height.num_blocks {
+            state.height = height;
+            state.proofs = proofs;
+        } else if state.height == height {
+            // Requests at this height got here first.
+            for (index, proof) in proofs {
+                state.proofs.entry(index).or_insert(proof);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A background thread which refreshes a [MerkleProofCache] whenever a new
+/// block appears.
+pub struct MerkleProofCacheThread {
+    join_handle: Option<JoinHandle<()>>,
+    stop_requested: Arc<AtomicBool>,
+}
+
+impl MerkleProofCacheThread {
+    /// Start checking for new blocks every `poll_interval`.
+    pub fn start(
+        cache: Arc<MerkleProofCache>,
+        block_provider: Box<dyn BlockProvider>,
+        poll_interval: Duration,
+        logger: Logger,
+    ) -> Self {
+        let stop_requested = Arc::new(AtomicBool::new(false));
+        let thread_stop_requested = stop_requested.clone();
+        let join_handle = ThreadBuilder::new()
+            .name("MerkleProofCache".to_owned())
+            .spawn(move || {
+                log::info!(logger, "Merkle proof cache thread started.");
+                while !thread_stop_requested.load(Ordering::SeqCst) {
+                    if let Err(err) = cache.refresh(block_provider.as_ref()) {
+                        log::error!(logger, "Could not refresh merkle proof cache: {}", err);
+                    }
+                    std::thread::sleep(poll_interval);
+                }
+                log::info!(logger, "Merkle proof cache thread stopped.");
+            })
+            .expect("Could not spawn thread");
+        Self {
+            join_handle: Some(join_handle),
+            stop_requested,
+        }
+    }
+
+    /// Stop and join the thread
+    pub fn stop(&mut self) -> Result<(), ()> {
+        if let Some(join_handle) = self.join_handle.take() {
+            self.stop_requested.store(true, Ordering::SeqCst);
+            join_handle.join().map_err(|_| ())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for MerkleProofCacheThread {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mc_blockchain_types::{Block, BlockIndex};
+    use mc_crypto_keys::CompressedRistrettoPublic;
+    use mc_fog_block_provider::{BlocksDataResponse, TxOutInfoByPublicKeyResponse};
+
+    /// A ledger with one TxOut per block.
+    #[derive(Clone)]
+    struct FakeBlockProvider {
+        height: ProofHeight,
+    }
+
+    impl BlockProvider for FakeBlockProvider {
+        fn num_blocks(&self) -> Result<u64, BlockProviderError> {
+            Ok(self.height.num_blocks)
+        }
+
+        fn get_latest_block(&self) -> Result<Block, BlockProviderError> {
+            Ok(Block {
+                index: self.height.num_blocks - 1,
+                cumulative_txo_count: self.height.global_txo_count,
+                ..Default::default()
+            })
+        }
+
+        fn get_blocks_data(
+            &self,
+            _block_indices: &[BlockIndex],
+        ) -> Result<BlocksDataResponse, BlockProviderError> {
+            unimplemented!()
+        }
+
+        fn poll_block_timestamp(
+            &self,
+            _block_index: BlockIndex,
+            _watcher_timeout: Duration,
+        ) -> u64 {
+            unimplemented!()
+        }
+
+        fn get_tx_out_and_membership_proof_by_index(
+            &self,
+            tx_out_index: u64,
+        ) -> Result<(TxOut, TxOutMembershipProof), BlockProviderError> {
+            if tx_out_index < self.height.global_txo_count {
+                Ok(proof(tx_out_index, self.height))
+            } else {
+                Err(BlockProviderError::NotFound)
+            }
+        }
+
+        fn get_tx_out_info_by_public_key(
+            &self,
+            _tx_out_pub_keys: &[CompressedRistrettoPublic],
+        ) -> Result<TxOutInfoByPublicKeyResponse, BlockProviderError> {
+            unimplemented!()
+        }
+    }
+
+    fn proof(index: u64, height: ProofHeight) -> (TxOut, TxOutMembershipProof) {
+        (
+            Default::default(),
+            TxOutMembershipProof::new(index, height.global_txo_count - 1, vec![]),
+        )
+    }
+
+    const FIRST: ProofHeight = ProofHeight {
+        num_blocks: 10,
+        global_txo_count: 10,
+    };
+    const SECOND: ProofHeight = ProofHeight {
+        num_blocks: 11,
+        global_txo_count: 11,
+    };
+
+    #[test]
+    fn proofs_are_only_served_at_their_height() {
+        let cache = MerkleProofCache::new(10);
+
+        assert!(cache.get(FIRST, 3).is_none());
+        let (tx_out, proof_at_first) = proof(3, FIRST);
+        cache.insert(FIRST, 3, tx_out, proof_at_first.clone());
+        assert_eq!(cache.get(FIRST, 3).unwrap().1, proof_at_first);
+        assert!(cache.get(SECOND, 3).is_none());
+
+        // Proofs which don't match their height are not cached.
+        let (tx_out, proof_at_first) = proof(4, FIRST);
+        cache.insert(SECOND, 4, tx_out, proof_at_first);
+        assert!(cache.get(SECOND, 4).is_none());
+    }
+
+    #[test]
+    fn refresh_recomputes_recently_requested_proofs() {
+        let cache = MerkleProofCache::new(2);
+        for index in [1, 2, 3] {
+            assert!(cache.get(FIRST, index).is_none());
+        }
+
+        cache.refresh(&FakeBlockProvider { height: FIRST }).unwrap();
+        assert_eq!(cache.get(FIRST, 3).unwrap().1, proof(3, FIRST).1);
+
+        cache
+            .refresh(&FakeBlockProvider { height: SECOND })
+            .unwrap();
+        assert_eq!(cache.get(SECOND, 2).unwrap().1, proof(2, SECOND).1);
+        assert_eq!(cache.get(SECOND, 3).unwrap().1, proof(3, SECOND).1);
+        // Index 1 was not among the two most recently requested.
+        assert!(cache.get(SECOND, 1).is_none());
+        assert!(cache.get(FIRST, 3).is_none());
+    }
+}
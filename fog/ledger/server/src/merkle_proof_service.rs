@@ -1,6 +1,9 @@
 // Copyright (c) 2018-2022 The MobileCoin Foundation
 
-use crate::SVC_COUNTERS;
+use crate::{
+    merkle_proof_cache::{MerkleProofCache, ProofHeight},
+    SVC_COUNTERS,
+};
 use grpcio::{RpcContext, RpcStatus, UnarySink};
 use mc_attest_api::attest::{AuthMessage, Message};
 use mc_attest_enclave_api::ClientSession;
@@ -12,9 +15,10 @@ use mc_fog_ledger_enclave::{GetOutputsResponse, LedgerEnclaveProxy, OutputContex
 use mc_fog_ledger_enclave_api::Error as EnclaveError;
 use mc_transaction_core::tx::{TxOut, TxOutMembershipProof};
 use mc_util_grpc::{
-    check_request_chain_id, rpc_database_err, rpc_internal_error, rpc_invalid_arg_error,
-    rpc_logger, rpc_permissions_error, send_result, Authenticator,
+    rpc_database_err, rpc_internal_error, rpc_invalid_arg_error, rpc_logger, rpc_permissions_error,
+    send_result, InterceptorChain,
 };
+use mc_util_metrics::service_metrics;
 use std::sync::Arc;
 
 // Maximum number of TxOuts that may be returned for a single request.
@@ -22,26 +26,27 @@ pub const MAX_REQUEST_SIZE: usize = 2000;
 
 #[derive(Clone)]
 pub struct MerkleProofService<E: LedgerEnclaveProxy> {
-    chain_id: String,
     block_provider: Box<dyn BlockProvider>,
     enclave: E,
-    authenticator: Arc<dyn Authenticator + Send + Sync>,
+    interceptors: InterceptorChain,
+    /// Proofs of recently requested TxOuts, if caching is enabled
+    cache: Option<Arc<MerkleProofCache>>,
     logger: Logger,
 }
 
 impl<E: LedgerEnclaveProxy> MerkleProofService<E> {
     pub fn new(
-        chain_id: String,
         block_provider: Box<dyn BlockProvider>,
         enclave: E,
-        authenticator: Arc<dyn Authenticator + Send + Sync>,
+        interceptors: InterceptorChain,
+        cache: Option<Arc<MerkleProofCache>>,
         logger: Logger,
     ) -> Self {
         Self {
-            chain_id,
             block_provider,
             enclave,
-            authenticator,
+            interceptors,
+            cache,
             logger,
         }
     }
@@ -65,6 +70,9 @@ impl<E: LedgerEnclaveProxy> MerkleProofService<E> {
                     &self.logger,
                 ))
             }
+            Err(err @ (EnclaveError::TooManyOutputs(..) | EnclaveError::UnsupportedQuery(_))) => {
+                return Err(rpc_invalid_arg_error("get_outputs", err, &self.logger))
+            }
             Err(e) => return Err(rpc_internal_error("get_outputs", e, &self.logger)),
         };
 
@@ -114,6 +122,10 @@ impl<E: LedgerEnclaveProxy> MerkleProofService<E> {
             .map_err(|err| rpc_database_err(err, &self.logger))?;
 
         let latest_block_version = latest_block.version;
+        let height = ProofHeight {
+            num_blocks: latest_block.index + 1,
+            global_txo_count: latest_block.cumulative_txo_count,
+        };
 
         Ok(GetOutputsResponse {
             num_blocks: latest_block.index + 1,
@@ -122,7 +134,7 @@ impl<E: LedgerEnclaveProxy> MerkleProofService<E> {
                 .indexes
                 .iter()
                 .map(|idx| -> Result<OutputResult, BlockProviderError> {
-                    Ok(match self.get_output_impl(*idx)? {
+                    Ok(match self.get_output_impl(height, *idx)? {
                         Some((output, proof)) => OutputResult {
                             index: *idx,
                             result_code: OutputResultCode::Exists as u32,
@@ -146,46 +158,46 @@ impl<E: LedgerEnclaveProxy> MerkleProofService<E> {
 
     fn get_output_impl(
         &mut self,
+        height: ProofHeight,
         idx: u64,
     ) -> Result<Option<(TxOut, TxOutMembershipProof)>, BlockProviderError> {
+        if let Some(result) = self.cache.as_ref().and_then(|cache| cache.get(height, idx)) {
+            return Ok(Some(result));
+        }
         match self
             .block_provider
             .get_tx_out_and_membership_proof_by_index(idx)
         {
-            Ok(result) => Ok(Some(result)),
+            Ok((tx_out, proof)) => {
+                if let Some(cache) = self.cache.as_ref() {
+                    cache.insert(height, idx, tx_out.clone(), proof.clone());
+                }
+                Ok(Some((tx_out, proof)))
+            }
             Err(BlockProviderError::NotFound) => Ok(None),
             Err(err) => Err(err),
         }
     }
 }
 
+#[service_metrics(SVC_COUNTERS)]
 impl<E: LedgerEnclaveProxy> FogMerkleProofApi for MerkleProofService<E> {
     fn get_outputs(&mut self, ctx: RpcContext, request: Message, sink: UnarySink<Message>) {
-        let _timer = SVC_COUNTERS.req(&ctx);
         mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
-            if let Err(err) = check_request_chain_id(&self.chain_id, &ctx) {
+            if let Err(err) = self.interceptors.check(&ctx) {
                 return send_result(ctx, sink, Err(err), logger);
             }
 
-            if let Err(err) = self.authenticator.authenticate_rpc(&ctx) {
-                return send_result(ctx, sink, err.into(), logger);
-            }
-
             send_result(ctx, sink, self.get_outputs_auth(request), logger)
         })
     }
 
     fn auth(&mut self, ctx: RpcContext, request: AuthMessage, sink: UnarySink<AuthMessage>) {
-        let _timer = SVC_COUNTERS.req(&ctx);
         mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
-            if let Err(err) = check_request_chain_id(&self.chain_id, &ctx) {
+            if let Err(err) = self.interceptors.check(&ctx) {
                 return send_result(ctx, sink, Err(err), logger);
             }
 
-            if let Err(err) = self.authenticator.authenticate_rpc(&ctx) {
-                return send_result(ctx, sink, err.into(), logger);
-            }
-
             // TODO: Use the prost message directly, once available
             match self.enclave.client_accept(request.into()) {
                 Ok((response, _session_id)) => {
@@ -235,7 +247,6 @@ mod test {
         Amount, BlockVersion, Token,
     };
     use mc_util_from_random::FromRandom;
-    use mc_util_grpc::AnonymousAuthenticator;
     use rand::{rngs::StdRng, SeedableRng};
 
     /// Creates a number of TxOuts.
@@ -293,12 +304,11 @@ mod test {
         }
 
         let enclave = MockEnclave::default();
-        let authenticator = Arc::new(AnonymousAuthenticator);
         let mut ledger_server_node = MerkleProofService::new(
-            "local".to_string(),
             LocalBlockProvider::new(mock_ledger.clone(), None),
             enclave,
-            authenticator,
+            InterceptorChain::new(),
+            None,
             logger,
         );
 
@@ -352,12 +362,11 @@ mod test {
         }
 
         let enclave = MockEnclave::default();
-        let authenticator = Arc::new(AnonymousAuthenticator);
         let mut ledger_server_node = MerkleProofService::new(
-            "local".to_string(),
             LocalBlockProvider::new(mock_ledger, None),
             enclave,
-            authenticator,
+            InterceptorChain::new(),
+            None,
             logger,
         );
 
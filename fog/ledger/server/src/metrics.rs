@@ -2,8 +2,9 @@
 
 use lazy_static::lazy_static;
 use prometheus::{
-    histogram_opts, register_histogram, register_histogram_vec, register_int_counter, Histogram,
-    HistogramVec, IntCounter,
+    histogram_opts, opts, register_histogram, register_histogram_vec, register_int_counter,
+    register_int_counter_vec, register_int_gauge, register_int_gauge_vec, Histogram, HistogramVec,
+    IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
 };
 
 // Initialize global metrics
@@ -26,9 +27,73 @@ lazy_static! {
         "Queries to router"
     ))
     .expect("metric cannot be created");
+    pub static ref STORE_QUERY_BATCH_SIZE: Histogram = register_histogram!(histogram_opts!(
+        "fog_ledger_store_query_batch_size",
+        "Number of router queries checked per store ECALL",
+        vec![1.0, 2.0, 4.0, 8.0, 16.0, 32.0, 64.0, 128.0]
+    ))
+    .expect("metric cannot be created");
+    pub static ref SHARD_COVERAGE_GAP_BLOCKS: IntGauge = register_int_gauge!(
+        "fog_ledger_router_shard_coverage_gap_blocks",
+        "Number of blocks in the ledger not covered by any configured shard"
+    )
+    .expect("metric cannot be created");
+    pub static ref INTAKE_QUEUE_DEPTH: IntGaugeVec = register_int_gauge_vec!(
+        opts!(
+            "fog_ledger_store_intake_queue_depth",
+            "Number of fetched blocks waiting to be added to each epoch's enclave"
+        ),
+        &["epoch"]
+    )
+    .expect("metric cannot be created");
+    pub static ref INTAKE_QUEUE_NEXT_BLOCK: IntGaugeVec = register_int_gauge_vec!(
+        opts!(
+            "fog_ledger_store_intake_queue_next_block",
+            "Index of the next block to add from the intake queue to each epoch's enclave"
+        ),
+        &["epoch"]
+    )
+    .expect("metric cannot be created");
+    pub static ref KEY_IMAGE_SUBSCRIPTIONS: IntGauge = register_int_gauge!(
+        "fog_ledger_router_key_image_subscriptions",
+        "Number of streams subscribed to key image updates"
+    )
+    .expect("metric cannot be created");
     pub static ref AUTH_CLIENT_REQUESTS: IntCounter = register_int_counter!(
         "fog_ledger_router_auth_client_requests",
         "Auth requests to stores"
     )
     .expect("metric cannot be created");
+    pub static ref CONCURRENCY_LIMIT_IN_FLIGHT: IntGaugeVec = register_int_gauge_vec!(
+        opts!(
+            "fog_ledger_router_concurrency_limit_in_flight",
+            "Calls being served by each concurrency limited method"
+        ),
+        &["method"]
+    )
+    .expect("metric cannot be created");
+    pub static ref CONCURRENCY_LIMIT_QUEUED: IntGaugeVec = register_int_gauge_vec!(
+        opts!(
+            "fog_ledger_router_concurrency_limit_queued",
+            "Calls waiting for a turn at each concurrency limited method"
+        ),
+        &["method"]
+    )
+    .expect("metric cannot be created");
+    pub static ref CONCURRENCY_LIMIT_SHED: IntCounterVec = register_int_counter_vec!(
+        opts!(
+            "fog_ledger_router_concurrency_limit_shed",
+            "Calls rejected because the queue of a concurrency limited method was full"
+        ),
+        &["method"]
+    )
+    .expect("metric cannot be created");
+    pub static ref CONCURRENCY_LIMIT_QUEUE_TIME: HistogramVec = register_histogram_vec!(
+        histogram_opts!(
+            "fog_ledger_router_concurrency_limit_queue_time",
+            "Time calls spent waiting for a turn at a concurrency limited method"
+        ),
+        &["method"]
+    )
+    .expect("metric cannot be created");
 }
@@ -0,0 +1,179 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Tells key image subscriptions how many blocks the ledger has, so that they
+//! check their key images again once the key image stores have caught up with
+//! new blocks.
+
+use crate::metrics::KEY_IMAGE_SUBSCRIPTIONS;
+use futures::channel::mpsc::{channel, Receiver, Sender};
+use mc_common::logger::{log, Logger};
+use mc_fog_block_provider::BlockProvider;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{Builder as ThreadBuilder, JoinHandle},
+    time::Duration,
+};
+
+/// Sends the number of blocks in the ledger to each subscriber each time it
+/// is checked, and limits the key image subscriptions of a router.
+pub struct NewBlockNotifier {
+    subscribers: Mutex<Vec<Sender<u64>>>,
+    /// The most subscriptions which may exist at once
+    max_subscriptions: usize,
+    /// The most key images one subscription may be for
+    max_key_images_per_subscription: usize,
+}
+
+impl NewBlockNotifier {
+    /// Create a notifier allowing at most `max_subscriptions` subscriptions,
+    /// each for at most `max_key_images_per_subscription` key images.
+    pub fn new(max_subscriptions: usize, max_key_images_per_subscription: usize) -> Self {
+        Self {
+            subscribers: Default::default(),
+            max_subscriptions,
+            max_key_images_per_subscription,
+        }
+    }
+
+    /// The most key images one subscription may be for.
+    pub fn max_key_images_per_subscription(&self) -> usize {
+        self.max_key_images_per_subscription
+    }
+
+    /// Subscribe to new blocks, unless the limit on subscriptions has been
+    /// reached. The subscription ends when the receiver is dropped.
+    ///
+    /// A subscriber which hasn't received the last notification yet doesn't
+    /// get another one, so a slow subscriber sees fewer notifications rather
+    /// than a backlog of them.
+    pub fn subscribe(&self) -> Option<Receiver<u64>> {
+        let mut subscribers = self.subscribers.lock().expect("mutex poisoned");
+        subscribers.retain(|sender| !sender.is_closed());
+        if subscribers.len() >= self.max_subscriptions {
+            return None;
+        }
+        let (sender, receiver) = channel(0);
+        subscribers.push(sender);
+        KEY_IMAGE_SUBSCRIPTIONS.set(subscribers.len() as i64);
+        Some(receiver)
+    }
+
+    /// Tell every subscriber that the ledger has `num_blocks` blocks, and
+    /// forget the subscribers which have gone away.
+    pub fn notify(&self, num_blocks: u64) {
+        let mut subscribers = self.subscribers.lock().expect("mutex poisoned");
+        subscribers.retain_mut(|sender| match sender.try_send(num_blocks) {
+            Ok(()) => true,
+            Err(err) => !err.is_disconnected(),
+        });
+        KEY_IMAGE_SUBSCRIPTIONS.set(subscribers.len() as i64);
+    }
+}
+
+/// A thread which checks the number of blocks in the ledger, and notifies a
+/// [NewBlockNotifier] each time.
+///
+/// Subscribers are notified even when the ledger hasn't grown, because the
+/// key image stores may still have been catching up with the last new block
+/// the previous time a subscription's key images were checked.
+pub struct NewBlockNotifierThread {
+    join_handle: Option<JoinHandle<()>>,
+    stop_requested: Arc<AtomicBool>,
+}
+
+impl NewBlockNotifierThread {
+    /// Start checking for new blocks every `poll_interval`.
+    pub fn start(
+        notifier: Arc<NewBlockNotifier>,
+        block_provider: Box<dyn BlockProvider>,
+        poll_interval: Duration,
+        logger: Logger,
+    ) -> Self {
+        let stop_requested = Arc::new(AtomicBool::new(false));
+        let thread_stop_requested = stop_requested.clone();
+        let join_handle = ThreadBuilder::new()
+            .name("NewBlockNotifier".to_owned())
+            .spawn(move || {
+                log::info!(logger, "New block notifier thread started.");
+                while !thread_stop_requested.load(Ordering::SeqCst) {
+                    match block_provider.num_blocks() {
+                        Ok(num_blocks) => notifier.notify(num_blocks),
+                        Err(err) => log::error!(logger, "Could not get num blocks: {}", err),
+                    }
+                    std::thread::sleep(poll_interval);
+                }
+                log::info!(logger, "New block notifier thread stopped.");
+            })
+            .expect("Could not spawn thread");
+        Self {
+            join_handle: Some(join_handle),
+            stop_requested,
+        }
+    }
+
+    /// Stop and join the thread
+    pub fn stop(&mut self) -> Result<(), ()> {
+        if let Some(join_handle) = self.join_handle.take() {
+            self.stop_requested.store(true, Ordering::SeqCst);
+            join_handle.join().map_err(|_| ())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for NewBlockNotifierThread {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    #[test]
+    fn notifies_subscribers_without_a_backlog() {
+        let notifier = NewBlockNotifier::new(10, 10);
+        let mut receiver = notifier.subscribe().unwrap();
+
+        notifier.notify(10);
+        notifier.notify(11);
+        notifier.notify(12);
+
+        assert_eq!(receiver.try_next().unwrap(), Some(10));
+        assert!(receiver.try_next().is_err());
+
+        notifier.notify(13);
+        assert_eq!(receiver.try_next().unwrap(), Some(13));
+    }
+
+    #[test]
+    fn forgets_dropped_subscribers() {
+        let notifier = NewBlockNotifier::new(10, 10);
+        let receiver = notifier.subscribe().unwrap();
+        let mut other_receiver = notifier.subscribe().unwrap();
+        drop(receiver);
+
+        notifier.notify(5);
+
+        assert_eq!(notifier.subscribers.lock().unwrap().len(), 1);
+        assert_eq!(futures::executor::block_on(other_receiver.next()), Some(5));
+    }
+
+    #[test]
+    fn limits_subscriptions() {
+        let notifier = NewBlockNotifier::new(2, 10);
+        let receiver = notifier.subscribe().unwrap();
+        let _other_receiver = notifier.subscribe().unwrap();
+        assert!(notifier.subscribe().is_none());
+
+        // Ended subscriptions don't count towards the limit.
+        drop(receiver);
+        assert!(notifier.subscribe().is_some());
+    }
+}
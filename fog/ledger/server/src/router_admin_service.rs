@@ -1,46 +1,151 @@
 // Copyright (c) 2018-2022 The MobileCoin Foundation
 
-use crate::SVC_COUNTERS;
+use crate::{
+    shard_coverage::ShardCoverage,
+    shard_epoch::ShardEpoch,
+    shard_handoff::{ShardHandoff, ShardHandoffError},
+    SVC_COUNTERS,
+};
 use grpcio::{ChannelBuilder, RpcContext, RpcStatus, UnarySink};
 use itertools::Itertools;
 use mc_common::logger::{log, Logger};
 use mc_fog_api::{
-    fog_common::AddShardRequest,
+    fog_common::{AddShardRequest, CancelQueryRequest, InFlightQueries, InFlightQuery},
+    ledger::{
+        BlockedStoreMeasurements, CompleteShardHandoffRequest, ShardHandoffRequest,
+        ShardHandoffStatus,
+    },
     ledger_grpc::{KeyImageStoreApiClient, LedgerRouterAdminApi},
 };
-use mc_fog_uri::KeyImageStoreUri;
+use mc_fog_ledger_enclave::{LedgerEnclaveProxy, Result as EnclaveResult};
+use mc_fog_uri::{ConnectionUri, KeyImageStoreUri};
 use mc_util_grpc::{
-    rpc_invalid_arg_error, rpc_logger, rpc_precondition_error, send_result,
-    ConnectionUriGrpcioChannel, Empty,
+    record_audit_event, rpc_internal_error, rpc_invalid_arg_error, rpc_logger,
+    rpc_precondition_error, send_result, AuditEvent, ConnectionUriGrpcioChannel, Empty,
+    InFlightRequests,
 };
+use mc_util_metrics::service_metrics;
+use mc_util_uri::AdminUri;
 use std::{
     collections::HashMap,
     str::FromStr,
-    sync::{Arc, RwLock},
+    sync::{Arc, Mutex, RwLock},
 };
 
 #[derive(Clone)]
-pub struct LedgerRouterAdminService {
+pub struct LedgerRouterAdminService<E: LedgerEnclaveProxy> {
+    enclave: E,
     shard_clients: Arc<RwLock<HashMap<KeyImageStoreUri, Arc<KeyImageStoreApiClient>>>>,
+    shard_epoch: ShardEpoch,
+    shard_coverage: Arc<ShardCoverage>,
+    /// The store measurements blocked in the enclave, which the enclave
+    /// doesn't report back.
+    blocked_store_measurements: Arc<Mutex<Vec<[u8; 32]>>>,
+    shard_handoff: Arc<ShardHandoff>,
+    /// The client streams being handled by the router.
+    in_flight: Arc<InFlightRequests>,
     logger: Logger,
 }
 
-impl LedgerRouterAdminService {
+impl<E: LedgerEnclaveProxy> LedgerRouterAdminService<E> {
     pub fn new(
+        enclave: E,
         shard_clients: Arc<RwLock<HashMap<KeyImageStoreUri, Arc<KeyImageStoreApiClient>>>>,
+        shard_epoch: ShardEpoch,
+        shard_coverage: Arc<ShardCoverage>,
+        in_flight: Arc<InFlightRequests>,
         logger: Logger,
     ) -> Self {
+        let shard_handoff = Arc::new(ShardHandoff::new(
+            shard_clients.clone(),
+            shard_epoch.clone(),
+            shard_coverage.clone(),
+            logger.clone(),
+        ));
         Self {
+            enclave,
             shard_clients,
+            shard_epoch,
+            shard_coverage,
+            blocked_store_measurements: Default::default(),
+            shard_handoff,
+            in_flight,
             logger,
         }
     }
 
+    /// Block the enclave from attesting with Key Image Stores whose enclave
+    /// has one of the given MRENCLAVE values, replacing any previous list.
+    ///
+    /// Sessions with stores which are now blocked are closed at once. Queries
+    /// fail while a blocked store remains in the shard list, since the router
+    /// can't attest with it again, so it should be replaced by a store
+    /// running a good build.
+    pub fn set_blocked_store_measurements(&self, measurements: Vec<[u8; 32]>) -> EnclaveResult<()> {
+        let mut blocked_store_measurements = self
+            .blocked_store_measurements
+            .lock()
+            .expect("Mutex Poisoned");
+        let closed_stores = self
+            .enclave
+            .set_blocked_store_measurements(measurements.clone())?;
+        log::info!(
+            self.logger,
+            "Blocked {} store measurements, closing sessions with {:?}",
+            measurements.len(),
+            closed_stores
+        );
+        record_audit_event(
+            AuditEvent::StoreMeasurementsBlocked {
+                mr_enclaves: measurements.iter().map(hex::encode).collect(),
+                closed_stores: closed_stores.iter().map(ToString::to_string).collect(),
+            },
+            &self.logger,
+        );
+        *blocked_store_measurements = measurements;
+        Ok(())
+    }
+
+    fn set_blocked_store_measurements_impl(
+        &self,
+        request: BlockedStoreMeasurements,
+        logger: &Logger,
+    ) -> Result<Empty, RpcStatus> {
+        let measurements = request
+            .get_mr_enclaves()
+            .iter()
+            .map(|mr_enclave| <[u8; 32]>::try_from(&mr_enclave[..]))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| {
+                rpc_invalid_arg_error(
+                    "set_blocked_store_measurements",
+                    "Each MRENCLAVE must be 32 bytes".to_owned(),
+                    logger,
+                )
+            })?;
+        self.set_blocked_store_measurements(measurements)
+            .map_err(|err| rpc_internal_error("set_blocked_store_measurements", err, logger))?;
+        Ok(Empty::new())
+    }
+
+    fn get_blocked_store_measurements_impl(&self) -> BlockedStoreMeasurements {
+        let mut response = BlockedStoreMeasurements::new();
+        response.set_mr_enclaves(
+            self.blocked_store_measurements
+                .lock()
+                .expect("Mutex Poisoned")
+                .iter()
+                .map(|mr_enclave| mr_enclave.to_vec())
+                .collect(),
+        );
+        response
+    }
+
     fn add_shard_impl(&mut self, shard_uri: &str, logger: &Logger) -> Result<Empty, RpcStatus> {
         let key_image_store_uri = KeyImageStoreUri::from_str(shard_uri).map_err(|_| {
             rpc_invalid_arg_error(
                 "add_shard",
-                format!("Shard uri string {shard_uri} is invalid"),
+                "Shard uri string is invalid".to_owned(),
                 logger,
             )
         })?;
@@ -48,7 +153,10 @@ impl LedgerRouterAdminService {
         if shard_clients.keys().contains(&key_image_store_uri) {
             let error = rpc_precondition_error(
                 "add_shard",
-                format!("Shard uri {shard_uri} already exists in the shard list"),
+                format!(
+                    "Shard uri {} already exists in the shard list",
+                    key_image_store_uri.redacted()
+                ),
                 logger,
             );
             return Err(error);
@@ -63,16 +171,108 @@ impl LedgerRouterAdminService {
                 .keepalive_permit_without_calls(false)
                 .connect_to_uri(&key_image_store_uri, logger),
         );
+        record_audit_event(
+            AuditEvent::ShardAdded {
+                shard_uri: key_image_store_uri.redacted(),
+            },
+            logger,
+        );
         shard_clients.insert(key_image_store_uri, Arc::new(key_image_store_client));
+        self.shard_epoch.advance();
+        drop(shard_clients);
+
+        self.shard_coverage.recheck();
 
         Ok(Empty::new())
     }
+
+    fn begin_shard_handoff_impl(
+        &self,
+        request: ShardHandoffRequest,
+        logger: &Logger,
+    ) -> Result<ShardHandoffStatus, RpcStatus> {
+        let retiring = request
+            .get_retiring_shard_uris()
+            .iter()
+            .map(|uri| KeyImageStoreUri::from_str(uri))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| rpc_invalid_arg_error("retiring_shard_uris", err, logger))?;
+        let incoming = request
+            .get_incoming_shards()
+            .iter()
+            .map(|shard| {
+                let shard_uri = KeyImageStoreUri::from_str(shard.get_shard_uri())
+                    .map_err(|err| rpc_invalid_arg_error("shard_uri", err, logger))?;
+                let admin_uri = AdminUri::from_str(shard.get_admin_uri())
+                    .map_err(|err| rpc_invalid_arg_error("admin_uri", err, logger))?;
+                Ok((shard_uri, admin_uri))
+            })
+            .collect::<Result<Vec<_>, RpcStatus>>()?;
+
+        self.shard_handoff
+            .begin(retiring, incoming)
+            .map_err(|err| shard_handoff_error("begin_shard_handoff", err, logger))?;
+        Ok(self.shard_handoff.status())
+    }
+
+    fn complete_shard_handoff_impl(
+        &self,
+        request: CompleteShardHandoffRequest,
+        logger: &Logger,
+    ) -> Result<ShardHandoffStatus, RpcStatus> {
+        self.shard_handoff
+            .complete(request.get_force())
+            .map_err(|err| shard_handoff_error("complete_shard_handoff", err, logger))
+    }
+
+    fn cancel_shard_handoff_impl(&self, logger: &Logger) -> Result<Empty, RpcStatus> {
+        self.shard_handoff
+            .cancel()
+            .map_err(|err| shard_handoff_error("cancel_shard_handoff", err, logger))?;
+        Ok(Empty::new())
+    }
+
+    fn get_in_flight_queries_impl(&self) -> InFlightQueries {
+        let mut response = InFlightQueries::new();
+        response.set_queries(self.in_flight.list().iter().map(Into::into).collect());
+        response
+    }
+
+    fn cancel_query_impl(&self, id: u64, logger: &Logger) -> Result<InFlightQuery, RpcStatus> {
+        let info = self.in_flight.cancel(id).ok_or_else(|| {
+            rpc_precondition_error("cancel_query", format!("No in-flight query {id}"), logger)
+        })?;
+        log::info!(logger, "Cancelled in-flight query {}: {:?}", id, info);
+        record_audit_event(
+            AuditEvent::QueryCancelled {
+                method: info.method.clone(),
+                peer: info.peer.clone(),
+            },
+            logger,
+        );
+        Ok((&info).into())
+    }
 }
 
-impl LedgerRouterAdminApi for LedgerRouterAdminService {
+/// Requests for a handoff which doesn't fit the router's shards are invalid,
+/// while requests made at the wrong point of a handoff fail their
+/// precondition.
+fn shard_handoff_error(context: &str, err: ShardHandoffError, logger: &Logger) -> RpcStatus {
+    match err {
+        ShardHandoffError::AlreadyInProgress
+        | ShardHandoffError::NotInProgress
+        | ShardHandoffError::NotReady(_) => rpc_precondition_error(context, err, logger),
+        ShardHandoffError::NoIncomingShards
+        | ShardHandoffError::UnknownShard(_)
+        | ShardHandoffError::DuplicateShard(_)
+        | ShardHandoffError::InvalidUri(_, _) => rpc_invalid_arg_error(context, err, logger),
+    }
+}
+
+#[service_metrics(SVC_COUNTERS)]
+impl<E: LedgerEnclaveProxy> LedgerRouterAdminApi for LedgerRouterAdminService<E> {
     fn add_shard(&mut self, ctx: RpcContext, request: AddShardRequest, sink: UnarySink<Empty>) {
         log::info!(self.logger, "Request received in add_shard fn");
-        let _timer = SVC_COUNTERS.req(&ctx);
         mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
             send_result(
                 ctx,
@@ -82,4 +282,112 @@ impl LedgerRouterAdminApi for LedgerRouterAdminService {
             );
         });
     }
+
+    fn set_blocked_store_measurements(
+        &mut self,
+        ctx: RpcContext,
+        request: BlockedStoreMeasurements,
+        sink: UnarySink<Empty>,
+    ) {
+        mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
+            send_result(
+                ctx,
+                sink,
+                self.set_blocked_store_measurements_impl(request, logger),
+                logger,
+            );
+        });
+    }
+
+    fn get_blocked_store_measurements(
+        &mut self,
+        ctx: RpcContext,
+        _request: Empty,
+        sink: UnarySink<BlockedStoreMeasurements>,
+    ) {
+        mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
+            send_result(
+                ctx,
+                sink,
+                Ok(self.get_blocked_store_measurements_impl()),
+                logger,
+            );
+        });
+    }
+
+    fn begin_shard_handoff(
+        &mut self,
+        ctx: RpcContext,
+        request: ShardHandoffRequest,
+        sink: UnarySink<ShardHandoffStatus>,
+    ) {
+        mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
+            send_result(
+                ctx,
+                sink,
+                self.begin_shard_handoff_impl(request, logger),
+                logger,
+            );
+        });
+    }
+
+    fn get_shard_handoff_status(
+        &mut self,
+        ctx: RpcContext,
+        _request: Empty,
+        sink: UnarySink<ShardHandoffStatus>,
+    ) {
+        mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
+            send_result(ctx, sink, Ok(self.shard_handoff.status()), logger);
+        });
+    }
+
+    fn complete_shard_handoff(
+        &mut self,
+        ctx: RpcContext,
+        request: CompleteShardHandoffRequest,
+        sink: UnarySink<ShardHandoffStatus>,
+    ) {
+        mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
+            send_result(
+                ctx,
+                sink,
+                self.complete_shard_handoff_impl(request, logger),
+                logger,
+            );
+        });
+    }
+
+    fn cancel_shard_handoff(&mut self, ctx: RpcContext, _request: Empty, sink: UnarySink<Empty>) {
+        mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
+            send_result(ctx, sink, self.cancel_shard_handoff_impl(logger), logger);
+        });
+    }
+
+    fn get_in_flight_queries(
+        &mut self,
+        ctx: RpcContext,
+        _request: Empty,
+        sink: UnarySink<InFlightQueries>,
+    ) {
+        mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
+            send_result(ctx, sink, Ok(self.get_in_flight_queries_impl()), logger);
+        });
+    }
+
+    fn cancel_query(
+        &mut self,
+        ctx: RpcContext,
+        request: CancelQueryRequest,
+        sink: UnarySink<InFlightQuery>,
+    ) {
+        mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
+            send_result(
+                ctx,
+                sink,
+                self.cancel_query_impl(request.get_id(), logger),
+                logger,
+            );
+        });
+    }
 }
@@ -3,17 +3,27 @@
 use crate::{
     error::{router_server_err_to_rpc_status, RouterServerError},
     metrics::*,
-    SVC_COUNTERS,
+    new_block_notifier::NewBlockNotifier,
+    shard_coverage::ShardCoverage,
+    shard_epoch::ShardSnapshot,
+    sharding_strategy::{EpochShardingStrategy, ShardingStrategy},
+    ConcurrencyLimiter, SVC_COUNTERS,
+};
+use futures::{
+    channel::mpsc::Receiver,
+    future::{select, try_join_all, Either},
+    stream::FuturesUnordered,
+    SinkExt, StreamExt, TryStreamExt,
 };
-use futures::{future::try_join_all, SinkExt, TryStreamExt};
 use grpcio::{ChannelBuilder, DuplexSink, RequestStream, RpcStatus, WriteFlags};
 use mc_attest_api::attest;
-use mc_attest_enclave_api::{EnclaveMessage, NonceSession};
+use mc_attest_enclave_api::{EnclaveMessage, NonceSession, SealedClientMessage};
 use mc_common::{
     logger::{log, Logger},
     ResponderId,
 };
 use mc_fog_api::{
+    fog_common,
     ledger::{
         LedgerRequest, LedgerRequest_oneof_request_data, LedgerResponse, MultiKeyImageStoreRequest,
         MultiKeyImageStoreResponse, MultiKeyImageStoreResponseStatus,
@@ -21,18 +31,36 @@ use mc_fog_api::{
     ledger_grpc::KeyImageStoreApiClient,
 };
 use mc_fog_ledger_enclave::LedgerEnclaveProxy;
+use mc_fog_ledger_enclave_api::Error as LedgerEnclaveError;
+use mc_fog_types::common::BlockRange;
 use mc_fog_uri::{ConnectionUri, KeyImageStoreUri};
-use mc_util_grpc::{rpc_invalid_arg_error, ConnectionUriGrpcioChannel, ResponseStatus};
+use mc_util_grpc::{
+    record_audit_event, rpc_invalid_arg_error, rpc_precondition_error, rpc_unavailable_error,
+    AuditEvent, ConnectionUriGrpcioChannel, InFlightRequest, ResponseStatus,
+};
 use mc_util_metrics::GrpcMethodName;
 use mc_util_telemetry::{create_context, tracer, BoxedTracer, FutureExt, Tracer};
-use std::{collections::BTreeMap, str::FromStr, sync::Arc};
+use protobuf::Message;
+use std::{collections::BTreeMap, future::Future, str::FromStr, sync::Arc};
 
 /// Handles a series of requests sent by the Fog Ledger Router client,
 /// routing them out to shards.
+///
+/// All requests are routed to the shards in `shards`, which were the router's
+/// shards when the stream was opened.
+///
+/// Once the client subscribes to key images, the stream is answered with an
+/// update for them once the key image stores have added new blocks of the
+/// ledger, until the client cancels it. The client may close its side of the
+/// stream after subscribing.
 pub async fn handle_requests<E>(
     method_name: GrpcMethodName,
-    shard_clients: Vec<Arc<KeyImageStoreApiClient>>,
+    shards: ShardSnapshot,
+    shard_coverage: Arc<ShardCoverage>,
+    check_key_images_limiter: Arc<ConcurrencyLimiter>,
+    new_block_notifier: Arc<NewBlockNotifier>,
     enclave: E,
+    in_flight: InFlightRequest,
     mut requests: RequestStream<LedgerRequest>,
     mut responses: DuplexSink<LedgerResponse>,
     query_retries: usize,
@@ -41,28 +69,148 @@ pub async fn handle_requests<E>(
 where
     E: LedgerEnclaveProxy,
 {
-    while let Some(request) = requests.try_next().await? {
+    let mut subscription: Option<KeyImageSubscription> = None;
+    let mut requests_open = true;
+    loop {
+        in_flight.set_state(if subscription.is_some() {
+            "waiting for blocks"
+        } else {
+            "idle"
+        });
+        let event = match subscription.as_mut() {
+            None => match requests.try_next().await? {
+                Some(request) => StreamEvent::Request(request),
+                None => break,
+            },
+            Some(subscription) if requests_open => {
+                match select(requests.try_next(), subscription.new_blocks.next()).await {
+                    Either::Left((request, _)) => match request? {
+                        Some(request) => StreamEvent::Request(request),
+                        None => {
+                            requests_open = false;
+                            continue;
+                        }
+                    },
+                    Either::Right((Some(num_blocks), _)) => StreamEvent::NewBlock(num_blocks),
+                    Either::Right((None, _)) => break,
+                }
+            }
+            Some(subscription) => match subscription.new_blocks.next().await {
+                Some(num_blocks) => StreamEvent::NewBlock(num_blocks),
+                None => break,
+            },
+        };
+
+        let request = match event {
+            StreamEvent::Request(request) => request,
+            StreamEvent::NewBlock(num_blocks) => {
+                let Some(subscription) = subscription.as_mut() else {
+                    continue;
+                };
+                if num_blocks <= subscription.num_blocks_checked {
+                    continue;
+                }
+                in_flight.set_state("querying shards");
+                let result = check_subscribed_key_images(
+                    subscription.sealed_query.clone(),
+                    shards.shard_clients.clone(),
+                    &shard_coverage,
+                    &check_key_images_limiter,
+                    enclave.clone(),
+                    query_retries,
+                    logger.clone(),
+                )
+                .await;
+                match result {
+                    Ok((mut response, stores_num_blocks)) => {
+                        // Until the stores have added some of the new blocks,
+                        // checking again can't find anything new, so the
+                        // update is left for a later notification.
+                        let num_blocks_checked = stores_num_blocks.min(num_blocks);
+                        if num_blocks_checked <= subscription.num_blocks_checked {
+                            continue;
+                        }
+                        subscription.num_blocks_checked = num_blocks_checked;
+                        response.shard_epoch = shards.epoch;
+                        in_flight.response_sent(response.compute_size());
+                        responses.send((response, WriteFlags::default())).await?
+                    }
+                    Err(rpc_status) => return responses.fail(rpc_status).await,
+                }
+                continue;
+            }
+        };
+
         // Per the comment thread on pull request #2976, this should be
         // req_impl() and not req().
         // This is so that one call of the original request() method is
         // reported per each actual request the client sends.
         let _timer = SVC_COUNTERS.req_impl(&method_name);
+        in_flight.request_received(request.compute_size());
+        in_flight.set_state("querying shards");
 
-        let result = handle_request(
-            request,
-            shard_clients.clone(),
-            enclave.clone(),
-            query_retries,
-            logger.clone(),
-        )
-        .await;
+        let result = if !shards.satisfies(request.shard_epoch) {
+            Err(rpc_precondition_error(
+                "shard_epoch",
+                format!(
+                    "Requested shard epoch {}, but this stream is pinned to shard epoch {}",
+                    request.shard_epoch, shards.epoch
+                ),
+                &logger,
+            ))
+        } else if let Some(LedgerRequest_oneof_request_data::subscribe_key_images(query)) =
+            request.request_data
+        {
+            handle_subscribe_request(
+                query,
+                &mut subscription,
+                &new_block_notifier,
+                shards.shard_clients.clone(),
+                &shard_coverage,
+                &check_key_images_limiter,
+                enclave.clone(),
+                query_retries,
+                logger.clone(),
+            )
+            .await
+        } else if subscription.is_some() {
+            Err(rpc_precondition_error(
+                "subscribe_key_images",
+                "This stream has a key image subscription, and only carries subscription requests"
+                    .to_owned(),
+                &logger,
+            ))
+        } else {
+            let partial_results = request.stream_partial_results.then_some(PartialResults {
+                sink: &mut responses,
+                shard_epoch: shards.epoch,
+            });
+            handle_request(
+                request,
+                partial_results,
+                shards.shard_clients.clone(),
+                &shard_coverage,
+                &check_key_images_limiter,
+                enclave.clone(),
+                query_retries,
+                logger.clone(),
+            )
+            .await
+        }
+        .map(|mut response| {
+            response.shard_epoch = shards.epoch;
+            response
+        });
 
         let response_status = ResponseStatus::from(&result);
         SVC_COUNTERS.resp_impl(&method_name, response_status.is_success);
         SVC_COUNTERS.status_code_impl(&method_name, response_status.code);
 
         match result {
-            Ok(response) => responses.send((response, WriteFlags::default())).await?,
+            Ok(response) => {
+                in_flight.response_sent(response.compute_size());
+                responses.send((response, WriteFlags::default())).await?
+            }
             Err(rpc_status) => return responses.fail(rpc_status).await,
         }
     }
@@ -70,11 +218,138 @@ where
     Ok(())
 }
 
+/// Something which happened on a client's stream.
+enum StreamEvent {
+    /// The client sent a request
+    Request(LedgerRequest),
+    /// The ledger has this many blocks, so the key images of the stream's
+    /// subscription should be checked again if the stores hadn't added all of
+    /// them the last time
+    NewBlock(u64),
+}
+
+/// The key images a stream is subscribed to.
+struct KeyImageSubscription {
+    /// The client's CheckKeyImagesRequest, sealed so that the enclave can
+    /// check it again for each new block
+    sealed_query: SealedClientMessage,
+
+    /// Receives the number of blocks in the ledger each time it is checked
+    new_blocks: Receiver<u64>,
+
+    /// The number of blocks of the ledger which every key image store had
+    /// added when the key images were last checked
+    num_blocks_checked: u64,
+}
+
+/// Handles a client's request to subscribe to key images, replacing the key
+/// images of the stream's subscription if it has one, and answers with their
+/// current state.
+async fn handle_subscribe_request<E>(
+    query: attest::Message,
+    subscription: &mut Option<KeyImageSubscription>,
+    new_block_notifier: &NewBlockNotifier,
+    shard_clients: Vec<Arc<KeyImageStoreApiClient>>,
+    shard_coverage: &ShardCoverage,
+    check_key_images_limiter: &Arc<ConcurrencyLimiter>,
+    enclave: E,
+    query_retries: usize,
+    logger: Logger,
+) -> Result<LedgerResponse, RpcStatus>
+where
+    E: LedgerEnclaveProxy,
+{
+    let sealed_query = seal_query(
+        &enclave,
+        query,
+        new_block_notifier.max_key_images_per_subscription(),
+        &logger,
+    )?;
+    match subscription.as_mut() {
+        Some(subscription) => subscription.sealed_query = sealed_query.clone(),
+        None => {
+            let new_blocks = new_block_notifier.subscribe().ok_or_else(|| {
+                rpc_unavailable_error(
+                    "subscribe_key_images",
+                    "The router has reached its limit on key image subscriptions".to_owned(),
+                    &logger,
+                )
+            })?;
+            *subscription = Some(KeyImageSubscription {
+                sealed_query: sealed_query.clone(),
+                new_blocks,
+                num_blocks_checked: 0,
+            })
+        }
+    }
+
+    let (response, stores_num_blocks) = check_subscribed_key_images(
+        sealed_query,
+        shard_clients,
+        shard_coverage,
+        check_key_images_limiter,
+        enclave,
+        query_retries,
+        logger,
+    )
+    .await?;
+    if let Some(subscription) = subscription.as_mut() {
+        subscription.num_blocks_checked = stores_num_blocks.min(shard_coverage.num_blocks());
+    }
+    Ok(response)
+}
+
+/// Checks the key images of a subscription, answering with a
+/// key_images_update, and the number of blocks every store had added.
+async fn check_subscribed_key_images<E>(
+    sealed_query: SealedClientMessage,
+    shard_clients: Vec<Arc<KeyImageStoreApiClient>>,
+    shard_coverage: &ShardCoverage,
+    check_key_images_limiter: &Arc<ConcurrencyLimiter>,
+    enclave: E,
+    query_retries: usize,
+    logger: Logger,
+) -> Result<(LedgerResponse, u64), RpcStatus>
+where
+    E: LedgerEnclaveProxy,
+{
+    let tracer = tracer!();
+    shard_coverage.check_can_serve(&logger)?;
+    let _permit = check_key_images_limiter.acquire(&logger).await?;
+    let (mut response, stores_num_blocks) = handle_sealed_query_request(
+        sealed_query,
+        None,
+        enclave,
+        shard_clients,
+        query_retries,
+        logger,
+        &tracer,
+    )
+    .with_context(create_context(&tracer, "subscribe_key_images"))
+    .await?;
+
+    let update = response.take_check_key_image_response();
+    response.set_key_images_update(update);
+    Ok((response, stores_num_blocks))
+}
+
+/// Where to send partial results of a check_key_images request, for clients
+/// which asked for them.
+pub struct PartialResults<'a> {
+    /// The stream the final response will also be sent on
+    pub sink: &'a mut DuplexSink<LedgerResponse>,
+    /// The shard epoch the stream is pinned to
+    pub shard_epoch: u64,
+}
+
 /// Handles a client's request by performing either an authentication or a
 /// query.
 pub async fn handle_request<E>(
     request: LedgerRequest,
+    partial_results: Option<PartialResults<'_>>,
     shard_clients: Vec<Arc<KeyImageStoreApiClient>>,
+    shard_coverage: &ShardCoverage,
+    check_key_images_limiter: &Arc<ConcurrencyLimiter>,
     enclave: E,
     query_retries: usize,
     logger: Logger,
@@ -88,8 +363,11 @@ where
             tracer.in_span("auth", |_cx| handle_auth_request(enclave, request, logger))
         }
         Some(LedgerRequest_oneof_request_data::check_key_images(request)) => {
+            shard_coverage.check_can_serve(&logger)?;
+            let _permit = check_key_images_limiter.acquire(&logger).await?;
             handle_query_request(
                 request,
+                partial_results,
                 enclave,
                 shard_clients,
                 query_retries,
@@ -99,6 +377,13 @@ where
             .with_context(create_context(&tracer, "check_key_images"))
             .await
         }
+        Some(LedgerRequest_oneof_request_data::subscribe_key_images(_)) => {
+            Err(rpc_invalid_arg_error(
+                "Inavlid LedgerRequest request",
+                "subscribe_key_images is only supported on streams".to_string(),
+                &logger,
+            ))
+        }
         None => {
             let rpc_status = rpc_invalid_arg_error(
                 "Inavlid LedgerRequest request",
@@ -124,6 +409,11 @@ pub struct ProcessedShardResponseData {
 
     /// New, successfully processed query responses.
     pub new_query_responses: Vec<(ResponderId, attest::NonceMessage)>,
+
+    /// The number of blocks of the ledger which every store that answered
+    /// successfully had added, as reported by the stores. See
+    /// [store_num_blocks].
+    pub stores_num_blocks: u64,
 }
 
 impl ProcessedShardResponseData {
@@ -131,12 +421,39 @@ impl ProcessedShardResponseData {
         shard_clients_for_retry: Vec<Arc<KeyImageStoreApiClient>>,
         store_uris_for_authentication: Vec<KeyImageStoreUri>,
         new_query_responses: Vec<(ResponderId, attest::NonceMessage)>,
+        stores_num_blocks: u64,
     ) -> Self {
         ProcessedShardResponseData {
             shard_clients_for_retry,
             store_uris_for_authentication,
             new_query_responses,
+            stores_num_blocks,
+        }
+    }
+}
+
+/// The number of blocks of the ledger which a store had added, judging from
+/// the processed block range it reported: every block of its epoch before
+/// the returned index.
+///
+/// A store which has added all of its epoch's blocks, or which didn't report
+/// its progress because it predates doing so, doesn't hold anything up and
+/// counts as having added every block.
+fn store_num_blocks(
+    store_uri: &KeyImageStoreUri,
+    processed_block_range: &fog_common::BlockRange,
+) -> u64 {
+    let processed_block_range = BlockRange::from(processed_block_range.clone());
+    if processed_block_range.end_block == 0 {
+        return u64::MAX;
+    }
+    match EpochShardingStrategy::try_from(store_uri.clone()) {
+        Ok(sharding_strategy)
+            if processed_block_range.end_block < sharding_strategy.get_block_range().end_block =>
+        {
+            processed_block_range.end_block
         }
+        _ => u64::MAX,
     }
 }
 
@@ -148,11 +465,16 @@ pub fn process_shard_responses(
     let mut shard_clients_for_retry = Vec::new();
     let mut store_uris_for_authentication = Vec::new();
     let mut new_query_responses = Vec::new();
+    let mut stores_num_blocks = u64::MAX;
 
     for (shard_client, mut response) in clients_and_responses {
         let store_uri = KeyImageStoreUri::from_str(response.get_store_uri())?;
         match response.get_status() {
             MultiKeyImageStoreResponseStatus::SUCCESS => {
+                stores_num_blocks = stores_num_blocks.min(store_num_blocks(
+                    &store_uri,
+                    response.get_processed_block_range(),
+                ));
                 let store_responder_id = store_uri.host_and_port_responder_id()?;
                 new_query_responses.push((store_responder_id, response.take_query_response()));
             }
@@ -194,6 +516,7 @@ pub fn process_shard_responses(
         shard_clients_for_retry,
         store_uris_for_authentication,
         new_query_responses,
+        stores_num_blocks,
     ))
 }
 
@@ -216,8 +539,12 @@ where
 }
 
 /// Handles a client's query request.
+///
+/// If `partial_results` is given, each time another shard responds, the
+/// results of the shards which have responded so far are sent to it.
 pub(crate) async fn handle_query_request<E>(
     query: attest::Message,
+    partial_results: Option<PartialResults<'_>>,
     enclave: E,
     shard_clients: Vec<Arc<KeyImageStoreApiClient>>,
     query_retries: usize,
@@ -227,17 +554,65 @@ pub(crate) async fn handle_query_request<E>(
 where
     E: LedgerEnclaveProxy,
 {
-    let mut query_responses: BTreeMap<ResponderId, EnclaveMessage<NonceSession>> = BTreeMap::new();
-    let mut shards_to_query = shard_clients.clone();
-    let sealed_query = enclave
-        .decrypt_and_seal_query(query.into())
-        .map_err(|err| {
-            router_server_err_to_rpc_status(
+    let sealed_query = seal_query(&enclave, query, 0, &logger)?;
+    let (response, _) = handle_sealed_query_request(
+        sealed_query,
+        partial_results,
+        enclave,
+        shard_clients,
+        query_retries,
+        logger,
+        tracer,
+    )
+    .await?;
+    Ok(response)
+}
+
+/// Decrypts a client's query, sealing it so that the enclave can unseal it
+/// once for each time it is sent to the shards. Queries with more than
+/// `max_key_images` key images are refused, unless it is zero.
+fn seal_query<E>(
+    enclave: &E,
+    query: attest::Message,
+    max_key_images: usize,
+    logger: &Logger,
+) -> Result<SealedClientMessage, RpcStatus>
+where
+    E: LedgerEnclaveProxy,
+{
+    enclave
+        .decrypt_and_seal_query(query.into(), max_key_images)
+        .map_err(|err| match err {
+            LedgerEnclaveError::TooManyKeyImages(..) | LedgerEnclaveError::UnsupportedQuery(_) => {
+                rpc_invalid_arg_error("Key Images Query", err, logger)
+            }
+            err => router_server_err_to_rpc_status(
                 "Key Images Query: internal encryption error",
                 err.into(),
                 logger.clone(),
-            )
-        })?;
+            ),
+        })
+}
+
+/// Sends a sealed client query to the shards, and collates their responses.
+///
+/// Also returns the number of blocks of the ledger which every store had
+/// added, as reported by the stores.
+async fn handle_sealed_query_request<E>(
+    sealed_query: SealedClientMessage,
+    mut partial_results: Option<PartialResults<'_>>,
+    enclave: E,
+    shard_clients: Vec<Arc<KeyImageStoreApiClient>>,
+    query_retries: usize,
+    logger: Logger,
+    tracer: &BoxedTracer,
+) -> Result<(LedgerResponse, u64), RpcStatus>
+where
+    E: LedgerEnclaveProxy,
+{
+    let mut query_responses: BTreeMap<ResponderId, EnclaveMessage<NonceSession>> = BTreeMap::new();
+    let mut shards_to_query = shard_clients.clone();
+    let mut stores_num_blocks = u64::MAX;
 
     // The retry logic here is:
     // Set retries remaining to query_retries
@@ -262,24 +637,30 @@ where
                     })
             })?
             .into();
-        let clients_and_responses =
-            route_query(&multi_ledger_store_query_request, shards_to_query.clone())
-                .with_context(create_context(
-                    tracer,
-                    "send_multi_key_image_request_to_shards",
-                ))
-                .await
-                .map_err(|err| {
-                    router_server_err_to_rpc_status(
-                        "Key Images Query: internal query routing error",
-                        err,
-                        logger.clone(),
-                    )
-                })?;
+        let mut shard_responses =
+            route_query(&multi_ledger_store_query_request, shards_to_query.clone());
+        let mut processed_shard_response_data =
+            ProcessedShardResponseData::new(Vec::new(), Vec::new(), Vec::new(), u64::MAX);
 
-        let processed_shard_response_data =
-            tracer.in_span("process_key_image_shard_responses", |_cx| {
-                process_shard_responses(clients_and_responses, logger.clone()).map_err(|err| {
+        // Process each shard's response as it arrives, so that partial results
+        // can be sent without waiting for the slowest shard.
+        while let Some(client_and_response) = shard_responses
+            .try_next()
+            .with_context(create_context(
+                tracer,
+                "send_multi_key_image_request_to_shards",
+            ))
+            .await
+            .map_err(|err| {
+                router_server_err_to_rpc_status(
+                    "Key Images Query: internal query routing error",
+                    err,
+                    logger.clone(),
+                )
+            })?
+        {
+            let processed = tracer.in_span("process_key_image_shard_responses", |_cx| {
+                process_shard_responses(vec![client_and_response], logger.clone()).map_err(|err| {
                     router_server_err_to_rpc_status(
                         "Key Images Query: internal query response processing",
                         err,
@@ -288,11 +669,43 @@ where
                 })
             })?;
 
-        for (store_responder_id, new_query_response) in processed_shard_response_data
-            .new_query_responses
-            .into_iter()
-        {
-            query_responses.insert(store_responder_id, new_query_response.into());
+            processed_shard_response_data
+                .shard_clients_for_retry
+                .extend(processed.shard_clients_for_retry);
+            processed_shard_response_data
+                .store_uris_for_authentication
+                .extend(processed.store_uris_for_authentication);
+            stores_num_blocks = stores_num_blocks.min(processed.stores_num_blocks);
+            let has_new_query_responses = !processed.new_query_responses.is_empty();
+            for (store_responder_id, new_query_response) in processed.new_query_responses {
+                query_responses.insert(store_responder_id, new_query_response.into());
+            }
+
+            if let Some(partial_results) = partial_results.as_mut() {
+                if has_new_query_responses && query_responses.len() < shard_clients.len() {
+                    let mut response = collate_query_responses(
+                        &enclave,
+                        sealed_query.clone(),
+                        query_responses.clone(),
+                        shard_clients.len(),
+                        &logger,
+                        tracer,
+                    )?;
+                    response.partial = true;
+                    response.shard_epoch = partial_results.shard_epoch;
+                    partial_results
+                        .sink
+                        .send((response, WriteFlags::default()))
+                        .await
+                        .map_err(|err| {
+                            router_server_err_to_rpc_status(
+                                "Key Images Query: sending partial results",
+                                err.into(),
+                                logger.clone(),
+                            )
+                        })?;
+                }
+            }
         }
 
         if query_responses.len() >= shard_clients.len() {
@@ -324,6 +737,30 @@ where
         ));
     }
 
+    let response = collate_query_responses(
+        &enclave,
+        sealed_query,
+        query_responses,
+        shard_clients.len(),
+        &logger,
+        tracer,
+    )?;
+    Ok((response, stores_num_blocks))
+}
+
+/// Collates the shards' responses into a response for the client.
+fn collate_query_responses<E>(
+    enclave: &E,
+    sealed_query: SealedClientMessage,
+    query_responses: BTreeMap<ResponderId, EnclaveMessage<NonceSession>>,
+    shards_total: usize,
+    logger: &Logger,
+    tracer: &BoxedTracer,
+) -> Result<LedgerResponse, RpcStatus>
+where
+    E: LedgerEnclaveProxy,
+{
+    let shards_responded = query_responses.len();
     let query_response = tracer.in_span("collate_key_image_responses", |_cx| {
         enclave
             .collate_shard_query_responses(sealed_query, query_responses)
@@ -338,18 +775,28 @@ where
 
     let mut response = LedgerResponse::new();
     response.set_check_key_image_response(query_response.into());
+    response.shards_responded = shards_responded as u32;
+    response.shards_total = shards_total as u32;
     Ok(response)
 }
 
-/// Sends a client's query request to all of the Fog Ledger shards.
-async fn route_query(
+/// Sends a client's query request to all of the Fog Ledger shards, yielding
+/// their responses in the order they arrive.
+fn route_query(
     request: &MultiKeyImageStoreRequest,
     shard_clients: Vec<Arc<KeyImageStoreApiClient>>,
-) -> Result<Vec<(Arc<KeyImageStoreApiClient>, MultiKeyImageStoreResponse)>, RouterServerError> {
-    let responses = shard_clients
+) -> FuturesUnordered<
+    impl Future<
+            Output = Result<
+                (Arc<KeyImageStoreApiClient>, MultiKeyImageStoreResponse),
+                RouterServerError,
+            >,
+        > + '_,
+> {
+    shard_clients
         .into_iter()
-        .map(|shard_client| query_shard(request, shard_client));
-    try_join_all(responses).await
+        .map(|shard_client| query_shard(request, shard_client))
+        .collect()
 }
 
 /// Sends a client's query request to one of the Fog Ledger shards.
@@ -381,11 +828,30 @@ async fn authenticate_ledger_stores<E: LedgerEnclaveProxy>(
     })
 }
 
-// Authenticates a Fog Ledger Store that has previously not been authenticated.
-async fn authenticate_ledger_store<E: LedgerEnclaveProxy>(
+// Authenticates a Fog Ledger Store that has previously not been authenticated,
+// and records the outcome in the audit log.
+pub(crate) async fn authenticate_ledger_store<E: LedgerEnclaveProxy>(
     enclave: E,
     ledger_store_url: KeyImageStoreUri,
     logger: Logger,
+) -> Result<(), RouterServerError> {
+    let store_uri = ledger_store_url.redacted();
+    let result = attest_ledger_store(enclave, ledger_store_url, &logger).await;
+    let event = match &result {
+        Ok(()) => AuditEvent::StoreAttested { store_uri },
+        Err(err) => AuditEvent::StoreAttestationFailed {
+            store_uri,
+            error: err.to_string(),
+        },
+    };
+    record_audit_event(event, &logger);
+    result
+}
+
+async fn attest_ledger_store<E: LedgerEnclaveProxy>(
+    enclave: E,
+    ledger_store_url: KeyImageStoreUri,
+    logger: &Logger,
 ) -> Result<(), RouterServerError> {
     let ledger_store_id = ledger_store_url.responder_id()?;
     let client_auth_request = enclave.ledger_store_init(ledger_store_id.clone())?;
@@ -397,7 +863,7 @@ async fn authenticate_ledger_store<E: LedgerEnclaveProxy>(
     let ledger_store_client = KeyImageStoreApiClient::new(
         ChannelBuilder::default_channel_builder(grpc_env)
             .keepalive_permit_without_calls(false)
-            .connect_to_uri(&ledger_store_url, &logger),
+            .connect_to_uri(&ledger_store_url, logger),
     );
 
     let auth_unary_receiver = ledger_store_client.auth_async(&client_auth_request.into())?;
@@ -1,8 +1,17 @@
 // Copyright (c) 2018-2022 The MobileCoin Foundation
 
 use crate::{
-    config::LedgerRouterConfig, counters, router_admin_service::LedgerRouterAdminService,
-    router_service::LedgerRouterService, BlockService, MerkleProofService, UntrustedTxOutService,
+    admission_control::CidrAdmissionControl,
+    config::LedgerRouterConfig,
+    counters,
+    merkle_proof_cache::{MerkleProofCache, MerkleProofCacheThread},
+    new_block_notifier::{NewBlockNotifier, NewBlockNotifierThread},
+    router_admin_service::LedgerRouterAdminService,
+    router_service::LedgerRouterService,
+    shard_coverage::ShardCoverage,
+    shard_epoch::ShardEpoch,
+    store_attestation::StoreAttestationThreads,
+    BlockService, ConcurrencyLimiter, MerkleProofService, UntrustedTxOutService,
 };
 use futures::executor::block_on;
 use grpcio::ChannelBuilder;
@@ -12,13 +21,15 @@ use mc_common::{
 };
 use mc_fog_api::ledger_grpc;
 use mc_fog_block_provider::BlockProvider;
-use mc_fog_ledger_enclave::LedgerEnclaveProxy;
+use mc_fog_ledger_enclave::{LedgerEnclave, LedgerEnclaveProxy};
 use mc_fog_uri::{ConnectionUri, FogLedgerUri};
 use mc_sgx_report_cache_untrusted::ReportCacheThread;
 use mc_util_grpc::{
-    AdminServer, AnonymousAuthenticator, Authenticator, ConnectionUriGrpcioChannel,
-    ConnectionUriGrpcioServer, TokenAuthenticator,
+    record_audit_event, AdminServer, AnonymousAuthenticator, AuditEvent, Authenticator,
+    ConnectionUriGrpcioChannel, ConnectionUriGrpcioServer, InFlightRequests, Interceptor,
+    InterceptorChain, ReadinessIndicator, TokenAuthenticator,
 };
+use mc_util_parse::SeqDisplay;
 use mc_util_uri::AdminUri;
 use std::{
     collections::HashMap,
@@ -30,12 +41,20 @@ where
     E: LedgerEnclaveProxy,
 {
     router_server: grpcio::Server,
-    admin_service: LedgerRouterAdminService,
-    client_listen_uri: FogLedgerUri,
+    admin_service: LedgerRouterAdminService<E>,
+    client_listen_uris: Vec<FogLedgerUri>,
     admin_listen_uri: AdminUri,
     config: LedgerRouterConfig,
     enclave: E,
+    block_provider: Box<dyn BlockProvider>,
+    shard_coverage: Arc<ShardCoverage>,
+    merkle_proof_cache: Option<Arc<MerkleProofCache>>,
+    merkle_proof_cache_thread: Option<MerkleProofCacheThread>,
+    new_block_notifier: Arc<NewBlockNotifier>,
+    new_block_notifier_thread: Option<NewBlockNotifierThread>,
     report_cache_thread: Option<ReportCacheThread>,
+    readiness_indicator: ReadinessIndicator,
+    store_attestation_threads: Option<StoreAttestationThreads>,
     logger: Logger,
     admin_server: Option<AdminServer>,
 }
@@ -44,12 +63,33 @@ impl<E> LedgerRouterServer<E>
 where
     E: LedgerEnclaveProxy,
 {
+    /// Create a router admitting callers as configured by
+    /// `config.admission_control`.
     pub fn new(
         config: LedgerRouterConfig,
         enclave: E,
         block_provider: Box<dyn BlockProvider>,
         logger: Logger,
     ) -> LedgerRouterServer<E> {
+        let admission_control = CidrAdmissionControl::from_config(&config.admission_control);
+        Self::new_with_admission_control(config, enclave, block_provider, admission_control, logger)
+    }
+
+    /// Create a router which asks `admission_control` whether to serve each
+    /// client call, before the other layers of its interceptor chain, instead
+    /// of using `config.admission_control`.
+    pub fn new_with_admission_control(
+        config: LedgerRouterConfig,
+        enclave: E,
+        block_provider: Box<dyn BlockProvider>,
+        admission_control: impl Interceptor + 'static,
+        logger: Logger,
+    ) -> LedgerRouterServer<E> {
+        config
+            .audit_log
+            .init("fog_ledger_router")
+            .expect("Could not open audit log");
+
         let mut ledger_store_grpc_clients = HashMap::new();
         let grpc_env = Arc::new(
             grpcio::EnvBuilder::new()
@@ -65,9 +105,16 @@ where
             ledger_store_grpc_clients.insert(shard_uri, Arc::new(ledger_store_grpc_client));
         }
         let ledger_store_grpc_clients = Arc::new(RwLock::new(ledger_store_grpc_clients));
+        let shard_epoch = ShardEpoch::new();
+        let shard_coverage = Arc::new(ShardCoverage::new(
+            config.shard_coverage_policy,
+            ledger_store_grpc_clients.clone(),
+            logger.clone(),
+        ));
 
         let client_authenticator: Arc<dyn Authenticator + Sync + Send> =
             if let Some(shared_secret) = config.client_auth_token_secret.as_ref() {
+                record_audit_event(AuditEvent::auth_token_secret_loaded(shared_secret), &logger);
                 Arc::new(TokenAuthenticator::new(
                     *shared_secret,
                     config.client_auth_token_max_lifetime,
@@ -76,6 +123,20 @@ where
             } else {
                 Arc::new(AnonymousAuthenticator)
             };
+        let standard_interceptors = InterceptorChain::from_config(
+            &config.interceptors,
+            &config.chain_id,
+            client_authenticator,
+            logger.clone(),
+        );
+        // Rejected callers should cost as little as possible.
+        let client_interceptors = InterceptorChain::new()
+            .with(admission_control)
+            .then(standard_interceptors);
+
+        enclave
+            .set_padding_buckets(config.padding_buckets.clone())
+            .expect("Could not set padding buckets");
 
         let env = Arc::new(
             grpcio::EnvBuilder::new()
@@ -83,14 +144,44 @@ where
                 .build(),
         );
 
-        // Health check service - will be used in both router + admin interface
-        let health_service = mc_util_grpc::HealthService::new(None, logger.clone()).into_service();
+        // Health check service - will be used in both router + admin interface.
+        // We're not ready until we have attested with the configured stores.
+        let readiness_indicator = ReadinessIndicator::default();
+        let health_service = mc_util_grpc::HealthService::new(
+            Some(readiness_indicator.clone().into()),
+            logger.clone(),
+        )
+        .into_service();
+
+        // Limit expensive methods, so that they can't starve the others.
+        let limits = &config.concurrency_limits;
+        let check_key_images_limiter = Arc::new(ConcurrencyLimiter::new(
+            "check_key_images",
+            limits.check_key_images_max_concurrency,
+            limits.check_key_images_max_queued,
+        ));
+        let get_blocks_limiter = Arc::new(ConcurrencyLimiter::new(
+            "get_blocks",
+            limits.get_blocks_max_concurrency,
+            limits.get_blocks_max_queued,
+        ));
 
         // Build our router server.
         // Init ledger router service.
+        let new_block_notifier = Arc::new(NewBlockNotifier::new(
+            config.key_image_subscription_max_streams,
+            config.key_image_subscription_max_key_images,
+        ));
+        let in_flight = Arc::new(InFlightRequests::default());
         let ledger_service = LedgerRouterService::new(
             enclave.clone(),
             ledger_store_grpc_clients.clone(),
+            shard_epoch.clone(),
+            shard_coverage.clone(),
+            check_key_images_limiter,
+            client_interceptors.clone(),
+            new_block_notifier.clone(),
+            in_flight.clone(),
             config.query_retries,
             logger.clone(),
         );
@@ -101,61 +192,85 @@ where
         let unary_key_image_service = ledger_grpc::create_fog_key_image_api(ledger_service);
 
         // Init ledger router admin service.
-        let admin_service =
-            LedgerRouterAdminService::new(ledger_store_grpc_clients, logger.clone());
+        let admin_service = LedgerRouterAdminService::new(
+            enclave.clone(),
+            ledger_store_grpc_clients,
+            shard_epoch,
+            shard_coverage.clone(),
+            in_flight,
+            logger.clone(),
+        );
+        admin_service
+            .set_blocked_store_measurements(config.blocked_store_measurements.clone())
+            .expect("Could not block store measurements");
         log::debug!(logger, "Constructed Ledger Router Admin GRPC Service");
 
         // Non-routed servers and services
         // Init merkle proof service
+        let merkle_proof_cache = (config.merkle_proof_cache_size > 0)
+            .then(|| Arc::new(MerkleProofCache::new(config.merkle_proof_cache_size)));
         let merkle_proof_service =
             ledger_grpc::create_fog_merkle_proof_api(MerkleProofService::new(
-                config.chain_id.clone(),
                 block_provider.clone(),
                 enclave.clone(),
-                client_authenticator.clone(),
+                client_interceptors.clone(),
+                merkle_proof_cache.clone(),
                 logger.clone(),
             ));
         // Init untrusted tx out service
         let untrusted_tx_out_service =
             ledger_grpc::create_fog_untrusted_tx_out_api(UntrustedTxOutService::new(
-                config.chain_id.clone(),
                 block_provider.clone(),
-                client_authenticator.clone(),
+                client_interceptors.clone(),
                 logger.clone(),
             ));
         // Init block service
         let block_service = ledger_grpc::create_fog_block_api(BlockService::new(
-            config.chain_id.clone(),
-            block_provider,
-            client_authenticator,
+            block_provider.clone(),
+            client_interceptors,
+            get_blocks_limiter,
             logger.clone(),
         ));
 
         // Package service into grpc server
+        let client_listen_uris = core::iter::once(&config.client_listen_uri)
+            .chain(&config.additional_client_listen_uris)
+            .cloned()
+            .collect::<Vec<_>>();
         log::info!(
             logger,
             "Starting Ledger Router server on {}",
-            config.client_listen_uri.addr(),
+            SeqDisplay(client_listen_uris.iter().map(|uri| uri.addr())),
         );
 
-        let router_server = grpcio::ServerBuilder::new(env)
+        let router_server = config
+            .message_size
+            .apply_to_server(grpcio::ServerBuilder::new(env.clone()), env)
             .register_service(ledger_router_service)
             .register_service(unary_key_image_service)
             .register_service(merkle_proof_service)
             .register_service(untrusted_tx_out_service)
             .register_service(block_service)
             .register_service(health_service)
-            .build_using_uri(&config.client_listen_uri, logger.clone())
+            .build_using_uris(&client_listen_uris, logger.clone())
             .expect("Could not build Ledger Router Server");
 
         Self {
             router_server,
             admin_service,
-            client_listen_uri: config.client_listen_uri.clone(),
+            client_listen_uris,
             admin_listen_uri: config.admin_listen_uri.clone(),
             config,
             enclave,
+            block_provider,
+            shard_coverage,
+            merkle_proof_cache,
+            merkle_proof_cache_thread: None,
+            new_block_notifier,
+            new_block_notifier_thread: None,
             report_cache_thread: None,
+            readiness_indicator,
+            store_attestation_threads: None,
             logger,
             admin_server: None,
         }
@@ -163,6 +278,8 @@ where
 
     /// Starts the server
     pub fn start(&mut self) {
+        self.update_shard_coverage();
+
         self.report_cache_thread = Some(
             ReportCacheThread::start(
                 self.enclave.clone(),
@@ -172,11 +289,35 @@ where
             .expect("failed starting report cache thread"),
         );
 
+        self.store_attestation_threads = Some(StoreAttestationThreads::start(
+            self.enclave.clone(),
+            self.config.shard_uris.clone(),
+            self.config.store_attestation_max_backoff,
+            self.readiness_indicator.clone(),
+            self.logger.clone(),
+        ));
+
+        if let Some(cache) = self.merkle_proof_cache.as_ref() {
+            self.merkle_proof_cache_thread = Some(MerkleProofCacheThread::start(
+                cache.clone(),
+                self.block_provider.clone(),
+                self.config.merkle_proof_cache_poll_interval,
+                self.logger.clone(),
+            ));
+        }
+
+        self.new_block_notifier_thread = Some(NewBlockNotifierThread::start(
+            self.new_block_notifier.clone(),
+            self.block_provider.clone(),
+            self.config.key_image_subscription_poll_interval,
+            self.logger.clone(),
+        ));
+
         self.router_server.start();
         log::info!(
             self.logger,
             "Router API listening on {}",
-            self.client_listen_uri.addr()
+            SeqDisplay(self.client_listen_uris.iter().map(|uri| uri.addr()))
         );
 
         let config_json =
@@ -204,8 +345,45 @@ where
         );
     }
 
+    /// Check that the configured shards still cover every block in the ledger.
+    ///
+    /// This should be called periodically, since shards are configured with
+    /// fixed block ranges while the ledger keeps growing.
+    pub fn update_shard_coverage(&self) {
+        match self.block_provider.num_blocks() {
+            Ok(num_blocks) => {
+                self.shard_coverage.update(num_blocks);
+            }
+            Err(err) => log::error!(
+                self.logger,
+                "Could not get the number of blocks to check shard coverage: {}",
+                err
+            ),
+        }
+    }
+
+    /// Whether the router has attested with every configured Key Image Store.
+    pub fn is_ready(&self) -> bool {
+        self.readiness_indicator.ready()
+    }
+
     /// Stops the server
     pub fn stop(&mut self) {
+        if let Some(mut threads) = self.store_attestation_threads.take() {
+            threads
+                .stop()
+                .expect("Could not stop store attestation threads");
+        }
+        if let Some(mut thread) = self.merkle_proof_cache_thread.take() {
+            thread
+                .stop()
+                .expect("Could not stop merkle proof cache thread");
+        }
+        if let Some(mut thread) = self.new_block_notifier_thread.take() {
+            thread
+                .stop()
+                .expect("Could not stop new block notifier thread");
+        }
         block_on(self.router_server.shutdown()).expect("Could not stop router grpc server");
     }
 }
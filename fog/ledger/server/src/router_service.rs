@@ -1,10 +1,13 @@
 // Copyright (c) 2018-2022 The MobileCoin Foundation
 
 use crate::{
+    new_block_notifier::NewBlockNotifier,
     router_handlers::{self, handle_auth_request, handle_query_request},
-    SVC_COUNTERS,
+    shard_coverage::ShardCoverage,
+    shard_epoch::ShardEpoch,
+    ConcurrencyLimiter, SVC_COUNTERS,
 };
-use futures::{FutureExt, TryFutureExt};
+use futures::{future::Abortable, FutureExt, TryFutureExt};
 use grpcio::{DuplexSink, RequestStream, RpcContext, UnarySink};
 use mc_attest_api::attest::{AuthMessage, Message};
 use mc_common::logger::{log, Logger};
@@ -14,8 +17,10 @@ use mc_fog_api::{
 };
 use mc_fog_ledger_enclave::LedgerEnclaveProxy;
 use mc_fog_uri::KeyImageStoreUri;
-use mc_util_grpc::{rpc_internal_error, rpc_logger};
-use mc_util_metrics::ServiceMetrics;
+use mc_util_grpc::{
+    rpc_internal_error, rpc_logger, send_result, InFlightRequests, InterceptorChain,
+};
+use mc_util_metrics::{service_metrics, ServiceMetrics};
 use mc_util_telemetry::tracer;
 
 use std::{
@@ -30,6 +35,17 @@ where
 {
     enclave: E,
     shards: Arc<RwLock<HashMap<KeyImageStoreUri, Arc<ledger_grpc::KeyImageStoreApiClient>>>>,
+    shard_epoch: ShardEpoch,
+    shard_coverage: Arc<ShardCoverage>,
+    /// Limits how many key image checks are served at once, over both APIs.
+    check_key_images_limiter: Arc<ConcurrencyLimiter>,
+    /// Admission control, authentication and other checks run before any
+    /// enclave work is done.
+    interceptors: InterceptorChain,
+    /// Tells streams with key image subscriptions about new blocks.
+    new_block_notifier: Arc<NewBlockNotifier>,
+    /// The client streams being handled, which the admin API lists.
+    in_flight: Arc<InFlightRequests>,
     query_retries: usize,
     logger: Logger,
 }
@@ -40,18 +56,31 @@ impl<E: LedgerEnclaveProxy> LedgerRouterService<E> {
     pub fn new(
         enclave: E,
         shards: Arc<RwLock<HashMap<KeyImageStoreUri, Arc<ledger_grpc::KeyImageStoreApiClient>>>>,
+        shard_epoch: ShardEpoch,
+        shard_coverage: Arc<ShardCoverage>,
+        check_key_images_limiter: Arc<ConcurrencyLimiter>,
+        interceptors: InterceptorChain,
+        new_block_notifier: Arc<NewBlockNotifier>,
+        in_flight: Arc<InFlightRequests>,
         query_retries: usize,
         logger: Logger,
     ) -> Self {
         Self {
             enclave,
             shards,
+            shard_epoch,
+            shard_coverage,
+            check_key_images_limiter,
+            interceptors,
+            new_block_notifier,
+            in_flight,
             query_retries,
             logger,
         }
     }
 }
 
+#[service_metrics(SVC_COUNTERS)]
 impl<E> LedgerApi for LedgerRouterService<E>
 where
     E: LedgerEnclaveProxy,
@@ -62,21 +91,34 @@ where
         requests: RequestStream<LedgerRequest>,
         responses: DuplexSink<LedgerResponse>,
     ) {
-        let _timer = SVC_COUNTERS.req(&ctx);
         mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
             log::warn!(
                 self.logger,
                 "Streaming GRPC Ledger API only partially implemented."
             );
             let logger = logger.clone();
+            if let Err(rpc_status) = self.interceptors.check(&ctx) {
+                let future = responses
+                    .fail(rpc_status)
+                    .map_err(move |err| log::error!(&logger, "failed to reply: {}", err))
+                    .map(|_| ());
+                return ctx.spawn(future);
+            }
 
-            let shards = self.shards.read().expect("RwLock poisoned");
+            // Pin the stream to the current shards for its whole lifetime.
+            let shards = self.shard_epoch.snapshot(&self.shards);
             let method_name = ServiceMetrics::get_method_name(&ctx);
+            let (in_flight, abort_registration) =
+                self.in_flight.track(&ctx, shards.shard_clients.len());
 
             let future = router_handlers::handle_requests(
                 method_name,
-                shards.values().cloned().collect(),
+                shards,
+                self.shard_coverage.clone(),
+                self.check_key_images_limiter.clone(),
+                self.new_block_notifier.clone(),
                 self.enclave.clone(),
+                in_flight,
                 requests,
                 responses,
                 self.query_retries,
@@ -86,7 +128,9 @@ where
             // TODO: Do more with the error than just push it to the log.
             .map(|_| ());
 
-            ctx.spawn(future)
+            // The stream is dropped, and the client sees it fail, if it is
+            // cancelled through the admin API.
+            ctx.spawn(Abortable::new(future, abort_registration).map(|_| ()))
         });
     }
 }
@@ -99,14 +143,25 @@ async fn unary_check_key_image_impl<E>(
     enclave: E,
     sink: UnarySink<Message>,
     shard_clients: Vec<Arc<KeyImageStoreApiClient>>,
+    shard_coverage: Arc<ShardCoverage>,
+    limiter: Arc<ConcurrencyLimiter>,
     scope_logger: Logger,
 ) -> Result<(), grpcio::Error>
 where
     E: LedgerEnclaveProxy,
 {
+    if let Err(rpc_status) = shard_coverage.check_can_serve(&scope_logger) {
+        return sink.fail(rpc_status).await;
+    }
+    let _permit = match limiter.acquire(&scope_logger).await {
+        Ok(permit) => permit,
+        Err(rpc_status) => return sink.fail(rpc_status).await,
+    };
+
     let tracer = tracer!();
     let result = handle_query_request(
         request,
+        None,
         enclave,
         shard_clients,
         query_retries,
@@ -134,10 +189,13 @@ where
 }
 
 // This API is the unary key-image-specific equivalent of LedgerApi.
+#[service_metrics(SVC_COUNTERS)]
 impl<E: LedgerEnclaveProxy> FogKeyImageApi for LedgerRouterService<E> {
     fn check_key_images(&mut self, ctx: RpcContext, request: Message, sink: UnarySink<Message>) {
-        let _timer = SVC_COUNTERS.req(&ctx);
         mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
+            if let Err(rpc_status) = self.interceptors.check(&ctx) {
+                return send_result(ctx, sink, Err(rpc_status), logger);
+            }
             let logger = logger.clone();
             let shards = self.shards.read().expect("RwLock poisoned");
 
@@ -147,6 +205,8 @@ impl<E: LedgerEnclaveProxy> FogKeyImageApi for LedgerRouterService<E> {
                 self.enclave.clone(),
                 sink,
                 shards.values().cloned().collect(),
+                self.shard_coverage.clone(),
+                self.check_key_images_limiter.clone(),
                 logger.clone(),
             )
             .map_err(move |err| log::error!(&logger, "failed to reply: {}", err))
@@ -158,8 +218,10 @@ impl<E: LedgerEnclaveProxy> FogKeyImageApi for LedgerRouterService<E> {
     }
 
     fn auth(&mut self, ctx: RpcContext, request: AuthMessage, sink: UnarySink<AuthMessage>) {
-        let _timer = SVC_COUNTERS.req(&ctx);
         mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
+            if let Err(rpc_status) = self.interceptors.check(&ctx) {
+                return send_result(ctx, sink, Err(rpc_status), logger);
+            }
             let logger = logger.clone();
             let result = handle_auth_request(self.enclave.clone(), request, logger.clone());
             let future = match result {
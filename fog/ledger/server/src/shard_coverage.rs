@@ -0,0 +1,245 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Tracks whether the router's shards cover every block in the ledger.
+//!
+//! A key image which appeared in a block that no shard is responsible for
+//! would be reported to the client as not spent. The router therefore keeps
+//! track of the blocks its shards do not cover, and depending on the
+//! configured [ShardCoveragePolicy] refuses key image queries while such gaps
+//! exist.
+
+use crate::{
+    config::ShardCoveragePolicy,
+    metrics::SHARD_COVERAGE_GAP_BLOCKS,
+    sharding_strategy::{EpochShardingStrategy, ShardingStrategy},
+};
+use grpcio::RpcStatus;
+use itertools::Itertools;
+use mc_common::logger::{log, Logger};
+use mc_fog_api::ledger_grpc::KeyImageStoreApiClient;
+use mc_fog_types::common::BlockRange;
+use mc_fog_uri::{ConnectionUri, KeyImageStoreUri};
+use mc_util_grpc::rpc_unavailable_error;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock},
+};
+
+/// Returns the sub-ranges of `[0, num_blocks)` which are not contained in any
+/// of `shard_ranges`, in increasing order.
+pub fn find_coverage_gaps<'a>(
+    shard_ranges: impl IntoIterator<Item = &'a BlockRange>,
+    num_blocks: u64,
+) -> Vec<BlockRange> {
+    let mut shard_ranges = shard_ranges
+        .into_iter()
+        .filter(|range| range.is_valid())
+        .collect::<Vec<_>>();
+    shard_ranges.sort();
+
+    let mut gaps = Vec::new();
+    let mut first_uncovered_block = 0;
+    for range in shard_ranges {
+        if first_uncovered_block >= num_blocks {
+            break;
+        }
+        if range.start_block > first_uncovered_block {
+            gaps.push(BlockRange::new(
+                first_uncovered_block,
+                range.start_block.min(num_blocks),
+            ));
+        }
+        first_uncovered_block = first_uncovered_block.max(range.end_block);
+    }
+    if first_uncovered_block < num_blocks {
+        gaps.push(BlockRange::new(first_uncovered_block, num_blocks));
+    }
+    gaps
+}
+
+#[derive(Default)]
+struct CoverageState {
+    /// The number of blocks in the ledger when coverage was last computed.
+    num_blocks: u64,
+    /// The blocks which no shard covered at that time.
+    gaps: Vec<BlockRange>,
+}
+
+/// The router's view of how well its shards cover the ledger.
+///
+/// Coverage is recomputed when the ledger grows (see [ShardCoverage::update])
+/// and when the set of shards changes (see [ShardCoverage::recheck]).
+pub struct ShardCoverage {
+    policy: ShardCoveragePolicy,
+    shards: Arc<RwLock<HashMap<KeyImageStoreUri, Arc<KeyImageStoreApiClient>>>>,
+    state: Mutex<CoverageState>,
+    logger: Logger,
+}
+
+impl ShardCoverage {
+    pub fn new(
+        policy: ShardCoveragePolicy,
+        shards: Arc<RwLock<HashMap<KeyImageStoreUri, Arc<KeyImageStoreApiClient>>>>,
+        logger: Logger,
+    ) -> Self {
+        Self {
+            policy,
+            shards,
+            state: Default::default(),
+            logger,
+        }
+    }
+
+    /// Recompute coverage for a ledger containing `num_blocks` blocks,
+    /// returning the uncovered block ranges.
+    pub fn update(&self, num_blocks: u64) -> Vec<BlockRange> {
+        let shard_ranges = self.shard_ranges();
+        let gaps = find_coverage_gaps(shard_ranges.iter(), num_blocks);
+        SHARD_COVERAGE_GAP_BLOCKS.set(gaps.iter().map(BlockRange::len).sum::<u64>() as i64);
+
+        let mut state = self.state.lock().expect("mutex poisoned");
+        if gaps != state.gaps {
+            if gaps.is_empty() {
+                log::info!(
+                    self.logger,
+                    "Shards now cover all {} blocks of the ledger",
+                    num_blocks
+                );
+            } else {
+                log::warn!(
+                    self.logger,
+                    "Shards do not cover blocks {} of the ledger (policy: {:?})",
+                    gaps.iter().join(", "),
+                    self.policy
+                );
+            }
+        }
+        state.num_blocks = num_blocks;
+        state.gaps = gaps.clone();
+        gaps
+    }
+
+    /// Recompute coverage after the set of shards has changed, against the
+    /// last known number of blocks.
+    pub fn recheck(&self) -> Vec<BlockRange> {
+        let num_blocks = self.state.lock().expect("mutex poisoned").num_blocks;
+        self.update(num_blocks)
+    }
+
+    /// The number of blocks in the ledger when coverage was last computed.
+    pub fn num_blocks(&self) -> u64 {
+        self.state.lock().expect("mutex poisoned").num_blocks
+    }
+
+    /// Check whether key image queries may currently be served.
+    pub fn check_can_serve(&self, logger: &Logger) -> Result<(), RpcStatus> {
+        let state = self.state.lock().expect("mutex poisoned");
+        if state.gaps.is_empty() || self.policy == ShardCoveragePolicy::Degraded {
+            return Ok(());
+        }
+        Err(rpc_unavailable_error(
+            "Key Images Query",
+            format!(
+                "No key image store covers blocks {}",
+                state.gaps.iter().join(", ")
+            ),
+            logger,
+        ))
+    }
+
+    fn shard_ranges(&self) -> Vec<BlockRange> {
+        let shards = self.shards.read().expect("RwLock poisoned");
+        shards
+            .keys()
+            .filter_map(|uri| match EpochShardingStrategy::try_from(uri.clone()) {
+                Ok(sharding_strategy) => Some(sharding_strategy.get_block_range()),
+                Err(err) => {
+                    log::error!(
+                        self.logger,
+                        "Could not get sharding strategy for shard {}: {}",
+                        uri.redacted(),
+                        err
+                    );
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mc_common::logger::test_with_logger;
+    use std::str::FromStr;
+
+    #[test]
+    fn find_coverage_gaps_with_overlapping_shards() {
+        let ranges = [
+            BlockRange::new(50, 120),
+            BlockRange::new(0, 60),
+            BlockRange::new(100, u64::MAX),
+        ];
+        assert!(find_coverage_gaps(ranges.iter(), 1000).is_empty());
+    }
+
+    #[test]
+    fn find_coverage_gaps_reports_holes_and_tail() {
+        let ranges = [
+            BlockRange::new(10, 20),
+            BlockRange::new(30, 40),
+            BlockRange::new(35, 50),
+            BlockRange::new(200, 300),
+        ];
+        assert_eq!(
+            find_coverage_gaps(ranges.iter(), 100),
+            vec![
+                BlockRange::new(0, 10),
+                BlockRange::new(20, 30),
+                BlockRange::new(50, 100)
+            ]
+        );
+        assert_eq!(
+            find_coverage_gaps(ranges.iter(), 0),
+            Vec::<BlockRange>::new()
+        );
+        assert_eq!(
+            find_coverage_gaps([].iter(), 5),
+            vec![BlockRange::new(0, 5)]
+        );
+    }
+
+    #[test_with_logger]
+    fn refuse_policy_fails_queries_while_gaps_exist(logger: Logger) {
+        let shards = Arc::new(RwLock::new(HashMap::new()));
+        let coverage =
+            ShardCoverage::new(ShardCoveragePolicy::Refuse, shards.clone(), logger.clone());
+        assert_eq!(coverage.update(10), vec![BlockRange::new(0, 10)]);
+        assert!(coverage.check_can_serve(&logger).is_err());
+
+        let grpc_env = Arc::new(grpcio::EnvBuilder::new().build());
+        let uri = KeyImageStoreUri::from_str(
+            "insecure-key-image-store://localhost:3228?sharding_strategy=0-100",
+        )
+        .unwrap();
+        let client =
+            KeyImageStoreApiClient::new(grpcio::ChannelBuilder::new(grpc_env).connect(&uri.addr()));
+        shards.write().unwrap().insert(uri, Arc::new(client));
+        assert!(coverage.recheck().is_empty());
+        assert!(coverage.check_can_serve(&logger).is_ok());
+
+        assert_eq!(coverage.update(150), vec![BlockRange::new(100, 150)]);
+        assert!(coverage.check_can_serve(&logger).is_err());
+    }
+
+    #[test_with_logger]
+    fn degraded_policy_serves_despite_gaps(logger: Logger) {
+        let coverage = ShardCoverage::new(
+            ShardCoveragePolicy::Degraded,
+            Default::default(),
+            logger.clone(),
+        );
+        assert!(!coverage.update(10).is_empty());
+        assert!(coverage.check_can_serve(&logger).is_ok());
+    }
+}
@@ -0,0 +1,108 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Pins streaming clients to a consistent set of shards.
+//!
+//! Every change to the router's set of shards starts a new shard epoch. A
+//! stream takes a [ShardSnapshot] when it is opened and routes all of its
+//! queries to those shards, so that its responses never mix shard sets from
+//! before and after a rebalance. Each response carries the snapshot's epoch,
+//! and a client may send the epoch it saw earlier to make sure it is still
+//! talking to the same set of shards.
+
+use mc_fog_api::ledger_grpc::KeyImageStoreApiClient;
+use mc_fog_uri::KeyImageStoreUri;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+};
+
+/// The epoch of the router's set of shards.
+///
+/// The epoch must only be advanced while holding the write lock on the shards,
+/// and only read while holding a read lock on them, so that an epoch always
+/// identifies a single set of shards.
+#[derive(Clone, Debug)]
+pub struct ShardEpoch(Arc<AtomicU64>);
+
+impl ShardEpoch {
+    /// The value clients send when they don't require a particular epoch.
+    pub const ANY: u64 = 0;
+
+    /// Start counting epochs, from the first one which isn't [Self::ANY].
+    pub fn new() -> Self {
+        Self(Arc::new(AtomicU64::new(Self::ANY + 1)))
+    }
+
+    /// The current epoch
+    pub fn current(&self) -> u64 {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Start a new epoch, after the set of shards changed.
+    pub fn advance(&self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Take a snapshot of the current shards.
+    pub fn snapshot(
+        &self,
+        shards: &RwLock<HashMap<KeyImageStoreUri, Arc<KeyImageStoreApiClient>>>,
+    ) -> ShardSnapshot {
+        let shards = shards.read().expect("RwLock poisoned");
+        ShardSnapshot {
+            epoch: self.current(),
+            shard_clients: shards.values().cloned().collect(),
+        }
+    }
+}
+
+impl Default for ShardEpoch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The shards of one shard epoch.
+#[derive(Clone)]
+pub struct ShardSnapshot {
+    /// The epoch in which the shards were the router's set of shards
+    pub epoch: u64,
+    /// Clients for each of the shards
+    pub shard_clients: Vec<Arc<KeyImageStoreApiClient>>,
+}
+
+impl ShardSnapshot {
+    /// Whether a client which asked for `requested_epoch` may be served from
+    /// this snapshot.
+    pub fn satisfies(&self, requested_epoch: u64) -> bool {
+        requested_epoch == ShardEpoch::ANY || requested_epoch == self.epoch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshots_follow_epochs() {
+        let shards = RwLock::new(HashMap::new());
+        let epoch = ShardEpoch::new();
+
+        let first = epoch.snapshot(&shards);
+        assert_ne!(first.epoch, ShardEpoch::ANY);
+        assert!(first.satisfies(ShardEpoch::ANY));
+        assert!(first.satisfies(first.epoch));
+
+        {
+            let _shards = shards.write().unwrap();
+            epoch.advance();
+        }
+        let second = epoch.snapshot(&shards);
+        assert_eq!(second.epoch, first.epoch + 1);
+        assert!(!second.satisfies(first.epoch));
+        assert!(!first.satisfies(second.epoch));
+    }
+}
@@ -0,0 +1,474 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Hands the blocks of some shards over to new shards without interrupting
+//! service.
+//!
+//! When the epoch boundaries of the Key Image Stores are reconfigured, the
+//! stores for the new epochs have to load their blocks before they can answer
+//! queries. During a [ShardHandoff] the router keeps querying the retiring
+//! shards, while it watches the incoming shards' progress through their admin
+//! APIs. Once every incoming shard has caught up, the retiring shards are
+//! replaced with the incoming shards in a single step, which starts a new
+//! shard epoch, so no query ever sees a mix of the two sets.
+
+use crate::{
+    shard_coverage::{find_coverage_gaps, ShardCoverage},
+    shard_epoch::ShardEpoch,
+    sharding_strategy::{EpochShardingStrategy, ShardingStrategy},
+};
+use displaydoc::Display;
+use grpcio::{CallOption, ChannelBuilder};
+use itertools::Itertools;
+use mc_common::logger::{log, Logger};
+use mc_fog_api::{
+    ledger::{IncomingShardStatus, KeyImageStoreState, ShardHandoffStatus},
+    ledger_grpc::{KeyImageStoreAdminApiClient, KeyImageStoreApiClient},
+};
+use mc_fog_types::common::BlockRange;
+use mc_fog_uri::{ConnectionUri, KeyImageStoreUri};
+use mc_util_grpc::{record_audit_event, AuditEvent, ConnectionUriGrpcioChannel, Empty};
+use mc_util_uri::AdminUri;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
+};
+
+/// How long to wait for an incoming shard to report its progress.
+const ADMIN_CALL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Whether a store responsible for `epoch_block_range` which has processed
+/// `processed_block_range` has every block of its epoch in a ledger of
+/// `num_blocks` blocks.
+pub fn is_caught_up(
+    epoch_block_range: &BlockRange,
+    processed_block_range: &BlockRange,
+    num_blocks: u64,
+) -> bool {
+    processed_block_range.start_block <= epoch_block_range.start_block
+        && processed_block_range.end_block >= epoch_block_range.end_block.min(num_blocks)
+}
+
+/// Why a shard handoff could not be started or completed.
+#[derive(Clone, Debug, Display, Eq, PartialEq)]
+pub enum ShardHandoffError {
+    /// A shard handoff is already in progress
+    AlreadyInProgress,
+    /// No shard handoff is in progress
+    NotInProgress,
+    /// A shard handoff needs at least one incoming shard
+    NoIncomingShards,
+    /// Shard {0} is not one of the router's shards
+    UnknownShard(String),
+    /// Shard {0} is already one of the router's shards
+    DuplicateShard(String),
+    /// Invalid shard URI {0}: {1}
+    InvalidUri(String, String),
+    /// The handoff is not ready to complete: {0}
+    NotReady(String),
+}
+
+/// A shard which takes over blocks once the handoff completes.
+struct IncomingShard {
+    uri: KeyImageStoreUri,
+    client: Arc<KeyImageStoreApiClient>,
+    admin_client: KeyImageStoreAdminApiClient,
+}
+
+struct PendingHandoff {
+    retiring: Vec<KeyImageStoreUri>,
+    incoming: Vec<IncomingShard>,
+}
+
+/// Coordinates handing blocks over from some of the router's shards to new
+/// shards.
+pub struct ShardHandoff {
+    shards: Arc<RwLock<HashMap<KeyImageStoreUri, Arc<KeyImageStoreApiClient>>>>,
+    shard_epoch: ShardEpoch,
+    shard_coverage: Arc<ShardCoverage>,
+    pending: Mutex<Option<PendingHandoff>>,
+    grpc_env: Arc<grpcio::Environment>,
+    logger: Logger,
+}
+
+impl ShardHandoff {
+    pub fn new(
+        shards: Arc<RwLock<HashMap<KeyImageStoreUri, Arc<KeyImageStoreApiClient>>>>,
+        shard_epoch: ShardEpoch,
+        shard_coverage: Arc<ShardCoverage>,
+        logger: Logger,
+    ) -> Self {
+        let grpc_env = Arc::new(
+            grpcio::EnvBuilder::new()
+                .name_prefix("shard-handoff".to_string())
+                .build(),
+        );
+        Self {
+            shards,
+            shard_epoch,
+            shard_coverage,
+            pending: Default::default(),
+            grpc_env,
+            logger,
+        }
+    }
+
+    /// Start handing the blocks of the `retiring` shards over to the
+    /// `incoming` shards, whose progress is read from the admin API at the
+    /// paired admin URI.
+    ///
+    /// The incoming shards are not queried until the handoff is completed.
+    pub fn begin(
+        &self,
+        retiring: Vec<KeyImageStoreUri>,
+        incoming: Vec<(KeyImageStoreUri, AdminUri)>,
+    ) -> Result<(), ShardHandoffError> {
+        let mut pending = self.pending.lock().expect("mutex poisoned");
+        if pending.is_some() {
+            return Err(ShardHandoffError::AlreadyInProgress);
+        }
+        if incoming.is_empty() {
+            return Err(ShardHandoffError::NoIncomingShards);
+        }
+
+        {
+            let shards = self.shards.read().expect("RwLock poisoned");
+            if let Some(uri) = retiring.iter().find(|uri| !shards.contains_key(uri)) {
+                return Err(ShardHandoffError::UnknownShard(uri.redacted()));
+            }
+            if let Some((uri, _)) = incoming
+                .iter()
+                .find(|(uri, _)| shards.contains_key(uri) && !retiring.contains(uri))
+            {
+                return Err(ShardHandoffError::DuplicateShard(uri.redacted()));
+            }
+        }
+        if let Some((uri, err)) = incoming.iter().find_map(|(uri, _)| {
+            EpochShardingStrategy::try_from(uri.clone())
+                .err()
+                .map(|err| (uri, err))
+        }) {
+            return Err(ShardHandoffError::InvalidUri(uri.redacted(), err));
+        }
+
+        let incoming = incoming
+            .into_iter()
+            .map(|(uri, admin_uri)| {
+                let client = KeyImageStoreApiClient::new(
+                    ChannelBuilder::default_channel_builder(self.grpc_env.clone())
+                        .keepalive_permit_without_calls(false)
+                        .connect_to_uri(&uri, &self.logger),
+                );
+                let admin_client = KeyImageStoreAdminApiClient::new(
+                    ChannelBuilder::default_channel_builder(self.grpc_env.clone())
+                        .connect_to_uri(&admin_uri, &self.logger),
+                );
+                IncomingShard {
+                    uri,
+                    client: Arc::new(client),
+                    admin_client,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        record_audit_event(
+            AuditEvent::ShardHandoffStarted {
+                retiring_shard_uris: retiring.iter().map(ConnectionUri::redacted).collect(),
+                incoming_shard_uris: incoming.iter().map(|shard| shard.uri.redacted()).collect(),
+            },
+            &self.logger,
+        );
+        *pending = Some(PendingHandoff { retiring, incoming });
+        Ok(())
+    }
+
+    /// The progress of the handoff in progress, if any.
+    pub fn status(&self) -> ShardHandoffStatus {
+        let pending = self.pending.lock().expect("mutex poisoned");
+        self.status_of(pending.as_ref())
+    }
+
+    /// Replace the retiring shards with the incoming shards, and start a new
+    /// shard epoch.
+    ///
+    /// Unless `force` is set, this fails while an incoming shard hasn't caught
+    /// up, or while the new set of shards would leave blocks uncovered.
+    pub fn complete(&self, force: bool) -> Result<ShardHandoffStatus, ShardHandoffError> {
+        let mut pending = self.pending.lock().expect("mutex poisoned");
+        let status = self.status_of(pending.as_ref());
+        let handoff = pending.take().ok_or(ShardHandoffError::NotInProgress)?;
+
+        if !status.get_ready() && !force {
+            let reason = describe_unready(&status);
+            *pending = Some(handoff);
+            return Err(ShardHandoffError::NotReady(reason));
+        }
+
+        let mut shards = self.shards.write().expect("RwLock poisoned");
+        for uri in &handoff.retiring {
+            shards.remove(uri);
+        }
+        for shard in &handoff.incoming {
+            shards.insert(shard.uri.clone(), shard.client.clone());
+        }
+        self.shard_epoch.advance();
+        drop(shards);
+
+        self.shard_coverage.recheck();
+
+        log::info!(
+            self.logger,
+            "Completed shard handoff from [{}] to [{}]{}",
+            handoff
+                .retiring
+                .iter()
+                .map(ConnectionUri::redacted)
+                .join(", "),
+            handoff
+                .incoming
+                .iter()
+                .map(|shard| shard.uri.redacted())
+                .join(", "),
+            if status.get_ready() { "" } else { " (forced)" }
+        );
+        record_audit_event(
+            AuditEvent::ShardHandoffCompleted {
+                retiring_shard_uris: handoff
+                    .retiring
+                    .iter()
+                    .map(ConnectionUri::redacted)
+                    .collect(),
+                incoming_shard_uris: handoff
+                    .incoming
+                    .iter()
+                    .map(|shard| shard.uri.redacted())
+                    .collect(),
+                forced: !status.get_ready(),
+            },
+            &self.logger,
+        );
+
+        Ok(self.status_of(None))
+    }
+
+    /// Abandon the handoff in progress, leaving the router's shards as they
+    /// were.
+    pub fn cancel(&self) -> Result<(), ShardHandoffError> {
+        let handoff = self
+            .pending
+            .lock()
+            .expect("mutex poisoned")
+            .take()
+            .ok_or(ShardHandoffError::NotInProgress)?;
+        record_audit_event(
+            AuditEvent::ShardHandoffCancelled {
+                incoming_shard_uris: handoff
+                    .incoming
+                    .iter()
+                    .map(|shard| shard.uri.redacted())
+                    .collect(),
+            },
+            &self.logger,
+        );
+        Ok(())
+    }
+
+    fn status_of(&self, pending: Option<&PendingHandoff>) -> ShardHandoffStatus {
+        let mut status = ShardHandoffStatus::new();
+        let Some(handoff) = pending else {
+            status.set_shard_epoch(self.shard_epoch.current());
+            return status;
+        };
+
+        let num_blocks = self.shard_coverage.num_blocks();
+        let incoming_shards = handoff
+            .incoming
+            .iter()
+            .map(|shard| self.incoming_shard_status(shard, num_blocks))
+            .collect::<Vec<_>>();
+
+        let ranges_after_handoff = {
+            let shards = self.shards.read().expect("RwLock poisoned");
+            status.set_shard_epoch(self.shard_epoch.current());
+            shards
+                .keys()
+                .filter(|uri| !handoff.retiring.contains(uri))
+                .chain(handoff.incoming.iter().map(|shard| &shard.uri))
+                .filter_map(|uri| EpochShardingStrategy::try_from(uri.clone()).ok())
+                .map(|sharding_strategy| sharding_strategy.get_block_range())
+                .collect::<Vec<_>>()
+        };
+        let coverage_gaps = find_coverage_gaps(ranges_after_handoff.iter(), num_blocks);
+
+        status.set_in_progress(true);
+        status.set_num_blocks(num_blocks);
+        status.set_retiring_shard_uris(
+            handoff
+                .retiring
+                .iter()
+                .map(ConnectionUri::redacted)
+                .collect(),
+        );
+        status.set_ready(
+            coverage_gaps.is_empty()
+                && incoming_shards
+                    .iter()
+                    .all(IncomingShardStatus::get_caught_up),
+        );
+        status.set_incoming_shards(incoming_shards.into());
+        status.set_coverage_gaps(coverage_gaps.iter().map(Into::into).collect());
+        status
+    }
+
+    fn incoming_shard_status(&self, shard: &IncomingShard, num_blocks: u64) -> IncomingShardStatus {
+        let mut status = IncomingShardStatus::new();
+        status.set_shard_uri(shard.uri.redacted());
+        match self.fetch_store_state(shard) {
+            Ok(state) => {
+                let epoch_block_range = BlockRange::from(state.get_epoch_block_range().clone());
+                let processed_block_range =
+                    BlockRange::from(state.get_processed_block_range().clone());
+                status.set_caught_up(is_caught_up(
+                    &epoch_block_range,
+                    &processed_block_range,
+                    num_blocks,
+                ));
+                status.set_epoch_block_range((&epoch_block_range).into());
+                status.set_processed_block_range((&processed_block_range).into());
+            }
+            Err(err) => status.set_error(err),
+        }
+        status
+    }
+
+    /// Ask the shard's admin API for the state of the store at the shard's
+    /// URI.
+    fn fetch_store_state(&self, shard: &IncomingShard) -> Result<KeyImageStoreState, String> {
+        let responder_id = shard
+            .uri
+            .responder_id()
+            .map_err(|err| err.to_string())?
+            .to_string();
+        let states = shard
+            .admin_client
+            .get_store_states_opt(
+                &Empty::new(),
+                CallOption::default().timeout(ADMIN_CALL_TIMEOUT),
+            )
+            .map_err(|err| format!("Could not get store states: {err}"))?;
+        states
+            .stores
+            .into_iter()
+            .find(|state| state.get_responder_id() == responder_id)
+            .ok_or_else(|| format!("The admin API doesn't describe store {responder_id}"))
+    }
+}
+
+fn describe_unready(status: &ShardHandoffStatus) -> String {
+    status
+        .get_incoming_shards()
+        .iter()
+        .filter(|shard| !shard.get_caught_up())
+        .map(|shard| {
+            if shard.get_error().is_empty() {
+                format!(
+                    "{} has processed up to block {}",
+                    shard.get_shard_uri(),
+                    shard.get_processed_block_range().get_end_block()
+                )
+            } else {
+                format!("{}: {}", shard.get_shard_uri(), shard.get_error())
+            }
+        })
+        .chain(status.get_coverage_gaps().iter().map(|gap| {
+            format!(
+                "no shard would cover blocks [{},{})",
+                gap.get_start_block(),
+                gap.get_end_block()
+            )
+        }))
+        .join("; ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ShardCoveragePolicy;
+    use mc_common::logger::test_with_logger;
+    use std::str::FromStr;
+
+    fn shard_uri(port: u16, epoch: &str) -> KeyImageStoreUri {
+        KeyImageStoreUri::from_str(&format!(
+            "insecure-key-image-store://localhost:{port}?sharding_strategy={epoch}"
+        ))
+        .unwrap()
+    }
+
+    fn admin_uri(port: u16) -> AdminUri {
+        AdminUri::from_str(&format!("insecure-mca://localhost:{port}")).unwrap()
+    }
+
+    #[test]
+    fn caught_up_once_epoch_blocks_in_ledger_are_processed() {
+        let epoch = BlockRange::new(100, 200);
+
+        assert!(!is_caught_up(&epoch, &BlockRange::new(100, 150), 1000));
+        assert!(is_caught_up(&epoch, &BlockRange::new(100, 200), 1000));
+        assert!(!is_caught_up(&epoch, &BlockRange::new(100, 150), 160));
+        assert!(is_caught_up(&epoch, &BlockRange::new(100, 160), 160));
+        assert!(!is_caught_up(&epoch, &BlockRange::new(0, 0), 160));
+    }
+
+    #[test_with_logger]
+    fn begin_validates_shards(logger: Logger) {
+        let grpc_env = Arc::new(grpcio::EnvBuilder::new().build());
+        let retiring = shard_uri(3228, "0-100");
+        let client = Arc::new(KeyImageStoreApiClient::new(
+            ChannelBuilder::new(grpc_env).connect(&retiring.addr()),
+        ));
+        let shards = Arc::new(RwLock::new(HashMap::from([(retiring.clone(), client)])));
+        let coverage = Arc::new(ShardCoverage::new(
+            ShardCoveragePolicy::Refuse,
+            shards.clone(),
+            logger.clone(),
+        ));
+        let shard_epoch = ShardEpoch::new();
+        let handoff = ShardHandoff::new(shards, shard_epoch.clone(), coverage, logger);
+
+        assert_eq!(
+            handoff.begin(vec![retiring.clone()], vec![]),
+            Err(ShardHandoffError::NoIncomingShards)
+        );
+        assert_eq!(
+            handoff.begin(
+                vec![shard_uri(3229, "0-50")],
+                vec![(shard_uri(3230, "0-50"), admin_uri(3231))]
+            ),
+            Err(ShardHandoffError::UnknownShard(
+                shard_uri(3229, "0-50").redacted()
+            ))
+        );
+        assert!(!handoff.status().get_in_progress());
+
+        handoff
+            .begin(
+                vec![retiring.clone()],
+                vec![
+                    (shard_uri(3230, "0-50"), admin_uri(3231)),
+                    (shard_uri(3232, "50-100"), admin_uri(3233)),
+                ],
+            )
+            .unwrap();
+        assert_eq!(
+            handoff.begin(vec![retiring], vec![]),
+            Err(ShardHandoffError::AlreadyInProgress)
+        );
+        handoff.cancel().unwrap();
+        assert_eq!(handoff.cancel(), Err(ShardHandoffError::NotInProgress));
+        assert_eq!(
+            handoff.complete(true).map(|_| ()),
+            Err(ShardHandoffError::NotInProgress)
+        );
+        assert_eq!(shard_epoch.current(), ShardEpoch::new().current());
+    }
+}
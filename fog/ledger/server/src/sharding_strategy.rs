@@ -7,6 +7,7 @@
 
 use mc_blockchain_types::BlockIndex;
 use mc_fog_types::{common::BlockRange, BlockCount};
+use mc_fog_uri::{ConnectionUri, KeyImageStoreUri};
 use serde::Serialize;
 use std::str::FromStr;
 
@@ -40,6 +41,17 @@ pub struct EpochShardingStrategy {
     epoch_block_range: BlockRange,
 }
 
+impl TryFrom<KeyImageStoreUri> for EpochShardingStrategy {
+    type Error = String;
+
+    fn try_from(src: KeyImageStoreUri) -> Result<Self, Self::Error> {
+        match src.get_param("sharding_strategy") {
+            Some(sharding_strategy_string) => Self::from_str(&sharding_strategy_string),
+            None => Ok(Self::default()),
+        }
+    }
+}
+
 impl ShardingStrategy for EpochShardingStrategy {
     fn should_process_block(&self, block_index: BlockIndex) -> bool {
         self.epoch_block_range.contains(block_index)
@@ -245,4 +257,24 @@ mod epoch_sharding_strategy_tests {
 
         assert!(is_ready)
     }
+
+    #[test]
+    fn try_from_store_uri_reads_sharding_strategy_param() {
+        let uri = KeyImageStoreUri::from_str(
+            "insecure-key-image-store://localhost:3228?sharding_strategy=100-200",
+        )
+        .unwrap();
+        let epoch_sharding_strategy = EpochShardingStrategy::try_from(uri).unwrap();
+        assert_eq!(
+            epoch_sharding_strategy.get_block_range(),
+            BlockRange::new(100, 200)
+        );
+
+        let uri = KeyImageStoreUri::from_str("insecure-key-image-store://localhost:3228").unwrap();
+        let epoch_sharding_strategy = EpochShardingStrategy::try_from(uri).unwrap();
+        assert_eq!(
+            epoch_sharding_strategy.get_block_range(),
+            BlockRange::new(0, u64::MAX)
+        );
+    }
 }
@@ -0,0 +1,154 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Attestation with the configured Key Image Stores when the router starts.
+//!
+//! Queries attest with a store lazily, once the store reports that it can't
+//! decrypt them, so without this the first wallet requests after a deploy
+//! would each wait for a round of attestation with every store. Instead, the
+//! router attests with each configured store as it starts, retrying each with
+//! exponential backoff until it succeeds, and reports itself as not ready
+//! until every store is attested, so that it only gets traffic once queries
+//! can be answered right away.
+//!
+//! Stores added later through the admin API are still attested lazily.
+
+use crate::router_handlers::authenticate_ledger_store;
+use futures::executor::block_on;
+use mc_common::logger::{log, Logger};
+use mc_fog_ledger_enclave::LedgerEnclaveProxy;
+use mc_fog_uri::{ConnectionUri, KeyImageStoreUri};
+use mc_util_grpc::ReadinessIndicator;
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::{Builder as ThreadBuilder, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// How long to wait before retrying a store the first time.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+
+/// How often a thread waiting to retry checks whether it should stop.
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Background threads, one per store, which attest with the stores and then
+/// set the router ready.
+pub struct StoreAttestationThreads {
+    join_handles: Vec<JoinHandle<()>>,
+    stop_requested: Arc<AtomicBool>,
+}
+
+impl StoreAttestationThreads {
+    /// Start attesting with each of `store_uris`, waiting at most
+    /// `max_backoff` between attempts with a store, and set
+    /// `readiness_indicator` once all of them are attested.
+    pub fn start<E: LedgerEnclaveProxy>(
+        enclave: E,
+        store_uris: Vec<KeyImageStoreUri>,
+        max_backoff: Duration,
+        readiness_indicator: ReadinessIndicator,
+        logger: Logger,
+    ) -> Self {
+        let stop_requested = Arc::new(AtomicBool::new(false));
+        if store_uris.is_empty() {
+            readiness_indicator.set_ready();
+        }
+
+        let num_pending = Arc::new(AtomicUsize::new(store_uris.len()));
+        let join_handles = store_uris
+            .into_iter()
+            .map(|store_uri| {
+                let enclave = enclave.clone();
+                let readiness_indicator = readiness_indicator.clone();
+                let num_pending = num_pending.clone();
+                let stop_requested = stop_requested.clone();
+                let logger = logger.clone();
+                ThreadBuilder::new()
+                    .name("StoreAttestation".to_owned())
+                    .spawn(move || {
+                        if attest_with_retries(
+                            enclave,
+                            &store_uri,
+                            max_backoff,
+                            &stop_requested,
+                            &logger,
+                        ) && num_pending.fetch_sub(1, Ordering::SeqCst) == 1
+                        {
+                            log::info!(logger, "Attested with every Key Image Store");
+                            readiness_indicator.set_ready();
+                        }
+                    })
+                    .expect("Could not spawn thread")
+            })
+            .collect();
+
+        Self {
+            join_handles,
+            stop_requested,
+        }
+    }
+
+    /// Stop and join the threads
+    pub fn stop(&mut self) -> Result<(), ()> {
+        self.stop_requested.store(true, Ordering::SeqCst);
+        for join_handle in self.join_handles.drain(..) {
+            join_handle.join().map_err(|_| ())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for StoreAttestationThreads {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+/// Attest with a store until it succeeds, or a stop is requested. Returns
+/// whether the store was attested.
+fn attest_with_retries<E: LedgerEnclaveProxy>(
+    enclave: E,
+    store_uri: &KeyImageStoreUri,
+    max_backoff: Duration,
+    stop_requested: &AtomicBool,
+    logger: &Logger,
+) -> bool {
+    let mut backoff = INITIAL_BACKOFF.min(max_backoff);
+    while !stop_requested.load(Ordering::SeqCst) {
+        match block_on(authenticate_ledger_store(
+            enclave.clone(),
+            store_uri.clone(),
+            logger.clone(),
+        )) {
+            Ok(()) => {
+                log::info!(
+                    logger,
+                    "Attested with Key Image Store {}",
+                    store_uri.redacted()
+                );
+                return true;
+            }
+            Err(err) => {
+                log::warn!(
+                    logger,
+                    "Could not attest with Key Image Store {}, retrying in {:?}: {}",
+                    store_uri.redacted(),
+                    backoff,
+                    err
+                );
+            }
+        }
+
+        let retry_at = Instant::now() + backoff;
+        while Instant::now() < retry_at && !stop_requested.load(Ordering::SeqCst) {
+            std::thread::sleep(
+                STOP_POLL_INTERVAL.min(retry_at.saturating_duration_since(Instant::now())),
+            );
+        }
+        backoff = (backoff * 2).min(max_backoff);
+    }
+    false
+}
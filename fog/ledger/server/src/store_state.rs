@@ -0,0 +1,329 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Redacted descriptions of the state of Key Image Store enclaves, for
+//! debugging.
+//!
+//! Each store describes its ORAM's capacity and fill level, the epoch of
+//! blocks it is responsible for and how far through them it has got, without
+//! revealing anything about the key images themselves. [diff_store_states]
+//! compares the descriptions collected from a fleet of stores, to help find
+//! the stores which claim different coverage than their peers.
+
+use crate::{shard_coverage::find_coverage_gaps, DbPollSharedState, SVC_COUNTERS};
+use displaydoc::Display;
+use grpcio::{RpcContext, RpcStatus, UnarySink};
+use itertools::Itertools;
+use mc_common::logger::Logger;
+use mc_fog_api::{
+    fog_common,
+    ledger::{KeyImageStoreState, KeyImageStoreStates},
+    ledger_grpc::KeyImageStoreAdminApi,
+};
+use mc_fog_types::common::BlockRange;
+use mc_util_grpc::{rpc_logger, send_result, Empty};
+use mc_util_metrics::service_metrics;
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+/// Stores whose ORAM holds more than this percentage of its capacity are
+/// reported, since only about 75% of the capacity can be used.
+const NEAR_CAPACITY_PERCENT: u64 = 75;
+
+/// Describes the state of one Key Image Store.
+#[derive(Clone)]
+pub struct KeyImageStoreStateSource {
+    responder_id: String,
+    omap_capacity: u64,
+    epoch_block_range: BlockRange,
+    db_poll_shared_state: Arc<Mutex<DbPollSharedState>>,
+}
+
+impl KeyImageStoreStateSource {
+    /// Describe the store `responder_id`, whose enclave was created with
+    /// `omap_capacity`, and is loaded with the blocks in `epoch_block_range`
+    /// by the db fetcher sharing `db_poll_shared_state`.
+    pub fn new(
+        responder_id: String,
+        omap_capacity: u64,
+        epoch_block_range: BlockRange,
+        db_poll_shared_state: Arc<Mutex<DbPollSharedState>>,
+    ) -> Self {
+        Self {
+            responder_id,
+            omap_capacity,
+            epoch_block_range,
+            db_poll_shared_state,
+        }
+    }
+
+    /// The store's current state.
+    pub fn state(&self) -> KeyImageStoreState {
+        let shared_state = self.db_poll_shared_state.lock().expect("mutex poisoned");
+        let mut state = KeyImageStoreState::new();
+        state.set_responder_id(self.responder_id.clone());
+        state.set_omap_capacity(self.omap_capacity);
+        state.set_num_key_images(shared_state.num_key_images);
+        state.set_epoch_block_range((&self.epoch_block_range).into());
+        state.set_processed_block_range((&shared_state.processed_block_range).into());
+        state.set_latest_block_version(shared_state.latest_block_version);
+        state.set_server_version(env!("CARGO_PKG_VERSION").to_owned());
+        state
+    }
+}
+
+/// Serves the KeyImageStoreAdminAPI, describing each of the stores served by
+/// this process.
+#[derive(Clone)]
+pub struct KeyImageStoreAdminService {
+    stores: Vec<KeyImageStoreStateSource>,
+    logger: Logger,
+}
+
+impl KeyImageStoreAdminService {
+    /// Describe each of `stores`.
+    pub fn new(stores: Vec<KeyImageStoreStateSource>, logger: Logger) -> Self {
+        Self { stores, logger }
+    }
+
+    fn get_store_states_impl(&self) -> Result<KeyImageStoreStates, RpcStatus> {
+        let mut states = KeyImageStoreStates::new();
+        states.set_stores(self.stores.iter().map(|store| store.state()).collect());
+        Ok(states)
+    }
+}
+
+#[service_metrics(SVC_COUNTERS)]
+impl KeyImageStoreAdminApi for KeyImageStoreAdminService {
+    fn get_store_states(
+        &mut self,
+        ctx: RpcContext,
+        _request: Empty,
+        sink: UnarySink<KeyImageStoreStates>,
+    ) {
+        mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
+            send_result(ctx, sink, self.get_store_states_impl(), logger)
+        })
+    }
+}
+
+/// A way in which the stores of a fleet disagree, or look unhealthy.
+#[derive(Clone, Debug, Display, Eq, PartialEq)]
+pub enum StoreStateDiscrepancy {
+    /// Stores for epoch {0} have processed different blocks: {1}
+    ProcessedBlocks(BlockRange, String),
+    /// Stores for epoch {0} hold different numbers of key images for the same
+    /// blocks: {1}
+    NumKeyImages(BlockRange, String),
+    /// Stores for epoch {0} were built with different ORAM capacities: {1}
+    OmapCapacity(BlockRange, String),
+    /// Stores run different server versions: {0}
+    ServerVersion(String),
+    /// No store has processed blocks {0}
+    CoverageGap(BlockRange),
+    /// Store {0} holds {1} key images, close to its ORAM capacity of {2}
+    NearCapacity(String, u64, u64),
+}
+
+/// Compare the states of a fleet of stores, and list where they disagree.
+///
+/// Stores with the same epoch are replicas of each other, and should agree on
+/// everything but how many blocks they have processed so far. Together, the
+/// stores should have processed every block up to the end of the furthest
+/// processed range.
+pub fn diff_store_states(states: &[KeyImageStoreState]) -> Vec<StoreStateDiscrepancy> {
+    let mut discrepancies = Vec::new();
+
+    let mut epochs: BTreeMap<BlockRange, Vec<&KeyImageStoreState>> = BTreeMap::new();
+    for state in states {
+        epochs
+            .entry(block_range(state.get_epoch_block_range()))
+            .or_default()
+            .push(state);
+    }
+    for (epoch, replicas) in epochs {
+        if let Some(differences) = describe_differences(&replicas, |state| {
+            block_range(state.get_processed_block_range()).to_string()
+        }) {
+            discrepancies.push(StoreStateDiscrepancy::ProcessedBlocks(
+                epoch.clone(),
+                differences,
+            ));
+        }
+
+        let mut same_blocks: BTreeMap<BlockRange, Vec<&KeyImageStoreState>> = BTreeMap::new();
+        for state in replicas.iter().copied() {
+            same_blocks
+                .entry(block_range(state.get_processed_block_range()))
+                .or_default()
+                .push(state);
+        }
+        for replicas in same_blocks.values() {
+            if let Some(differences) =
+                describe_differences(replicas, |state| state.get_num_key_images().to_string())
+            {
+                discrepancies.push(StoreStateDiscrepancy::NumKeyImages(
+                    epoch.clone(),
+                    differences,
+                ));
+            }
+        }
+
+        if let Some(differences) =
+            describe_differences(&replicas, |state| state.get_omap_capacity().to_string())
+        {
+            discrepancies.push(StoreStateDiscrepancy::OmapCapacity(epoch, differences));
+        }
+    }
+
+    let states = states.iter().collect::<Vec<_>>();
+    if let Some(differences) =
+        describe_differences(&states, |state| state.get_server_version().to_owned())
+    {
+        discrepancies.push(StoreStateDiscrepancy::ServerVersion(differences));
+    }
+
+    let processed_ranges = states
+        .iter()
+        .map(|state| block_range(state.get_processed_block_range()))
+        .collect::<Vec<_>>();
+    let num_blocks = processed_ranges
+        .iter()
+        .map(|range| range.end_block)
+        .max()
+        .unwrap_or_default();
+    discrepancies.extend(
+        find_coverage_gaps(&processed_ranges, num_blocks)
+            .into_iter()
+            .map(StoreStateDiscrepancy::CoverageGap),
+    );
+
+    discrepancies.extend(
+        states
+            .iter()
+            .filter(|state| {
+                state.get_num_key_images() * 100 > state.get_omap_capacity() * NEAR_CAPACITY_PERCENT
+            })
+            .map(|state| {
+                StoreStateDiscrepancy::NearCapacity(
+                    state.get_responder_id().to_owned(),
+                    state.get_num_key_images(),
+                    state.get_omap_capacity(),
+                )
+            }),
+    );
+
+    discrepancies
+}
+
+fn block_range(range: &fog_common::BlockRange) -> BlockRange {
+    BlockRange::new(range.get_start_block(), range.get_end_block())
+}
+
+/// If `describe` doesn't give the same value for all of `states`, list the
+/// value for each store.
+fn describe_differences(
+    states: &[&KeyImageStoreState],
+    describe: impl Fn(&KeyImageStoreState) -> String,
+) -> Option<String> {
+    let descriptions = states
+        .iter()
+        .map(|state| (state.get_responder_id(), describe(state)))
+        .collect::<Vec<_>>();
+    if descriptions.iter().map(|(_, value)| value).all_equal() {
+        return None;
+    }
+    Some(
+        descriptions
+            .iter()
+            .map(|(responder_id, value)| format!("{responder_id}={value}"))
+            .join(", "),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store_state(
+        responder_id: &str,
+        epoch: BlockRange,
+        processed: BlockRange,
+        num_key_images: u64,
+    ) -> KeyImageStoreState {
+        let mut state = KeyImageStoreState::new();
+        state.set_responder_id(responder_id.to_owned());
+        state.set_omap_capacity(1000);
+        state.set_num_key_images(num_key_images);
+        state.set_epoch_block_range((&epoch).into());
+        state.set_processed_block_range((&processed).into());
+        state.set_server_version("6.0.2".to_owned());
+        state
+    }
+
+    #[test]
+    fn agreeing_stores_have_no_discrepancies() {
+        let early = BlockRange::new(0, 100);
+        let late = BlockRange::new(100, u64::MAX);
+        let states = [
+            store_state("a:443", early.clone(), early.clone(), 500),
+            store_state("b:443", early.clone(), early.clone(), 500),
+            store_state("c:443", late.clone(), BlockRange::new(100, 150), 200),
+            store_state("d:443", late, BlockRange::new(100, 150), 200),
+        ];
+
+        assert_eq!(diff_store_states(&states), vec![]);
+    }
+
+    #[test]
+    fn replicas_claiming_different_coverage_are_reported() {
+        let epoch = BlockRange::new(0, 100);
+        let mut states = vec![
+            store_state("a:443", epoch.clone(), BlockRange::new(0, 100), 500),
+            store_state("b:443", epoch.clone(), BlockRange::new(0, 100), 490),
+            store_state("c:443", epoch.clone(), BlockRange::new(0, 90), 450),
+        ];
+        states[2].set_server_version("6.0.1".to_owned());
+
+        assert_eq!(
+            diff_store_states(&states),
+            vec![
+                StoreStateDiscrepancy::ProcessedBlocks(
+                    epoch.clone(),
+                    "a:443=[0,100), b:443=[0,100), c:443=[0,90)".to_owned()
+                ),
+                StoreStateDiscrepancy::NumKeyImages(epoch, "a:443=500, b:443=490".to_owned()),
+                StoreStateDiscrepancy::ServerVersion(
+                    "a:443=6.0.2, b:443=6.0.2, c:443=6.0.1".to_owned()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn gaps_and_full_stores_are_reported() {
+        let states = [
+            store_state(
+                "a:443",
+                BlockRange::new(0, 100),
+                BlockRange::new(0, 80),
+                100,
+            ),
+            store_state(
+                "b:443",
+                BlockRange::new(100, u64::MAX),
+                BlockRange::new(100, 120),
+                800,
+            ),
+        ];
+
+        assert_eq!(
+            diff_store_states(&states),
+            vec![
+                StoreStateDiscrepancy::CoverageGap(BlockRange::new(80, 100)),
+                StoreStateDiscrepancy::NearCapacity("b:443".to_owned(), 800, 1000),
+            ]
+        );
+    }
+}
@@ -5,35 +5,37 @@ use grpcio::{RpcContext, RpcStatus, UnarySink};
 use mc_common::logger::Logger;
 use mc_crypto_keys::CompressedRistrettoPublic;
 use mc_fog_api::{
-    ledger::{TxOutRequest, TxOutResponse},
+    ledger::{
+        BlockMerkleRoot, MerkleRootHistoryRequest, MerkleRootHistoryResponse, TxOutRequest,
+        TxOutResponse,
+    },
     ledger_grpc::FogUntrustedTxOutApi,
 };
-use mc_fog_block_provider::{BlockProvider, TxOutInfoByPublicKeyResponse};
+use mc_fog_block_provider::{BlockProvider, RootElementsResponse, TxOutInfoByPublicKeyResponse};
 use mc_util_grpc::{
-    check_request_chain_id, rpc_internal_error, rpc_invalid_arg_error, rpc_logger, send_result,
-    Authenticator,
+    rpc_internal_error, rpc_invalid_arg_error, rpc_logger, send_result, InterceptorChain,
 };
-use std::sync::Arc;
+use mc_util_metrics::service_metrics;
+
+/// The most blocks a get_merkle_root_history call may ask about.
+pub const MAX_MERKLE_ROOT_HISTORY_BLOCKS: u64 = 10_000;
 
 #[derive(Clone)]
 pub struct UntrustedTxOutService {
-    chain_id: String,
     block_provider: Box<dyn BlockProvider>,
-    authenticator: Arc<dyn Authenticator + Send + Sync>,
+    interceptors: InterceptorChain,
     logger: Logger,
 }
 
 impl UntrustedTxOutService {
     pub fn new(
-        chain_id: String,
         block_provider: Box<dyn BlockProvider>,
-        authenticator: Arc<dyn Authenticator + Send + Sync>,
+        interceptors: InterceptorChain,
         logger: Logger,
     ) -> Self {
         Self {
-            chain_id,
             block_provider,
-            authenticator,
+            interceptors,
             logger,
         }
     }
@@ -66,8 +68,56 @@ impl UntrustedTxOutService {
 
         Ok(response)
     }
+
+    fn get_merkle_root_history_impl(
+        &mut self,
+        request: MerkleRootHistoryRequest,
+    ) -> Result<MerkleRootHistoryResponse, RpcStatus> {
+        mc_common::trace_time!(self.logger, "Get Merkle Root History");
+
+        let range = request.get_range();
+        let num_requested = range.end_block.saturating_sub(range.start_block);
+        if num_requested > MAX_MERKLE_ROOT_HISTORY_BLOCKS {
+            return Err(rpc_invalid_arg_error(
+                "get_merkle_root_history",
+                format!(
+                    "{num_requested} blocks requested, at most {MAX_MERKLE_ROOT_HISTORY_BLOCKS} allowed"
+                ),
+                &self.logger,
+            ));
+        }
+        let block_indices = (range.start_block..range.end_block).collect::<Vec<_>>();
+
+        let RootElementsResponse {
+            results,
+            latest_block,
+        } = self
+            .block_provider
+            .get_root_elements(block_indices.as_slice())
+            .map_err(|err| rpc_internal_error("get_root_elements", err, &self.logger))?;
+
+        let mut response = MerkleRootHistoryResponse::new();
+        response.num_blocks = latest_block.index + 1;
+        response.global_txo_count = latest_block.cumulative_txo_count;
+
+        response.roots = block_indices
+            .into_iter()
+            .zip(results)
+            .filter_map(|(index, root)| {
+                let root = root?;
+                let mut result = BlockMerkleRoot::new();
+                result.index = index;
+                result.set_root_element((&root.root_element).into());
+                result.txo_count = root.txo_count;
+                Some(result)
+            })
+            .collect();
+
+        Ok(response)
+    }
 }
 
+#[service_metrics(SVC_COUNTERS)]
 impl FogUntrustedTxOutApi for UntrustedTxOutService {
     fn get_tx_outs(
         &mut self,
@@ -75,17 +125,32 @@ impl FogUntrustedTxOutApi for UntrustedTxOutService {
         request: TxOutRequest,
         sink: UnarySink<TxOutResponse>,
     ) {
-        let _timer = SVC_COUNTERS.req(&ctx);
         mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
-            if let Err(err) = check_request_chain_id(&self.chain_id, &ctx) {
+            if let Err(err) = self.interceptors.check(&ctx) {
                 return send_result(ctx, sink, Err(err), logger);
             }
 
-            if let Err(err) = self.authenticator.authenticate_rpc(&ctx) {
-                return send_result(ctx, sink, err.into(), logger);
+            send_result(ctx, sink, self.get_tx_outs_impl(request), logger)
+        })
+    }
+
+    fn get_merkle_root_history(
+        &mut self,
+        ctx: RpcContext,
+        request: MerkleRootHistoryRequest,
+        sink: UnarySink<MerkleRootHistoryResponse>,
+    ) {
+        mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
+            if let Err(err) = self.interceptors.check(&ctx) {
+                return send_result(ctx, sink, Err(err), logger);
             }
 
-            send_result(ctx, sink, self.get_tx_outs_impl(request), logger)
+            send_result(
+                ctx,
+                sink,
+                self.get_merkle_root_history_impl(request),
+                logger,
+            )
         })
     }
 }
@@ -16,26 +16,30 @@ use mc_fog_api::ledger::TxOutResultCode;
 use mc_fog_block_provider::LocalBlockProvider;
 use mc_fog_ledger_connection::{
     Error, FogKeyImageGrpcClient, FogMerkleProofGrpcClient, FogUntrustedLedgerGrpcClient,
-    KeyImageResultExtension, LedgerGrpcClient, OutputResultExtension,
+    KeyImageResultExtension, LedgerGrpcClient, NumBlocksConvergence, OutputResultExtension,
 };
 use mc_fog_ledger_enclave::LedgerSgxEnclave;
 use mc_fog_ledger_server::{
     sharding_strategy::EpochShardingStrategy, KeyImageStoreServer, LedgerRouterConfig,
-    LedgerRouterServer, LedgerStoreConfig, ShardingStrategy,
+    LedgerRouterServer, LedgerStoreConfig, ShardingStrategy, MAX_MERKLE_ROOT_HISTORY_BLOCKS,
+    MAX_TXO_COUNT_HISTORY_BLOCKS,
+};
+use mc_fog_test_infra::{
+    chaos_proxy::{ChaosConfig, ChaosProxy},
+    get_enclave_path,
 };
-use mc_fog_test_infra::get_enclave_path;
 use mc_fog_uri::{ConnectionUri, FogLedgerUri, KeyImageStoreUri};
 use mc_ledger_db::{test_utils::recreate_ledger_db, Ledger, LedgerDB};
 use mc_transaction_core::{
-    membership_proofs::compute_implied_merkle_root, ring_signature::KeyImage, tokens::Mob, Amount,
-    Token,
+    membership_proofs::compute_implied_merkle_root, ring_signature::KeyImage, tokens::Mob,
+    tx::TxOutMembershipElement, Amount, Token,
 };
 use mc_util_from_random::FromRandom;
 use mc_util_grpc::{GrpcRetryConfig, CHAIN_ID_MISMATCH_ERR_MSG};
 use mc_util_test_helper::{CryptoRng, RngCore, RngType, SeedableRng};
 use mc_util_uri::AdminUri;
 use mc_watcher::watcher_db::WatcherDB;
-use std::{path::PathBuf, str::FromStr, sync::Arc, thread::sleep, time::Duration};
+use std::{net::SocketAddr, path::PathBuf, str::FromStr, sync::Arc, thread::sleep, time::Duration};
 use tempfile::TempDir;
 use url::Url;
 
@@ -127,6 +131,7 @@ fn fog_ledger_merkle_proofs_test(logger: Logger) {
                 mobilecoind_uri: None,
                 admin_listen_uri: admin_listen_uri.clone(),
                 client_listen_uri: client_listen_uri.clone(),
+                additional_client_listen_uris: vec![],
                 client_responder_id: client_listen_uri
                     .responder_id()
                     .expect("Couldn't get responder ID for router"),
@@ -134,6 +139,20 @@ fn fog_ledger_merkle_proofs_test(logger: Logger) {
                 client_auth_token_secret: None,
                 client_auth_token_max_lifetime: Default::default(),
                 query_retries: 3,
+                interceptors: Default::default(),
+                message_size: Default::default(),
+                audit_log: Default::default(),
+                shard_coverage_policy: Default::default(),
+                merkle_proof_cache_size: 0,
+                merkle_proof_cache_poll_interval: Default::default(),
+                concurrency_limits: Default::default(),
+                admission_control: Default::default(),
+                store_attestation_max_backoff: Duration::from_secs(1),
+                blocked_store_measurements: vec![],
+                key_image_subscription_poll_interval: Duration::from_millis(100),
+                key_image_subscription_max_streams: 10000,
+                key_image_subscription_max_key_images: 1000,
+                padding_buckets: vec![],
             };
 
             let enclave = LedgerSgxEnclave::new(
@@ -213,7 +232,7 @@ fn fog_ledger_merkle_proofs_test(logger: Logger) {
             // Check that wrong chain id results in an error
             let mut client = FogMerkleProofGrpcClient::new(
                 "wrong".to_string(),
-                client_listen_uri,
+                client_listen_uri.clone(),
                 GRPC_RETRY_CONFIG,
                 [identity],
                 grpc_env,
@@ -226,18 +245,25 @@ fn fog_ledger_merkle_proofs_test(logger: Logger) {
             );
 
             if let Err(err) = result {
-                match err {
-                    Error::Connection(
-                        _,
-                        retry::Error {
-                            error:
-                                mc_fog_enclave_connection::Error::Rpc(grpcio::Error::RpcFailure(status)),
-                            ..
-                        },
-                    ) => {
-                        let expected = format!("{} '{}'", CHAIN_ID_MISMATCH_ERR_MSG, "local");
-                        assert_eq!(status.message(), expected);
-                    }
+                match &err {
+                    Error::Connection(_, retry::Error { error, .. }) => match error.inner() {
+                        mc_fog_enclave_connection::Error::Rpc(grpcio::Error::RpcFailure(
+                            status,
+                        )) => {
+                            let expected = format!("{} '{}'", CHAIN_ID_MISMATCH_ERR_MSG, "local");
+                            assert_eq!(status.message(), expected);
+                            let context = error.context().expect("error has no context");
+                            assert_eq!(context.endpoint, client_listen_uri.addr());
+                            // Every retry failed the same way.
+                            assert_eq!(
+                                context.attempt,
+                                GRPC_RETRY_CONFIG.grpc_retry_count as u64 + 1
+                            );
+                        }
+                        _ => {
+                            panic!("unexpected enclave connection error: {err}");
+                        }
+                    },
                     _ => {
                         panic!("unexpected grpcio error: {err}");
                     }
@@ -355,6 +381,12 @@ fn fog_ledger_key_images_test(logger: Logger) {
                 omap_capacity: OMAP_CAPACITY,
                 sharding_strategy: ShardingStrategy::Epoch(EpochShardingStrategy::default()),
                 poll_interval: Duration::from_millis(250),
+                additional_epochs: vec![],
+                key_image_export_dir: None,
+                intake_queue_dir: None,
+                intake_queue_capacity: 1000,
+                oram_memory_budget: None,
+                oram_spill_dir: None,
             };
             let store_enclave = LedgerSgxEnclave::new(
                 get_enclave_path(mc_fog_ledger_enclave::ENCLAVE_FILE),
@@ -389,6 +421,7 @@ fn fog_ledger_key_images_test(logger: Logger) {
                 mobilecoind_uri: None,
                 admin_listen_uri: admin_listen_uri.clone(),
                 client_listen_uri: client_listen_uri.clone(),
+                additional_client_listen_uris: vec![],
                 shard_uris: vec![store_uri],
                 client_responder_id: client_listen_uri
                     .responder_id()
@@ -396,6 +429,20 @@ fn fog_ledger_key_images_test(logger: Logger) {
                 client_auth_token_secret: None,
                 client_auth_token_max_lifetime: Default::default(),
                 query_retries: 3,
+                interceptors: Default::default(),
+                message_size: Default::default(),
+                audit_log: Default::default(),
+                shard_coverage_policy: Default::default(),
+                merkle_proof_cache_size: 0,
+                merkle_proof_cache_poll_interval: Default::default(),
+                concurrency_limits: Default::default(),
+                admission_control: Default::default(),
+                store_attestation_max_backoff: Duration::from_secs(1),
+                blocked_store_measurements: vec![],
+                key_image_subscription_poll_interval: Duration::from_millis(100),
+                key_image_subscription_max_streams: 10000,
+                key_image_subscription_max_key_images: 1000,
+                padding_buckets: vec![],
             };
 
             let enclave = LedgerSgxEnclave::new(
@@ -415,32 +462,37 @@ fn fog_ledger_key_images_test(logger: Logger) {
             store_server.start();
             router_server.start();
 
+            // The router attests with its store before reporting ready.
+            let mut n = 0;
+            while !router_server.is_ready() {
+                sleep(Duration::from_millis(100));
+                n += 1;
+                if n > 300 {
+                    panic!("Router did not attest with its store");
+                }
+            }
+
             let identity = mc_fog_ledger_enclave_measurement::mr_signer_identity(None);
 
             let grpc_env = Arc::new(grpcio::EnvBuilder::new().build());
             let mut client =
                 LedgerGrpcClient::new(client_listen_uri, [identity], grpc_env, logger.clone());
 
-            // Check on key images
-            let mut response =
-                block_on(client.check_key_images(&[keys[0], keys[1], keys[3], keys[7], keys[19]]))
-                    .expect("check_key_images failed");
-
-            let mut n = 1;
-            // adding a delay to give fog ledger time to fully initialize
-            while response.num_blocks != num_blocks {
-                response = block_on(
-                    client.check_key_images(&[keys[0], keys[1], keys[3], keys[7], keys[19]]),
-                )
-                .expect("check_key_images failed");
-
-                sleep(Duration::from_secs(10));
-                // panic on the 20th time
-                n += 1; //
-                if n > 20 {
-                    panic!("Fog ledger not  fully initialized");
-                }
+            // Check on key images, once fog ledger has loaded the ledger
+            let response = NumBlocksConvergence {
+                timeout: Duration::from_secs(200),
+                ..NumBlocksConvergence::new(num_blocks)
             }
+            .wait_for(
+                || {
+                    block_on(
+                        client.check_key_images(&[keys[0], keys[1], keys[3], keys[7], keys[19]]),
+                    )
+                },
+                |response| response.num_blocks,
+            )
+            .expect("check_key_images failed");
+            assert_eq!(response.num_blocks, num_blocks);
 
             // FIXME assert_eq!(response.num_txos, ...);
             assert_eq!(response.results[0].key_image, keys[0]);
@@ -575,6 +627,7 @@ fn fog_ledger_blocks_api_test(logger: Logger) {
             mobilecoind_uri: None,
             admin_listen_uri,
             client_listen_uri: client_listen_uri.clone(),
+            additional_client_listen_uris: vec![],
             client_responder_id: client_listen_uri
                 .responder_id()
                 .expect("Couldn't get responder ID for router"),
@@ -582,6 +635,20 @@ fn fog_ledger_blocks_api_test(logger: Logger) {
             client_auth_token_secret: None,
             client_auth_token_max_lifetime: Default::default(),
             query_retries: 3,
+            interceptors: Default::default(),
+            message_size: Default::default(),
+            audit_log: Default::default(),
+            shard_coverage_policy: Default::default(),
+            merkle_proof_cache_size: 0,
+            merkle_proof_cache_poll_interval: Default::default(),
+            concurrency_limits: Default::default(),
+            admission_control: Default::default(),
+            store_attestation_max_backoff: Duration::from_secs(1),
+            blocked_store_measurements: vec![],
+            key_image_subscription_poll_interval: Duration::from_millis(100),
+            key_image_subscription_max_streams: 10000,
+            key_image_subscription_max_key_images: 1000,
+            padding_buckets: vec![],
         };
 
         let enclave = LedgerSgxEnclave::new(
@@ -647,6 +714,28 @@ fn fog_ledger_blocks_api_test(logger: Logger) {
         );
         assert_eq!(result.num_blocks, num_blocks);
         assert_eq!(result.global_txo_count, ledger.num_txos().unwrap());
+
+        // Get the txo count history, past the end of the ledger
+        let result = client.get_txo_count_history(1..10).unwrap();
+        let history = result
+            .blocks
+            .iter()
+            .map(|block| (block.index, block.global_txo_count))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            history,
+            (1..num_blocks)
+                .map(|index| (index, ledger.get_block(index).unwrap().cumulative_txo_count))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(history[..2], [(1, 3), (2, 6)]);
+        assert_eq!(result.num_blocks, num_blocks);
+        assert_eq!(result.global_txo_count, ledger.num_txos().unwrap());
+
+        // Ranges over the limit are rejected
+        assert!(client
+            .get_txo_count_history(0..MAX_TXO_COUNT_HISTORY_BLOCKS + 1)
+            .is_err());
     }
 
     // grpcio detaches all its threads and does not join them :(
@@ -734,6 +823,7 @@ fn fog_ledger_untrusted_tx_out_api_test(logger: Logger) {
             mobilecoind_uri: None,
             admin_listen_uri,
             client_listen_uri: client_listen_uri.clone(),
+            additional_client_listen_uris: vec![],
             client_responder_id: client_listen_uri
                 .responder_id()
                 .expect("Couldn't get responder ID for router"),
@@ -741,6 +831,20 @@ fn fog_ledger_untrusted_tx_out_api_test(logger: Logger) {
             client_auth_token_secret: None,
             client_auth_token_max_lifetime: Default::default(),
             query_retries: 3,
+            interceptors: Default::default(),
+            message_size: Default::default(),
+            audit_log: Default::default(),
+            shard_coverage_policy: Default::default(),
+            merkle_proof_cache_size: 0,
+            merkle_proof_cache_poll_interval: Default::default(),
+            concurrency_limits: Default::default(),
+            admission_control: Default::default(),
+            store_attestation_max_backoff: Duration::from_secs(1),
+            blocked_store_measurements: vec![],
+            key_image_subscription_poll_interval: Duration::from_millis(100),
+            key_image_subscription_max_streams: 10000,
+            key_image_subscription_max_key_images: 1000,
+            padding_buckets: vec![],
         };
 
         let enclave = LedgerSgxEnclave::new(
@@ -796,6 +900,45 @@ fn fog_ledger_untrusted_tx_out_api_test(logger: Logger) {
             result.results[1].timestamp_result_code,
             TimestampResultCode::BlockIndexOutOfBounds as u32
         );
+
+        // Get the merkle root history, past the end of the ledger
+        let result = client.get_merkle_root_history(0..10).unwrap();
+        assert_eq!(result.num_blocks, 4);
+        assert_eq!(result.global_txo_count, ledger.num_txos().unwrap());
+        let history = result
+            .roots
+            .iter()
+            .map(|root| {
+                (
+                    root.index,
+                    TxOutMembershipElement::try_from(root.get_root_element()).unwrap(),
+                    root.txo_count,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            history,
+            (0..4)
+                .map(|index| {
+                    let block = ledger.get_block(index).unwrap();
+                    let txo_count = block.cumulative_txo_count
+                        - ledger.get_block_contents(index).unwrap().outputs.len() as u64;
+                    (index, block.root_element, txo_count)
+                })
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            history
+                .iter()
+                .map(|(_, _, txo_count)| *txo_count)
+                .collect::<Vec<_>>(),
+            [0, 1, 3, 6]
+        );
+
+        // Ranges over the limit are rejected
+        assert!(client
+            .get_merkle_root_history(0..MAX_MERKLE_ROOT_HISTORY_BLOCKS + 1)
+            .is_err());
     }
 
     // grpcio detaches all its threads and does not join them :(
@@ -905,6 +1048,12 @@ fn fog_router_unary_key_image_test(logger: Logger) {
                 omap_capacity: OMAP_CAPACITY,
                 sharding_strategy: ShardingStrategy::Epoch(EpochShardingStrategy::default()),
                 poll_interval: Duration::from_millis(250),
+                additional_epochs: vec![],
+                key_image_export_dir: None,
+                intake_queue_dir: None,
+                intake_queue_capacity: 1000,
+                oram_memory_budget: None,
+                oram_spill_dir: None,
             };
             let store_enclave = LedgerSgxEnclave::new(
                 get_enclave_path(mc_fog_ledger_enclave::ENCLAVE_FILE),
@@ -939,6 +1088,7 @@ fn fog_router_unary_key_image_test(logger: Logger) {
                 mobilecoind_uri: None,
                 admin_listen_uri: admin_listen_uri.clone(),
                 client_listen_uri: router_client_listen_uri.clone(),
+                additional_client_listen_uris: vec![],
                 client_responder_id: router_client_listen_uri
                     .responder_id()
                     .expect("Couldn't get responder ID for router"),
@@ -946,6 +1096,20 @@ fn fog_router_unary_key_image_test(logger: Logger) {
                 client_auth_token_secret: None,
                 client_auth_token_max_lifetime: Default::default(),
                 query_retries: 3,
+                interceptors: Default::default(),
+                message_size: Default::default(),
+                audit_log: Default::default(),
+                shard_coverage_policy: Default::default(),
+                merkle_proof_cache_size: 0,
+                merkle_proof_cache_poll_interval: Default::default(),
+                concurrency_limits: Default::default(),
+                admission_control: Default::default(),
+                store_attestation_max_backoff: Duration::from_secs(1),
+                blocked_store_measurements: vec![],
+                key_image_subscription_poll_interval: Duration::from_millis(100),
+                key_image_subscription_max_streams: 10000,
+                key_image_subscription_max_key_images: 1000,
+                padding_buckets: vec![],
             };
 
             let enclave = LedgerSgxEnclave::new(
@@ -977,26 +1141,14 @@ fn fog_router_unary_key_image_test(logger: Logger) {
                 logger.clone(),
             );
 
-            // Check on key images
-            let mut response = client
-                .check_key_images(&[keys[0], keys[1], keys[3], keys[7], keys[19]])
-                .expect("check_key_images failed");
-
-            let mut n = 1;
-
-            while response.num_blocks != num_blocks {
-                response = client
-                    .check_key_images(&[keys[0], keys[1], keys[3], keys[7], keys[19]])
-                    .expect("check_key_images failed");
-
-                // Ideally this should not require a sleep, but that's for a later PR.
-                sleep(Duration::from_secs(10));
-                // panic on the 20th time
-                n += 1; //
-                if n > 20 {
-                    panic!("Fog ledger not  fully initialized");
-                }
+            // Check on key images, once fog ledger has loaded the ledger
+            let response = NumBlocksConvergence {
+                timeout: Duration::from_secs(200),
+                ..NumBlocksConvergence::new(num_blocks)
             }
+            .check_key_images(&mut client, &[keys[0], keys[1], keys[3], keys[7], keys[19]])
+            .expect("check_key_images failed");
+            assert_eq!(response.num_blocks, num_blocks);
 
             // FIXME assert_eq!(response.num_txos, ...);
             assert_eq!(response.results[0].key_image, keys[0]);
@@ -1054,6 +1206,195 @@ fn fog_router_unary_key_image_test(logger: Logger) {
     }
 }
 
+/// Key image queries should survive latency and connection resets between the
+/// client and the router, and between the router and the key image store.
+#[test_with_logger]
+fn fog_router_key_images_through_chaos_test(logger: Logger) {
+    let mut rng = RngType::from_seed([0u8; 32]);
+    let block_version = BlockVersion::MAX;
+    let alice = AccountKey::random_with_fog(&mut rng);
+    let recipients = vec![alice.default_subaddress()];
+    let keys: Vec<KeyImage> = (0..4).map(|x| KeyImage::from(x as u64)).collect();
+
+    let ledger_dir = TempDir::new().expect("Could not get test_ledger tempdir");
+    let db_full_path = ledger_dir.path();
+    let mut ledger = recreate_ledger_db(db_full_path);
+    let (watcher, watcher_dir) = setup_watcher_db(logger.clone());
+    add_block_to_ledger(
+        block_version,
+        &mut ledger,
+        &recipients,
+        &[],
+        &mut rng,
+        &watcher,
+    );
+    let num_blocks = add_block_to_ledger(
+        block_version,
+        &mut ledger,
+        &recipients,
+        &keys[0..2],
+        &mut rng,
+        &watcher,
+    );
+
+    {
+        let latency = ChaosConfig {
+            latency: Duration::from_millis(20),
+            ..Default::default()
+        };
+
+        // The store and router only see connections from their proxies, so
+        // they respond as the proxies' addresses.
+        let store_port = portpicker::pick_unused_port().expect("No free ports");
+        let store_proxy = ChaosProxy::start(
+            SocketAddr::from(([127, 0, 0, 1], store_port)),
+            latency.clone(),
+            1,
+        )
+        .expect("Could not start store proxy");
+        let store_uri = KeyImageStoreUri::from_str(&format!(
+            "insecure-key-image-store://127.0.0.1:{store_port}"
+        ))
+        .unwrap();
+        let proxied_store_uri = KeyImageStoreUri::from_str(&format!(
+            "insecure-key-image-store://{}",
+            store_proxy.addr()
+        ))
+        .unwrap();
+        let store_config = LedgerStoreConfig {
+            chain_id: "local".to_string(),
+            client_responder_id: proxied_store_uri
+                .responder_id()
+                .expect("Couldn't get responder ID for store"),
+            client_listen_uri: store_uri,
+            ledger_db: Some(db_full_path.to_path_buf()),
+            watcher_db: Some(watcher_dir.clone()),
+            mobilecoind_uri: None,
+            admin_listen_uri: None,
+            client_auth_token_secret: None,
+            client_auth_token_max_lifetime: Default::default(),
+            omap_capacity: OMAP_CAPACITY,
+            sharding_strategy: ShardingStrategy::Epoch(EpochShardingStrategy::default()),
+            poll_interval: Duration::from_millis(250),
+            additional_epochs: vec![],
+            key_image_export_dir: None,
+            intake_queue_dir: None,
+            intake_queue_capacity: 1000,
+            oram_memory_budget: None,
+            oram_spill_dir: None,
+        };
+        let store_enclave = LedgerSgxEnclave::new(
+            get_enclave_path(mc_fog_ledger_enclave::ENCLAVE_FILE),
+            &store_config.client_responder_id,
+            store_config.omap_capacity,
+            logger.clone(),
+        );
+        let mut store_server = KeyImageStoreServer::new_from_config(
+            store_config,
+            store_enclave,
+            LocalBlockProvider::new(ledger.clone(), watcher.clone()),
+            EpochShardingStrategy::default(),
+            SystemTimeProvider,
+            logger.clone(),
+        );
+
+        let router_port = portpicker::pick_unused_port().expect("No free ports");
+        let router_proxy =
+            ChaosProxy::start(SocketAddr::from(([127, 0, 0, 1], router_port)), latency, 2)
+                .expect("Could not start router proxy");
+        let router_client_listen_uri =
+            FogLedgerUri::from_str(&format!("insecure-fog-ledger://127.0.0.1:{router_port}"))
+                .unwrap();
+        let proxied_router_uri =
+            FogLedgerUri::from_str(&format!("insecure-fog-ledger://{}", router_proxy.addr()))
+                .unwrap();
+        let admin_listen_uri = AdminUri::from_str(&format!(
+            "insecure-mca://127.0.0.1:{}",
+            portpicker::pick_unused_port().expect("No free ports")
+        ))
+        .unwrap();
+        let router_config = LedgerRouterConfig {
+            chain_id: "local".to_string(),
+            ledger_db: Some(db_full_path.to_path_buf()),
+            watcher_db: Some(watcher_dir),
+            mobilecoind_uri: None,
+            admin_listen_uri,
+            client_listen_uri: router_client_listen_uri,
+            additional_client_listen_uris: vec![],
+            client_responder_id: proxied_router_uri
+                .responder_id()
+                .expect("Couldn't get responder ID for router"),
+            shard_uris: vec![proxied_store_uri],
+            client_auth_token_secret: None,
+            client_auth_token_max_lifetime: Default::default(),
+            query_retries: 3,
+            interceptors: Default::default(),
+            message_size: Default::default(),
+            audit_log: Default::default(),
+            shard_coverage_policy: Default::default(),
+            merkle_proof_cache_size: 0,
+            merkle_proof_cache_poll_interval: Default::default(),
+            concurrency_limits: Default::default(),
+            admission_control: Default::default(),
+            store_attestation_max_backoff: Duration::from_secs(1),
+            blocked_store_measurements: vec![],
+            key_image_subscription_poll_interval: Duration::from_millis(100),
+            key_image_subscription_max_streams: 10000,
+            key_image_subscription_max_key_images: 1000,
+            padding_buckets: vec![],
+        };
+        let enclave = LedgerSgxEnclave::new(
+            get_enclave_path(mc_fog_ledger_enclave::ENCLAVE_FILE),
+            &router_config.client_responder_id,
+            0,
+            logger.clone(),
+        );
+        let mut router_server = LedgerRouterServer::new(
+            router_config,
+            enclave,
+            LocalBlockProvider::new(ledger.clone(), watcher.clone()),
+            logger.clone(),
+        );
+
+        store_server.start();
+        router_server.start();
+
+        let identity = mc_fog_ledger_enclave_measurement::mr_signer_identity(None);
+        let grpc_env = Arc::new(grpcio::EnvBuilder::new().build());
+        let mut client = FogKeyImageGrpcClient::new(
+            String::default(),
+            proxied_router_uri,
+            GRPC_RETRY_CONFIG,
+            [identity],
+            grpc_env,
+            logger.clone(),
+        );
+
+        // Wait for the store to load the ledger.
+        let response = NumBlocksConvergence {
+            timeout: Duration::from_secs(20),
+            ..NumBlocksConvergence::new(num_blocks)
+        }
+        .wait_for(
+            || {
+                // Cut every connection before each query, so that each one has
+                // to reconnect, and possibly reattest, on both hops.
+                router_proxy.reset_connections();
+                store_proxy.reset_connections();
+                client.check_key_images(&[keys[0], keys[3]])
+            },
+            |response| response.num_blocks,
+        )
+        .expect("check_key_images failed");
+        assert_eq!(response.num_blocks, num_blocks);
+        assert_eq!(response.results[0].status(), Ok(Some(1)));
+        assert_eq!(response.results[1].status(), Ok(None));
+    }
+
+    // grpcio detaches all its threads and does not join them, see above.
+    sleep(Duration::from_millis(1000));
+}
+
 // Infra
 
 /// Adds a block containing one txo for each provided recipient and returns new
@@ -15,7 +15,10 @@ use mc_fog_ledger_server::{
     LedgerRouterServer, LedgerStoreConfig, ShardingStrategy,
 };
 use mc_fog_test_infra::get_enclave_path;
-use mc_fog_types::{common::BlockRange, ledger::KeyImageResult};
+use mc_fog_types::{
+    common::BlockRange,
+    ledger::{KeyImageResult, KeyImageResultCode},
+};
 use mc_fog_uri::{FogLedgerUri, KeyImageStoreUri};
 use mc_ledger_db::{test_utils::recreate_ledger_db, LedgerDB};
 use mc_rand::{CryptoRng, RngCore};
@@ -83,6 +86,12 @@ fn create_store_config(
         omap_capacity,
         sharding_strategy: ShardingStrategy::Epoch(EpochShardingStrategy::new(block_range)),
         poll_interval: POLL_INTERVAL,
+        additional_epochs: vec![],
+        key_image_export_dir: None,
+        intake_queue_dir: None,
+        intake_queue_capacity: 1000,
+        oram_memory_budget: None,
+        oram_spill_dir: None,
     }
 }
 
@@ -215,10 +224,25 @@ fn create_router(
             .responder_id()
             .expect("Couldn't get responder ID for router"),
         client_listen_uri: uri,
+        additional_client_listen_uris: vec![],
         admin_listen_uri: admin_uri,
         client_auth_token_secret: None,
         client_auth_token_max_lifetime: Default::default(),
         query_retries: 3,
+        interceptors: Default::default(),
+        message_size: Default::default(),
+        audit_log: Default::default(),
+        shard_coverage_policy: Default::default(),
+        merkle_proof_cache_size: 0,
+        merkle_proof_cache_poll_interval: Default::default(),
+        concurrency_limits: Default::default(),
+        admission_control: Default::default(),
+        store_attestation_max_backoff: Duration::from_secs(1),
+        blocked_store_measurements: vec![],
+        key_image_subscription_poll_interval: Duration::from_millis(100),
+        key_image_subscription_max_streams: 10000,
+        key_image_subscription_max_key_images: 1000,
+        padding_buckets: vec![],
     };
 
     let enclave = LedgerSgxEnclave::new(
@@ -437,6 +461,25 @@ async fn smoke_test() {
     assert_eq!(response.global_txo_count, new_transactions + 1);
     assert_eq!(response.latest_block_version, *BlockVersion::MAX);
     assert_eq!(response.max_block_version, *BlockVersion::MAX);
+
+    // Grab them all at once again, streaming partial results. Partial results
+    // never claim a key image is spent unless the final results do.
+    let mut partial_responses = vec![];
+    let streamed_response = test_environment
+        .router_client
+        .check_key_images_streaming(&keys, |partial| partial_responses.push(partial))
+        .await
+        .expect("check_key_images_streaming failed");
+    assert_eq!(streamed_response.results, response.results);
+    assert!(partial_responses.len() < (num_stores + 1) as usize);
+    for partial in partial_responses {
+        assert_eq!(partial.results.len(), key_index as usize);
+        for (partial_result, result) in partial.results.iter().zip(&response.results) {
+            if partial_result.key_image_result_code == KeyImageResultCode::Spent as u32 {
+                assert_eq!(partial_result, result);
+            }
+        }
+    }
 }
 
 #[tokio::test(flavor = "multi_thread")]
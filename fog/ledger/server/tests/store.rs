@@ -30,7 +30,7 @@ use mc_fog_types::ledger::{CheckKeyImagesRequest, KeyImageQuery};
 use mc_fog_uri::{ConnectionUri, KeyImageStoreScheme, KeyImageStoreUri};
 use mc_ledger_db::{test_utils::recreate_ledger_db, LedgerDB};
 use mc_rand::{CryptoRng, RngCore};
-use mc_util_grpc::AnonymousAuthenticator;
+use mc_util_grpc::InterceptorChain;
 use mc_util_test_helper::{Rng, RngType, SeedableRng};
 use mc_util_uri::UriScheme;
 use mc_watcher::watcher_db::WatcherDB;
@@ -124,6 +124,12 @@ impl<R: RngCore + CryptoRng> TestingContext<R> {
             omap_capacity,
             sharding_strategy: ShardingStrategy::Epoch(EpochShardingStrategy::default()),
             poll_interval: Duration::from_millis(250),
+            additional_epochs: vec![],
+            key_image_export_dir: None,
+            intake_queue_dir: None,
+            intake_queue_capacity: 1000,
+            oram_memory_budget: None,
+            oram_spill_dir: None,
         };
 
         Self {
@@ -167,7 +173,7 @@ pub fn direct_key_image_store_check(logger: Logger) {
         client_listen_uri.clone(),
         enclave.clone(), //LedgerSgxEnclave is an Arc<SgxEnclave> internally
         shared_state.clone(),
-        Arc::new(AnonymousAuthenticator),
+        InterceptorChain::new(),
         logger.clone(),
     );
 
@@ -254,7 +260,7 @@ pub fn direct_key_image_store_check(logger: Logger) {
 
     // Decrypt and seal
     let sealed_query = enclave
-        .decrypt_and_seal_query(msg)
+        .decrypt_and_seal_query(msg, 0)
         .expect("Unable to decrypt and seal client message.");
 
     let mut multi_query = enclave
@@ -267,12 +273,18 @@ pub fn direct_key_image_store_check(logger: Logger) {
     println!("Nonce session on message is {:?}", query.channel_id);
 
     // Get an untrusted query
-    let (processed_block_range, last_known_block_cumulative_txo_count, latest_block_version) = {
+    let (
+        processed_block_range,
+        last_known_block_cumulative_txo_count,
+        latest_block_version,
+        ledger_num_blocks,
+    ) = {
         let shared_state = shared_state.lock().expect("mutex poisoned");
         (
             shared_state.processed_block_range.clone(),
             shared_state.last_known_block_cumulative_txo_count,
             shared_state.latest_block_version,
+            shared_state.ledger_num_blocks,
         )
     };
 
@@ -281,6 +293,7 @@ pub fn direct_key_image_store_check(logger: Logger) {
         last_known_block_cumulative_txo_count,
         latest_block_version,
         max_block_version: latest_block_version.max(*MAX_BLOCK_VERSION),
+        ledger_num_blocks,
     };
 
     let result = enclave
@@ -301,6 +314,8 @@ pub fn direct_key_image_store_check(logger: Logger) {
     let done_response: CheckKeyImagesResponse =
         mc_util_serial::decode(&plaintext_bytes).expect("Failed to decode CheckKeyImagesResponse.");
     assert_eq!(done_response.results.len(), 1);
+    assert_eq!(done_response.ledger_num_blocks, ledger_num_blocks);
+    assert!(!done_response.serving_degraded);
 
     let test_results = done_response
         .results
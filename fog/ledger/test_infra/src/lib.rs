@@ -72,6 +72,10 @@ impl LedgerEnclave for MockEnclave {
         unimplemented!()
     }
 
+    fn set_padding_buckets(&self, _padding_buckets: Vec<u32>) -> EnclaveResult<()> {
+        unimplemented!()
+    }
+
     fn get_identity(&self) -> EnclaveResult<X25519Public> {
         unimplemented!()
     }
@@ -121,9 +125,17 @@ impl LedgerEnclave for MockEnclave {
         unimplemented!()
     }
 
+    fn set_blocked_store_measurements(
+        &self,
+        _measurements: Vec<[u8; 32]>,
+    ) -> EnclaveResult<Vec<ResponderId>> {
+        unimplemented!()
+    }
+
     fn decrypt_and_seal_query(
         &self,
         _client_query: EnclaveMessage<ClientSession>,
+        _max_key_images: usize,
     ) -> EnclaveResult<mc_attest_enclave_api::SealedClientMessage> {
         unimplemented!()
     }
@@ -0,0 +1,49 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Errors which can occur when verifying fog ledger responses.
+
+use displaydoc::Display;
+use mc_blockchain_types::BlockIndex;
+use mc_fog_ledger_connection_core::{KeyImageQueryError, OutputError};
+use mc_transaction_core::membership_proofs::MembershipProofError;
+
+/// An error verifying a fog ledger response
+#[derive(Debug, Display, PartialEq)]
+pub enum Error {
+    /// The id of block {0} does not match its contents
+    InvalidBlockId(BlockIndex),
+    /// Only {0} trusted validators signed block {1}, but {2} are required
+    NotEnoughSignatures(usize, BlockIndex, usize),
+    /// The contents do not match the hash in block {0}
+    BlockContentsHashMismatch(BlockIndex),
+    /// The result for output {0} carries a proof for output {1}
+    ProofIndexMismatch(u64, u64),
+    /// The membership proof of output {0} is malformed: {1}
+    InvalidMembershipProof(u64, MembershipProofError),
+    /// The membership proof of output {0} does not lead to the root of block
+    /// {1}
+    OutputNotInBlock(u64, BlockIndex),
+    /// Output query failed: {0}
+    Output(OutputError),
+    /// Key image query failed: {0}
+    KeyImage(KeyImageQueryError),
+    /// The key image is not reported as spent
+    KeyImageNotSpent,
+    /// The key image was reported spent in block {0}, but block {1} was given
+    WrongBlock(BlockIndex, BlockIndex),
+    /// The key image was reported spent in block {0}, but is not in its
+    /// contents
+    KeyImageNotInBlock(BlockIndex),
+}
+
+impl From<OutputError> for Error {
+    fn from(src: OutputError) -> Self {
+        Self::Output(src)
+    }
+}
+
+impl From<KeyImageQueryError> for Error {
+    fn from(src: KeyImageQueryError) -> Self {
+        Self::KeyImage(src)
+    }
+}
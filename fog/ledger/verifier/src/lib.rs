@@ -0,0 +1,19 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Verification of fog ledger responses by light clients.
+//!
+//! Fog ledger answers queries about the blockchain, but a client talking to
+//! it only knows that the answers come from an attested enclave, not that the
+//! blocks the enclave was fed are the ones the consensus network agreed on.
+//! This crate checks block headers against signatures made by a trusted set
+//! of consensus validators, and then checks fog ledger's answers against those
+//! headers, so that light clients need not trust the fog operator for the
+//! integrity of the blockchain.
+
+#![deny(missing_docs)]
+
+mod error;
+mod verifier;
+
+pub use error::Error;
+pub use verifier::{FogLedgerVerifier, TrustedBlockSigners};
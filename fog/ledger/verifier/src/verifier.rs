@@ -0,0 +1,345 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+use crate::Error;
+use mc_blockchain_types::{Block, BlockContents, BlockSignature};
+use mc_crypto_keys::Ed25519Public;
+use mc_fog_ledger_connection_core::{KeyImageResultExtension, OutputResultExtension};
+use mc_fog_types::ledger::{GetOutputsResponse, KeyImageResult};
+use mc_transaction_core::{
+    membership_proofs::is_membership_proof_valid,
+    tx::{TxOut, TxOutMembershipProof},
+};
+use std::collections::BTreeSet;
+
+/// The consensus validators whose block signatures are trusted, and how many
+/// of them must sign a block for it to be accepted.
+///
+/// The signatures are those the validators make over each block they
+/// externalize, as collected from their block archives by the watcher.
+#[derive(Clone, Debug)]
+pub struct TrustedBlockSigners {
+    signers: BTreeSet<Ed25519Public>,
+    threshold: usize,
+}
+
+impl TrustedBlockSigners {
+    /// Trust blocks signed by at least `threshold` of `signers`.
+    pub fn new(signers: impl IntoIterator<Item = Ed25519Public>, threshold: usize) -> Self {
+        Self {
+            signers: signers.into_iter().collect(),
+            threshold,
+        }
+    }
+
+    /// Verify that `block` is well-formed, and that at least the threshold of
+    /// trusted validators signed it. Signatures by other signers, and invalid
+    /// signatures, are ignored.
+    pub fn verify_block(&self, block: &Block, signatures: &[BlockSignature]) -> Result<(), Error> {
+        if !block.is_block_id_valid() {
+            return Err(Error::InvalidBlockId(block.index));
+        }
+
+        let trusted_signers = signatures
+            .iter()
+            .filter(|signature| self.signers.contains(signature.signer()))
+            .filter(|signature| signature.verify(block).is_ok())
+            .map(|signature| signature.signer())
+            .collect::<BTreeSet<_>>();
+        if trusted_signers.len() < self.threshold {
+            return Err(Error::NotEnoughSignatures(
+                trusted_signers.len(),
+                block.index,
+                self.threshold,
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Verifies fog ledger responses against block headers signed by trusted
+/// validators.
+///
+/// Like the light client verifier, this makes no network connections, and
+/// verifying things does not change its state. The caller fetches the blocks
+/// and signatures, e.g. from a watcher, alongside the fog ledger response.
+#[derive(Clone, Debug)]
+pub struct FogLedgerVerifier {
+    /// The validators whose signatures on block headers are trusted.
+    pub trusted_signers: TrustedBlockSigners,
+}
+
+impl FogLedgerVerifier {
+    /// Verify against block headers signed by `trusted_signers`.
+    pub fn new(trusted_signers: TrustedBlockSigners) -> Self {
+        Self { trusted_signers }
+    }
+
+    /// Verify that `block` was signed by enough trusted validators.
+    pub fn verify_block(&self, block: &Block, signatures: &[BlockSignature]) -> Result<(), Error> {
+        self.trusted_signers.verify_block(block, signatures)
+    }
+
+    /// Verify a GetOutputs response, whose proofs were requested relative to
+    /// `merkle_root_block`: the block must be signed, and the membership proof
+    /// of every output which exists must lead to the block's root element.
+    ///
+    /// Returns the status of each output, as given by
+    /// [OutputResultExtension::status].
+    pub fn verify_outputs(
+        &self,
+        response: &GetOutputsResponse,
+        merkle_root_block: &Block,
+        signatures: &[BlockSignature],
+    ) -> Result<Vec<Option<(TxOut, TxOutMembershipProof)>>, Error> {
+        self.verify_block(merkle_root_block, signatures)?;
+
+        let root_hash = &merkle_root_block.root_element.hash.0;
+        response
+            .results
+            .iter()
+            .map(|result| {
+                let status = result.status()?;
+                if let Some((output, proof)) = status.as_ref() {
+                    if proof.index != result.index {
+                        return Err(Error::ProofIndexMismatch(result.index, proof.index));
+                    }
+                    let is_valid = is_membership_proof_valid(output, proof, root_hash)
+                        .map_err(|err| Error::InvalidMembershipProof(result.index, err))?;
+                    if !is_valid {
+                        return Err(Error::OutputNotInBlock(
+                            result.index,
+                            merkle_root_block.index,
+                        ));
+                    }
+                }
+                Ok(status)
+            })
+            .collect()
+    }
+
+    /// Verify that a key image which fog ledger reports as spent appears in
+    /// the contents of the signed block it was reported spent in.
+    ///
+    /// Note that a key image reported as not spent can't be verified this
+    /// way, since that would need the contents of every block.
+    pub fn verify_key_image_spent(
+        &self,
+        result: &KeyImageResult,
+        block: &Block,
+        block_contents: &BlockContents,
+        signatures: &[BlockSignature],
+    ) -> Result<(), Error> {
+        let spent_at = result.status()?.ok_or(Error::KeyImageNotSpent)?;
+        if spent_at != block.index {
+            return Err(Error::WrongBlock(spent_at, block.index));
+        }
+        self.verify_block(block, signatures)?;
+        if block_contents.hash() != block.contents_hash {
+            return Err(Error::BlockContentsHashMismatch(block.index));
+        }
+        if !block_contents.key_images.contains(&result.key_image) {
+            return Err(Error::KeyImageNotInBlock(block.index));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mc_blockchain_types::BlockVersion;
+    use mc_crypto_keys::{Ed25519Pair, RistrettoPrivate};
+    use mc_fog_types::ledger::{KeyImageResultCode, OutputResult, OutputResultCode};
+    use mc_transaction_core::{
+        encrypted_fog_hint::EncryptedFogHint,
+        membership_proofs::{hash_leaf, Range},
+        ring_signature::KeyImage,
+        tx::TxOutMembershipElement,
+        Amount,
+    };
+    use mc_util_from_random::FromRandom;
+    use mc_util_test_helper::{get_seeded_rng, RngType};
+
+    fn random_tx_out(rng: &mut RngType) -> TxOut {
+        TxOut::new(
+            BlockVersion::MAX,
+            Amount::new(10, 0.into()),
+            &FromRandom::from_random(rng),
+            &RistrettoPrivate::from_random(rng),
+            EncryptedFogHint::fake_onetime_hint(rng),
+        )
+        .unwrap()
+    }
+
+    struct TestChain {
+        signers: Vec<Ed25519Pair>,
+        tx_out: TxOut,
+        block_contents: BlockContents,
+        block: Block,
+    }
+
+    impl TestChain {
+        fn new(rng: &mut RngType) -> Self {
+            let signers = (0..3).map(|_| Ed25519Pair::from_random(rng)).collect();
+            let tx_out = random_tx_out(rng);
+            let block_contents = BlockContents {
+                key_images: vec![KeyImage::from(7u64)],
+                outputs: vec![tx_out.clone()],
+                ..Default::default()
+            };
+            let root_element =
+                TxOutMembershipElement::new(Range::new(0, 0).unwrap(), hash_leaf(&tx_out));
+            let block = Block::new(
+                BlockVersion::MAX,
+                &Default::default(),
+                0,
+                1,
+                &root_element,
+                &block_contents,
+            );
+            Self {
+                signers,
+                tx_out,
+                block_contents,
+                block,
+            }
+        }
+
+        fn verifier(&self, threshold: usize) -> FogLedgerVerifier {
+            FogLedgerVerifier::new(TrustedBlockSigners::new(
+                self.signers.iter().map(|signer| signer.public_key()),
+                threshold,
+            ))
+        }
+
+        fn signatures(&self, signers: &[usize]) -> Vec<BlockSignature> {
+            signers
+                .iter()
+                .map(|index| {
+                    BlockSignature::from_block_and_keypair(&self.block, &self.signers[*index])
+                        .unwrap()
+                })
+                .collect()
+        }
+
+        fn outputs_response(&self, proof: TxOutMembershipProof) -> GetOutputsResponse {
+            GetOutputsResponse {
+                results: vec![OutputResult {
+                    index: 0,
+                    result_code: OutputResultCode::Exists as u32,
+                    output: self.tx_out.clone(),
+                    proof,
+                }],
+                num_blocks: 1,
+                global_txo_count: 1,
+                latest_block_version: *BlockVersion::MAX,
+                max_block_version: *BlockVersion::MAX,
+                padding: vec![],
+            }
+        }
+    }
+
+    #[test]
+    fn blocks_need_enough_trusted_signatures() {
+        let mut rng = get_seeded_rng();
+        let chain = TestChain::new(&mut rng);
+        let verifier = chain.verifier(2);
+
+        assert_eq!(
+            verifier.verify_block(&chain.block, &chain.signatures(&[0, 2])),
+            Ok(())
+        );
+        // The same signer twice only counts once.
+        assert_eq!(
+            verifier.verify_block(&chain.block, &chain.signatures(&[1, 1])),
+            Err(Error::NotEnoughSignatures(1, 0, 2))
+        );
+
+        let untrusted = Ed25519Pair::from_random(&mut rng);
+        let mut signatures = chain.signatures(&[0]);
+        signatures.push(BlockSignature::from_block_and_keypair(&chain.block, &untrusted).unwrap());
+        assert_eq!(
+            verifier.verify_block(&chain.block, &signatures),
+            Err(Error::NotEnoughSignatures(1, 0, 2))
+        );
+
+        let mut tampered = chain.block.clone();
+        tampered.cumulative_txo_count += 1;
+        assert_eq!(
+            verifier.verify_block(&tampered, &chain.signatures(&[0, 1, 2])),
+            Err(Error::InvalidBlockId(0))
+        );
+    }
+
+    #[test]
+    fn outputs_must_be_in_the_signed_block() {
+        let mut rng = get_seeded_rng();
+        let chain = TestChain::new(&mut rng);
+        let verifier = chain.verifier(2);
+        let signatures = chain.signatures(&[0, 1]);
+
+        let proof = TxOutMembershipProof::new(0, 0, vec![chain.block.root_element.clone()]);
+        let statuses = verifier
+            .verify_outputs(&chain.outputs_response(proof), &chain.block, &signatures)
+            .unwrap();
+        assert_eq!(statuses.len(), 1);
+        assert_eq!(statuses[0].as_ref().unwrap().0, chain.tx_out);
+
+        // A proof leading to a different root is rejected.
+        let other_tx_out = random_tx_out(&mut rng);
+        let mut response = chain.outputs_response(TxOutMembershipProof::new(
+            0,
+            0,
+            vec![TxOutMembershipElement::new(
+                Range::new(0, 0).unwrap(),
+                hash_leaf(&other_tx_out),
+            )],
+        ));
+        response.results[0].output = other_tx_out;
+        assert_eq!(
+            verifier.verify_outputs(&response, &chain.block, &signatures),
+            Err(Error::OutputNotInBlock(0, 0))
+        );
+    }
+
+    #[test]
+    fn spent_key_images_must_be_in_the_signed_block() {
+        let mut rng = get_seeded_rng();
+        let chain = TestChain::new(&mut rng);
+        let verifier = chain.verifier(2);
+        let signatures = chain.signatures(&[1, 2]);
+        let spent = |key_image: u64| KeyImageResult {
+            key_image: KeyImage::from(key_image),
+            spent_at: 0,
+            timestamp: 0,
+            timestamp_result_code: 0,
+            key_image_result_code: KeyImageResultCode::Spent as u32,
+        };
+
+        assert_eq!(
+            verifier.verify_key_image_spent(
+                &spent(7),
+                &chain.block,
+                &chain.block_contents,
+                &signatures
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            verifier.verify_key_image_spent(
+                &spent(8),
+                &chain.block,
+                &chain.block_contents,
+                &signatures
+            ),
+            Err(Error::KeyImageNotInBlock(0))
+        );
+
+        let mut other_contents = chain.block_contents.clone();
+        other_contents.key_images.push(KeyImage::from(8u64));
+        assert_eq!(
+            verifier.verify_key_image_spent(&spent(8), &chain.block, &other_contents, &signatures),
+            Err(Error::BlockContentsHashMismatch(0))
+        );
+    }
+}
@@ -98,6 +98,24 @@ lazy_static! {
     static ref OCALL_REENTRANCY_MUTEX: Mutex<()> = Mutex::new(());
 }
 
+/// Set the tree-top caching threshold so that ORAM storage objects created
+/// after this keep at most about `max_bytes` of data on the enclave heap each,
+/// and the rest in untrusted storage.
+///
+/// The threshold is a power of two, so `max_bytes` is rounded down to one. An
+/// oblivious map is made of several ORAM storage objects, for the ORAM itself
+/// and for its recursive position maps, but the position maps are much smaller
+/// than the ORAM's tree, so this also bounds the heap the whole map uses.
+pub fn set_treetop_caching_budget(max_bytes: u64) {
+    TREETOP_CACHING_THRESHOLD_LOG2
+        .store(treetop_caching_threshold_log2(max_bytes), Ordering::SeqCst);
+}
+
+// The largest threshold which doesn't exceed `max_bytes`
+fn treetop_caching_threshold_log2(max_bytes: u64) -> u32 {
+    max_bytes.max(1).ilog2()
+}
+
 /// Cipher type. Anything implementing StreamCipher and KeyIvInit at 128
 /// bit security should be acceptable
 type CipherType = Ctr64BE<Aes256>;
@@ -729,6 +747,15 @@ mod helpers {
         }
     }
 
+    #[test]
+    fn treetop_caching_budget_rounds_down() {
+        assert_eq!(treetop_caching_threshold_log2(0), 0);
+        assert_eq!(treetop_caching_threshold_log2(4096), 12);
+        assert_eq!(treetop_caching_threshold_log2(4097), 12);
+        assert_eq!(treetop_caching_threshold_log2((1 << 25) - 1), 24);
+        assert_eq!(treetop_caching_threshold_log2(u64::MAX), 63);
+    }
+
     // Test what happens when we exercise the ORAM
     // This is simlar to the integration test in `mc-fog-ocall-oram-storage-testing`
     #[test]
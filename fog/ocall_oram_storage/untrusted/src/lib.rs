@@ -3,8 +3,8 @@
 //! An implementation of the fog-ocall-oram-storage-edl interface
 //!
 //! This crate implements and exports the functions defined in the EDL file.
-//! This is the only public API of this crate, everything else is an
-//! implementation detail.
+//! Along with [set_oram_spill_dir], this is the only public API of this crate,
+//! everything else is an implementation detail.
 //!
 //! Main ideas:
 //! Instead of a global data structure protected by a mutex, this API does
@@ -19,6 +19,9 @@
 //!   reconstituted whenever the enclave wants to access the allocation
 //! - The box is freed when the enclave releases the allocation (This probably
 //!   won't actually happen in production)
+//! - If a spill directory is set, the data items are kept in a file in it
+//!   instead, and read and written with pread / pwrite. Metadata stays on the
+//!   heap, since it is much smaller.
 //!
 //! When debug assertions are on, we keep track in a global variable which ids
 //! are valid and which ones aren't so that we can give nice panic messages and
@@ -39,9 +42,64 @@ use mc_common::logger::global_log;
 use std::{
     alloc::{alloc, alloc_zeroed, dealloc, Layout},
     boxed::Box,
-    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    fs::{self, File, OpenOptions},
+    os::unix::fs::FileExt,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex,
+    },
 };
 
+/// The directory in which to keep the data items of new allocations, if any
+static SPILL_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Used to give each spill file a distinct name
+static NEXT_SPILL_FILE_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Keep the data items of ORAM storage allocated after this in files in `dir`,
+/// instead of on the heap, or on the heap again if `dir` is `None`.
+///
+/// This lets the enclave's storage exceed RAM, at the cost of a disk read or
+/// write for each item accessed. The enclave encrypts and authenticates
+/// everything it stores here, so the files need no more protection than the
+/// heap has. Each file is removed as soon as it is created, so that it's
+/// cleaned up even if the process is killed.
+pub fn set_oram_spill_dir(dir: Option<PathBuf>) {
+    *SPILL_DIR.lock().expect("spill dir mutex poisoned") = dir;
+}
+
+/// Where the data items of an allocation are kept
+enum DataSegment {
+    /// On the heap, at this pointer
+    Heap(*mut u64),
+    /// In this file, which has already been removed from its directory
+    File(File),
+}
+
+impl DataSegment {
+    /// A zeroed file in `dir` for `len` bytes of data items
+    fn create_file(dir: &Path, len: usize) -> Self {
+        let path = dir.join(format!(
+            "oram-storage-{}-{}",
+            process::id(),
+            NEXT_SPILL_FILE_ID.fetch_add(1, Ordering::SeqCst)
+        ));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .unwrap_or_else(|err| panic!("Could not create spill file {path:?}: {err}"));
+        fs::remove_file(&path)
+            .unwrap_or_else(|err| panic!("Could not remove spill file {path:?}: {err}"));
+        file.set_len(len as u64)
+            .unwrap_or_else(|err| panic!("Could not allocate {len} bytes in {path:?}: {err}"));
+        Self::File(file)
+    }
+}
+
 /// Resources held on untrusted side in connection to an allocation request by
 /// enclave
 ///
@@ -54,8 +112,8 @@ struct UntrustedAllocation {
     data_item_size: usize,
     /// The size of a meta item in bytes
     meta_item_size: usize,
-    /// The pointer to the data items
-    data_pointer: *mut u64,
+    /// The data items
+    data: DataSegment,
     /// The pointer to the meta items
     meta_pointer: *mut u64,
     /// A flag set to true when a thread is in the critical section and released
@@ -87,9 +145,15 @@ impl UntrustedAllocation {
     /// Data and meta item sizes must be divisible by 8, consistent with the
     /// contract described in the edl file
     pub fn new(count: usize, data_item_size: usize, meta_item_size: usize) -> Self {
-        let mem_kb = compute_mem_kb(count, data_item_size, meta_item_size);
+        let spill_dir = SPILL_DIR.lock().expect("spill dir mutex poisoned").clone();
+        let heap_data_item_size = if spill_dir.is_some() {
+            0
+        } else {
+            data_item_size
+        };
+        let mem_kb = compute_mem_kb(count, heap_data_item_size, meta_item_size);
         let total_mem_kb = mem_kb + TOTAL_MEM_FOOTPRINT_KB.fetch_add(mem_kb, Ordering::SeqCst);
-        global_log::info!("Untrusted is allocating oram storage: count = {}, data_size = {}, meta_size = {}, mem = {} KB. Total mem allocated this way = {} KB", count, data_item_size, meta_item_size, mem_kb, total_mem_kb);
+        global_log::info!("Untrusted is allocating oram storage: count = {}, data_size = {}, meta_size = {}, mem = {} KB, spill dir = {:?}. Total mem allocated this way = {} KB", count, data_item_size, meta_item_size, mem_kb, spill_dir, total_mem_kb);
         assert!(
             data_item_size % 8 == 0,
             "data item size is not good: {data_item_size}"
@@ -99,15 +163,21 @@ impl UntrustedAllocation {
             "meta item size is not good: {meta_item_size}"
         );
 
-        let data_pointer = unsafe {
-            alloc(Layout::from_size_align(count * data_item_size, 8).unwrap()) as *mut u64
+        let data = match spill_dir {
+            Some(dir) => DataSegment::create_file(&dir, count * data_item_size),
+            None => {
+                let data_pointer = unsafe {
+                    alloc(Layout::from_size_align(count * data_item_size, 8).unwrap()) as *mut u64
+                };
+                if data_pointer.is_null() {
+                    panic!(
+                        "Could not allocate memory for data segment: {}",
+                        count * data_item_size
+                    )
+                }
+                DataSegment::Heap(data_pointer)
+            }
         };
-        if data_pointer.is_null() {
-            panic!(
-                "Could not allocate memory for data segment: {}",
-                count * data_item_size
-            )
-        }
         let meta_pointer = unsafe {
             alloc_zeroed(Layout::from_size_align(count * meta_item_size, 8).unwrap()) as *mut u64
         };
@@ -125,7 +195,7 @@ impl UntrustedAllocation {
             count,
             data_item_size,
             meta_item_size,
-            data_pointer,
+            data,
             meta_pointer,
             critical_section_flag,
             checkout_flag,
@@ -136,15 +206,22 @@ impl UntrustedAllocation {
 impl Drop for UntrustedAllocation {
     fn drop(&mut self) {
         unsafe {
-            dealloc(
-                self.data_pointer as *mut u8,
-                Layout::from_size_align_unchecked(self.count * self.data_item_size, 8),
-            );
+            let heap_data_item_size = match self.data {
+                DataSegment::Heap(data_pointer) => {
+                    dealloc(
+                        data_pointer as *mut u8,
+                        Layout::from_size_align_unchecked(self.count * self.data_item_size, 8),
+                    );
+                    self.data_item_size
+                }
+                // The file is closed when it's dropped
+                DataSegment::File(_) => 0,
+            };
             dealloc(
                 self.meta_pointer as *mut u8,
                 Layout::from_size_align_unchecked(self.count * self.meta_item_size, 8),
             );
-            let mem_kb = compute_mem_kb(self.count, self.data_item_size, self.meta_item_size);
+            let mem_kb = compute_mem_kb(self.count, heap_data_item_size, self.meta_item_size);
             TOTAL_MEM_FOOTPRINT_KB.fetch_sub(mem_kb, Ordering::SeqCst);
         }
     }
@@ -237,13 +314,26 @@ pub unsafe extern "C" fn checkout_oram_storage(
 
     let indices = core::slice::from_raw_parts(idx, idx_len);
 
-    for (count, index) in indices.iter().enumerate() {
-        let index = *index as usize;
-        core::ptr::copy_nonoverlapping(
-            (*ptr).data_pointer.add(data_copy_size * index),
-            databuf.add(data_copy_size * count),
-            data_copy_size,
-        );
+    match &(*ptr).data {
+        DataSegment::Heap(data_pointer) => {
+            for (count, index) in indices.iter().enumerate() {
+                let index = *index as usize;
+                core::ptr::copy_nonoverlapping(
+                    data_pointer.add(data_copy_size * index),
+                    databuf.add(data_copy_size * count),
+                    data_copy_size,
+                );
+            }
+        }
+        DataSegment::File(file) => {
+            let data_item_size = (*ptr).data_item_size;
+            let databuf =
+                core::slice::from_raw_parts_mut(databuf as *mut u8, idx_len * data_item_size);
+            for (item, index) in databuf.chunks_exact_mut(data_item_size).zip(indices) {
+                file.read_exact_at(item, index * data_item_size as u64)
+                    .unwrap_or_else(|err| panic!("Could not read spilled oram storage: {err}"));
+            }
+        }
     }
 
     for (count, index) in indices.iter().enumerate() {
@@ -309,13 +399,26 @@ pub unsafe extern "C" fn checkin_oram_storage(
 
     let indices = core::slice::from_raw_parts(idx, idx_len);
 
-    for (count, index) in indices.iter().enumerate() {
-        let index = *index as usize;
-        core::ptr::copy_nonoverlapping(
-            databuf.add(data_copy_size * count),
-            (*ptr).data_pointer.add(data_copy_size * index),
-            data_copy_size,
-        );
+    match &(*ptr).data {
+        DataSegment::Heap(data_pointer) => {
+            for (count, index) in indices.iter().enumerate() {
+                let index = *index as usize;
+                core::ptr::copy_nonoverlapping(
+                    databuf.add(data_copy_size * count),
+                    data_pointer.add(data_copy_size * index),
+                    data_copy_size,
+                );
+            }
+        }
+        DataSegment::File(file) => {
+            let data_item_size = (*ptr).data_item_size;
+            let databuf =
+                core::slice::from_raw_parts(databuf as *const u8, idx_len * data_item_size);
+            for (item, index) in databuf.chunks_exact(data_item_size).zip(indices) {
+                file.write_all_at(item, index * data_item_size as u64)
+                    .unwrap_or_else(|err| panic!("Could not write spilled oram storage: {err}"));
+            }
+        }
     }
 
     for (count, index) in indices.iter().enumerate() {
@@ -8,9 +8,11 @@ use mc_common::{
     logger::{log, o},
     sentry,
 };
-use mc_fog_overseer_server::{config::OverseerConfig, server, service::OverseerService};
+use mc_fog_overseer_server::{
+    config::OverseerConfig, grpc_service::OverseerGrpcServer, server, service::OverseerService,
+};
 use mc_fog_sql_recovery_db::SqlRecoveryDb;
-use mc_util_cli::ParserWithBuildInfo;
+use mc_util_cli::LayeredConfig;
 
 #[rocket::main]
 async fn main() -> Result<(), rocket::Error> {
@@ -18,7 +20,7 @@ async fn main() -> Result<(), rocket::Error> {
     mc_common::setup_panic_handler();
     let _sentry_guard = sentry::init();
 
-    let config = OverseerConfig::parse();
+    let config = OverseerConfig::load();
 
     // Open the database.
     let database_url =
@@ -32,13 +34,27 @@ async fn main() -> Result<(), rocket::Error> {
         panic!("fog-overseer cannot connect to database '{database_url}': {err:?}")
     });
 
-    let mut overseer_service =
-        OverseerService::new(config.ingest_cluster_uris, recovery_db, logger.clone());
+    let mut overseer_service = OverseerService::new(
+        config.ingest_cluster_uris,
+        recovery_db,
+        config.state_history_path,
+        logger.clone(),
+    );
     overseer_service
         .start()
         .expect("OverseerService failed to start");
     log::info!(logger, "OverseerService successfully started.");
 
+    let _grpc_server = config.overseer_grpc_listen_uri.as_ref().map(|listen_uri| {
+        let mut grpc_server = OverseerGrpcServer::new(
+            overseer_service.cluster_status_tracker(),
+            listen_uri,
+            logger.clone(),
+        );
+        grpc_server.start();
+        grpc_server
+    });
+
     let overseer_state = server::OverseerState { overseer_service };
 
     let rocket_config = rocket::Config::figment()
@@ -0,0 +1,425 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Tracks the states of the nodes in the Fog Ingest cluster, so that the
+//! cluster can be reported on at a glance.
+//!
+//! Every time a node is polled, its state is recorded here. When a node's
+//! state, or its ingress public key, changes, the change is kept as a
+//! transition, and appended as a line of JSON to the state history file, if
+//! one is configured, so that the history survives restarts of Fog Overseer.
+
+use crate::error::OverseerError;
+use mc_common::logger::{log, Logger};
+use mc_fog_api::ingest_common::{IngestControllerMode, IngestSummary};
+use mc_fog_ingest_client::FogIngestGrpcClient;
+use mc_fog_uri::{ConnectionUri, FogIngestUri};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The most transitions that are kept in memory and reported.
+pub const MAX_RECENT_TRANSITIONS: usize = 1000;
+
+/// The state of a Fog Ingest node, as seen by Fog Overseer.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum IngestNodeState {
+    /// The node hasn't been seen in any state before.
+    Unknown,
+    /// The node is actively scanning the blockchain.
+    Active,
+    /// The node is not scanning the blockchain.
+    Idle,
+    /// The node did not respond to Fog Overseer.
+    Unresponsive,
+}
+
+/// The latest status of a Fog Ingest node.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct IngestNodeStatus {
+    /// The node's fog-ingest:// URI.
+    pub uri: String,
+    /// The node's state.
+    pub state: IngestNodeState,
+    /// The hex-encoded ingress public key of the node, if it responded.
+    pub ingress_pubkey: Option<String>,
+    /// The hex-encoded egress public key of the node, if it responded.
+    pub egress_pubkey: Option<String>,
+    /// The next block index the node will scan, if it responded.
+    pub next_block_index: Option<u64>,
+    /// The node's current ingest invocation id, if it is active.
+    pub ingest_invocation_id: Option<i64>,
+    /// Why the node's status could not be retrieved, if it didn't respond.
+    pub error: Option<String>,
+    /// When the node was last polled, in seconds since the Unix epoch.
+    pub last_seen: u64,
+    /// When the node entered its current state, in seconds since the Unix
+    /// epoch.
+    pub state_since: u64,
+}
+
+/// A change to the state, or to the ingress public key, of a Fog Ingest node.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct IngestNodeStateTransition {
+    /// The node's fog-ingest:// URI.
+    pub uri: String,
+    /// When the change was seen, in seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// The node's state before the change.
+    pub from_state: IngestNodeState,
+    /// The node's state after the change.
+    pub to_state: IngestNodeState,
+    /// The hex-encoded ingress public key of the node after the change, if it
+    /// responded.
+    pub ingress_pubkey: Option<String>,
+}
+
+/// The status of the Fog Ingest cluster.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct IngestClusterStatus {
+    /// Whether Fog Overseer is performing automatic failover.
+    pub overseer_enabled: bool,
+    /// The status of each node that has been polled, ordered by URI.
+    pub nodes: Vec<IngestNodeStatus>,
+    /// The most recent changes to the nodes' states, oldest first.
+    pub recent_transitions: Vec<IngestNodeStateTransition>,
+}
+
+/// The last state and ingress public key seen for a node, which a new
+/// observation is compared with to decide whether it is a transition.
+#[derive(Clone)]
+struct LastState {
+    state: IngestNodeState,
+    ingress_pubkey: Option<String>,
+    since: u64,
+}
+
+#[derive(Default)]
+struct TrackerInner {
+    nodes: BTreeMap<String, IngestNodeStatus>,
+    last_states: HashMap<String, LastState>,
+    recent_transitions: VecDeque<IngestNodeStateTransition>,
+}
+
+impl TrackerInner {
+    fn push_transition(&mut self, transition: IngestNodeStateTransition) {
+        self.last_states.insert(
+            transition.uri.clone(),
+            LastState {
+                state: transition.to_state,
+                ingress_pubkey: transition.ingress_pubkey.clone(),
+                since: transition.timestamp,
+            },
+        );
+        if self.recent_transitions.len() == MAX_RECENT_TRANSITIONS {
+            self.recent_transitions.pop_front();
+        }
+        self.recent_transitions.push_back(transition);
+    }
+}
+
+/// Records the states of the Fog Ingest nodes and the transitions between
+/// them.
+pub struct ClusterStatusTracker {
+    ingest_clients: Arc<Vec<FogIngestGrpcClient>>,
+    is_enabled: Arc<AtomicBool>,
+    history_path: Option<PathBuf>,
+    inner: Mutex<TrackerInner>,
+    logger: Logger,
+}
+
+impl ClusterStatusTracker {
+    /// Create a tracker for the nodes reached through `ingest_clients`,
+    /// appending transitions to the file at `history_path`, if any.
+    pub fn new(
+        ingest_clients: Arc<Vec<FogIngestGrpcClient>>,
+        is_enabled: Arc<AtomicBool>,
+        history_path: Option<PathBuf>,
+        logger: Logger,
+    ) -> Self {
+        Self {
+            ingest_clients,
+            is_enabled,
+            history_path,
+            inner: Default::default(),
+            logger,
+        }
+    }
+
+    /// Read back the transitions in the state history file, if it exists, so
+    /// that states from before a restart are neither lost nor reported as new
+    /// transitions.
+    pub fn load_history(&self) -> Result<(), OverseerError> {
+        let Some(path) = self.history_path.as_ref() else {
+            return Ok(());
+        };
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(state_history_error(path, err)),
+        };
+
+        let mut inner = self.inner.lock().expect("mutex poisoned");
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|err| state_history_error(path, err))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let transition: IngestNodeStateTransition =
+                serde_json::from_str(&line).map_err(|err| state_history_error(path, err))?;
+            inner.push_transition(transition);
+        }
+        log::info!(
+            self.logger,
+            "Loaded {} ingest node state transitions from {}",
+            inner.recent_transitions.len(),
+            path.display()
+        );
+        Ok(())
+    }
+
+    /// Poll every node in the cluster, record what was seen, and return the
+    /// status of the cluster.
+    pub fn poll(&self) -> IngestClusterStatus {
+        for ingest_client in self.ingest_clients.iter() {
+            let uri = ingest_client.get_uri();
+            let result = ingest_client.get_status();
+            self.observe(uri, result.as_ref().map_err(|err| err.to_string()));
+        }
+        self.snapshot()
+    }
+
+    /// Record the result of polling the node at `uri`.
+    pub fn observe(&self, uri: &FogIngestUri, result: Result<&IngestSummary, String>) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        self.observe_at(&uri.to_string(), result, now)
+    }
+
+    /// The status of the cluster, as of the last time each node was polled.
+    pub fn snapshot(&self) -> IngestClusterStatus {
+        let inner = self.inner.lock().expect("mutex poisoned");
+        IngestClusterStatus {
+            overseer_enabled: self.is_enabled.load(Ordering::SeqCst),
+            nodes: inner.nodes.values().cloned().collect(),
+            recent_transitions: inner.recent_transitions.iter().cloned().collect(),
+        }
+    }
+
+    fn observe_at(&self, uri: &str, result: Result<&IngestSummary, String>, now: u64) {
+        let mut status = match result {
+            Ok(summary) => {
+                let state = match summary.get_mode() {
+                    IngestControllerMode::Active => IngestNodeState::Active,
+                    IngestControllerMode::Idle => IngestNodeState::Idle,
+                };
+                IngestNodeStatus {
+                    uri: uri.to_owned(),
+                    state,
+                    ingress_pubkey: Some(hex::encode(summary.get_ingress_pubkey().get_data())),
+                    egress_pubkey: Some(hex::encode(summary.get_egress_pubkey())),
+                    next_block_index: Some(summary.get_next_block_index()),
+                    ingest_invocation_id: (state == IngestNodeState::Active)
+                        .then(|| summary.get_ingest_invocation_id()),
+                    error: None,
+                    last_seen: now,
+                    state_since: now,
+                }
+            }
+            Err(error) => IngestNodeStatus {
+                uri: uri.to_owned(),
+                state: IngestNodeState::Unresponsive,
+                ingress_pubkey: None,
+                egress_pubkey: None,
+                next_block_index: None,
+                ingest_invocation_id: None,
+                error: Some(error),
+                last_seen: now,
+                state_since: now,
+            },
+        };
+
+        let mut inner = self.inner.lock().expect("mutex poisoned");
+        let last_state = inner.last_states.get(uri).cloned();
+        let from_state = last_state
+            .as_ref()
+            .map_or(IngestNodeState::Unknown, |last_state| last_state.state);
+        // An unresponsive node's ingress key is unknown rather than changed.
+        let key_changed = status.ingress_pubkey.is_some()
+            && last_state
+                .as_ref()
+                .map(|last_state| &last_state.ingress_pubkey)
+                != Some(&status.ingress_pubkey);
+
+        match last_state {
+            Some(last_state) if last_state.state == status.state && !key_changed => {
+                status.state_since = last_state.since;
+            }
+            _ => {
+                let transition = IngestNodeStateTransition {
+                    uri: uri.to_owned(),
+                    timestamp: now,
+                    from_state,
+                    to_state: status.state,
+                    ingress_pubkey: status.ingress_pubkey.clone(),
+                };
+                log::info!(
+                    self.logger,
+                    "Ingest node {} is now {:?}, was {:?}",
+                    uri.redacted(),
+                    transition.to_state,
+                    transition.from_state
+                );
+                self.append_to_history(&transition);
+                inner.push_transition(transition);
+            }
+        }
+        inner.nodes.insert(uri.to_owned(), status);
+    }
+
+    fn append_to_history(&self, transition: &IngestNodeStateTransition) {
+        let Some(path) = self.history_path.as_ref() else {
+            return;
+        };
+        let result = serde_json::to_string(transition)
+            .map_err(|err| state_history_error(path, err))
+            .and_then(|line| {
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .and_then(|mut file| writeln!(file, "{line}"))
+                    .map_err(|err| state_history_error(path, err))
+            });
+        if let Err(err) = result {
+            log::error!(self.logger, "{}", err);
+        }
+    }
+}
+
+fn state_history_error(path: &Path, err: impl std::fmt::Display) -> OverseerError {
+    OverseerError::StateHistory(format!("{}: {err}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mc_api::external;
+    use mc_common::logger::{test_with_logger, Logger};
+
+    const URI: &str = "insecure-fog-ingest://node1.example.com:3226/";
+
+    fn tracker(history_path: Option<PathBuf>, logger: Logger) -> ClusterStatusTracker {
+        ClusterStatusTracker::new(
+            Arc::new(Vec::new()),
+            Arc::new(AtomicBool::new(true)),
+            history_path,
+            logger,
+        )
+    }
+
+    fn summary(mode: IngestControllerMode, key_byte: u8) -> IngestSummary {
+        let mut ingress_pubkey = external::CompressedRistretto::new();
+        ingress_pubkey.set_data(vec![key_byte; 32]);
+        let mut summary = IngestSummary::new();
+        summary.set_mode(mode);
+        summary.set_ingress_pubkey(ingress_pubkey);
+        summary.set_next_block_index(10);
+        summary.set_ingest_invocation_id(7);
+        summary
+    }
+
+    #[test_with_logger]
+    fn records_transitions_only_on_changes(logger: Logger) {
+        let tracker = tracker(None, logger);
+        let idle = summary(IngestControllerMode::Idle, 1);
+        let active = summary(IngestControllerMode::Active, 1);
+
+        tracker.observe_at(URI, Ok(&idle), 100);
+        tracker.observe_at(URI, Ok(&idle), 105);
+        tracker.observe_at(URI, Ok(&active), 110);
+        tracker.observe_at(URI, Err("unavailable".to_owned()), 115);
+        tracker.observe_at(URI, Err("unavailable".to_owned()), 120);
+
+        let status = tracker.snapshot();
+        assert!(status.overseer_enabled);
+        assert_eq!(status.nodes.len(), 1);
+        let node = &status.nodes[0];
+        assert_eq!(node.state, IngestNodeState::Unresponsive);
+        assert_eq!(node.error.as_deref(), Some("unavailable"));
+        assert_eq!(node.last_seen, 120);
+        assert_eq!(node.state_since, 115);
+
+        let states = status
+            .recent_transitions
+            .iter()
+            .map(|transition| {
+                (
+                    transition.from_state,
+                    transition.to_state,
+                    transition.timestamp,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            states,
+            vec![
+                (IngestNodeState::Unknown, IngestNodeState::Idle, 100),
+                (IngestNodeState::Idle, IngestNodeState::Active, 110),
+                (IngestNodeState::Active, IngestNodeState::Unresponsive, 115),
+            ]
+        );
+    }
+
+    #[test_with_logger]
+    fn records_ingress_key_changes(logger: Logger) {
+        let tracker = tracker(None, logger);
+        tracker.observe_at(URI, Ok(&summary(IngestControllerMode::Idle, 1)), 100);
+        tracker.observe_at(URI, Ok(&summary(IngestControllerMode::Idle, 2)), 105);
+
+        let status = tracker.snapshot();
+        assert_eq!(status.nodes[0].state_since, 105);
+        assert_eq!(status.nodes[0].ingest_invocation_id, None);
+        assert_eq!(status.recent_transitions.len(), 2);
+        assert_eq!(
+            status.recent_transitions[1].ingress_pubkey,
+            Some(hex::encode([2u8; 32]))
+        );
+    }
+
+    #[test_with_logger]
+    fn history_survives_restarts(logger: Logger) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state_history.jsonl");
+        let active = summary(IngestControllerMode::Active, 1);
+
+        let first = tracker(Some(path.clone()), logger.clone());
+        first.load_history().unwrap();
+        first.observe_at(URI, Ok(&summary(IngestControllerMode::Idle, 1)), 100);
+        first.observe_at(URI, Ok(&active), 110);
+
+        let second = tracker(Some(path), logger);
+        second.load_history().unwrap();
+        assert_eq!(
+            second.snapshot().recent_transitions,
+            first.snapshot().recent_transitions
+        );
+
+        // The node is still active, which is not a new transition.
+        second.observe_at(URI, Ok(&active), 200);
+        let status = second.snapshot();
+        assert_eq!(status.recent_transitions.len(), 2);
+        assert_eq!(status.nodes[0].state_since, 110);
+    }
+}
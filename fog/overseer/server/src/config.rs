@@ -5,8 +5,9 @@
 
 use clap::Parser;
 use mc_fog_sql_recovery_db::SqlRecoveryDbConnectionConfig;
-use mc_fog_uri::FogIngestUri;
+use mc_fog_uri::{FogIngestUri, FogOverseerUri};
 use serde::Serialize;
+use std::path::PathBuf;
 
 /// Parser configuration options for an Overseer Server
 #[derive(Clone, Serialize, Parser)]
@@ -29,6 +30,16 @@ pub struct OverseerConfig {
     #[clap(long, use_value_delimiter = true, env = "MC_INGEST_CLUSTER_URIS")]
     pub ingest_cluster_uris: Vec<FogIngestUri>,
 
+    /// gRPC listening URI for the ingest cluster status API. The API is only
+    /// served if this is set.
+    #[clap(long, env = "MC_OVERSEER_GRPC_LISTEN_URI")]
+    pub overseer_grpc_listen_uri: Option<FogOverseerUri>,
+
+    /// File to persist the ingest nodes' state transitions to, so that their
+    /// history survives restarts.
+    #[clap(long, env = "MC_OVERSEER_STATE_HISTORY_PATH")]
+    pub state_history_path: Option<PathBuf>,
+
     /// Postgres config
     #[clap(flatten)]
     pub postgres_config: SqlRecoveryDbConnectionConfig,
@@ -55,5 +66,27 @@ mod tests {
 
         assert_eq!(config.ingest_cluster_uris[0].port(), 3226);
         assert_eq!(config.ingest_cluster_uris[1].port(), 3227);
+        assert!(config.overseer_grpc_listen_uri.is_none());
+        assert!(config.state_history_path.is_none());
+    }
+
+    #[test]
+    fn cluster_status_config_example() {
+        let config = OverseerConfig::try_parse_from([
+            "/usr/bin/fog_overseer_server",
+            "--ingest-cluster-uris",
+            "insecure-fog-ingest://0.0.0.0:3226/",
+            "--overseer-grpc-listen-uri",
+            "insecure-fog-overseer://0.0.0.0:4267/",
+            "--state-history-path",
+            "/var/lib/fog-overseer/state_history.jsonl",
+        ])
+        .expect("Could not parse command line arguments.");
+
+        assert_eq!(config.overseer_grpc_listen_uri.unwrap().port(), 4267);
+        assert_eq!(
+            config.state_history_path,
+            Some(PathBuf::from("/var/lib/fog-overseer/state_history.jsonl"))
+        );
     }
 }
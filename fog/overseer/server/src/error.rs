@@ -30,6 +30,9 @@ pub enum OverseerError {
 
     /// There are multiple active Fog Ingest nodes at once: {0}
     MultipleActiveNodes(String),
+
+    /// Reading or writing the ingest node state history failed: {0}
+    StateHistory(String),
 }
 
 impl From<SqlRecoveryDbError> for OverseerError {
@@ -0,0 +1,162 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Exposes the status of the Fog Ingest cluster over gRPC, for admin
+//! dashboards that would rather not scrape the HTTP API.
+//!
+//! gRPC Client -> *Overseer gRPC Server* -> ClusterStatusTracker
+
+use crate::cluster_status::{
+    ClusterStatusTracker, IngestClusterStatus, IngestNodeState, IngestNodeStateTransition,
+    IngestNodeStatus,
+};
+use futures::executor::block_on;
+use grpcio::{RpcContext, RpcStatus, Server as GrpcioServer, ServerBuilder, UnarySink};
+use mc_common::logger::{log, Logger};
+use mc_fog_api::{overseer, overseer_grpc, Empty};
+use mc_fog_uri::{ConnectionUri, FogOverseerUri};
+use mc_util_grpc::{rpc_logger, send_result, ConnectionUriGrpcioServer, HealthService};
+use std::sync::Arc;
+
+/// Implements the FogOverseerAPI gRPC service.
+#[derive(Clone)]
+pub struct OverseerGrpcService {
+    cluster_status: Arc<ClusterStatusTracker>,
+    logger: Logger,
+}
+
+impl OverseerGrpcService {
+    /// Create a service reporting on the cluster `cluster_status` tracks.
+    pub fn new(cluster_status: Arc<ClusterStatusTracker>, logger: Logger) -> Self {
+        Self {
+            cluster_status,
+            logger,
+        }
+    }
+
+    fn get_ingest_cluster_status_impl(&self) -> Result<overseer::IngestClusterStatus, RpcStatus> {
+        Ok(self.cluster_status.poll().into())
+    }
+}
+
+impl overseer_grpc::FogOverseerApi for OverseerGrpcService {
+    fn get_ingest_cluster_status(
+        &mut self,
+        ctx: RpcContext,
+        _request: Empty,
+        sink: UnarySink<overseer::IngestClusterStatus>,
+    ) {
+        mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
+            send_result(ctx, sink, self.get_ingest_cluster_status_impl(), logger)
+        })
+    }
+}
+
+/// The gRPC server for the FogOverseerAPI.
+pub struct OverseerGrpcServer {
+    server: GrpcioServer,
+    uri: FogOverseerUri,
+    logger: Logger,
+}
+
+impl OverseerGrpcServer {
+    /// Construct a server listening on `listen_uri`.
+    pub fn new(
+        cluster_status: Arc<ClusterStatusTracker>,
+        listen_uri: &FogOverseerUri,
+        logger: Logger,
+    ) -> Self {
+        let env = Arc::new(
+            grpcio::EnvBuilder::new()
+                .name_prefix("OverseerServer-RPC".to_string())
+                .build(),
+        );
+
+        let overseer_service = overseer_grpc::create_fog_overseer_api(OverseerGrpcService::new(
+            cluster_status,
+            logger.clone(),
+        ));
+        let health_service = HealthService::new(None, logger.clone()).into_service();
+
+        let server = ServerBuilder::new(env)
+            .register_service(overseer_service)
+            .register_service(health_service)
+            .build_using_uri(listen_uri, logger.clone())
+            .expect("Could not build a server using the overseer gRPC listen URI");
+
+        Self {
+            server,
+            uri: listen_uri.clone(),
+            logger,
+        }
+    }
+
+    /// Start the server.
+    pub fn start(&mut self) {
+        self.server.start();
+        log::info!(
+            self.logger,
+            "Overseer gRPC API listening on {}",
+            self.uri.addr()
+        );
+    }
+
+    /// Stop the server.
+    pub fn stop(&mut self) {
+        block_on(self.server.shutdown()).expect("Could not stop grpc server");
+    }
+}
+
+impl Drop for OverseerGrpcServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl From<IngestNodeState> for overseer::IngestNodeState {
+    fn from(src: IngestNodeState) -> Self {
+        match src {
+            IngestNodeState::Unknown => Self::Unknown,
+            IngestNodeState::Active => Self::Active,
+            IngestNodeState::Idle => Self::Idle,
+            IngestNodeState::Unresponsive => Self::Unresponsive,
+        }
+    }
+}
+
+impl From<IngestNodeStatus> for overseer::IngestNodeStatus {
+    fn from(src: IngestNodeStatus) -> Self {
+        let mut status = Self::new();
+        status.set_uri(src.uri);
+        status.set_state(src.state.into());
+        status.set_ingress_pubkey(src.ingress_pubkey.unwrap_or_default());
+        status.set_egress_pubkey(src.egress_pubkey.unwrap_or_default());
+        status.set_next_block_index(src.next_block_index.unwrap_or_default());
+        status.set_ingest_invocation_id(src.ingest_invocation_id.unwrap_or_default());
+        status.set_error(src.error.unwrap_or_default());
+        status.set_last_seen(src.last_seen);
+        status.set_state_since(src.state_since);
+        status
+    }
+}
+
+impl From<IngestNodeStateTransition> for overseer::IngestNodeStateTransition {
+    fn from(src: IngestNodeStateTransition) -> Self {
+        let mut transition = Self::new();
+        transition.set_uri(src.uri);
+        transition.set_timestamp(src.timestamp);
+        transition.set_from_state(src.from_state.into());
+        transition.set_to_state(src.to_state.into());
+        transition.set_ingress_pubkey(src.ingress_pubkey.unwrap_or_default());
+        transition
+    }
+}
+
+impl From<IngestClusterStatus> for overseer::IngestClusterStatus {
+    fn from(src: IngestClusterStatus) -> Self {
+        let mut status = Self::new();
+        status.set_overseer_enabled(src.overseer_enabled);
+        status.set_nodes(src.nodes.into_iter().map(Into::into).collect());
+        status.set_recent_transitions(src.recent_transitions.into_iter().map(Into::into).collect());
+        status
+    }
+}
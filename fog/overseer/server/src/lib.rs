@@ -5,7 +5,9 @@
 #![feature(proc_macro_hygiene, decl_macro)]
 #![deny(missing_docs)]
 
+pub mod cluster_status;
 pub mod config;
+pub mod grpc_service;
 pub mod metrics;
 pub mod responses;
 pub mod server;
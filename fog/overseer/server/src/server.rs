@@ -6,7 +6,8 @@
 //! HTTP Client -> *Overseer Rocket Server* -> OverseerService -> OverseerWorker
 
 use crate::{
-    error::OverseerError, responses::GetIngestSummariesResponse, service::OverseerService,
+    cluster_status::IngestClusterStatus, error::OverseerError,
+    responses::GetIngestSummariesResponse, service::OverseerService,
 };
 use mc_fog_recovery_db_iface::RecoveryDb;
 use mc_fog_sql_recovery_db::SqlRecoveryDb;
@@ -34,6 +35,15 @@ fn get_ingest_summaries(
     state.overseer_service.get_ingest_summaries().map(Json)
 }
 
+/// Polls every ingest node, and reports their states along with the recent
+/// transitions between them.
+#[get("/ingest_cluster_status")]
+fn get_ingest_cluster_status(
+    state: &rocket::State<OverseerState<SqlRecoveryDb>>,
+) -> Result<Json<IngestClusterStatus>, String> {
+    state.overseer_service.get_ingest_cluster_status().map(Json)
+}
+
 /// Produces metrics for Prometheus.
 ///
 /// Meant to be called only by the Prometheus pull mechanism.
@@ -66,7 +76,8 @@ pub fn initialize_rocket_server<T: rocket::figment::Provider>(
             disable,
             get_status,
             get_metrics,
-            get_ingest_summaries
+            get_ingest_summaries,
+            get_ingest_cluster_status
         ],
     )
 }
@@ -7,15 +7,21 @@
 //!
 //! HTTP Client -> Overseer Rocket Server -> *OverseerService* -> OverseerWorker
 
-use crate::{error::OverseerError, responses::GetIngestSummariesResponse, worker::OverseerWorker};
+use crate::{
+    cluster_status::{ClusterStatusTracker, IngestClusterStatus},
+    error::OverseerError,
+    responses::GetIngestSummariesResponse,
+    worker::OverseerWorker,
+};
 use mc_common::logger::{log, Logger};
 use mc_fog_ingest_client::FogIngestGrpcClient;
 use mc_fog_recovery_db_iface::RecoveryDb;
 use mc_fog_types::ingest_common::IngestSummary;
-use mc_fog_uri::FogIngestUri;
+use mc_fog_uri::{ConnectionUri, FogIngestUri};
 use prometheus::{self, Encoder};
 use std::{
     collections::HashMap,
+    path::PathBuf,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
@@ -33,6 +39,7 @@ where
     overseer_worker: Option<OverseerWorker>,
     recovery_db: DB,
     is_enabled: Arc<AtomicBool>,
+    cluster_status: Arc<ClusterStatusTracker>,
 }
 
 impl<DB: RecoveryDb + Clone + Send + Sync + 'static> OverseerService<DB>
@@ -43,7 +50,15 @@ where
     const GRPC_RETRY_SECONDS: Duration = Duration::from_millis(10000);
 
     /// Insantiate the service with the given URIs and DB.
-    pub fn new(ingest_cluster_uris: Vec<FogIngestUri>, recovery_db: DB, logger: Logger) -> Self {
+    ///
+    /// The ingest nodes' state transitions are persisted to
+    /// `state_history_path`, if it is given.
+    pub fn new(
+        ingest_cluster_uris: Vec<FogIngestUri>,
+        recovery_db: DB,
+        state_history_path: Option<PathBuf>,
+        logger: Logger,
+    ) -> Self {
         let grpcio_env = Arc::new(grpcio::EnvBuilder::new().build());
         let ingest_clients: Vec<FogIngestGrpcClient> = ingest_cluster_uris
             .iter()
@@ -56,12 +71,21 @@ where
                 )
             })
             .collect();
+        let ingest_clients = Arc::new(ingest_clients);
+        let is_enabled = Arc::new(AtomicBool::new(false));
+        let cluster_status = Arc::new(ClusterStatusTracker::new(
+            ingest_clients.clone(),
+            is_enabled.clone(),
+            state_history_path,
+            logger.clone(),
+        ));
         Self {
-            ingest_clients: Arc::new(ingest_clients),
+            ingest_clients,
             logger,
             overseer_worker: None,
             recovery_db,
-            is_enabled: Arc::new(AtomicBool::new(false)),
+            is_enabled,
+            cluster_status,
         }
     }
 
@@ -77,6 +101,8 @@ where
 
     fn start_helper(&mut self) -> Result<(), OverseerError> {
         assert!(self.overseer_worker.is_none());
+        self.cluster_status.load_history()?;
+
         log::info!(self.logger, "Starting overseer worker");
 
         self.overseer_worker = Some(OverseerWorker::new(
@@ -84,6 +110,7 @@ where
             self.recovery_db.clone(),
             self.logger.clone(),
             self.is_enabled.clone(),
+            self.cluster_status.clone(),
         ));
 
         Ok(())
@@ -156,7 +183,7 @@ where
                         log::trace!(
                             self.logger,
                             "Got ingest summary for node with URI '{}': {:?}",
-                            uri,
+                            uri.redacted(),
                             proto_ingest_summary
                         );
                         IngestSummary::try_from(&proto_ingest_summary).map_err(|err| {
@@ -181,6 +208,18 @@ where
 
         Ok(GetIngestSummariesResponse { ingest_summaries })
     }
+
+    /// Poll all ingest nodes, and get their states along with the recent
+    /// transitions between them.
+    pub fn get_ingest_cluster_status(&self) -> Result<IngestClusterStatus, String> {
+        Ok(self.cluster_status.poll())
+    }
+
+    /// The tracker of the ingest nodes' states, for sharing with the gRPC
+    /// status API.
+    pub fn cluster_status_tracker(&self) -> Arc<ClusterStatusTracker> {
+        self.cluster_status.clone()
+    }
 }
 
 impl<DB: RecoveryDb + Clone + Send + Sync + 'static> Drop for OverseerService<DB>
@@ -7,14 +7,14 @@
 //!
 //! HTTP Client -> Overseer Rocket Server -> OverseerService -> *OverseerWorker*
 
-use crate::{error::OverseerError, metrics};
+use crate::{cluster_status::ClusterStatusTracker, error::OverseerError, metrics};
 use mc_api::external;
 use mc_common::logger::{log, Logger};
 use mc_crypto_keys::CompressedRistrettoPublic;
 use mc_fog_api::ingest_common::{IngestControllerMode, IngestSummary};
 use mc_fog_ingest_client::FogIngestGrpcClient;
 use mc_fog_recovery_db_iface::{IngressPublicKeyRecord, IngressPublicKeyRecordFilters, RecoveryDb};
-use mc_fog_uri::FogIngestUri;
+use mc_fog_uri::{ConnectionUri, FogIngestUri};
 use retry::{delay::Fixed, retry_with_index, OperationResult};
 use std::{
     collections::HashSet,
@@ -47,6 +47,7 @@ impl OverseerWorker {
         recovery_db: DB,
         logger: Logger,
         is_enabled: Arc<AtomicBool>,
+        cluster_status: Arc<ClusterStatusTracker>,
     ) -> Self
     where
         OverseerError: From<DB::Error>,
@@ -64,6 +65,7 @@ impl OverseerWorker {
                         thread_is_enabled,
                         thread_stop_requested,
                         HashSet::new(),
+                        cluster_status,
                         logger,
                     )
                 })
@@ -114,6 +116,9 @@ struct OverseerWorkerThread<DB: RecoveryDb> {
     /// This helps us debug when a node starts responding again.
     unresponsive_node_urls: HashSet<FogIngestUri>,
 
+    /// Records the state of each node that is polled.
+    cluster_status: Arc<ClusterStatusTracker>,
+
     logger: Logger,
 }
 
@@ -143,6 +148,7 @@ where
         is_enabled: Arc<AtomicBool>,
         stop_requested: Arc<AtomicBool>,
         unresponsive_node_urls: HashSet<FogIngestUri>,
+        cluster_status: Arc<ClusterStatusTracker>,
         logger: Logger,
     ) {
         let thread = Self {
@@ -151,6 +157,7 @@ where
             is_enabled,
             stop_requested,
             unresponsive_node_urls,
+            cluster_status,
             logger,
         };
         thread.run();
@@ -249,24 +256,27 @@ where
     ) -> Result<Vec<IngestSummaryNodeMapping>, OverseerError> {
         let logger = &self.logger;
         let unresponsive_node_urls = &mut self.unresponsive_node_urls;
+        let cluster_status = &self.cluster_status;
         self.ingest_clients
             .iter()
             .enumerate()
             .map(|(node_index, ingest_client)| {
                 let uri = ingest_client.get_uri();
-                match ingest_client.get_status() {
+                let result = ingest_client.get_status();
+                cluster_status.observe(uri, result.as_ref().map_err(|err| err.to_string()));
+                match result {
                     Ok(ingest_summary) => {
                         log::trace!(
                             logger,
                             "Ingest summary retrieved from '{}': {:?}",
-                            uri,
+                            uri.redacted(),
                             ingest_summary
                         );
                         if unresponsive_node_urls.remove(uri) {
                             log::info!(
                             logger,
                             "Node {} was previously unresponsive, but just successfully responded!",
-                            uri,
+                            uri.redacted(),
                         );
                         }
                         Ok(IngestSummaryNodeMapping {
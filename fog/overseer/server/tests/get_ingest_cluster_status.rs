@@ -0,0 +1,50 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+mod utils;
+
+use mc_common::logger::{test_with_logger, Logger};
+use mc_fog_ingest_server_test_utils::IngestServerTestHelper;
+use mc_fog_overseer_server::cluster_status::{IngestClusterStatus, IngestNodeState};
+use utils::TestHelperExt;
+
+const BASE_PORT: u16 = 8900;
+
+// Ensures that the cluster status reports each node's state, and the
+// transitions the nodes went through.
+#[test_with_logger]
+fn cluster_status_reports_node_states_and_transitions(logger: Logger) {
+    let mut helper = IngestServerTestHelper::new(BASE_PORT, logger);
+    helper.add_origin_block();
+    let nodes = helper.make_nodes(3);
+
+    // Initialize an OverseerService with an associated server.
+    let client = helper.enable_overseer_for_nodes(&nodes);
+
+    let get_status = || -> IngestClusterStatus {
+        let response = client.get("/ingest_cluster_status").dispatch();
+        let body = response.into_string().unwrap();
+        serde_json::from_str(&body).unwrap_or_else(|err| panic!("{err}: {body}"))
+    };
+
+    let status = get_status();
+    assert!(!status.overseer_enabled);
+    assert_eq!(status.nodes.len(), 3);
+    assert!(status
+        .nodes
+        .iter()
+        .all(|node| node.state == IngestNodeState::Idle));
+    assert_eq!(status.recent_transitions.len(), 3);
+
+    nodes[0].activate().expect("first node failed to activate");
+    let status = get_status();
+    let num_active = status
+        .nodes
+        .iter()
+        .filter(|node| node.state == IngestNodeState::Active)
+        .count();
+    assert_eq!(num_active, 1);
+    let last_transition = status.recent_transitions.last().unwrap();
+    assert_eq!(last_transition.from_state, IngestNodeState::Idle);
+    assert_eq!(last_transition.to_state, IngestNodeState::Active);
+    assert_eq!(status.recent_transitions.len(), 4);
+}
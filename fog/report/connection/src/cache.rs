@@ -0,0 +1,259 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Caching of fog report server responses, so that building many transactions
+//! to recipients of the same fog deployment doesn't fetch the same reports
+//! over and over.
+
+use crate::{Error, GrpcFogReportConnection};
+use mc_common::{
+    logger::{log, Logger},
+    HashMap,
+};
+use mc_fog_report_types::{FogReportResponses, ReportResponse};
+use mc_fog_sig::verify_report_response;
+use mc_util_uri::{ConnectionUri, FogUri};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// How long fetched fog reports are reused for.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct FogReportCacheConfig {
+    /// How long a response is served from the cache before it is fetched
+    /// again.
+    pub ttl: Duration,
+    /// How long past its TTL a response may still be served, while it is
+    /// fetched again in the background.
+    pub stale_while_revalidate: Duration,
+}
+
+/// A fog report server connection which caches the response of each fog URL.
+///
+/// A response is only cached after its certificate chain and its signature
+/// over the reports have been verified. Whether a response is valid for a
+/// given recipient still has to be checked by the fog resolver, since that
+/// depends on the recipient's fog authority signature.
+///
+/// A cached response is fetched again once it is older than the configured
+/// TTL, or as soon as one of its reports' pubkey has expired, since
+/// transactions can't be built against an expired pubkey.
+#[derive(Clone)]
+pub struct CachingFogReportConnection {
+    /// The connection used to fetch responses which are not cached
+    conn: GrpcFogReportConnection,
+    /// How long responses are reused for
+    config: FogReportCacheConfig,
+    /// Cached responses, by fog URL
+    entries: Arc<Mutex<HashMap<String, CacheEntry>>>,
+    /// The logging instance
+    logger: Logger,
+}
+
+impl CachingFogReportConnection {
+    /// Create a new CachingFogReportConnection object
+    pub fn new(
+        conn: GrpcFogReportConnection,
+        config: FogReportCacheConfig,
+        logger: Logger,
+    ) -> Self {
+        Self {
+            conn,
+            config,
+            entries: Default::default(),
+            logger,
+        }
+    }
+
+    /// Fetch fog reports corresponding to a series of FogUris, returning
+    /// FogReportResponses table, using cached responses where possible.
+    ///
+    /// `num_blocks` is the number of blocks in the ledger, which determines
+    /// whether the pubkeys in a cached response have expired.
+    pub fn fetch_fog_reports(
+        &self,
+        uris: impl Iterator<Item = FogUri>,
+        num_blocks: u64,
+    ) -> Result<FogReportResponses, Error> {
+        let mut responses = FogReportResponses::default();
+        for uri in uris {
+            let key = uri.to_string();
+            if !responses.contains_key(&key) {
+                let response = self.fetch_fog_report(&uri, num_blocks)?;
+                responses.insert(key, response);
+            }
+        }
+        Ok(responses)
+    }
+
+    /// Given a fog report uri, return its cached response, or fetch it over
+    /// grpc if it is not cached or has expired.
+    ///
+    /// A stale response is returned as-is, and fetched again on a background
+    /// thread.
+    pub fn fetch_fog_report(&self, uri: &FogUri, num_blocks: u64) -> Result<ReportResponse, Error> {
+        {
+            let mut entries = self.entries.lock().expect("mutex poisoned");
+            if let Some(entry) = entries.get_mut(&uri.to_string()) {
+                match entry.freshness(&self.config, num_blocks, Instant::now()) {
+                    Freshness::Fresh => return Ok(entry.response.clone()),
+                    Freshness::Stale => {
+                        if !entry.refreshing {
+                            entry.refreshing = true;
+                            self.refresh_in_background(uri.clone());
+                        }
+                        return Ok(entry.response.clone());
+                    }
+                    Freshness::Expired => {}
+                }
+            }
+        }
+        self.fetch_and_cache(uri)
+    }
+
+    /// Forget the cached responses.
+    pub fn clear(&self) {
+        self.entries.lock().expect("mutex poisoned").clear();
+    }
+
+    fn fetch_and_cache(&self, uri: &FogUri) -> Result<ReportResponse, Error> {
+        let result = self.conn.fetch_fog_report(uri).and_then(|response| {
+            verify_report_response(&response)
+                .map_err(|err| Error::InvalidSignature(uri.clone(), err))?;
+            Ok(response)
+        });
+
+        let mut entries = self.entries.lock().expect("mutex poisoned");
+        match &result {
+            Ok(response) => {
+                entries.insert(
+                    uri.to_string(),
+                    CacheEntry {
+                        response: response.clone(),
+                        fetched_at: Instant::now(),
+                        refreshing: false,
+                    },
+                );
+            }
+            Err(_) => {
+                if let Some(entry) = entries.get_mut(&uri.to_string()) {
+                    entry.refreshing = false;
+                }
+            }
+        }
+        result
+    }
+
+    fn refresh_in_background(&self, uri: FogUri) {
+        let this = self.clone();
+        std::thread::spawn(move || {
+            if let Err(err) = this.fetch_and_cache(&uri) {
+                log::warn!(
+                    this.logger,
+                    "Could not refresh cached fog reports from {}: {}",
+                    uri.redacted(),
+                    err
+                );
+            }
+        });
+    }
+}
+
+/// A cached response
+struct CacheEntry {
+    response: ReportResponse,
+    fetched_at: Instant,
+    /// Whether a background refresh of this entry is in progress
+    refreshing: bool,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum Freshness {
+    /// The entry can be used
+    Fresh,
+    /// The entry can be used, but should be fetched again
+    Stale,
+    /// The entry must be fetched again before it is used
+    Expired,
+}
+
+impl CacheEntry {
+    fn freshness(&self, config: &FogReportCacheConfig, num_blocks: u64, now: Instant) -> Freshness {
+        let pubkey_expired = self
+            .response
+            .reports
+            .iter()
+            .map(|report| report.pubkey_expiry)
+            .min()
+            .map_or(true, |pubkey_expiry| pubkey_expiry <= num_blocks);
+        if pubkey_expired {
+            return Freshness::Expired;
+        }
+
+        let age = now.saturating_duration_since(self.fetched_at);
+        if age < config.ttl {
+            Freshness::Fresh
+        } else if age < config.ttl + config.stale_while_revalidate {
+            Freshness::Stale
+        } else {
+            Freshness::Expired
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mc_fog_report_types::Report;
+
+    fn entry(pubkey_expiries: &[u64], fetched_at: Instant) -> CacheEntry {
+        CacheEntry {
+            response: ReportResponse {
+                reports: pubkey_expiries
+                    .iter()
+                    .map(|pubkey_expiry| Report {
+                        pubkey_expiry: *pubkey_expiry,
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            },
+            fetched_at,
+            refreshing: false,
+        }
+    }
+
+    #[test]
+    fn freshness_follows_ttl_and_pubkey_expiry() {
+        let config = FogReportCacheConfig {
+            ttl: Duration::from_secs(60),
+            stale_while_revalidate: Duration::from_secs(30),
+        };
+        let fetched_at = Instant::now();
+        let secs = |secs| fetched_at + Duration::from_secs(secs);
+
+        let entry = entry(&[200, 100], fetched_at);
+        assert_eq!(entry.freshness(&config, 50, secs(0)), Freshness::Fresh);
+        assert_eq!(entry.freshness(&config, 50, secs(59)), Freshness::Fresh);
+        assert_eq!(entry.freshness(&config, 50, secs(60)), Freshness::Stale);
+        assert_eq!(entry.freshness(&config, 50, secs(89)), Freshness::Stale);
+        assert_eq!(entry.freshness(&config, 50, secs(90)), Freshness::Expired);
+
+        // The earliest pubkey expiry counts, however young the entry is
+        assert_eq!(entry.freshness(&config, 99, secs(0)), Freshness::Fresh);
+        assert_eq!(entry.freshness(&config, 100, secs(0)), Freshness::Expired);
+    }
+
+    #[test]
+    fn responses_without_reports_are_not_reused() {
+        let config = FogReportCacheConfig {
+            ttl: Duration::from_secs(60),
+            stale_while_revalidate: Duration::from_secs(30),
+        };
+        let fetched_at = Instant::now();
+        assert_eq!(
+            entry(&[], fetched_at).freshness(&config, 0, fetched_at),
+            Freshness::Expired
+        );
+    }
+}
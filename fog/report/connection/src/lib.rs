@@ -10,12 +10,17 @@
 use displaydoc::Display;
 use grpcio::{CallOption, ChannelBuilder, Environment, MetadataBuilder};
 use mc_common::logger::{log, o, Logger};
+use mc_crypto_keys::SignatureError;
 use mc_fog_report_api::{report::ReportRequest, report_grpc};
 use mc_fog_report_types::ReportResponse;
+use mc_fog_sig::Error as FogSigError;
 use mc_util_grpc::{ConnectionUriGrpcioChannel, CHAIN_ID_GRPC_HEADER};
-use mc_util_uri::FogUri;
-use std::sync::Arc;
+use mc_util_uri::{ConnectionUri, FogUri};
+use std::{convert::Infallible, sync::Arc};
 
+mod cache;
+
+pub use cache::{CachingFogReportConnection, FogReportCacheConfig};
 pub use mc_fog_report_types::FogReportResponses;
 
 /// Fog report server connection based on grpcio
@@ -103,7 +108,7 @@ impl GrpcFogReportConnection {
             log::warn!(
                 self.logger,
                 "Report server at {} has no available reports",
-                uri
+                uri.redacted()
             );
             return Err(Error::NoReports(uri.clone()));
         }
@@ -120,6 +125,8 @@ pub enum Error {
     Rpc(grpcio::Error),
     /// Fog Report Server has no available reports: {0}
     NoReports(FogUri),
+    /// Fog Report Server at {0} returned an invalid signature: {1}
+    InvalidSignature(FogUri, FogSigError<Infallible, SignatureError>),
 }
 
 impl From<grpcio::Error> for Error {
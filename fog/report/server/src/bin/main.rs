@@ -5,7 +5,7 @@
 use mc_common::{logger, sentry};
 use mc_fog_report_server::{Config, Materials, Server};
 use mc_fog_sql_recovery_db::SqlRecoveryDb;
-use mc_util_cli::ParserWithBuildInfo;
+use mc_util_cli::LayeredConfig;
 use mc_util_grpc::AdminServer;
 use std::{env, sync::Arc};
 
@@ -15,7 +15,7 @@ fn main() {
     mc_common::setup_panic_handler();
     let _sentry_guard = sentry::init();
 
-    let config = Config::parse();
+    let config = Config::load();
 
     let materials = Materials::try_from(&config).expect("Could not read cryptographic materials");
 
@@ -8,7 +8,9 @@ use grpcio::{Server as GrpcioServer, ServerBuilder};
 use mc_common::logger::{log, Logger};
 use mc_fog_api::report_grpc;
 use mc_fog_recovery_db_iface::ReportDb;
-use mc_util_grpc::{ConnectionUriGrpcioServer, HealthService};
+use mc_util_grpc::{
+    ChainIdInterceptor, ConnectionUriGrpcioServer, HealthService, InterceptorChain,
+};
 use mc_util_uri::{ConnectionUri, FogUri};
 use std::sync::Arc;
 
@@ -34,8 +36,14 @@ impl Server {
                 .build(),
         );
 
-        let report_service =
-            report_grpc::create_report_api(Service::new(chain_id, db, materials, logger.clone()));
+        // Reports are public, so only the chain id is checked.
+        let interceptors = InterceptorChain::new().with(ChainIdInterceptor::new(chain_id));
+        let report_service = report_grpc::create_report_api(Service::new(
+            interceptors,
+            db,
+            materials,
+            logger.clone(),
+        ));
         log::debug!(logger, "Constructed Report GRPC Service");
 
         // Health check service
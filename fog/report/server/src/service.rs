@@ -15,7 +15,7 @@ use mc_fog_recovery_db_iface::{RecoveryDbError, ReportDb};
 use mc_fog_report_types::{Report, ReportResponse};
 use mc_fog_sig_report::Signer as ReportSigner;
 use mc_util_grpc::{
-    check_request_chain_id, rpc_database_err, rpc_internal_error, rpc_logger, send_result,
+    rpc_database_err, rpc_internal_error, rpc_logger, send_result, InterceptorChain,
 };
 use prost::DecodeError;
 
@@ -28,9 +28,8 @@ pub struct Service<R: ReportDb + Clone + Send + Sync> {
     /// Cryptographic materials used in response construction
     materials: Materials,
 
-    /// Chain id to check against the user-provided chain id (if present in
-    /// request)
-    chain_id: String,
+    /// Checks run on requests before they are served, e.g. the chain id
+    interceptors: InterceptorChain,
 
     /// Slog logger object
     logger: Logger,
@@ -74,9 +73,14 @@ impl<E: RecoveryDbError> Error<E> {
 impl<R: ReportDb + Clone + Send + Sync> Service<R> {
     /// Creates a new report service node (but does not create sockets and start
     /// it etc.)
-    pub fn new(chain_id: String, report_db: R, materials: Materials, logger: Logger) -> Self {
+    pub fn new(
+        interceptors: InterceptorChain,
+        report_db: R,
+        materials: Materials,
+        logger: Logger,
+    ) -> Self {
         Self {
-            chain_id,
+            interceptors,
             report_db,
             materials,
             logger,
@@ -126,7 +130,7 @@ impl<R: ReportDb + Clone + Send + Sync> ReportApi for Service<R> {
     ) {
         let _timer = SVC_COUNTERS.req(&ctx);
         logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
-            if let Err(err) = check_request_chain_id(&self.chain_id, &ctx) {
+            if let Err(err) = self.interceptors.check(&ctx) {
                 return send_result(ctx, sink, Err(err), logger);
             }
 
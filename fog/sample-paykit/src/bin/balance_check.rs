@@ -69,7 +69,8 @@ fn main() {
         account_key,
         logger.clone(),
     )
-    .build();
+    .build()
+    .expect("Could not build client");
 
     loop {
         // Do a balance check and print result on one line in stdout
@@ -134,7 +134,8 @@ impl RemoteWalletService {
             account_key,
             self.logger.clone(),
         )
-        .build();
+        .build()
+        .map_err(|err| rpc_internal_error("build_client", err, &self.logger))?;
 
         let (balances, block_count) = client
             .check_balance()
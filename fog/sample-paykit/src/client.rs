@@ -14,7 +14,7 @@ use mc_blockchain_types::{BlockIndex, BlockVersion};
 use mc_common::logger::{log, Logger};
 use mc_connection::{
     BlockchainConnection, Connection, Error as ConnectionError, HardcodedCredentialsProvider,
-    ProposeTxResult, ThickClient, UserTxConnection,
+    NetworkStatus, NetworkStatusCache, ProposeTxResult, ThickClient, UserTxConnection,
 };
 use mc_crypto_keys::CompressedRistrettoPublic;
 use mc_crypto_ring_signature_signer::{LocalRingSigner, OneTimeKeyDeriveData, RingSigner};
@@ -64,7 +64,7 @@ pub struct Client {
     ring_size: usize,
     account_key: AccountKey,
     tx_data: CachedTxData,
-    block_info_cache: Option<BlockInfo>,
+    network_status: NetworkStatusCache,
 
     /// Number of blocks for which to try and get the new transaction to be
     /// included in the ledger. This value is used to calculate the
@@ -104,7 +104,7 @@ impl Client {
             ring_size,
             account_key,
             tx_data,
-            block_info_cache: None,
+            network_status: Default::default(),
             new_tx_block_attempts: DEFAULT_NEW_TX_BLOCK_ATTEMPTS,
             logger,
         }
@@ -195,10 +195,11 @@ impl Client {
                 if let ConnectionError::TransactionValidation(
                     ProposeTxResult::FeeMapDigestMismatch,
                     _,
+                    _,
                 ) = err
                 {
-                    // Clear block_info cache so that fee map info will be regenerated
-                    // next time.
+                    // Clear network status cache so that fee map info will be
+                    // regenerated next time.
                     //
                     // NOTE: In a real client, what you should actually do is check
                     // with several nodes what the fee map is supposed to be,
@@ -211,7 +212,7 @@ impl Client {
                     // The sample paykit is just test code, and it only has one url
                     // for a consensus node in this revision, so we don't do that
                     // here, we just reset the fee map.
-                    self.block_info_cache = None;
+                    self.network_status.invalidate();
                 }
 
                 err
@@ -364,9 +365,9 @@ impl Client {
 
         let tombstone_block = self.compute_tombstone_block()?;
 
-        let block_version = BlockVersion::try_from(self.tx_data.get_latest_block_version())?;
-
-        let fee_map = self.get_fee_map(true)?;
+        let network_status = self.get_network_status(true)?;
+        let block_version = network_status.block_version;
+        let fee_map = network_status.fee_map()?;
 
         // Make fog resolver
         let fog_uris = [&self.account_key.change_subaddress(), target_address]
@@ -439,7 +440,7 @@ impl Client {
 
         let tombstone_block = self.compute_tombstone_block()?;
 
-        let block_version = BlockVersion::try_from(self.tx_data.get_latest_block_version())?;
+        let block_version = self.get_network_status(true)?.block_version;
 
         // Make fog resolver
         let fog_uris = self
@@ -591,7 +592,8 @@ impl Client {
             .fetch_fog_reports(fog_uris.into_iter())?;
         let fog_resolver = FogResolver::new(fog_responses, &self.fog_identities)?;
 
-        let block_version = BlockVersion::try_from(self.tx_data.get_latest_block_version())?;
+        let network_status = self.get_network_status(true)?;
+        let block_version = network_status.block_version;
 
         let change_destination = ReservedSubaddresses::from(&self.account_key);
 
@@ -601,7 +603,7 @@ impl Client {
             TransactionBuilder::new(block_version, fee, fog_resolver, EmptyMemoBuilder)?;
         tx_builder.set_tombstone_block(tombstone_block);
 
-        tx_builder.set_fee_map(self.get_fee_map(true)?);
+        tx_builder.set_fee_map(network_status.fee_map()?);
 
         // Aggregate total required outlay due to the SCI
         // (Note: In the partial fill case, there will be more outlays later)
@@ -913,47 +915,54 @@ impl Client {
         Ok(res.num_blocks + self.new_tx_block_attempts as u64)
     }
 
-    /// Retrieve the current last block info structure from consensus service.
+    /// Retrieve the network status from consensus service.
     ///
-    /// This includes fee data and last block index, and the configured block
-    /// version
+    /// This includes the block version new transactions should use, the
+    /// minimum fee of each token, and the features the block version enables.
     ///
     /// Arguments:
     /// * allow_cached If true, then we may skip a network call and use cached
     ///   value.
-    pub fn get_last_block_info(&mut self, allow_cached: bool) -> Result<BlockInfo> {
+    pub fn get_network_status(&mut self, allow_cached: bool) -> Result<NetworkStatus> {
         // Clear cache if we aren't allowed to use it, it will be repopulated in this
         // call.
         if !allow_cached {
-            self.block_info_cache = None;
+            self.network_status.invalidate();
         }
         // Check if we know our cache is stale for other reasons, like if fog responses
         // told us the block version has increased.
-        if let Some(block_info_cache) = self.block_info_cache.as_ref() {
-            if block_info_cache.network_block_version != self.tx_data.get_latest_block_version() {
-                self.block_info_cache = None;
+        if let Some(network_status) = self.network_status.cached() {
+            if *network_status.block_version != self.tx_data.get_latest_block_version() {
+                self.network_status.invalidate();
             }
         }
 
-        if self.block_info_cache.is_none() {
-            let block_info = self.consensus_service_conn.fetch_block_info()?;
-            // Opportunistically update our cached block version value
-            self.tx_data
-                .notify_block_version(block_info.network_block_version);
-            self.block_info_cache = Some(block_info);
-        }
+        let network_status = self
+            .network_status
+            .get_network_status(&mut self.consensus_service_conn)?;
+        // Opportunistically update our cached block version value
+        self.tx_data
+            .notify_block_version(*network_status.block_version);
+        Ok(network_status)
+    }
 
-        let block_info = self.consensus_service_conn.fetch_block_info()?;
-        Ok(block_info)
+    /// Retrieve the current last block info structure from consensus service.
+    ///
+    /// This includes fee data and last block index, and the configured block
+    /// version
+    ///
+    /// Arguments:
+    /// * allow_cached If true, then we may skip a network call and use cached
+    ///   value.
+    pub fn get_last_block_info(&mut self, allow_cached: bool) -> Result<BlockInfo> {
+        Ok(self.get_network_status(allow_cached)?.into())
     }
 
     /// Get the currently cached fee-map
     ///
     /// If the cache is empty, then we get fresh data from consensus.
     pub fn get_fee_map(&mut self, allow_cached: bool) -> Result<FeeMap> {
-        let block_info = self.get_last_block_info(allow_cached)?;
-        let fee_map = FeeMap::try_from(block_info.minimum_fees)?;
-        Ok(fee_map)
+        Ok(self.get_network_status(allow_cached)?.fee_map()?)
     }
 
     /// Retrieve the currently configured minimum fee for a token id from the
@@ -964,8 +973,8 @@ impl Client {
         allow_cached: bool,
     ) -> Result<Option<u64>> {
         Ok(self
-            .get_last_block_info(allow_cached)?
-            .minimum_fee_or_none(&token_id))
+            .get_network_status(allow_cached)?
+            .minimum_fee(&token_id))
     }
 
     /// Get the public b58 address for this client
@@ -2,9 +2,10 @@
 
 //! Client Builder
 
-use crate::client::Client;
+use crate::{client::Client, error::Result};
 use grpcio::EnvBuilder;
 use mc_account_keys::{AccountKey, PublicAddress};
+use mc_attest_verifier_config::TrustedIdentitySet;
 use mc_attestation_verifier::{TrustedIdentity, TrustedMrSignerIdentity};
 use mc_common::logger::{log, o, Logger};
 use mc_connection::{HardcodedCredentialsProvider, ThickClient};
@@ -47,6 +48,10 @@ pub struct ClientBuilder {
     fog_ingest_sigstruct: Option<Signature>,
     fog_ledger_sigstruct: Option<Signature>,
     fog_view_sigstruct: Option<Signature>,
+
+    // Optional identities loaded at runtime, used for services without a
+    // sigstruct
+    trusted_identity_set: Option<TrustedIdentitySet>,
 }
 
 impl ClientBuilder {
@@ -73,6 +78,7 @@ impl ClientBuilder {
             fog_ingest_sigstruct: None,
             fog_ledger_sigstruct: None,
             fog_view_sigstruct: None,
+            trusted_identity_set: None,
         }
     }
 
@@ -125,15 +131,27 @@ impl ClientBuilder {
         self
     }
 
-    /// Create the client
-    pub fn build(self) -> Client {
+    /// Sets the trusted identities to attest services with, e.g. the identities
+    /// of a set loaded with
+    /// [mc_attest_verifier_config::SignedTrustedIdentitySet::from_signed_json].
+    /// These replace the identities built into the client, for services
+    /// without a sigstruct.
+    #[must_use]
+    pub fn trusted_identity_set(mut self, set: Option<TrustedIdentitySet>) -> Self {
+        self.trusted_identity_set = set;
+        self
+    }
+
+    /// Create the client. This fails if the trusted identity set lacks an
+    /// identity needed for one of the services.
+    pub fn build(self) -> Result<Client> {
         let grpc_env = Arc::new(
             EnvBuilder::new()
                 .name_prefix(format!("sdk-{}", self.uri.addr()))
                 .build(),
         );
 
-        let fog_view_client = self.build_fog_view_conn(grpc_env.clone());
+        let fog_view_client = self.build_fog_view_conn(grpc_env.clone())?;
 
         log::info!(
             self.logger,
@@ -141,94 +159,98 @@ impl ClientBuilder {
             self.ledger_server_address
         );
         let (fog_merkle_proof, fog_key_image, fog_untrusted, fog_block) =
-            self.build_fog_ledger_server_conns(grpc_env.clone());
+            self.build_fog_ledger_server_conns(grpc_env.clone())?;
 
-        let identity = self.consensus_identity();
+        let identities = self.consensus_identities()?;
 
         log::debug!(
             self.logger,
-            "Consensus attestation identity: {:?}",
-            identity
+            "Consensus attestation identities: {:?}",
+            identities
         );
 
         let consensus_service_conn = ThickClient::new(
             self.chain_id.clone(),
             self.uri.clone(),
-            [identity],
+            identities,
             grpc_env.clone(),
             HardcodedCredentialsProvider::from(&self.uri),
             self.logger.new(o!("mc.cxn" => self.uri.addr())),
         )
         .expect("ThickClient::new returned an error");
 
-        let fog_ingest_identity = self.fog_ingest_identity();
+        let fog_ingest_identities = self.fog_ingest_identities()?;
 
         log::debug!(
             self.logger,
-            "Fog ingest attestation identity: {:?}",
-            fog_ingest_identity
+            "Fog ingest attestation identities: {:?}",
+            fog_ingest_identities
         );
 
         let fog_report_conn =
             GrpcFogReportConnection::new(self.chain_id.clone(), grpc_env, self.logger.clone());
 
-        Client::new(
+        Ok(Client::new(
             consensus_service_conn,
             fog_view_client,
             fog_merkle_proof,
             fog_key_image,
             fog_block,
             fog_report_conn,
-            [fog_ingest_identity],
+            fog_ingest_identities,
             fog_untrusted,
             self.ring_size,
             self.key,
             self.address_book,
             self.logger,
-        )
+        ))
     }
 
     // Build a Fog View connection, taking into account acct_host_override
     // and default port
-    fn build_fog_view_conn(&self, grpc_env: Arc<grpcio::Environment>) -> FogViewGrpcClient {
-        let identity = self.fog_view_identity();
+    fn build_fog_view_conn(&self, grpc_env: Arc<grpcio::Environment>) -> Result<FogViewGrpcClient> {
+        let identities = self.fog_view_identities()?;
 
-        log::debug!(self.logger, "Fog view attestation identity: {:?}", identity);
+        log::debug!(
+            self.logger,
+            "Fog view attestation identities: {:?}",
+            identities
+        );
 
-        FogViewGrpcClient::new(
+        Ok(FogViewGrpcClient::new(
             self.chain_id.clone(),
             self.fog_view_address.clone(),
             self.grpc_retry_config,
-            [identity],
+            identities,
             grpc_env,
             self.logger.clone(),
-        )
+        ))
     }
 
     // Build a Fog Ledger connection.
     fn build_fog_ledger_server_conns(
         &self,
         grpc_env: Arc<grpcio::Environment>,
-    ) -> (
+    ) -> Result<(
         FogMerkleProofGrpcClient,
         FogKeyImageGrpcClient,
         FogUntrustedLedgerGrpcClient,
         FogBlockGrpcClient,
-    ) {
-        let identity = self.fog_ledger_identity();
+    )> {
+        let identities = self.fog_ledger_identities()?;
 
         log::debug!(
             self.logger,
-            "Fog ledger attestation identity: {:?}",
-            identity
+            "Fog ledger attestation identities: {:?}",
+            identities
         );
 
-        (
+        Ok((
             FogMerkleProofGrpcClient::new(
                 self.chain_id.clone(),
                 self.ledger_server_address.clone(),
                 self.grpc_retry_config,
-                [identity.clone()],
+                identities.clone(),
                 grpc_env.clone(),
                 self.logger.clone(),
             ),
@@ -236,7 +258,7 @@ impl ClientBuilder {
                 self.chain_id.clone(),
                 self.ledger_server_address.clone(),
                 self.grpc_retry_config,
-                [identity],
+                identities,
                 grpc_env.clone(),
                 self.logger.clone(),
             ),
@@ -252,11 +274,22 @@ impl ClientBuilder {
                 grpc_env,
                 self.logger.clone(),
             ),
-        )
+        ))
+    }
+
+    // Get the identities of an enclave from the trusted identity set, if there
+    // is one
+    fn identities_from_set(&self, enclave_name: &str) -> Result<Option<Vec<TrustedIdentity>>> {
+        Ok(self
+            .trusted_identity_set
+            .as_ref()
+            .map(|set| set.identities(enclave_name))
+            .transpose()?)
     }
 
-    // Get consensus attestation identity (dynamic or build time, MRSIGNER)
-    fn consensus_identity(&self) -> TrustedIdentity {
+    // Get consensus attestation identities (dynamic, from the trusted
+    // identity set, or build time, MRSIGNER)
+    fn consensus_identities(&self) -> Result<Vec<TrustedIdentity>> {
         if let Some(signature) = self.consensus_sigstruct.as_ref() {
             let mr_signer_identity = TrustedMrSignerIdentity::new(
                 signature.mrsigner().into(),
@@ -265,14 +298,16 @@ impl ClientBuilder {
                 [] as [&str; 0],
                 mc_consensus_enclave_measurement::HARDENING_ADVISORIES,
             );
-            mr_signer_identity.into()
-        } else {
-            mc_consensus_enclave_measurement::mr_signer_identity(None)
+            return Ok(vec![mr_signer_identity.into()]);
         }
+        Ok(self
+            .identities_from_set("consensus")?
+            .unwrap_or_else(|| vec![mc_consensus_enclave_measurement::mr_signer_identity(None)]))
     }
 
-    // Get fog ingest attestation identity (dynamic or build time, MRSIGNER)
-    fn fog_ingest_identity(&self) -> TrustedIdentity {
+    // Get fog ingest attestation identities (dynamic, from the trusted
+    // identity set, or build time, MRSIGNER)
+    fn fog_ingest_identities(&self) -> Result<Vec<TrustedIdentity>> {
         if let Some(signature) = self.fog_ingest_sigstruct.as_ref() {
             let mr_signer_identity = TrustedMrSignerIdentity::new(
                 signature.mrsigner().into(),
@@ -281,14 +316,16 @@ impl ClientBuilder {
                 [] as [&str; 0],
                 mc_fog_ingest_enclave_measurement::HARDENING_ADVISORIES,
             );
-            mr_signer_identity.into()
-        } else {
-            mc_fog_ingest_enclave_measurement::mr_signer_identity(None)
+            return Ok(vec![mr_signer_identity.into()]);
         }
+        Ok(self
+            .identities_from_set("fog-ingest")?
+            .unwrap_or_else(|| vec![mc_fog_ingest_enclave_measurement::mr_signer_identity(None)]))
     }
 
-    // Get fog ledger attestation identity (dynamic or build time, MRSIGNER)
-    fn fog_ledger_identity(&self) -> TrustedIdentity {
+    // Get fog ledger attestation identities (dynamic, from the trusted
+    // identity set, or build time, MRSIGNER)
+    fn fog_ledger_identities(&self) -> Result<Vec<TrustedIdentity>> {
         if let Some(signature) = self.fog_ledger_sigstruct.as_ref() {
             let mr_signer_identity = TrustedMrSignerIdentity::new(
                 signature.mrsigner().into(),
@@ -297,14 +334,16 @@ impl ClientBuilder {
                 [] as [&str; 0],
                 mc_fog_ledger_enclave_measurement::HARDENING_ADVISORIES,
             );
-            mr_signer_identity.into()
-        } else {
-            mc_fog_ledger_enclave_measurement::mr_signer_identity(None)
+            return Ok(vec![mr_signer_identity.into()]);
         }
+        Ok(self
+            .identities_from_set("fog-ledger")?
+            .unwrap_or_else(|| vec![mc_fog_ledger_enclave_measurement::mr_signer_identity(None)]))
     }
 
-    // Get fog view attestation identity (dynamic or build time, MRSIGNER)
-    fn fog_view_identity(&self) -> TrustedIdentity {
+    // Get fog view attestation identities (dynamic, from the trusted
+    // identity set, or build time, MRSIGNER)
+    fn fog_view_identities(&self) -> Result<Vec<TrustedIdentity>> {
         if let Some(signature) = self.fog_view_sigstruct.as_ref() {
             let mr_signer_identity = TrustedMrSignerIdentity::new(
                 signature.mrsigner().into(),
@@ -313,9 +352,10 @@ impl ClientBuilder {
                 [] as [&str; 0],
                 mc_fog_view_enclave_measurement::HARDENING_ADVISORIES,
             );
-            mr_signer_identity.into()
-        } else {
-            mc_fog_view_enclave_measurement::mr_signer_identity(None)
+            return Ok(vec![mr_signer_identity.into()]);
         }
+        Ok(self
+            .identities_from_set("fog-view")?
+            .unwrap_or_else(|| vec![mc_fog_view_enclave_measurement::mr_signer_identity(None)]))
     }
 }
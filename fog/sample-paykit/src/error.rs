@@ -3,7 +3,8 @@
 //! MobileCoin SDK Errors
 
 use displaydoc::Display;
-use mc_connection::{Error as ConnectionError, ProposeTxResult};
+use mc_attest_verifier_config::Error as TrustedIdentitySetError;
+use mc_connection::{Error as ConnectionError, ProposeTxRejectionDetails, ProposeTxResult};
 use mc_consensus_api::ConversionError;
 use mc_crypto_keys::KeyError;
 use mc_fog_enclave_connection::Error as EnclaveConnectionError;
@@ -117,7 +118,7 @@ pub enum Error {
     Conversion(ConversionError),
 
     /// Proposed transcation rejected: {0:?}: {1}
-    TxRejected(ProposeTxResult, String),
+    TxRejected(ProposeTxResult, String, ProposeTxRejectionDetails),
 
     /// Could not parse uri: {0}
     Uri(UriParseError),
@@ -148,12 +149,17 @@ pub enum Error {
 
     /// Fee Map: {0}
     FeeMap(FeeMapError),
+
+    /// Trusted identity set: {0}
+    TrustedIdentitySet(TrustedIdentitySetError),
 }
 
 impl From<ConnectionError> for Error {
     fn from(x: ConnectionError) -> Error {
         match x {
-            ConnectionError::TransactionValidation(tve, msg) => Error::TxRejected(tve, msg),
+            ConnectionError::TransactionValidation(tve, msg, details) => {
+                Error::TxRejected(tve, msg, details)
+            }
             other => Error::ConsensusConnection(other),
         }
     }
@@ -230,3 +236,9 @@ impl From<FeeMapError> for Error {
         Error::FeeMap(x)
     }
 }
+
+impl From<TrustedIdentitySetError> for Error {
+    fn from(src: TrustedIdentitySetError) -> Self {
+        Self::TrustedIdentitySet(src)
+    }
+}
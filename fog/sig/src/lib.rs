@@ -48,6 +48,9 @@
 //! order to support enclaves for clients and report servers.
 
 mod public_address;
+mod report_response;
+
+pub use report_response::verify_report_response;
 
 use core::fmt::{Debug, Display};
 use displaydoc::Display;
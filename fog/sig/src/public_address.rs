@@ -3,16 +3,15 @@
 //! This module provides the implementation of the all-in-one verifier for
 //! public addresses.
 
-use crate::{Error, Verifier};
-use mc_account_keys::PublicAddress;
-use mc_crypto_keys::{Ed25519Signature, RistrettoSignature, SignatureError};
-use mc_crypto_x509_utils::{
-    PublicKeyType, X509CertificateChain, X509CertificateIter, X509KeyExtrator,
+use crate::{
+    report_response::{parse_chain, verify_report_signature},
+    Error, Verifier,
 };
+use mc_account_keys::PublicAddress;
+use mc_crypto_keys::{RistrettoSignature, SignatureError};
+use mc_crypto_x509_utils::X509CertificateChain;
 use mc_fog_report_types::ReportResponse;
 use mc_fog_sig_authority::Verifier as AuthorityVerifier;
-use mc_fog_sig_report::Verifier as ReportVerifier;
-use x509_signature::X509Certificate;
 
 impl Verifier for PublicAddress {
     type ReportSigError = SignatureError;
@@ -21,16 +20,7 @@ impl Verifier for PublicAddress {
         &self,
         report_response: &ReportResponse,
     ) -> Result<(), Error<<Self as AuthorityVerifier>::Error, Self::ReportSigError>> {
-        // Vec<Vec<u8>> -> Vec<&[u8]>
-        // Vec<&[u8]> -> Vec<X509Certificate>
-        let certs = X509CertificateIter::from(
-            report_response
-                .chain
-                .iter()
-                .map(|der| der.as_slice())
-                .collect::<Vec<&[u8]>>(),
-        )
-        .collect::<Vec<X509Certificate>>();
+        let certs = parse_chain(report_response);
 
         // Get the authority signature
         let authority_sig =
@@ -44,15 +34,7 @@ impl Verifier for PublicAddress {
         .map_err(Error::Authority)?;
 
         // Verify the signature over the reports matches the leaf cert in the chain
-        match certs.leaf()?.mc_public_key().map_err(Error::Pubkey)? {
-            PublicKeyType::Ed25519(pubkey) => {
-                let sig = Ed25519Signature::try_from(report_response.signature.as_slice())
-                    .map_err(Error::SignatureParse)?;
-                pubkey
-                    .verify_reports(report_response.reports.as_slice(), &sig)
-                    .map_err(Error::Report)
-            }
-        }
+        verify_report_signature(&certs, report_response)
     }
 }
 
@@ -68,6 +50,7 @@ mod tests {
     use pem::Pem;
     use rand_core::SeedableRng;
     use rand_hc::Hc128Rng;
+    use x509_signature::X509Certificate;
 
     /// Setup a functional fog authority scheme.
     ///
@@ -131,6 +114,8 @@ mod tests {
         public_address
             .verify_fog_sig(&report_response)
             .expect("Correct ReportResponse did not pass");
+        crate::verify_report_response(&report_response)
+            .expect("Correct ReportResponse did not pass recipient-independent checks");
     }
 
     /// Test a scenario where the chain has been removed.
@@ -159,5 +144,7 @@ mod tests {
         public_address
             .verify_fog_sig(&report_response)
             .expect_err("Bad ReportResponse with empty chain accepted");
+        crate::verify_report_response(&report_response)
+            .expect_err("Bad ReportResponse with empty chain accepted");
     }
 }
@@ -0,0 +1,62 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! This module verifies the parts of a report server response which do not
+//! depend on the recipient: the certificate chain, and the leaf certificate's
+//! signature over the reports.
+
+use crate::Error;
+use core::{
+    convert::Infallible,
+    fmt::{Debug, Display},
+};
+use mc_crypto_keys::{Ed25519Signature, SignatureError};
+use mc_crypto_x509_utils::{
+    PublicKeyType, X509CertificateChain, X509CertificateIter, X509KeyExtrator,
+};
+use mc_fog_report_types::ReportResponse;
+use mc_fog_sig_report::Verifier as ReportVerifier;
+use x509_signature::X509Certificate;
+
+/// Verify the certificate chain of a report server response, and that its leaf
+/// certificate signed the reports.
+///
+/// This does not check the fog authority signature of any recipient, so a
+/// response which passes may still not be valid for sending to a given
+/// recipient, which [Verifier::verify_fog_sig](crate::Verifier) checks.
+pub fn verify_report_response(
+    report_response: &ReportResponse,
+) -> Result<(), Error<Infallible, SignatureError>> {
+    let certs = parse_chain(report_response);
+    certs.verified_root()?;
+    verify_report_signature(&certs, report_response)
+}
+
+/// Parse the certificate chain of a report server response.
+pub(crate) fn parse_chain(report_response: &ReportResponse) -> Vec<X509Certificate> {
+    // Vec<Vec<u8>> -> Vec<&[u8]>
+    // Vec<&[u8]> -> Vec<X509Certificate>
+    X509CertificateIter::from(
+        report_response
+            .chain
+            .iter()
+            .map(|der| der.as_slice())
+            .collect::<Vec<&[u8]>>(),
+    )
+    .collect::<Vec<X509Certificate>>()
+}
+
+/// Verify the signature over the reports matches the leaf cert in the chain.
+pub(crate) fn verify_report_signature<A: Debug + Display>(
+    certs: &[X509Certificate],
+    report_response: &ReportResponse,
+) -> Result<(), Error<A, SignatureError>> {
+    match certs.leaf()?.mc_public_key().map_err(Error::Pubkey)? {
+        PublicKeyType::Ed25519(pubkey) => {
+            let sig = Ed25519Signature::try_from(report_response.signature.as_slice())
+                .map_err(Error::SignatureParse)?;
+            pubkey
+                .verify_reports(report_response.reports.as_slice(), &sig)
+                .map_err(Error::Report)
+        }
+    }
+}
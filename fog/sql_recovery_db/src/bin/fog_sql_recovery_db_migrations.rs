@@ -3,20 +3,72 @@
 //! A helper utility for running migrations on a database configured via
 //! DATABASE_URL.
 
+use clap::{Parser, Subcommand};
 use diesel::{prelude::*, PgConnection};
-use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
-use std::env;
+use mc_fog_sql_recovery_db::migrations::{pending_migrations, run_migrations, validate_migrations};
 
-const MIGRATIONS: EmbeddedMigrations = embed_migrations!("./migrations");
+/// Command line configuration for the migrations utility.
+#[derive(Clone, Debug, Parser)]
+#[clap(version)]
+struct Config {
+    /// The database to migrate
+    #[clap(long, env = "DATABASE_URL")]
+    database_url: String,
+
+    /// What to do. Defaults to running the pending migrations.
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Clone, Debug, Subcommand)]
+enum Command {
+    /// Validate the applied migrations, and apply the pending ones.
+    Run {
+        /// Apply the pending migrations in a transaction which is rolled
+        /// back, to check that they would succeed without changing the
+        /// database.
+        #[clap(long)]
+        dry_run: bool,
+    },
+
+    /// List the migrations which have not been applied yet.
+    Pending,
+
+    /// Check that the applied migrations are known to this version, and
+    /// haven't changed since they were applied.
+    Validate,
+}
 
 fn main() {
-    let database_url = env::var("DATABASE_URL").expect("Missing DATABASE_URL environment variable");
+    let config = Config::parse();
 
-    let conn = &mut PgConnection::establish(&database_url)
+    let conn = &mut PgConnection::establish(&config.database_url)
         .expect("fog-sql-recovery-db-migrations cannot connect to PG database");
 
-    conn.run_pending_migrations(MIGRATIONS)
-        .expect("Failed running migrations");
-
-    println!("Done migrating Fog recovery DB!");
+    match config.command.unwrap_or(Command::Run { dry_run: false }) {
+        Command::Run { dry_run } => {
+            let applied = run_migrations(conn, dry_run).expect("Failed running migrations");
+            for name in &applied {
+                println!("Applied {name}");
+            }
+            if dry_run {
+                println!("Dry run: rolled back {} migrations", applied.len());
+            } else {
+                println!("Done migrating Fog recovery DB!");
+            }
+        }
+        Command::Pending => {
+            let pending = pending_migrations(conn).expect("Failed listing pending migrations");
+            if pending.is_empty() {
+                println!("No pending migrations");
+            }
+            for name in pending {
+                println!("{name}");
+            }
+        }
+        Command::Validate => {
+            validate_migrations(conn).expect("Failed validating migrations");
+            println!("Applied migrations are valid");
+        }
+    }
 }
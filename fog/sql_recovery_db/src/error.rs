@@ -41,6 +41,16 @@ pub enum Error {
     /// Overlapping missed block range: {0:?} overlaps with {0:?}
     OverlappingMissedBlocksRange(BlockRange, BlockRange),
 
+    /// Migration: {0}
+    Migration(String),
+
+    /// Migration {0} was applied to the database, but is not known to this
+    /// version
+    UnknownMigration(String),
+
+    /// Migration {0} was changed after it was applied to the database
+    MigrationChecksumMismatch(String),
+
     /**
      * The data in the database could not be decoded as
      * AttestationEvidence: {0:?}
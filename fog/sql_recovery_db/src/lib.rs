@@ -9,6 +9,7 @@ extern crate diesel_migrations;
 
 pub use error::Error;
 
+pub mod migrations;
 pub mod test_utils;
 
 mod error;
@@ -82,9 +83,16 @@ pub struct SqlRecoveryDbConnectionConfig {
     pub postgres_connection_timeout: Duration,
 
     /// The maximum number of connections managed by the pool.
+    /// When a read replica is used, this applies to each of the two pools.
     #[clap(long, default_value = "1", env = "MC_POSTGRES_MAX_CONNECTIONS")]
     pub postgres_max_connections: u32,
 
+    /// The minimum number of idle connections the pool tries to maintain, so
+    /// that bursts of queries don't have to wait for new connections. Defaults
+    /// to the maximum number of connections.
+    #[clap(long, env = "MC_POSTGRES_MIN_IDLE_CONNECTIONS")]
+    pub postgres_min_idle_connections: Option<u32>,
+
     /// How many times to retry when we get retriable errors (connection /
     /// diesel errors)
     #[clap(long, default_value = "3", env = "MC_POSTGRES_RETRY_COUNT")]
@@ -103,6 +111,7 @@ impl Default for SqlRecoveryDbConnectionConfig {
             postgres_max_lifetime: Duration::from_secs(120),
             postgres_connection_timeout: Duration::from_secs(5),
             postgres_max_connections: 1,
+            postgres_min_idle_connections: None,
             postgres_retry_count: 3,
             postgres_retry_millis: 20,
         }
@@ -110,22 +119,34 @@ impl Default for SqlRecoveryDbConnectionConfig {
 }
 
 /// SQL-backed recovery database.
+///
+/// Queries which only read from the database may be sent to a read replica
+/// (see [SqlRecoveryDb::new_with_read_replica]), while all writes go to the
+/// primary. Since a replica can lag behind the primary, only services which
+/// never write, like fog view, should use one: a service which reads back what
+/// it has just written could otherwise miss its own writes.
 #[derive(Clone)]
 pub struct SqlRecoveryDb {
+    /// Connections to the primary, used for writes.
     pool: Pool<ConnectionManager<PgConnection>>,
+    /// Connections used for read-only queries. This is the same pool as `pool`
+    /// unless a read replica is configured.
+    read_pool: Pool<ConnectionManager<PgConnection>>,
     config: SqlRecoveryDbConnectionConfig,
     logger: Logger,
 }
 
 impl SqlRecoveryDb {
-    /// Create a new instance using a pre-existing connection pool.
+    /// Create a new instance using pre-existing connection pools.
     fn new(
         pool: Pool<ConnectionManager<PgConnection>>,
+        read_pool: Pool<ConnectionManager<PgConnection>>,
         config: SqlRecoveryDbConnectionConfig,
         logger: Logger,
     ) -> Self {
         Self {
             pool,
+            read_pool,
             config,
             logger,
         }
@@ -138,15 +159,47 @@ impl SqlRecoveryDb {
         config: SqlRecoveryDbConnectionConfig,
         logger: Logger,
     ) -> Result<Self, Error> {
+        let pool = Self::build_pool(database_url, &config)?;
+        Ok(Self::new(pool.clone(), pool, config, logger))
+    }
+
+    /// Create a new instance which sends read-only queries to a read replica,
+    /// and everything else to the primary at `database_url`.
+    ///
+    /// Each of the two gets its own connection pool, configured by `config`.
+    /// If `read_replica_url` is None, this is the same as
+    /// [SqlRecoveryDb::new_from_url].
+    pub fn new_with_read_replica(
+        database_url: &str,
+        read_replica_url: Option<&str>,
+        config: SqlRecoveryDbConnectionConfig,
+        logger: Logger,
+    ) -> Result<Self, Error> {
+        let Some(read_replica_url) = read_replica_url else {
+            return Self::new_from_url(database_url, config, logger);
+        };
+        let pool = Self::build_pool(database_url, &config)?;
+        let read_pool = Self::build_pool(read_replica_url, &config)?;
+        log::info!(
+            logger,
+            "Sending read-only recovery db queries to a read replica"
+        );
+        Ok(Self::new(pool, read_pool, config, logger))
+    }
+
+    fn build_pool(
+        database_url: &str,
+        config: &SqlRecoveryDbConnectionConfig,
+    ) -> Result<Pool<ConnectionManager<PgConnection>>, Error> {
         let manager = ConnectionManager::<PgConnection>::new(database_url);
-        let pool = Pool::builder()
+        Ok(Pool::builder()
             .max_size(config.postgres_max_connections)
+            .min_idle(config.postgres_min_idle_connections)
             .idle_timeout(Some(config.postgres_idle_timeout))
             .max_lifetime(Some(config.postgres_max_lifetime))
             .connection_timeout(config.postgres_connection_timeout)
             .test_on_check_out(true)
-            .build(manager)?;
-        Ok(Self::new(pool, config, logger))
+            .build(manager)?)
     }
 
     // Helper function for retries config
@@ -312,7 +365,7 @@ impl SqlRecoveryDb {
         &self,
         key: &CompressedRistrettoPublic,
     ) -> Result<Option<IngressPublicKeyStatus>, Error> {
-        let conn = &mut self.pool.get()?;
+        let conn = &mut self.read_pool.get()?;
         self.get_ingress_key_status_impl(conn, key)
     }
 
@@ -375,7 +428,7 @@ impl SqlRecoveryDb {
     ) -> Result<Option<u64>, Error> {
         let key_bytes: &[u8] = key.as_ref();
 
-        let conn = &mut self.pool.get()?;
+        let conn = &mut self.read_pool.get()?;
 
         use schema::ingested_blocks::dsl;
         let maybe_index: Option<i64> = dsl::ingested_blocks
@@ -391,7 +444,7 @@ impl SqlRecoveryDb {
         start_block_at_least: u64,
         ingress_public_key_record_filters: &IngressPublicKeyRecordFilters,
     ) -> Result<Vec<IngressPublicKeyRecord>, Error> {
-        let conn = &mut self.pool.get()?;
+        let conn = &mut self.read_pool.get()?;
 
         use schema::ingress_keys::dsl;
         let last_scanned_block = diesel::dsl::sql::<diesel::sql_types::BigInt>(
@@ -509,7 +562,7 @@ impl SqlRecoveryDb {
     fn get_ingestable_ranges_retriable(
         &self,
     ) -> Result<Vec<mc_fog_recovery_db_iface::IngestableRange>, Error> {
-        let conn = &mut self.pool.get()?;
+        let conn = &mut self.read_pool.get()?;
 
         // For each ingest invocation we are aware of get its id, start block, is
         // decommissioned and the max block number it has ingested (if
@@ -705,7 +758,7 @@ impl SqlRecoveryDb {
     }
 
     fn get_missed_block_ranges_retriable(&self) -> Result<Vec<BlockRange>, Error> {
-        let conn = &mut self.pool.get()?;
+        let conn = &mut self.read_pool.get()?;
         self.get_missed_block_ranges_impl(conn)
     }
 
@@ -718,7 +771,7 @@ impl SqlRecoveryDb {
             return Ok((Default::default(), i64::MAX));
         }
 
-        let conn = &mut self.pool.get()?;
+        let conn = &mut self.read_pool.get()?;
         let mut events: Vec<(i64, FogUserEvent)> = Vec::new();
 
         // Collect all events of interest
@@ -888,7 +941,7 @@ impl SqlRecoveryDb {
         start_block: u64,
         search_keys: &[Vec<u8>],
     ) -> Result<Vec<FixedTxOutSearchResult>, Error> {
-        let conn = &mut self.pool.get()?;
+        let conn = &mut self.read_pool.get()?;
 
         let query = schema::ingested_blocks::dsl::ingested_blocks
             .filter(schema::ingested_blocks::dsl::block_number.ge(start_block as i64))
@@ -941,7 +994,7 @@ impl SqlRecoveryDb {
         ingress_key: CompressedRistrettoPublic,
         block_index: u64,
     ) -> Result<Option<Vec<ETxOutRecord>>, Error> {
-        let conn = &mut self.pool.get()?;
+        let conn = &mut self.read_pool.get()?;
 
         let key_bytes: &[u8] = ingress_key.as_ref();
         let query = schema::ingested_blocks::dsl::ingested_blocks
@@ -980,7 +1033,7 @@ impl SqlRecoveryDb {
         ingress_key: CompressedRistrettoPublic,
         block_range: &BlockRange,
     ) -> Result<Vec<Vec<ETxOutRecord>>, Error> {
-        let conn = &mut self.pool.get()?;
+        let conn = &mut self.read_pool.get()?;
 
         // The idea is:
         // Similar to get_tx_outs_by_block_and_key_retriable, but now
@@ -1040,7 +1093,7 @@ impl SqlRecoveryDb {
         ingress_key: CompressedRistrettoPublic,
         block_index: u64,
     ) -> Result<Option<IngestInvocationId>, Error> {
-        let conn = &mut self.pool.get()?;
+        let conn = &mut self.read_pool.get()?;
 
         let key_bytes: &[u8] = ingress_key.as_ref();
         let query = schema::ingested_blocks::dsl::ingested_blocks
@@ -1074,7 +1127,7 @@ impl SqlRecoveryDb {
         &self,
         block_index: u64,
     ) -> Result<Option<u64>, Error> {
-        let conn = &mut self.pool.get()?;
+        let conn = &mut self.read_pool.get()?;
 
         let query = schema::ingested_blocks::dsl::ingested_blocks
             .filter(schema::ingested_blocks::dsl::block_number.eq(block_index as i64))
@@ -1110,7 +1163,7 @@ impl SqlRecoveryDb {
         &self,
         block_index: u64,
     ) -> Result<Option<u64>, Error> {
-        let conn = &mut self.pool.get()?;
+        let conn = &mut self.read_pool.get()?;
 
         let query = schema::ingested_blocks::dsl::ingested_blocks
             .filter(schema::ingested_blocks::dsl::block_number.eq(block_index as i64))
@@ -1122,7 +1175,7 @@ impl SqlRecoveryDb {
 
     /// Get the highest block index for which we have any data at all.
     fn get_highest_known_block_index_retriable(&self) -> Result<Option<u64>, Error> {
-        let conn = &mut self.pool.get()?;
+        let conn = &mut self.read_pool.get()?;
         SqlRecoveryDb::get_highest_known_block_index_impl(conn)
     }
 
@@ -1132,7 +1185,7 @@ impl SqlRecoveryDb {
     ////
 
     fn get_all_reports_retriable(&self) -> Result<Vec<(String, ReportData)>, Error> {
-        let conn = &mut self.pool.get()?;
+        let conn = &mut self.read_pool.get()?;
 
         let query = schema::reports::dsl::reports
             .select((
@@ -1256,7 +1309,7 @@ impl SqlRecoveryDb {
         &self,
         expiration: NaiveDateTime,
     ) -> Result<Vec<ExpiredInvocationRecord>, Error> {
-        let conn = &mut self.pool.get()?;
+        let conn = &mut self.read_pool.get()?;
         self.get_expired_invocations_impl(conn, expiration)
     }
 }
@@ -2515,6 +2568,43 @@ mod tests {
         assert_eq!(db.get_highest_known_block_index().unwrap(), Some(125));
     }
 
+    #[test_with_logger]
+    fn test_reads_use_read_replica(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([123u8; 32]);
+        let primary_test_context = test_utils::SqlRecoveryDbTestContext::new(logger.clone());
+        let replica_test_context = test_utils::SqlRecoveryDbTestContext::new(logger.clone());
+        let primary = primary_test_context.get_db_instance();
+
+        // Use an unrelated database as the "replica", so that we can tell which
+        // database each query went to.
+        let db = SqlRecoveryDb::new_with_read_replica(
+            &primary_test_context.db_url(),
+            Some(&replica_test_context.db_url()),
+            Default::default(),
+            logger,
+        )
+        .unwrap();
+
+        let ingress_key = CompressedRistrettoPublic::from(RistrettoPublic::from_random(&mut rng));
+        db.new_ingress_key(&ingress_key, 120).unwrap();
+        let invoc_id = db
+            .new_ingest_invocation(None, &ingress_key, &random_kex_rng_pubkey(&mut rng), 120)
+            .unwrap();
+        let (block, records) = random_block(&mut rng, 123, 10);
+        db.add_block_data(&invoc_id, &block, 0, &records).unwrap();
+
+        // Writes went to the primary.
+        assert_eq!(primary.get_highest_known_block_index().unwrap(), Some(123));
+        assert!(primary
+            .get_ingress_key_status(&ingress_key)
+            .unwrap()
+            .is_some());
+
+        // Reads went to the replica, which never received them.
+        assert_eq!(db.get_highest_known_block_index().unwrap(), None);
+        assert_eq!(db.get_ingress_key_status(&ingress_key).unwrap(), None);
+    }
+
     fn create_attestation_evidence(name: &str) -> AttestationEvidence {
         let report_data = prost::EnclaveReportDataContents {
             nonce: format!("{name} nonce").into_bytes(),
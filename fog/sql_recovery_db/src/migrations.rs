@@ -0,0 +1,165 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Schema migrations for the recovery database.
+//!
+//! The migrations are embedded in the crate, and applied in order of their
+//! versions with diesel's migration harness. In addition, the checksum of
+//! each migration's up.sql is recorded when it is applied, so that a
+//! migration which was edited after it was applied, or a database migrated by
+//! a newer version of fog, is detected before anything else is changed.
+//! Databases migrated before checksums were recorded have the checksums of
+//! their applied migrations recorded on the next run.
+
+use crate::Error;
+use diesel::{
+    pg::PgConnection,
+    prelude::*,
+    result::Error as DieselError,
+    sql_types::{Binary, Text},
+};
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+use sha2::{Digest, Sha256};
+
+/// The migrations of the recovery database.
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("./migrations");
+
+/// The up.sql of each migration, by version, from which its checksum is
+/// computed. A new migration must be added here as well as to the
+/// migrations directory.
+const MIGRATION_SQL: &[(&str, &str)] = &[
+    (
+        "00000000000000",
+        include_str!("../migrations/00000000000000_diesel_initial_setup/up.sql"),
+    ),
+    (
+        "20201103203017",
+        include_str!("../migrations/2020-11-03-203017_create_initial_db/up.sql"),
+    ),
+];
+
+/// The checksum of a migration's up.sql, if it is known.
+fn migration_checksum(version: &str) -> Option<Vec<u8>> {
+    MIGRATION_SQL
+        .iter()
+        .find(|(sql_version, _)| *sql_version == version)
+        .map(|(_, sql)| Sha256::digest(sql.as_bytes()).to_vec())
+}
+
+#[derive(QueryableByName)]
+struct RecordedChecksum {
+    #[diesel(sql_type = Text)]
+    version: String,
+    #[diesel(sql_type = Binary)]
+    checksum: Vec<u8>,
+}
+
+/// The names of the migrations which have not been applied to the database
+/// yet, in the order they would be applied.
+pub fn pending_migrations(conn: &mut PgConnection) -> Result<Vec<String>, Error> {
+    Ok(conn
+        .pending_migrations(MIGRATIONS)
+        .map_err(|err| Error::Migration(err.to_string()))?
+        .iter()
+        .map(|migration| migration.name().to_string())
+        .collect())
+}
+
+/// Check that every migration applied to the database is known, and has the
+/// checksum recorded when it was applied.
+pub fn validate_migrations(conn: &mut PgConnection) -> Result<(), Error> {
+    conn.transaction(validate_applied_migrations)
+}
+
+/// Validate the applied migrations, and apply the pending ones, returning
+/// their names.
+///
+/// If `dry_run` is set, the migrations are applied in a transaction which is
+/// then rolled back, so that a migration which would fail is found without
+/// changing the database.
+pub fn run_migrations(conn: &mut PgConnection, dry_run: bool) -> Result<Vec<String>, Error> {
+    let mut applied = Vec::new();
+    let result = conn.transaction(|conn| {
+        validate_applied_migrations(conn)?;
+        applied = pending_migrations(conn)?;
+        let versions = conn
+            .run_pending_migrations(MIGRATIONS)
+            .map_err(|err| Error::Migration(err.to_string()))?;
+        for version in versions {
+            record_checksum(conn, &version.to_string())?;
+        }
+
+        if dry_run {
+            Err(Error::Orm(DieselError::RollbackTransaction))
+        } else {
+            Ok(())
+        }
+    });
+
+    match result {
+        Ok(()) => Ok(applied),
+        Err(Error::Orm(DieselError::RollbackTransaction)) if dry_run => Ok(applied),
+        Err(err) => Err(err),
+    }
+}
+
+fn validate_applied_migrations(conn: &mut PgConnection) -> Result<(), Error> {
+    diesel::sql_query(
+        "CREATE TABLE IF NOT EXISTS __fog_migration_checksums (
+            version VARCHAR(50) PRIMARY KEY NOT NULL,
+            checksum BYTEA NOT NULL,
+            applied_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+    )
+    .execute(conn)?;
+
+    let recorded = diesel::sql_query("SELECT version, checksum FROM __fog_migration_checksums")
+        .load::<RecordedChecksum>(conn)?;
+    let applied = conn
+        .applied_migrations()
+        .map_err(|err| Error::Migration(err.to_string()))?;
+
+    for version in applied {
+        let version = version.to_string();
+        let checksum =
+            migration_checksum(&version).ok_or_else(|| Error::UnknownMigration(version.clone()))?;
+        match recorded.iter().find(|recorded| recorded.version == version) {
+            Some(recorded) if recorded.checksum != checksum => {
+                return Err(Error::MigrationChecksumMismatch(version))
+            }
+            Some(_) => {}
+            None => record_checksum(conn, &version)?,
+        }
+    }
+
+    Ok(())
+}
+
+fn record_checksum(conn: &mut PgConnection, version: &str) -> Result<(), Error> {
+    let checksum =
+        migration_checksum(version).ok_or_else(|| Error::UnknownMigration(version.to_owned()))?;
+    diesel::sql_query("INSERT INTO __fog_migration_checksums (version, checksum) VALUES ($1, $2)")
+        .bind::<Text, _>(version)
+        .bind::<Binary, _>(checksum)
+        .execute(conn)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::pg::Pg;
+    use diesel_migrations::MigrationSource;
+
+    #[test]
+    fn every_migration_has_a_checksum() {
+        let migrations = MigrationSource::<Pg>::migrations(&MIGRATIONS).unwrap();
+        assert_eq!(migrations.len(), MIGRATION_SQL.len());
+        for migration in migrations {
+            let version = migration.name().version().to_string();
+            assert!(
+                migration_checksum(&version).is_some(),
+                "missing up.sql for migration {version}"
+            );
+        }
+    }
+}
@@ -2,9 +2,8 @@
 
 //! Utilities for testing.
 
-use crate::{SqlRecoveryDb, SqlRecoveryDbConnectionConfig};
+use crate::{migrations::run_migrations, SqlRecoveryDb, SqlRecoveryDbConnectionConfig};
 use diesel::{prelude::*, PgConnection};
-use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 use mc_common::logger::{log, Logger};
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
 use retry::{
@@ -13,7 +12,6 @@ use retry::{
 };
 use std::time::Duration;
 
-const MIGRATIONS: EmbeddedMigrations = embed_migrations!("./migrations");
 const DB_CONNECTION_SLEEP_PERIOD: Duration = Duration::from_secs(3);
 const TOTAL_RETRY_COUNT: usize = 5;
 
@@ -55,8 +53,7 @@ impl SqlRecoveryDbTestContext {
         log::info!(&logger, "Connecting to newly created PG DB '{}'", db_url);
 
         let conn = &mut SqlRecoveryDbTestContext::establish_connection(&db_url);
-        conn.run_pending_migrations(MIGRATIONS)
-            .expect("failed running migrations");
+        run_migrations(conn, false).expect("failed running migrations");
 
         // Success
         Self {
@@ -0,0 +1,282 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! A TCP proxy which injects network faults, for integration tests.
+//!
+//! Put a [ChaosProxy] between a client and a server (e.g. a fog client and the
+//! ledger router, or the router and a key image store) by pointing the client
+//! at [ChaosProxy::addr] instead of the server. The proxy forwards bytes in
+//! both directions, and according to its [ChaosConfig] it can:
+//! - delay every chunk of data it forwards,
+//! - drop new connections as soon as they are accepted,
+//! - cut established connections at random points.
+//!
+//! Live connections can also be cut on demand with
+//! [ChaosProxy::reset_connections], which lets a test force a client through
+//! its retry and reattestation paths at a known point.
+
+use rand_core::{RngCore, SeedableRng};
+use rand_hc::Hc128Rng;
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// How often blocked threads check whether the proxy is stopping.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The faults a [ChaosProxy] injects. The default forwards everything
+/// unchanged.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChaosConfig {
+    /// Delay added before forwarding each chunk of data
+    pub latency: Duration,
+    /// The probability that a new connection is closed as soon as it is
+    /// accepted
+    pub drop_probability: f64,
+    /// The probability that a connection is cut before forwarding a chunk of
+    /// data
+    pub reset_probability: f64,
+}
+
+/// Forwards TCP connections to a target address, injecting faults.
+pub struct ChaosProxy {
+    addr: SocketAddr,
+    state: Arc<ProxyState>,
+    join_handle: Option<JoinHandle<()>>,
+}
+
+struct ProxyState {
+    target: SocketAddr,
+    config: Mutex<ChaosConfig>,
+    rng: Mutex<Hc128Rng>,
+    /// Both sides of every live connection, so that they can be cut
+    connections: Mutex<Vec<(TcpStream, TcpStream)>>,
+    stop_requested: AtomicBool,
+}
+
+impl ProxyState {
+    /// Returns true with the given probability.
+    fn chance(&self, probability: f64) -> bool {
+        if probability <= 0.0 {
+            return false;
+        }
+        let sample = self.rng.lock().expect("mutex poisoned").next_u32();
+        (sample as f64) < probability * (u32::MAX as f64 + 1.0)
+    }
+
+    fn config(&self) -> ChaosConfig {
+        self.config.lock().expect("mutex poisoned").clone()
+    }
+
+    fn handle_connection(self: &Arc<Self>, client: TcpStream) -> io::Result<()> {
+        if self.chance(self.config().drop_probability) {
+            return client.shutdown(Shutdown::Both);
+        }
+        let server = TcpStream::connect(self.target)?;
+        self.connections
+            .lock()
+            .expect("mutex poisoned")
+            .push((client.try_clone()?, server.try_clone()?));
+
+        for (from, to) in [(client.try_clone()?, server.try_clone()?), (server, client)] {
+            let state = self.clone();
+            thread::Builder::new()
+                .name("ChaosProxyPump".to_owned())
+                .spawn(move || state.pump(from, to))?;
+        }
+        Ok(())
+    }
+
+    /// Forward data from one side of a connection to the other, until either
+    /// side closes or the connection is cut.
+    fn pump(&self, mut from: TcpStream, mut to: TcpStream) {
+        let mut buf = [0u8; 16 * 1024];
+        let _ = from.set_read_timeout(Some(POLL_INTERVAL));
+        while !self.stop_requested.load(Ordering::SeqCst) {
+            let len = match from.read(&mut buf) {
+                Ok(0) => break,
+                Ok(len) => len,
+                Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    continue
+                }
+                Err(_) => break,
+            };
+            let config = self.config();
+            if !config.latency.is_zero() {
+                thread::sleep(config.latency);
+            }
+            if self.chance(config.reset_probability) || to.write_all(&buf[..len]).is_err() {
+                break;
+            }
+        }
+        let _ = from.shutdown(Shutdown::Both);
+        let _ = to.shutdown(Shutdown::Both);
+    }
+}
+
+impl ChaosProxy {
+    /// Start a proxy on a free localhost port, forwarding to `target`.
+    ///
+    /// The `seed` makes the injected faults reproducible for a given sequence
+    /// of connections and data.
+    pub fn start(target: SocketAddr, config: ChaosConfig, seed: u64) -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+
+        let state = Arc::new(ProxyState {
+            target,
+            config: Mutex::new(config),
+            rng: Mutex::new(Hc128Rng::seed_from_u64(seed)),
+            connections: Mutex::new(Vec::new()),
+            stop_requested: AtomicBool::new(false),
+        });
+
+        let thread_state = state.clone();
+        let join_handle =
+            thread::Builder::new()
+                .name("ChaosProxy".to_owned())
+                .spawn(move || {
+                    while !thread_state.stop_requested.load(Ordering::SeqCst) {
+                        match listener.accept() {
+                            Ok((client, _)) => {
+                                // Connections are handled with blocking reads,
+                                // which time out to check for a stop request.
+                                let _ = client
+                                    .set_nonblocking(false)
+                                    .and_then(|_| thread_state.handle_connection(client));
+                            }
+                            Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                                thread::sleep(POLL_INTERVAL)
+                            }
+                            Err(_) => thread::sleep(POLL_INTERVAL),
+                        }
+                    }
+                })?;
+
+        Ok(Self {
+            addr,
+            state,
+            join_handle: Some(join_handle),
+        })
+    }
+
+    /// The address clients should connect to instead of the target.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Change the faults injected from now on.
+    pub fn set_config(&self, config: ChaosConfig) {
+        *self.state.config.lock().expect("mutex poisoned") = config;
+    }
+
+    /// Cut every connection which is currently open through the proxy.
+    pub fn reset_connections(&self) {
+        let connections =
+            std::mem::take(&mut *self.state.connections.lock().expect("mutex poisoned"));
+        for (client, server) in connections {
+            let _ = client.shutdown(Shutdown::Both);
+            let _ = server.shutdown(Shutdown::Both);
+        }
+    }
+
+    /// Stop accepting connections and cut the open ones.
+    pub fn stop(&mut self) {
+        if let Some(join_handle) = self.join_handle.take() {
+            self.state.stop_requested.store(true, Ordering::SeqCst);
+            let _ = join_handle.join();
+            self.reset_connections();
+        }
+    }
+}
+
+impl Drop for ChaosProxy {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    /// Start a server which echoes everything back.
+    fn echo_server() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                thread::spawn(move || {
+                    let mut reader = stream.try_clone().unwrap();
+                    let _ = io::copy(&mut reader, &mut stream);
+                });
+            }
+        });
+        addr
+    }
+
+    fn echo(stream: &mut TcpStream, message: &[u8]) -> io::Result<Vec<u8>> {
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+        stream.write_all(message)?;
+        let mut response = vec![0u8; message.len()];
+        stream.read_exact(&mut response)?;
+        Ok(response)
+    }
+
+    #[test]
+    fn forwards_with_latency() {
+        let latency = Duration::from_millis(50);
+        let proxy = ChaosProxy::start(
+            echo_server(),
+            ChaosConfig {
+                latency,
+                ..Default::default()
+            },
+            0,
+        )
+        .unwrap();
+
+        let mut stream = TcpStream::connect(proxy.addr()).unwrap();
+        let start = Instant::now();
+        assert_eq!(echo(&mut stream, b"hello").unwrap(), b"hello");
+        // Both directions are delayed.
+        assert!(start.elapsed() >= 2 * latency);
+    }
+
+    #[test]
+    fn drops_and_resets_connections() {
+        let proxy = ChaosProxy::start(
+            echo_server(),
+            ChaosConfig {
+                drop_probability: 1.0,
+                ..Default::default()
+            },
+            0,
+        )
+        .unwrap();
+        let mut stream = TcpStream::connect(proxy.addr()).unwrap();
+        assert!(echo(&mut stream, b"hello").is_err());
+
+        proxy.set_config(ChaosConfig::default());
+        let mut stream = TcpStream::connect(proxy.addr()).unwrap();
+        assert_eq!(echo(&mut stream, b"hello").unwrap(), b"hello");
+
+        proxy.reset_connections();
+        assert!(echo(&mut stream, b"hello").is_err());
+
+        proxy.set_config(ChaosConfig {
+            reset_probability: 1.0,
+            ..Default::default()
+        });
+        let mut stream = TcpStream::connect(proxy.addr()).unwrap();
+        assert!(echo(&mut stream, b"hello").is_err());
+    }
+}
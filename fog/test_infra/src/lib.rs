@@ -5,6 +5,7 @@
 #![allow(non_snake_case)]
 #![deny(missing_docs)]
 
+pub mod chaos_proxy;
 pub mod db_tests;
 pub mod mock_client;
 pub mod mock_users;
@@ -1,6 +1,6 @@
 // Copyright (c) 2018-2022 The MobileCoin Foundation
 
-use alloc::vec::Vec;
+use alloc::{vec, vec::Vec};
 use displaydoc::Display;
 use mc_transaction_core::{
     ring_signature::KeyImage,
@@ -67,6 +67,11 @@ pub struct GetOutputsResponse {
     /// for an update (by comparing to their local value of max_block_version).
     #[prost(uint32, tag = "5")]
     pub max_block_version: u32,
+
+    /// Zero bytes added by the enclave to reach a padding bucket. Clients
+    /// should ignore them.
+    #[prost(bytes, tag = "6")]
+    pub padding: Vec<u8>,
 }
 
 /// The result of an individual query for an output and membership proof
@@ -156,6 +161,31 @@ pub struct CheckKeyImagesResponse {
     /// for an update (by comparing to their local value of max_block_version).
     #[prost(uint32, tag = "5")]
     pub max_block_version: u32,
+
+    /// Zero bytes added by the enclave to reach a padding bucket. Clients
+    /// should ignore them.
+    #[prost(bytes, tag = "6")]
+    pub padding: Vec<u8>,
+
+    /// Number of blocks in the ledger the server ingests key images from.
+    ///
+    /// This is at least `num_blocks`, which is how many of them the server has
+    /// ingested. Servers which predate this field leave it zero.
+    #[prost(uint64, tag = "7")]
+    pub ledger_num_blocks: u64,
+
+    /// Whether the server has not yet ingested every block of its ledger. Key
+    /// images reported as not spent may then have been spent in a block the
+    /// server has not ingested yet.
+    #[prost(bool, tag = "8")]
+    pub serving_degraded: bool,
+
+    /// The enclave's Ed25519 signature over
+    /// [key_image_response_signed_message] of this response and the responder
+    /// id the client attested to, made with the identity key bound into its
+    /// attestation evidence. Servers which predate this field leave it empty.
+    #[prost(bytes, tag = "9")]
+    pub signature: Vec<u8>,
 }
 
 /// A result which tells for a given key image, whether it was spent or not
@@ -192,6 +222,131 @@ pub struct KeyImageResult {
     pub key_image_result_code: u32,
 }
 
+/// The largest padding bucket, in bytes. Larger configured buckets are
+/// dropped, see [LedgerCapabilities::with_padding_buckets].
+pub const MAX_PADDING_BUCKET: u32 = 1 << 20;
+
+/// Tag of the `padding` field in [GetOutputsResponse] and
+/// [CheckKeyImagesResponse].
+const PADDING_TAG: u32 = 6;
+
+/// A response which can be padded to a size bucket, to hide its exact size
+/// from an observer of the encrypted traffic.
+pub trait PaddedResponse: Message {
+    /// The padding field of the response.
+    fn padding_mut(&mut self) -> &mut Vec<u8>;
+}
+
+impl PaddedResponse for GetOutputsResponse {
+    fn padding_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.padding
+    }
+}
+
+impl PaddedResponse for CheckKeyImagesResponse {
+    fn padding_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.padding
+    }
+}
+
+/// The length of the padding field contents which brings an encoded message
+/// of `unpadded_len` bytes, plus the key of the padding field, to the smallest
+/// of the `buckets` which can be reached, or failing that to the smallest
+/// reachable multiple of the largest bucket. Zero if no padding is needed, or
+/// there are no buckets.
+fn padding_len(unpadded_len: usize, key_len: usize, buckets: &[u32]) -> usize {
+    let Some(largest) = buckets.iter().max().map(|bucket| *bucket as usize) else {
+        return 0;
+    };
+    let mut fitting = buckets
+        .iter()
+        .map(|bucket| *bucket as usize)
+        .filter(|bucket| *bucket >= unpadded_len)
+        .collect::<Vec<_>>();
+    fitting.sort_unstable();
+    let multiples = (unpadded_len.div_ceil(largest).max(1)..).map(|count| count * largest);
+
+    // The padding field costs its key, its length prefix and its contents, so
+    // some targets can't be hit exactly; those move on to the next one.
+    fitting
+        .into_iter()
+        .chain(multiples)
+        .find_map(|target| {
+            if target == unpadded_len {
+                Some(0)
+            } else {
+                target
+                    .checked_sub(unpadded_len + key_len)
+                    .and_then(mc_util_serial::padding_field_len)
+            }
+        })
+        .unwrap_or_default()
+}
+
+/// Set the padding of a response so that its encoded length is the smallest
+/// of the `buckets` which can be reached, given the overhead of encoding the
+/// padding field itself, or if it fits in none of them, a multiple of the
+/// largest. The padding is all zeroes, so the result only depends on the rest
+/// of the response and the buckets.
+///
+/// No buckets disables padding.
+pub fn pad_response<R: PaddedResponse>(response: &mut R, buckets: &[u32]) {
+    response.padding_mut().clear();
+    let padding_len = padding_len(
+        response.encoded_len(),
+        prost::encoding::key_len(PADDING_TAG),
+        buckets,
+    );
+    response.padding_mut().resize(padding_len, 0);
+}
+
+/// Pad an encoded request to the `buckets` as [pad_response] pads responses,
+/// with an unknown field which the enclave skips when decoding the request.
+pub fn pad_request(request: &mut Vec<u8>, buckets: &[u32]) {
+    let padding_len = padding_len(
+        request.len(),
+        prost::encoding::key_len(mc_util_serial::PADDING_FIELD_TAG),
+        buckets,
+    );
+    if padding_len > 0 {
+        prost::encoding::bytes::encode(
+            mc_util_serial::PADDING_FIELD_TAG,
+            &vec![0u8; padding_len],
+            request,
+        );
+    }
+}
+
+/// Domain separator for the message signed in a [CheckKeyImagesResponse].
+const KEY_IMAGE_RESPONSE_SIGNATURE_CONTEXT: &[u8] = b"mc-fog-ledger-check-key-images-response";
+
+/// Tag of the `results` field in [CheckKeyImagesResponse].
+const RESULTS_TAG: u32 = 3;
+
+/// The message a ledger enclave signs in a [CheckKeyImagesResponse]: the
+/// responder id the client attested to, the chain and ingestion state the
+/// enclave reports, and its results encoded as in the response.
+pub fn key_image_response_signed_message(
+    responder_id: &str,
+    response: &CheckKeyImagesResponse,
+) -> Vec<u8> {
+    let mut message = KEY_IMAGE_RESPONSE_SIGNATURE_CONTEXT.to_vec();
+    message.extend_from_slice(&(responder_id.len() as u64).to_le_bytes());
+    message.extend_from_slice(responder_id.as_bytes());
+    message.extend_from_slice(&response.num_blocks.to_le_bytes());
+    message.extend_from_slice(&response.global_txo_count.to_le_bytes());
+    message.extend_from_slice(&response.latest_block_version.to_le_bytes());
+    message.extend_from_slice(&response.ledger_num_blocks.to_le_bytes());
+    message.push(response.serving_degraded as u8);
+    prost::encoding::message::encode_repeated(RESULTS_TAG, &response.results, &mut message);
+    message
+}
+
+/// Remove the padding from a response received from the enclave.
+pub fn strip_padding<R: PaddedResponse>(response: &mut R) {
+    *response.padding_mut() = Vec::new();
+}
+
 /// An enum corresponding to the KeyImageResultCode proto enum
 #[derive(PartialEq, Eq, Debug, Display)]
 #[repr(u32)]
@@ -218,3 +373,377 @@ impl TryFrom<u32> for KeyImageResultCode {
         }
     }
 }
+
+/// An enum corresponding to the OutputResultCode proto enum
+#[derive(PartialEq, Eq, Debug, Display)]
+#[repr(u32)]
+pub enum OutputResultCode {
+    /// A TxOut with this index was not found in the ledger.
+    DoesNotExist = 1,
+    /// A TxOut with this index was found, with its merkle proof.
+    Exists,
+    /// A database error prevented the request from being satisfied.
+    OutputDatabaseError,
+}
+
+impl TryFrom<u32> for OutputResultCode {
+    type Error = ();
+    fn try_from(src: u32) -> Result<OutputResultCode, ()> {
+        if src == OutputResultCode::DoesNotExist as u32 {
+            Ok(OutputResultCode::DoesNotExist)
+        } else if src == OutputResultCode::Exists as u32 {
+            Ok(OutputResultCode::Exists)
+        } else if src == OutputResultCode::OutputDatabaseError as u32 {
+            Ok(OutputResultCode::OutputDatabaseError)
+        } else {
+            Err(())
+        }
+    }
+}
+
+/// The version of the ledger enclave API spoken by this crate.
+pub const LEDGER_API_VERSION: u32 = 1;
+
+/// A kind of query a ledger enclave may answer. Corresponds to the
+/// LedgerQueryType proto enum.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Display)]
+#[repr(u32)]
+pub enum LedgerQueryType {
+    /// Checking whether key images were spent.
+    CheckKeyImages = 1,
+    /// Getting outputs and their membership proofs.
+    GetOutputs,
+}
+
+/// A way a ledger enclave may pad its responses. Corresponds to the
+/// LedgerPaddingMode proto enum.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Display)]
+#[repr(u32)]
+pub enum LedgerPaddingMode {
+    /// Padding responses and requests to the enclave's
+    /// [LedgerCapabilities::padding_buckets], see [pad_response] and
+    /// [pad_request].
+    SizeBuckets = 1,
+}
+
+/// The features of the ledger enclave API which a client and an enclave
+/// agree to use for an attested session.
+///
+/// The client offers the capabilities it understands in its auth request,
+/// and the enclave answers in its auth response with those it supports too,
+/// see [LedgerCapabilities::negotiate]. Both travel inside the attested
+/// handshake, so the untrusted server can't tamper with them. This lets new
+/// query types and limits roll out without upgrading clients and servers in
+/// lockstep, since each side only uses what the other agreed to. Clients and
+/// enclaves predating negotiation send nothing, which is treated as
+/// [LedgerCapabilities::legacy].
+#[derive(Clone, Message, Eq, PartialEq)]
+pub struct LedgerCapabilities {
+    /// The version of the ledger enclave API.
+    #[prost(uint32, tag = "1")]
+    pub version: u32,
+
+    /// The kinds of queries which may be made, as [LedgerQueryType] values.
+    #[prost(uint32, repeated, tag = "2")]
+    pub query_types: Vec<u32>,
+
+    /// The most key images which may be checked in one request, or zero if
+    /// there is no limit.
+    #[prost(uint32, tag = "3")]
+    pub max_key_images_per_request: u32,
+
+    /// The most outputs which may be requested in one request, or zero if
+    /// there is no limit.
+    #[prost(uint32, tag = "4")]
+    pub max_outputs_per_request: u32,
+
+    /// The ways in which responses may be padded, as [LedgerPaddingMode]
+    /// values.
+    #[prost(uint32, repeated, tag = "5")]
+    pub padding_modes: Vec<u32>,
+
+    /// The sizes, in bytes and in increasing order, which the enclave pads
+    /// responses to and clients pad requests to. The operator configures
+    /// these, rather than each client choosing its own, so that the sizes of
+    /// every client's messages fall in the same buckets.
+    #[prost(uint32, repeated, tag = "6")]
+    pub padding_buckets: Vec<u32>,
+}
+
+impl LedgerCapabilities {
+    /// Everything this version of the API supports, without any limits on
+    /// the sizes of requests.
+    pub fn current() -> Self {
+        Self {
+            version: LEDGER_API_VERSION,
+            query_types: [LedgerQueryType::CheckKeyImages, LedgerQueryType::GetOutputs]
+                .iter()
+                .map(|query_type| *query_type as u32)
+                .collect(),
+            max_key_images_per_request: 0,
+            max_outputs_per_request: 0,
+            padding_modes: [LedgerPaddingMode::SizeBuckets as u32].into(),
+            padding_buckets: Vec::new(),
+        }
+    }
+
+    /// These capabilities, with the given padding buckets. Buckets which are
+    /// zero or larger than [MAX_PADDING_BUCKET] are dropped.
+    pub fn with_padding_buckets(mut self, buckets: &[u32]) -> Self {
+        self.padding_buckets = buckets
+            .iter()
+            .copied()
+            .filter(|bucket| (1..=MAX_PADDING_BUCKET).contains(bucket))
+            .collect();
+        self.padding_buckets.sort_unstable();
+        self.padding_buckets.dedup();
+        self
+    }
+
+    /// What can be assumed of a peer predating negotiation: the queries which
+    /// predate it, without any padding.
+    pub fn legacy() -> Self {
+        Self {
+            version: 0,
+            padding_modes: Vec::new(),
+            ..Self::current()
+        }
+    }
+
+    /// The capabilities an enclave with these capabilities uses with a client
+    /// which offered `offered`: the lower of the two versions, the query types
+    /// and padding modes both support, the tighter of the two limits on
+    /// request sizes, and the enclave's padding buckets if both support
+    /// padding to them.
+    pub fn negotiate(&self, offered: &Self) -> Self {
+        let min_limit = |ours: u32, theirs: u32| match (ours, theirs) {
+            (0, limit) | (limit, 0) => limit,
+            (ours, theirs) => ours.min(theirs),
+        };
+        let padding_modes = self
+            .padding_modes
+            .iter()
+            .filter(|padding_mode| offered.padding_modes.contains(padding_mode))
+            .copied()
+            .collect::<Vec<_>>();
+        let padding_buckets = if padding_modes.contains(&(LedgerPaddingMode::SizeBuckets as u32)) {
+            self.padding_buckets.clone()
+        } else {
+            Vec::new()
+        };
+        Self {
+            version: self.version.min(offered.version),
+            query_types: self
+                .query_types
+                .iter()
+                .filter(|query_type| offered.query_types.contains(query_type))
+                .copied()
+                .collect(),
+            max_key_images_per_request: min_limit(
+                self.max_key_images_per_request,
+                offered.max_key_images_per_request,
+            ),
+            max_outputs_per_request: min_limit(
+                self.max_outputs_per_request,
+                offered.max_outputs_per_request,
+            ),
+            padding_modes,
+            padding_buckets,
+        }
+    }
+
+    /// Whether queries of the given type may be made.
+    pub fn supports_query(&self, query_type: LedgerQueryType) -> bool {
+        self.query_types.contains(&(query_type as u32))
+    }
+
+    /// Whether responses may be padded in the given way.
+    pub fn supports_padding(&self, padding_mode: LedgerPaddingMode) -> bool {
+        self.padding_modes.contains(&(padding_mode as u32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn signed_message_covers_responder_and_ingestion_status() {
+        let response = CheckKeyImagesResponse {
+            num_blocks: 10,
+            ledger_num_blocks: 12,
+            serving_degraded: true,
+            ..Default::default()
+        };
+        let message = key_image_response_signed_message("ledger.example.com:443", &response);
+
+        assert_ne!(
+            message,
+            key_image_response_signed_message("other.example.com:443", &response)
+        );
+        assert_ne!(
+            message,
+            key_image_response_signed_message(
+                "ledger.example.com:443",
+                &CheckKeyImagesResponse {
+                    ledger_num_blocks: 10,
+                    ..response.clone()
+                }
+            )
+        );
+        assert_ne!(
+            message,
+            key_image_response_signed_message(
+                "ledger.example.com:443",
+                &CheckKeyImagesResponse {
+                    serving_degraded: false,
+                    ..response.clone()
+                }
+            )
+        );
+        // The padding and signature are not signed.
+        assert_eq!(
+            message,
+            key_image_response_signed_message(
+                "ledger.example.com:443",
+                &CheckKeyImagesResponse {
+                    padding: vec![0; 16],
+                    signature: vec![0x55; 64],
+                    ..response
+                }
+            )
+        );
+    }
+
+    /// Check that `len` is one of the `buckets`, or a multiple of the largest
+    /// which is not much more than `unpadded_len`.
+    fn assert_in_bucket(len: usize, unpadded_len: usize, buckets: &[u32]) {
+        let largest = *buckets.iter().max().unwrap() as usize;
+        assert!(len >= unpadded_len);
+        assert!(
+            buckets.contains(&(len as u32))
+                || (len % largest == 0 && len < unpadded_len + 2 * largest + 8)
+        );
+    }
+
+    #[test]
+    fn pad_response_reaches_bucket() {
+        for buckets in [
+            &[1u32][..],
+            &[7],
+            &[64, 128],
+            &[1000, 100],
+            &[128, 512, 4096],
+        ] {
+            for num_results in 0..40u64 {
+                let mut response = CheckKeyImagesResponse {
+                    num_blocks: 1 << (num_results % 40),
+                    results: (0..num_results)
+                        .map(|index| KeyImageResult {
+                            key_image: KeyImage::from(index),
+                            spent_at: index,
+                            ..Default::default()
+                        })
+                        .collect(),
+                    ..Default::default()
+                };
+                let unpadded = response.clone();
+
+                pad_response(&mut response, buckets);
+                assert_in_bucket(response.encoded_len(), unpadded.encoded_len(), buckets);
+                assert!(response.padding.iter().all(|byte| *byte == 0));
+
+                let mut decoded: CheckKeyImagesResponse =
+                    mc_util_serial::decode(&mc_util_serial::encode(&response)).unwrap();
+                strip_padding(&mut decoded);
+                assert_eq!(decoded, unpadded);
+            }
+        }
+    }
+
+    #[test]
+    fn pad_response_without_buckets() {
+        let mut response = GetOutputsResponse {
+            num_blocks: 10,
+            padding: vec![0; 5],
+            ..Default::default()
+        };
+        pad_response(&mut response, &[]);
+        assert!(response.padding.is_empty());
+    }
+
+    #[test]
+    fn pad_request_decodes_to_request() {
+        let buckets = [256, 1024];
+        for num_queries in 0..40u64 {
+            let request = CheckKeyImagesRequest {
+                queries: (0..num_queries)
+                    .map(|index| KeyImageQuery {
+                        key_image: KeyImage::from(index),
+                        start_block: index,
+                    })
+                    .collect(),
+            };
+            let mut bytes = mc_util_serial::encode(&request);
+            let unpadded_len = bytes.len();
+
+            pad_request(&mut bytes, &buckets);
+            assert_in_bucket(bytes.len(), unpadded_len, &buckets);
+            let decoded: CheckKeyImagesRequest = mc_util_serial::decode(&bytes).unwrap();
+            assert_eq!(decoded, request);
+        }
+
+        let mut bytes = vec![1, 2, 3];
+        pad_request(&mut bytes, &[]);
+        assert_eq!(bytes, [1, 2, 3]);
+    }
+
+    #[test]
+    fn with_padding_buckets_drops_invalid_buckets() {
+        let capabilities = LedgerCapabilities::current().with_padding_buckets(&[
+            4096,
+            0,
+            512,
+            MAX_PADDING_BUCKET + 1,
+            512,
+            MAX_PADDING_BUCKET,
+        ]);
+        assert_eq!(
+            capabilities.padding_buckets,
+            [512, 4096, MAX_PADDING_BUCKET]
+        );
+    }
+
+    #[test]
+    fn negotiate_capabilities() {
+        let enclave = LedgerCapabilities {
+            max_key_images_per_request: 5000,
+            ..LedgerCapabilities::current().with_padding_buckets(&[1024, 256])
+        };
+        let client = LedgerCapabilities {
+            version: LEDGER_API_VERSION + 1,
+            query_types: vec![LedgerQueryType::CheckKeyImages as u32, 42],
+            max_key_images_per_request: 0,
+            max_outputs_per_request: 100,
+            padding_modes: vec![LedgerPaddingMode::SizeBuckets as u32],
+            padding_buckets: vec![1],
+        };
+
+        let negotiated = enclave.negotiate(&client);
+        assert_eq!(negotiated.version, LEDGER_API_VERSION);
+        assert!(negotiated.supports_query(LedgerQueryType::CheckKeyImages));
+        assert!(!negotiated.supports_query(LedgerQueryType::GetOutputs));
+        assert_eq!(negotiated.query_types.len(), 1);
+        assert_eq!(negotiated.max_key_images_per_request, 5000);
+        assert_eq!(negotiated.max_outputs_per_request, 100);
+        assert!(negotiated.supports_padding(LedgerPaddingMode::SizeBuckets));
+        assert_eq!(negotiated.padding_buckets, [256, 1024]);
+
+        let legacy = enclave.negotiate(&LedgerCapabilities::legacy());
+        assert_eq!(legacy.version, 0);
+        assert!(legacy.supports_query(LedgerQueryType::GetOutputs));
+        assert!(!legacy.supports_padding(LedgerPaddingMode::SizeBuckets));
+        assert!(legacy.padding_buckets.is_empty());
+    }
+}
@@ -25,6 +25,8 @@ pub mod ledger;
 /// Types related to fog view
 pub mod view;
 
+mod sensitive;
+
 /// An Encrypted Tx Out Record, consisting of a fog search_key (rng output),
 /// and an mc-crypto-box encrypted payload, containing the FogTxOutRecord
 /// protobuf.
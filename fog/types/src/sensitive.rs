@@ -0,0 +1,257 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Memory hygiene for fog types which hold secrets: search keys, the shared
+//! secrets of KexRngs, and decrypted TxOut records.
+//!
+//! These types implement [Zeroize], so that clients can wipe them when they
+//! are done with them, and [ConstantTimeEq], so that comparing them doesn't
+//! leak where they differ through timing.
+//!
+//! With the `zeroize-on-drop` feature, they are also wiped when dropped. This
+//! makes it impossible to move their fields out, so code which does that
+//! must `core::mem::take` them instead. Only enable it in clients.
+
+use crate::{
+    view::{
+        FixedTxOutSearchResult, QueryRequest, RngFastForward, TxOutAmountMaskedTokenId,
+        TxOutRecord, TxOutSearchResult,
+    },
+    ETxOutRecord,
+};
+use subtle::{Choice, ConstantTimeEq};
+use zeroize::Zeroize;
+
+/// Implement Zeroize, and with the `zeroize-on-drop` feature Drop, for a type
+/// by zeroizing each of the listed fields.
+macro_rules! impl_zeroize {
+    ($ty:ty, $($field:ident).+ $(, $($fields:ident).+)*) => {
+        impl Zeroize for $ty {
+            fn zeroize(&mut self) {
+                self.$($field).+.zeroize();
+                $(self.$($fields).+.zeroize();)*
+            }
+        }
+
+        #[cfg(feature = "zeroize-on-drop")]
+        impl Drop for $ty {
+            fn drop(&mut self) {
+                self.zeroize();
+            }
+        }
+
+        #[cfg(feature = "zeroize-on-drop")]
+        impl zeroize::ZeroizeOnDrop for $ty {}
+    };
+}
+
+/// Implement ConstantTimeEq for a type by comparing each of the listed fields.
+macro_rules! impl_ct_eq {
+    ($ty:ty, $field:ident $(, $fields:ident)*) => {
+        impl ConstantTimeEq for $ty {
+            fn ct_eq(&self, other: &Self) -> Choice {
+                self.$field.ct_eq(&other.$field) $(& self.$fields.ct_eq(&other.$fields))*
+            }
+        }
+    };
+}
+
+impl_zeroize!(ETxOutRecord, search_key, payload);
+impl_ct_eq!(ETxOutRecord, search_key, payload);
+
+impl_zeroize!(QueryRequest, get_txos, fast_forward_rngs);
+
+impl_zeroize!(
+    RngFastForward,
+    rng.secret,
+    rng.buffer,
+    rng.counter,
+    start_index,
+    num_search_keys
+);
+
+impl_zeroize!(
+    TxOutSearchResult,
+    search_key,
+    result_code,
+    ciphertext,
+    padding
+);
+impl_ct_eq!(
+    TxOutSearchResult,
+    search_key,
+    result_code,
+    ciphertext,
+    padding
+);
+
+impl_zeroize!(
+    FixedTxOutSearchResult,
+    search_key,
+    result_code,
+    ciphertext,
+    payload_length
+);
+impl_ct_eq!(
+    FixedTxOutSearchResult,
+    search_key,
+    result_code,
+    ciphertext,
+    payload_length
+);
+
+impl_zeroize!(
+    TxOutRecord,
+    tx_out_amount_commitment_data,
+    tx_out_amount_masked_value,
+    tx_out_target_key_data,
+    tx_out_public_key_data,
+    tx_out_global_index,
+    block_index,
+    timestamp,
+    tx_out_amount_commitment_data_crc32,
+    tx_out_e_memo_data,
+    tx_out_amount_masked_token_id
+);
+
+impl ConstantTimeEq for TxOutRecord {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        // Whether the masked token id is present is not secret, since every
+        // record written since masked token ids were introduced has one.
+        let masked_token_ids_eq = match (
+            &self.tx_out_amount_masked_token_id,
+            &other.tx_out_amount_masked_token_id,
+        ) {
+            (Some(a), Some(b)) => a.ct_eq(b),
+            (None, None) => Choice::from(1),
+            _ => Choice::from(0),
+        };
+        self.tx_out_amount_commitment_data
+            .ct_eq(&other.tx_out_amount_commitment_data)
+            & self
+                .tx_out_amount_masked_value
+                .ct_eq(&other.tx_out_amount_masked_value)
+            & self
+                .tx_out_target_key_data
+                .ct_eq(&other.tx_out_target_key_data)
+            & self
+                .tx_out_public_key_data
+                .ct_eq(&other.tx_out_public_key_data)
+            & self.tx_out_global_index.ct_eq(&other.tx_out_global_index)
+            & self.block_index.ct_eq(&other.block_index)
+            & self.timestamp.ct_eq(&other.timestamp)
+            & self
+                .tx_out_amount_commitment_data_crc32
+                .ct_eq(&other.tx_out_amount_commitment_data_crc32)
+            & self.tx_out_e_memo_data.ct_eq(&other.tx_out_e_memo_data)
+            & masked_token_ids_eq
+    }
+}
+
+impl TxOutAmountMaskedTokenId {
+    fn version_and_bytes(&self) -> (u8, &[u8]) {
+        match self {
+            Self::TxOutAmountMaskedTokenIdV1(bytes) => (1, bytes),
+            Self::TxOutAmountMaskedTokenIdV2(bytes) => (2, bytes),
+        }
+    }
+}
+
+impl Zeroize for TxOutAmountMaskedTokenId {
+    fn zeroize(&mut self) {
+        match self {
+            Self::TxOutAmountMaskedTokenIdV1(bytes) | Self::TxOutAmountMaskedTokenIdV2(bytes) => {
+                bytes.zeroize()
+            }
+        }
+    }
+}
+
+impl ConstantTimeEq for TxOutAmountMaskedTokenId {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        let (version, bytes) = self.version_and_bytes();
+        let (other_version, other_bytes) = other.version_and_bytes();
+        version.ct_eq(&other_version) & bytes.ct_eq(other_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use mc_fog_kex_rng::StoredRng;
+
+    #[test]
+    fn search_results_zeroize() {
+        let mut result = TxOutSearchResult {
+            search_key: vec![1; 16],
+            result_code: 1,
+            ciphertext: vec![2; 32],
+            padding: vec![0; 8],
+        };
+        result.zeroize();
+        assert!(result.search_key.is_empty());
+        assert_eq!(result.result_code, 0);
+        assert!(result.ciphertext.is_empty());
+    }
+
+    #[test]
+    fn fast_forward_rngs_zeroize() {
+        let mut request = QueryRequest {
+            get_txos: vec![vec![1; 16]],
+            fast_forward_rngs: vec![RngFastForward {
+                rng: StoredRng {
+                    secret: vec![3; 32],
+                    buffer: vec![4; 16],
+                    counter: 5,
+                    version: 1,
+                },
+                start_index: 6,
+                num_search_keys: 7,
+            }],
+            pad_response: false,
+        };
+        request.fast_forward_rngs[0].zeroize();
+        assert!(request.fast_forward_rngs[0].rng.secret.is_empty());
+        assert!(request.fast_forward_rngs[0].rng.buffer.is_empty());
+        assert_eq!(request.fast_forward_rngs[0].rng.counter, 0);
+
+        request.zeroize();
+        assert!(request.get_txos.is_empty());
+        assert!(request.fast_forward_rngs.is_empty());
+    }
+
+    #[test]
+    fn constant_time_eq_matches_eq() {
+        let record = ETxOutRecord {
+            search_key: vec![1; 16],
+            payload: vec![2; 32],
+        };
+        let mut other = record.clone();
+        assert!(bool::from(record.ct_eq(&other)));
+        other.payload[31] = 3;
+        assert!(!bool::from(record.ct_eq(&other)));
+
+        let record = TxOutRecord {
+            tx_out_amount_commitment_data: vec![],
+            tx_out_amount_masked_value: 1,
+            tx_out_target_key_data: vec![2; 32],
+            tx_out_public_key_data: vec![3; 32],
+            tx_out_global_index: 4,
+            block_index: 5,
+            timestamp: 6,
+            tx_out_amount_commitment_data_crc32: 7,
+            tx_out_e_memo_data: vec![8; 66],
+            tx_out_amount_masked_token_id: Some(
+                TxOutAmountMaskedTokenId::TxOutAmountMaskedTokenIdV1(vec![9; 4]),
+            ),
+        };
+        let mut other = record.clone();
+        assert!(bool::from(record.ct_eq(&other)));
+        other.tx_out_amount_masked_token_id =
+            Some(TxOutAmountMaskedTokenId::TxOutAmountMaskedTokenIdV2(vec![
+                9;
+                4
+            ]));
+        assert!(!bool::from(record.ct_eq(&other)));
+    }
+}
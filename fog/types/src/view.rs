@@ -7,6 +7,7 @@ use displaydoc::Display;
 use mc_attest_enclave_api::{EnclaveMessage, NonceSession};
 use mc_common::ResponderId;
 use mc_crypto_keys::{CompressedRistrettoPublic, KeyError, RistrettoPrivate, RistrettoPublic};
+use mc_fog_kex_rng::{BufferedRng, Error as KexRngError, VersionedKexRng};
 use mc_transaction_core::{
     encrypted_fog_hint::{EncryptedFogHint, ENCRYPTED_FOG_HINT_LEN},
     tx::TxOut,
@@ -15,11 +16,17 @@ use mc_transaction_core::{
 use prost::{Message, Oneof};
 use serde::{Deserialize, Serialize};
 
-pub use mc_fog_kex_rng::KexRngPubkey;
+pub use mc_fog_kex_rng::{KexRngPubkey, StoredRng};
 
 /// The length of the ciphertext in the FixedTxOutSearchResult.
 pub const FIXED_CIPHERTEXT_LENGTH: usize = 255;
 
+/// When a QueryRequest sets pad_response, the enclave pads the encrypted
+/// QueryResponse it sends to the client up to a multiple of this many bytes,
+/// so that its size only reveals roughly how many results and events it
+/// contains.
+pub const QUERY_RESPONSE_PADDING_BLOCK_SIZE: usize = 4096;
+
 // User <-> enclave proto schema types
 // These are synced with types in fog_api view.proto, and tests enforce that
 // they round trip These are NOT expected to be synced with Db schema types
@@ -38,6 +45,12 @@ pub struct QueryRequestAAD {
     // TODO this is currently unused
     #[prost(uint64, tag = "2")]
     pub start_from_block_index: u64,
+
+    /// Random bytes which identify the query across the client's retries, so
+    /// that the router can answer every attempt with the same results. Empty
+    /// if the query should not be deduplicated.
+    #[prost(bytes, tag = "3")]
+    pub idempotency_token: Vec<u8>,
 }
 
 /// The QueryRequest structure, which should be passed as the encrypted data
@@ -48,6 +61,81 @@ pub struct QueryRequest {
     /// These should all be values that came from KexRng's
     #[prost(bytes, repeated, tag = "1")]
     pub get_txos: Vec<Vec<u8>>,
+
+    /// KexRng's for the enclave to fast-forward and produce search keys from,
+    /// in addition to get_txos
+    #[prost(message, repeated, tag = "2")]
+    pub fast_forward_rngs: Vec<RngFastForward>,
+
+    /// Whether the enclave should pad the QueryResponse up to a multiple of
+    /// QUERY_RESPONSE_PADDING_BLOCK_SIZE, e.g. for a client which shapes its
+    /// traffic
+    #[prost(bool, tag = "3")]
+    pub pad_response: bool,
+}
+
+impl QueryRequest {
+    /// The search keys to query for: get_txos, followed by the outputs of each
+    /// of the fast_forward_rngs in turn.
+    pub fn search_keys(&self) -> Result<Vec<Vec<u8>>, RngFastForwardError> {
+        let num_fast_forward_keys = self
+            .fast_forward_rngs
+            .iter()
+            .map(|fast_forward| fast_forward.num_search_keys as u64)
+            .sum::<u64>();
+        if num_fast_forward_keys > MAX_FAST_FORWARD_SEARCH_KEYS {
+            return Err(RngFastForwardError::TooManySearchKeys(
+                num_fast_forward_keys,
+            ));
+        }
+
+        let mut search_keys = self.get_txos.clone();
+        for fast_forward in self.fast_forward_rngs.iter() {
+            let mut rng = VersionedKexRng::try_from(fast_forward.rng.clone())?;
+            rng.seek(fast_forward.start_index);
+            search_keys.extend(rng.take(fast_forward.num_search_keys as usize));
+        }
+        Ok(search_keys)
+    }
+}
+
+/// The most search keys that the fast_forward_rngs of one QueryRequest may
+/// produce.
+pub const MAX_FAST_FORWARD_SEARCH_KEYS: u64 = 1000;
+
+/// A KexRng which the enclave should fast-forward to start_index, and then
+/// search for the next num_search_keys outputs of.
+///
+/// This lets a client recovering from a backup of its rng skip straight to
+/// the outputs it hasn't seen, without computing the search keys itself.
+#[derive(Clone, Eq, PartialEq, Message)]
+pub struct RngFastForward {
+    /// The client's KexRng
+    #[prost(message, required, tag = "1")]
+    pub rng: StoredRng,
+
+    /// The index of the first output to search for
+    #[prost(uint64, tag = "2")]
+    pub start_index: u64,
+
+    /// The number of consecutive outputs to search for
+    #[prost(uint32, tag = "3")]
+    pub num_search_keys: u32,
+}
+
+/// An error that can occur when producing search keys from an RngFastForward
+#[derive(Clone, Debug, Display)]
+pub enum RngFastForwardError {
+    /// Invalid KexRng: {0}
+    KexRng(KexRngError),
+    /// {0} search keys requested, more than MAX_FAST_FORWARD_SEARCH_KEYS
+    TooManySearchKeys(u64),
+}
+
+impl From<KexRngError> for RngFastForwardError {
+    fn from(src: KexRngError) -> Self {
+        Self::KexRng(src)
+    }
 }
 
 /// The QueryResponse structure, returned by the enclave in response to an
@@ -314,10 +402,10 @@ impl From<&MaskedAmount> for TxOutAmountMaskedTokenId {
 
 /// This conversion must be constant time.
 impl From<FixedTxOutSearchResult> for TxOutSearchResult {
-    fn from(src: FixedTxOutSearchResult) -> Self {
+    fn from(mut src: FixedTxOutSearchResult) -> Self {
         // The ciphertext field's length will always be FIXED_CIPHERTEXT_LENGTH, so this
         // is constant time.
-        let mut ciphertext = src.ciphertext.clone();
+        let mut ciphertext = core::mem::take(&mut src.ciphertext);
         let mut padding = vec![0; FIXED_CIPHERTEXT_LENGTH];
 
         ciphertext.truncate(src.payload_length as usize);
@@ -325,7 +413,7 @@ impl From<FixedTxOutSearchResult> for TxOutSearchResult {
         padding.truncate(padding_length);
 
         TxOutSearchResult {
-            search_key: src.search_key,
+            search_key: core::mem::take(&mut src.search_key),
             result_code: src.result_code,
             ciphertext,
             padding,
@@ -542,12 +630,28 @@ impl FogTxOut {
     /// Or
     /// * An error if recovery failed
     pub fn try_recover_tx_out(&self, view_key: &RistrettoPrivate) -> Result<TxOut, FogTxOutError> {
-        // Reconstruct compressed commitment based on our view key.
-        // The first step is reconstructing the TxOut shared secret
         let public_key = RistrettoPublic::try_from(&self.public_key)?;
+        self.try_recover_tx_out_with_public_key(view_key, &public_key)
+    }
 
+    /// Try to recover a TxOut, like [FogTxOut::try_recover_tx_out], using an
+    /// already decompressed tx public key.
+    ///
+    /// This lets callers recovering many TxOuts decompress their public keys
+    /// as a batch, see `RistrettoPublic::decompress_batch`.
+    ///
+    /// Arguments:
+    /// * view_key: the private view key of the recipient of this TxOut
+    /// * public_key: the decompressed `public_key` of this FogTxOut
+    pub fn try_recover_tx_out_with_public_key(
+        &self,
+        view_key: &RistrettoPrivate,
+        public_key: &RistrettoPublic,
+    ) -> Result<TxOut, FogTxOutError> {
+        // Reconstruct compressed commitment based on our view key.
+        // The first step is reconstructing the TxOut shared secret
         let tx_out_shared_secret =
-            mc_transaction_core::get_tx_out_shared_secret(view_key, &public_key);
+            mc_transaction_core::get_tx_out_shared_secret(view_key, public_key);
 
         // Reconstruct the correct masked amount version, based on which of the oneof
         // proto field was present
@@ -682,11 +786,61 @@ mod view_tests {
 #[cfg(test)]
 mod tests {
     use crate::view::{
-        FixedTxOutSearchResult, TxOutSearchResult, TxOutSearchResultCode, FIXED_CIPHERTEXT_LENGTH,
+        FixedTxOutSearchResult, QueryRequest, RngFastForward, RngFastForwardError,
+        TxOutSearchResult, TxOutSearchResultCode, FIXED_CIPHERTEXT_LENGTH,
+        MAX_FAST_FORWARD_SEARCH_KEYS,
     };
     use alloc::{vec, vec::Vec};
+    use mc_crypto_keys::RistrettoPrivate;
+    use mc_fog_kex_rng::{KexRngPubkey, NewFromKex, VersionedKexRng};
     use yare::parameterized;
 
+    fn test_rng() -> VersionedKexRng {
+        let private_key = RistrettoPrivate::try_from(&[1u8; 32]).unwrap();
+        let pubkey = KexRngPubkey {
+            public_key: vec![2u8; 32],
+            version: 0,
+        };
+        VersionedKexRng::try_from_kex_pubkey(&pubkey, &private_key).unwrap()
+    }
+
+    #[test]
+    fn search_keys_fast_forwards_rngs() {
+        let rng = test_rng();
+        let req = QueryRequest {
+            get_txos: vec![vec![7u8; 16]],
+            fast_forward_rngs: vec![RngFastForward {
+                rng: rng.clone().into(),
+                start_index: 5,
+                num_search_keys: 3,
+            }],
+            pad_response: false,
+        };
+
+        let mut expected = vec![vec![7u8; 16]];
+        expected.extend(rng.skip(5).take(3));
+        assert_eq!(req.search_keys().unwrap(), expected);
+    }
+
+    #[test]
+    fn search_keys_limits_fast_forwards() {
+        let fast_forward = RngFastForward {
+            rng: test_rng().into(),
+            start_index: 0,
+            num_search_keys: MAX_FAST_FORWARD_SEARCH_KEYS as u32 / 2 + 1,
+        };
+        let req = QueryRequest {
+            get_txos: vec![],
+            fast_forward_rngs: vec![fast_forward.clone(), fast_forward],
+            pad_response: false,
+        };
+
+        assert!(matches!(
+            req.search_keys(),
+            Err(RngFastForwardError::TooManySearchKeys(_))
+        ));
+    }
+
     #[parameterized(
     payload_length_is_0 = { 0 },
     payload_length_is_1 = { 1 },
@@ -88,6 +88,20 @@ impl UriScheme for FogIngestScheme {
     const DEFAULT_INSECURE_PORT: u16 = 3221;
 }
 
+/// Fog Overseer Uri Scheme
+#[derive(Debug, Hash, Ord, PartialOrd, Eq, PartialEq, Clone)]
+pub struct FogOverseerScheme {}
+
+impl UriScheme for FogOverseerScheme {
+    /// The part before the '://' of a URL.
+    const SCHEME_SECURE: &'static str = "fog-overseer";
+    const SCHEME_INSECURE: &'static str = "insecure-fog-overseer";
+
+    /// Default port numbers
+    const DEFAULT_SECURE_PORT: u16 = 443;
+    const DEFAULT_INSECURE_PORT: u16 = 4267;
+}
+
 /// Ingest Peer Uri Scheme
 #[derive(Debug, Hash, Ord, PartialOrd, Eq, PartialEq, Clone)]
 pub struct IngestPeerScheme {}
@@ -118,6 +132,8 @@ pub type FogViewStoreUri = Uri<FogViewStoreScheme>;
 /// Uri used when talking to fog-view service, with the right default ports and
 /// scheme.
 pub type FogViewUri = Uri<FogViewScheme>;
+/// Uri used when talking to the fog-overseer status service.
+pub type FogOverseerUri = Uri<FogOverseerScheme>;
 /// Uri used when talking to fog-ingest-peer service.
 pub type IngestPeerUri = Uri<IngestPeerScheme>;
 
@@ -210,6 +226,24 @@ mod tests {
         assert!(!uri.use_tls());
     }
 
+    #[test]
+    fn test_ipv6_fog_ledger_uris() {
+        let uri = FogLedgerUri::from_str("insecure-fog-ledger://[::]:3228/").unwrap();
+        assert_eq!(uri.addr(), "[::]:3228");
+        assert!(uri.ip_addr().unwrap().is_unspecified());
+
+        let uri = KeyImageStoreUri::from_str(
+            "insecure-key-image-store://[fd00::10]:3228/?responder-id=store1.svc.cluster.local:3228&sharding_strategy=0-100",
+        )
+        .unwrap();
+        assert_eq!(uri.addr(), "[fd00::10]:3228");
+        assert_eq!(
+            uri.responder_id().unwrap(),
+            ResponderId::from_str("store1.svc.cluster.local:3228").unwrap()
+        );
+        assert_eq!(uri.subdomain(), None);
+    }
+
     #[test]
     fn test_invalid_fog_ledger_uris() {
         assert!(FogLedgerUri::from_str("http://127.0.0.1/").is_err());
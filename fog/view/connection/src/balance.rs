@@ -0,0 +1,353 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! A one-shot balance check against fog view and fog ledger.
+//!
+//! [get_balance] finds an account's TxOuts using fog view, confirms they
+//! belong to the account by view-key matching them, and asks fog ledger which
+//! of their key images have been spent. Clients which keep state between
+//! balance checks, like the sample paykit, should track TxOuts themselves
+//! instead of calling this repeatedly.
+
+use displaydoc::Display;
+use mc_account_keys::{
+    AccountKey, CHANGE_SUBADDRESS_INDEX, DEFAULT_SUBADDRESS_INDEX, GIFT_CODE_SUBADDRESS_INDEX,
+};
+use mc_blockchain_types::BlockIndex;
+use mc_crypto_keys::RistrettoPublic;
+use mc_fog_ledger_connection::{
+    Error as LedgerConnectionError, FogKeyImageGrpcClient, KeyImageQueryError,
+    KeyImageResultExtension,
+};
+use mc_fog_types::{
+    common::BlockRange,
+    ledger::CheckKeyImagesResponse,
+    view::{FogTxOut, TxOutRecord},
+};
+use mc_fog_view_protocol::{FogViewConnection, TxOutPollingError, UserPrivate, UserRngSet};
+use mc_transaction_core::{
+    get_tx_out_shared_secret,
+    onetime_keys::{recover_onetime_private_key, recover_public_subaddress_spend_key},
+    ring_signature::KeyImage,
+    Amount, TokenId,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{Debug, Display},
+};
+
+/// Maximum number of key images we ask fog ledger about in a single query.
+const MAX_KEY_IMAGES_PER_QUERY: usize = 100;
+
+/// Something which can check key images against fog ledger.
+///
+/// This is implemented for [FogKeyImageGrpcClient], and lets [get_balance] be
+/// used with other transports.
+pub trait KeyImageChecker {
+    /// The error type returned by this checker
+    type Error: Debug + Display;
+
+    /// Check whether the given key images have been spent
+    fn check_key_images(
+        &mut self,
+        key_images: &[KeyImage],
+    ) -> Result<CheckKeyImagesResponse, Self::Error>;
+}
+
+impl KeyImageChecker for FogKeyImageGrpcClient {
+    type Error = LedgerConnectionError;
+
+    fn check_key_images(
+        &mut self,
+        key_images: &[KeyImage],
+    ) -> Result<CheckKeyImagesResponse, Self::Error> {
+        FogKeyImageGrpcClient::check_key_images(self, key_images)
+    }
+}
+
+/// The result of a balance check.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Balance {
+    /// The unspent value of each token held by the account. Tokens with a
+    /// zero balance are omitted.
+    pub balances: BTreeMap<TokenId, u64>,
+
+    /// The number of blocks the balance is consistent with. This is the
+    /// smaller of the block counts fog view and fog ledger had processed.
+    pub num_blocks: u64,
+
+    /// Block ranges which fog ingest reported as missed. TxOuts in these
+    /// blocks are not found by fog view, so they are not part of the balance.
+    pub missed_block_ranges: Vec<BlockRange>,
+
+    /// The number of TxOuts returned by fog view which could not be matched
+    /// against the account key.
+    pub num_unmatched_tx_outs: usize,
+}
+
+/// An error that can occur when checking a balance
+#[derive(Debug, Display)]
+pub enum BalanceError<ViewError: Debug + Display, LedgerError: Debug + Display> {
+    /// Fog view: {0}
+    View(TxOutPollingError<ViewError>),
+    /// Fog ledger: {0}
+    Ledger(LedgerError),
+    /// Fog ledger could not check a key image: {0}
+    KeyImage(KeyImageQueryError),
+    /// Fog ledger did not return a result for one of our key images
+    MissingKeyImageResult,
+    /// The balance of token id {0} overflows a u64
+    BalanceOverflow(TokenId),
+}
+
+/// A TxOut which view-key matched against the account.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct MatchedTxOut {
+    block_index: BlockIndex,
+    amount: Amount,
+    key_image: KeyImage,
+}
+
+/// Check the balance of an account.
+///
+/// All of the account's TxOuts are retrieved from fog view, starting from the
+/// beginning of the chain, and all of their key images are checked against
+/// fog ledger. TxOuts are looked for at the default, change and gift code
+/// subaddresses.
+///
+/// Fog view and fog ledger may have processed different numbers of blocks.
+/// The returned balance is computed as of the smaller of the two, so that it
+/// reflects a single point in the history of the chain: TxOuts which appeared
+/// after that point are left out, and TxOuts which were spent after that point
+/// are still counted.
+///
+/// Arguments:
+/// * view_client: A connection to fog view
+/// * key_image_client: A connection to fog ledger
+/// * account_key: The account whose balance to check
+pub fn get_balance<V: FogViewConnection, K: KeyImageChecker>(
+    view_client: &mut V,
+    key_image_client: &mut K,
+    account_key: &AccountKey,
+) -> Result<Balance, BalanceError<V::Error, K::Error>> {
+    let mut rng_set = UserRngSet::new();
+    let (records, missed_block_ranges, mut errors) =
+        view_client.poll(&mut rng_set, &UserPrivate::from(account_key));
+    if !errors.is_empty() {
+        return Err(BalanceError::View(errors.swap_remove(0)));
+    }
+    let view_num_blocks = u64::from(rng_set.get_highest_processed_block_count());
+
+    let spsk_to_index = [
+        DEFAULT_SUBADDRESS_INDEX,
+        CHANGE_SUBADDRESS_INDEX,
+        GIFT_CODE_SUBADDRESS_INDEX,
+    ]
+    .into_iter()
+    .map(|index| (*account_key.subaddress(index).spend_public_key(), index))
+    .collect::<HashMap<_, _>>();
+
+    let num_records = records.len();
+    let tx_outs = match_tx_out_records(records, account_key, &spsk_to_index);
+    let num_unmatched_tx_outs = num_records - tx_outs.len();
+
+    let mut ledger_num_blocks = u64::MAX;
+    let mut spent_at = HashMap::<KeyImage, Option<BlockIndex>>::new();
+    let key_images = tx_outs
+        .iter()
+        .map(|tx_out| tx_out.key_image)
+        .collect::<Vec<_>>();
+    for key_images in key_images.chunks(MAX_KEY_IMAGES_PER_QUERY) {
+        let response = key_image_client
+            .check_key_images(key_images)
+            .map_err(BalanceError::Ledger)?;
+        ledger_num_blocks = ledger_num_blocks.min(response.num_blocks);
+        for result in response.results.iter() {
+            let status = result.status().map_err(BalanceError::KeyImage)?;
+            spent_at.insert(result.key_image, status);
+        }
+    }
+
+    // When we have no key images to ask about, fog ledger places no limit on
+    // the point at which the balance is consistent.
+    let num_blocks = view_num_blocks.min(ledger_num_blocks);
+    let balances = tally_balance(&tx_outs, &spent_at, num_blocks)?;
+
+    Ok(Balance {
+        balances,
+        num_blocks,
+        missed_block_ranges,
+        num_unmatched_tx_outs,
+    })
+}
+
+/// View-key match TxOut records against the account, computing their amounts
+/// and key images. Records which do not belong to one of the subaddresses in
+/// `spsk_to_index` are left out.
+fn match_tx_out_records(
+    records: Vec<TxOutRecord>,
+    account_key: &AccountKey,
+    spsk_to_index: &HashMap<RistrettoPublic, u64>,
+) -> Vec<MatchedTxOut> {
+    let fog_tx_outs = records
+        .into_iter()
+        .filter_map(|record| Some((record.block_index, record.get_fog_tx_out().ok()?)))
+        .collect::<Vec<_>>();
+
+    // Decompressing the keys of every TxOut accounts for much of the cost of
+    // matching, so do it for all of them at once.
+    let public_keys = RistrettoPublic::decompress_batch(
+        &fog_tx_outs
+            .iter()
+            .map(|(_, fog_tx_out)| fog_tx_out.public_key)
+            .collect::<Vec<_>>(),
+    );
+    let target_keys = RistrettoPublic::decompress_batch(
+        &fog_tx_outs
+            .iter()
+            .map(|(_, fog_tx_out)| fog_tx_out.target_key)
+            .collect::<Vec<_>>(),
+    );
+
+    fog_tx_outs
+        .iter()
+        .zip(public_keys.into_iter().zip(target_keys))
+        .filter_map(|((block_index, fog_tx_out), (public_key, target_key))| {
+            match_fog_tx_out(
+                *block_index,
+                fog_tx_out,
+                &public_key.ok()?,
+                &target_key.ok()?,
+                account_key,
+                spsk_to_index,
+            )
+        })
+        .collect()
+}
+
+/// View-key match a single TxOut against the account. Returns None if the
+/// TxOut does not belong to one of the subaddresses in `spsk_to_index`.
+fn match_fog_tx_out(
+    block_index: BlockIndex,
+    fog_tx_out: &FogTxOut,
+    tx_public_key: &RistrettoPublic,
+    tx_target_key: &RistrettoPublic,
+    account_key: &AccountKey,
+    spsk_to_index: &HashMap<RistrettoPublic, u64>,
+) -> Option<MatchedTxOut> {
+    let tx_out = fog_tx_out
+        .try_recover_tx_out_with_public_key(account_key.view_private_key(), tx_public_key)
+        .ok()?;
+
+    let shared_secret = get_tx_out_shared_secret(account_key.view_private_key(), tx_public_key);
+    let (amount, _blinding) = tx_out
+        .get_masked_amount()
+        .ok()?
+        .get_value(&shared_secret)
+        .ok()?;
+
+    let subaddress_spk = recover_public_subaddress_spend_key(
+        account_key.view_private_key(),
+        tx_target_key,
+        tx_public_key,
+    );
+    let subaddress_index = spsk_to_index.get(&subaddress_spk)?;
+    let onetime_private_key = recover_onetime_private_key(
+        tx_public_key,
+        account_key.view_private_key(),
+        &account_key.subaddress_spend_private(*subaddress_index),
+    );
+
+    Some(MatchedTxOut {
+        block_index,
+        amount,
+        key_image: KeyImage::from(&onetime_private_key),
+    })
+}
+
+/// Sum the value of the TxOuts which are unspent as of `num_blocks`.
+fn tally_balance<V: Debug + Display, L: Debug + Display>(
+    tx_outs: &[MatchedTxOut],
+    spent_at: &HashMap<KeyImage, Option<BlockIndex>>,
+    num_blocks: u64,
+) -> Result<BTreeMap<TokenId, u64>, BalanceError<V, L>> {
+    let mut balances = BTreeMap::<TokenId, u64>::new();
+    for tx_out in tx_outs {
+        if tx_out.block_index >= num_blocks {
+            continue;
+        }
+        let status = spent_at
+            .get(&tx_out.key_image)
+            .ok_or(BalanceError::MissingKeyImageResult)?;
+        if matches!(status, Some(spent_block) if *spent_block < num_blocks) {
+            continue;
+        }
+        let balance = balances.entry(tx_out.amount.token_id).or_default();
+        *balance = balance
+            .checked_add(tx_out.amount.value)
+            .ok_or(BalanceError::BalanceOverflow(tx_out.amount.token_id))?;
+    }
+    balances.retain(|_, value| *value > 0);
+    Ok(balances)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestError = BalanceError<String, String>;
+
+    fn tx_out(block_index: BlockIndex, value: u64, token_id: u64) -> MatchedTxOut {
+        MatchedTxOut {
+            block_index,
+            amount: Amount::new(value, TokenId::from(token_id)),
+            key_image: KeyImage::from(block_index * 1000 + value),
+        }
+    }
+
+    #[test]
+    fn tally_balance_is_consistent_at_num_blocks() {
+        let tx_outs = vec![
+            // Unspent
+            tx_out(1, 10, 0),
+            // Spent before num_blocks
+            tx_out(2, 20, 0),
+            // Spent after num_blocks, so still counted
+            tx_out(3, 30, 0),
+            // Appeared after num_blocks
+            tx_out(8, 40, 0),
+            // Another token
+            tx_out(4, 5, 1),
+        ];
+        let spent_at = HashMap::from([
+            (tx_outs[0].key_image, None),
+            (tx_outs[1].key_image, Some(4)),
+            (tx_outs[2].key_image, Some(7)),
+            (tx_outs[3].key_image, None),
+            (tx_outs[4].key_image, Some(5)),
+        ]);
+
+        let balances = tally_balance::<String, String>(&tx_outs, &spent_at, 6).unwrap();
+        assert_eq!(balances, BTreeMap::from([(TokenId::from(0), 40)]));
+
+        let balances = tally_balance::<String, String>(&tx_outs, &spent_at, 10).unwrap();
+        assert_eq!(balances, BTreeMap::from([(TokenId::from(0), 50)]));
+    }
+
+    #[test]
+    fn tally_balance_requires_every_key_image_result() {
+        let tx_outs = vec![tx_out(1, 10, 0)];
+        let result: Result<_, TestError> = tally_balance(&tx_outs, &HashMap::new(), 5);
+        assert!(matches!(result, Err(BalanceError::MissingKeyImageResult)));
+    }
+
+    #[test]
+    fn tally_balance_rejects_overflowing_balances() {
+        let tx_outs = vec![tx_out(0, u64::MAX, 0), tx_out(1, 1, 0)];
+        let spent_at = HashMap::from_iter(tx_outs.iter().map(|tx_out| (tx_out.key_image, None)));
+        let result: Result<_, TestError> = tally_balance(&tx_outs, &spent_at, 5);
+        assert!(matches!(
+            result,
+            Err(BalanceError::BalanceOverflow(token_id)) if token_id == TokenId::from(0)
+        ));
+    }
+}
@@ -0,0 +1,66 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Timer-driven cover traffic for a constant-rate [FogViewGrpcClient].
+//!
+//! Constant-rate shaping only hides when a wallet is active if the idle slots
+//! are filled too. [CoverTrafficSender] does that from a background thread,
+//! so that clients don't need to call
+//! [FogViewGrpcClient::send_cover_request] themselves.
+
+use crate::FogViewGrpcClient;
+use mc_common::logger::{log, Logger};
+use std::{
+    sync::{
+        mpsc::{channel, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    thread::{Builder as ThreadBuilder, JoinHandle},
+    time::Duration,
+};
+
+/// Sends a cover request on a fog view client about once per interval, for as
+/// long as it is alive. Real requests made through the same client in the
+/// meantime take the slot instead, so cover requests only fill idle slots.
+pub struct CoverTrafficSender {
+    /// Dropping this stops the thread.
+    stop_sender: Option<Sender<()>>,
+    /// The thread sending cover requests.
+    join_handle: Option<JoinHandle<()>>,
+}
+
+impl CoverTrafficSender {
+    /// Start sending cover requests on `client` every `interval`, which should
+    /// be the interval of its
+    /// [ConstantRateConfig](mc_fog_enclave_connection::ConstantRateConfig).
+    pub fn new(client: Arc<Mutex<FogViewGrpcClient>>, interval: Duration, logger: Logger) -> Self {
+        let (stop_sender, stop_receiver) = channel::<()>();
+        let join_handle = ThreadBuilder::new()
+            .name("FogViewCoverTraffic".to_owned())
+            .spawn(move || {
+                while let Err(RecvTimeoutError::Timeout) = stop_receiver.recv_timeout(interval) {
+                    let Ok(mut client) = client.lock() else {
+                        log::error!(logger, "Fog view client mutex poisoned, stopping");
+                        break;
+                    };
+                    if let Err(err) = client.send_cover_request() {
+                        log::debug!(logger, "Cover request failed: {}", err);
+                    }
+                }
+            })
+            .expect("Could not spawn cover traffic thread");
+
+        Self {
+            stop_sender: Some(stop_sender),
+            join_handle: Some(join_handle),
+        }
+    }
+}
+
+impl Drop for CoverTrafficSender {
+    fn drop(&mut self) {
+        self.stop_sender.take();
+        if let Some(join_handle) = self.join_handle.take() {
+            join_handle.join().expect("Cover traffic thread panicked");
+        }
+    }
+}
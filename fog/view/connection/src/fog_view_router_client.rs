@@ -5,7 +5,10 @@
 use aes_gcm::Aes256Gcm;
 use der::DateTime;
 use futures::{executor::block_on, SinkExt, TryStreamExt};
-use grpcio::{ChannelBuilder, ClientDuplexReceiver, ClientDuplexSender, Environment};
+use grpcio::{
+    CallOption, ChannelBuilder, ClientDuplexReceiver, ClientDuplexSender, Environment,
+    MetadataBuilder,
+};
 use mc_attest_ake::{
     AuthResponseInput, ClientInitiate, Error as AttestAkeError, Ready, Start, Transition,
 };
@@ -16,20 +19,22 @@ use mc_common::{
     logger::{log, o, Logger},
     time::{SystemTimeProvider, TimeProvider},
 };
-use mc_crypto_keys::X25519;
+use mc_crypto_keys::{CompressedRistrettoPublic, X25519};
 use mc_crypto_noise::CipherError;
 use mc_fog_api::{
+    ingress_keys::{encode_ingress_keys, INGRESS_KEYS_HEADER},
     view::{FogViewRouterRequest, FogViewRouterResponse},
     view_grpc::FogViewRouterApiClient,
 };
-use mc_fog_types::view::{QueryRequest, QueryRequestAAD, QueryResponse};
+use mc_fog_types::view::{QueryRequest, QueryRequestAAD, QueryResponse, RngFastForward};
 use mc_fog_uri::{ConnectionUri, FogViewRouterUri};
+use mc_fog_view_protocol::FogViewConnection;
 use mc_rand::McRng;
-use mc_util_grpc::ConnectionUriGrpcioChannel;
+use mc_util_grpc::{ConnectionUriGrpcioChannel, MessageTooLarge};
 use mc_util_serial::DecodeError;
 use mc_util_uri::UriConversionError;
 use sha2::Sha512;
-use std::sync::Arc;
+use std::{fmt::Display, sync::Arc};
 
 /// A high-level object mediating requests to the fog view router service
 pub struct FogViewRouterGrpcClient {
@@ -87,6 +92,32 @@ impl FogViewRouterGrpcClient {
         }
     }
 
+    /// Tell the fog view router which ingress keys the user's fog reports were
+    /// issued under, so that it only queries the view stores serving them.
+    /// This is needed when the user's wallet provider shares fog with others.
+    ///
+    /// This reopens the streaming connection, since the ingress keys are sent
+    /// as a header when the stream is opened.
+    pub fn with_ingress_keys<'a>(
+        mut self,
+        ingress_keys: impl IntoIterator<Item = &'a CompressedRistrettoPublic>,
+    ) -> Self {
+        let mut headers = MetadataBuilder::new();
+        headers
+            .add_str(INGRESS_KEYS_HEADER, &encode_ingress_keys(ingress_keys))
+            .expect("Could not add ingress keys header");
+        let call_option = CallOption::default().headers(headers.build());
+        let (request_sender, response_receiver) = self
+            ._fog_view_router_client
+            .request_opt(call_option)
+            .expect("Could not retrieve grpc sender and receiver.");
+
+        self.deattest();
+        self.request_sender = request_sender;
+        self.response_receiver = response_receiver;
+        self
+    }
+
     fn is_attested(&self) -> bool {
         self.attest_cipher.is_some()
     }
@@ -146,6 +177,47 @@ impl FogViewRouterGrpcClient {
         start_from_user_event_id: i64,
         start_from_block_index: u64,
         search_keys: Vec<Vec<u8>>,
+    ) -> Result<QueryResponse, Error> {
+        let plaintext_request = QueryRequest {
+            get_txos: search_keys,
+            fast_forward_rngs: Vec::new(),
+            pad_response: false,
+        };
+        self.query_request(
+            start_from_user_event_id,
+            start_from_block_index,
+            plaintext_request,
+        )
+        .await
+    }
+
+    /// Queries for the outputs of `fast_forward_rngs`, which the enclave
+    /// produces from each rng's start_index on, so that a client restoring
+    /// its rngs from a backup doesn't have to compute or send them.
+    pub async fn query_fast_forward(
+        &mut self,
+        start_from_user_event_id: i64,
+        start_from_block_index: u64,
+        fast_forward_rngs: Vec<RngFastForward>,
+    ) -> Result<QueryResponse, Error> {
+        let plaintext_request = QueryRequest {
+            get_txos: Vec::new(),
+            fast_forward_rngs,
+            pad_response: false,
+        };
+        self.query_request(
+            start_from_user_event_id,
+            start_from_block_index,
+            plaintext_request,
+        )
+        .await
+    }
+
+    async fn query_request(
+        &mut self,
+        start_from_user_event_id: i64,
+        start_from_block_index: u64,
+        plaintext_request: QueryRequest,
     ) -> Result<QueryResponse, Error> {
         log::trace!(self.logger, "Query was called");
         if !self.is_attested() {
@@ -153,13 +225,10 @@ impl FogViewRouterGrpcClient {
             verification_report?;
         }
 
-        let plaintext_request = QueryRequest {
-            get_txos: search_keys,
-        };
-
         let req_aad = QueryRequestAAD {
             start_from_user_event_id,
             start_from_block_index,
+            idempotency_token: crate::new_idempotency_token(),
         };
 
         let aad = mc_util_serial::encode(&req_aad);
@@ -207,6 +276,31 @@ impl FogViewRouterGrpcClient {
     }
 }
 
+/// Requests are made over the client's streaming connection, so a poll of many
+/// accounts with [FogViewConnection::poll_accounts] uses one attested session.
+impl FogViewConnection for FogViewRouterGrpcClient {
+    type Error = Error;
+
+    fn request(
+        &mut self,
+        start_from_user_event_id: i64,
+        start_from_block_index: u64,
+        search_keys: Vec<Vec<u8>>,
+    ) -> Result<QueryResponse, Self::Error> {
+        let result = block_on(self.query(
+            start_from_user_event_id,
+            start_from_block_index,
+            search_keys,
+        ));
+        // A failed attestation or a broken cipher state can't be reused, so
+        // attest again on the next request.
+        if result.is_err() {
+            self.deattest();
+        }
+        result
+    }
+}
+
 impl Drop for FogViewRouterGrpcClient {
     fn drop(&mut self) {
         block_on(self.request_sender.close()).expect("Couldn't close the router request sender");
@@ -231,6 +325,9 @@ pub enum Error {
     /// Grpc errors.
     Grpc(grpcio::Error),
 
+    /// A request or response exceeded a grpc message size limit.
+    MessageTooLarge(MessageTooLarge),
+
     /// Response not received
     ResponseNotReceived,
 
@@ -238,6 +335,21 @@ pub enum Error {
     Other(String),
 }
 
+impl Display for Error {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Decode(err) => write!(formatter, "Decode error: {err}"),
+            Self::UriConversion(err) => write!(formatter, "Uri conversion error: {err}"),
+            Self::Cipher(err) => write!(formatter, "Cipher error: {err}"),
+            Self::Attestation(err) => write!(formatter, "Attestation error: {err}"),
+            Self::Grpc(err) => write!(formatter, "Grpc error: {err}"),
+            Self::MessageTooLarge(err) => write!(formatter, "Message too large: {err}"),
+            Self::ResponseNotReceived => write!(formatter, "Response not received"),
+            Self::Other(msg) => write!(formatter, "Other: {msg}"),
+        }
+    }
+}
+
 impl From<DecodeError> for Error {
     fn from(err: DecodeError) -> Self {
         Self::Decode(err)
@@ -252,7 +364,10 @@ impl From<CipherError> for Error {
 
 impl From<grpcio::Error> for Error {
     fn from(err: grpcio::Error) -> Self {
-        Self::Grpc(err)
+        match MessageTooLarge::from_grpc_error(&err) {
+            Some(too_large) => Self::MessageTooLarge(too_large),
+            None => Self::Grpc(err),
+        }
     }
 }
 
@@ -7,21 +7,37 @@
 
 pub mod fog_view_router_client;
 
+mod balance;
+mod cover_traffic;
+pub use balance::{get_balance, Balance, BalanceError, KeyImageChecker};
+pub use cover_traffic::CoverTrafficSender;
+
 use grpcio::{ChannelBuilder, Environment};
 use mc_attestation_verifier::TrustedIdentity;
 use mc_common::{
     logger::{log, o, Logger},
     trace_time,
 };
-use mc_fog_api::view_grpc;
-use mc_fog_enclave_connection::{EnclaveConnection, Error as EnclaveConnectionError};
+use mc_crypto_keys::CompressedRistrettoPublic;
+use mc_fog_api::{
+    ingress_keys::{encode_ingress_keys, INGRESS_KEYS_HEADER},
+    view_grpc,
+};
+use mc_fog_enclave_connection::{
+    ConstantRateConfig, EnclaveConnection, Error as EnclaveConnectionError,
+};
 use mc_fog_types::view::{QueryRequest, QueryRequestAAD, QueryResponse};
 use mc_fog_uri::FogViewUri;
 use mc_fog_view_protocol::FogViewConnection;
+use mc_rand::{McRng, RngCore};
 use mc_util_grpc::{ConnectionUriGrpcioChannel, GrpcRetryConfig};
 use mc_util_telemetry::{tracer, Tracer};
 use retry::Error as RetryError;
-use std::{fmt::Display, sync::Arc};
+use std::{
+    fmt::Display,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 /// A high-level object mediating requests to the fog view service
 pub struct FogViewGrpcClient {
@@ -31,6 +47,11 @@ pub struct FogViewGrpcClient {
     grpc_retry_config: GrpcRetryConfig,
     /// The uri we connected to
     uri: FogViewUri,
+    /// The aad and number of search keys of the last request, which cover
+    /// requests imitate
+    last_request_shape: Option<(QueryRequestAAD, usize)>,
+    /// Whether to ask the enclave to pad its responses
+    pad_responses: bool,
     /// A logger object
     logger: Logger,
 }
@@ -69,9 +90,83 @@ impl FogViewGrpcClient {
             ),
             grpc_retry_config,
             uri,
+            last_request_shape: None,
+            pad_responses: false,
             logger,
         }
     }
+
+    /// Send requests at a constant rate, padded to a fixed size, and ask the
+    /// enclave to pad its responses. Callers should also fill idle slots with
+    /// cover requests, e.g. with a [CoverTrafficSender].
+    pub fn with_constant_rate(mut self, config: ConstantRateConfig) -> Self {
+        self.conn = self.conn.with_constant_rate(config);
+        self.pad_responses = true;
+        self
+    }
+
+    /// Tell a fog view router which ingress keys the user's fog reports were
+    /// issued under, so that it only queries the view stores serving them.
+    /// This is needed when the user's wallet provider shares fog with others.
+    pub fn with_ingress_keys<'a>(
+        mut self,
+        ingress_keys: impl IntoIterator<Item = &'a CompressedRistrettoPublic>,
+    ) -> Self {
+        self.conn = self
+            .conn
+            .with_header(INGRESS_KEYS_HEADER, encode_ingress_keys(ingress_keys));
+        self
+    }
+
+    /// If the current constant-rate slot is idle, fill it with a query for as
+    /// many random search keys as the last real request had, which the server
+    /// will not find. Returns whether a cover request was sent.
+    pub fn send_cover_request(&mut self) -> Result<bool, Error> {
+        let Some((mut req_aad, num_search_keys)) = self.last_request_shape.clone() else {
+            return Ok(false);
+        };
+        // A cover request must not be answered as a retry of the real one.
+        req_aad.idempotency_token = new_idempotency_token();
+
+        let mut rng = McRng;
+        let get_txos = (0..num_search_keys)
+            .map(|_| {
+                let mut search_key = vec![0u8; SEARCH_KEY_LEN];
+                rng.fill_bytes(&mut search_key);
+                search_key
+            })
+            .collect();
+        let req = QueryRequest {
+            get_txos,
+            fast_forward_rngs: Vec::new(),
+            pad_response: self.pad_responses,
+        };
+        let aad_bytes = mc_util_serial::encode(&req_aad);
+
+        self.conn
+            .encrypted_cover_request::<_, QueryResponse>(&req, &aad_bytes)
+            .map_err(|error| Error {
+                uri: self.uri.clone(),
+                error: RetryError {
+                    error,
+                    total_delay: Duration::ZERO,
+                    tries: 1,
+                },
+            })
+    }
+}
+
+/// The length of a fog view search key
+const SEARCH_KEY_LEN: usize = 16;
+
+/// The length of the idempotency token of a fog view query
+const IDEMPOTENCY_TOKEN_LEN: usize = 32;
+
+/// A random token identifying a fog view query across its retries
+pub fn new_idempotency_token() -> Vec<u8> {
+    let mut token = vec![0u8; IDEMPOTENCY_TOKEN_LEN];
+    McRng.fill_bytes(&mut token);
+    token
 }
 
 impl FogViewConnection for FogViewGrpcClient {
@@ -94,22 +189,30 @@ impl FogViewConnection for FogViewGrpcClient {
                 search_keys.len()
             );
 
+            let num_search_keys = search_keys.len();
             let req = QueryRequest {
                 get_txos: search_keys,
+                fast_forward_rngs: Vec::new(),
+                pad_response: self.pad_responses,
             };
 
+            // Every attempt of the request carries the same token, so that a
+            // router answers retries with the results of the first attempt.
             let req_aad = QueryRequestAAD {
                 start_from_user_event_id,
                 start_from_block_index,
+                idempotency_token: new_idempotency_token(),
             };
+            self.last_request_shape = Some((req_aad.clone(), num_search_keys));
 
             let aad_bytes = mc_util_serial::encode(&req_aad);
 
             let retry_config = self.grpc_retry_config;
+            let started_at = Instant::now();
             retry_config
-                .retry(|| {
+                .retry_with_index(|attempt| {
                     self.conn
-                        .retriable_encrypted_enclave_request(&req, &aad_bytes)
+                        .retriable_encrypted_enclave_request(&req, &aad_bytes, attempt, started_at)
                 })
                 .map_err(|error| Error {
                     uri: self.uri.clone(),
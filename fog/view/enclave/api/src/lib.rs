@@ -85,6 +85,8 @@ pub enum ViewEnclaveRequest {
     QueryStore(EnclaveMessage<NonceSession>, UntrustedQueryResponse),
     /// Request from untrusted to add encrypted tx out records to ORAM
     AddRecords(Vec<ETxOutRecord>),
+    /// Get statistics about the ORAM holding the tx out records
+    GetOramStats,
     /// Takes a client query message and returns a SealedClientMessage
     /// sealed for the current enclave.
     DecryptAndSealQuery(EnclaveMessage<ClientSession>),
@@ -117,6 +119,31 @@ pub struct ViewEnclaveInitParams {
     pub desired_capacity: u64,
 }
 
+/// Statistics about the oblivious map which holds the tx out records.
+///
+/// These only describe how full the map is, and nothing about which records
+/// it holds, so they are safe to export from the enclave.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct OramStats {
+    /// The number of records in the map.
+    pub num_records: u64,
+    /// The capacity the map was created with. About 75% of this can be used.
+    pub capacity: u64,
+    /// The number of writes which failed because the map could not evict an
+    /// existing record to make room for the new one.
+    pub num_overflows: u64,
+}
+
+impl OramStats {
+    /// The percentage of the map's capacity which is in use.
+    pub fn fill_percent(&self) -> u64 {
+        if self.capacity == 0 {
+            return 0;
+        }
+        self.num_records * 100 / self.capacity
+    }
+}
+
 /// The API for the view enclave
 pub trait ViewEnclaveApi: ReportableEnclave {
     /// Perform one-time initialization upon enclave startup.
@@ -179,6 +206,10 @@ pub trait ViewEnclaveApi: ReportableEnclave {
     /// enclave's ORAM
     fn add_records(&self, records: Vec<ETxOutRecord>) -> Result<()>;
 
+    /// Get statistics about the view enclave's ORAM, so that the server can
+    /// warn about it filling up before it overflows
+    fn get_oram_stats(&self) -> Result<OramStats>;
+
     /// Decrypts a client query message and converts it into a
     /// SealedClientMessage which can be unsealed multiple times to
     /// construct the MultiViewStoreQuery.
@@ -260,6 +291,8 @@ pub enum Error {
     Cipher(CipherError),
     /// Fog View Shard query response collation error.
     QueryResponseCollation,
+    /// Invalid rng fast-forward in query request
+    RngFastForward,
 }
 
 impl From<SgxError> for Error {
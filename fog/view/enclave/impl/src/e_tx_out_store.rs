@@ -14,7 +14,7 @@ use aligned_cmov::{
 use alloc::boxed::Box;
 use mc_common::logger::Logger;
 use mc_fog_types::view::{FixedTxOutSearchResult, TxOutSearchResultCode, FIXED_CIPHERTEXT_LENGTH};
-use mc_fog_view_enclave_api::AddRecordsError;
+use mc_fog_view_enclave_api::{AddRecordsError, OramStats};
 use mc_oblivious_map::CuckooHashTableCreator;
 use mc_oblivious_ram::PathORAM4096Z4Creator;
 use mc_oblivious_traits::{
@@ -66,6 +66,9 @@ pub struct ETxOutStore<OSC: ORAMStorageCreator<StorageDataSize, StorageMetaSize>
     /// The size byte from the payload for the last ciphertext we stored in omap
     last_ciphertext_size_byte: u8,
 
+    /// The number of writes to omap which failed with OMAP_OVERFLOW
+    num_overflows: u64,
+
     /// The logger object
     #[allow(dead_code)]
     logger: Logger,
@@ -82,6 +85,7 @@ impl<OSC: ORAMStorageCreator<StorageDataSize, StorageMetaSize>> ETxOutStore<OSC>
                 desired_capacity, STASH_SIZE, McRng::default
             )),
             last_ciphertext_size_byte: 0,
+            num_overflows: 0,
             logger,
         }
     }
@@ -116,6 +120,7 @@ impl<OSC: ORAMStorageCreator<StorageDataSize, StorageMetaSize>> ETxOutStore<OSC>
         if omap_result_code == OMAP_INVALID_KEY {
             return Err(AddRecordsError::KeyRejected);
         } else if omap_result_code == OMAP_OVERFLOW {
+            self.num_overflows += 1;
             return Err(AddRecordsError::MapOverflow(
                 self.omap.len(),
                 self.omap.capacity(),
@@ -134,6 +139,14 @@ impl<OSC: ORAMStorageCreator<StorageDataSize, StorageMetaSize>> ETxOutStore<OSC>
         Ok(())
     }
 
+    pub fn stats(&self) -> OramStats {
+        OramStats {
+            num_records: self.omap.len(),
+            capacity: self.omap.capacity(),
+            num_overflows: self.num_overflows,
+        }
+    }
+
     pub fn find_record(&mut self, search_key: &[u8]) -> FixedTxOutSearchResult {
         let mut result = FixedTxOutSearchResult {
             search_key: search_key.to_vec(),
@@ -29,12 +29,12 @@ use mc_fog_recovery_db_iface::FogUserEvent;
 use mc_fog_types::{
     view::{
         FixedTxOutSearchResult, MultiViewStoreQueryResponse, QueryRequest, QueryResponse,
-        TxOutSearchResult,
+        TxOutSearchResult, QUERY_RESPONSE_PADDING_BLOCK_SIZE,
     },
     ETxOutRecord,
 };
 use mc_fog_view_enclave_api::{
-    Error, Result, UntrustedQueryResponse, ViewEnclaveApi, ViewEnclaveInitParams,
+    Error, OramStats, Result, UntrustedQueryResponse, ViewEnclaveApi, ViewEnclaveInitParams,
 };
 use mc_oblivious_traits::ORAMStorageCreator;
 use mc_sgx_compat::sync::Mutex;
@@ -67,16 +67,18 @@ where
         }
     }
 
+    fn decode_query_request(&self, plaintext_request: &[u8]) -> Result<QueryRequest> {
+        mc_util_serial::decode(plaintext_request).map_err(|e| {
+            log::error!(self.logger, "Could not decode user request: {}", e);
+            Error::ProstDecode
+        })
+    }
+
     fn query_impl(
         &self,
-        plaintext_request: &[u8],
+        req: &QueryRequest,
         untrusted_query_response: UntrustedQueryResponse,
     ) -> Result<Vec<u8>> {
-        let req: QueryRequest = mc_util_serial::decode(plaintext_request).map_err(|e| {
-            log::error!(self.logger, "Could not decode user request: {}", e);
-            Error::ProstDecode
-        })?;
-
         // Prepare the untrusted part of the response.
         let mut missed_block_ranges = Vec::new();
         let mut rng_records = Vec::new();
@@ -109,13 +111,14 @@ where
             fixed_tx_out_search_results: Default::default(),
         };
 
+        let search_keys = self.search_keys(req)?;
+
         // Do the txos part, scope lock of e_tx_out_store
         {
             let mut lk = self.e_tx_out_store.lock()?;
             let store = lk.as_mut().ok_or(Error::EnclaveNotInitialized)?;
 
-            resp.fixed_tx_out_search_results = req
-                .get_txos
+            resp.fixed_tx_out_search_results = search_keys
                 .iter()
                 .map(|key| store.find_record(&key[..]))
                 .collect();
@@ -124,6 +127,15 @@ where
         let response_plaintext_bytes = mc_util_serial::encode(&resp);
         Ok(response_plaintext_bytes)
     }
+
+    /// The search keys of a query request, including those produced by
+    /// fast-forwarding its rngs.
+    fn search_keys(&self, req: &QueryRequest) -> Result<Vec<Vec<u8>>> {
+        req.search_keys().map_err(|e| {
+            log::error!(self.logger, "Could not fast-forward user rngs: {}", e);
+            Error::RngFastForward
+        })
+    }
 }
 
 impl<OSC> ReportableEnclave for ViewEnclave<OSC>
@@ -192,8 +204,14 @@ where
     ) -> Result<Vec<u8>> {
         let channel_id = msg.channel_id.clone();
         let user_plaintext = self.ake.client_decrypt(msg)?;
-        let response_plaintext_bytes =
-            self.query_impl(&user_plaintext, untrusted_query_response)?;
+        let req = self.decode_query_request(&user_plaintext)?;
+        let mut response_plaintext_bytes = self.query_impl(&req, untrusted_query_response)?;
+        if req.pad_response {
+            mc_util_serial::pad_encoded(
+                &mut response_plaintext_bytes,
+                QUERY_RESPONSE_PADDING_BLOCK_SIZE,
+            );
+        }
         let response = self
             .ake
             .client_encrypt(&channel_id, &[], &response_plaintext_bytes)?;
@@ -208,8 +226,8 @@ where
     ) -> Result<EnclaveMessage<NonceSession>> {
         let channel_id = msg.channel_id.clone();
         let user_plaintext = self.ake.frontend_decrypt(msg)?;
-        let response_plaintext_bytes =
-            self.query_impl(&user_plaintext, untrusted_query_response)?;
+        let req = self.decode_query_request(&user_plaintext)?;
+        let response_plaintext_bytes = self.query_impl(&req, untrusted_query_response)?;
         let response = self
             .ake
             .frontend_encrypt(&channel_id, &[], &response_plaintext_bytes)?;
@@ -227,6 +245,12 @@ where
         Ok(())
     }
 
+    fn get_oram_stats(&self) -> Result<OramStats> {
+        let lk = self.e_tx_out_store.lock()?;
+        let store = lk.as_ref().ok_or(Error::EnclaveNotInitialized)?;
+        Ok(store.stats())
+    }
+
     /// Decrypts a client query message and converts it into a
     /// SealedClientMessage which can be unsealed multiple times to
     /// construct the MultiViewStoreQuery.
@@ -282,9 +306,16 @@ where
                 Error::ProstDecode
             })?;
 
+        let client_search_keys = self.search_keys(&client_query_request)?;
         let client_query_response =
-            self.create_client_query_response(client_query_request, shard_query_responses)?;
-        let response_plaintext_bytes = mc_util_serial::encode(&client_query_response);
+            self.create_client_query_response(client_search_keys, shard_query_responses)?;
+        let mut response_plaintext_bytes = mc_util_serial::encode(&client_query_response);
+        if client_query_request.pad_response {
+            mc_util_serial::pad_encoded(
+                &mut response_plaintext_bytes,
+                QUERY_RESPONSE_PADDING_BLOCK_SIZE,
+            );
+        }
         let response =
             self.ake
                 .client_encrypt(&channel_id, &sealed_query.aad, &response_plaintext_bytes)?;
@@ -299,7 +330,7 @@ where
 {
     fn create_client_query_response(
         &self,
-        client_query_request: QueryRequest,
+        client_search_keys: Vec<Vec<u8>>,
         shard_query_responses: Vec<MultiViewStoreQueryResponse>,
     ) -> Result<QueryResponse> {
         let shard_query_responses = shard_query_responses
@@ -318,11 +349,11 @@ where
             })
             .collect::<Result<Vec<_>>>()?;
 
-        Self::create_response(client_query_request, shard_query_responses)
+        Self::create_response(client_search_keys, shard_query_responses)
     }
 
     fn create_response(
-        client_query_request: QueryRequest,
+        client_search_keys: Vec<Vec<u8>>,
         mut responses: Vec<DecryptedMultiViewStoreQueryResponse>,
     ) -> Result<QueryResponse> {
         let mut result: QueryResponse = QueryResponse::default();
@@ -343,7 +374,7 @@ where
             block_data.highest_processed_block_signature_timestamp;
 
         result.fixed_tx_out_search_results =
-            Self::get_collated_tx_out_search_results(client_query_request, &responses)?;
+            Self::get_collated_tx_out_search_results(client_search_keys, &responses)?;
         result.tx_out_search_results = result
             .fixed_tx_out_search_results
             .iter()
@@ -355,7 +386,7 @@ where
     }
 
     fn get_collated_tx_out_search_results(
-        client_query_request: QueryRequest,
+        client_search_keys: Vec<Vec<u8>>,
         responses: &[DecryptedMultiViewStoreQueryResponse],
     ) -> Result<Vec<FixedTxOutSearchResult>> {
         let plaintext_search_results = responses
@@ -364,7 +395,7 @@ where
             .collect::<Vec<FixedTxOutSearchResult>>();
 
         oblivious_utils::collate_shard_tx_out_search_results(
-            client_query_request.get_txos,
+            client_search_keys,
             plaintext_search_results,
         )
     }
@@ -24,7 +24,8 @@ use mc_sgx_types::{sgx_attributes_t, sgx_enclave_id_t, sgx_launch_token_t, sgx_m
 use mc_sgx_urts::SgxEnclave;
 
 pub use mc_fog_view_enclave_api::{
-    Error, Result, ViewEnclaveApi, ViewEnclaveInitParams, ViewEnclaveProxy, ViewEnclaveRequest,
+    Error, OramStats, Result, ViewEnclaveApi, ViewEnclaveInitParams, ViewEnclaveProxy,
+    ViewEnclaveRequest,
 };
 
 mod ecall;
@@ -221,6 +222,12 @@ impl ViewEnclaveApi for SgxViewEnclave {
         mc_util_serial::deserialize(&outbuf[..])?
     }
 
+    fn get_oram_stats(&self) -> Result<OramStats> {
+        let inbuf = mc_util_serial::serialize(&ViewEnclaveRequest::GetOramStats)?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mc_util_serial::deserialize(&outbuf[..])?
+    }
+
     fn decrypt_and_seal_query(
         &self,
         client_query: EnclaveMessage<ClientSession>,
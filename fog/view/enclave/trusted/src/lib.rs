@@ -131,6 +131,7 @@ pub fn ecall_dispatcher(inbuf: &[u8]) -> Result<Vec<u8>, sgx_status_t> {
             serialize(&ENCLAVE.query_store(req, untrusted_query_response))
         }
         ViewEnclaveRequest::AddRecords(records) => serialize(&ENCLAVE.add_records(records)),
+        ViewEnclaveRequest::GetOramStats => serialize(&ENCLAVE.get_oram_stats()),
         ViewEnclaveRequest::DecryptAndSealQuery(client_query) => {
             serialize(&ENCLAVE.decrypt_and_seal_query(client_query))
         }
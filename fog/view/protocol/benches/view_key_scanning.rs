@@ -0,0 +1,108 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Measures the curve operations which dominate recovering a wallet's TxOuts:
+//! decompressing TxOut keys, and checking them against a view key.
+//!
+//! The benchmark groups are named after the curve25519-dalek backend in use,
+//! so that runs of portable and AVX2 builds, or on different hosts, can be
+//! compared side by side.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use mc_account_keys::AccountKey;
+use mc_crypto_keys::{CompressedRistrettoPublic, RistrettoPublic};
+use mc_transaction_core::onetime_keys::recover_public_subaddress_spend_key;
+use mc_util_from_random::FromRandom;
+use rand_core::SeedableRng;
+use rand_hc::Hc128Rng;
+
+/// The number of subaddresses checked for each TxOut.
+const NUM_SUBADDRESSES: u64 = 4;
+
+/// Random compressed (target_key, public_key) pairs, as found in TxOuts.
+fn tx_out_keys(
+    rng: &mut Hc128Rng,
+    num_tx_outs: usize,
+) -> Vec<(CompressedRistrettoPublic, CompressedRistrettoPublic)> {
+    (0..num_tx_outs)
+        .map(|_| {
+            (
+                RistrettoPublic::from_random(rng).into(),
+                RistrettoPublic::from_random(rng).into(),
+            )
+        })
+        .collect()
+}
+
+fn view_key_scanning_benchmarks(c: &mut Criterion) {
+    let mut rng = Hc128Rng::seed_from_u64(0);
+    let account_key = AccountKey::random(&mut rng);
+    let subaddress_spend_keys = (0..NUM_SUBADDRESSES)
+        .map(|index| RistrettoPublic::from(&account_key.subaddress_spend_private(index)))
+        .collect::<Vec<_>>();
+
+    let backend = mc_util_build_info::dalek_backend();
+    let mut decompress = c.benchmark_group(format!("decompress/{backend}"));
+    for num_tx_outs in [256, 4096] {
+        let keys = tx_out_keys(&mut rng, num_tx_outs)
+            .into_iter()
+            .map(|(target_key, _)| target_key)
+            .collect::<Vec<_>>();
+        decompress.throughput(Throughput::Elements(num_tx_outs as u64));
+
+        decompress.bench_with_input(BenchmarkId::new("single", num_tx_outs), &keys, |b, keys| {
+            b.iter(|| {
+                black_box(
+                    keys.iter()
+                        .map(RistrettoPublic::try_from)
+                        .collect::<Result<Vec<_>, _>>(),
+                )
+            })
+        });
+
+        decompress.bench_with_input(BenchmarkId::new("batch", num_tx_outs), &keys, |b, keys| {
+            b.iter(|| black_box(RistrettoPublic::try_decompress_batch(keys)))
+        });
+    }
+    decompress.finish();
+
+    let mut scan = c.benchmark_group(format!("view_key_scan/{backend}"));
+    for num_tx_outs in [256, 4096] {
+        let tx_outs = tx_out_keys(&mut rng, num_tx_outs);
+        scan.throughput(Throughput::Elements(num_tx_outs as u64));
+
+        scan.bench_with_input(
+            BenchmarkId::new("recover_subaddress", num_tx_outs),
+            &tx_outs,
+            |b, tx_outs| {
+                b.iter(|| {
+                    tx_outs
+                        .iter()
+                        .filter(|(target_key, public_key)| {
+                            let (Ok(target_key), Ok(public_key)) = (
+                                RistrettoPublic::try_from(target_key),
+                                RistrettoPublic::try_from(public_key),
+                            ) else {
+                                return false;
+                            };
+                            let spend_key = recover_public_subaddress_spend_key(
+                                account_key.view_private_key(),
+                                &target_key,
+                                &public_key,
+                            );
+                            subaddress_spend_keys.contains(&spend_key)
+                        })
+                        .count()
+                })
+            },
+        );
+    }
+    scan.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(50);
+    targets = view_key_scanning_benchmarks
+}
+
+criterion_main!(benches);
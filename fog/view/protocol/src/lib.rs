@@ -4,6 +4,9 @@
 
 extern crate alloc;
 
+mod multi_account;
+pub use multi_account::{AccountPoll, AccountPollResult, MultiAccountPollResult};
+
 mod polling;
 pub use polling::{FogViewConnection, TxOutPollingError};
 
@@ -0,0 +1,419 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Polling fog view for many accounts over one connection.
+//!
+//! RNG records and missed block ranges are not specific to a user, so
+//! accounts which have synced to the same point can share one events query.
+//! Their search keys are then sent together, in requests of a bounded size,
+//! and the results are handed back to the account whose RNG produced each
+//! search key. A custodial wallet syncing thousands of accounts therefore
+//! needs a handful of requests per round over a single session, rather than a
+//! session per account.
+
+use crate::{
+    polling::FogViewConnection,
+    user_private::UserPrivate,
+    user_rng_set::{TxOutRecoveryError, UserRngSet},
+};
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::fmt::{Debug, Display};
+use mc_common::{HashMap, HashSet};
+use mc_fog_kex_rng::BufferedRng;
+use mc_fog_types::{
+    common::BlockRange,
+    view::{TxOutRecord, TxOutSearchResult},
+    BlockCount,
+};
+
+/// The most search keys sent in a single request. See
+/// [FogViewConnection::poll] for how this was chosen.
+const MAX_SEARCH_KEYS_PER_REQUEST: usize = 500;
+
+/// An account polled by [FogViewConnection::poll_accounts]
+pub struct AccountPoll<'a> {
+    /// The account's RNGs and position in the fog view event stream, which
+    /// are updated by the poll
+    pub user_rng_set: &'a mut UserRngSet,
+    /// The account's keys
+    pub upriv: &'a UserPrivate,
+}
+
+/// What [FogViewConnection::poll_accounts] found for one account
+#[derive(Debug, Default)]
+pub struct AccountPollResult {
+    /// The account's new TxOuts
+    pub tx_outs: Vec<TxOutRecord>,
+    /// Block ranges which fog did not process for the account, and which
+    /// should be scanned by other means
+    pub missed_block_ranges: Vec<BlockRange>,
+    /// Errors recovering the account's TxOuts
+    pub errors: Vec<TxOutRecoveryError>,
+}
+
+/// The results of [FogViewConnection::poll_accounts]
+#[derive(Debug)]
+pub struct MultiAccountPollResult<ConnError: Debug + Display> {
+    /// The result for each account, in the order the accounts were given
+    pub accounts: Vec<AccountPollResult>,
+    /// The connection error which interrupted the poll, if any.
+    ///
+    /// Accounts which were not fully polled keep their previous highest
+    /// processed block count, so polling them again is safe. TxOuts which
+    /// were found before the error are still returned.
+    pub conn_error: Option<ConnError>,
+}
+
+/// Poll fog view for the new TxOuts of each of the given accounts.
+pub(crate) fn poll_accounts<C: FogViewConnection + ?Sized>(
+    conn: &mut C,
+    accounts: &mut [AccountPoll],
+) -> MultiAccountPollResult<C::Error> {
+    let mut results = Vec::new();
+    results.resize_with(accounts.len(), AccountPollResult::default);
+
+    // Accounts at the same point of the event stream and of the ledger can
+    // share every request.
+    let mut cohorts = BTreeMap::<(i64, u64), Vec<usize>>::new();
+    for (index, account) in accounts.iter().enumerate() {
+        let position = (
+            account.user_rng_set.get_next_start_from_user_event_id(),
+            account
+                .user_rng_set
+                .get_highest_processed_block_count()
+                .into(),
+        );
+        cohorts.entry(position).or_default().push(index);
+    }
+
+    for ((start_from_user_event_id, start_from_block_index), cohort) in cohorts {
+        if let Err(err) = poll_cohort(
+            conn,
+            accounts,
+            &mut results,
+            &cohort,
+            start_from_user_event_id,
+            start_from_block_index,
+        ) {
+            return MultiAccountPollResult {
+                accounts: results,
+                conn_error: Some(err),
+            };
+        }
+    }
+
+    MultiAccountPollResult {
+        accounts: results,
+        conn_error: None,
+    }
+}
+
+/// Poll the accounts of one cohort, following the same steps as
+/// [FogViewConnection::poll] does for a single account.
+fn poll_cohort<C: FogViewConnection + ?Sized>(
+    conn: &mut C,
+    accounts: &mut [AccountPoll],
+    results: &mut [AccountPollResult],
+    cohort: &[usize],
+    start_from_user_event_id: i64,
+    start_from_block_index: u64,
+) -> Result<(), C::Error> {
+    // Update seeds, get block count
+    let events = conn.request(
+        start_from_user_event_id,
+        start_from_block_index,
+        Default::default(),
+    )?;
+    for &index in cohort {
+        let account = &mut accounts[index];
+        for rng_record in events.rng_records.iter() {
+            if let Err(err) = account
+                .user_rng_set
+                .ingest_rng_record(account.upriv, rng_record)
+            {
+                results[index].errors.push(err);
+            }
+        }
+        results[index]
+            .missed_block_ranges
+            .extend(events.missed_block_ranges.iter().cloned());
+        account
+            .user_rng_set
+            .set_next_start_from_user_event_id(events.next_start_from_user_event_id);
+    }
+
+    let mut new_highest_processed_block_count = events.highest_processed_block_count;
+    if BlockCount::from(start_from_block_index)
+        >= BlockCount::from(new_highest_processed_block_count)
+    {
+        return Ok(());
+    }
+
+    // The owner of each search key we ask about
+    let mut search_key_owners = HashMap::<Vec<u8>, usize>::default();
+    // The rngs of each account which are dead, as in FogViewConnection::poll
+    let mut dead_rng_sets = HashMap::<usize, HashSet<Vec<u8>>>::default();
+    let mut request_multiplier = 1u64;
+
+    loop {
+        let num_live_rngs: usize = cohort
+            .iter()
+            .map(|index| {
+                let num_dead_rngs = dead_rng_sets.get(index).map_or(0, HashSet::len);
+                accounts[*index]
+                    .user_rng_set
+                    .get_rngs()
+                    .len()
+                    .saturating_sub(num_dead_rngs)
+            })
+            .sum();
+        if num_live_rngs == 0 {
+            break;
+        }
+
+        let max_request_multiplier =
+            core::cmp::max(MAX_SEARCH_KEYS_PER_REQUEST as u64 / num_live_rngs as u64, 1);
+        request_multiplier = core::cmp::min(request_multiplier * 2, max_request_multiplier);
+
+        // Collect the search keys of every live rng, without advancing them.
+        let mut old_rng_indices = HashMap::<(usize, Vec<u8>), u64>::default();
+        let mut search_keys = Vec::new();
+        search_key_owners.clear();
+        for &index in cohort {
+            let dead_rng_set = dead_rng_sets.entry(index).or_default();
+            for (nonce, rng) in accounts[index].user_rng_set.get_rngs().iter() {
+                old_rng_indices.insert((index, nonce.clone()), rng.index());
+                if dead_rng_set.contains(&nonce[..]) {
+                    continue;
+                }
+                for search_key in rng.clone().take(request_multiplier as usize) {
+                    search_key_owners.insert(search_key.clone(), index);
+                    search_keys.push(search_key);
+                }
+            }
+        }
+
+        let mut search_results = HashMap::<usize, Vec<TxOutSearchResult>>::default();
+        for chunk in search_keys.chunks(MAX_SEARCH_KEYS_PER_REQUEST) {
+            let resp = conn.request(
+                i64::MAX, // We don't care about any events, we just want to search for TXOs.
+                start_from_block_index,
+                chunk.to_vec(),
+            )?;
+            new_highest_processed_block_count = core::cmp::min(
+                new_highest_processed_block_count,
+                resp.highest_processed_block_count,
+            );
+            for result in resp.tx_out_search_results {
+                // A result for a search key we didn't ask about can't be any
+                // account's TxOut.
+                if let Some(index) = search_key_owners.get(&result.search_key) {
+                    search_results.entry(*index).or_default().push(result);
+                }
+            }
+            for &index in cohort {
+                results[index]
+                    .missed_block_ranges
+                    .extend(resp.missed_block_ranges.iter().cloned());
+            }
+        }
+
+        for &index in cohort {
+            let account = &mut accounts[index];
+            let account_results = search_results.remove(&index).unwrap_or_default();
+            let (tx_outs, errors) = account
+                .user_rng_set
+                .ingest_tx_out_search_results(account.upriv, &account_results);
+            results[index].tx_outs.extend(tx_outs);
+            results[index].errors.extend(errors);
+
+            // An rng which did not advance by request_multiplier steps is dead.
+            let dead_rng_set = account
+                .user_rng_set
+                .get_rngs()
+                .iter()
+                .filter(|(nonce, rng)| {
+                    old_rng_indices
+                        .get(&(index, nonce.to_vec()))
+                        .map(|old_index| old_index + request_multiplier > rng.index())
+                        .unwrap_or(true)
+                })
+                .map(|(nonce, _)| nonce.clone())
+                .collect();
+            dead_rng_sets.insert(index, dead_rng_set);
+        }
+    }
+
+    // Don't update the block count in reverse, as in FogViewConnection::poll.
+    for &index in cohort {
+        let user_rng_set = &mut accounts[index].user_rng_set;
+        if new_highest_processed_block_count
+            > user_rng_set.get_highest_processed_block_count().into()
+        {
+            user_rng_set.set_highest_processed_block_count(new_highest_processed_block_count);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{string::String, vec};
+    use mc_crypto_box::{CryptoBox, VersionedCryptoBox};
+    use mc_crypto_keys::{Ristretto, RistrettoPrivate, RistrettoPublic};
+    use mc_fog_kex_rng::{KexRng20201124, KexRngPubkey, VersionedKexRng};
+    use mc_fog_types::view::{QueryResponse, RngRecord, TxOutSearchResultCode};
+    use mc_util_from_random::FromRandom;
+    use rand_core::SeedableRng;
+    use rand_hc::Hc128Rng;
+
+    /// A fog view server with one rng record, which knows the TxOuts found by
+    /// some search keys
+    #[derive(Default)]
+    struct MockFogView {
+        rng_records: Vec<RngRecord>,
+        tx_outs: HashMap<Vec<u8>, Vec<u8>>,
+        num_blocks: u64,
+        requests: Vec<(i64, usize)>,
+    }
+
+    impl FogViewConnection for MockFogView {
+        type Error = String;
+
+        fn request(
+            &mut self,
+            start_from_user_event_id: i64,
+            _start_from_block_index: u64,
+            search_keys: Vec<Vec<u8>>,
+        ) -> Result<QueryResponse, Self::Error> {
+            self.requests
+                .push((start_from_user_event_id, search_keys.len()));
+            let rng_records = self
+                .rng_records
+                .iter()
+                .skip(start_from_user_event_id.clamp(0, i64::MAX) as usize)
+                .cloned()
+                .collect();
+            let tx_out_search_results = search_keys
+                .into_iter()
+                .map(|search_key| match self.tx_outs.get(&search_key) {
+                    Some(ciphertext) => TxOutSearchResult {
+                        search_key,
+                        result_code: TxOutSearchResultCode::Found as u32,
+                        ciphertext: ciphertext.clone(),
+                        padding: vec![],
+                    },
+                    None => TxOutSearchResult {
+                        search_key,
+                        result_code: TxOutSearchResultCode::NotFound as u32,
+                        ciphertext: vec![],
+                        padding: vec![],
+                    },
+                })
+                .collect();
+            Ok(QueryResponse {
+                highest_processed_block_count: self.num_blocks,
+                next_start_from_user_event_id: self.rng_records.len() as i64,
+                rng_records,
+                tx_out_search_results,
+                ..Default::default()
+            })
+        }
+    }
+
+    #[test]
+    fn poll_accounts_demultiplexes_results() {
+        let mut rng = Hc128Rng::from_seed([7u8; 32]);
+        let ingest_private = RistrettoPrivate::from_random(&mut rng);
+        let kex_pubkey = KexRngPubkey::from_public_key::<KexRng20201124, Ristretto>(
+            &RistrettoPublic::from(&ingest_private),
+        );
+        let mut fog_view = MockFogView {
+            rng_records: vec![RngRecord {
+                ingest_invocation_id: 1,
+                pubkey: kex_pubkey.clone(),
+                start_block: 0,
+            }],
+            num_blocks: 10,
+            ..Default::default()
+        };
+
+        // Give the i-th user i TxOuts.
+        let users = (0..3)
+            .map(|_| UserPrivate::random(&mut rng))
+            .collect::<Vec<_>>();
+        for (i, user) in users.iter().enumerate() {
+            let user_rng =
+                VersionedKexRng::try_from_kex_pubkey(&kex_pubkey, user.get_view_key()).unwrap();
+            for (j, search_key) in user_rng.take(i).enumerate() {
+                let record = TxOutRecord {
+                    tx_out_amount_commitment_data: Vec::new(),
+                    tx_out_amount_masked_value: 0,
+                    tx_out_target_key_data: Vec::new(),
+                    tx_out_public_key_data: Vec::new(),
+                    tx_out_global_index: (i * 10 + j) as u64,
+                    block_index: j as u64,
+                    timestamp: 0,
+                    tx_out_amount_commitment_data_crc32: 0,
+                    tx_out_e_memo_data: Vec::new(),
+                    tx_out_amount_masked_token_id: None,
+                };
+                let ciphertext = VersionedCryptoBox::default()
+                    .encrypt(
+                        &mut rng,
+                        &user.get_view_pubkey(),
+                        &mc_util_serial::encode(&record),
+                    )
+                    .unwrap();
+                fog_view.tx_outs.insert(search_key, ciphertext);
+            }
+        }
+
+        let mut user_rng_sets = vec![UserRngSet::new(); users.len()];
+        let mut accounts = user_rng_sets
+            .iter_mut()
+            .zip(users.iter())
+            .map(|(user_rng_set, upriv)| AccountPoll {
+                user_rng_set,
+                upriv,
+            })
+            .collect::<Vec<_>>();
+        let result = fog_view.poll_accounts(&mut accounts);
+        assert!(result.conn_error.is_none());
+
+        for (i, account_result) in result.accounts.iter().enumerate() {
+            assert!(
+                account_result.errors.is_empty(),
+                "{:?}",
+                account_result.errors
+            );
+            let mut global_indices = account_result
+                .tx_outs
+                .iter()
+                .map(|tx_out| tx_out.tx_out_global_index)
+                .collect::<Vec<_>>();
+            global_indices.sort();
+            assert_eq!(
+                global_indices,
+                (0..i).map(|j| (i * 10 + j) as u64).collect::<Vec<_>>()
+            );
+        }
+        for user_rng_set in user_rng_sets.iter() {
+            assert_eq!(
+                user_rng_set.get_highest_processed_block_count(),
+                BlockCount::from(10u64)
+            );
+            assert_eq!(user_rng_set.get_next_start_from_user_event_id(), 1);
+        }
+
+        // The accounts shared a single events request.
+        assert_eq!(
+            fog_view
+                .requests
+                .iter()
+                .filter(|(_, num_search_keys)| *num_search_keys == 0)
+                .count(),
+            1
+        );
+    }
+}
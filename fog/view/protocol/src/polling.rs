@@ -15,6 +15,7 @@
 //! TxOutputRecord's, in your paykit implementation.
 
 use crate::{
+    multi_account::{self, AccountPoll, MultiAccountPollResult},
     user_private::UserPrivate,
     user_rng_set::{TxOutRecoveryError, UserRngSet},
 };
@@ -228,6 +229,19 @@ pub trait FogViewConnection {
         }
         (results, missed_block_ranges, errs)
     }
+
+    /// Poll for the new TxOuts of many accounts at once, as `poll` does for
+    /// one account.
+    ///
+    /// Accounts at the same point share their events requests, and their
+    /// search keys are combined into as few requests as possible, so this
+    /// makes far fewer requests than polling each account in turn.
+    fn poll_accounts(
+        &mut self,
+        accounts: &mut [AccountPoll],
+    ) -> MultiAccountPollResult<Self::Error> {
+        multi_account::poll_accounts(self, accounts)
+    }
 }
 
 /// TxOutPollingError type
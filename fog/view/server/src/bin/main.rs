@@ -6,7 +6,7 @@ use mc_common::{logger::log, time::SystemTimeProvider};
 use mc_fog_sql_recovery_db::SqlRecoveryDb;
 use mc_fog_view_enclave::{SgxViewEnclave, ENCLAVE_FILE};
 use mc_fog_view_server::{config, config::MobileAcctViewConfig, server::ViewServer};
-use mc_util_cli::ParserWithBuildInfo;
+use mc_util_cli::LayeredConfig;
 use mc_util_grpc::AdminServer;
 use std::{env, sync::Arc};
 
@@ -15,11 +15,15 @@ fn main() {
     let (logger, _global_logger_guard) =
         mc_common::logger::create_app_logger(mc_common::logger::o!());
     mc_common::setup_panic_handler();
-    let config = MobileAcctViewConfig::parse();
+    let config = MobileAcctViewConfig::load();
 
     let database_url = env::var("DATABASE_URL").expect("Missing DATABASE_URL environment variable");
-    let recovery_db = SqlRecoveryDb::new_from_url(
+    // Fog view never writes to the recovery db, so it can be served by a read
+    // replica if there is one.
+    let read_replica_url = env::var("DATABASE_READ_REPLICA_URL").ok();
+    let recovery_db = SqlRecoveryDb::new_with_read_replica(
         &database_url,
+        read_replica_url.as_deref(),
         config.postgres_config.clone(),
         logger.clone(),
     )
@@ -5,13 +5,14 @@
 use grpcio::ChannelBuilder;
 use mc_common::{logger::log, time::SystemTimeProvider};
 use mc_fog_api::view_grpc::FogViewStoreApiClient;
+use mc_fog_uri::ConnectionUri;
 use mc_fog_view_enclave::{SgxViewEnclave, ENCLAVE_FILE};
 use mc_fog_view_server::{
     config::FogViewRouterConfig,
     fog_view_router_server::{FogViewRouterServer, Shard},
     sharding_strategy::{EpochShardingStrategy, ShardingStrategy},
 };
-use mc_util_cli::ParserWithBuildInfo;
+use mc_util_cli::LayeredConfig;
 use mc_util_grpc::ConnectionUriGrpcioChannel;
 use std::{
     env,
@@ -22,7 +23,7 @@ fn main() {
     let (logger, _global_logger_guard) =
         mc_common::logger::create_app_logger(mc_common::logger::o!());
     mc_common::setup_panic_handler();
-    let config = FogViewRouterConfig::parse();
+    let config = FogViewRouterConfig::load();
 
     let enclave_path = env::current_exe()
         .expect("Could not get the path of our executable")
@@ -63,9 +64,15 @@ fn main() {
 
         // TODO: update this logic once we introduce other types of sharding strategies.
         let epoch_sharding_strategy = EpochShardingStrategy::try_from(shard_uri.clone())
-            .unwrap_or_else(|_| panic!("Could not get sharding strategy for uri: {shard_uri:?}"));
+            .unwrap_or_else(|_| {
+                panic!(
+                    "Could not get sharding strategy for uri: {}",
+                    shard_uri.redacted()
+                )
+            });
         let block_range = epoch_sharding_strategy.get_block_range();
-        let shard = Shard::new(shard_uri, Arc::new(fog_view_store_grpc_client), block_range);
+        let shard = Shard::new(shard_uri, Arc::new(fog_view_store_grpc_client), block_range)
+            .with_ingress_keys(epoch_sharding_strategy.ingress_keys().cloned());
         shards.push(shard);
     }
     let shards = Arc::new(RwLock::new(shards));
@@ -45,6 +45,12 @@ where
         let mut next_blocks = HashMap::default();
 
         for rec in ingress_key_records {
+            // The TxOuts of ingress keys which the sharding strategy excludes
+            // are never loaded.
+            if !self.sharding_strategy.should_process_ingress_key(&rec.key) {
+                continue;
+            }
+
             if let Some(last_processed_block) = self.processed_block_per_ingress_key.get(&rec.key) {
                 // A block has previously been processed for this ingress key. See if the
                 // next one can be provided by it, and if so add it to the list of next blocks
@@ -149,8 +155,10 @@ where
             // any of them need to provide this block and have not provided it
             for rec in ingress_keys {
                 let epoch = self.sharding_strategy.get_block_range();
-                let is_key_responsible = rec.get_block_range().overlaps(&epoch)
-                    && rec.covers_block_index(next_block_index);
+                let is_key_responsible =
+                    self.sharding_strategy.should_process_ingress_key(&rec.key)
+                        && rec.get_block_range().overlaps(&epoch)
+                        && rec.covers_block_index(next_block_index);
                 if !is_key_responsible {
                     continue;
                 }
@@ -1306,4 +1314,42 @@ mod tests {
         block_tracker.block_processed(CompressedRistrettoPublic::from_random(&mut rng), 101);
         assert_eq!(block_tracker.highest_known_block_count(), 102);
     }
+
+    // Ingress keys which the sharding strategy excludes are neither loaded nor
+    // waited on.
+    #[test_with_logger]
+    fn excluded_ingress_keys_are_skipped(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([123u8; 32]);
+        let status = IngressPublicKeyStatus {
+            start_block: 10,
+            pubkey_expiry: 20,
+            retired: false,
+            lost: false,
+        };
+        let rec1 = IngressPublicKeyRecord {
+            key: CompressedRistrettoPublic::from_random(&mut rng),
+            status: status.clone(),
+            last_scanned_block: None,
+        };
+        let rec2 = IngressPublicKeyRecord {
+            key: CompressedRistrettoPublic::from_random(&mut rng),
+            status,
+            last_scanned_block: None,
+        };
+        let sharding_strategy =
+            EpochShardingStrategy::default().with_ingress_keys([rec1.key].into_iter().collect());
+        let mut block_tracker = BlockTracker::new(logger, sharding_strategy);
+        let recs = [rec1.clone(), rec2.clone()];
+
+        let expected_state = HashMap::from_iter(vec![(rec1.key, 10)]);
+        assert_eq!(block_tracker.next_blocks(&recs), expected_state);
+
+        for block_index in 10..15 {
+            block_tracker.block_processed(rec1.key, block_index);
+        }
+        assert_eq!(
+            block_tracker.highest_fully_processed_block_count(&recs),
+            (15, None)
+        );
+    }
 }
@@ -7,6 +7,7 @@ use clap::Parser;
 use mc_common::ResponderId;
 use mc_fog_sql_recovery_db::SqlRecoveryDbConnectionConfig;
 use mc_fog_uri::{FogViewRouterUri, FogViewStoreUri, FogViewUri};
+use mc_util_grpc::{AuditLogConfig, InterceptorConfig, MessageSizeConfig};
 use mc_util_parse::parse_duration_in_seconds;
 use mc_util_uri::AdminUri;
 use serde::Serialize;
@@ -38,6 +39,7 @@ pub struct MobileAcctViewConfig {
     /// Enables authenticating client requests using Authorization tokens using
     /// the provided hex-encoded 32 bytes shared secret.
     #[clap(long, value_parser = mc_util_parse::parse_hex::<[u8; 32]>, env = "MC_CLIENT_AUTH_TOKEN_SECRET")]
+    #[serde(serialize_with = "mc_util_cli::redact_secret")]
     pub client_auth_token_secret: Option<[u8; 32]>,
 
     /// Maximal client authentication token lifetime, in seconds (only relevant
@@ -59,6 +61,12 @@ pub struct MobileAcctViewConfig {
     #[clap(long, default_value = "1048576", env = "MC_OMAP_CAPACITY")]
     pub omap_capacity: u64,
 
+    /// The percentage of the OMAP capacity above which the server logs a
+    /// warning each time it adds records, so that operators can restart it
+    /// with a larger capacity before the hash table overflows.
+    #[clap(long, default_value = "70", env = "MC_OMAP_FILL_WARNING_PERCENT")]
+    pub omap_fill_warning_percent: u64,
+
     /// Postgres config
     #[clap(flatten)]
     pub postgres_config: SqlRecoveryDbConnectionConfig,
@@ -70,7 +78,9 @@ pub struct MobileAcctViewConfig {
     pub block_query_batch_size: usize,
 
     /// Determines which group of TxOuts the Fog View Store instance will
-    /// process.
+    /// process: a block range such as `0-10000`, or `default` for all blocks,
+    /// optionally followed by `@` and the dot separated, hex-encoded ingress
+    /// keys whose TxOuts to process.
     #[clap(long, default_value = "default", env = "MC_SHARDING_STRATEGY")]
     pub sharding_strategy: ShardingStrategy,
 }
@@ -128,6 +138,7 @@ pub struct FogViewRouterConfig {
     /// Enables authenticating client requests using Authorization tokens using
     /// the provided hex-encoded 32 bytes shared secret.
     #[clap(long, value_parser = mc_util_parse::parse_hex::<[u8; 32]>, env = "MC_CLIENT_AUTH_TOKEN_SECRET")]
+    #[serde(serialize_with = "mc_util_cli::redact_secret")]
     pub client_auth_token_secret: Option<[u8; 32]>,
 
     /// Maximal client authentication token lifetime, in seconds (only relevant
@@ -135,6 +146,24 @@ pub struct FogViewRouterConfig {
     /// hours).
     #[clap(long, default_value = "86400", value_parser = parse_duration_in_seconds, env = "MC_CLIENT_AUTH_TOKEN_MAX_LIFETIME")]
     pub client_auth_token_max_lifetime: Duration,
+
+    /// Optional request interceptor layers for client-facing services.
+    #[clap(flatten)]
+    pub interceptors: InterceptorConfig,
+
+    /// Optional gRPC message size limits for client-facing services.
+    #[clap(flatten)]
+    pub message_size: MessageSizeConfig,
+
+    /// Optional audit log of administrative and attestation events.
+    #[clap(flatten)]
+    pub audit_log: AuditLogConfig,
+
+    /// How long retries of a client query, identified by its idempotency
+    /// token, are answered from the store responses of its first attempt, in
+    /// seconds. 0 turns deduplication off.
+    #[clap(long, default_value = "60", value_parser = parse_duration_in_seconds, env = "MC_QUERY_DEDUP_WINDOW")]
+    pub query_dedup_window: Duration,
 }
 
 /// A FogViewRouterServer can either fulfill streaming or unary requests, and
@@ -41,6 +41,18 @@ lazy_static::lazy_static! {
     // Last known block cumulative txo count
     pub static ref LAST_KNOWN_BLOCK_CUMULATIVE_TXO_COUNT: IntGauge = OP_COUNTERS.gauge("last_known_block_cumulative_txo_count");
 
+    // Number of records in the enclave's ORAM.
+    pub static ref ORAM_NUM_RECORDS: IntGauge = OP_COUNTERS.gauge("oram_num_records");
+
+    // Capacity of the enclave's ORAM.
+    pub static ref ORAM_CAPACITY: IntGauge = OP_COUNTERS.gauge("oram_capacity");
+
+    // Percentage of the enclave's ORAM capacity that is in use.
+    pub static ref ORAM_FILL_PERCENT: IntGauge = OP_COUNTERS.gauge("oram_fill_percent");
+
+    // Number of writes to the enclave's ORAM that failed because it overflowed.
+    pub static ref ORAM_OVERFLOW_COUNT: IntGauge = OP_COUNTERS.gauge("oram_overflow_count");
+
     // Number of records currently in the db fetcher fetched_records queue.
     pub static ref DB_FETCHER_NUM_QUEUED_RECORDS: IntGauge = OP_COUNTERS.gauge("db_fetcher_num_queued_records");
 }
@@ -8,6 +8,7 @@ use crate::{
     config::{FogViewRouterConfig, RouterClientListenUri},
     counters,
     fog_view_router_service::FogViewRouterService,
+    query_dedup::{QueryDedupWindow, MAX_DEDUP_QUERIES},
     router_admin_service::FogViewRouterAdminService,
 };
 use futures::executor::block_on;
@@ -15,16 +16,20 @@ use mc_common::{
     logger::{log, Logger},
     time::TimeProvider,
 };
+use mc_crypto_keys::CompressedRistrettoPublic;
 use mc_fog_api::view_grpc;
 use mc_fog_types::common::BlockRange;
 use mc_fog_uri::{ConnectionUri, FogViewStoreUri};
 use mc_fog_view_enclave::ViewEnclaveProxy;
 use mc_sgx_report_cache_untrusted::ReportCacheThread;
 use mc_util_grpc::{
-    AdminServer, AnonymousAuthenticator, Authenticator, ConnectionUriGrpcioServer,
-    TokenAuthenticator,
+    record_audit_event, AdminServer, AnonymousAuthenticator, AuditEvent, Authenticator,
+    ConnectionUriGrpcioServer, InFlightRequests, InterceptorChain, TokenAuthenticator,
+};
+use std::{
+    collections::BTreeSet,
+    sync::{Arc, RwLock},
 };
-use std::sync::{Arc, RwLock};
 
 pub struct FogViewRouterServer<E>
 where
@@ -50,6 +55,10 @@ pub struct Shard {
 
     /// The `BlockRange` that this shard is responsible for providing.
     pub block_range: BlockRange,
+
+    /// The ingress keys whose TxOuts this shard provides, if it doesn't
+    /// provide the TxOuts of every ingress key.
+    pub ingress_keys: Option<BTreeSet<CompressedRistrettoPublic>>,
 }
 
 impl Shard {
@@ -62,6 +71,32 @@ impl Shard {
             uri,
             grpc_client,
             block_range,
+            ingress_keys: None,
+        }
+    }
+
+    /// Limit the shard to the TxOuts of the given ingress keys, if any.
+    pub fn with_ingress_keys(
+        mut self,
+        ingress_keys: Option<BTreeSet<CompressedRistrettoPublic>>,
+    ) -> Self {
+        self.ingress_keys = ingress_keys;
+        self
+    }
+
+    /// Returns true if this shard should be queried by a client with the given
+    /// ingress keys. Shards limited to some ingress keys are only queried by
+    /// clients which said they use one of them.
+    pub fn serves_ingress_keys(
+        &self,
+        client_ingress_keys: Option<&BTreeSet<CompressedRistrettoPublic>>,
+    ) -> bool {
+        match (self.ingress_keys.as_ref(), client_ingress_keys) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(ingress_keys), Some(client_ingress_keys)) => {
+                !ingress_keys.is_disjoint(client_ingress_keys)
+            }
         }
     }
 }
@@ -87,8 +122,14 @@ where
                 .build(),
         );
 
+        config
+            .audit_log
+            .init("fog_view_router")
+            .expect("Could not open audit log");
+
         let client_authenticator: Arc<dyn Authenticator + Sync + Send> =
             if let Some(shared_secret) = config.client_auth_token_secret.as_ref() {
+                record_audit_event(AuditEvent::auth_token_secret_loaded(shared_secret), &logger);
                 Arc::new(TokenAuthenticator::new(
                     *shared_secret,
                     config.client_auth_token_max_lifetime,
@@ -97,8 +138,22 @@ where
             } else {
                 Arc::new(AnonymousAuthenticator)
             };
+        let client_interceptors = InterceptorChain::from_config(
+            &config.interceptors,
+            &config.chain_id,
+            client_authenticator,
+            logger.clone(),
+        );
+
+        let query_dedup = Arc::new(QueryDedupWindow::new(
+            config.query_dedup_window,
+            MAX_DEDUP_QUERIES,
+        ));
+
+        let in_flight = Arc::new(InFlightRequests::default());
 
-        let admin_service = FogViewRouterAdminService::new(shards.clone(), logger.clone());
+        let admin_service =
+            FogViewRouterAdminService::new(shards.clone(), in_flight.clone(), logger.clone());
         log::debug!(logger, "Constructed Fog View Router Admin GRPC Service");
 
         // Health check service
@@ -110,8 +165,9 @@ where
                     view_grpc::create_fog_view_router_api(FogViewRouterService::new(
                         enclave.clone(),
                         shards,
-                        config.chain_id.clone(),
-                        client_authenticator,
+                        client_interceptors,
+                        query_dedup,
+                        in_flight,
                         logger.clone(),
                     ));
                 log::debug!(logger, "Constructed Fog View Router streaming GRPC Service");
@@ -121,7 +177,9 @@ where
                     streaming_uri.addr(),
                 );
 
-                grpcio::ServerBuilder::new(env)
+                config
+                    .message_size
+                    .apply_to_server(grpcio::ServerBuilder::new(env.clone()), env)
                     .register_service(fog_view_router_service)
                     .register_service(health_service)
                     .build_using_uri(streaming_uri, logger.clone())
@@ -132,8 +190,9 @@ where
                     view_grpc::create_fog_view_api(FogViewRouterService::new(
                         enclave.clone(),
                         shards,
-                        config.chain_id.clone(),
-                        client_authenticator,
+                        client_interceptors,
+                        query_dedup,
+                        in_flight,
                         logger.clone(),
                     ));
                 log::debug!(logger, "Constructed Fog View Router unary GRPC Service");
@@ -142,7 +201,9 @@ where
                     "Starting Fog View Router unary server on {}",
                     unary_uri.addr(),
                 );
-                grpcio::ServerBuilder::new(env)
+                config
+                    .message_size
+                    .apply_to_server(grpcio::ServerBuilder::new(env.clone()), env)
                     .register_service(fog_view_router_service)
                     .register_service(health_service)
                     .build_using_uri(unary_uri, logger.clone())
@@ -1,17 +1,23 @@
 // Copyright (c) 2018-2022 The MobileCoin Foundation
 
-use crate::{fog_view_router_server::Shard, router_request_handler, SVC_COUNTERS};
-use futures::{executor::block_on, FutureExt, TryFutureExt};
-use grpcio::{DuplexSink, RequestStream, RpcContext, UnarySink};
+use crate::{
+    fog_view_router_server::Shard, query_dedup::QueryDedupWindow, router_request_handler,
+    SVC_COUNTERS,
+};
+use futures::{executor::block_on, future::Abortable, FutureExt, TryFutureExt};
+use grpcio::{DuplexSink, RequestStream, RpcContext, RpcStatus, UnarySink};
 use mc_attest_api::attest;
 use mc_common::logger::{log, Logger};
 use mc_fog_api::{
+    ingress_keys::ingress_keys_from_metadata,
     view::{FogViewRouterRequest, FogViewRouterResponse},
     view_grpc::{FogViewApi, FogViewRouterApi},
 };
 use mc_fog_view_enclave_api::ViewEnclaveProxy;
-use mc_util_grpc::{check_request_chain_id, rpc_logger, send_result, Authenticator};
-use mc_util_metrics::ServiceMetrics;
+use mc_util_grpc::{
+    rpc_invalid_arg_error, rpc_logger, send_result, InFlightRequests, InterceptorChain,
+};
+use mc_util_metrics::{service_metrics, ServiceMetrics};
 use mc_util_telemetry::tracer;
 use std::sync::{Arc, RwLock};
 
@@ -22,9 +28,12 @@ where
 {
     enclave: E,
     shards: Arc<RwLock<Vec<Shard>>>,
-    chain_id: String,
-    /// GRPC request authenticator.
-    authenticator: Arc<dyn Authenticator + Send + Sync>,
+    /// Checks run on every request before it is handled.
+    interceptors: InterceptorChain,
+    /// The store responses of recent queries, for answering their retries.
+    query_dedup: Arc<QueryDedupWindow>,
+    /// The client streams being handled, which the admin API lists.
+    in_flight: Arc<InFlightRequests>,
     logger: Logger,
 }
 
@@ -37,20 +46,36 @@ impl<E: ViewEnclaveProxy> FogViewRouterService<E> {
     pub fn new(
         enclave: E,
         shards: Arc<RwLock<Vec<Shard>>>,
-        chain_id: String,
-        authenticator: Arc<dyn Authenticator + Send + Sync>,
+        interceptors: InterceptorChain,
+        query_dedup: Arc<QueryDedupWindow>,
+        in_flight: Arc<InFlightRequests>,
         logger: Logger,
     ) -> Self {
         Self {
             enclave,
             shards,
-            chain_id,
-            authenticator,
+            interceptors,
+            query_dedup,
+            in_flight,
             logger,
         }
     }
+
+    /// The shards to query for a request, based on the ingress keys the client
+    /// sent in the request headers.
+    fn shards_for_request(
+        &self,
+        ctx: &RpcContext,
+        logger: &Logger,
+    ) -> Result<Vec<Shard>, RpcStatus> {
+        let ingress_keys = ingress_keys_from_metadata(ctx.request_headers())
+            .map_err(|err| rpc_invalid_arg_error("Invalid ingress keys header", err, logger))?;
+        let shards = self.shards.read().expect("RwLock poisoned");
+        router_request_handler::select_shards(&shards, ingress_keys.as_ref(), logger)
+    }
 }
 
+#[service_metrics(SVC_COUNTERS)]
 impl<E> FogViewRouterApi for FogViewRouterService<E>
 where
     E: ViewEnclaveProxy,
@@ -65,12 +90,24 @@ where
             let logger = logger.clone();
             // TODO: Confirm that we don't need to perform the authenticator logic. I think
             // we don't  because of streaming...
-            let shards = self.shards.read().expect("RwLock poisoned");
+            let shards = match self.shards_for_request(&ctx, &logger) {
+                Ok(shards) => shards,
+                Err(rpc_status) => {
+                    let future = responses
+                        .fail(rpc_status)
+                        .map_err(move |err| log::error!(&logger, "failed to reply: {}", err))
+                        .map(|_| ());
+                    return ctx.spawn(future);
+                }
+            };
             let method_name = ServiceMetrics::get_method_name(&ctx);
+            let (in_flight, abort_registration) = self.in_flight.track(&ctx, shards.len());
             let future = router_request_handler::handle_requests(
                 method_name,
-                shards.clone(),
+                shards,
                 self.enclave.clone(),
+                self.query_dedup.clone(),
+                in_flight,
                 requests,
                 responses,
                 logger.clone(),
@@ -79,11 +116,14 @@ where
             // TODO: Do stuff with the error
             .map(|_| ());
 
-            ctx.spawn(future)
+            // The stream is dropped, and the client sees it fail, if it is
+            // cancelled through the admin API.
+            ctx.spawn(Abortable::new(future, abort_registration).map(|_| ()))
         });
     }
 }
 
+#[service_metrics(SVC_COUNTERS)]
 impl<E> FogViewApi for FogViewRouterService<E>
 where
     E: ViewEnclaveProxy,
@@ -94,15 +134,10 @@ where
         request: attest::AuthMessage,
         sink: UnarySink<attest::AuthMessage>,
     ) {
-        let _timer = SVC_COUNTERS.req(&ctx);
         mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
-            if let Err(err) = check_request_chain_id(&self.chain_id, &ctx) {
+            if let Err(err) = self.interceptors.check(&ctx) {
                 return send_result(ctx, sink, Err(err), logger);
             }
-
-            if let Err(err) = self.authenticator.authenticate_rpc(&ctx) {
-                return send_result(ctx, sink, err.into(), logger);
-            }
             let result = router_request_handler::handle_auth_request(
                 self.enclave.clone(),
                 request,
@@ -121,21 +156,22 @@ where
         sink: UnarySink<attest::Message>,
     ) {
         mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
-            if let Err(err) = check_request_chain_id(&self.chain_id, &ctx) {
+            if let Err(err) = self.interceptors.check(&ctx) {
                 return send_result(ctx, sink, Err(err), logger);
             }
 
-            if let Err(err) = self.authenticator.authenticate_rpc(&ctx) {
-                return send_result(ctx, sink, err.into(), logger);
-            }
+            let shards = match self.shards_for_request(&ctx, logger) {
+                Ok(shards) => shards,
+                Err(rpc_status) => return send_result(ctx, sink, Err(rpc_status), logger),
+            };
 
             // This will block the async API. We should use some sort of differentiator...
-            let shards = self.shards.read().expect("RwLock poisoned");
             let tracer = tracer!();
             let result = block_on(router_request_handler::handle_query_request(
                 request,
                 self.enclave.clone(),
-                shards.clone(),
+                shards,
+                &self.query_dedup,
                 self.logger.clone(),
                 &tracer,
             ))
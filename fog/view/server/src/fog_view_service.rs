@@ -18,8 +18,9 @@ use mc_fog_view_enclave::{Error as ViewEnclaveError, ViewEnclaveProxy};
 use mc_fog_view_enclave_api::UntrustedQueryResponse;
 use mc_util_grpc::{
     rpc_internal_error, rpc_invalid_arg_error, rpc_logger, rpc_permissions_error, send_result,
-    Authenticator,
+    InterceptorChain,
 };
+use mc_util_metrics::service_metrics;
 use mc_util_telemetry::{tracer, BoxedTracer, Tracer};
 use std::sync::{Arc, Mutex};
 
@@ -42,8 +43,9 @@ where
     /// The URI that this service listens on.
     uri: FogViewStoreUri,
 
-    /// GRPC request authenticator.
-    authenticator: Arc<dyn Authenticator + Send + Sync>,
+    /// Checks run on GRPC requests before they are served, e.g.
+    /// authentication.
+    interceptors: InterceptorChain,
 
     /// Slog logger object
     logger: Logger,
@@ -65,7 +67,7 @@ where
         db: Arc<DB>,
         db_poll_shared_state: Arc<Mutex<DbPollSharedState>>,
         uri: FogViewStoreUri,
-        authenticator: Arc<dyn Authenticator + Send + Sync>,
+        interceptors: InterceptorChain,
         sharding_strategy: SS,
         logger: Logger,
     ) -> Self {
@@ -74,7 +76,7 @@ where
             db,
             db_poll_shared_state,
             uri,
-            authenticator,
+            interceptors,
             sharding_strategy,
             logger,
         }
@@ -253,6 +255,7 @@ where
 }
 
 /// Implement the FogViewStoreService gRPC trait.
+#[service_metrics(SVC_COUNTERS)]
 impl<E, DB, SS> FogViewStoreApi for FogViewService<E, DB, SS>
 where
     E: ViewEnclaveProxy,
@@ -265,10 +268,9 @@ where
         request: attest::AuthMessage,
         sink: UnarySink<attest::AuthMessage>,
     ) {
-        let _timer = SVC_COUNTERS.req(&ctx);
         mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
-            if let Err(err) = self.authenticator.authenticate_rpc(&ctx) {
-                return send_result(ctx, sink, err.into(), logger);
+            if let Err(err) = self.interceptors.check(&ctx) {
+                return send_result(ctx, sink, Err(err), logger);
             }
 
             send_result(ctx, sink, self.auth_impl(request, logger), logger);
@@ -285,8 +287,8 @@ where
         sink: UnarySink<MultiViewStoreQueryResponse>,
     ) {
         mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
-            if let Err(err) = self.authenticator.authenticate_rpc(&ctx) {
-                return send_result(ctx, sink, err.into(), logger);
+            if let Err(err) = self.interceptors.check(&ctx) {
+                return send_result(ctx, sink, Err(err), logger);
             }
             let response =
                 self.process_queries(self.uri.clone(), request.queries.into_vec(), logger);
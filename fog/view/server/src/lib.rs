@@ -16,6 +16,7 @@ mod block_tracker;
 mod counters;
 mod db_fetcher;
 mod metrics;
+mod query_dedup;
 mod router_admin_service;
 mod router_request_handler;
 mod shard_responses_processor;
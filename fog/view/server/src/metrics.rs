@@ -26,6 +26,11 @@ lazy_static! {
         "Queries to router"
     ))
     .expect("metric cannot be created");
+    pub static ref DEDUPLICATED_QUERIES: IntCounter = register_int_counter!(
+        "fog_view_router_deduplicated_queries",
+        "Client query retries answered from the store responses of an earlier attempt"
+    )
+    .expect("metric cannot be created");
     pub static ref AUTH_CLIENT_REQUESTS: IntCounter = register_int_counter!(
         "fog_view_router_auth_client_requests",
         "Auth requests to stores"
@@ -0,0 +1,166 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Answers retried client queries from the store responses of their first
+//! attempt.
+//!
+//! A client tags each query with a random idempotency token in its AAD, and
+//! sends the same token when it retries the query, e.g. because the response
+//! was lost. Within the dedup window, the router collates a retry from the
+//! store responses it got for the first attempt, instead of querying the
+//! stores again, so every attempt of a query reports the same TxOuts and the
+//! same highest processed block count, and a wallet can't count TxOuts from
+//! two different views of the ledger.
+
+use mc_fog_types::view::MultiViewStoreQueryResponse;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// The most queries whose store responses are kept at once.
+pub const MAX_DEDUP_QUERIES: usize = 10_000;
+
+/// The store responses of recent queries, by idempotency token.
+pub struct QueryDedupWindow {
+    /// How long the store responses of a query are kept.
+    window: Duration,
+    /// The most queries whose store responses are kept at once.
+    capacity: usize,
+    entries: Mutex<DedupEntries>,
+}
+
+#[derive(Default)]
+struct DedupEntries {
+    responses: HashMap<Vec<u8>, (Instant, Vec<MultiViewStoreQueryResponse>)>,
+    /// Tokens in the order they were inserted, oldest first.
+    order: VecDeque<(Instant, Vec<u8>)>,
+}
+
+impl DedupEntries {
+    /// Forget the queries which are older than `window`, and the oldest ones
+    /// beyond `capacity`.
+    fn evict(&mut self, now: Instant, window: Duration, capacity: usize) {
+        while let Some((inserted_at, token)) = self.order.front() {
+            let expired = now.saturating_duration_since(*inserted_at) >= window;
+            if !expired && self.order.len() <= capacity {
+                break;
+            }
+            if self
+                .responses
+                .get(token)
+                .map_or(false, |(entry_inserted_at, _)| {
+                    entry_inserted_at == inserted_at
+                })
+            {
+                self.responses.remove(token);
+            }
+            self.order.pop_front();
+        }
+    }
+}
+
+impl QueryDedupWindow {
+    /// Keep the store responses of up to `capacity` queries for `window`.
+    /// A zero window turns deduplication off.
+    pub fn new(window: Duration, capacity: usize) -> Self {
+        Self {
+            window,
+            capacity,
+            entries: Default::default(),
+        }
+    }
+
+    fn is_enabled(&self, token: &[u8]) -> bool {
+        !token.is_empty() && !self.window.is_zero() && self.capacity > 0
+    }
+
+    /// The store responses of an earlier attempt of the query with `token`,
+    /// if it was made within the window.
+    pub fn get(&self, token: &[u8]) -> Option<Vec<MultiViewStoreQueryResponse>> {
+        if !self.is_enabled(token) {
+            return None;
+        }
+        let mut entries = self.entries.lock().expect("mutex poisoned");
+        entries.evict(Instant::now(), self.window, self.capacity);
+        entries
+            .responses
+            .get(token)
+            .map(|(_, responses)| responses.clone())
+    }
+
+    /// Keep the store responses of the query with `token`, replacing those of
+    /// an earlier attempt.
+    pub fn insert(&self, token: &[u8], responses: Vec<MultiViewStoreQueryResponse>) {
+        if !self.is_enabled(token) {
+            return;
+        }
+        let now = Instant::now();
+        let mut entries = self.entries.lock().expect("mutex poisoned");
+        entries.responses.insert(token.to_vec(), (now, responses));
+        entries.order.push_back((now, token.to_vec()));
+        entries.evict(now, self.window, self.capacity);
+    }
+
+    /// Forget the store responses of the query with `token`, e.g. because
+    /// they could not be collated.
+    pub fn remove(&self, token: &[u8]) {
+        self.entries
+            .lock()
+            .expect("mutex poisoned")
+            .responses
+            .remove(token);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mc_common::ResponderId;
+    use mc_fog_types::{common::BlockRange, view::MultiViewStoreQueryResponseStatus};
+    use std::str::FromStr;
+
+    fn responses(store: &str) -> Vec<MultiViewStoreQueryResponse> {
+        vec![MultiViewStoreQueryResponse {
+            encrypted_query_response: Default::default(),
+            store_responder_id: ResponderId::from_str(&format!("{store}:443")).unwrap(),
+            store_uri: format!("insecure-fog-view-store://{store}:443"),
+            block_range: BlockRange::new(0, 10),
+            status: MultiViewStoreQueryResponseStatus::Success,
+        }]
+    }
+
+    #[test]
+    fn retries_get_the_first_responses() {
+        let window = QueryDedupWindow::new(Duration::from_secs(60), 2);
+        assert!(window.get(b"a").is_none());
+
+        window.insert(b"a", responses("store-a"));
+        window.insert(b"b", responses("store-b"));
+        assert!(window.get(b"a") == Some(responses("store-a")));
+
+        // The oldest query is forgotten once the window is full.
+        window.insert(b"c", responses("store-c"));
+        assert!(window.get(b"a").is_none());
+        assert!(window.get(b"b") == Some(responses("store-b")));
+
+        window.remove(b"b");
+        assert!(window.get(b"b").is_none());
+
+        // Queries without a token are never deduplicated.
+        window.insert(b"", responses("store-d"));
+        assert!(window.get(b"").is_none());
+    }
+
+    #[test]
+    fn responses_expire() {
+        let window = QueryDedupWindow::new(Duration::from_millis(10), 10);
+        window.insert(b"a", responses("store-a"));
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(window.get(b"a").is_none());
+
+        let disabled = QueryDedupWindow::new(Duration::ZERO, 10);
+        disabled.insert(b"a", responses("store-a"));
+        assert!(disabled.get(b"a").is_none());
+    }
+}
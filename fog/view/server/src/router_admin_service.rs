@@ -8,14 +8,15 @@ use crate::{
 use grpcio::{ChannelBuilder, RpcContext, RpcStatus, UnarySink};
 use mc_common::logger::{log, Logger};
 use mc_fog_api::{
-    fog_common::AddShardRequest,
+    fog_common::{AddShardRequest, CancelQueryRequest, InFlightQueries, InFlightQuery},
     view_grpc::{FogViewRouterAdminApi, FogViewStoreApiClient},
 };
-use mc_fog_uri::FogViewStoreUri;
+use mc_fog_uri::{ConnectionUri, FogViewStoreUri};
 use mc_util_grpc::{
-    rpc_invalid_arg_error, rpc_logger, rpc_precondition_error, send_result,
-    ConnectionUriGrpcioChannel, Empty,
+    record_audit_event, rpc_invalid_arg_error, rpc_logger, rpc_precondition_error, send_result,
+    AuditEvent, ConnectionUriGrpcioChannel, Empty, InFlightRequests,
 };
+use mc_util_metrics::service_metrics;
 use std::{
     str::FromStr,
     sync::{Arc, RwLock},
@@ -24,19 +25,49 @@ use std::{
 #[derive(Clone)]
 pub struct FogViewRouterAdminService {
     shards: Arc<RwLock<Vec<Shard>>>,
+    in_flight: Arc<InFlightRequests>,
     logger: Logger,
 }
 
 impl FogViewRouterAdminService {
-    pub fn new(shards: Arc<RwLock<Vec<Shard>>>, logger: Logger) -> Self {
-        Self { shards, logger }
+    pub fn new(
+        shards: Arc<RwLock<Vec<Shard>>>,
+        in_flight: Arc<InFlightRequests>,
+        logger: Logger,
+    ) -> Self {
+        Self {
+            shards,
+            in_flight,
+            logger,
+        }
+    }
+
+    fn get_in_flight_queries_impl(&self) -> InFlightQueries {
+        let mut response = InFlightQueries::new();
+        response.set_queries(self.in_flight.list().iter().map(Into::into).collect());
+        response
+    }
+
+    fn cancel_query_impl(&self, id: u64, logger: &Logger) -> Result<InFlightQuery, RpcStatus> {
+        let info = self.in_flight.cancel(id).ok_or_else(|| {
+            rpc_precondition_error("cancel_query", format!("No in-flight query {id}"), logger)
+        })?;
+        log::info!(logger, "Cancelled in-flight query {}: {:?}", id, info);
+        record_audit_event(
+            AuditEvent::QueryCancelled {
+                method: info.method.clone(),
+                peer: info.peer.clone(),
+            },
+            logger,
+        );
+        Ok((&info).into())
     }
 
     fn add_shard_impl(&mut self, shard_uri: &str, logger: &Logger) -> Result<Empty, RpcStatus> {
         let view_store_uri = FogViewStoreUri::from_str(shard_uri).map_err(|_| {
             rpc_invalid_arg_error(
                 "add_shard",
-                format!("Shard uri string {shard_uri} is invalid"),
+                "Shard uri string is invalid".to_owned(),
                 logger,
             )
         })?;
@@ -47,7 +78,10 @@ impl FogViewRouterAdminService {
         {
             let error = rpc_precondition_error(
                 "add_shard",
-                format!("Shard uri {shard_uri} already exists in the shard list"),
+                format!(
+                    "Shard uri {} already exists in the shard list",
+                    view_store_uri.redacted()
+                ),
                 logger,
             );
             return Err(error);
@@ -63,19 +97,31 @@ impl FogViewRouterAdminService {
                 .connect_to_uri(&view_store_uri, logger),
         );
         let epoch_sharding_strategy = EpochShardingStrategy::try_from(view_store_uri.clone())
-            .unwrap_or_else(|_| panic!("Could not get sharding strategy for uri: {shard_uri:?}"));
+            .unwrap_or_else(|_| {
+                panic!(
+                    "Could not get sharding strategy for uri: {}",
+                    view_store_uri.redacted()
+                )
+            });
         let block_range = epoch_sharding_strategy.get_block_range();
-        let shard = Shard::new(view_store_uri, Arc::new(view_store_client), block_range);
+        record_audit_event(
+            AuditEvent::ShardAdded {
+                shard_uri: view_store_uri.redacted(),
+            },
+            logger,
+        );
+        let shard = Shard::new(view_store_uri, Arc::new(view_store_client), block_range)
+            .with_ingress_keys(epoch_sharding_strategy.ingress_keys().cloned());
         shards.push(shard);
 
         Ok(Empty::new())
     }
 }
 
+#[service_metrics(SVC_COUNTERS)]
 impl FogViewRouterAdminApi for FogViewRouterAdminService {
     fn add_shard(&mut self, ctx: RpcContext, request: AddShardRequest, sink: UnarySink<Empty>) {
         log::info!(self.logger, "Request received in add_shard fn");
-        let _timer = SVC_COUNTERS.req(&ctx);
         mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
             send_result(
                 ctx,
@@ -85,4 +131,31 @@ impl FogViewRouterAdminApi for FogViewRouterAdminService {
             );
         });
     }
+
+    fn get_in_flight_queries(
+        &mut self,
+        ctx: RpcContext,
+        _request: Empty,
+        sink: UnarySink<InFlightQueries>,
+    ) {
+        mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
+            send_result(ctx, sink, Ok(self.get_in_flight_queries_impl()), logger);
+        });
+    }
+
+    fn cancel_query(
+        &mut self,
+        ctx: RpcContext,
+        request: CancelQueryRequest,
+        sink: UnarySink<InFlightQuery>,
+    ) {
+        mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
+            send_result(
+                ctx,
+                sink,
+                self.cancel_query_impl(request.get_id(), logger),
+                logger,
+            );
+        });
+    }
 }
@@ -4,8 +4,10 @@ use crate::{
     error::{router_server_err_to_rpc_status, RouterServerError},
     fog_view_router_server::Shard,
     metrics::{
-        AUTH_CLIENT_REQUESTS, CLIENT_QUERY_RETRIES, ROUTER_QUERY_REQUESTS, STORE_QUERY_REQUESTS,
+        AUTH_CLIENT_REQUESTS, CLIENT_QUERY_RETRIES, DEDUPLICATED_QUERIES, ROUTER_QUERY_REQUESTS,
+        STORE_QUERY_REQUESTS,
     },
+    query_dedup::QueryDedupWindow,
     shard_responses_processor, SVC_COUNTERS,
 };
 use futures::{future::try_join_all, SinkExt, TryStreamExt};
@@ -13,18 +15,23 @@ use grpcio::{ChannelBuilder, DuplexSink, RequestStream, RpcStatus, WriteFlags};
 use mc_attest_api::attest;
 use mc_attest_enclave_api::SealedClientMessage;
 use mc_common::logger::{log, Logger};
+use mc_crypto_keys::CompressedRistrettoPublic;
 use mc_fog_api::{
     view::{FogViewRouterRequest, FogViewRouterResponse, MultiViewStoreQueryRequest},
     view_grpc::FogViewStoreApiClient,
 };
-use mc_fog_types::view::MultiViewStoreQueryResponse;
+use mc_fog_types::view::{MultiViewStoreQueryResponse, QueryRequestAAD};
 use mc_fog_uri::FogViewStoreUri;
 use mc_fog_view_enclave_api::ViewEnclaveProxy;
-use mc_util_grpc::{rpc_invalid_arg_error, ConnectionUriGrpcioChannel, ResponseStatus};
+use mc_util_grpc::{
+    record_audit_event, rpc_invalid_arg_error, AuditEvent, ConnectionUriGrpcioChannel,
+    InFlightRequest, ResponseStatus,
+};
 use mc_util_metrics::GrpcMethodName;
 use mc_util_telemetry::{create_context, tracer, BoxedTracer, FutureExt, Tracer};
 use mc_util_uri::ConnectionUri;
-use std::{sync::Arc, time::Instant};
+use protobuf::Message;
+use std::{collections::BTreeSet, sync::Arc, time::Instant};
 const RETRY_COUNT: usize = 3;
 
 /// Handles a series of requests sent by the Fog Router client.
@@ -32,6 +39,8 @@ pub async fn handle_requests<E>(
     method_name: GrpcMethodName,
     shards: Vec<Shard>,
     enclave: E,
+    query_dedup: Arc<QueryDedupWindow>,
+    in_flight: InFlightRequest,
     mut requests: RequestStream<FogViewRouterRequest>,
     mut responses: DuplexSink<FogViewRouterResponse>,
     logger: Logger,
@@ -41,7 +50,16 @@ where
 {
     while let Some(request) = requests.try_next().await? {
         let _timer = SVC_COUNTERS.req_impl(&method_name);
-        let result = handle_request(request, shards.clone(), enclave.clone(), logger.clone()).await;
+        in_flight.request_received(request.compute_size());
+        in_flight.set_state("querying shards");
+        let result = handle_request(
+            request,
+            shards.clone(),
+            enclave.clone(),
+            &query_dedup,
+            logger.clone(),
+        )
+        .await;
 
         // Perform prometheus logic before the match statement to ensure that
         // this logic is executed.
@@ -49,10 +67,15 @@ where
         SVC_COUNTERS.resp_impl(&method_name, response_status.is_success);
         SVC_COUNTERS.status_code_impl(&method_name, response_status.code);
 
+        in_flight.set_state("sending response");
         match result {
-            Ok(response) => responses.send((response, WriteFlags::default())).await?,
+            Ok(response) => {
+                in_flight.response_sent(response.compute_size());
+                responses.send((response, WriteFlags::default())).await?
+            }
             Err(rpc_status) => return responses.fail(rpc_status).await,
         }
+        in_flight.set_state("idle");
     }
     responses.close().await?;
     Ok(())
@@ -64,6 +87,7 @@ pub async fn handle_request<E>(
     mut request: FogViewRouterRequest,
     shards: Vec<Shard>,
     enclave: E,
+    query_dedup: &QueryDedupWindow,
     logger: Logger,
 ) -> Result<FogViewRouterResponse, RpcStatus>
 where
@@ -75,9 +99,16 @@ where
             handle_auth_request(enclave, request.take_auth(), logger)
         })
     } else if request.has_query() {
-        handle_query_request(request.take_query(), enclave, shards, logger, &tracer)
-            .with_context(create_context(&tracer, "router_query"))
-            .await
+        handle_query_request(
+            request.take_query(),
+            enclave,
+            shards,
+            query_dedup,
+            logger,
+            &tracer,
+        )
+        .with_context(create_context(&tracer, "router_query"))
+        .await
     } else {
         let rpc_status = rpc_invalid_arg_error(
             "Inavlid FogViewRouterRequest request",
@@ -88,6 +119,30 @@ where
     }
 }
 
+/// Selects the shards to query for a client which uses the given ingress
+/// keys, or which didn't say which ingress keys it uses.
+///
+/// Fails if there are shards, but none of them serve the client.
+pub fn select_shards(
+    shards: &[Shard],
+    ingress_keys: Option<&BTreeSet<CompressedRistrettoPublic>>,
+    logger: &Logger,
+) -> Result<Vec<Shard>, RpcStatus> {
+    let selected_shards: Vec<Shard> = shards
+        .iter()
+        .filter(|shard| shard.serves_ingress_keys(ingress_keys))
+        .cloned()
+        .collect();
+    if selected_shards.is_empty() && !shards.is_empty() {
+        return Err(rpc_invalid_arg_error(
+            "Query: select shards",
+            "No view store serves the client's ingress keys",
+            logger,
+        ));
+    }
+    Ok(selected_shards)
+}
+
 /// Handles a client's authentication request.
 pub fn handle_auth_request<E>(
     enclave: E,
@@ -108,16 +163,27 @@ where
 }
 
 /// Handles a client's query request.
+///
+/// A retry of a query whose first attempt is still in the dedup window is
+/// collated from the store responses of the first attempt, so that the client
+/// gets the same results from every attempt.
 pub async fn handle_query_request<E>(
     query: attest::Message,
     enclave: E,
     shards: Vec<Shard>,
+    query_dedup: &QueryDedupWindow,
     logger: Logger,
     tracer: &BoxedTracer,
 ) -> Result<FogViewRouterResponse, RpcStatus>
 where
     E: ViewEnclaveProxy,
 {
+    // The AAD is plaintext, and a query which doesn't decode is left for the
+    // enclave to reject.
+    let idempotency_token = mc_util_serial::decode::<QueryRequestAAD>(query.get_aad())
+        .map(|aad| aad.idempotency_token)
+        .unwrap_or_default();
+
     let sealed_query = enclave
         .decrypt_and_seal_query(query.into())
         .map_err(|err| {
@@ -128,6 +194,27 @@ where
             )
         })?;
 
+    if let Some(query_responses) = query_dedup.get(&idempotency_token) {
+        match enclave.collate_shard_query_responses(sealed_query.clone(), query_responses) {
+            Ok(query_response) => {
+                DEDUPLICATED_QUERIES.inc();
+                let mut response = FogViewRouterResponse::new();
+                response.set_query(query_response.into());
+                return Ok(response);
+            }
+            // The sessions with the stores may have been replaced since the
+            // first attempt, so query them again.
+            Err(err) => {
+                log::debug!(
+                    logger,
+                    "Could not collate the store responses of an earlier attempt: {}",
+                    err
+                );
+                query_dedup.remove(&idempotency_token);
+            }
+        }
+    }
+
     let query_responses = get_query_responses(
         sealed_query.clone(),
         enclave.clone(),
@@ -136,6 +223,7 @@ where
     )
     .with_context(create_context(tracer, "router_get_query_responses"))
     .await?;
+    query_dedup.insert(&idempotency_token, query_responses.clone());
 
     let query_response = tracer.in_span("router_collate_query_responses", |_cx| {
         enclave
@@ -309,11 +397,30 @@ async fn authenticate_view_stores<E: ViewEnclaveProxy>(
     })
 }
 
-/// Authenticates a Fog View Store that has previously not been authenticated.
+/// Authenticates a Fog View Store that has previously not been authenticated,
+/// and records the outcome in the audit log.
 async fn authenticate_view_store<E: ViewEnclaveProxy>(
     enclave: E,
     view_store_url: FogViewStoreUri,
     logger: Logger,
+) -> Result<(), RouterServerError> {
+    let store_uri = view_store_url.redacted();
+    let result = attest_view_store(enclave, view_store_url, &logger).await;
+    let event = match &result {
+        Ok(()) => AuditEvent::StoreAttested { store_uri },
+        Err(err) => AuditEvent::StoreAttestationFailed {
+            store_uri,
+            error: err.to_string(),
+        },
+    };
+    record_audit_event(event, &logger);
+    result
+}
+
+async fn attest_view_store<E: ViewEnclaveProxy>(
+    enclave: E,
+    view_store_url: FogViewStoreUri,
+    logger: &Logger,
 ) -> Result<(), RouterServerError> {
     let view_store_id = view_store_url.host_and_port_responder_id()?;
     let nonce_auth_request = enclave.view_store_init(view_store_id.clone())?;
@@ -325,7 +432,7 @@ async fn authenticate_view_store<E: ViewEnclaveProxy>(
     let view_store_client = FogViewStoreApiClient::new(
         ChannelBuilder::default_channel_builder(grpc_env)
             .keepalive_permit_without_calls(false)
-            .connect_to_uri(&view_store_url, &logger),
+            .connect_to_uri(&view_store_url, logger),
     );
 
     let auth_unary_receiver = view_store_client.auth_async(&nonce_auth_request.into())?;
@@ -22,8 +22,8 @@ use mc_fog_uri::{ConnectionUri, FogViewStoreUri};
 use mc_fog_view_enclave::ViewEnclaveProxy;
 use mc_sgx_report_cache_untrusted::ReportCacheThread;
 use mc_util_grpc::{
-    AnonymousAuthenticator, Authenticator, ConnectionUriGrpcioServer, ReadinessIndicator,
-    TokenAuthenticator,
+    AnonymousAuthenticator, AuthInterceptor, Authenticator, ConnectionUriGrpcioServer,
+    InterceptorChain, ReadinessIndicator, TokenAuthenticator,
 };
 use mc_util_telemetry::{
     block_span_builder, start_block_span, telemetry_static_key, tracer, Key, Span,
@@ -113,7 +113,7 @@ where
             Arc::new(recovery_db),
             db_poll_thread.get_shared_state(),
             uri,
-            client_authenticator,
+            InterceptorChain::new().with(AuthInterceptor::new(client_authenticator)),
             sharding_strategy,
             logger.clone(),
         ));
@@ -424,6 +424,9 @@ where
     /// a warning
     last_unblocked_at: Instant,
 
+    /// The ORAM fill percentage above which we log a warning.
+    omap_fill_warning_percent: u64,
+
     /// Logger
     logger: Logger,
 }
@@ -473,6 +476,7 @@ where
             db_fetcher_readiness_indicator,
             server_readiness_indicator,
             last_unblocked_at: Instant::now(),
+            omap_fill_warning_percent: config.omap_fill_warning_percent,
             logger,
         }
     }
@@ -632,6 +636,7 @@ where
                 // When we encounter this failure mode we will begin logging a high-priority log
                 // message every ten minutes indefinitely. The server can still serve client
                 // requests in the meantime since those execute on a separate thread.
+                self.update_oram_stats();
                 loop {
                     log::crit!(
                         self.logger,
@@ -666,7 +671,41 @@ where
                     counters::BLOCKS_ADDED_COUNT.inc();
                     counters::TXOS_ADDED_COUNT.inc_by(num_records as u64);
                 }
+
+                self.update_oram_stats();
+            }
+        }
+    }
+
+    // Export the ORAM fill level as metrics, and warn if it is getting close to
+    // overflowing. An overflow means the server must be restarted with a
+    // larger capacity, so operators want to hear about it well in advance.
+    fn update_oram_stats(&self) {
+        let stats = match self.enclave.get_oram_stats() {
+            Ok(stats) => stats,
+            Err(err) => {
+                log::error!(
+                    self.logger,
+                    "Failed getting ORAM stats from enclave: {}",
+                    err
+                );
+                return;
             }
+        };
+
+        counters::ORAM_NUM_RECORDS.set(stats.num_records as i64);
+        counters::ORAM_CAPACITY.set(stats.capacity as i64);
+        counters::ORAM_FILL_PERCENT.set(stats.fill_percent() as i64);
+        counters::ORAM_OVERFLOW_COUNT.set(stats.num_overflows as i64);
+
+        if stats.fill_percent() >= self.omap_fill_warning_percent {
+            log::warn!(
+                self.logger,
+                "ORAM is {}% full ({} of {} records), and will overflow at about 75%. Restart with a larger omap capacity.",
+                stats.fill_percent(),
+                stats.num_records,
+                stats.capacity
+            );
         }
     }
 
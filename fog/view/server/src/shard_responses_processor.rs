@@ -3,7 +3,7 @@
 use crate::{error::RouterServerError, fog_view_router_server::Shard};
 use mc_common::logger::{log, Logger};
 use mc_fog_types::view::MultiViewStoreQueryResponse;
-use mc_fog_uri::FogViewStoreUri;
+use mc_fog_uri::{ConnectionUri, FogViewStoreUri};
 use std::str::FromStr;
 
 /// The result of processing the MultiViewStoreQueryResponse from each Fog View
@@ -80,7 +80,11 @@ pub fn process_shard_responses(
             // Don't do anything if the Fog View Store isn't ready. It's already authenticated,
             // hasn't returned a new query response, and shouldn't be retried yet.
             mc_fog_types::view::MultiViewStoreQueryResponseStatus::NotReady => {
-                log::debug!(logger, "Shard {} status NotReady", response.store_uri);
+                log::debug!(
+                    logger,
+                    "Shard {} status NotReady",
+                    response.store_uri.redacted()
+                );
             }
         }
     }
@@ -4,8 +4,13 @@
 //!
 //! By determining which TxOuts to process, we are able to "shard" the set of
 //! TxOuts across Fog View Store instances.
+//!
+//! When several wallet providers share fog, each with its own ingress key, a
+//! store can also be limited to the TxOuts of some ingress keys, so that the
+//! stores of one provider never load the TxOuts of another.
 
 use mc_blockchain_types::BlockIndex;
+use mc_crypto_keys::CompressedRistrettoPublic;
 use mc_fog_types::{
     common::{BlockRange, BLOCK_RANGE_DELIMITER},
     BlockCount,
@@ -13,7 +18,15 @@ use mc_fog_types::{
 use mc_fog_uri::FogViewStoreUri;
 use mc_util_uri::ConnectionUri;
 use serde::Serialize;
-use std::str::FromStr;
+use std::{collections::BTreeSet, str::FromStr};
+
+/// Separates the block range of an epoch sharding strategy from its ingress
+/// keys.
+pub const INGRESS_KEYS_DELIMITER: char = '@';
+
+/// Separates the hex-encoded ingress keys of an epoch sharding strategy. This
+/// can't be a comma, since lists of shard URIs are comma separated.
+pub const INGRESS_KEY_DELIMITER: char = '.';
 
 /// Tells a Fog View Store for which blocks it should process TxOuts.
 pub trait ShardingStrategy {
@@ -29,6 +42,12 @@ pub trait ShardingStrategy {
 
     /// Returns the block range that this sharding strategy is responsible for.
     fn get_block_range(&self) -> BlockRange;
+
+    /// Returns true if the Fog View Store should process TxOuts written with
+    /// this ingress key.
+    fn should_process_ingress_key(&self, _ingress_key: &CompressedRistrettoPublic) -> bool {
+        true
+    }
 }
 
 /// Determines whether or not to process a block's TxOuts based on the "epoch"
@@ -37,11 +56,19 @@ pub trait ShardingStrategy {
 ///
 /// In practice, the set of Fog View Shards will contain overlapping
 /// [epoch_block_ranges] in order to obfuscate which shard processed the TxOuts.
+///
+/// The strategy is written as the block range (or `default`, for all blocks),
+/// optionally followed by `@` and the dot separated, hex-encoded ingress keys
+/// whose TxOuts the store processes, e.g. `0-10000@<key1>.<key2>`.
 #[derive(Clone, Serialize)]
 pub struct EpochShardingStrategy {
     /// If a block falls within this range, then the Fog View Store should
     /// process its TxOuts.
     epoch_block_range: BlockRange,
+
+    /// If set, the Fog View Store only processes TxOuts written with one of
+    /// these ingress keys.
+    ingress_keys: Option<BTreeSet<CompressedRistrettoPublic>>,
 }
 
 impl TryFrom<FogViewStoreUri> for EpochShardingStrategy {
@@ -67,12 +94,19 @@ impl ShardingStrategy for EpochShardingStrategy {
     fn get_block_range(&self) -> BlockRange {
         self.epoch_block_range.clone()
     }
+
+    fn should_process_ingress_key(&self, ingress_key: &CompressedRistrettoPublic) -> bool {
+        self.ingress_keys
+            .as_ref()
+            .map_or(true, |ingress_keys| ingress_keys.contains(ingress_key))
+    }
 }
 
 impl Default for EpochShardingStrategy {
     fn default() -> Self {
         Self {
             epoch_block_range: BlockRange::new(0, u64::MAX),
+            ingress_keys: None,
         }
     }
 }
@@ -81,13 +115,36 @@ impl ToString for EpochShardingStrategy {
     fn to_string(&self) -> String {
         let start_block = self.epoch_block_range.start_block;
         let end_block = self.epoch_block_range.end_block;
-        format!("{start_block}{BLOCK_RANGE_DELIMITER}{end_block}")
+        let mut result = format!("{start_block}{BLOCK_RANGE_DELIMITER}{end_block}");
+        if let Some(ingress_keys) = self.ingress_keys.as_ref() {
+            let ingress_keys = ingress_keys
+                .iter()
+                .map(|key| key.to_string())
+                .collect::<Vec<_>>()
+                .join(&INGRESS_KEY_DELIMITER.to_string());
+            result = format!("{result}{INGRESS_KEYS_DELIMITER}{ingress_keys}");
+        }
+        result
     }
 }
 
 impl EpochShardingStrategy {
     pub fn new(epoch_block_range: BlockRange) -> Self {
-        Self { epoch_block_range }
+        Self {
+            epoch_block_range,
+            ingress_keys: None,
+        }
+    }
+
+    /// Limit the strategy to the TxOuts of the given ingress keys.
+    pub fn with_ingress_keys(mut self, ingress_keys: BTreeSet<CompressedRistrettoPublic>) -> Self {
+        self.ingress_keys = Some(ingress_keys);
+        self
+    }
+
+    /// The ingress keys whose TxOuts the strategy is limited to, if any.
+    pub fn ingress_keys(&self) -> Option<&BTreeSet<CompressedRistrettoPublic>> {
+        self.ingress_keys.as_ref()
     }
 
     fn have_enough_blocks_been_processed(&self, processed_block_count: BlockCount) -> bool {
@@ -118,20 +175,48 @@ impl FromStr for EpochShardingStrategy {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.eq("default") {
-            return Ok(EpochShardingStrategy::default());
-        }
-        match BlockRange::from_str(s) {
-            Ok(block_range) => Ok(Self::new(block_range)),
-            Err(e) => Err(format!("Invalid epoch sharding strategy: {e}")),
+        let (block_range, ingress_keys) = match s.split_once(INGRESS_KEYS_DELIMITER) {
+            Some((block_range, ingress_keys)) => (block_range, Some(ingress_keys)),
+            None => (s, None),
+        };
+
+        let strategy = if block_range.eq("default") {
+            EpochShardingStrategy::default()
+        } else {
+            match BlockRange::from_str(block_range) {
+                Ok(block_range) => Self::new(block_range),
+                Err(e) => return Err(format!("Invalid epoch sharding strategy: {e}")),
+            }
+        };
+
+        match ingress_keys {
+            Some(ingress_keys) => {
+                let ingress_keys = ingress_keys
+                    .split(INGRESS_KEY_DELIMITER)
+                    .map(parse_ingress_key)
+                    .collect::<Result<BTreeSet<_>, _>>()?;
+                Ok(strategy.with_ingress_keys(ingress_keys))
+            }
+            None => Ok(strategy),
         }
     }
 }
 
+fn parse_ingress_key(s: &str) -> Result<CompressedRistrettoPublic, String> {
+    let bytes =
+        hex::decode(s).map_err(|e| format!("Invalid ingress key in sharding strategy: {e}"))?;
+    CompressedRistrettoPublic::try_from(&bytes[..])
+        .map_err(|e| format!("Invalid ingress key in sharding strategy: {e}"))
+}
+
 #[cfg(test)]
 mod epoch_sharding_strategy_tests {
     use super::*;
 
+    fn ingress_key(byte: u8) -> CompressedRistrettoPublic {
+        CompressedRistrettoPublic::try_from(&[byte; 32]).unwrap()
+    }
+
     #[test]
     fn should_process_block_block_index_is_before_epoch_start_returns_false() {
         const START_BLOCK: BlockIndex = 50;
@@ -274,4 +359,68 @@ mod epoch_sharding_strategy_tests {
 
         assert!(is_ready_to_serve_txouts)
     }
+
+    #[test]
+    fn should_process_ingress_key_without_ingress_keys_returns_true() {
+        let epoch_sharding_strategy = EpochShardingStrategy::default();
+
+        let should_process_ingress_key =
+            epoch_sharding_strategy.should_process_ingress_key(&ingress_key(1));
+
+        assert!(should_process_ingress_key)
+    }
+
+    #[test]
+    fn should_process_ingress_key_only_returns_true_for_listed_keys() {
+        let listed_key = ingress_key(1);
+        let other_key = ingress_key(2);
+        let epoch_sharding_strategy =
+            EpochShardingStrategy::default().with_ingress_keys(BTreeSet::from([listed_key]));
+
+        assert!(epoch_sharding_strategy.should_process_ingress_key(&listed_key));
+        assert!(!epoch_sharding_strategy.should_process_ingress_key(&other_key));
+    }
+
+    #[test]
+    fn from_str_parses_ingress_keys() {
+        let key1 = ingress_key(1);
+        let key2 = ingress_key(2);
+        let s = format!("50-100@{}.{}", hex::encode(key1), hex::encode(key2));
+
+        let epoch_sharding_strategy = EpochShardingStrategy::from_str(&s).unwrap();
+
+        assert_eq!(
+            epoch_sharding_strategy.get_block_range(),
+            BlockRange::new(50, 100)
+        );
+        assert_eq!(
+            epoch_sharding_strategy.ingress_keys(),
+            Some(&BTreeSet::from([key1, key2]))
+        );
+        assert_eq!(epoch_sharding_strategy.to_string(), s);
+    }
+
+    #[test]
+    fn from_str_parses_default_with_ingress_keys() {
+        let key = ingress_key(1);
+        let s = format!("default@{}", hex::encode(key));
+
+        let epoch_sharding_strategy = EpochShardingStrategy::from_str(&s).unwrap();
+
+        assert_eq!(
+            epoch_sharding_strategy.get_block_range(),
+            BlockRange::new(0, u64::MAX)
+        );
+        assert_eq!(
+            epoch_sharding_strategy.ingress_keys(),
+            Some(&BTreeSet::from([key]))
+        );
+    }
+
+    #[test]
+    fn from_str_rejects_invalid_ingress_key() {
+        assert!(EpochShardingStrategy::from_str("0-100@not-hex").is_err());
+        assert!(EpochShardingStrategy::from_str("0-100@0102").is_err());
+        assert!(EpochShardingStrategy::from_str("0-100@").is_err());
+    }
 }
@@ -88,6 +88,10 @@ impl RouterTestEnvironment {
             client_auth_token_max_lifetime: Default::default(),
             client_auth_token_secret: None,
             admin_listen_uri,
+            interceptors: Default::default(),
+            message_size: Default::default(),
+            audit_log: Default::default(),
+            query_dedup_window: Duration::from_secs(60),
         };
         let router_server = Self::create_router_server(config, store_clients, &logger);
         let router_client = Self::create_router_streaming_client(router_uri, logger);
@@ -125,6 +129,10 @@ impl RouterTestEnvironment {
             client_auth_token_max_lifetime: Default::default(),
             client_auth_token_secret: None,
             admin_listen_uri,
+            interceptors: Default::default(),
+            message_size: Default::default(),
+            audit_log: Default::default(),
+            query_dedup_window: Duration::from_secs(60),
         };
         let router_server = Self::create_router_server(config, store_clients, &logger);
         let router_client = Self::create_router_unary_client(chain_id, router_uri, logger);
@@ -222,6 +230,7 @@ impl RouterTestEnvironment {
                     client_listen_uri: uri.clone(),
                     client_auth_token_secret: None,
                     omap_capacity,
+                    omap_fill_warning_percent: 70,
                     admin_listen_uri: Default::default(),
                     client_auth_token_max_lifetime: Default::default(),
                     sharding_strategy,
@@ -0,0 +1,217 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Streaming iterators over the blocks and TxOuts of a [LedgerDB].
+//!
+//! The iterators read the ledger in batches, each in its own short-lived
+//! read-only transaction, so that a long scan neither holds the whole range
+//! in memory nor keeps a read transaction open for its duration. A long-lived
+//! LMDB read transaction stops pages freed by writers from being reused,
+//! which makes the database file grow while the ledger is being appended to.
+//!
+//! Since each batch is read in a new transaction, a scan with an open end
+//! also returns blocks and TxOuts that were appended after it started.
+
+use crate::{Error, LedgerDB};
+use mc_blockchain_types::{BlockData, BlockIndex};
+use mc_transaction_core::tx::TxOut;
+use std::{
+    collections::VecDeque,
+    ops::{Bound, RangeBounds},
+};
+
+/// The default number of blocks read in each transaction.
+pub const DEFAULT_BLOCK_BATCH_SIZE: u64 = 100;
+
+/// The default number of TxOuts read in each transaction.
+pub const DEFAULT_TX_OUT_BATCH_SIZE: u64 = 1000;
+
+/// Reads the items from `start` up to, but not including, `end` in a single
+/// transaction, stopping early at the end of the ledger.
+type ReadBatch<T> = fn(&LedgerDB, u64, u64) -> Result<Vec<T>, Error>;
+
+/// An iterator which reads the ledger in batches.
+///
+/// The iterator ends at the end of its range, or at the end of the ledger if
+/// that comes first. After yielding an error, it yields nothing more.
+pub struct LedgerIter<'a, T> {
+    ledger_db: &'a LedgerDB,
+    read_batch: ReadBatch<T>,
+    next: u64,
+    end: Option<u64>,
+    batch_size: u64,
+    buffer: VecDeque<T>,
+    done: bool,
+}
+
+/// An iterator over the blocks of a ledger, with their contents, signatures
+/// and metadata.
+pub type BlockDataIter<'a> = LedgerIter<'a, BlockData>;
+
+/// An iterator over the TxOuts of a ledger, with their global indexes.
+pub type TxOutIter<'a> = LedgerIter<'a, (u64, TxOut)>;
+
+impl<'a, T> LedgerIter<'a, T> {
+    fn new(
+        ledger_db: &'a LedgerDB,
+        read_batch: ReadBatch<T>,
+        range: impl RangeBounds<u64>,
+        batch_size: u64,
+    ) -> Self {
+        let next = match range.start_bound() {
+            Bound::Included(start) => *start,
+            Bound::Excluded(start) => start.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(end) => Some(end.saturating_add(1)),
+            Bound::Excluded(end) => Some(*end),
+            Bound::Unbounded => None,
+        };
+        Self {
+            ledger_db,
+            read_batch,
+            next,
+            end,
+            batch_size,
+            buffer: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    /// Set the number of items read in each transaction.
+    pub fn with_batch_size(mut self, batch_size: u64) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    fn read_next_batch(&mut self) -> Result<(), Error> {
+        let mut batch_end = self.next.saturating_add(self.batch_size);
+        if let Some(end) = self.end {
+            batch_end = batch_end.min(end);
+        }
+        if self.next >= batch_end {
+            return Ok(());
+        }
+
+        let batch = (self.read_batch)(self.ledger_db, self.next, batch_end)?;
+        self.next += batch.len() as u64;
+        self.buffer.extend(batch);
+        Ok(())
+    }
+}
+
+impl<'a, T> Iterator for LedgerIter<'a, T> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        if self.buffer.is_empty() {
+            if let Err(err) = self.read_next_batch() {
+                self.done = true;
+                return Some(Err(err));
+            }
+        }
+        match self.buffer.pop_front() {
+            Some(item) => Some(Ok(item)),
+            None => {
+                self.done = true;
+                None
+            }
+        }
+    }
+}
+
+impl LedgerDB {
+    /// Iterate over the blocks with indexes in `range`, reading them in
+    /// batches of [DEFAULT_BLOCK_BATCH_SIZE].
+    pub fn iter_blocks(&self, range: impl RangeBounds<BlockIndex>) -> BlockDataIter<'_> {
+        LedgerIter::new(
+            self,
+            LedgerDB::read_block_data_batch,
+            range,
+            DEFAULT_BLOCK_BATCH_SIZE,
+        )
+    }
+
+    /// Iterate over the TxOuts with global indexes in `range`, reading them
+    /// in batches of [DEFAULT_TX_OUT_BATCH_SIZE].
+    pub fn iter_tx_outs(&self, range: impl RangeBounds<u64>) -> TxOutIter<'_> {
+        LedgerIter::new(
+            self,
+            LedgerDB::read_tx_out_batch,
+            range,
+            DEFAULT_TX_OUT_BATCH_SIZE,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_utils::create_ledger, Ledger};
+    use mc_blockchain_test_utils::get_blocks;
+    use mc_transaction_core::BlockVersion;
+    use mc_util_test_helper::get_seeded_rng;
+
+    fn populated_ledger(num_blocks: usize) -> LedgerDB {
+        let mut ledger_db = create_ledger();
+        let blocks = get_blocks(
+            BlockVersion::MAX,
+            num_blocks,
+            2,
+            1,
+            2,
+            1 << 20,
+            None,
+            &mut get_seeded_rng(),
+        );
+        for block_data in &blocks {
+            ledger_db.append_block_data(block_data).unwrap();
+        }
+        ledger_db
+    }
+
+    #[test]
+    fn iter_blocks_matches_get_block_data() {
+        let ledger_db = populated_ledger(7);
+
+        let blocks = ledger_db
+            .iter_blocks(2..6)
+            .with_batch_size(3)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        let expected = (2..6)
+            .map(|index| ledger_db.get_block_data(index).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(blocks, expected);
+    }
+
+    #[test]
+    fn iter_blocks_stops_at_end_of_ledger() {
+        let ledger_db = populated_ledger(5);
+
+        assert_eq!(ledger_db.iter_blocks(..).with_batch_size(2).count(), 5);
+        assert_eq!(ledger_db.iter_blocks(3..100).count(), 2);
+        assert_eq!(ledger_db.iter_blocks(5..).count(), 0);
+    }
+
+    #[test]
+    fn iter_tx_outs_matches_get_tx_out_by_index() {
+        let ledger_db = populated_ledger(4);
+        let num_txos = ledger_db.num_txos().unwrap();
+
+        let tx_outs = ledger_db
+            .iter_tx_outs(1..=num_txos)
+            .with_batch_size(2)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(tx_outs.len() as u64, num_txos - 1);
+        for (index, tx_out) in tx_outs {
+            assert_eq!(tx_out, ledger_db.get_tx_out_by_index(index).unwrap());
+        }
+    }
+}
@@ -25,6 +25,7 @@ use mc_util_serial::{decode, encode, Message};
 use mc_util_telemetry::{
     mark_span_as_active, start_block_span, telemetry_static_key, tracer, Key, Span,
 };
+use rayon::prelude::*;
 use std::{
     fs,
     path::{Path, PathBuf},
@@ -47,6 +48,11 @@ pub const KEY_IMAGES_BY_BLOCK_DB_NAME: &str = "ledger_db:key_images_by_block";
 pub const TX_OUTS_BY_BLOCK_DB_NAME: &str = "ledger_db:tx_outs_by_block";
 pub const BLOCK_NUMBER_BY_TX_OUT_INDEX: &str = "ledger_db:block_number_by_tx_out_index";
 
+/// The number of TxOut indexes from which membership proofs are computed in
+/// parallel. Smaller requests, like those for a single ring, are not worth the
+/// overhead.
+const MIN_PARALLEL_MEMBERSHIP_PROOFS: usize = 128;
+
 /// Keys used by the `counts` database.
 pub const NUM_BLOCKS_KEY: &str = "num_blocks";
 
@@ -219,6 +225,8 @@ impl Ledger for LedgerDB {
         let file_size = self.db_file_size().unwrap_or(0);
         self.metrics.db_file_size.set(file_size as i64);
 
+        self.update_lmdb_metrics()?;
+
         Ok(())
     }
 
@@ -264,21 +272,7 @@ impl Ledger for LedgerDB {
     /// blockchain.
     fn get_block_data(&self, block_number: u64) -> Result<BlockData, Error> {
         let db_transaction = self.env.begin_ro_txn()?;
-
-        let block = self.get_block_impl(&db_transaction, block_number)?;
-        let contents = self.get_block_contents_impl(&db_transaction, block_number)?;
-        let signature = match self.get_block_signature_impl(&db_transaction, block_number) {
-            Ok(sig) => Ok(Some(sig)),
-            Err(Error::NotFound) => Ok(None),
-            Err(err) => Err(err),
-        }?;
-        let metadata = match self.get_block_metadata_impl(&db_transaction, block_number) {
-            Ok(metadata) => Ok(Some(metadata)),
-            Err(Error::NotFound) => Ok(None),
-            Err(err) => Err(err),
-        }?;
-
-        Ok(BlockData::new(block, contents, signature, metadata))
+        self.get_block_data_impl(&db_transaction, block_number)
     }
 
     /// Gets block index by a TxOut global index.
@@ -337,18 +331,23 @@ impl Ledger for LedgerDB {
     }
 
     /// Gets a proof of memberships for TxOuts with indexes `indexes`.
+    ///
+    /// Large sets of indexes are split between worker threads. All proofs are
+    /// relative to the same Merkle tree, i.e. as of the same number of TxOuts.
     fn get_tx_out_proof_of_memberships(
         &self,
         indexes: &[u64],
     ) -> Result<Vec<TxOutMembershipProof>, Error> {
+        if indexes.len() >= MIN_PARALLEL_MEMBERSHIP_PROOFS {
+            let num_tx_outs = self.num_txos()?;
+            if let Some(proofs) =
+                self.get_tx_out_proof_of_memberships_parallel(indexes, num_tx_outs)?
+            {
+                return Ok(proofs);
+            }
+        }
         let db_transaction = self.env.begin_ro_txn()?;
-        indexes
-            .iter()
-            .map(|index| {
-                self.tx_out_store
-                    .get_merkle_proof_of_membership(*index, &db_transaction)
-            })
-            .collect()
+        self.get_tx_out_proof_of_memberships_impl(indexes, &db_transaction)
     }
 
     /// Get the tx out root membership element from the tx out Merkle Tree.
@@ -430,6 +429,51 @@ impl Ledger for LedgerDB {
 }
 
 impl LedgerDB {
+    fn get_tx_out_proof_of_memberships_impl(
+        &self,
+        indexes: &[u64],
+        db_transaction: &impl Transaction,
+    ) -> Result<Vec<TxOutMembershipProof>, Error> {
+        indexes
+            .iter()
+            .map(|index| {
+                self.tx_out_store
+                    .get_merkle_proof_of_membership(*index, db_transaction)
+            })
+            .collect()
+    }
+
+    /// Compute proofs of membership in parallel, each worker using its own
+    /// read-only transaction, since LMDB transactions can't be shared between
+    /// threads.
+    ///
+    /// The proofs must all be relative to the tree of `num_tx_outs` TxOuts. If
+    /// a block is appended while the workers are starting, or if a worker
+    /// can't start a transaction (e.g. because LMDB ran out of reader slots),
+    /// this returns None, and the caller should compute the proofs in a
+    /// single transaction instead.
+    fn get_tx_out_proof_of_memberships_parallel(
+        &self,
+        indexes: &[u64],
+        num_tx_outs: u64,
+    ) -> Result<Option<Vec<TxOutMembershipProof>>, Error> {
+        let chunk_size = indexes.len().div_ceil(rayon::current_num_threads());
+        let chunks = indexes
+            .par_chunks(chunk_size.max(1))
+            .map(|chunk| {
+                let Ok(db_transaction) = self.env.begin_ro_txn() else {
+                    return Ok(None);
+                };
+                if self.tx_out_store.num_tx_outs(&db_transaction)? != num_tx_outs {
+                    return Ok(None);
+                }
+                self.get_tx_out_proof_of_memberships_impl(chunk, &db_transaction)
+                    .map(Some)
+            })
+            .collect::<Result<Option<Vec<_>>, Error>>()?;
+        Ok(chunks.map(|chunks| chunks.into_iter().flatten().collect()))
+    }
+
     /// Opens an existing Ledger Database in the given path.
     #[allow(clippy::unreadable_literal)]
     pub fn open(path: &Path) -> Result<LedgerDB, Error> {
@@ -536,6 +580,22 @@ impl LedgerDB {
         let file_size = self.db_file_size().unwrap_or(0);
         self.metrics.db_file_size.set(file_size as i64);
 
+        self.update_lmdb_metrics()?;
+
+        Ok(())
+    }
+
+    /// Update the gauges tracking how full the LMDB map is.
+    fn update_lmdb_metrics(&self) -> Result<(), Error> {
+        let info = self.env.info()?;
+        let stat = self.env.stat()?;
+        let free_pages = self.env.freelist()?;
+
+        self.metrics.observe_map_size(info.map_size() as u64);
+        self.metrics.used_pages.set(info.last_pgno() as i64 + 1);
+        self.metrics.free_pages.set(free_pages as i64);
+        self.metrics.page_size.set(stat.page_size() as i64);
+
         Ok(())
     }
 
@@ -872,6 +932,64 @@ impl LedgerDB {
         })
     }
 
+    /// Implementation of the `get_block_data` method that operates inside a
+    /// given transaction.
+    fn get_block_data_impl(
+        &self,
+        db_transaction: &impl Transaction,
+        block_number: u64,
+    ) -> Result<BlockData, Error> {
+        let block = self.get_block_impl(db_transaction, block_number)?;
+        let contents = self.get_block_contents_impl(db_transaction, block_number)?;
+        let signature = match self.get_block_signature_impl(db_transaction, block_number) {
+            Ok(sig) => Ok(Some(sig)),
+            Err(Error::NotFound) => Ok(None),
+            Err(err) => Err(err),
+        }?;
+        let metadata = match self.get_block_metadata_impl(db_transaction, block_number) {
+            Ok(metadata) => Ok(Some(metadata)),
+            Err(Error::NotFound) => Ok(None),
+            Err(err) => Err(err),
+        }?;
+
+        Ok(BlockData::new(block, contents, signature, metadata))
+    }
+
+    /// Read the blocks from `start` up to, but not including, `end`, in a
+    /// single read-only transaction. The batch stops early at the end of the
+    /// ledger.
+    pub(crate) fn read_block_data_batch(
+        &self,
+        start: u64,
+        end: u64,
+    ) -> Result<Vec<BlockData>, Error> {
+        let db_transaction = self.env.begin_ro_txn()?;
+        let num_blocks = key_bytes_to_u64(db_transaction.get(self.counts, &NUM_BLOCKS_KEY)?);
+        (start..end.min(num_blocks))
+            .map(|block_number| self.get_block_data_impl(&db_transaction, block_number))
+            .collect()
+    }
+
+    /// Read the TxOuts with global indexes from `start` up to, but not
+    /// including, `end`, in a single read-only transaction. The batch stops
+    /// early at the last TxOut in the ledger.
+    pub(crate) fn read_tx_out_batch(
+        &self,
+        start: u64,
+        end: u64,
+    ) -> Result<Vec<(u64, TxOut)>, Error> {
+        let db_transaction = self.env.begin_ro_txn()?;
+        let num_tx_outs = self.tx_out_store.num_tx_outs(&db_transaction)?;
+        (start..end.min(num_tx_outs))
+            .map(|index| {
+                let tx_out = self
+                    .tx_out_store
+                    .get_tx_out_by_index(index, &db_transaction)?;
+                Ok((index, tx_out))
+            })
+            .collect()
+    }
+
     /// Implementation of the `get_block_signature` method that operates inside
     /// a given transaction.
     fn get_block_signature_impl(
@@ -959,7 +1077,9 @@ mod ledger_db_test {
     use crate::test_utils::{add_block_contents_to_ledger, add_txos_and_key_images_to_ledger};
     use mc_blockchain_test_utils::{get_blocks, make_block_metadata};
     use mc_crypto_keys::Ed25519Pair;
-    use mc_transaction_core::{membership_proofs::compute_implied_merkle_root, BlockVersion};
+    use mc_transaction_core::{
+        constants::RING_SIZE, membership_proofs::compute_implied_merkle_root, BlockVersion,
+    };
     use mc_transaction_core_test_utils::{
         create_mint_config_tx, create_mint_config_tx_and_signers, create_mint_tx,
         create_test_tx_out, mint_config_tx_to_validated as to_validated,
@@ -1043,6 +1163,26 @@ mod ledger_db_test {
         assert_eq!(ledger_db.num_txos().unwrap(), 0);
     }
 
+    #[test]
+    // The LMDB gauges should track the map as blocks are appended.
+    fn lmdb_metrics() {
+        let mut ledger_db = create_db();
+        let metrics = &ledger_db.metrics;
+        assert_eq!(metrics.map_size.get(), MAX_LMDB_FILE_SIZE as i64);
+        assert!(metrics.page_size.get() > 0);
+        let initial_used_pages = metrics.used_pages.get();
+        assert!(initial_used_pages > 0);
+
+        populate_db(&mut ledger_db, 10, 10);
+        let metrics = &ledger_db.metrics;
+        assert!(metrics.used_pages.get() > initial_used_pages);
+        assert_eq!(metrics.map_size_growth_count.get(), 0);
+
+        metrics.observe_map_size(2 * MAX_LMDB_FILE_SIZE as u64);
+        metrics.observe_map_size(2 * MAX_LMDB_FILE_SIZE as u64);
+        assert_eq!(metrics.map_size_growth_count.get(), 1);
+    }
+
     #[test]
     // Appending a block without any minting-related transactions should correctly
     // update each LMDB database.
@@ -2709,6 +2849,35 @@ mod ledger_db_test {
         }
     }
 
+    #[test]
+    // Large batches of membership proofs, which are computed in parallel, match
+    // the proofs computed one at a time, in the order they were requested.
+    fn get_tx_out_proof_of_memberships_in_parallel() {
+        let mut ledger_db = create_db();
+        populate_db(&mut ledger_db, 10, 40);
+        let num_txos = ledger_db.num_txos().unwrap();
+        let mut rng: StdRng = SeedableRng::from_seed([7u8; 32]);
+
+        let indexes = (0..2 * MIN_PARALLEL_MEMBERSHIP_PROOFS)
+            .map(|_| rng.next_u64() % num_txos)
+            .collect::<Vec<_>>();
+        let proofs = ledger_db.get_tx_out_proof_of_memberships(&indexes).unwrap();
+        assert_eq!(proofs.len(), indexes.len());
+        for (index, proof) in indexes.iter().zip(proofs.iter()) {
+            let expected = ledger_db
+                .get_tx_out_proof_of_memberships(&[*index])
+                .unwrap();
+            assert_eq!(proof, &expected[0]);
+        }
+
+        let mut indexes = indexes;
+        indexes.push(num_txos);
+        assert_eq!(
+            ledger_db.get_tx_out_proof_of_memberships(&indexes),
+            Err(Error::TxOutIndexOutOfBounds(num_txos))
+        );
+    }
+
     // FIXME(MC-526): If these benches are not marked ignore, they get run during
     // cargo test and they are not compiled with optimizations which makes them
     // take several minutes I think they should probably be moved to
@@ -2739,4 +2908,23 @@ mod ledger_db_test {
                 .unwrap()
         })
     }
+
+    #[bench]
+    #[ignore]
+    fn bench_get_tx_out_proof_of_memberships(b: &mut Bencher) {
+        let mut ledger_db = create_db();
+        let n_blocks = 30;
+        let n_txs_per_block = 1000;
+        populate_db(&mut ledger_db, n_blocks, n_txs_per_block);
+        let num_txos = ledger_db.num_txos().unwrap();
+        let mut rng: StdRng = SeedableRng::from_seed([1u8; 32]);
+
+        // A thousand rings of 11, as a ledger router would request for a large
+        // batch of transactions.
+        let indexes = (0..1000 * RING_SIZE)
+            .map(|_| rng.next_u64() % num_txos)
+            .collect::<Vec<_>>();
+
+        b.iter(|| ledger_db.get_tx_out_proof_of_memberships(&indexes).unwrap())
+    }
 }
@@ -8,6 +8,7 @@
 extern crate test;
 
 mod error;
+mod iter;
 mod ledger_trait;
 mod metrics;
 mod mint_config_store;
@@ -20,6 +21,7 @@ pub mod tx_out_store;
 
 pub use crate::{
     error::Error,
+    iter::{BlockDataIter, LedgerIter, TxOutIter},
     ledger_db::{create_ledger_in, key_bytes_to_u64, u64_to_key_bytes, LedgerDB},
     ledger_trait::{Ledger, MockLedger},
     metrics::LedgerMetrics,
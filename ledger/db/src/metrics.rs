@@ -99,6 +99,24 @@ pub struct LedgerMetrics {
     /// The size (in bytes) of the ledger database.
     pub db_file_size: IntGauge,
 
+    /// The size (in bytes) of the LMDB memory map, i.e. how large the database
+    /// may grow before writes fail.
+    pub map_size: IntGauge,
+
+    /// The number of LMDB pages in use, up to the highest page written.
+    pub used_pages: IntGauge,
+
+    /// The number of LMDB pages on the free list, which are reused before the
+    /// database grows.
+    pub free_pages: IntGauge,
+
+    /// The size (in bytes) of an LMDB page.
+    pub page_size: IntGauge,
+
+    /// Number of times the LMDB map size was seen to grow, e.g. because
+    /// another process using the database grew it.
+    pub map_size_growth_count: IntCounter,
+
     /// Time it takes to perform append_block.
     append_block_time: Histogram,
 }
@@ -130,12 +148,42 @@ impl LedgerMetrics {
                 .gauges
                 .with_label_values(&["db_file_size", db_path_str]),
 
+            map_size: COLLECTOR
+                .gauges
+                .with_label_values(&["map_size", db_path_str]),
+
+            used_pages: COLLECTOR
+                .gauges
+                .with_label_values(&["used_pages", db_path_str]),
+
+            free_pages: COLLECTOR
+                .gauges
+                .with_label_values(&["free_pages", db_path_str]),
+
+            page_size: COLLECTOR
+                .gauges
+                .with_label_values(&["page_size", db_path_str]),
+
+            map_size_growth_count: COLLECTOR
+                .counters
+                .with_label_values(&["map_size_growth_count", db_path_str]),
+
             append_block_time: COLLECTOR
                 .duration
                 .with_label_values(&["append_block", db_path_str]),
         }
     }
 
+    /// Record the current LMDB map size, counting a growth event if it is
+    /// larger than the last one recorded.
+    pub fn observe_map_size(&self, map_size: u64) {
+        let previous = self.map_size.get();
+        if previous > 0 && map_size as i64 > previous {
+            self.map_size_growth_count.inc();
+        }
+        self.map_size.set(map_size as i64);
+    }
+
     pub fn observe_append_block_time(&self, start_time: Instant) {
         self.append_block_time
             .observe(duration_to_seconds(start_time.elapsed()));
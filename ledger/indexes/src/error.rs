@@ -0,0 +1,44 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+use displaydoc::Display;
+use mc_blockchain_types::BlockIndex;
+
+/// An error building or querying ledger indexes.
+#[derive(Debug, Display)]
+pub enum Error {
+    /// LMDB: {0}
+    Lmdb(lmdb::Error),
+
+    /// Ledger: {0}
+    Ledger(mc_ledger_db::Error),
+
+    /// IO: {0}
+    Io(std::io::Error),
+
+    /// Invalid index entry
+    InvalidEntry,
+
+    /// The ledger has {0} blocks, but {1} were indexed
+    LedgerTruncated(u64, u64),
+
+    /// Block {0} of the ledger is not the block which was indexed
+    LedgerMismatch(BlockIndex),
+}
+
+impl From<lmdb::Error> for Error {
+    fn from(err: lmdb::Error) -> Self {
+        Self::Lmdb(err)
+    }
+}
+
+impl From<mc_ledger_db::Error> for Error {
+    fn from(err: mc_ledger_db::Error) -> Self {
+        Self::Ledger(err)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
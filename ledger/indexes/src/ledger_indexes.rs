@@ -0,0 +1,533 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! LMDB-backed secondary indexes of a ledger.
+//!
+//! The ledger itself can look up TxOuts by public key and key images, but
+//! explorers also need to know where each of them is, and to list the TxOuts
+//! and mints of a token, which would otherwise take a scan of the whole
+//! ledger. These indexes are kept in their own LMDB environment, so they can
+//! be built for an existing ledger, and rebuilt by deleting them.
+
+use crate::Error;
+use lmdb::{Cursor, Database, DatabaseFlags, Environment, RwTransaction, Transaction, WriteFlags};
+use mc_blockchain_types::{Block, BlockContents, BlockIndex};
+use mc_crypto_keys::CompressedRistrettoPublic;
+use mc_ledger_db::{key_bytes_to_u64, u64_to_key_bytes, Ledger};
+use mc_transaction_core::{ring_signature::KeyImage, tx::TxOut, TokenId};
+use std::{cmp::min, path::Path};
+
+// LMDB Constants
+const MAX_LMDB_FILE_SIZE: usize = 1 << 40; // 1 TB
+
+// LMDB Database Names
+const TX_OUTS_BY_PUBLIC_KEY_DB_NAME: &str = "ledger_indexes:tx_outs_by_public_key";
+const BLOCKS_BY_KEY_IMAGE_DB_NAME: &str = "ledger_indexes:blocks_by_key_image";
+const TX_OUTS_BY_TOKEN_DB_NAME: &str = "ledger_indexes:tx_outs_by_token";
+const MINTS_BY_TOKEN_DB_NAME: &str = "ledger_indexes:mints_by_token";
+const METADATA_DB_NAME: &str = "ledger_indexes:metadata";
+
+// Metadata keys
+const NUM_BLOCKS_KEY: &str = "num_blocks";
+const LAST_BLOCK_ID_KEY: &str = "last_block_id";
+
+/// The most blocks indexed in one LMDB transaction.
+const UPDATE_BATCH_BLOCKS: u64 = 1000;
+
+/// Where a TxOut is in the ledger.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct TxOutLocation {
+    /// The block containing the TxOut.
+    pub block_index: BlockIndex,
+
+    /// The global index of the TxOut in the ledger.
+    pub tx_out_index: u64,
+}
+
+impl TxOutLocation {
+    fn to_bytes(self) -> [u8; 16] {
+        let mut bytes = [0u8; 16];
+        bytes[..8].copy_from_slice(&u64_to_key_bytes(self.block_index));
+        bytes[8..].copy_from_slice(&u64_to_key_bytes(self.tx_out_index));
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() != 16 {
+            return Err(Error::InvalidEntry);
+        }
+        Ok(Self {
+            block_index: key_bytes_to_u64(&bytes[..8]),
+            tx_out_index: key_bytes_to_u64(&bytes[8..]),
+        })
+    }
+}
+
+/// The amount of a token minted in a block.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct TokenMint {
+    /// The block containing the mint transactions.
+    pub block_index: BlockIndex,
+
+    /// The total amount minted by the block's mint transactions, saturating at
+    /// u64::MAX.
+    pub amount: u64,
+}
+
+/// Secondary indexes of the blocks of a ledger, up to the block they were
+/// last updated with.
+pub struct LedgerIndexes {
+    env: Environment,
+
+    /// TxOut public key -> TxOutLocation
+    tx_outs_by_public_key: Database,
+
+    /// Key image -> index of the block spending it
+    blocks_by_key_image: Database,
+
+    /// (token id, TxOut index) -> block index, for TxOuts with a public token
+    /// id
+    tx_outs_by_token: Database,
+
+    /// (token id, block index) -> amount minted
+    mints_by_token: Database,
+
+    /// The number of blocks indexed, and the id of the last of them
+    metadata: Database,
+}
+
+impl LedgerIndexes {
+    /// Open the indexes at the given path, creating empty ones if there are
+    /// none.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        std::fs::create_dir_all(path.as_ref())?;
+        let env = Environment::new()
+            .set_max_dbs(5)
+            .set_map_size(MAX_LMDB_FILE_SIZE)
+            .open(path.as_ref())?;
+
+        Ok(Self {
+            tx_outs_by_public_key: env
+                .create_db(Some(TX_OUTS_BY_PUBLIC_KEY_DB_NAME), DatabaseFlags::empty())?,
+            blocks_by_key_image: env
+                .create_db(Some(BLOCKS_BY_KEY_IMAGE_DB_NAME), DatabaseFlags::empty())?,
+            tx_outs_by_token: env
+                .create_db(Some(TX_OUTS_BY_TOKEN_DB_NAME), DatabaseFlags::empty())?,
+            mints_by_token: env.create_db(Some(MINTS_BY_TOKEN_DB_NAME), DatabaseFlags::empty())?,
+            metadata: env.create_db(Some(METADATA_DB_NAME), DatabaseFlags::empty())?,
+            env,
+        })
+    }
+
+    /// The number of blocks indexed.
+    pub fn num_blocks(&self) -> Result<u64, Error> {
+        let db_txn = self.env.begin_ro_txn()?;
+        match db_txn.get(self.metadata, &NUM_BLOCKS_KEY) {
+            Ok(bytes) if bytes.len() == 8 => Ok(key_bytes_to_u64(bytes)),
+            Ok(_) => Err(Error::InvalidEntry),
+            Err(lmdb::Error::NotFound) => Ok(0),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Index the blocks appended to the ledger since the last update.
+    ///
+    /// Blocks are indexed in batches, each committed as it is done, so an
+    /// interrupted update resumes after the last batch.
+    ///
+    /// Returns the number of blocks indexed.
+    pub fn update(&self, ledger: &impl Ledger) -> Result<u64, Error> {
+        let ledger_num_blocks = ledger.num_blocks()?;
+        let start_block = self.num_blocks()?;
+        if ledger_num_blocks < start_block {
+            return Err(Error::LedgerTruncated(ledger_num_blocks, start_block));
+        }
+        if let Some(last_block_index) = start_block.checked_sub(1) {
+            let db_txn = self.env.begin_ro_txn()?;
+            let last_block_id = db_txn.get(self.metadata, &LAST_BLOCK_ID_KEY)?;
+            if ledger.get_block(last_block_index)?.id.as_ref() != last_block_id {
+                return Err(Error::LedgerMismatch(last_block_index));
+            }
+        }
+
+        let mut next_block = start_block;
+        while next_block < ledger_num_blocks {
+            let end_block = min(next_block + UPDATE_BATCH_BLOCKS, ledger_num_blocks);
+            let mut db_txn = self.env.begin_rw_txn()?;
+            for block_index in next_block..end_block {
+                let block = ledger.get_block(block_index)?;
+                let block_contents = ledger.get_block_contents(block_index)?;
+                self.index_block(&mut db_txn, &block, &block_contents)?;
+                if block_index + 1 == end_block {
+                    db_txn.put(
+                        self.metadata,
+                        &LAST_BLOCK_ID_KEY,
+                        &block.id,
+                        WriteFlags::empty(),
+                    )?;
+                }
+            }
+            db_txn.put(
+                self.metadata,
+                &NUM_BLOCKS_KEY,
+                &u64_to_key_bytes(end_block),
+                WriteFlags::empty(),
+            )?;
+            db_txn.commit()?;
+            next_block = end_block;
+        }
+        Ok(next_block - start_block)
+    }
+
+    fn index_block(
+        &self,
+        db_txn: &mut RwTransaction,
+        block: &Block,
+        block_contents: &BlockContents,
+    ) -> Result<(), Error> {
+        let num_outputs = block_contents.outputs.len() as u64;
+        let first_tx_out_index = block
+            .cumulative_txo_count
+            .checked_sub(num_outputs)
+            .ok_or(Error::InvalidEntry)?;
+        for (tx_out_index, tx_out) in (first_tx_out_index..).zip(block_contents.outputs.iter()) {
+            let location = TxOutLocation {
+                block_index: block.index,
+                tx_out_index,
+            };
+            db_txn.put(
+                self.tx_outs_by_public_key,
+                &tx_out.public_key,
+                &location.to_bytes(),
+                WriteFlags::empty(),
+            )?;
+            if let Some(token_id) = public_token_id(tx_out) {
+                db_txn.put(
+                    self.tx_outs_by_token,
+                    &token_key(token_id, tx_out_index),
+                    &u64_to_key_bytes(block.index),
+                    WriteFlags::empty(),
+                )?;
+            }
+        }
+
+        for key_image in &block_contents.key_images {
+            db_txn.put(
+                self.blocks_by_key_image,
+                key_image,
+                &u64_to_key_bytes(block.index),
+                WriteFlags::empty(),
+            )?;
+        }
+
+        for mint_tx in &block_contents.mint_txs {
+            let key = token_key(mint_tx.prefix.token_id.into(), block.index);
+            let minted = match db_txn.get(self.mints_by_token, &key) {
+                Ok(bytes) if bytes.len() == 8 => key_bytes_to_u64(bytes),
+                Ok(_) => return Err(Error::InvalidEntry),
+                Err(lmdb::Error::NotFound) => 0,
+                Err(err) => return Err(err.into()),
+            };
+            db_txn.put(
+                self.mints_by_token,
+                &key,
+                &u64_to_key_bytes(minted.saturating_add(mint_tx.prefix.amount)),
+                WriteFlags::empty(),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Where the TxOut with the given public key is, if it was indexed.
+    pub fn get_tx_out_location(
+        &self,
+        public_key: &CompressedRistrettoPublic,
+    ) -> Result<Option<TxOutLocation>, Error> {
+        let db_txn = self.env.begin_ro_txn()?;
+        match db_txn.get(self.tx_outs_by_public_key, public_key) {
+            Ok(bytes) => Ok(Some(TxOutLocation::from_bytes(bytes)?)),
+            Err(lmdb::Error::NotFound) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// The index of the block which spent the given key image, if it was
+    /// indexed.
+    pub fn get_block_index_by_key_image(
+        &self,
+        key_image: &KeyImage,
+    ) -> Result<Option<BlockIndex>, Error> {
+        let db_txn = self.env.begin_ro_txn()?;
+        match db_txn.get(self.blocks_by_key_image, key_image) {
+            Ok(bytes) if bytes.len() == 8 => Ok(Some(key_bytes_to_u64(bytes))),
+            Ok(_) => Err(Error::InvalidEntry),
+            Err(lmdb::Error::NotFound) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Up to `max_tx_outs` TxOuts of the given token, in ledger order,
+    /// starting at the TxOut with global index `start_tx_out_index`.
+    ///
+    /// Only TxOuts whose token id is public are indexed, i.e. those of blocks
+    /// older than block version 2, which are all MOB.
+    pub fn get_tx_outs_by_token(
+        &self,
+        token_id: TokenId,
+        start_tx_out_index: u64,
+        max_tx_outs: usize,
+    ) -> Result<Vec<TxOutLocation>, Error> {
+        Ok(self
+            .read_token_entries(
+                self.tx_outs_by_token,
+                token_id,
+                start_tx_out_index,
+                max_tx_outs,
+            )?
+            .into_iter()
+            .map(|(tx_out_index, block_index)| TxOutLocation {
+                block_index,
+                tx_out_index,
+            })
+            .collect())
+    }
+
+    /// Up to `max_mints` blocks which minted the given token, in ledger order,
+    /// starting at block `start_block`.
+    pub fn get_mints_by_token(
+        &self,
+        token_id: TokenId,
+        start_block: BlockIndex,
+        max_mints: usize,
+    ) -> Result<Vec<TokenMint>, Error> {
+        Ok(self
+            .read_token_entries(self.mints_by_token, token_id, start_block, max_mints)?
+            .into_iter()
+            .map(|(block_index, amount)| TokenMint {
+                block_index,
+                amount,
+            })
+            .collect())
+    }
+
+    /// Read up to `max_entries` (index, value) pairs of a database keyed by
+    /// (token id, index), starting at `start_index`.
+    fn read_token_entries(
+        &self,
+        db: Database,
+        token_id: TokenId,
+        start_index: u64,
+        max_entries: usize,
+    ) -> Result<Vec<(u64, u64)>, Error> {
+        let db_txn = self.env.begin_ro_txn()?;
+        let mut cursor = db_txn.open_ro_cursor(db)?;
+        let token_prefix = u64_to_key_bytes(*token_id);
+        let mut entries = Vec::new();
+        for result in cursor.iter_from(token_key(token_id, start_index)) {
+            let (key_bytes, value_bytes) = result?;
+            if entries.len() >= max_entries || !key_bytes.starts_with(&token_prefix) {
+                break;
+            }
+            if key_bytes.len() != 16 || value_bytes.len() != 8 {
+                return Err(Error::InvalidEntry);
+            }
+            entries.push((
+                key_bytes_to_u64(&key_bytes[8..]),
+                key_bytes_to_u64(value_bytes),
+            ));
+        }
+        Ok(entries)
+    }
+}
+
+/// The token id of a TxOut, if its amount does not mask it.
+///
+/// Token ids are masked since block version 2. Before that, every TxOut was
+/// MOB.
+fn public_token_id(tx_out: &TxOut) -> Option<TokenId> {
+    let masked_amount = tx_out.get_masked_amount().ok()?;
+    masked_amount
+        .masked_token_id()
+        .is_empty()
+        .then_some(TokenId::MOB)
+}
+
+/// The key of an entry of a token index, which sorts by token, then index.
+fn token_key(token_id: TokenId, index: u64) -> [u8; 16] {
+    let mut key = [0u8; 16];
+    key[..8].copy_from_slice(&u64_to_key_bytes(*token_id));
+    key[8..].copy_from_slice(&u64_to_key_bytes(index));
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mc_blockchain_test_utils::get_blocks;
+    use mc_blockchain_types::{BlockData, BlockVersion};
+    use mc_crypto_keys::Ed25519Pair;
+    use mc_ledger_db::test_utils::{get_mock_ledger_and_blocks, MockLedger};
+    use mc_transaction_core_test_utils::create_mint_tx;
+    use mc_util_from_random::FromRandom;
+    use mc_util_test_helper::{RngType, SeedableRng};
+
+    fn append_blocks(ledger: &mut MockLedger, blocks: &[BlockData]) {
+        for block_data in blocks {
+            ledger.append_block_data(block_data).unwrap();
+        }
+    }
+
+    #[test]
+    fn indexes_new_blocks_incrementally() {
+        let dir = tempfile::tempdir().unwrap();
+        let indexes = LedgerIndexes::open(dir.path()).unwrap();
+        let (mut ledger, mut blocks) = get_mock_ledger_and_blocks(3);
+
+        assert_eq!(indexes.update(&ledger).unwrap(), 3);
+        assert_eq!(indexes.update(&ledger).unwrap(), 0);
+
+        let new_blocks = get_blocks(
+            BlockVersion::ZERO,
+            2,
+            2,
+            1,
+            1,
+            1 << 20,
+            blocks.last().unwrap().block().clone(),
+            &mut RngType::from_seed([1u8; 32]),
+        );
+        append_blocks(&mut ledger, &new_blocks);
+        blocks.extend(new_blocks);
+        drop(indexes);
+
+        let indexes = LedgerIndexes::open(dir.path()).unwrap();
+        assert_eq!(indexes.update(&ledger).unwrap(), 2);
+        assert_eq!(indexes.num_blocks().unwrap(), 5);
+
+        let mut tx_out_index = 0;
+        for block_data in &blocks {
+            let block_index = block_data.block().index;
+            for tx_out in &block_data.contents().outputs {
+                assert_eq!(
+                    indexes.get_tx_out_location(&tx_out.public_key).unwrap(),
+                    Some(TxOutLocation {
+                        block_index,
+                        tx_out_index,
+                    })
+                );
+                tx_out_index += 1;
+            }
+            for key_image in &block_data.contents().key_images {
+                assert_eq!(
+                    indexes.get_block_index_by_key_image(key_image).unwrap(),
+                    Some(block_index)
+                );
+            }
+        }
+        assert_eq!(
+            indexes
+                .get_block_index_by_key_image(&KeyImage::from(u64::MAX))
+                .unwrap(),
+            None
+        );
+
+        // Block version 0 TxOuts are all MOB.
+        let mob_tx_outs = indexes
+            .get_tx_outs_by_token(TokenId::MOB, 0, usize::MAX)
+            .unwrap();
+        assert_eq!(mob_tx_outs.len() as u64, tx_out_index);
+        assert_eq!(
+            indexes.get_tx_outs_by_token(TokenId::MOB, 3, 2).unwrap(),
+            mob_tx_outs[3..5]
+        );
+        assert_eq!(
+            indexes
+                .get_tx_outs_by_token(TokenId::from(1), 0, usize::MAX)
+                .unwrap(),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn indexes_mints_but_not_masked_token_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        let indexes = LedgerIndexes::open(dir.path()).unwrap();
+        let mut rng = RngType::from_seed([2u8; 32]);
+        let blocks = get_blocks(BlockVersion::MAX, 2, 2, 2, 1, 1 << 20, None, &mut rng);
+        let mut ledger = MockLedger::default();
+        append_blocks(&mut ledger, &blocks);
+
+        let token_id = TokenId::from(1);
+        let signers = [Ed25519Pair::from_random(&mut rng)];
+        let block_contents = BlockContents {
+            key_images: vec![KeyImage::from(7)],
+            mint_txs: vec![
+                create_mint_tx(token_id, &signers, 10, &mut rng),
+                create_mint_tx(token_id, &signers, 5, &mut rng),
+            ],
+            ..Default::default()
+        };
+        let block = Block::new_with_parent(
+            BlockVersion::MAX,
+            blocks[1].block(),
+            &Default::default(),
+            &block_contents,
+        );
+        ledger
+            .append_block(&block, &block_contents, None, None)
+            .unwrap();
+
+        assert_eq!(indexes.update(&ledger).unwrap(), 3);
+        assert!(indexes
+            .get_tx_out_location(&blocks[1].contents().outputs[0].public_key)
+            .unwrap()
+            .is_some());
+        assert_eq!(
+            indexes
+                .get_tx_outs_by_token(TokenId::MOB, 0, usize::MAX)
+                .unwrap(),
+            vec![]
+        );
+        assert_eq!(
+            indexes.get_mints_by_token(token_id, 0, usize::MAX).unwrap(),
+            vec![TokenMint {
+                block_index: 2,
+                amount: 15
+            }]
+        );
+        assert_eq!(
+            indexes.get_mints_by_token(token_id, 3, usize::MAX).unwrap(),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn updating_from_another_ledger_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let indexes = LedgerIndexes::open(dir.path()).unwrap();
+        let (ledger, _) = get_mock_ledger_and_blocks(3);
+        indexes.update(&ledger).unwrap();
+
+        let mut other_ledger = MockLedger::default();
+        let other_blocks = get_blocks(
+            BlockVersion::ZERO,
+            4,
+            2,
+            1,
+            1,
+            1 << 20,
+            None,
+            &mut RngType::from_seed([3u8; 32]),
+        );
+        append_blocks(&mut other_ledger, &other_blocks);
+        assert!(matches!(
+            indexes.update(&other_ledger),
+            Err(Error::LedgerMismatch(2))
+        ));
+
+        let (short_ledger, _) = get_mock_ledger_and_blocks(2);
+        assert!(matches!(
+            indexes.update(&short_ledger),
+            Err(Error::LedgerTruncated(2, 3))
+        ));
+    }
+}
@@ -0,0 +1,13 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Secondary indexes of a ledger, maintained incrementally as blocks are
+//! appended to it, to back block explorers and analytics.
+#![deny(missing_docs)]
+
+mod error;
+mod ledger_indexes;
+
+pub use crate::{
+    error::Error,
+    ledger_indexes::{LedgerIndexes, TokenMint, TxOutLocation},
+};
@@ -15,7 +15,8 @@ use mc_common::{
     trace_time, ResponderId,
 };
 use mc_connection::{
-    BlockchainConnection, Connection, ConnectionManager, RetryableBlockchainConnection,
+    BlockFetchPipeline, BlockchainConnection, Connection, ConnectionManager,
+    RetryableBlockchainConnection,
 };
 use mc_ledger_db::Ledger;
 use mc_transaction_core::ring_signature::KeyImage;
@@ -56,6 +57,8 @@ pub struct LedgerSyncService<
     /// Timeout for network requests.
     get_blocks_timeout: Duration,
     get_block_contents_timeout: Duration,
+    /// How blocks are requested from each peer.
+    block_fetch_pipeline: BlockFetchPipeline,
     metadata_provider: BMP,
     logger: Logger,
 }
@@ -108,10 +111,19 @@ impl<
             metadata_provider,
             get_blocks_timeout: DEFAULT_GET_BLOCKS_TIMEOUT,
             get_block_contents_timeout: DEFAULT_GET_BLOCK_CONTENTS_TIMEOUT,
+            block_fetch_pipeline: BlockFetchPipeline::default(),
             logger,
         }
     }
 
+    /// Set how blocks are requested from each peer: how many blocks per
+    /// request, and how many requests may be outstanding at once. Raising the
+    /// number of outstanding requests speeds up syncing over high-latency
+    /// links.
+    pub fn set_block_fetch_pipeline(&mut self, block_fetch_pipeline: BlockFetchPipeline) {
+        self.block_fetch_pipeline = block_fetch_pipeline;
+    }
+
     /// Identifies Blocks that are potentially safe to append to the local
     /// ledger.
     ///
@@ -152,6 +164,7 @@ impl<
             &self.manager,
             last_block,
             limit,
+            &self.block_fetch_pipeline,
             self.get_blocks_timeout,
             &self.logger,
         );
@@ -415,14 +428,18 @@ impl<
 /// * `manager` - Manager instance.
 /// * `append_after_block` - The block we're trying to append to.
 /// * `limit` - Maximal number of blocks to fetch.
+/// * `pipeline` - How blocks are requested from each peer.
 /// * `timeout` - Overall request timeout.
 ///
 /// Peers are queried concurrently, and any successful responses collected
-/// before a timeout occurs are returned.
+/// before a timeout occurs are returned. Each peer's blocks are verified as
+/// they arrive, so a peer serving a broken chain is dropped without waiting for
+/// the rest of its blocks.
 fn get_blocks<BC: BlockchainConnection + 'static>(
     manager: &ConnectionManager<BC>,
     append_after_block: Block,
     limit: u32,
+    pipeline: &BlockFetchPipeline,
     timeout: Duration,
     logger: &Logger,
 ) -> HashMap<ResponderId, Vec<Block>> {
@@ -438,13 +455,14 @@ fn get_blocks<BC: BlockchainConnection + 'static>(
     for conn in manager.conns().into_iter() {
         let thread_results_and_condvar = results_and_condvar.clone();
         let thread_append_after_block = append_after_block.clone();
+        let pipeline = *pipeline;
         let logger = logger.clone();
         thread::Builder::new()
             .name(format!("GetBlocks:{conn}"))
             .spawn(move || {
                 let (lock, condvar) = &*thread_results_and_condvar;
 
-                // Perform call to get the blocks from the peer. Blocks are later verified by `identify_safe_blocks`.
+                // Perform call to get the blocks from the peer. Block IDs are verified as they arrive, and the contents later by `identify_safe_blocks`.
                 let start = thread_append_after_block.index + 1;
                 let end = start + u64::from(limit);
                 let mut blocks_result = Vec::new();
@@ -453,17 +471,21 @@ fn get_blocks<BC: BlockchainConnection + 'static>(
                     Err(e) => {
                         log::warn!(
                             logger,
-                            "Could not insert blocks from connection with {:?} due to NodeID conversion failure {:?}",
-                            conn.uri().to_string(),
+                            "Could not insert blocks from connection with {} due to NodeID conversion failure {:?}",
+                            conn.uri().redacted(),
                             e
                         );
                         panic!("No node id");
                     }
                 };
                 match conn
-                    .fetch_blocks(start..end, Fibonacci::from_millis(10).take(5))
+                    .fetch_blocks_pipelined(
+                        start..end,
+                        Some(&thread_append_after_block),
+                        &pipeline,
+                        Fibonacci::from_millis(10).take(5),
+                    )
                     .map_err(LedgerSyncError::Consensus)
-                    .and_then(|blocks| verify_block_ids(blocks, &thread_append_after_block))
                 {
                     Ok(mut blocks) => {
                         log::debug!(logger, "Received {} blocks from {}", blocks.len(), conn);
@@ -503,27 +525,6 @@ fn get_blocks<BC: BlockchainConnection + 'static>(
         .collect()
 }
 
-fn verify_block_ids(
-    blocks: Vec<Block>,
-    append_after_block: &Block,
-) -> Result<Vec<Block>, LedgerSyncError> {
-    let mut prev_block = append_after_block;
-
-    for block in blocks.iter() {
-        if block.parent_id != prev_block.id {
-            return Err(LedgerSyncError::InvalidBlockId);
-        }
-
-        if !block.is_block_id_valid() {
-            return Err(LedgerSyncError::InvalidBlockId);
-        }
-
-        prev_block = block;
-    }
-
-    Ok(blocks)
-}
-
 /// For each block index, group nodes according to the block they externalized
 /// (if any).
 ///
@@ -996,7 +997,14 @@ mod tests {
         let conn_manager = ConnectionManager::new(vec![fast_peer, slow_peer], logger.clone());
 
         let limit: u32 = 10; // Number of blocks to get.
-        let responses = get_blocks(&conn_manager, first_block.clone(), limit, timeout, &logger);
+        let responses = get_blocks(
+            &conn_manager,
+            first_block.clone(),
+            limit,
+            &BlockFetchPipeline::default(),
+            timeout,
+            &logger,
+        );
 
         // Only node 1 should be in the responses.
         assert!(responses.contains_key(&test_peer_uri(1).responder_id().unwrap()));
@@ -1013,6 +1021,40 @@ mod tests {
         }
     }
 
+    #[test_with_logger]
+    // `get_blocks` should join up the blocks of several requests, stopping at
+    // the end of the peer's ledger.
+    fn test_get_blocks_in_several_requests(logger: Logger) {
+        let local_node_id = test_node_id(123);
+
+        let timeout = Duration::from_millis(1000);
+        let ledger = get_mock_ledger(25);
+        let first_block = ledger.get_block(0).unwrap();
+        let peer = MockPeerConnection::new(test_peer_uri(1), local_node_id, ledger.clone(), 0);
+        let conn_manager = ConnectionManager::new(vec![peer], logger.clone());
+        let pipeline = BlockFetchPipeline {
+            blocks_per_request: 4,
+            max_outstanding_requests: 2,
+        };
+
+        for (limit, expected_num_blocks) in [(10, 10), (24, 24), (30, 24)] {
+            let responses = get_blocks(
+                &conn_manager,
+                first_block.clone(),
+                limit,
+                &pipeline,
+                timeout,
+                &logger,
+            );
+
+            let blocks_received = &responses[&test_peer_uri(1).responder_id().unwrap()];
+            assert_eq!(blocks_received.len(), expected_num_blocks);
+            for (i, block) in blocks_received.iter().enumerate() {
+                assert_eq!(*block, ledger.get_block(i as u64 + 1).unwrap());
+            }
+        }
+    }
+
     #[test_with_logger]
     // `get_block_contents` should get correct transactions for the indicated
     // blocks.
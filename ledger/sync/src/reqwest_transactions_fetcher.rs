@@ -3,6 +3,13 @@
 //! Implementation of the `TransactionsFetcher` trait that fetches transactions
 //! data over http(s) using the `reqwest` library. It can be used, for example,
 //! to get transaction data from S3.
+//!
+//! When several archive sources are configured, consecutive ranges of blocks
+//! are assigned to the sources in turn, so that concurrent fetches of
+//! different ranges are spread across all of them. Every block is verified
+//! before it is handed out, and a source which fails or serves invalid blocks
+//! is skipped in favor of the next one. Downloads are accounted per source,
+//! see [ReqwestTransactionsFetcher::source_stats].
 
 use crate::transactions_fetcher_trait::{TransactionFetcherError, TransactionsFetcher};
 use displaydoc::Display;
@@ -16,11 +23,12 @@ use mc_common::{
 use protobuf::Message;
 use reqwest::Error as ReqwestError;
 use std::{
-    fs,
+    fs, slice,
     sync::{
         atomic::{AtomicU64, Ordering},
         Arc, Mutex,
     },
+    time::{Duration, Instant},
 };
 use url::Url;
 
@@ -64,6 +72,49 @@ impl From<ReqwestError> for ReqwestTransactionsFetcherError {
 
 impl TransactionFetcherError for ReqwestTransactionsFetcherError {}
 
+/// Download accounting for one archive source.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SourceStats {
+    /// The source's URL.
+    pub url: Url,
+
+    /// Number of objects requested from the source.
+    pub num_requests: u64,
+
+    /// Number of requests which failed, including objects which could not be
+    /// parsed.
+    pub num_failed_requests: u64,
+
+    /// Number of objects with blocks which failed verification, or which
+    /// conflicted with blocks from another source.
+    pub num_invalid_objects: u64,
+
+    /// Number of bytes downloaded from the source.
+    pub bytes_downloaded: u64,
+
+    /// Time spent downloading from the source.
+    pub download_time: Duration,
+}
+
+impl SourceStats {
+    /// The average download speed from this source, if anything was
+    /// downloaded.
+    pub fn bytes_per_second(&self) -> Option<f64> {
+        let seconds = self.download_time.as_secs_f64();
+        (seconds > 0.0).then(|| self.bytes_downloaded as f64 / seconds)
+    }
+}
+
+/// Counters behind [SourceStats], shared by the clones of a fetcher.
+#[derive(Default)]
+struct SourceCounters {
+    num_requests: AtomicU64,
+    num_failed_requests: AtomicU64,
+    num_invalid_objects: AtomicU64,
+    bytes_downloaded: AtomicU64,
+    download_micros: AtomicU64,
+}
+
 #[derive(Clone)]
 pub struct ReqwestTransactionsFetcher {
     /// List of URLs to try and fetch objects from.
@@ -75,8 +126,8 @@ pub struct ReqwestTransactionsFetcher {
     /// Logger.
     logger: Logger,
 
-    /// The most recently used URL index (in `source_urls`).
-    source_index_counter: Arc<AtomicU64>,
+    /// Download accounting for each of `source_urls`.
+    source_counters: Arc<Vec<SourceCounters>>,
 
     /// Cache mapping a `BlockIndex` to `BlockData`, filled by merged blocks
     /// when possible.
@@ -123,11 +174,13 @@ impl ReqwestTransactionsFetcher {
             })
             .collect();
 
+        let source_urls = source_urls?;
+        let source_counters = source_urls.iter().map(|_| Default::default()).collect();
         Ok(Self {
-            source_urls: source_urls?,
+            source_urls,
             client,
             logger,
-            source_index_counter: Arc::new(AtomicU64::new(0)),
+            source_counters: Arc::new(source_counters),
             blocks_cache: Arc::new(Mutex::new(LruCache::new(MAX_PREFETCHED_BLOCKS))),
             merged_blocks_bucket_sizes: DEFAULT_MERGED_BLOCKS_BUCKET_SIZES.to_vec(),
             hits: Arc::new(AtomicU64::new(0)),
@@ -139,6 +192,59 @@ impl ReqwestTransactionsFetcher {
         self.merged_blocks_bucket_sizes = bucket_sizes.to_vec();
     }
 
+    /// Download accounting for each source, in the order of `source_urls`.
+    pub fn source_stats(&self) -> Vec<SourceStats> {
+        self.source_urls
+            .iter()
+            .zip(self.source_counters.iter())
+            .map(|(url, counters)| SourceStats {
+                url: url.clone(),
+                num_requests: counters.num_requests.load(Ordering::SeqCst),
+                num_failed_requests: counters.num_failed_requests.load(Ordering::SeqCst),
+                num_invalid_objects: counters.num_invalid_objects.load(Ordering::SeqCst),
+                bytes_downloaded: counters.bytes_downloaded.load(Ordering::SeqCst),
+                download_time: Duration::from_micros(
+                    counters.download_micros.load(Ordering::SeqCst),
+                ),
+            })
+            .collect()
+    }
+
+    /// The number of consecutive blocks assigned to each source in turn. This
+    /// is the smallest merged blocks bucket size, so that each merged block
+    /// is only fetched from one source.
+    fn source_range_size(&self) -> u64 {
+        self.merged_blocks_bucket_sizes
+            .iter()
+            .copied()
+            .min()
+            .unwrap_or(1)
+            .max(1)
+    }
+
+    /// The indexes in `source_urls` to try fetching `block_index` from, in
+    /// order: the source its range is assigned to, then the others.
+    fn sources_for_block(&self, block_index: BlockIndex) -> impl Iterator<Item = usize> {
+        let num_sources = self.source_urls.len();
+        let first = (block_index / self.source_range_size()) as usize % num_sources.max(1);
+        (0..num_sources).map(move |offset| (first + offset) % num_sources)
+    }
+
+    /// The index in `source_urls` of the source `url` belongs to, if any.
+    fn source_of_url(&self, url: &Url) -> Option<usize> {
+        self.source_urls
+            .iter()
+            .position(|source_url| url.as_str().starts_with(source_url.as_str()))
+    }
+
+    fn count_invalid_object(&self, url: &Url) {
+        if let Some(source_index) = self.source_of_url(url) {
+            self.source_counters[source_index]
+                .num_invalid_objects
+                .fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
     pub fn block_from_url(&self, url: &Url) -> Result<BlockData, ReqwestTransactionsFetcherError> {
         let archive_block: blockchain::ArchiveBlock = self.fetch_protobuf_object(url)?;
 
@@ -146,6 +252,16 @@ impl ReqwestTransactionsFetcher {
             ReqwestTransactionsFetcherError::InvalidBlockReceived(url.to_string(), err.to_string())
         })?;
 
+        if let Err(reason) =
+            verify_blocks_data(block_data.block().index, slice::from_ref(&block_data))
+        {
+            self.count_invalid_object(url);
+            return Err(ReqwestTransactionsFetcherError::InvalidBlockReceived(
+                url.to_string(),
+                reason,
+            ));
+        }
+
         Ok(block_data)
     }
 
@@ -156,9 +272,21 @@ impl ReqwestTransactionsFetcher {
     ) -> Result<Vec<BlockData>, ReqwestTransactionsFetcherError> {
         let archive_blocks: blockchain::ArchiveBlocks = self.fetch_protobuf_object(url)?;
 
-        Vec::<BlockData>::try_from(&archive_blocks).map_err(|err| {
+        let blocks_data = Vec::<BlockData>::try_from(&archive_blocks).map_err(|err| {
             ReqwestTransactionsFetcherError::InvalidBlockReceived(url.to_string(), err.to_string())
-        })
+        })?;
+
+        if let Some(first_block_data) = blocks_data.first() {
+            if let Err(reason) = verify_blocks_data(first_block_data.block().index, &blocks_data) {
+                self.count_invalid_object(url);
+                return Err(ReqwestTransactionsFetcherError::InvalidBlockReceived(
+                    url.to_string(),
+                    reason,
+                ));
+            }
+        }
+
+        Ok(blocks_data)
     }
 
     pub fn get_origin_block_and_transactions(
@@ -171,6 +299,40 @@ impl ReqwestTransactionsFetcher {
         &self,
         url: &Url,
     ) -> Result<M, ReqwestTransactionsFetcherError> {
+        let counters = self
+            .source_of_url(url)
+            .map(|source_index| &self.source_counters[source_index]);
+        if let Some(counters) = counters {
+            counters.num_requests.fetch_add(1, Ordering::SeqCst);
+        }
+
+        let start = Instant::now();
+        let result = self.fetch_protobuf_object_uncounted(url);
+
+        if let Some(counters) = counters {
+            counters
+                .download_micros
+                .fetch_add(start.elapsed().as_micros() as u64, Ordering::SeqCst);
+            match &result {
+                Ok((_obj, num_bytes)) => {
+                    counters
+                        .bytes_downloaded
+                        .fetch_add(*num_bytes, Ordering::SeqCst);
+                }
+                Err(_) => {
+                    counters.num_failed_requests.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+        }
+
+        result.map(|(obj, _num_bytes)| obj)
+    }
+
+    /// Fetch and parse an object, returning it with its size in bytes.
+    fn fetch_protobuf_object_uncounted<M: Message>(
+        &self,
+        url: &Url,
+    ) -> Result<(M, u64), ReqwestTransactionsFetcherError> {
         // Special treatment for file:// to read from a local directory.
         let bytes: Vec<u8> = if url.scheme() == "file" {
             let path = &url[url::Position::BeforeHost..url::Position::AfterPath];
@@ -194,7 +356,7 @@ impl ReqwestTransactionsFetcher {
             )
         })?;
 
-        Ok(obj)
+        Ok((obj, bytes.len() as u64))
     }
 
     fn get_cached_block_data(
@@ -261,10 +423,38 @@ impl ReqwestTransactionsFetcher {
             return Ok(cached_block_data);
         }
 
-        // Get the source to fetch from.
-        let source_index_counter =
-            self.source_index_counter.fetch_add(1, Ordering::SeqCst) as usize;
-        let source_url = &self.source_urls[source_index_counter % self.source_urls.len()];
+        if self.source_urls.is_empty() {
+            return Err(ReqwestTransactionsFetcherError::NoUrlsConfigured);
+        }
+
+        // Try the source this block's range is assigned to first, falling back
+        // to the others if it fails.
+        let mut last_error = ReqwestTransactionsFetcherError::NoUrlsConfigured;
+        for source_index in self.sources_for_block(block_index) {
+            match self.get_block_data_from_source(source_index, block_index, expected_block) {
+                Ok(block_data) => return Ok(block_data),
+                Err(err) => {
+                    log::debug!(
+                        self.logger,
+                        "Failed getting block #{} from {}: {}",
+                        block_index,
+                        self.source_urls[source_index],
+                        err
+                    );
+                    last_error = err;
+                }
+            }
+        }
+        Err(last_error)
+    }
+
+    fn get_block_data_from_source(
+        &self,
+        source_index: usize,
+        block_index: BlockIndex,
+        expected_block: Option<&Block>,
+    ) -> Result<BlockData, ReqwestTransactionsFetcherError> {
+        let source_url = &self.source_urls[source_index];
 
         // Try and fetch a merged block if we stand a chance of finding one.
         for bucket in self.merged_blocks_bucket_sizes.iter() {
@@ -296,7 +486,23 @@ impl ReqwestTransactionsFetcher {
                     {
                         let mut blocks_cache = self.blocks_cache.lock().expect("mutex poisoned");
                         for block_data in blocks_data.into_iter() {
-                            blocks_cache.put(block_data.block().index, block_data);
+                            let index = block_data.block().index;
+                            // Another source's merged block may already have cached this block.
+                            // Both verified, so if they differ the sources disagree on the
+                            // chain, and neither copy is trusted over the other.
+                            let conflicting = blocks_cache
+                                .put(index, block_data.clone())
+                                .filter(|cached| *cached != block_data);
+                            if conflicting.is_some() {
+                                log::warn!(
+                                    self.logger,
+                                    "{} disagrees with another source about block #{}",
+                                    url,
+                                    index
+                                );
+                                blocks_cache.pop(&index);
+                                self.count_invalid_object(&url);
+                            }
                         }
                     }
 
@@ -355,6 +561,33 @@ impl ReqwestTransactionsFetcher {
     }
 }
 
+/// Check that `blocks_data` are consecutive blocks starting at `first_index`,
+/// each with a valid ID and the contents it commits to, and each the parent of
+/// the next.
+fn verify_blocks_data(first_index: BlockIndex, blocks_data: &[BlockData]) -> Result<(), String> {
+    for (block_data, index) in blocks_data.iter().zip(first_index..) {
+        let block = block_data.block();
+        if block.index != index {
+            return Err(format!("expected block #{index}, got #{}", block.index));
+        }
+        if !block.is_block_id_valid() {
+            return Err(format!("block #{index} has an invalid id"));
+        }
+        if block_data.contents().hash() != block.contents_hash {
+            return Err(format!("block #{index} contents hash mismatch"));
+        }
+    }
+    for pair in blocks_data.windows(2) {
+        if pair[1].block().parent_id != pair[0].block().id {
+            return Err(format!(
+                "block #{} is not the parent of the next block",
+                pair[0].block().index
+            ));
+        }
+    }
+    Ok(())
+}
+
 impl TransactionsFetcher for ReqwestTransactionsFetcher {
     type Error = ReqwestTransactionsFetcherError;
 
@@ -366,3 +599,124 @@ impl TransactionsFetcher for ReqwestTransactionsFetcher {
         self.get_block_data_by_index(block.index, Some(block))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mc_blockchain_test_utils::get_blocks;
+    use mc_blockchain_types::BlockVersion;
+    use mc_common::logger::{test_with_logger, Logger};
+    use mc_util_test_helper::get_seeded_rng;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    fn write_object(dir: &Path, path: std::path::PathBuf, obj: &impl Message) {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, obj.write_to_bytes().unwrap()).unwrap();
+    }
+
+    /// Write `blocks_data` to a new archive directory, as single blocks and as
+    /// merged blocks of `bucket_size`.
+    fn write_archive(blocks_data: &[BlockData], bucket_size: u64) -> TempDir {
+        let dir = TempDir::new().unwrap();
+        for block_data in blocks_data {
+            write_object(
+                dir.path(),
+                block_num_to_s3block_path(block_data.block().index),
+                &blockchain::ArchiveBlock::from(block_data),
+            );
+        }
+        for chunk in blocks_data.chunks(bucket_size as usize) {
+            write_object(
+                dir.path(),
+                merged_block_num_to_s3block_path(bucket_size, chunk[0].block().index),
+                &blockchain::ArchiveBlocks::from(chunk),
+            );
+        }
+        dir
+    }
+
+    fn fetcher(
+        archives: &[&TempDir],
+        bucket_size: u64,
+        logger: Logger,
+    ) -> ReqwestTransactionsFetcher {
+        let urls = archives
+            .iter()
+            .map(|dir| Url::from_directory_path(dir.path()).unwrap().to_string())
+            .collect();
+        let mut fetcher = ReqwestTransactionsFetcher::new(urls, logger).unwrap();
+        fetcher.set_merged_blocks_bucket_sizes(&[bucket_size]);
+        fetcher
+    }
+
+    #[test_with_logger]
+    fn block_ranges_are_spread_across_sources(logger: Logger) {
+        let mut rng = get_seeded_rng();
+        let blocks_data = get_blocks(BlockVersion::MAX, 20, 2, 1, 1, 1000, None, &mut rng);
+        let archive_a = write_archive(&blocks_data, 5);
+        let archive_b = write_archive(&blocks_data, 5);
+        let fetcher = fetcher(&[&archive_a, &archive_b], 5, logger);
+
+        for block_data in &blocks_data {
+            let fetched = fetcher
+                .get_block_data_by_index(block_data.block().index, Some(block_data.block()))
+                .unwrap();
+            assert_eq!(fetched, *block_data);
+        }
+
+        // Each source served two merged blocks, and nothing else.
+        for stats in fetcher.source_stats() {
+            assert_eq!(stats.num_requests, 2);
+            assert_eq!(stats.num_failed_requests, 0);
+            assert_eq!(stats.num_invalid_objects, 0);
+            assert!(stats.bytes_downloaded > 0);
+        }
+    }
+
+    #[test_with_logger]
+    fn failures_fall_back_to_another_source(logger: Logger) {
+        let mut rng = get_seeded_rng();
+        let blocks_data = get_blocks(BlockVersion::MAX, 10, 2, 1, 1, 1000, None, &mut rng);
+
+        // Source A, assigned blocks 0..5, serves them with altered contents,
+        // which no longer match their blocks.
+        let mut tampered = blocks_data.clone();
+        for block_data in &mut tampered[..5] {
+            let mut contents = block_data.contents().clone();
+            contents.outputs.pop();
+            *block_data = BlockData::new(block_data.block().clone(), contents, None, None);
+        }
+        let archive_a = write_archive(&tampered, 5);
+
+        // Source B, assigned blocks 5..10, has lost them.
+        let archive_b = write_archive(&blocks_data[..5], 5);
+
+        let fetcher = fetcher(&[&archive_a, &archive_b], 5, logger);
+        for index in [2, 7] {
+            assert_eq!(
+                fetcher
+                    .get_block_data_by_index(index, Some(blocks_data[index as usize].block()))
+                    .unwrap(),
+                blocks_data[index as usize]
+            );
+        }
+
+        let stats = fetcher.source_stats();
+        assert_eq!(stats[0].num_invalid_objects, 1);
+        assert_eq!(stats[0].num_failed_requests, 0);
+        assert_eq!(stats[1].num_invalid_objects, 0);
+        assert_eq!(stats[1].num_failed_requests, 1);
+    }
+
+    #[test]
+    fn blocks_data_must_form_a_chain() {
+        let mut rng = get_seeded_rng();
+        let blocks_data = get_blocks(BlockVersion::MAX, 4, 1, 1, 1, 1000, None, &mut rng);
+
+        assert_eq!(verify_blocks_data(0, &blocks_data), Ok(()));
+        assert!(verify_blocks_data(1, &blocks_data).is_err());
+        assert!(verify_blocks_data(0, &[blocks_data[0].clone(), blocks_data[2].clone()]).is_err());
+    }
+}
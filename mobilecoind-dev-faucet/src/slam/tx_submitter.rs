@@ -57,6 +57,7 @@ impl TxSubmitter {
                 ConnectionError::TransactionValidation(
                     ProposeTxResult::TombstoneBlockExceeded,
                     _,
+                    _,
                 ) => {
                     log::debug!(logger, "Transaction {} tombstone block exceeded", counter);
                     Err(SubmitTxError::Rebuild)
@@ -64,6 +65,7 @@ impl TxSubmitter {
                 ConnectionError::TransactionValidation(
                     ProposeTxResult::ContainsSpentKeyImage,
                     _,
+                    _,
                 ) => {
                     log::info!(logger, "Transaction {} contains a spent key image", counter);
                     Err(SubmitTxError::Fatal)
@@ -0,0 +1,363 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Streams balance changes to subscribers as monitors process blocks, so
+//! that clients don't have to poll for them.
+//!
+//! Each subscription follows the processed blocks of one monitor, starting at
+//! the monitor's next block when it subscribes, and reports every block in
+//! which a subscribed subaddress received or spent TxOuts. A subscriber which
+//! falls behind is not sent more changes until it catches up, so none are
+//! lost, and the subscription ends when its receiver is dropped.
+
+use crate::{
+    database::Database,
+    error::Error,
+    monitor_store::MonitorId,
+    processed_block_store::{ProcessedTxOut, ProcessedTxOutDirection},
+};
+use futures::channel::mpsc::{channel, Receiver, Sender};
+use mc_common::logger::{log, Logger};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+/// How many changes may wait to be sent to a subscriber before the
+/// subscription stops reading blocks.
+const SUBSCRIPTION_BUFFER_SIZE: usize = 64;
+
+/// The TxOuts of one token which a subaddress received and spent in a block.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BalanceChange {
+    /// The monitor the subaddress belongs to.
+    pub monitor_id: MonitorId,
+
+    /// The subaddress whose balance changed.
+    pub subaddress_index: u64,
+
+    /// The token whose balance changed.
+    pub token_id: u64,
+
+    /// The block whose processing changed the balance.
+    pub block_index: u64,
+
+    /// The total value of the TxOuts received in the block.
+    pub received: u64,
+
+    /// The total value of the TxOuts spent in the block.
+    pub spent: u64,
+
+    /// The balance when the change was sent, which includes the block and
+    /// possibly later ones.
+    pub balance: u64,
+
+    /// The TxOuts received and spent in the block.
+    pub tx_outs: Vec<ProcessedTxOut>,
+}
+
+/// Group the TxOuts of a processed block into changes of the balances of the
+/// subaddresses accepted by `is_subscribed`, with their balances still
+/// unset.
+fn block_changes(
+    monitor_id: &MonitorId,
+    block_index: u64,
+    tx_outs: Vec<ProcessedTxOut>,
+    is_subscribed: impl Fn(u64) -> bool,
+) -> Vec<BalanceChange> {
+    let mut changes = BTreeMap::<(u64, u64), BalanceChange>::new();
+    for tx_out in tx_outs {
+        if !is_subscribed(tx_out.subaddress_index) {
+            continue;
+        }
+        let received = match ProcessedTxOutDirection::try_from(tx_out.direction) {
+            Ok(ProcessedTxOutDirection::Received) => true,
+            Ok(ProcessedTxOutDirection::Spent) => false,
+            _ => continue,
+        };
+        let change = changes
+            .entry((tx_out.subaddress_index, tx_out.token_id))
+            .or_insert_with(|| BalanceChange {
+                monitor_id: *monitor_id,
+                subaddress_index: tx_out.subaddress_index,
+                token_id: tx_out.token_id,
+                block_index,
+                received: 0,
+                spent: 0,
+                balance: 0,
+                tx_outs: Vec::new(),
+            });
+        if received {
+            change.received = change.received.saturating_add(tx_out.value);
+        } else {
+            change.spent = change.spent.saturating_add(tx_out.value);
+        }
+        change.tx_outs.push(tx_out);
+    }
+    changes.into_values().collect()
+}
+
+struct Subscription {
+    monitor_id: MonitorId,
+    /// The subscribed subaddresses, or all of the monitor's if empty.
+    subaddress_indices: Vec<u64>,
+    /// The next block to report.
+    next_block: u64,
+    /// Changes waiting for room in the channel.
+    pending: VecDeque<BalanceChange>,
+    sender: Sender<BalanceChange>,
+}
+
+impl Subscription {
+    fn is_subscribed(&self, subaddress_index: u64) -> bool {
+        self.subaddress_indices.is_empty() || self.subaddress_indices.contains(&subaddress_index)
+    }
+
+    /// Send as many pending changes as there is room for, returning false if
+    /// the subscriber has gone away.
+    fn flush(&mut self) -> bool {
+        while let Some(change) = self.pending.pop_front() {
+            if let Err(err) = self.sender.try_send(change) {
+                if err.is_disconnected() {
+                    return false;
+                }
+                self.pending.push_front(err.into_inner());
+                break;
+            }
+        }
+        true
+    }
+
+    /// Read the blocks the monitor processed since the last poll, while the
+    /// subscriber keeps up, returning false if it has gone away.
+    fn poll(&mut self, mobilecoind_db: &Database, logger: &Logger) -> bool {
+        if !self.flush() {
+            return false;
+        }
+
+        let monitor_next_block = match mobilecoind_db.get_monitor_data(&self.monitor_id) {
+            Ok(data) => data.next_block,
+            Err(err) => {
+                log::error!(
+                    logger,
+                    "Error getting data of subscribed monitor {}: {:?}",
+                    self.monitor_id,
+                    err
+                );
+                return true;
+            }
+        };
+
+        while self.pending.is_empty() && self.next_block < monitor_next_block {
+            let tx_outs =
+                match mobilecoind_db.get_processed_block(&self.monitor_id, self.next_block) {
+                    Ok(tx_outs) => tx_outs,
+                    Err(err) => {
+                        log::error!(
+                            logger,
+                            "Error getting processed block {} for monitor {}: {:?}",
+                            self.next_block,
+                            self.monitor_id,
+                            err
+                        );
+                        return true;
+                    }
+                };
+
+            let mut changes = block_changes(&self.monitor_id, self.next_block, tx_outs, |index| {
+                self.is_subscribed(index)
+            });
+            for change in changes.iter_mut() {
+                match balance(mobilecoind_db, change) {
+                    Ok(balance) => change.balance = balance,
+                    Err(err) => {
+                        log::error!(
+                            logger,
+                            "Error getting balance of subaddress {} of monitor {}: {:?}",
+                            change.subaddress_index,
+                            self.monitor_id,
+                            err
+                        );
+                        return true;
+                    }
+                }
+            }
+
+            self.pending.extend(changes);
+            self.next_block += 1;
+            if !self.flush() {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// The current balance of the subaddress and token of a change, saturating
+/// at u64::MAX.
+fn balance(mobilecoind_db: &Database, change: &BalanceChange) -> Result<u64, Error> {
+    let balance = mobilecoind_db
+        .get_utxos_for_subaddress(&change.monitor_id, change.subaddress_index)?
+        .iter()
+        .filter(|utxo| utxo.token_id == change.token_id)
+        .map(|utxo| utxo.value as u128)
+        .sum::<u128>();
+    Ok(u64::try_from(balance).unwrap_or(u64::MAX))
+}
+
+/// The balance subscriptions of the API's clients.
+#[derive(Default)]
+pub struct BalanceSubscriptions {
+    subscriptions: Mutex<Vec<Subscription>>,
+}
+
+impl BalanceSubscriptions {
+    /// Subscribe to the balance changes of a monitor's subaddresses, or of
+    /// all of them if `subaddress_indices` is empty, starting at
+    /// `next_block`.
+    pub fn subscribe(
+        &self,
+        monitor_id: MonitorId,
+        subaddress_indices: Vec<u64>,
+        next_block: u64,
+    ) -> Receiver<BalanceChange> {
+        let (sender, receiver) = channel(SUBSCRIPTION_BUFFER_SIZE);
+        self.subscriptions
+            .lock()
+            .expect("mutex poisoned")
+            .push(Subscription {
+                monitor_id,
+                subaddress_indices,
+                next_block,
+                pending: VecDeque::new(),
+                sender,
+            });
+        receiver
+    }
+
+    /// Send each subscriber the changes of the blocks processed since the
+    /// last poll, and forget the subscribers which have gone away.
+    fn poll(&self, mobilecoind_db: &Database, logger: &Logger) {
+        self.subscriptions
+            .lock()
+            .expect("mutex poisoned")
+            .retain_mut(|subscription| subscription.poll(mobilecoind_db, logger));
+    }
+}
+
+/// Balance Subscription Thread - holds objects needed to cleanly terminate
+/// the thread which feeds balance subscriptions.
+pub struct BalanceSubscriptionThread {
+    /// The main thread handle.
+    join_handle: Option<thread::JoinHandle<()>>,
+
+    /// Stop trigger, used to signal the thread to terminate.
+    stop_requested: Arc<AtomicBool>,
+}
+
+impl BalanceSubscriptionThread {
+    pub fn start(
+        mobilecoind_db: Database,
+        subscriptions: Arc<BalanceSubscriptions>,
+        poll_interval: Duration,
+        logger: Logger,
+    ) -> Self {
+        let stop_requested = Arc::new(AtomicBool::new(false));
+
+        let thread_stop_requested = stop_requested.clone();
+        let join_handle = thread::Builder::new()
+            .name("BalanceSubscriptions".to_owned())
+            .spawn(move || {
+                log::debug!(logger, "Balance subscription thread started");
+                while !thread_stop_requested.load(Ordering::SeqCst) {
+                    subscriptions.poll(&mobilecoind_db, &logger);
+                    thread::sleep(poll_interval);
+                }
+                log::debug!(logger, "Balance subscription thread stopped");
+            })
+            .expect("Could not spawn balance subscription thread");
+
+        Self {
+            join_handle: Some(join_handle),
+            stop_requested,
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.stop_requested.store(true, Ordering::SeqCst);
+        if let Some(join_handle) = self.join_handle.take() {
+            join_handle
+                .join()
+                .expect("BalanceSubscriptionThread join failed");
+        }
+    }
+}
+
+impl Drop for BalanceSubscriptionThread {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mc_crypto_keys::CompressedRistrettoPublic;
+    use mc_transaction_core::ring_signature::KeyImage;
+
+    fn tx_out(
+        subaddress_index: u64,
+        token_id: u64,
+        direction: ProcessedTxOutDirection,
+        value: u64,
+    ) -> ProcessedTxOut {
+        ProcessedTxOut {
+            subaddress_index,
+            public_key: CompressedRistrettoPublic::try_from(&[value as u8; 32]).unwrap(),
+            key_image: KeyImage::from(value),
+            value,
+            direction: direction as i32,
+            token_id,
+        }
+    }
+
+    #[test]
+    fn test_block_changes() {
+        let monitor_id = MonitorId::from([1u8; 32]);
+        let changes = block_changes(
+            &monitor_id,
+            10,
+            vec![
+                tx_out(0, 0, ProcessedTxOutDirection::Received, 5),
+                tx_out(0, 0, ProcessedTxOutDirection::Spent, 3),
+                tx_out(0, 0, ProcessedTxOutDirection::Received, 2),
+                tx_out(0, 1, ProcessedTxOutDirection::Received, 7),
+                tx_out(1, 0, ProcessedTxOutDirection::Spent, 11),
+                tx_out(2, 0, ProcessedTxOutDirection::Received, 13),
+                tx_out(0, 0, ProcessedTxOutDirection::Invalid, 17),
+            ],
+            |subaddress_index| subaddress_index < 2,
+        );
+
+        assert_eq!(
+            changes
+                .iter()
+                .map(|change| (
+                    change.subaddress_index,
+                    change.token_id,
+                    change.received,
+                    change.spent,
+                    change.tx_outs.len()
+                ))
+                .collect::<Vec<_>>(),
+            vec![(0, 0, 7, 3, 3), (0, 1, 7, 0, 1), (1, 0, 0, 11, 1)]
+        );
+        assert!(changes
+            .iter()
+            .all(|change| change.block_index == 10 && change.monitor_id == monitor_id));
+    }
+}
@@ -9,7 +9,7 @@ use mc_ledger_db::{Ledger, LedgerDB};
 use mc_ledger_sync::{LedgerSyncServiceThread, PollingNetworkState, ReqwestTransactionsFetcher};
 use mc_mobilecoind::{
     config::Config, database::Database, payments::TransactionsManager, service::Service,
-    t3_sync::T3SyncThread,
+    t3_sync::T3SyncThread, webhook::WebhookThread,
 };
 use mc_util_telemetry::setup_default_tracer;
 use mc_watcher::{watcher::WatcherSyncThread, watcher_db::create_or_open_rw_watcher_db};
@@ -117,12 +117,18 @@ fn main() {
 
             let mobilecoind_db = Database::new(mobilecoind_db, logger.clone())
                 .expect("Could not open mobilecoind_db");
+            if let Some(db_key) = config.db_key() {
+                mobilecoind_db
+                    .unlock_or_encrypt(&db_key)
+                    .expect("Could not unlock mobilecoind_db with the db key");
+                log::info!(logger, "Unlocked mobilecoind_db with the db key");
+            }
 
             let transactions_manager = TransactionsManager::new(
                 ledger_db.clone(),
                 mobilecoind_db.clone(),
                 peer_manager,
-                config.get_fog_resolver_factory(logger.clone()),
+                config.get_fog_resolver_factory(ledger_db.clone(), logger.clone()),
                 logger.clone(),
             );
 
@@ -141,6 +147,16 @@ fn main() {
                 _ => None,
             };
 
+            let _webhook_thread = (!config.webhook_config.webhook_urls.is_empty()).then(|| {
+                WebhookThread::start(
+                    mobilecoind_db.clone(),
+                    ledger_db.clone(),
+                    config.webhook_config.clone(),
+                    config.poll_interval,
+                    logger.clone(),
+                )
+            });
+
             let _api_server = Service::new(
                 ledger_db,
                 mobilecoind_db,
@@ -3,25 +3,29 @@
 
 //! Configuration parameters for mobilecoind
 
+use crate::db_crypto::PASSWORD_LEN;
 use clap::Parser;
 use displaydoc::Display;
 use mc_attestation_verifier::{TrustedIdentity, TrustedMrSignerIdentity};
 use mc_common::{logger::Logger, ResponderId};
 use mc_connection::{ConnectionManager, HardcodedCredentialsProvider, ThickClient};
 use mc_consensus_scp::QuorumSet;
-use mc_fog_report_connection::GrpcFogReportConnection;
+use mc_fog_report_connection::{
+    CachingFogReportConnection, FogReportCacheConfig, GrpcFogReportConnection,
+};
 use mc_fog_report_resolver::FogResolver;
+use mc_ledger_db::{Ledger, LedgerDB};
 use mc_mobilecoind_api::MobilecoindUri;
 use mc_sgx_css::Signature;
 use mc_t3_api::T3Uri;
-use mc_util_parse::{load_css_file, parse_duration_in_seconds};
+use mc_util_parse::{load_css_file, parse_duration_in_seconds, parse_hex};
 use mc_util_uri::{ConnectionUri, ConsensusClientUri, FogUri};
 #[cfg(all(feature = "ip-check", not(feature = "bypass-ip-check")))]
 use reqwest::{
     blocking::Client,
     header::{HeaderMap, HeaderValue, InvalidHeaderValue, AUTHORIZATION, CONTENT_TYPE},
 };
-use std::{path::PathBuf, sync::Arc, time::Duration};
+use std::{fs, path::PathBuf, sync::Arc, time::Duration};
 
 /// Configuration parameters for mobilecoind
 #[derive(Debug, Parser)]
@@ -73,6 +77,16 @@ pub struct Config {
     #[clap(long, env = "MC_MOBILECOIND_DB")]
     pub mobilecoind_db: Option<PathBuf>,
 
+    /// File holding the hex-encoded 32 byte key to encrypt the mobilecoind
+    /// database with, e.g. as written by a KMS-backed secret store.
+    ///
+    /// The database is unlocked with this key at startup, rather than waiting
+    /// for the UnlockDb API, and is encrypted with it if it isn't yet. After
+    /// rotating the key with the SetDbPassword API, this file must be updated
+    /// to hold the new key.
+    #[clap(long, env = "MC_DB_KEY_FILE", requires = "mobilecoind_db")]
+    pub db_key_file: Option<PathBuf>,
+
     /// URI to listen on and serve requests from.
     #[clap(long, env = "MC_LISTEN_URI")]
     pub listen_uri: Option<MobilecoindUri>,
@@ -91,6 +105,18 @@ pub struct Config {
     #[clap(long, value_parser = load_css_file, env = "MC_FOG_INGEST_ENCLAVE_CSS")]
     pub fog_ingest_enclave_css: Option<Signature>,
 
+    /// How many seconds to reuse the fog reports fetched for a fog url for,
+    /// when building transactions to fog recipients. 0, the default, fetches
+    /// them for every transaction. Reports are fetched again before this if
+    /// one of their pubkeys expires.
+    #[clap(long, default_value = "0", value_parser = parse_duration_in_seconds, env = "MC_FOG_REPORT_CACHE_TTL")]
+    pub fog_report_cache_ttl: Duration,
+
+    /// How many seconds past --fog-report-cache-ttl cached fog reports may
+    /// still be used for, while they are fetched again in the background.
+    #[clap(long, default_value = "0", value_parser = parse_duration_in_seconds, env = "MC_FOG_REPORT_CACHE_STALE_WHILE_REVALIDATE")]
+    pub fog_report_cache_stale_while_revalidate: Duration,
+
     /// Automatically migrate the ledger db (if it exists) into the most recent
     /// version.
     #[clap(long, env = "MC_LEDGER_DB_MIGRATE")]
@@ -113,6 +139,10 @@ pub struct Config {
     /// T3 API Key
     #[clap(long, env = "T3_API_KEY", requires = "t3_uri")]
     pub t3_api_key: Option<String>,
+
+    /// Webhook notifications config.
+    #[clap(flatten)]
+    pub webhook_config: WebhookConfig,
 }
 
 fn parse_quorum_set_from_json(src: &str) -> Result<QuorumSet<ResponderId>, String> {
@@ -179,6 +209,18 @@ impl Config {
         QuorumSet::new_with_node_ids(node_ids.len() as u32, node_ids)
     }
 
+    /// Read the database encryption key from the db key file, if there is one.
+    /// Panics on error.
+    pub fn db_key(&self) -> Option<[u8; PASSWORD_LEN]> {
+        self.db_key_file.as_ref().map(|path| {
+            let contents = fs::read_to_string(path)
+                .unwrap_or_else(|err| panic!("Could not read db key file {path:?}: {err}"));
+            parse_hex(contents.trim()).unwrap_or_else(|err| {
+                panic!("db key file {path:?} must hold {PASSWORD_LEN} hex-encoded bytes: {err}")
+            })
+        })
+    }
+
     /// Get the attestation identity used to verify fog reports when sending to
     /// fog recipients
     pub fn fog_ingest_identity(&self) -> Option<TrustedIdentity> {
@@ -197,8 +239,11 @@ impl Config {
     /// Get the function which creates FogResolver given a list of recipient
     /// addresses The string error should be mapped by invoker of this
     /// factory to Error::FogError
+    ///
+    /// The ledger is used to tell when cached fog reports have expired.
     pub fn get_fog_resolver_factory(
         &self,
+        ledger_db: LedgerDB,
         logger: Logger,
     ) -> Arc<dyn Fn(&[FogUri]) -> Result<FogResolver, String> + Send + Sync> {
         let env = Arc::new(
@@ -207,7 +252,21 @@ impl Config {
                 .build(),
         );
 
-        let conn = GrpcFogReportConnection::new(self.peers_config.chain_id.to_owned(), env, logger);
+        let conn = GrpcFogReportConnection::new(
+            self.peers_config.chain_id.to_owned(),
+            env,
+            logger.clone(),
+        );
+        let cached_conn = (!self.fog_report_cache_ttl.is_zero()).then(|| {
+            CachingFogReportConnection::new(
+                conn.clone(),
+                FogReportCacheConfig {
+                    ttl: self.fog_report_cache_ttl,
+                    stale_while_revalidate: self.fog_report_cache_stale_while_revalidate,
+                },
+                logger,
+            )
+        });
 
         let identity = self.fog_ingest_identity();
 
@@ -215,9 +274,16 @@ impl Config {
             if fog_uris.is_empty() {
                 Ok(Default::default())
             } else if let Some(identity) = identity.as_ref() {
-                let report_responses = conn
-                    .fetch_fog_reports(fog_uris.iter().cloned())
-                    .map_err(|err| format!("Failed fetching fog reports: {err}"))?;
+                let report_responses = match cached_conn.as_ref() {
+                    Some(cached_conn) => {
+                        let num_blocks = ledger_db
+                            .num_blocks()
+                            .map_err(|err| format!("Failed getting number of blocks: {err}"))?;
+                        cached_conn.fetch_fog_reports(fog_uris.iter().cloned(), num_blocks)
+                    }
+                    None => conn.fetch_fog_reports(fog_uris.iter().cloned()),
+                }
+                .map_err(|err| format!("Failed fetching fog reports: {err}"))?;
                 Ok(FogResolver::new(report_responses, [identity])
                     .map_err(|err| format!("Invalid fog url: {err}"))?)
             } else {
@@ -286,6 +352,36 @@ impl Config {
     }
 }
 
+/// Configuration of the webhooks that are notified of deposits and spent key
+/// images.
+#[derive(Clone, Debug, Default, Parser)]
+pub struct WebhookConfig {
+    /// URLs to POST notification events to. No notifications are sent if
+    /// this is empty.
+    #[clap(
+        long = "webhook-url",
+        use_value_delimiter = true,
+        env = "MC_WEBHOOK_URL",
+        requires = "webhook_secret",
+        requires = "mobilecoind_db"
+    )]
+    pub webhook_urls: Vec<String>,
+
+    /// Secret used to HMAC-sign the notification events.
+    #[clap(long, env = "MC_WEBHOOK_SECRET")]
+    pub webhook_secret: Option<String>,
+
+    /// Number of blocks, counting the one a deposit appeared in, that must be
+    /// in the ledger before the deposit is reported as confirmed.
+    #[clap(long, default_value = "1", env = "MC_WEBHOOK_CONFIRMATIONS")]
+    pub webhook_confirmations: u64,
+
+    /// Number of times to try delivering an event to a webhook before giving
+    /// up on it.
+    #[clap(long, default_value = "5", env = "MC_WEBHOOK_MAX_ATTEMPTS")]
+    pub webhook_max_attempts: usize,
+}
+
 /// Wrapper for configuring and parsing peer URIs.
 #[derive(Clone, Debug, Parser)]
 pub struct PeersConfig {
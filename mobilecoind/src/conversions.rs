@@ -32,6 +32,7 @@ impl From<&UnspentTxOut> for api::UnspentTxOut {
         dst.set_attempted_spend_tombstone(src.attempted_spend_tombstone);
         dst.set_token_id(src.token_id);
         dst.set_memo_payload(src.memo_payload.clone());
+        dst.set_frozen(src.frozen);
 
         if let Ok(mp) = MemoPayload::try_from(&src.memo_payload[..]) {
             dst.set_decoded_memo(decode_memo(&mp));
@@ -53,6 +54,7 @@ impl TryFrom<&api::UnspentTxOut> for UnspentTxOut {
         let attempted_spend_tombstone = src.attempted_spend_tombstone;
         let token_id = src.token_id;
         let memo_payload = src.memo_payload.clone();
+        let frozen = src.frozen;
 
         Ok(Self {
             tx_out,
@@ -63,6 +65,7 @@ impl TryFrom<&api::UnspentTxOut> for UnspentTxOut {
             attempted_spend_tombstone,
             token_id,
             memo_payload,
+            frozen,
         })
     }
 }
@@ -347,6 +350,7 @@ mod test {
             attempted_spend_tombstone,
             token_id: *Mob::ID,
             memo_payload: vec![6u8, 66],
+            frozen: false,
         };
 
         let proto = api::UnspentTxOut::from(&rust);
@@ -544,6 +548,7 @@ mod test {
                 attempted_spend_tombstone,
                 token_id: *Mob::ID,
                 memo_payload: vec![9u8, 66],
+                frozen: false,
             }
         };
 
@@ -10,6 +10,7 @@ use crate::{
     subaddress_store::{SubaddressId, SubaddressSPKId, SubaddressStore},
     t3_store::T3Store,
     utxo_store::{UtxoId, UtxoStore},
+    webhook_store::{WebhookEvent, WebhookStore},
 };
 
 use crate::utxo_store::UnspentTxOut;
@@ -68,6 +69,9 @@ pub struct Database {
     /// T3 store.
     t3_store: T3Store,
 
+    /// Webhook store.
+    webhook_store: WebhookStore,
+
     /// Logger.
     logger: Logger,
 }
@@ -76,7 +80,7 @@ impl Database {
     pub fn new<P: AsRef<Path>>(path: P, logger: Logger) -> Result<Self, Error> {
         let env = Arc::new(
             Environment::new()
-                .set_max_dbs(13)
+                .set_max_dbs(17)
                 .set_map_size(MAX_LMDB_FILE_SIZE)
                 .open(path.as_ref())?,
         );
@@ -103,6 +107,7 @@ impl Database {
         let utxo_store = UtxoStore::new(env.clone(), logger.clone())?;
         let processed_block_store = ProcessedBlockStore::new(env.clone(), logger.clone())?;
         let t3_store = T3Store::new(env.clone(), logger.clone())?;
+        let webhook_store = WebhookStore::new(env.clone(), logger.clone())?;
 
         Ok(Self {
             env,
@@ -112,6 +117,7 @@ impl Database {
             utxo_store,
             processed_block_store,
             t3_store,
+            webhook_store,
             logger,
         })
     }
@@ -150,6 +156,16 @@ impl Database {
         Ok(())
     }
 
+    /// Unlock the database with a key provided at startup, e.g. by a KMS,
+    /// encrypting the database with it first if it is not encrypted yet.
+    pub fn unlock_or_encrypt(&self, key: &[u8]) -> Result<(), Error> {
+        if self.is_db_encrypted() {
+            self.check_and_store_password(key)
+        } else {
+            self.re_encrypt(key)
+        }
+    }
+
     pub fn add_monitor(&self, data: &MonitorData) -> Result<MonitorId, Error> {
         mc_common::trace_time!(self.logger, "add_monitor");
 
@@ -180,6 +196,8 @@ impl Database {
 
         self.processed_block_store.remove(&mut db_txn, id)?;
 
+        self.webhook_store.remove(&mut db_txn, id)?;
+
         self.monitor_store.remove(&mut db_txn, id)?;
 
         db_txn.commit()?;
@@ -254,6 +272,22 @@ impl Database {
         Ok(())
     }
 
+    pub fn set_utxos_frozen(
+        &self,
+        monitor_id: &MonitorId,
+        key_images: &[KeyImage],
+        frozen: bool,
+    ) -> Result<(), Error> {
+        let mut db_txn = self.env.begin_rw_txn()?;
+
+        self.utxo_store
+            .set_frozen(&mut db_txn, monitor_id, key_images, frozen)?;
+
+        db_txn.commit()?;
+
+        Ok(())
+    }
+
     /// Feed data processed from a given block into the various stores.
     pub fn block_processed(
         &self,
@@ -374,6 +408,67 @@ impl Database {
         db_txn.commit()?;
         Ok(())
     }
+
+    /// Get the next block to generate webhook events for, for a given monitor.
+    /// The first time this is called for a monitor, this is the monitor's next
+    /// block to process.
+    pub fn get_webhook_next_block(&self, monitor_id: &MonitorId) -> Result<u64, Error> {
+        let mut db_txn = self.env.begin_rw_txn()?;
+        if let Some(next_block) = self.webhook_store.get_next_block(&db_txn, monitor_id)? {
+            return Ok(next_block);
+        }
+
+        let monitor_data = self.monitor_store.get_data(&db_txn, monitor_id)?;
+        self.webhook_store
+            .set_next_block(&mut db_txn, monitor_id, monitor_data.next_block)?;
+        db_txn.commit()?;
+        Ok(monitor_data.next_block)
+    }
+
+    /// Queue the webhook events generated for a monitor's processed block, and
+    /// move the monitor on to the next block.
+    pub fn webhook_block_events_generated(
+        &self,
+        monitor_id: &MonitorId,
+        block_index: u64,
+        events: &[WebhookEvent],
+    ) -> Result<(), Error> {
+        let mut db_txn = self.env.begin_rw_txn()?;
+        self.webhook_store
+            .block_events_generated(&mut db_txn, monitor_id, block_index, events)?;
+        db_txn.commit()?;
+        Ok(())
+    }
+
+    /// Queue the webhook events of the deposits which are confirmed, given the
+    /// number of blocks in the ledger. Returns the number of events queued.
+    pub fn webhook_deposits_confirmed(
+        &self,
+        num_blocks: u64,
+        confirmations: u64,
+    ) -> Result<usize, Error> {
+        let mut db_txn = self.env.begin_rw_txn()?;
+        let num_confirmed =
+            self.webhook_store
+                .deposits_confirmed(&mut db_txn, num_blocks, confirmations)?;
+        db_txn.commit()?;
+        Ok(num_confirmed)
+    }
+
+    /// Get the next webhook event to deliver, along with its index so it can
+    /// then be removed.
+    pub fn peek_webhook_event(&self) -> Result<Option<(u64, WebhookEvent)>, Error> {
+        let db_txn = self.env.begin_ro_txn()?;
+        self.webhook_store.peek_event(&db_txn)
+    }
+
+    /// Remove a webhook event from the queue.
+    pub fn remove_webhook_event(&self, index: u64) -> Result<(), Error> {
+        let mut db_txn = self.env.begin_rw_txn()?;
+        self.webhook_store.remove_event(&mut db_txn, index)?;
+        db_txn.commit()?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -549,6 +644,53 @@ mod test {
         );
     }
 
+    // Test that a key provided at startup encrypts a plaintext db, and unlocks
+    // it after a restart.
+    #[test_with_logger]
+    fn test_unlock_or_encrypt(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([123u8; 32]);
+        let account_key = AccountKey::random(&mut rng);
+
+        let mobilecoind_db_tmp = TempDir::new().expect("Could not make tempdir for mobilecoind db");
+        let mobilecoind_db_path = mobilecoind_db_tmp
+            .path()
+            .to_str()
+            .expect("Could not get path as string");
+
+        let mobilecoind_db = Database::new(mobilecoind_db_path, logger.clone())
+            .expect("failed creating new mobilecoind db");
+        let monitor_data = MonitorData::new(
+            account_key,
+            0,  // first_subaddress
+            10, // num_subaddresses
+            0,  // first_block
+            "", // name
+        )
+        .unwrap();
+        let monitor_id = mobilecoind_db
+            .add_monitor(&monitor_data)
+            .expect("failed adding monitor");
+
+        // The first startup with a key encrypts the db.
+        mobilecoind_db.unlock_or_encrypt(&[10; 32]).unwrap();
+        assert!(mobilecoind_db.is_db_encrypted());
+        assert!(mobilecoind_db.is_unlocked());
+
+        // Later startups unlock it, but only with the same key.
+        let mobilecoind_db = Database::new(mobilecoind_db_path, logger.clone())
+            .expect("failed creating new mobilecoind db");
+        assert!(!mobilecoind_db.is_unlocked());
+        assert!(mobilecoind_db.unlock_or_encrypt(&[11; 32]).is_err());
+        assert!(!mobilecoind_db.is_unlocked());
+
+        mobilecoind_db.unlock_or_encrypt(&[10; 32]).unwrap();
+        assert!(mobilecoind_db.is_unlocked());
+        assert_eq!(
+            mobilecoind_db.get_monitor_map().unwrap(),
+            HashMap::from_iter(vec![(monitor_id, monitor_data)])
+        );
+    }
+
     // Inserting a monitor that overlaps subaddresses of another monitor should
     // result in an error.
     #[test_with_logger]
@@ -9,7 +9,9 @@ pub mod database;
 pub mod payments;
 pub mod service;
 pub mod t3_sync;
+pub mod webhook;
 
+mod balance_subscription;
 mod conversions;
 mod database_key;
 mod db_crypto;
@@ -21,6 +23,7 @@ mod sync;
 mod t3_store;
 mod transaction_memo;
 mod utxo_store;
+mod webhook_store;
 pub use utxo_store::UnspentTxOut;
 
 #[cfg(any(test, feature = "test_utils"))]
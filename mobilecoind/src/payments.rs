@@ -14,15 +14,15 @@ use mc_connection::{
 };
 use mc_crypto_keys::{RistrettoPrivate, RistrettoPublic};
 use mc_crypto_ring_signature_signer::NoKeysRingSigner;
-use mc_fog_report_validation::FogPubkeyResolver;
+use mc_fog_report_validation::{FogPubkeyError, FogPubkeyResolver};
 use mc_ledger_db::{Error as LedgerError, Ledger, LedgerDB};
 use mc_rand::{CryptoRng, RngCore};
 use mc_transaction_builder::{
     InputCredentials, MemoBuilder, ReservedSubaddresses, SignedContingentInputBuilder,
-    TransactionBuilder, TxOutContext,
+    TransactionBuilder, TxOutContext, TxPolicy,
 };
 use mc_transaction_core::{
-    constants::{MAX_INPUTS, RING_SIZE},
+    constants::{MAX_INPUTS, MAX_OUTPUTS, RING_SIZE},
     onetime_keys::recover_onetime_private_key,
     ring_signature::KeyImage,
     tx::{Tx, TxOut, TxOutMembershipProof},
@@ -34,7 +34,7 @@ use mc_transaction_extra::{
 use mc_util_uri::FogUri;
 use rand::Rng;
 use std::{
-    cmp::{max, Reverse},
+    cmp::{max, min, Reverse},
     collections::BTreeMap,
     str::FromStr,
     sync::{
@@ -123,6 +123,32 @@ pub struct SciForTx {
     pub partial_fill_value: u64,
 }
 
+/// The outcome of validating a recipient's public address ahead of paying it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FogAddressVerdict {
+    /// The address has no fog report url, so no fog hint is needed.
+    ValidWithoutFog,
+    /// The fog pubkey was fetched and fully validated.
+    ValidWithFog {
+        /// The last block the fog pubkey may be used for.
+        pubkey_expiry: BlockIndex,
+        /// The tombstone block a transaction to this address would get.
+        tombstone_block: BlockIndex,
+    },
+    /// The fog report url could not be parsed.
+    InvalidFogReportUrl(String),
+    /// The fog report could not be fetched.
+    FogReportUnavailable(String),
+    /// The fog report failed signature chain or attestation verification.
+    FogReportVerificationFailed(String),
+    /// The fog pubkey expires before a new transaction could be included in
+    /// the ledger.
+    FogPubkeyExpired {
+        /// The last block the fog pubkey may be used for.
+        pubkey_expiry: BlockIndex,
+    },
+}
+
 pub struct TransactionsManager<
     T: BlockchainConnection + UserTxConnection + 'static,
     FPR: FogPubkeyResolver,
@@ -201,37 +227,34 @@ impl<T: BlockchainConnection + UserTxConnection + 'static, FPR: FogPubkeyResolve
         }
     }
 
-    // Gets the network block version and fee information.
+    // Gets the rules the network currently applies to new transactions, from a
+    // list of BlockInfo objects.
     //
     // * The block version is the max of the local ledger block version and the
     //   network-reported block version.
-    // * If opt_fee is nonzero, then that fee value is returned. Otherwise, the
-    //   minimum fee for this token id reported by the network is returned.
+    // * The fee map is the one reported by the majority of nodes.
     //
-    // If the network does not report a minimum fee for this token id, and the user
-    // does not specify a nonzero fee, then we return an error.
-    fn get_network_fee_and_block_version(
-        &self,
-        token_id: TokenId,
-        opt_fee: u64,
-        last_block_info: &BlockInfo,
-    ) -> Result<(u64, u32), Error> {
+    // # Arguments
+    // * `last_block_infos` - The last block info we have from each node
+    fn get_tx_policy(&self, last_block_infos: &[BlockInfo]) -> Result<TxPolicy, Error> {
+        let last_block_info = get_majority_block_info(last_block_infos)
+            .ok_or_else(|| Error::TxBuild("No block info available".into()))?;
+
         // Figure out the block_version, taking max of local ledger and network
         let block_version = max(
             self.ledger_db.get_latest_block()?.version,
             last_block_info.network_block_version,
         );
+        let block_version =
+            BlockVersion::try_from(block_version).map_err(|err| Error::TxBuild(err.to_string()))?;
 
-        // Figure out the fee using either user-specified fee, or the network-reported
-        // fee.
-        Ok(if opt_fee != 0 {
-            (opt_fee, block_version)
-        } else {
-            let fee = last_block_info
-                .minimum_fee_or_none(&token_id)
-                .ok_or_else(|| Error::TxBuild("Token cannot be used to pay fees".into()))?;
-            (fee, block_version)
-        })
+        let fee_map = FeeMap::try_from(last_block_info.minimum_fees)?;
+
+        Ok(TxPolicy::new(
+            block_version,
+            fee_map,
+            last_block_info.block_index,
+        ))
     }
 
     // A helper for figuring ou the minimum fee, fee map and block version from a
@@ -246,18 +269,13 @@ impl<T: BlockchainConnection + UserTxConnection + 'static, FPR: FogPubkeyResolve
         token_id: TokenId,
         opt_fee: u64,
     ) -> Result<(u64, FeeMap, BlockVersion), Error> {
-        let last_block_info = get_majority_block_info(last_block_infos)
-            .ok_or_else(|| Error::TxBuild("No block info available".into()))?;
-
-        let (fee, block_version) =
-            self.get_network_fee_and_block_version(token_id, opt_fee, &last_block_info)?;
-
-        let fee_map = FeeMap::try_from(last_block_info.minimum_fees)?;
+        let policy = self.get_tx_policy(last_block_infos)?;
 
-        let block_version =
-            BlockVersion::try_from(block_version).map_err(|err| Error::TxBuild(err.to_string()))?;
+        let fee = policy
+            .fee(token_id, opt_fee)
+            .map_err(|err| Error::TxBuild(err.to_string()))?;
 
-        Ok((fee, fee_map, block_version))
+        Ok((fee.value, policy.fee_map().clone(), policy.block_version()))
     }
 
     /// Create a TxProposal, using only one token id for the whole transaction.
@@ -531,8 +549,7 @@ impl<T: BlockchainConnection + UserTxConnection + 'static, FPR: FogPubkeyResolve
             .subaddress(change_subaddress_index);
 
         // Figure out the block version, fee and minimum fee map.
-        let (_fee, _fee_map, block_version) =
-            self.get_fee_info_and_block_version(last_block_infos, 0.into(), 0)?;
+        let block_version = self.get_tx_policy(last_block_infos)?.block_version();
 
         // Get global index of the utxo
         let global_index = self
@@ -644,6 +661,189 @@ impl<T: BlockchainConnection + UserTxConnection + 'static, FPR: FogPubkeyResolve
             selected_utxos
         );
 
+        self.build_merge_tx_proposal(
+            &logger,
+            &monitor_data.account_key,
+            subaddress_index,
+            token_id,
+            selected_utxos,
+            fee,
+            fee_map,
+            block_version,
+            num_blocks_in_ledger,
+        )
+    }
+
+    /// Create a TxProposal that splits the funds of a subaddress into a number
+    /// of equally sized outputs back to the same subaddress.
+    ///
+    /// # Arguments
+    /// * `monitor_id` - Monitor ID of the inputs to spend.
+    /// * `subaddress_index` - Subaddress of the inputs to spend, which also
+    ///   receives the outputs and any change.
+    /// * `token_id` - Token id to transact in.
+    /// * `output_value` - Value of each output.
+    /// * `num_outputs` - Number of outputs to create.
+    /// * `last_block_infos` - Last block info responses from the network, for
+    ///   determining fees. This should normally come from polling_network_state
+    /// * `opt_fee` - Transaction fee in smallest representable units. If zero,
+    ///   use network-reported minimum fee.
+    /// * `opt_tombstone` - Tombstone block. If zero, sets to default.
+    #[allow(clippy::too_many_arguments)]
+    pub fn generate_split_tx(
+        &self,
+        monitor_id: &MonitorId,
+        subaddress_index: u64,
+        token_id: TokenId,
+        output_value: u64,
+        num_outputs: usize,
+        last_block_infos: &[BlockInfo],
+        opt_fee: u64,
+        opt_tombstone: u64,
+    ) -> Result<TxProposal, Error> {
+        let logger = self.logger.new(
+            o!("monitor_id" => monitor_id.to_string(), "subaddress_index" => subaddress_index),
+        );
+        log::trace!(logger, "Generating split transaction...");
+
+        // One output is needed for the change.
+        if num_outputs == 0 || num_outputs >= MAX_OUTPUTS as usize {
+            return Err(Error::InvalidArgument(
+                "num_outputs".to_owned(),
+                format!("must be between 1 and {}", MAX_OUTPUTS - 1),
+            ));
+        }
+        if output_value == 0 {
+            return Err(Error::InvalidArgument(
+                "output_value".to_owned(),
+                "must be greater than zero".to_owned(),
+            ));
+        }
+
+        let monitor_data = self.mobilecoind_db.get_monitor_data(monitor_id)?;
+
+        let inputs = self
+            .mobilecoind_db
+            .get_utxos_for_subaddress(monitor_id, subaddress_index)?
+            .into_iter()
+            .filter(|utxo| utxo.token_id == *token_id)
+            .collect::<Vec<_>>();
+
+        let outlays = vec![
+            OutlayV2 {
+                receiver: monitor_data.account_key.subaddress(subaddress_index),
+                amount: Amount::new(output_value, token_id),
+                tx_private_key: None,
+            };
+            num_outputs
+        ];
+
+        self.build_mixed_transaction(
+            monitor_id,
+            token_id,
+            subaddress_index,
+            &inputs,
+            &[],
+            &outlays,
+            last_block_infos,
+            opt_fee,
+            opt_tombstone,
+            None,
+        )
+    }
+
+    /// Create a TxProposal that merges the smallest UTXOs of a subaddress into
+    /// a single output back to the same subaddress.
+    ///
+    /// # Arguments
+    /// * `monitor_id` - Monitor ID of the inputs to spend.
+    /// * `subaddress_index` - Subaddress of the inputs to spend.
+    /// * `token_id` - Token id to transact in.
+    /// * `max_inputs` - Maximum number of UTXOs to merge. If zero, or more than
+    ///   a transaction can have, merges as many as a transaction can have.
+    /// * `max_input_value` - Only merge UTXOs of at most this value. If zero,
+    ///   UTXOs of any value are merged.
+    /// * `last_block_infos` - Last block info responses from the network, for
+    ///   determining fees. This should normally come from polling_network_state
+    /// * `opt_fee` - Optional fee to use. If zero, we will attempt to query the
+    ///   network for fee information.
+    #[allow(clippy::too_many_arguments)]
+    pub fn generate_consolidation_tx(
+        &self,
+        monitor_id: &MonitorId,
+        subaddress_index: u64,
+        token_id: TokenId,
+        max_inputs: usize,
+        max_input_value: u64,
+        last_block_infos: &[BlockInfo],
+        opt_fee: u64,
+    ) -> Result<TxProposal, Error> {
+        let logger = self.logger.new(
+            o!("monitor_id" => monitor_id.to_string(), "subaddress_index" => subaddress_index),
+        );
+        log::trace!(logger, "Generating consolidation transaction...");
+
+        let monitor_data = self.mobilecoind_db.get_monitor_data(monitor_id)?;
+
+        let num_blocks_in_ledger = self.ledger_db.num_blocks()?;
+
+        let (fee, fee_map, block_version) =
+            self.get_fee_info_and_block_version(last_block_infos, token_id, opt_fee)?;
+
+        let selected_utxos = {
+            let inputs = self
+                .mobilecoind_db
+                .get_utxos_for_subaddress(monitor_id, subaddress_index)?
+                .into_iter()
+                .filter(|utxo| utxo.token_id == *token_id)
+                .collect::<Vec<_>>();
+            Self::select_utxos_for_consolidation(
+                num_blocks_in_ledger,
+                &inputs,
+                match max_inputs {
+                    0 => MAX_INPUTS as usize,
+                    max_inputs => min(max_inputs, MAX_INPUTS as usize),
+                },
+                max_input_value,
+                fee,
+            )?
+        };
+
+        log::trace!(
+            logger,
+            "Selected {} utxos: {:?}",
+            selected_utxos.len(),
+            selected_utxos
+        );
+
+        self.build_merge_tx_proposal(
+            &logger,
+            &monitor_data.account_key,
+            subaddress_index,
+            token_id,
+            selected_utxos,
+            fee,
+            fee_map,
+            block_version,
+            num_blocks_in_ledger,
+        )
+    }
+
+    /// Build a TxProposal that merges the given UTXOs into a single output back
+    /// to the subaddress they belong to.
+    #[allow(clippy::too_many_arguments)]
+    fn build_merge_tx_proposal(
+        &self,
+        logger: &Logger,
+        account_key: &AccountKey,
+        subaddress_index: u64,
+        token_id: TokenId,
+        selected_utxos: Vec<UnspentTxOut>,
+        fee: u64,
+        fee_map: FeeMap,
+        block_version: BlockVersion,
+        num_blocks_in_ledger: u64,
+    ) -> Result<TxProposal, Error> {
         // Figure out total amount of transaction (excluding fee).
         let total_value: u64 = selected_utxos.iter().map(|utxo| utxo.value).sum();
         log::trace!(
@@ -685,7 +885,7 @@ impl<T: BlockchainConnection + UserTxConnection + 'static, FPR: FogPubkeyResolve
 
         // We are paying ourselves the entire amount.
         let outlays = vec![OutlayV2 {
-            receiver: monitor_data.account_key.subaddress(subaddress_index),
+            receiver: account_key.subaddress(subaddress_index),
             amount: Amount::new(total_value - fee, token_id),
             tx_private_key: None,
         }];
@@ -699,7 +899,7 @@ impl<T: BlockchainConnection + UserTxConnection + 'static, FPR: FogPubkeyResolve
             block_version,
             token_id,
             fee,
-            &monitor_data.account_key,
+            account_key,
             subaddress_index,
             &outlays,
             tombstone_block,
@@ -711,7 +911,7 @@ impl<T: BlockchainConnection + UserTxConnection + 'static, FPR: FogPubkeyResolve
         )?;
         log::trace!(
             logger,
-            "Optimization tx constructed, hash={}",
+            "Merge tx constructed, hash={}",
             tx_proposal.tx.tx_hash()
         );
 
@@ -823,6 +1023,89 @@ impl<T: BlockchainConnection + UserTxConnection + 'static, FPR: FogPubkeyResolve
         Ok(tx_proposal)
     }
 
+    /// Check that a recipient can be paid, fetching and verifying their fog
+    /// report if they have one.
+    ///
+    /// # Arguments
+    /// * `receiver` - The recipient's public address.
+    /// * `opt_tombstone` - Tombstone block the sender intends to use. If zero,
+    ///   the default is assumed. Otherwise it must be past the end of the
+    ///   ledger, or no transaction using it could land.
+    ///
+    /// Returns the verdict, and the number of blocks in the ledger it was
+    /// reached with. Problems with the recipient's fog setup are reported in
+    /// the verdict, errors are only returned for invalid arguments and local
+    /// failures such as reading the ledger.
+    pub fn validate_fog_address(
+        &self,
+        receiver: &PublicAddress,
+        opt_tombstone: BlockIndex,
+    ) -> Result<(FogAddressVerdict, u64), Error> {
+        let num_blocks_in_ledger = self.ledger_db.num_blocks()?;
+        if opt_tombstone > 0 && opt_tombstone <= num_blocks_in_ledger {
+            return Err(Error::InvalidArgument(
+                "tombstone".to_string(),
+                format!(
+                    "Tombstone block {opt_tombstone} is not past the end of the ledger ({num_blocks_in_ledger} blocks)"
+                ),
+            ));
+        }
+        let tombstone_block = if opt_tombstone > 0 {
+            opt_tombstone
+        } else {
+            num_blocks_in_ledger + DEFAULT_NEW_TX_BLOCK_ATTEMPTS
+        };
+
+        let fog_uri = match extract_fog_uri(receiver) {
+            Ok(Some(fog_uri)) => fog_uri,
+            Ok(None) => return Ok((FogAddressVerdict::ValidWithoutFog, num_blocks_in_ledger)),
+            Err(err) => {
+                return Ok((
+                    FogAddressVerdict::InvalidFogReportUrl(err.to_string()),
+                    num_blocks_in_ledger,
+                ))
+            }
+        };
+
+        let fog_resolver = match (self.fog_resolver_factory)(core::slice::from_ref(&fog_uri)) {
+            Ok(fog_resolver) => fog_resolver,
+            Err(err) => {
+                return Ok((
+                    FogAddressVerdict::FogReportUnavailable(err),
+                    num_blocks_in_ledger,
+                ))
+            }
+        };
+
+        let verdict = match fog_resolver.get_fog_pubkey(receiver) {
+            Ok(fog_pubkey) if fog_pubkey.pubkey_expiry <= num_blocks_in_ledger => {
+                FogAddressVerdict::FogPubkeyExpired {
+                    pubkey_expiry: fog_pubkey.pubkey_expiry,
+                }
+            }
+            Ok(fog_pubkey) => FogAddressVerdict::ValidWithFog {
+                pubkey_expiry: fog_pubkey.pubkey_expiry,
+                tombstone_block: min(tombstone_block, fog_pubkey.pubkey_expiry),
+            },
+            Err(FogPubkeyError::Url(err)) => {
+                FogAddressVerdict::InvalidFogReportUrl(err.to_string())
+            }
+            Err(
+                err @ (FogPubkeyError::NoMatchingReportResponse(_)
+                | FogPubkeyError::NoMatchingReportId(_, _)),
+            ) => FogAddressVerdict::FogReportUnavailable(err.to_string()),
+            Err(err) => FogAddressVerdict::FogReportVerificationFailed(err.to_string()),
+        };
+        log::debug!(
+            self.logger,
+            "Validated fog address {}: {:?}",
+            receiver,
+            verdict
+        );
+
+        Ok((verdict, num_blocks_in_ledger))
+    }
+
     /// Submit a previously built tx proposal to the network.
     pub fn submit_tx_proposal(&self, tx_proposal: &TxProposal) -> Result<u64, Error> {
         // Pick a peer to submit to.
@@ -874,7 +1157,7 @@ impl<T: BlockchainConnection + UserTxConnection + 'static, FPR: FogPubkeyResolve
         // Sort the utxos in descending order by value.
         let mut sorted_utxos: Vec<UnspentTxOut> = utxos
             .iter()
-            .filter(|utxo| utxo.token_id == token_id)
+            .filter(|utxo| utxo.token_id == token_id && !utxo.frozen)
             .cloned()
             .collect();
         sorted_utxos.sort_by_key(|utxo| Reverse(utxo.value));
@@ -959,7 +1242,7 @@ impl<T: BlockchainConnection + UserTxConnection + 'static, FPR: FogPubkeyResolve
 
         let mut spendable_inputs: Vec<&UnspentTxOut> = inputs
             .iter()
-            .filter(|utxo| num_blocks_in_ledger >= utxo.attempted_spend_tombstone)
+            .filter(|utxo| num_blocks_in_ledger >= utxo.attempted_spend_tombstone && !utxo.frozen)
             .collect();
 
         // No point in merging if we are able to spend all inputs at once.
@@ -1010,6 +1293,51 @@ impl<T: BlockchainConnection + UserTxConnection + 'static, FPR: FogPubkeyResolve
         }
     }
 
+    /// Select UTXOs for consolidation: the `max_inputs` smallest UTXOs which
+    /// are spendable and worth at most `max_input_value` (any value if zero).
+    ///
+    /// Returns selected UTXOs
+    fn select_utxos_for_consolidation(
+        num_blocks_in_ledger: u64,
+        inputs: &[UnspentTxOut],
+        max_inputs: usize,
+        max_input_value: u64,
+        fee: u64,
+    ) -> Result<Vec<UnspentTxOut>, Error> {
+        if max_inputs < 2 {
+            return Err(Error::InvalidArgument(
+                "max_inputs".to_owned(),
+                "need at least 2 inputs to be able to merge".to_owned(),
+            ));
+        }
+
+        let mut spendable_inputs: Vec<&UnspentTxOut> = inputs
+            .iter()
+            .filter(|utxo| {
+                num_blocks_in_ledger >= utxo.attempted_spend_tombstone
+                    && !utxo.frozen
+                    && (max_input_value == 0 || utxo.value <= max_input_value)
+            })
+            .collect();
+        spendable_inputs.sort_by_key(|utxo| utxo.value);
+        spendable_inputs.truncate(max_inputs);
+
+        if spendable_inputs.len() < 2 {
+            return Err(Error::OptimizationNotBeneficial(
+                "Not enough spendable UTXOs to merge".to_owned(),
+            ));
+        }
+
+        let total: u64 = spendable_inputs.iter().map(|utxo| utxo.value).sum();
+        if total <= fee {
+            return Err(Error::OptimizationNotBeneficial(
+                "Merging UTXOs would result in a loss".to_owned(),
+            ));
+        }
+
+        Ok(spendable_inputs.into_iter().cloned().collect())
+    }
+
     /// Get membership proofs for a list of transaction outputs.
     pub fn get_membership_proofs(
         &self,
@@ -1654,6 +1982,7 @@ mod test {
                 attempted_spend_tombstone: 0,
                 token_id: *token_id,
                 memo_payload: vec![],
+                frozen: false,
             })
             .collect()
     }
@@ -1924,4 +2253,123 @@ mod test {
         );
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_select_utxos_skips_frozen_utxos() {
+        let mut utxos = generate_utxos(4);
+
+        utxos[0].value = 100 * MILLIMOB_TO_PICOMOB;
+        utxos[1].value = 200 * MILLIMOB_TO_PICOMOB;
+        utxos[2].value = 300 * MILLIMOB_TO_PICOMOB;
+        utxos[3].value = 2000 * MILLIMOB_TO_PICOMOB;
+        utxos[0].frozen = true;
+
+        // Sending 300 should select 200 + 300 rather than 100 + 200.
+        let selected_utxos = TransactionsManager::<
+            ThickClient<HardcodedCredentialsProvider>,
+            MockFogPubkeyResolver,
+        >::select_utxos_for_value(
+            Mob::ID, &utxos, 300 * MILLIMOB_TO_PICOMOB, utxos.len()
+        )
+        .unwrap();
+        assert_eq!(selected_utxos, vec![utxos[1].clone(), utxos[2].clone()]);
+
+        // Optimizing with max_inputs=2 should select 200, 2000.
+        let selected_utxos = TransactionsManager::<
+            ThickClient<HardcodedCredentialsProvider>,
+            MockFogPubkeyResolver,
+        >::select_utxos_for_optimization(
+            1000, &utxos, 2, Mob::ID, Mob::MINIMUM_FEE
+        )
+        .unwrap();
+        assert_eq!(selected_utxos, vec![utxos[1].clone(), utxos[3].clone()]);
+
+        // Consolidating should select 200, 300.
+        let selected_utxos = TransactionsManager::<
+            ThickClient<HardcodedCredentialsProvider>,
+            MockFogPubkeyResolver,
+        >::select_utxos_for_consolidation(
+            1000, &utxos, 2, 0, Mob::MINIMUM_FEE
+        )
+        .unwrap();
+        assert_eq!(selected_utxos, vec![utxos[1].clone(), utxos[2].clone()]);
+    }
+
+    #[test]
+    fn test_select_utxos_for_consolidation() {
+        let mut utxos = generate_utxos(5);
+
+        utxos[0].value = 300 * MILLIMOB_TO_PICOMOB;
+        utxos[1].value = 100 * MILLIMOB_TO_PICOMOB;
+        utxos[2].value = 2000 * MILLIMOB_TO_PICOMOB;
+        utxos[3].value = 200 * MILLIMOB_TO_PICOMOB;
+        utxos[4].value = 1000 * MILLIMOB_TO_PICOMOB;
+
+        // The smallest inputs are selected, up to max_inputs.
+        let selected_utxos = TransactionsManager::<
+            ThickClient<HardcodedCredentialsProvider>,
+            MockFogPubkeyResolver,
+        >::select_utxos_for_consolidation(
+            1000, &utxos, 3, 0, Mob::MINIMUM_FEE
+        )
+        .unwrap();
+        assert_eq!(
+            selected_utxos,
+            vec![utxos[1].clone(), utxos[3].clone(), utxos[0].clone()]
+        );
+
+        // Inputs above max_input_value are not selected.
+        let selected_utxos = TransactionsManager::<
+            ThickClient<HardcodedCredentialsProvider>,
+            MockFogPubkeyResolver,
+        >::select_utxos_for_consolidation(
+            1000,
+            &utxos,
+            16,
+            1000 * MILLIMOB_TO_PICOMOB,
+            Mob::MINIMUM_FEE,
+        )
+        .unwrap();
+        assert_eq!(
+            selected_utxos,
+            vec![
+                utxos[1].clone(),
+                utxos[3].clone(),
+                utxos[0].clone(),
+                utxos[4].clone()
+            ]
+        );
+
+        // Pending inputs are not selected.
+        utxos[1].attempted_spend_tombstone = 2000;
+        let selected_utxos = TransactionsManager::<
+            ThickClient<HardcodedCredentialsProvider>,
+            MockFogPubkeyResolver,
+        >::select_utxos_for_consolidation(
+            1000, &utxos, 2, 0, Mob::MINIMUM_FEE
+        )
+        .unwrap();
+        assert_eq!(selected_utxos, vec![utxos[3].clone(), utxos[0].clone()]);
+
+        // Merging a single input, or inputs worth less than the fee, is an error.
+        let result = TransactionsManager::<
+            ThickClient<HardcodedCredentialsProvider>,
+            MockFogPubkeyResolver,
+        >::select_utxos_for_consolidation(
+            1000,
+            &utxos,
+            16,
+            200 * MILLIMOB_TO_PICOMOB,
+            Mob::MINIMUM_FEE,
+        );
+        assert!(result.is_err());
+
+        let result = TransactionsManager::<
+            ThickClient<HardcodedCredentialsProvider>,
+            MockFogPubkeyResolver,
+        >::select_utxos_for_consolidation(
+            1000, &utxos, 16, 0, 10000 * MILLIMOB_TO_PICOMOB
+        );
+        assert!(result.is_err());
+    }
 }
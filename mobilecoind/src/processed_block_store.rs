@@ -322,6 +322,7 @@ mod test {
                     attempted_spend_tombstone: 0,
                     token_id: *Mob::ID,
                     memo_payload: vec![],
+                    frozen: false,
                 }
             })
             .collect();
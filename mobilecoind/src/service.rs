@@ -7,20 +7,26 @@
 //! * writes matching transactions to a local DB, organized by subaddress_id
 
 use crate::{
+    balance_subscription::{BalanceChange, BalanceSubscriptionThread, BalanceSubscriptions},
     database::Database,
     error::Error,
     monitor_store::{MonitorData, MonitorId},
-    payments::{Outlay, OutlayV2, SciForTx, TransactionsManager, TxProposal},
+    payments::{FogAddressVerdict, Outlay, OutlayV2, SciForTx, TransactionsManager, TxProposal},
+    processed_block_store::ProcessedTxOut,
     sync::SyncThread,
     transaction_memo::TransactionMemo,
     utxo_store::{UnspentTxOut, UtxoId},
 };
 use api::ledger::{TxOutResult, TxOutResultCode};
 use bip39::{Language, Mnemonic, MnemonicType};
-use grpcio::{EnvBuilder, RpcContext, RpcStatus, RpcStatusCode, ServerBuilder, UnarySink};
+use futures::{channel::mpsc::Receiver, FutureExt, SinkExt, StreamExt};
+use grpcio::{
+    EnvBuilder, RpcContext, RpcStatus, RpcStatusCode, ServerBuilder, ServerStreamingSink,
+    UnarySink, WriteFlags,
+};
 use mc_account_keys::{
-    burn_address, AccountKey, PublicAddress, RootIdentity, ShortAddressHash,
-    DEFAULT_SUBADDRESS_INDEX,
+    burn_address, AccountKey, KeyImageCheckpoint, PublicAddress, RootIdentity, ShortAddressHash,
+    ViewOnlyAccountBundle, DEFAULT_SUBADDRESS_INDEX,
 };
 use mc_api::blockchain::ArchiveBlock;
 use mc_blockchain_types::BlockIndex;
@@ -28,7 +34,7 @@ use mc_common::{
     logger::{log, Logger},
     HashMap,
 };
-use mc_connection::{BlockInfo, BlockchainConnection, UserTxConnection};
+use mc_connection::{BlockInfo, BlockchainConnection, Error as ConnectionError, UserTxConnection};
 use mc_core::slip10::Slip10KeyGenerator;
 use mc_crypto_keys::{CompressedRistrettoPublic, RistrettoPublic};
 use mc_fog_report_validation::FogPubkeyResolver;
@@ -53,15 +59,25 @@ use mc_util_grpc::{
     rpc_internal_error, rpc_invalid_arg_error, rpc_logger, send_result, AdminService,
     BuildInfoService, ConnectionUriGrpcioServer,
 };
+use mc_util_uri::ConnectionUri;
 use mc_watcher::watcher_db::WatcherDB;
 use mc_watcher_api::TimestampResultCode;
-use protobuf::{ProtobufEnum, RepeatedField};
-use std::sync::{Arc, Mutex, RwLock};
+use protobuf::{Message, ProtobufEnum, RepeatedField};
+use std::{
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
+};
+
+/// How often balance subscriptions check for newly processed blocks.
+const BALANCE_SUBSCRIPTION_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
 pub struct Service {
     /// Sync thread.
     _sync_thread: Arc<Mutex<Option<SyncThread>>>,
 
+    /// Thread feeding balance subscriptions.
+    _balance_subscription_thread: BalanceSubscriptionThread,
+
     /// GRPC server.
     _server: grpcio::Server,
 }
@@ -81,7 +97,7 @@ impl Service {
         chain_id: String,
         logger: Logger,
     ) -> Self {
-        let sync_thread = if mobilecoind_db.is_db_encrypted() {
+        let sync_thread = if !mobilecoind_db.is_unlocked() {
             log::info!(logger, "Db encryption enabled, sync task would start once password is provided via the API.");
             Arc::new(Mutex::new(None))
         } else {
@@ -112,6 +128,14 @@ impl Service {
             })
         };
 
+        let balance_subscriptions = Arc::new(BalanceSubscriptions::default());
+        let balance_subscription_thread = BalanceSubscriptionThread::start(
+            mobilecoind_db.clone(),
+            balance_subscriptions.clone(),
+            BALANCE_SUBSCRIPTION_POLL_INTERVAL,
+            logger.clone(),
+        );
+
         let api = ServiceApi::new(
             transactions_manager,
             ledger_db,
@@ -119,6 +143,7 @@ impl Service {
             watcher_db,
             network_state,
             start_sync_thread,
+            balance_subscriptions,
             chain_id,
             logger.clone(),
         );
@@ -142,7 +167,11 @@ impl Service {
         .into_service();
 
         // Package service into grpc server.
-        log::info!(logger, "Starting mobilecoind API Service on {}", listen_uri);
+        log::info!(
+            logger,
+            "Starting mobilecoind API Service on {}",
+            listen_uri.redacted()
+        );
         let env = Arc::new(
             EnvBuilder::new()
                 .cq_count(1)
@@ -164,6 +193,7 @@ impl Service {
         Self {
             _server: server,
             _sync_thread: sync_thread,
+            _balance_subscription_thread: balance_subscription_thread,
         }
     }
 }
@@ -178,6 +208,7 @@ pub struct ServiceApi<
     watcher_db: Option<WatcherDB>,
     network_state: Arc<RwLock<PollingNetworkState<T>>>,
     start_sync_thread: Arc<dyn Fn() + Send + Sync>,
+    balance_subscriptions: Arc<BalanceSubscriptions>,
     chain_id: String,
     logger: Logger,
 }
@@ -193,6 +224,7 @@ impl<T: BlockchainConnection + UserTxConnection + 'static, FPR: FogPubkeyResolve
             watcher_db: self.watcher_db.clone(),
             network_state: self.network_state.clone(),
             start_sync_thread: self.start_sync_thread.clone(),
+            balance_subscriptions: self.balance_subscriptions.clone(),
             chain_id: self.chain_id.clone(),
             logger: self.logger.clone(),
         }
@@ -209,6 +241,7 @@ impl<T: BlockchainConnection + UserTxConnection + 'static, FPR: FogPubkeyResolve
         watcher_db: Option<WatcherDB>,
         network_state: Arc<RwLock<PollingNetworkState<T>>>,
         start_sync_thread: Arc<dyn Fn() + Send + Sync>,
+        balance_subscriptions: Arc<BalanceSubscriptions>,
         chain_id: String,
         logger: Logger,
     ) -> Self {
@@ -219,6 +252,7 @@ impl<T: BlockchainConnection + UserTxConnection + 'static, FPR: FogPubkeyResolve
             watcher_db,
             network_state,
             start_sync_thread,
+            balance_subscriptions,
             chain_id,
             logger,
         }
@@ -399,6 +433,39 @@ impl<T: BlockchainConnection + UserTxConnection + 'static, FPR: FogPubkeyResolve
         Ok(response)
     }
 
+    fn set_unspent_tx_out_frozen_impl(
+        &mut self,
+        request: api::SetUnspentTxOutFrozenRequest,
+    ) -> Result<api::Empty, RpcStatus> {
+        // Get MonitorId from from the GRPC request.
+        let monitor_id = MonitorId::try_from(&request.monitor_id)
+            .map_err(|err| rpc_invalid_arg_error("monitor_id.try_from.bytes", err, &self.logger))?;
+
+        // Get list of key images from the request.
+        let key_images: Vec<KeyImage> = request
+            .get_key_images()
+            .iter()
+            .map(|key_image| {
+                KeyImage::try_from(key_image)
+                    .map_err(|err| rpc_invalid_arg_error("key_image.try_from", err, &self.logger))
+            })
+            .collect::<Result<Vec<KeyImage>, RpcStatus>>()?;
+
+        // Update the database.
+        self.mobilecoind_db
+            .set_utxos_frozen(&monitor_id, &key_images, request.frozen)
+            .map_err(|err| match err {
+                Error::UtxoIdNotFound => {
+                    RpcStatus::with_message(RpcStatusCode::NOT_FOUND, "key_images".into())
+                }
+                err => rpc_internal_error("mobilecoind_db.set_utxos_frozen", err, &self.logger),
+            })?;
+
+        // Return success response.
+        let response = api::Empty::new();
+        Ok(response)
+    }
+
     fn generate_root_entropy_impl(
         &mut self,
         _request: api::Empty,
@@ -584,6 +651,68 @@ impl<T: BlockchainConnection + UserTxConnection + 'static, FPR: FogPubkeyResolve
         Ok(response)
     }
 
+    fn validate_fog_address_impl(
+        &mut self,
+        request: api::ValidateFogAddressRequest,
+    ) -> Result<api::ValidateFogAddressResponse, RpcStatus> {
+        // Read the receiver proto
+        let receiver = PublicAddress::try_from(request.get_receiver())
+            .map_err(|err| rpc_invalid_arg_error("receiver.try_from", err, &self.logger))?;
+
+        let (verdict, num_blocks) = self
+            .transactions_manager
+            .validate_fog_address(&receiver, request.get_tombstone())
+            .map_err(|err| match err {
+                Error::InvalidArgument(..) => rpc_invalid_arg_error(
+                    "transactions_manager.validate_fog_address",
+                    err,
+                    &self.logger,
+                ),
+                err => rpc_internal_error(
+                    "transactions_manager.validate_fog_address",
+                    err,
+                    &self.logger,
+                ),
+            })?;
+
+        let mut response = api::ValidateFogAddressResponse::new();
+        response.set_num_blocks(num_blocks);
+        match verdict {
+            FogAddressVerdict::ValidWithoutFog => {
+                response.set_status(api::FogAddressStatus::ValidWithoutFog);
+            }
+            FogAddressVerdict::ValidWithFog {
+                pubkey_expiry,
+                tombstone_block,
+            } => {
+                response.set_status(api::FogAddressStatus::ValidWithFog);
+                response.set_pubkey_expiry(pubkey_expiry);
+                response.set_tombstone(tombstone_block);
+            }
+            FogAddressVerdict::InvalidFogReportUrl(err) => {
+                response.set_status(api::FogAddressStatus::InvalidFogReportUrl);
+                response.set_error(err);
+            }
+            FogAddressVerdict::FogReportUnavailable(err) => {
+                response.set_status(api::FogAddressStatus::FogReportUnavailable);
+                response.set_error(err);
+            }
+            FogAddressVerdict::FogReportVerificationFailed(err) => {
+                response.set_status(api::FogAddressStatus::FogReportVerificationFailed);
+                response.set_error(err);
+            }
+            FogAddressVerdict::FogPubkeyExpired { pubkey_expiry } => {
+                response.set_status(api::FogAddressStatus::FogPubkeyExpired);
+                response.set_pubkey_expiry(pubkey_expiry);
+                response.set_error(format!(
+                    "fog pubkey expired at block {pubkey_expiry}, ledger has {num_blocks} blocks"
+                ));
+            }
+        }
+
+        Ok(response)
+    }
+
     fn parse_request_code_impl(
         &mut self,
         request: api::ParseRequestCodeRequest,
@@ -735,6 +864,7 @@ impl<T: BlockchainConnection + UserTxConnection + 'static, FPR: FogPubkeyResolve
             attempted_spend_height: 0,
             attempted_spend_tombstone: 0,
             memo_payload,
+            frozen: false,
         };
 
         let mut response = api::ParseTransferCodeResponse::new();
@@ -860,6 +990,88 @@ impl<T: BlockchainConnection + UserTxConnection + 'static, FPR: FogPubkeyResolve
         Ok(response)
     }
 
+    fn parse_view_only_account_code_impl(
+        &mut self,
+        request: api::ParseViewOnlyAccountCodeRequest,
+    ) -> Result<api::ParseViewOnlyAccountCodeResponse, RpcStatus> {
+        let wrapper =
+            api::printable::PrintableWrapper::b58_decode(request.get_b58_code().to_string())
+                .map_err(|err| {
+                    rpc_invalid_arg_error("PrintableWrapper_b58_decode", err, &self.logger)
+                })?;
+
+        if !wrapper.has_view_only_account_bundle() {
+            return Err(RpcStatus::with_message(
+                RpcStatusCode::INVALID_ARGUMENT,
+                "Not a view-only account bundle".into(),
+            ));
+        }
+
+        // Convert through the account-keys type so that the keys are validated.
+        let bundle = ViewOnlyAccountBundle::try_from(wrapper.get_view_only_account_bundle())
+            .map_err(|err| {
+                rpc_invalid_arg_error("ViewOnlyAccountBundle.try_from", err, &self.logger)
+            })?;
+
+        let mut response = api::ParseViewOnlyAccountCodeResponse::new();
+        response.set_view_private_key(bundle.view_private_key().into());
+        response.set_spend_public_key(bundle.spend_public_key().into());
+        response.set_fog_report_url(bundle.fog_report_url().unwrap_or_default().to_string());
+        response.set_fog_report_id(bundle.fog_report_id().unwrap_or_default().to_string());
+        response.set_fog_authority_spki(bundle.fog_authority_spki().unwrap_or_default().to_vec());
+        if let Some(checkpoint) = bundle.key_image_checkpoint() {
+            response.set_has_key_image_checkpoint(true);
+            response.set_checkpoint_block_index(checkpoint.block_index);
+            response.set_key_images(RepeatedField::from_vec(
+                checkpoint.key_images.iter().map(Into::into).collect(),
+            ));
+        }
+        Ok(response)
+    }
+
+    fn create_view_only_account_code_impl(
+        &mut self,
+        request: api::CreateViewOnlyAccountCodeRequest,
+    ) -> Result<api::CreateViewOnlyAccountCodeResponse, RpcStatus> {
+        let monitor_id = MonitorId::try_from(&request.monitor_id)
+            .map_err(|err| rpc_invalid_arg_error("monitor_id.try_from.bytes", err, &self.logger))?;
+
+        // Read the monitor's data before its outputs, so that every output
+        // received before the checkpoint's block is included. Outputs the
+        // monitor processes in between are included too, which is harmless.
+        let data = self
+            .mobilecoind_db
+            .get_monitor_data(&monitor_id)
+            .map_err(|err| {
+                rpc_internal_error("mobilecoind_db.get_monitor_data", err, &self.logger)
+            })?;
+
+        let utxos = self
+            .mobilecoind_db
+            .get_utxos_for_monitor(&monitor_id)
+            .map_err(|err| {
+                rpc_internal_error("mobilecoind_db.get_utxos_for_monitor", err, &self.logger)
+            })?;
+
+        let checkpoint = KeyImageCheckpoint::new(
+            data.next_block,
+            utxos.iter().map(|utxo| utxo.key_image).collect(),
+        );
+        let bundle = ViewOnlyAccountBundle::from_account_key(&data.account_key, Some(checkpoint));
+
+        let mut wrapper = api::printable::PrintableWrapper::new();
+        wrapper.set_view_only_account_bundle((&bundle).into());
+
+        let encoded = wrapper
+            .b58_encode()
+            .map_err(|err| rpc_internal_error("b58_encode", err, &self.logger))?;
+
+        let mut response = api::CreateViewOnlyAccountCodeResponse::new();
+        response.set_b58_code(encoded);
+        response.set_checkpoint_block_index(data.next_block);
+        Ok(response)
+    }
+
     /// Get mixins
     fn get_mixins_impl(
         &mut self,
@@ -1228,6 +1440,71 @@ impl<T: BlockchainConnection + UserTxConnection + 'static, FPR: FogPubkeyResolve
         Ok(response)
     }
 
+    fn generate_split_tx_impl(
+        &mut self,
+        request: api::GenerateSplitTxRequest,
+    ) -> Result<api::GenerateSplitTxResponse, RpcStatus> {
+        // Get monitor id from request.
+        let monitor_id = MonitorId::try_from(&request.monitor_id)
+            .map_err(|err| rpc_internal_error("monitor_id.try_from.bytes", err, &self.logger))?;
+
+        // Generate split tx.
+        let tx_proposal = self
+            .transactions_manager
+            .generate_split_tx(
+                &monitor_id,
+                request.subaddress,
+                TokenId::from(request.token_id),
+                request.output_value,
+                request.num_outputs as usize,
+                &self.get_last_block_infos(),
+                request.fee,
+                request.tombstone,
+            )
+            .map_err(|err| {
+                rpc_internal_error("transactions_manager.generate_split_tx", err, &self.logger)
+            })?;
+
+        // Success.
+        let mut response = api::GenerateSplitTxResponse::new();
+        response.set_tx_proposal((&tx_proposal).into());
+        Ok(response)
+    }
+
+    fn generate_consolidation_tx_impl(
+        &mut self,
+        request: api::GenerateConsolidationTxRequest,
+    ) -> Result<api::GenerateConsolidationTxResponse, RpcStatus> {
+        // Get monitor id from request.
+        let monitor_id = MonitorId::try_from(&request.monitor_id)
+            .map_err(|err| rpc_internal_error("monitor_id.try_from.bytes", err, &self.logger))?;
+
+        // Generate consolidation tx.
+        let tx_proposal = self
+            .transactions_manager
+            .generate_consolidation_tx(
+                &monitor_id,
+                request.subaddress,
+                TokenId::from(request.token_id),
+                request.max_inputs as usize,
+                request.max_input_value,
+                &self.get_last_block_infos(),
+                request.fee,
+            )
+            .map_err(|err| {
+                rpc_internal_error(
+                    "transactions_manager.generate_consolidation_tx",
+                    err,
+                    &self.logger,
+                )
+            })?;
+
+        // Success.
+        let mut response = api::GenerateConsolidationTxResponse::new();
+        response.set_tx_proposal((&tx_proposal).into());
+        Ok(response)
+    }
+
     fn generate_tx_from_tx_out_list_impl(
         &mut self,
         request: api::GenerateTxFromTxOutListRequest,
@@ -1606,8 +1883,23 @@ impl<T: BlockchainConnection + UserTxConnection + 'static, FPR: FogPubkeyResolve
         let block_height = self
             .transactions_manager
             .submit_tx_proposal(&tx_proposal)
-            .map_err(|err| {
-                rpc_internal_error("transactions_manager.submit_tx_proposal", err, &self.logger)
+            .map_err(|err| match err {
+                // Consensus rejected the transaction. Report why as structured
+                // details, so that clients don't have to parse the message.
+                Error::Connection(retry::Error {
+                    error: ConnectionError::TransactionValidation(result, msg, details),
+                    ..
+                }) => {
+                    log::info!(self.logger, "Tx rejected: {:?}: {}", result, msg);
+                    RpcStatus::with_details(
+                        RpcStatusCode::FAILED_PRECONDITION,
+                        format!("Tx rejected: {result:?}: {msg}"),
+                        details.write_to_bytes().unwrap_or_default(),
+                    )
+                }
+                err => {
+                    rpc_internal_error("transactions_manager.submit_tx_proposal", err, &self.logger)
+                }
             })?;
 
         // Update the attempted spend block height in db. Note that we swallow the error
@@ -2161,16 +2453,7 @@ impl<T: BlockchainConnection + UserTxConnection + 'static, FPR: FogPubkeyResolve
             })?
             .iter()
             .map(|src| {
-                let mut dst = api::ProcessedTxOut::new();
-                dst.set_monitor_id(monitor_id.to_vec());
-                dst.set_subaddress_index(src.subaddress_index);
-                dst.set_public_key((&src.public_key).into());
-                dst.set_key_image((&src.key_image).into());
-                dst.set_value(src.value);
-                dst.set_direction(
-                    api::ProcessedTxOutDirection::from_i32(src.direction)
-                        .unwrap_or(api::ProcessedTxOutDirection::Invalid),
-                );
+                let mut dst = processed_tx_out_to_api(&monitor_id, src);
 
                 let subaddress = account_key.subaddress(src.subaddress_index);
                 let mut wrapper = api::printable::PrintableWrapper::new();
@@ -2179,7 +2462,6 @@ impl<T: BlockchainConnection + UserTxConnection + 'static, FPR: FogPubkeyResolve
                     .b58_encode()
                     .map_err(|err| rpc_internal_error("wrapper.b58_encode", err, &self.logger))?;
                 dst.set_address_code(encoded);
-                dst.set_token_id(src.token_id);
                 Ok(dst)
             })
             .collect::<Result<Vec<_>, _>>()?;
@@ -2294,6 +2576,43 @@ impl<T: BlockchainConnection + UserTxConnection + 'static, FPR: FogPubkeyResolve
         Ok(response)
     }
 
+    fn subscribe_balance_impl(
+        &mut self,
+        request: api::SubscribeBalanceRequest,
+    ) -> Result<Receiver<BalanceChange>, RpcStatus> {
+        // Get MonitorId from from the GRPC request.
+        let monitor_id = MonitorId::try_from(&request.monitor_id)
+            .map_err(|err| rpc_internal_error("monitor_id.try_from.bytes", err, &self.logger))?;
+
+        // Get monitor data for this monitor.
+        let monitor_data = self
+            .mobilecoind_db
+            .get_monitor_data(&monitor_id)
+            .map_err(|err| {
+                rpc_internal_error("mobilecoind_db.get_monitor_data", err, &self.logger)
+            })?;
+
+        // Only the monitor's subaddresses can change balance.
+        let subaddress_indexes = monitor_data.subaddress_indexes();
+        if let Some(index) = request
+            .subaddress_indices
+            .iter()
+            .find(|index| !subaddress_indexes.contains(index))
+        {
+            return Err(rpc_invalid_arg_error(
+                "subaddress_indices",
+                format!("subaddress {index} is not monitored by {monitor_id}"),
+                &self.logger,
+            ));
+        }
+
+        Ok(self.balance_subscriptions.subscribe(
+            monitor_id,
+            request.subaddress_indices,
+            monitor_data.next_block,
+        ))
+    }
+
     fn send_payment_impl(
         &mut self,
         request: api::SendPaymentRequest,
@@ -2584,6 +2903,42 @@ impl<T: BlockchainConnection + UserTxConnection + 'static, FPR: FogPubkeyResolve
     }
 }
 
+/// Convert a processed TxOut of a monitor, without its address code.
+fn processed_tx_out_to_api(monitor_id: &MonitorId, src: &ProcessedTxOut) -> api::ProcessedTxOut {
+    let mut dst = api::ProcessedTxOut::new();
+    dst.set_monitor_id(monitor_id.to_vec());
+    dst.set_subaddress_index(src.subaddress_index);
+    dst.set_public_key((&src.public_key).into());
+    dst.set_key_image((&src.key_image).into());
+    dst.set_value(src.value);
+    dst.set_direction(
+        api::ProcessedTxOutDirection::from_i32(src.direction)
+            .unwrap_or(api::ProcessedTxOutDirection::Invalid),
+    );
+    dst.set_token_id(src.token_id);
+    dst
+}
+
+impl From<BalanceChange> for api::BalanceChangeEvent {
+    fn from(src: BalanceChange) -> Self {
+        let mut dst = api::BalanceChangeEvent::new();
+        dst.set_monitor_id(src.monitor_id.to_vec());
+        dst.set_subaddress_index(src.subaddress_index);
+        dst.set_token_id(src.token_id);
+        dst.set_block_index(src.block_index);
+        dst.set_received(src.received);
+        dst.set_spent(src.spent);
+        dst.set_balance(src.balance);
+        dst.set_tx_outs(
+            src.tx_outs
+                .iter()
+                .map(|tx_out| processed_tx_out_to_api(&src.monitor_id, tx_out))
+                .collect(),
+        );
+        dst
+    }
+}
+
 macro_rules! build_api {
     ($( $service_function_name:ident $service_request_type:ident $service_response_type:ident $service_function_impl:ident $(,)?)+)
     =>
@@ -2605,6 +2960,36 @@ macro_rules! build_api {
                     )
                 }
             )+
+
+            fn subscribe_balance(
+                &mut self,
+                ctx: RpcContext,
+                request: api::SubscribeBalanceRequest,
+                sink: ServerStreamingSink<api::BalanceChangeEvent>,
+            ) {
+                let logger = rpc_logger(&ctx, &self.logger);
+                match self.subscribe_balance_impl(request) {
+                    Ok(changes) => {
+                        let mut events = changes.map(|change| {
+                            Ok((api::BalanceChangeEvent::from(change), WriteFlags::default()))
+                        });
+                        ctx.spawn(async move {
+                            let mut sink = sink;
+                            // The subscription ends when the client goes away.
+                            if let Err(err) = sink.send_all(&mut events).await {
+                                log::debug!(logger, "Balance subscription ended: {}", err);
+                            }
+                        });
+                    }
+                    Err(err) => {
+                        ctx.spawn(sink.fail(err).map(move |result| {
+                            if let Err(err) = result {
+                                log::error!(logger, "Failed to reply: {}", err);
+                            }
+                        }));
+                    }
+                }
+            }
         }
     );
 }
@@ -2617,6 +3002,7 @@ build_api! {
     get_monitor_status GetMonitorStatusRequest GetMonitorStatusResponse get_monitor_status_impl,
     get_unspent_tx_out_list GetUnspentTxOutListRequest GetUnspentTxOutListResponse get_unspent_tx_out_list_impl,
     get_all_unspent_tx_out GetAllUnspentTxOutRequest GetAllUnspentTxOutResponse get_all_unspent_tx_out_impl,
+    set_unspent_tx_out_frozen SetUnspentTxOutFrozenRequest Empty set_unspent_tx_out_frozen_impl,
 
     // Utilities
     generate_root_entropy Empty GenerateRootEntropyResponse generate_root_entropy_impl,
@@ -2626,6 +3012,7 @@ build_api! {
     get_public_address GetPublicAddressRequest GetPublicAddressResponse get_public_address_impl,
     get_short_address_hash GetShortAddressHashRequest GetShortAddressHashResponse get_short_address_hash_impl,
     validate_authenticated_sender_memo ValidateAuthenticatedSenderMemoRequest ValidateAuthenticatedSenderMemoResponse validate_authenticated_sender_memo_impl,
+    validate_fog_address ValidateFogAddressRequest ValidateFogAddressResponse validate_fog_address_impl,
 
     // b58 codes
     parse_request_code ParseRequestCodeRequest ParseRequestCodeResponse parse_request_code_impl,
@@ -2634,12 +3021,16 @@ build_api! {
     create_transfer_code CreateTransferCodeRequest CreateTransferCodeResponse create_transfer_code_impl,
     parse_address_code ParseAddressCodeRequest ParseAddressCodeResponse parse_address_code_impl,
     create_address_code CreateAddressCodeRequest CreateAddressCodeResponse create_address_code_impl,
+    parse_view_only_account_code ParseViewOnlyAccountCodeRequest ParseViewOnlyAccountCodeResponse parse_view_only_account_code_impl,
+    create_view_only_account_code CreateViewOnlyAccountCodeRequest CreateViewOnlyAccountCodeResponse create_view_only_account_code_impl,
 
     // Transactions
     get_mixins GetMixinsRequest GetMixinsResponse get_mixins_impl,
     get_membership_proofs GetMembershipProofsRequest GetMembershipProofsResponse get_membership_proofs_impl,
     generate_tx GenerateTxRequest GenerateTxResponse generate_tx_impl,
     generate_optimization_tx GenerateOptimizationTxRequest GenerateOptimizationTxResponse generate_optimization_tx_impl,
+    generate_split_tx GenerateSplitTxRequest GenerateSplitTxResponse generate_split_tx_impl,
+    generate_consolidation_tx GenerateConsolidationTxRequest GenerateConsolidationTxResponse generate_consolidation_tx_impl,
     generate_transfer_code_tx GenerateTransferCodeTxRequest GenerateTransferCodeTxResponse generate_transfer_code_tx_impl,
     generate_tx_from_tx_out_list GenerateTxFromTxOutListRequest GenerateTxFromTxOutListResponse generate_tx_from_tx_out_list_impl,
     generate_burn_redemption_tx GenerateBurnRedemptionTxRequest GenerateBurnRedemptionTxResponse generate_burn_redemption_tx_impl,
@@ -2705,7 +3096,7 @@ mod test {
         EmptyMemoBuilder, MemoBuilder, RTHMemoBuilder, TransactionBuilder, TxOutContext,
     };
     use mc_transaction_core::{
-        constants::{MAX_INPUTS, RING_SIZE},
+        constants::{MAX_INPUTS, MAX_OUTPUTS, RING_SIZE},
         encrypted_fog_hint::EncryptedFogHint,
         fog_hint::FogHint,
         get_tx_out_shared_secret,
@@ -2718,7 +3109,7 @@ mod test {
     use mc_util_repr_bytes::{typenum::U32, GenericArray, ReprBytes};
     use mc_util_uri::FogUri;
     use rand::{rngs::StdRng, SeedableRng};
-    use std::{assert_matches::assert_matches, str::FromStr};
+    use std::{assert_matches::assert_matches, collections::BTreeMap, str::FromStr};
 
     const BLOCK_VERSION: BlockVersion = BlockVersion::MAX;
 
@@ -3043,6 +3434,7 @@ mod test {
                     attempted_spend_height: 0,
                     attempted_spend_tombstone: 0,
                     memo_payload: MemoPayload::default().into(),
+                    frozen: false,
                 }
             })
             .collect();
@@ -3203,6 +3595,7 @@ mod test {
                     attempted_spend_height: 0,
                     attempted_spend_tombstone: 0,
                     memo_payload: MemoPayload::default().into(),
+                    frozen: false,
                 }
             })
             .collect();
@@ -3215,6 +3608,84 @@ mod test {
         );
     }
 
+    #[test_with_logger]
+    fn test_set_unspent_tx_out_frozen_impl(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([23u8; 32]);
+
+        let account_key = AccountKey::random(&mut rng);
+        let data = MonitorData::new(
+            account_key.clone(),
+            0,  // first_subaddress
+            20, // num_subaddresses
+            0,  // first_block
+            "", // name
+        )
+        .unwrap();
+
+        // 1 known recipient, 3 random recipients and no monitors.
+        let (ledger_db, mobilecoind_db, client, _server, _server_conn_manager) =
+            get_testing_environment(
+                BLOCK_VERSION,
+                3,
+                &[account_key.default_subaddress()],
+                &[],
+                logger.clone(),
+                &mut rng,
+            );
+
+        // Insert into database.
+        let monitor_id = mobilecoind_db.add_monitor(&data).unwrap();
+
+        // Allow the new monitor to process the ledger.
+        wait_for_monitors(&mobilecoind_db, &ledger_db, &logger);
+
+        let utxos = mobilecoind_db
+            .get_utxos_for_subaddress(&monitor_id, 0)
+            .unwrap();
+        assert!(utxos.len() > 2);
+
+        // Freeze the first two utxos.
+        let mut request = api::SetUnspentTxOutFrozenRequest::new();
+        request.set_monitor_id(monitor_id.to_vec());
+        request.set_key_images(RepeatedField::from_vec(vec![
+            (&utxos[0].key_image).into(),
+            (&utxos[1].key_image).into(),
+        ]));
+        request.set_frozen(true);
+        client.set_unspent_tx_out_frozen(&request).unwrap();
+
+        let mut request = api::GetUnspentTxOutListRequest::new();
+        request.set_monitor_id(monitor_id.to_vec());
+        request.set_subaddress_index(0);
+        let response = client.get_unspent_tx_out_list(&request).unwrap();
+        let frozen_key_images: HashSet<KeyImage> = response
+            .output_list
+            .iter()
+            .map(|proto_utxo| UnspentTxOut::try_from(proto_utxo).unwrap())
+            .filter(|utxo| utxo.frozen)
+            .map(|utxo| utxo.key_image)
+            .collect();
+        assert_eq!(
+            frozen_key_images,
+            HashSet::from_iter([utxos[0].key_image, utxos[1].key_image])
+        );
+
+        // Unknown key images should fail, and not freeze anything.
+        let mut request = api::SetUnspentTxOutFrozenRequest::new();
+        request.set_monitor_id(monitor_id.to_vec());
+        request.set_key_images(RepeatedField::from_vec(vec![
+            (&utxos[2].key_image).into(),
+            (&KeyImage::from(101)).into(),
+        ]));
+        request.set_frozen(true);
+        assert!(client.set_unspent_tx_out_frozen(&request).is_err());
+
+        let utxos = mobilecoind_db
+            .get_utxos_for_subaddress(&monitor_id, 0)
+            .unwrap();
+        assert_eq!(utxos.iter().filter(|utxo| utxo.frozen).count(), 2);
+    }
+
     #[test_with_logger]
     fn test_generate_root_entropy_impl(logger: Logger) {
         let mut rng: StdRng = SeedableRng::from_seed([23u8; 32]);
@@ -3505,6 +3976,138 @@ mod test {
         assert!(!response.success);
     }
 
+    #[test_with_logger]
+    fn test_validate_fog_address(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([21u8; 32]);
+
+        let pubkey = RistrettoPublic::from(&RistrettoPrivate::from_random(&mut rng));
+        let mut fog_pubkeys = BTreeMap::new();
+        fog_pubkeys.insert(
+            "fog://fresh.example.com".to_string(),
+            FullyValidatedFogPubkey {
+                pubkey,
+                pubkey_expiry: 10000,
+            },
+        );
+        fog_pubkeys.insert(
+            "fog://stale.example.com".to_string(),
+            FullyValidatedFogPubkey {
+                pubkey,
+                pubkey_expiry: 1,
+            },
+        );
+        let fog_resolver_factory: Arc<
+            dyn Fn(&[FogUri]) -> Result<MockFogResolver, String> + Send + Sync,
+        > = Arc::new(move |fog_uris| -> Result<MockFogResolver, String> {
+            if fog_uris
+                .iter()
+                .any(|uri| uri.to_string().contains("unreachable"))
+            {
+                return Err("Failed fetching fog reports".to_string());
+            }
+            Ok(MockFogResolver(fog_pubkeys.clone()))
+        });
+
+        let (ledger_db, mobilecoind_db) = test_utils::get_test_databases(
+            BLOCK_VERSION,
+            3,
+            &[],
+            test_utils::GET_TESTING_ENVIRONMENT_NUM_BLOCKS,
+            logger.clone(),
+            &mut rng,
+        );
+        let port = test_utils::get_free_port();
+        let uri =
+            MobilecoindUri::from_str(&format!("insecure-mobilecoind://127.0.0.1:{port}/")).unwrap();
+        let (_server, _server_conn_manager) = test_utils::setup_server::<MockFogResolver>(
+            logger.clone(),
+            ledger_db.clone(),
+            mobilecoind_db,
+            None,
+            Some(fog_resolver_factory),
+            &uri,
+        );
+        let client = test_utils::setup_client(&uri, &logger);
+
+        let num_blocks = ledger_db.num_blocks().unwrap();
+        let try_validate = |fog_report_url: Option<&str>, tombstone: u64| {
+            let mut rng: StdRng = SeedableRng::from_seed([22u8; 32]);
+            let account_key = match fog_report_url {
+                Some(url) => AccountKey::new_with_fog(
+                    &RistrettoPrivate::from_random(&mut rng),
+                    &RistrettoPrivate::from_random(&mut rng),
+                    url.to_string(),
+                    Default::default(),
+                    <Vec<u8>>::default(),
+                ),
+                None => AccountKey::random(&mut rng),
+            };
+            let mut request = api::ValidateFogAddressRequest::new();
+            request.set_receiver((&account_key.default_subaddress()).into());
+            request.set_tombstone(tombstone);
+            client.validate_fog_address(&request)
+        };
+        let validate = |fog_report_url: Option<&str>, tombstone: u64| {
+            try_validate(fog_report_url, tombstone).unwrap()
+        };
+
+        // An address without fog is always valid.
+        let response = validate(None, 0);
+        assert_eq!(
+            response.get_status(),
+            api::FogAddressStatus::ValidWithoutFog
+        );
+        assert_eq!(response.get_num_blocks(), num_blocks);
+
+        // A fresh fog pubkey gets the default tombstone block.
+        let response = validate(Some("fog://fresh.example.com"), 0);
+        assert_eq!(response.get_status(), api::FogAddressStatus::ValidWithFog);
+        assert_eq!(response.get_pubkey_expiry(), 10000);
+        assert_eq!(
+            response.get_tombstone(),
+            num_blocks + DEFAULT_NEW_TX_BLOCK_ATTEMPTS
+        );
+
+        // A requested tombstone block past the pubkey expiry is capped.
+        let response = validate(Some("fog://fresh.example.com"), 20000);
+        assert_eq!(response.get_status(), api::FogAddressStatus::ValidWithFog);
+        assert_eq!(response.get_tombstone(), 10000);
+
+        // A requested tombstone block which is not past the end of the ledger
+        // is rejected, because no transaction using it could land.
+        assert!(try_validate(Some("fog://fresh.example.com"), num_blocks).is_err());
+
+        // A pubkey expiring before the next block cannot be used.
+        let response = validate(Some("fog://stale.example.com"), 0);
+        assert_eq!(
+            response.get_status(),
+            api::FogAddressStatus::FogPubkeyExpired
+        );
+        assert_eq!(response.get_pubkey_expiry(), 1);
+
+        // No report for this url.
+        let response = validate(Some("fog://missing.example.com"), 0);
+        assert_eq!(
+            response.get_status(),
+            api::FogAddressStatus::FogReportUnavailable
+        );
+        assert!(!response.get_error().is_empty());
+
+        // The report server could not be reached.
+        let response = validate(Some("fog://unreachable.example.com"), 0);
+        assert_eq!(
+            response.get_status(),
+            api::FogAddressStatus::FogReportUnavailable
+        );
+
+        // The fog url does not parse.
+        let response = validate(Some("http://not-fog.example.com"), 0);
+        assert_eq!(
+            response.get_status(),
+            api::FogAddressStatus::InvalidFogReportUrl
+        );
+    }
+
     #[test_with_logger]
     fn test_vectors_validate_authenticated_sender_memo_impl(logger: Logger) {
         // In this test, we take an actual TxOut generated by signal, at block version
@@ -4168,6 +4771,7 @@ mod test {
                     attempted_spend_tombstone: 0,
                     token_id: *Mob::ID,
                     memo_payload: vec![],
+                    frozen: false,
                 }
             })
             .collect();
@@ -6120,6 +6724,63 @@ mod test {
         );
     }
 
+    #[test_with_logger]
+    fn test_generate_split_tx(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([23u8; 32]);
+
+        let sender = AccountKey::random(&mut rng);
+        let data = MonitorData::new(
+            sender.clone(),
+            0,  // first_subaddress
+            20, // num_subaddresses
+            0,  // first_block
+            "", // name
+        )
+        .unwrap();
+
+        // 1 known recipient, 3 random recipients and no monitors.
+        let (ledger_db, mobilecoind_db, client, _server, _server_conn_manager) =
+            get_testing_environment(
+                BLOCK_VERSION,
+                3,
+                &[sender.default_subaddress()],
+                &[],
+                logger.clone(),
+                &mut rng,
+            );
+
+        // Insert into database.
+        let monitor_id = mobilecoind_db.add_monitor(&data).unwrap();
+
+        // Allow the new monitor to process the ledger.
+        wait_for_monitors(&mobilecoind_db, &ledger_db, &logger);
+
+        // Split into 3 outputs, which a single utxo can pay for.
+        let output_value = DEFAULT_PER_RECIPIENT_AMOUNT / 4;
+        let mut request = api::GenerateSplitTxRequest::new();
+        request.set_monitor_id(monitor_id.to_vec());
+        request.set_subaddress(0);
+        request.set_output_value(output_value);
+        request.set_num_outputs(3);
+
+        let response = client.generate_split_tx(&request).unwrap();
+        let tx_proposal = TxProposal::try_from(response.get_tx_proposal()).unwrap();
+
+        assert_eq!(tx_proposal.utxos.len(), 1);
+        assert_eq!(tx_proposal.outlays.len(), 3);
+        for outlay in tx_proposal.outlays.iter() {
+            assert_eq!(outlay.receiver, sender.subaddress(0));
+            assert_eq!(outlay.amount, Amount::new(output_value, Mob::ID));
+        }
+
+        // The outlays plus change.
+        assert_eq!(tx_proposal.tx.prefix.outputs.len(), 4);
+
+        // Too many outputs should fail.
+        request.set_num_outputs(MAX_OUTPUTS as u32);
+        assert!(client.generate_split_tx(&request).is_err());
+    }
+
     #[test_with_logger]
     fn test_generate_tx_from_tx_out_list(logger: Logger) {
         let mut rng: StdRng = SeedableRng::from_seed([23u8; 32]);
@@ -6444,6 +7105,83 @@ mod test {
         assert!(client.get_balance(&request).is_err());
     }
 
+    #[test_with_logger]
+    fn test_subscribe_balance(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([23u8; 32]);
+
+        let account_key = AccountKey::random(&mut rng);
+        let data = MonitorData::new(
+            account_key.clone(),
+            0,  // first_subaddress
+            20, // num_subaddresses
+            0,  // first_block
+            "", // name
+        )
+        .unwrap();
+
+        // 1 known recipient, 3 random recipients and no monitors.
+        let (mut ledger_db, mobilecoind_db, client, _server, _server_conn_manager) =
+            get_testing_environment(
+                BLOCK_VERSION,
+                3,
+                &[account_key.default_subaddress()],
+                &[],
+                logger.clone(),
+                &mut rng,
+            );
+
+        // Insert into database.
+        let id = mobilecoind_db.add_monitor(&data).unwrap();
+
+        // Allow the new monitor to process the ledger.
+        wait_for_monitors(&mobilecoind_db, &ledger_db, &logger);
+        let balance = test_utils::DEFAULT_PER_RECIPIENT_AMOUNT * ledger_db.num_blocks().unwrap();
+
+        // Subscribe to the default subaddress.
+        let mut request = api::SubscribeBalanceRequest::new();
+        request.set_monitor_id(id.to_vec());
+        request.set_subaddress_indices(vec![0]);
+        let mut events = client.subscribe_balance(&request).unwrap();
+
+        // Pay the default subaddress in a new block.
+        let block_data = add_block_to_ledger(
+            &mut ledger_db,
+            BLOCK_VERSION,
+            &[
+                AccountKey::random(&mut rng).default_subaddress(),
+                account_key.default_subaddress(),
+            ],
+            Amount::new(1000, Mob::ID),
+            &[KeyImage::from(101)],
+            &mut rng,
+        )
+        .unwrap();
+
+        // The change is streamed once the monitor processes the block.
+        let event = futures::executor::block_on(events.next())
+            .expect("subscription ended")
+            .unwrap();
+        assert_eq!(event.monitor_id, id.to_vec());
+        assert_eq!(event.subaddress_index, 0);
+        assert_eq!(event.token_id, *Mob::ID);
+        assert_eq!(event.block_index, block_data.block().index);
+        assert_eq!(event.received, 1000);
+        assert_eq!(event.spent, 0);
+        assert_eq!(event.balance, balance + 1000);
+        assert_eq!(event.tx_outs.len(), 1);
+        assert_eq!(
+            event.tx_outs[0].get_direction(),
+            api::ProcessedTxOutDirection::Received
+        );
+
+        // Subaddresses the monitor doesn't have should error.
+        let mut request = api::SubscribeBalanceRequest::new();
+        request.set_monitor_id(id.to_vec());
+        request.set_subaddress_indices(vec![20]);
+        let mut events = client.subscribe_balance(&request).unwrap();
+        assert!(futures::executor::block_on(events.next()).unwrap().is_err());
+    }
+
     #[test_with_logger]
     fn test_send_payment(logger: Logger) {
         let mut rng: StdRng = SeedableRng::from_seed([23u8; 32]);
@@ -7569,6 +8307,100 @@ mod test {
         }
     }
 
+    #[test_with_logger]
+    fn test_view_only_account_code(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([23u8; 32]);
+
+        let account_key = AccountKey::random(&mut rng);
+        let data = MonitorData::new(
+            account_key.clone(),
+            0,  // first_subaddress
+            20, // num_subaddresses
+            0,  // first_block
+            "", // name
+        )
+        .unwrap();
+
+        // 1 known recipient, 3 random recipients and no monitors.
+        let (ledger_db, mobilecoind_db, client, _server, _server_conn_manager) =
+            get_testing_environment(
+                BLOCK_VERSION,
+                3,
+                &[account_key.default_subaddress()],
+                &[],
+                logger.clone(),
+                &mut rng,
+            );
+
+        // Insert into database.
+        let id = mobilecoind_db.add_monitor(&data).unwrap();
+
+        // Allow the new monitor to process the ledger.
+        wait_for_monitors(&mobilecoind_db, &ledger_db, &logger);
+
+        // Export the monitor's account.
+        let mut request = api::CreateViewOnlyAccountCodeRequest::new();
+        request.set_monitor_id(id.to_vec());
+
+        let response = client.create_view_only_account_code(&request).unwrap();
+        let num_blocks = ledger_db.num_blocks().unwrap();
+        assert_eq!(response.get_checkpoint_block_index(), num_blocks);
+
+        // Attempt to decode it.
+        let mut request = api::ParseViewOnlyAccountCodeRequest::new();
+        request.set_b58_code(response.get_b58_code().to_string());
+
+        let response = client.parse_view_only_account_code(&request).unwrap();
+
+        // Check that the code carries the view-only part of the account, and the key
+        // images of its unspent outputs.
+        assert_eq!(
+            RistrettoPrivate::try_from(response.get_view_private_key()).unwrap(),
+            *account_key.view_private_key()
+        );
+        assert_eq!(
+            RistrettoPublic::try_from(response.get_spend_public_key()).unwrap(),
+            RistrettoPublic::from(account_key.spend_private_key())
+        );
+        assert_eq!(response.get_fog_report_url(), "");
+        assert!(response.get_has_key_image_checkpoint());
+        assert_eq!(response.get_checkpoint_block_index(), num_blocks);
+
+        let key_images: HashSet<KeyImage> = response
+            .get_key_images()
+            .iter()
+            .map(|key_image| KeyImage::try_from(key_image).unwrap())
+            .collect();
+        let expected_key_images: HashSet<KeyImage> = mobilecoind_db
+            .get_utxos_for_monitor(&id)
+            .unwrap()
+            .iter()
+            .map(|utxo| utxo.key_image)
+            .collect();
+        assert_eq!(key_images.len(), num_blocks as usize);
+        assert_eq!(key_images, expected_key_images);
+
+        // Other codes are not view-only account codes.
+        {
+            let mut request = api::CreateAddressCodeRequest::new();
+            request.set_receiver(mc_api::external::PublicAddress::from(
+                &account_key.default_subaddress(),
+            ));
+            let response = client.create_address_code(&request).unwrap();
+
+            let mut request = api::ParseViewOnlyAccountCodeRequest::new();
+            request.set_b58_code(response.get_b58_code().to_string());
+            assert!(client.parse_view_only_account_code(&request).is_err());
+        }
+
+        // Unknown monitors can't be exported.
+        {
+            let mut request = api::CreateViewOnlyAccountCodeRequest::new();
+            request.set_monitor_id(vec![3; 32]);
+            assert!(client.create_view_only_account_code(&request).is_err());
+        }
+    }
+
     #[test_with_logger]
     fn test_get_network_status(logger: Logger) {
         let mut rng: StdRng = SeedableRng::from_seed([23u8; 32]);
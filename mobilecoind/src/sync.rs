@@ -476,6 +476,7 @@ fn match_tx_outs_into_utxos(
                 attempted_spend_tombstone: 0,
                 token_id: *amount.token_id,
                 memo_payload,
+                frozen: false,
             }))
         })
         .collect();
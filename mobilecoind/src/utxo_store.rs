@@ -66,6 +66,11 @@ pub struct UnspentTxOut {
     // mobilecoind can interpret the new memos without having to rescan and rebuild the db.
     #[prost(bytes, tag = "8")]
     pub memo_payload: Vec<u8>,
+
+    /// Whether this UnspentTxOut is frozen. mobilecoind never selects frozen
+    /// UnspentTxOuts as inputs, but they can still be spent explicitly.
+    #[prost(bool, tag = "12")]
+    pub frozen: bool,
 }
 
 /// Type used as the key in the utxo_id_to_utxo  database.
@@ -404,6 +409,41 @@ impl UtxoStore {
         Ok(())
     }
 
+    /// Freeze or unfreeze the UnspentTxOuts of a monitor with the given key
+    /// images. Fails with [Error::UtxoIdNotFound], without changing any of
+    /// them, if one of the key images is not an UnspentTxOut of the monitor.
+    pub fn set_frozen(
+        &self,
+        db_txn: &mut RwTransaction<'_>,
+        monitor_id: &MonitorId,
+        key_images: &[KeyImage],
+        frozen: bool,
+    ) -> Result<(), Error> {
+        let mut utxos = Vec::with_capacity(key_images.len());
+        for key_image in key_images {
+            let utxo_id = UtxoId::from(key_image);
+            let subaddress_id = self.get_subaddress_id_by_utxo_id(db_txn, &utxo_id)?;
+            if subaddress_id.monitor_id != *monitor_id {
+                return Err(Error::UtxoIdNotFound);
+            }
+            utxos.push((utxo_id, self.get_utxo_by_id(db_txn, &utxo_id)?));
+        }
+
+        for (utxo_id, mut utxo) in utxos {
+            utxo.frozen = frozen;
+
+            let utxo_bytes = mc_util_serial::encode(&utxo);
+            db_txn.put(
+                self.utxo_id_to_utxo,
+                &utxo_id,
+                &utxo_bytes,
+                WriteFlags::empty(),
+            )?;
+        }
+
+        Ok(())
+    }
+
     /// Get all UtxoIds associated with a given subaddress.
     fn get_utxo_ids(
         &self,
@@ -495,6 +535,7 @@ mod test {
     use mc_rand::{CryptoRng, RngCore};
     use mc_transaction_core::{tokens::Mob, Token};
     use rand::{rngs::StdRng, SeedableRng};
+    use std::assert_matches::assert_matches;
     use tempfile::TempDir;
 
     fn setup_test_utxo_store(
@@ -518,6 +559,7 @@ mod test {
                     attempted_spend_tombstone: 0,
                     token_id: *Mob::ID,
                     memo_payload: vec![],
+                    frozen: false,
                 }
             })
             .collect();
@@ -1039,4 +1081,90 @@ mod test {
             }
         }
     }
+
+    #[test_with_logger]
+    fn test_set_frozen(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([123u8; 32]);
+        let (env, _ledger_db, utxo_store, utxos) = setup_test_utxo_store(&mut rng, &logger);
+        let (_monitor_data, monitor_id) = get_test_monitor_data_and_id(&mut rng);
+        let (_other_monitor_data, other_monitor_id) = get_test_monitor_data_and_id(&mut rng);
+
+        {
+            let mut db_txn = env.begin_rw_txn().unwrap();
+            for utxo in utxos.iter() {
+                utxo_store
+                    .append_utxo(&mut db_txn, &monitor_id, utxo.subaddress_index, utxo)
+                    .unwrap();
+            }
+            db_txn.commit().unwrap();
+        }
+
+        let frozen_key_images = vec![utxos[0].key_image, utxos[1].key_image];
+
+        // Freeze two of the utxos.
+        {
+            let mut db_txn = env.begin_rw_txn().unwrap();
+            utxo_store
+                .set_frozen(&mut db_txn, &monitor_id, &frozen_key_images, true)
+                .unwrap();
+            db_txn.commit().unwrap();
+        }
+
+        {
+            let db_txn = env.begin_ro_txn().unwrap();
+            for (i, utxo) in utxos.iter().enumerate() {
+                let utxo = utxo_store
+                    .get_utxo_by_id(&db_txn, &UtxoId::from(utxo))
+                    .unwrap();
+                assert_eq!(utxo.frozen, i < 2);
+            }
+        }
+
+        // Key images that don't belong to the monitor are rejected, and nothing
+        // changes.
+        {
+            let mut db_txn = env.begin_rw_txn().unwrap();
+            assert_matches!(
+                utxo_store.set_frozen(
+                    &mut db_txn,
+                    &monitor_id,
+                    &[utxos[0].key_image, KeyImage::from(1234567)],
+                    false,
+                ),
+                Err(Error::UtxoIdNotFound)
+            );
+            assert_matches!(
+                utxo_store.set_frozen(&mut db_txn, &other_monitor_id, &frozen_key_images, false),
+                Err(Error::UtxoIdNotFound)
+            );
+            db_txn.commit().unwrap();
+        }
+
+        {
+            let db_txn = env.begin_ro_txn().unwrap();
+            let utxo = utxo_store
+                .get_utxo_by_id(&db_txn, &UtxoId::from(&utxos[0]))
+                .unwrap();
+            assert!(utxo.frozen);
+        }
+
+        // Unfreeze them again.
+        {
+            let mut db_txn = env.begin_rw_txn().unwrap();
+            utxo_store
+                .set_frozen(&mut db_txn, &monitor_id, &frozen_key_images, false)
+                .unwrap();
+            db_txn.commit().unwrap();
+        }
+
+        {
+            let db_txn = env.begin_ro_txn().unwrap();
+            for utxo in utxos.iter() {
+                let utxo = utxo_store
+                    .get_utxo_by_id(&db_txn, &UtxoId::from(utxo))
+                    .unwrap();
+                assert!(!utxo.frozen);
+            }
+        }
+    }
 }
@@ -0,0 +1,447 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Code for notifying webhooks of deposits and spent key images, so that
+//! integrators don't have to poll for balance changes.
+//!
+//! Each event is POSTed as JSON to every configured webhook URL, with an
+//! `X-MobileCoin-Timestamp` header holding the time it was sent, in seconds
+//! since the Unix epoch, and an `X-MobileCoin-Signature` header holding
+//! `sha256=` followed by the hex HMAC-SHA256, keyed with the webhook secret,
+//! of the timestamp, a `.`, and the body.
+//!
+//! Events are generated from the processed blocks of each monitor, starting
+//! at the monitor's next block when it is first seen by the webhook thread.
+//! The next block to generate events for, the deposits which still need to be
+//! reported as confirmed, and the events which still need to be delivered are
+//! stored in the mobilecoind database, so events for blocks processed while
+//! mobilecoind is not running are generated after it restarts. A separate
+//! thread delivers the queued events in order, so that a slow webhook doesn't
+//! hold up generating them. An event which can't be delivered after the
+//! configured number of attempts is dropped. The `event_id` of an event is
+//! stable, and an event may be delivered again if mobilecoind stops while
+//! delivering it, so receivers should deduplicate.
+
+use crate::{
+    config::WebhookConfig,
+    database::Database,
+    error::Error,
+    monitor_store::MonitorId,
+    processed_block_store::{ProcessedTxOut, ProcessedTxOutDirection},
+    webhook_store::{WebhookEvent, WebhookEventKind},
+};
+use hex_fmt::HexFmt;
+use hmac::{Hmac, Mac};
+use mc_common::logger::{log, Logger};
+use mc_ledger_db::{Ledger, LedgerDB};
+use reqwest::{blocking::Client, header::CONTENT_TYPE};
+use retry::{
+    delay::{jitter, Exponential},
+    OperationResult,
+};
+use serde_json::json;
+use sha2::Sha256;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Header holding the time an event was sent.
+pub const TIMESTAMP_HEADER: &str = "X-MobileCoin-Timestamp";
+
+/// Header holding the signature of an event.
+pub const SIGNATURE_HEADER: &str = "X-MobileCoin-Signature";
+
+/// Delay before the first retry of delivering an event, in milliseconds.
+const RETRY_BASE_DELAY_MILLIS: u64 = 500;
+
+/// Factor the delay between attempts to deliver an event grows by.
+const RETRY_FACTOR: f64 = 2.0;
+
+/// Maximum delay between attempts to deliver an event.
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Webhook Thread - holds objects needed to cleanly terminate the threads
+/// which generate and deliver webhook events.
+pub struct WebhookThread {
+    /// The handles of the threads generating and delivering events.
+    join_handles: Vec<thread::JoinHandle<()>>,
+
+    /// Stop trigger, used to signal the threads to terminate.
+    stop_requested: Arc<AtomicBool>,
+}
+
+impl WebhookThread {
+    pub fn start(
+        mobilecoind_db: Database,
+        ledger_db: LedgerDB,
+        config: WebhookConfig,
+        poll_interval: Duration,
+        logger: Logger,
+    ) -> Self {
+        let stop_requested = Arc::new(AtomicBool::new(false));
+
+        let generate_thread = {
+            let mobilecoind_db = mobilecoind_db.clone();
+            let confirmations = config.webhook_confirmations;
+            let stop_requested = stop_requested.clone();
+            let logger = logger.clone();
+            thread::spawn(move || {
+                generate_events_thread_entry_point(
+                    mobilecoind_db,
+                    ledger_db,
+                    confirmations,
+                    poll_interval,
+                    stop_requested,
+                    logger,
+                );
+            })
+        };
+
+        let deliver_thread = {
+            let stop_requested = stop_requested.clone();
+            thread::spawn(move || {
+                deliver_events_thread_entry_point(
+                    mobilecoind_db,
+                    config,
+                    poll_interval,
+                    stop_requested,
+                    logger,
+                );
+            })
+        };
+
+        Self {
+            join_handles: vec![generate_thread, deliver_thread],
+            stop_requested,
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.stop_requested.store(true, Ordering::SeqCst);
+        for join_handle in self.join_handles.drain(..) {
+            join_handle.join().expect("WebhookThread join failed");
+        }
+    }
+}
+
+impl Drop for WebhookThread {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl WebhookEventKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Invalid => "invalid",
+            Self::DepositDetected => "deposit_detected",
+            Self::DepositConfirmed => "deposit_confirmed",
+            Self::WithdrawalSpent => "withdrawal_spent",
+        }
+    }
+}
+
+impl WebhookEvent {
+    fn kind_str(&self) -> &'static str {
+        WebhookEventKind::try_from(self.kind)
+            .unwrap_or(WebhookEventKind::Invalid)
+            .as_str()
+    }
+
+    /// An id which is the same every time this event is generated.
+    fn id(&self) -> String {
+        format!(
+            "{}:{}:{}:{}",
+            self.kind_str(),
+            self.monitor_id,
+            self.block_index,
+            HexFmt(self.tx_out.public_key.as_bytes())
+        )
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "event_id": self.id(),
+            "type": self.kind_str(),
+            "monitor_id": self.monitor_id.to_string(),
+            "subaddress_index": self.tx_out.subaddress_index,
+            "block_index": self.block_index,
+            "public_key": HexFmt(self.tx_out.public_key.as_bytes()).to_string(),
+            "key_image": HexFmt(&self.tx_out.key_image).to_string(),
+            "value": self.tx_out.value,
+            "token_id": self.tx_out.token_id,
+        })
+    }
+}
+
+/// The events for the TxOuts a monitor received and spent in a block.
+fn block_events(
+    monitor_id: &MonitorId,
+    block_index: u64,
+    tx_outs: Vec<ProcessedTxOut>,
+) -> Vec<WebhookEvent> {
+    tx_outs
+        .into_iter()
+        .filter_map(|tx_out| {
+            let kind = match ProcessedTxOutDirection::try_from(tx_out.direction) {
+                Ok(ProcessedTxOutDirection::Received) => WebhookEventKind::DepositDetected,
+                Ok(ProcessedTxOutDirection::Spent) => WebhookEventKind::WithdrawalSpent,
+                _ => return None,
+            };
+            Some(WebhookEvent {
+                kind: kind as i32,
+                monitor_id: *monitor_id,
+                block_index,
+                tx_out,
+            })
+        })
+        .collect()
+}
+
+/// The value of the signature header for a body sent at a given time.
+pub fn sign_payload(secret: &[u8], timestamp: u64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={}", HexFmt(mac.finalize().into_bytes()))
+}
+
+/// The delays between attempts to deliver an event, given the maximum number
+/// of attempts.
+fn retry_delays(max_attempts: usize) -> impl Iterator<Item = Duration> {
+    Exponential::from_millis_with_factor(RETRY_BASE_DELAY_MILLIS, RETRY_FACTOR)
+        .map(|delay| delay.min(RETRY_MAX_DELAY))
+        .map(jitter)
+        .take(max_attempts.saturating_sub(1))
+}
+
+fn generate_events_thread_entry_point(
+    mobilecoind_db: Database,
+    ledger_db: LedgerDB,
+    confirmations: u64,
+    poll_interval: Duration,
+    stop_requested: Arc<AtomicBool>,
+    logger: Logger,
+) {
+    log::info!(logger, "Webhook event generation thread started");
+
+    loop {
+        if stop_requested.load(Ordering::SeqCst) {
+            break;
+        }
+
+        match mobilecoind_db.get_monitor_map() {
+            Ok(monitors) => {
+                for (monitor_id, monitor_data) in monitors.iter() {
+                    if let Err(err) = generate_monitor_events(
+                        &mobilecoind_db,
+                        monitor_id,
+                        monitor_data.next_block,
+                    ) {
+                        log::error!(
+                            logger,
+                            "Error generating webhook events for monitor {}: {:?}",
+                            monitor_id,
+                            err
+                        );
+                    }
+                }
+            }
+            Err(err) => {
+                log::error!(logger, "Error getting monitors: {:?}", err);
+            }
+        }
+
+        match ledger_db.num_blocks() {
+            Ok(num_blocks) => {
+                if let Err(err) =
+                    mobilecoind_db.webhook_deposits_confirmed(num_blocks, confirmations)
+                {
+                    log::error!(logger, "Error generating confirmation events: {:?}", err);
+                }
+            }
+            Err(err) => log::error!(logger, "Error getting number of blocks: {:?}", err),
+        }
+
+        thread::sleep(poll_interval);
+    }
+}
+
+/// Generate the events for the blocks a monitor processed since the last
+/// call, up to `monitor_next_block`.
+fn generate_monitor_events(
+    mobilecoind_db: &Database,
+    monitor_id: &MonitorId,
+    monitor_next_block: u64,
+) -> Result<(), Error> {
+    let mut block_index = mobilecoind_db.get_webhook_next_block(monitor_id)?;
+    while block_index < monitor_next_block {
+        let tx_outs = mobilecoind_db.get_processed_block(monitor_id, block_index)?;
+        let events = block_events(monitor_id, block_index, tx_outs);
+        mobilecoind_db.webhook_block_events_generated(monitor_id, block_index, &events)?;
+        block_index += 1;
+    }
+    Ok(())
+}
+
+fn deliver_events_thread_entry_point(
+    mobilecoind_db: Database,
+    config: WebhookConfig,
+    poll_interval: Duration,
+    stop_requested: Arc<AtomicBool>,
+    logger: Logger,
+) {
+    log::info!(logger, "Webhook event delivery thread started");
+
+    let client = Client::new();
+    let secret = config.webhook_secret.clone().unwrap_or_default();
+
+    loop {
+        if stop_requested.load(Ordering::SeqCst) {
+            break;
+        }
+
+        match mobilecoind_db.peek_webhook_event() {
+            Ok(None) => thread::sleep(poll_interval),
+
+            Ok(Some((index, event))) => {
+                send_event(&client, &config, secret.as_bytes(), &event, &logger);
+
+                if let Err(err) = mobilecoind_db.remove_webhook_event(index) {
+                    log::error!(
+                        logger,
+                        "Error removing webhook event {} from queue: {:?}",
+                        event.id(),
+                        err
+                    );
+                    thread::sleep(poll_interval);
+                }
+            }
+
+            Err(err) => {
+                log::error!(logger, "Error getting next webhook event: {:?}", err);
+                thread::sleep(poll_interval);
+            }
+        }
+    }
+}
+
+/// Deliver an event to every webhook, retrying failed attempts.
+fn send_event(
+    client: &Client,
+    config: &WebhookConfig,
+    secret: &[u8],
+    event: &WebhookEvent,
+    logger: &Logger,
+) {
+    let body = event.to_json().to_string();
+    for url in config.webhook_urls.iter() {
+        let result = retry::retry(retry_delays(config.webhook_max_attempts), || {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            let response = client
+                .post(url)
+                .header(CONTENT_TYPE, "application/json")
+                .header(TIMESTAMP_HEADER, timestamp.to_string())
+                .header(
+                    SIGNATURE_HEADER,
+                    sign_payload(secret, timestamp, body.as_bytes()),
+                )
+                .body(body.clone())
+                .send()
+                .and_then(|response| response.error_for_status());
+            match response {
+                Ok(_) => OperationResult::Ok(()),
+                Err(err) => OperationResult::Retry(err),
+            }
+        });
+
+        match result {
+            Ok(()) => log::debug!(logger, "Sent event {} to {}", event.id(), url),
+            Err(err) => log::error!(
+                logger,
+                "Giving up on sending event {} to {} after {} attempts: {}",
+                event.id(),
+                url,
+                err.tries,
+                err.error
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mc_crypto_keys::CompressedRistrettoPublic;
+    use mc_transaction_core::ring_signature::KeyImage;
+
+    fn tx_out(direction: ProcessedTxOutDirection, value: u64) -> ProcessedTxOut {
+        ProcessedTxOut {
+            subaddress_index: 0,
+            public_key: CompressedRistrettoPublic::try_from(&[value as u8; 32]).unwrap(),
+            key_image: KeyImage::from(value),
+            value,
+            direction: direction as i32,
+            token_id: 0,
+        }
+    }
+
+    #[test]
+    fn test_sign_payload() {
+        // HMAC-SHA256 of "1700000000.{}" with key "key".
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"key").unwrap();
+        mac.update(b"1700000000.{}");
+        let expected = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+
+        assert_eq!(sign_payload(b"key", 1_700_000_000, b"{}"), expected);
+        assert_ne!(sign_payload(b"other key", 1_700_000_000, b"{}"), expected);
+        assert_ne!(sign_payload(b"key", 1_700_000_001, b"{}"), expected);
+    }
+
+    #[test]
+    fn test_block_events() {
+        let monitor_id = MonitorId::from([1u8; 32]);
+        let events = block_events(
+            &monitor_id,
+            10,
+            vec![
+                tx_out(ProcessedTxOutDirection::Received, 1),
+                tx_out(ProcessedTxOutDirection::Spent, 2),
+                tx_out(ProcessedTxOutDirection::Invalid, 3),
+            ],
+        );
+        assert_eq!(
+            events
+                .iter()
+                .map(|event| event.kind_str())
+                .collect::<Vec<_>>(),
+            vec!["deposit_detected", "withdrawal_spent"]
+        );
+        assert_eq!(events[0].to_json()["value"], 1);
+        assert_eq!(events[0].to_json()["event_id"], events[0].id());
+        assert_ne!(events[0].id(), events[1].id());
+
+        let confirmed = WebhookEvent {
+            kind: WebhookEventKind::DepositConfirmed as i32,
+            ..events[0].clone()
+        };
+        assert_eq!(confirmed.to_json()["type"], "deposit_confirmed");
+        assert_ne!(confirmed.id(), events[0].id());
+    }
+
+    #[test]
+    fn test_retry_delays() {
+        let delays = retry_delays(20).collect::<Vec<_>>();
+        assert_eq!(delays.len(), 19);
+        assert!(delays.iter().all(|delay| *delay <= RETRY_MAX_DELAY));
+        assert_eq!(retry_delays(1).count(), 0);
+    }
+}
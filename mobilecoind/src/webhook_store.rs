@@ -0,0 +1,480 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Database storage for webhook notifications.
+//! * Stores, for each monitor, the next processed block to generate events for.
+//! * Stores the deposits which still need to be reported as confirmed.
+//! * Stores a queue (outbox) of events which still need to be delivered.
+
+use crate::{
+    error::Error,
+    monitor_store::MonitorId,
+    processed_block_store::{ProcessedBlockKey, ProcessedTxOut},
+};
+use lmdb::{Cursor, Database, DatabaseFlags, Environment, RwTransaction, Transaction, WriteFlags};
+use mc_common::logger::Logger;
+use prost::{Enumeration, Message};
+use std::sync::Arc;
+
+// LMDB Database Names
+pub const MONITOR_ID_TO_NEXT_BLOCK_DB_NAME: &str =
+    "mobilecoind_db:webhook_store:monitor_id_to_next_block";
+pub const PENDING_DEPOSITS_DB_NAME: &str = "mobilecoind_db:webhook_store:pending_deposits";
+pub const OUTBOX_DB_NAME: &str = "mobilecoind_db:webhook_store:outbox";
+pub const COUNTERS_DB_NAME: &str = "mobilecoind_db:webhook_store:counters";
+
+// Key we use for storing how many events we have added to the outbox. This
+// gives us a monotonically increasing index for each event, so that events are
+// delivered in the order they were generated.
+pub const OUTBOX_COUNTER_KEY: &str = "outbox_counter";
+
+/// The kinds of event reported to webhooks.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Enumeration)]
+pub enum WebhookEventKind {
+    Invalid = 0,
+    /// A TxOut belonging to a monitor appeared in a block.
+    DepositDetected = 1,
+    /// A TxOut belonging to a monitor is at the configured depth.
+    DepositConfirmed = 2,
+    /// The key image of a TxOut belonging to a monitor appeared in a block.
+    WithdrawalSpent = 3,
+}
+
+/// An event to report to webhooks, as stored in the outbox.
+#[derive(Clone, Eq, PartialEq, Message)]
+pub struct WebhookEvent {
+    /// The kind of event.
+    #[prost(enumeration = "WebhookEventKind", tag = "1")]
+    pub kind: i32,
+
+    /// The monitor the TxOut belongs to.
+    #[prost(message, required, tag = "2")]
+    pub monitor_id: MonitorId,
+
+    /// The block the TxOut, or its key image, appeared in.
+    #[prost(uint64, tag = "3")]
+    pub block_index: u64,
+
+    /// The TxOut.
+    #[prost(message, required, tag = "4")]
+    pub tx_out: ProcessedTxOut,
+}
+
+/// The webhook database.
+#[derive(Clone)]
+pub struct WebhookStore {
+    /// Retain a reference to the Environment so the Database handles are valid.
+    _env: Arc<Environment>,
+
+    /// Mapping of MonitorId -> next block to generate events for.
+    monitor_id_to_next_block: Database,
+
+    /// Mapping of ProcessedBlockKey -> [ProcessedTxOut], for the deposits which
+    /// still need to be reported as confirmed.
+    pending_deposits: Database,
+
+    /// Mapping of an index -> WebhookEvent, for the events which still need to
+    /// be delivered.
+    outbox: Database,
+
+    /// Database for keeping track of counters.
+    /// Right now this is only used for OUTBOX_COUNTER_KEY.
+    counters: Database,
+}
+
+impl WebhookStore {
+    pub fn new(env: Arc<Environment>, _logger: Logger) -> Result<Self, Error> {
+        let monitor_id_to_next_block = env.create_db(
+            Some(MONITOR_ID_TO_NEXT_BLOCK_DB_NAME),
+            DatabaseFlags::empty(),
+        )?;
+        let pending_deposits =
+            env.create_db(Some(PENDING_DEPOSITS_DB_NAME), DatabaseFlags::DUP_SORT)?;
+        let outbox = env.create_db(Some(OUTBOX_DB_NAME), DatabaseFlags::empty())?;
+        let counters = env.create_db(Some(COUNTERS_DB_NAME), DatabaseFlags::empty())?;
+
+        Ok(Self {
+            _env: env,
+            monitor_id_to_next_block,
+            pending_deposits,
+            outbox,
+            counters,
+        })
+    }
+
+    /// Get the next block to generate events for, for a given monitor, or None
+    /// if no events were generated for it yet.
+    pub fn get_next_block(
+        &self,
+        db_txn: &impl Transaction,
+        monitor_id: &MonitorId,
+    ) -> Result<Option<u64>, Error> {
+        match db_txn.get(self.monitor_id_to_next_block, monitor_id) {
+            Ok(bytes) => Ok(Some(u64::from_be_bytes(
+                bytes.try_into().map_err(|_| Error::ValueDeserialization)?,
+            ))),
+            Err(lmdb::Error::NotFound) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Set the next block to generate events for, for a given monitor.
+    pub fn set_next_block(
+        &self,
+        db_txn: &mut RwTransaction<'_>,
+        monitor_id: &MonitorId,
+        next_block: u64,
+    ) -> Result<(), Error> {
+        db_txn.put(
+            self.monitor_id_to_next_block,
+            monitor_id,
+            &next_block.to_be_bytes(),
+            WriteFlags::empty(),
+        )?;
+        Ok(())
+    }
+
+    /// Queue the events generated for a monitor's processed block for delivery,
+    /// remember its deposits until they are confirmed, and move the monitor on
+    /// to the next block.
+    pub fn block_events_generated(
+        &self,
+        db_txn: &mut RwTransaction<'_>,
+        monitor_id: &MonitorId,
+        block_index: u64,
+        events: &[WebhookEvent],
+    ) -> Result<(), Error> {
+        // If the block being handed to us is not the one we expect, error out.
+        let next_block = self.get_next_block(db_txn, monitor_id)?;
+        if next_block != Some(block_index) {
+            return Err(Error::InvalidArgument(
+                "block_index".to_string(),
+                format!("Expected block {next_block:?}, got block {block_index}"),
+            ));
+        }
+
+        let key_bytes = ProcessedBlockKey::new(monitor_id, block_index).to_vec();
+        for event in events {
+            self.append_event(db_txn, event)?;
+
+            if event.kind == WebhookEventKind::DepositDetected as i32 {
+                db_txn.put(
+                    self.pending_deposits,
+                    &key_bytes,
+                    &mc_util_serial::encode(&event.tx_out),
+                    WriteFlags::NO_DUP_DATA,
+                )?;
+            }
+        }
+
+        self.set_next_block(db_txn, monitor_id, block_index + 1)
+    }
+
+    /// Queue the confirmation events of the deposits which are confirmed, given
+    /// the number of blocks in the ledger, and forget about those deposits.
+    /// Returns the number of events queued.
+    pub fn deposits_confirmed(
+        &self,
+        db_txn: &mut RwTransaction<'_>,
+        num_blocks: u64,
+        confirmations: u64,
+    ) -> Result<usize, Error> {
+        let mut confirmed = Vec::new();
+        {
+            let mut cursor = db_txn.open_rw_cursor(self.pending_deposits)?;
+            for result in cursor.iter_start() {
+                let (db_key, db_value) = result?;
+                let key = ProcessedBlockKey::try_from(db_key)?;
+                if num_blocks.saturating_sub(key.block_index) < confirmations {
+                    continue;
+                }
+                confirmed.push(WebhookEvent {
+                    kind: WebhookEventKind::DepositConfirmed as i32,
+                    monitor_id: key.monitor_id,
+                    block_index: key.block_index,
+                    tx_out: mc_util_serial::decode(db_value)?,
+                });
+                cursor.del(WriteFlags::empty())?;
+            }
+        }
+
+        for event in confirmed.iter() {
+            self.append_event(db_txn, event)?;
+        }
+        Ok(confirmed.len())
+    }
+
+    /// Get the next event to deliver (or None if the outbox is empty), along
+    /// with its index, so that it can be removed once delivered.
+    pub fn peek_event(
+        &self,
+        db_txn: &impl Transaction,
+    ) -> Result<Option<(u64, WebhookEvent)>, Error> {
+        let mut cursor = db_txn.open_ro_cursor(self.outbox)?;
+        let Some(result) = cursor.iter_start().next() else {
+            return Ok(None);
+        };
+        let (index_bytes, event_bytes) = result?;
+        let index = u64::from_be_bytes(
+            index_bytes
+                .try_into()
+                .map_err(|_| Error::ValueDeserialization)?,
+        );
+        Ok(Some((index, mc_util_serial::decode(event_bytes)?)))
+    }
+
+    /// Remove an event from the outbox.
+    pub fn remove_event(&self, db_txn: &mut RwTransaction<'_>, index: u64) -> Result<(), Error> {
+        db_txn.del(self.outbox, &index.to_be_bytes(), None)?;
+        Ok(())
+    }
+
+    /// Remove the data associated with a given monitor id. Events already in
+    /// the outbox are still delivered.
+    pub fn remove(
+        &self,
+        db_txn: &mut RwTransaction<'_>,
+        monitor_id: &MonitorId,
+    ) -> Result<(), Error> {
+        match db_txn.del(self.monitor_id_to_next_block, monitor_id, None) {
+            Ok(()) | Err(lmdb::Error::NotFound) => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        let start_key_bytes = ProcessedBlockKey::new(monitor_id, 0).to_vec();
+        let mut cursor = db_txn.open_rw_cursor(self.pending_deposits)?;
+        for result in cursor.iter_from(&start_key_bytes) {
+            let (db_key, _db_value) = result?;
+            let key = ProcessedBlockKey::try_from(db_key)?;
+            if key.monitor_id == *monitor_id {
+                cursor.del(WriteFlags::NO_DUP_DATA)?;
+            } else {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn append_event(
+        &self,
+        db_txn: &mut RwTransaction<'_>,
+        event: &WebhookEvent,
+    ) -> Result<(), Error> {
+        let index = self.get_outbox_counter(db_txn)?;
+
+        db_txn.put(
+            self.outbox,
+            &index.to_be_bytes(),
+            &mc_util_serial::encode(event),
+            WriteFlags::NO_OVERWRITE,
+        )?;
+
+        db_txn.put(
+            self.counters,
+            &OUTBOX_COUNTER_KEY,
+            &(index + 1).to_be_bytes(),
+            WriteFlags::empty(),
+        )?;
+
+        Ok(())
+    }
+
+    fn get_outbox_counter(&self, db_txn: &impl Transaction) -> Result<u64, Error> {
+        match db_txn.get(self.counters, &OUTBOX_COUNTER_KEY) {
+            Ok(bytes) => Ok(u64::from_be_bytes(
+                bytes.try_into().map_err(|_| Error::ValueDeserialization)?,
+            )),
+            Err(lmdb::Error::NotFound) => Ok(0),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::processed_block_store::ProcessedTxOutDirection;
+    use mc_common::logger::{test_with_logger, Logger};
+    use mc_crypto_keys::CompressedRistrettoPublic;
+    use mc_transaction_core::ring_signature::KeyImage;
+    use tempfile::TempDir;
+
+    fn setup_test_webhook_store(logger: &Logger) -> (Arc<Environment>, WebhookStore, TempDir) {
+        let db_tmp = TempDir::new().expect("Could not make tempdir for webhook store db");
+        let db_path = db_tmp
+            .path()
+            .to_str()
+            .expect("Could not get path as string");
+
+        let env = Arc::new(
+            Environment::new()
+                .set_max_dbs(10)
+                .set_map_size(10000000)
+                .open(db_path.as_ref())
+                .unwrap(),
+        );
+
+        let webhook_store = WebhookStore::new(env.clone(), logger.clone()).unwrap();
+
+        (env, webhook_store, db_tmp)
+    }
+
+    fn event(
+        kind: WebhookEventKind,
+        monitor_id: MonitorId,
+        block_index: u64,
+        value: u64,
+    ) -> WebhookEvent {
+        WebhookEvent {
+            kind: kind as i32,
+            monitor_id,
+            block_index,
+            tx_out: ProcessedTxOut {
+                subaddress_index: 0,
+                public_key: CompressedRistrettoPublic::try_from(&[value as u8; 32]).unwrap(),
+                key_image: KeyImage::from(value),
+                value,
+                direction: ProcessedTxOutDirection::Received as i32,
+                token_id: 0,
+            },
+        }
+    }
+
+    fn drain_outbox(env: &Environment, webhook_store: &WebhookStore) -> Vec<WebhookEvent> {
+        let mut events = Vec::new();
+        let mut db_txn = env.begin_rw_txn().unwrap();
+        while let Some((index, event)) = webhook_store.peek_event(&db_txn).unwrap() {
+            webhook_store.remove_event(&mut db_txn, index).unwrap();
+            events.push(event);
+        }
+        db_txn.commit().unwrap();
+        events
+    }
+
+    #[test_with_logger]
+    fn test_webhook_store_block_events_and_confirmations(logger: Logger) {
+        let (env, webhook_store, _db_tmp) = setup_test_webhook_store(&logger);
+        let monitor_id = MonitorId::from([1u8; 32]);
+
+        let deposit = event(WebhookEventKind::DepositDetected, monitor_id, 10, 1);
+        let spent = event(WebhookEventKind::WithdrawalSpent, monitor_id, 10, 2);
+
+        let mut db_txn = env.begin_rw_txn().unwrap();
+        assert_eq!(
+            webhook_store.get_next_block(&db_txn, &monitor_id).unwrap(),
+            None
+        );
+        // Events can only be generated for the next block.
+        assert!(webhook_store
+            .block_events_generated(&mut db_txn, &monitor_id, 10, &[])
+            .is_err());
+
+        webhook_store
+            .set_next_block(&mut db_txn, &monitor_id, 10)
+            .unwrap();
+        webhook_store
+            .block_events_generated(
+                &mut db_txn,
+                &monitor_id,
+                10,
+                &[deposit.clone(), spent.clone()],
+            )
+            .unwrap();
+        assert_eq!(
+            webhook_store.get_next_block(&db_txn, &monitor_id).unwrap(),
+            Some(11)
+        );
+        assert!(webhook_store
+            .block_events_generated(&mut db_txn, &monitor_id, 10, &[])
+            .is_err());
+
+        // Block 10 is the only block on top of the deposit.
+        assert_eq!(
+            webhook_store
+                .deposits_confirmed(&mut db_txn, 11, 2)
+                .unwrap(),
+            0
+        );
+        db_txn.commit().unwrap();
+
+        assert_eq!(
+            drain_outbox(&env, &webhook_store),
+            vec![deposit.clone(), spent]
+        );
+
+        let mut db_txn = env.begin_rw_txn().unwrap();
+        assert_eq!(
+            webhook_store
+                .deposits_confirmed(&mut db_txn, 12, 2)
+                .unwrap(),
+            1
+        );
+        // The deposit is only confirmed once.
+        assert_eq!(
+            webhook_store
+                .deposits_confirmed(&mut db_txn, 13, 2)
+                .unwrap(),
+            0
+        );
+        db_txn.commit().unwrap();
+
+        assert_eq!(
+            drain_outbox(&env, &webhook_store),
+            vec![WebhookEvent {
+                kind: WebhookEventKind::DepositConfirmed as i32,
+                ..deposit
+            }]
+        );
+        assert!(drain_outbox(&env, &webhook_store).is_empty());
+    }
+
+    #[test_with_logger]
+    fn test_webhook_store_remove_monitor(logger: Logger) {
+        let (env, webhook_store, _db_tmp) = setup_test_webhook_store(&logger);
+        let monitor_id1 = MonitorId::from([1u8; 32]);
+        let monitor_id2 = MonitorId::from([2u8; 32]);
+
+        let deposit1 = event(WebhookEventKind::DepositDetected, monitor_id1, 0, 1);
+        let deposit2 = event(WebhookEventKind::DepositDetected, monitor_id2, 0, 2);
+
+        let mut db_txn = env.begin_rw_txn().unwrap();
+        for (monitor_id, deposit) in [(monitor_id1, &deposit1), (monitor_id2, &deposit2)] {
+            webhook_store
+                .set_next_block(&mut db_txn, &monitor_id, 0)
+                .unwrap();
+            webhook_store
+                .block_events_generated(&mut db_txn, &monitor_id, 0, &[deposit.clone()])
+                .unwrap();
+        }
+
+        webhook_store.remove(&mut db_txn, &monitor_id1).unwrap();
+        assert_eq!(
+            webhook_store.get_next_block(&db_txn, &monitor_id1).unwrap(),
+            None
+        );
+        assert_eq!(
+            webhook_store.get_next_block(&db_txn, &monitor_id2).unwrap(),
+            Some(1)
+        );
+
+        // Only the deposit of the remaining monitor is confirmed.
+        assert_eq!(
+            webhook_store.deposits_confirmed(&mut db_txn, 1, 1).unwrap(),
+            1
+        );
+        db_txn.commit().unwrap();
+
+        // Events queued before the monitor was removed are still delivered.
+        assert_eq!(
+            drain_outbox(&env, &webhook_store),
+            vec![
+                deposit1,
+                deposit2.clone(),
+                WebhookEvent {
+                    kind: WebhookEventKind::DepositConfirmed as i32,
+                    ..deposit2
+                }
+            ]
+        );
+    }
+}
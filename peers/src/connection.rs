@@ -84,9 +84,9 @@ impl<Enclave: ConsensusEnclave + Clone + Send + Sync> PeerConnection<Enclave> {
         env: Arc<Environment>,
         logger: Logger,
     ) -> Self {
-        let remote_responder_id = uri.responder_id().unwrap_or_else(|_| {
-            panic!("Could not get responder id from uri {:?}", uri.to_string())
-        });
+        let remote_responder_id = uri
+            .responder_id()
+            .unwrap_or_else(|_| panic!("Could not get responder id from uri {}", uri.redacted()));
         let host_port = uri.addr();
 
         let logger = logger.new(o!("mc.peers.addr" => host_port));
@@ -181,9 +181,9 @@ impl<L: Ledger + Sync> BlockchainConnection for MockPeerConnection<L> {
 
 impl<L: Ledger + Sync> ConsensusConnection for MockPeerConnection<L> {
     fn remote_responder_id(&self) -> ResponderId {
-        self.uri.responder_id().unwrap_or_else(|_| {
-            panic!("Could not get responder ID from {:?}", self.uri.to_string())
-        })
+        self.uri
+            .responder_id()
+            .unwrap_or_else(|_| panic!("Could not get responder ID from {}", self.uri.redacted()))
     }
 
     fn local_node_id(&self) -> NodeID {
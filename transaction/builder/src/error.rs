@@ -73,6 +73,27 @@ pub enum TxBuilderError {
 
     /// Already have partial fill change
     AlreadyHavePartialFillChange,
+
+    /// Token id {0} cannot be used to pay fees on this network
+    FeeNotSupportedForToken(TokenId),
+
+    /// Tombstone block {0} has already passed, the next block is {1}
+    TombstoneBlockExceeded(u64, u64),
+
+    /// Tombstone block {0} is too far in the future, the limit is {1}
+    TombstoneBlockTooFar(u64, u64),
+
+    /// Too many inputs: {0} > {1}
+    TooManyInputs(usize, u64),
+
+    /// No outputs
+    NoOutputs,
+
+    /// Too many outputs: {0} > {1}
+    TooManyOutputs(usize, u64),
+
+    /// Insufficient funds: found {0}, need {1}
+    InsufficientFunds(u64, u64),
 }
 
 impl From<mc_util_serial::encode::Error> for TxBuilderError {
@@ -15,6 +15,7 @@ mod memo_builder;
 mod reserved_subaddresses;
 mod signed_contingent_input_builder;
 mod transaction_builder;
+mod tx_policy;
 
 #[cfg(any(test, feature = "test-only"))]
 pub mod test_utils;
@@ -31,3 +32,4 @@ pub use signed_contingent_input_builder::SignedContingentInputBuilder;
 pub use transaction_builder::{
     DefaultTxOutputsOrdering, TransactionBuilder, TxOutContext, TxOutputsOrdering,
 };
+pub use tx_policy::TxPolicy;
@@ -6,11 +6,11 @@
 
 use crate::{
     input_materials::InputMaterials, InputCredentials, MemoBuilder, ReservedSubaddresses,
-    TxBuilderError,
+    TxBuilderError, TxPolicy,
 };
 use alloc::{boxed::Box, vec::Vec};
 use core::{
-    cmp::{min, Ordering},
+    cmp::{max, min, Ordering, Reverse},
     fmt::Debug,
 };
 use mc_account_keys::PublicAddress;
@@ -164,6 +164,36 @@ impl<FPR: FogPubkeyResolver> TransactionBuilder<FPR> {
         })
     }
 
+    /// Initializes a new TransactionBuilder following a network's
+    /// [TxPolicy].
+    ///
+    /// The block version, fee and fee map are taken from the policy, and the
+    /// tombstone block starts out at the furthest value the policy allows.
+    ///
+    /// # Arguments
+    /// * `policy` - The rules the network currently applies to transactions
+    /// * `fee_token_id` - The token id to pay the fee in
+    /// * `opt_fee` - The fee value to pay. If zero, the minimum fee for
+    ///   `fee_token_id` is used.
+    /// * `fog_resolver` - Source of validated fog keys to use with this
+    ///   transaction
+    /// * `memo_builder` - An object which creates memos for the TxOuts in this
+    ///   transaction
+    pub fn new_with_policy(
+        policy: &TxPolicy,
+        fee_token_id: TokenId,
+        opt_fee: u64,
+        fog_resolver: FPR,
+        memo_builder: Box<dyn MemoBuilder + Send + Sync>,
+    ) -> Result<Self, TxBuilderError> {
+        let fee = policy.fee(fee_token_id, opt_fee)?;
+        let mut builder =
+            Self::new_with_box(policy.block_version(), fee, fog_resolver, memo_builder)?;
+        builder.set_fee_map(policy.fee_map().clone());
+        builder.impose_tombstone_block_limit(policy.max_tombstone_block());
+        Ok(builder)
+    }
+
     /// Add an Input to the transaction.
     ///
     /// # Arguments
@@ -174,6 +204,77 @@ impl<FPR: FogPubkeyResolver> TransactionBuilder<FPR> {
             .push(InputMaterials::Signable(input_credentials));
     }
 
+    /// Add inputs chosen among candidates, so that the inputs in the fee
+    /// token cover a value plus the fee, and set the fee to the estimate.
+    ///
+    /// The fee is estimated with [TxPolicy::estimate_fee] for the number of
+    /// inputs the transaction will have, and is never lowered below the fee
+    /// already set. Since the fee is set here, before any output is added,
+    /// memo builders record the same fee the transaction pays, and the
+    /// transaction doesn't have to be rebuilt once the fee is known.
+    ///
+    /// Candidates in other token ids are ignored, and the largest candidates
+    /// are used first, to keep the number of inputs small. Inputs already
+    /// added to the builder count towards the value.
+    ///
+    /// Returns the amount left over, which the caller should send to a change
+    /// output.
+    ///
+    /// # Arguments
+    /// * `policy` - The rules the network currently applies to transactions
+    /// * `value` - The value the outputs other than change will send, in the
+    ///   fee token id
+    /// * `num_outputs` - The number of outputs the transaction will create,
+    ///   including the change output
+    /// * `candidates` - The inputs which may be spent
+    pub fn add_inputs_covering_fee(
+        &mut self,
+        policy: &TxPolicy,
+        value: u64,
+        num_outputs: usize,
+        candidates: impl IntoIterator<Item = InputCredentials>,
+    ) -> Result<Amount, TxBuilderError> {
+        let token_id = self.fee.token_id;
+        let mut candidates = candidates
+            .into_iter()
+            .filter(|cred| cred.input_secret.amount.token_id == token_id)
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|cred| Reverse(cred.input_secret.amount.value));
+
+        let mut total = self
+            .input_materials
+            .iter()
+            .map(InputMaterials::amount)
+            .filter(|amount| amount.token_id == token_id)
+            .fold(0u64, |total, amount| total.saturating_add(amount.value));
+        let mut num_selected = 0;
+        loop {
+            let num_inputs = self.input_materials.len() + num_selected;
+            let shortfall = if num_inputs == 0 {
+                TxBuilderError::NoInputs
+            } else {
+                let estimate =
+                    policy.estimate_fee(num_inputs, num_outputs, token_id, self.block_version)?;
+                let fee = max(estimate.value, self.fee.value);
+                let target = value.saturating_add(fee);
+                if total >= target {
+                    self.set_fee(fee)?;
+                    for cred in candidates.drain(..num_selected) {
+                        self.add_input(cred);
+                    }
+                    return Ok(Amount::new(total - target, token_id));
+                }
+                TxBuilderError::InsufficientFunds(total, target)
+            };
+
+            let Some(cred) = candidates.get(num_selected) else {
+                return Err(shortfall);
+            };
+            total = total.saturating_add(cred.input_secret.amount.value);
+            num_selected += 1;
+        }
+    }
+
     /// Add a pre-signed Input to the transaction, also fulfilling any
     /// requirements imposed by the signed rules, so that our transaction
     /// will be valid.
@@ -4544,4 +4645,113 @@ pub mod transaction_builder_tests {
             }
         }
     }
+
+    #[test]
+    // A builder created from a policy takes its fee and tombstone window from it.
+    fn test_new_with_policy() {
+        let fee_map = FeeMap::try_from_iter([(Mob::ID, Mob::MINIMUM_FEE)]).unwrap();
+        let policy =
+            TxPolicy::new(BlockVersion::MAX, fee_map.clone(), 100).with_max_tombstone_blocks(50);
+
+        let mut transaction_builder = TransactionBuilder::new_with_policy(
+            &policy,
+            Mob::ID,
+            0,
+            MockFogResolver::default(),
+            Box::new(EmptyMemoBuilder),
+        )
+        .unwrap();
+        assert_eq!(transaction_builder.block_version, BlockVersion::MAX);
+        assert_eq!(transaction_builder.get_fee(), Mob::MINIMUM_FEE);
+        assert_eq!(transaction_builder.fee_map, Some(fee_map));
+        assert_eq!(transaction_builder.tombstone_block, 151);
+
+        // The tombstone block cannot be raised past the policy limit.
+        assert_eq!(transaction_builder.set_tombstone_block(1000), 151);
+        assert_eq!(transaction_builder.set_tombstone_block(120), 120);
+
+        // Tokens without a minimum fee are rejected.
+        assert_matches!(
+            TransactionBuilder::new_with_policy(
+                &policy,
+                TokenId::from(5),
+                0,
+                MockFogResolver::default(),
+                Box::new(EmptyMemoBuilder),
+            ),
+            Err(TxBuilderError::FeeNotSupportedForToken(_))
+        );
+    }
+
+    #[test]
+    // Inputs selected to cover the estimated fee balance the transaction.
+    fn test_add_inputs_covering_fee() {
+        let mut rng: StdRng = SeedableRng::from_seed([1u8; 32]);
+        let block_version = BlockVersion::MAX;
+        let fee = Mob::MINIMUM_FEE;
+        let fee_map = FeeMap::try_from_iter([(Mob::ID, fee)]).unwrap();
+        let policy = TxPolicy::new(block_version, fee_map, 100);
+
+        let fpr = MockFogResolver::default();
+        let sender = AccountKey::random(&mut rng);
+        let recipient = AccountKey::random(&mut rng);
+        let candidates = |rng: &mut StdRng| {
+            [
+                Amount::new(3 * fee, Mob::ID),
+                Amount::new(10 * fee, Mob::ID),
+                Amount::new(fee, Mob::ID),
+                Amount::new(100 * fee, TokenId::from(2)),
+            ]
+            .map(|amount| get_input_credentials(block_version, amount, &sender, &fpr, rng))
+        };
+
+        let mut transaction_builder = TransactionBuilder::new_with_policy(
+            &policy,
+            Mob::ID,
+            0,
+            fpr.clone(),
+            Box::new(EmptyMemoBuilder),
+        )
+        .unwrap();
+
+        // The two largest Mob inputs cover the value and the fee.
+        let change = transaction_builder
+            .add_inputs_covering_fee(&policy, 11 * fee, 2, candidates(&mut rng))
+            .unwrap();
+        assert_eq!(change, Amount::new(fee, Mob::ID));
+        assert_eq!(transaction_builder.input_materials.len(), 2);
+        assert_eq!(transaction_builder.get_fee(), fee);
+
+        transaction_builder
+            .add_output(
+                Amount::new(11 * fee, Mob::ID),
+                &recipient.default_subaddress(),
+                &mut rng,
+            )
+            .unwrap();
+        transaction_builder
+            .add_change_output(change, &ReservedSubaddresses::from(&sender), &mut rng)
+            .unwrap();
+        let tx = transaction_builder
+            .build(&NoKeysRingSigner {}, &mut rng)
+            .unwrap();
+        assert_eq!(tx.prefix.inputs.len(), 2);
+        assert!(validate_signature(block_version, &tx, &mut rng).is_ok());
+
+        // Nothing is added if the candidates fall short.
+        let mut transaction_builder = TransactionBuilder::new_with_policy(
+            &policy,
+            Mob::ID,
+            0,
+            fpr,
+            Box::new(EmptyMemoBuilder),
+        )
+        .unwrap();
+        assert_matches!(
+            transaction_builder.add_inputs_covering_fee(&policy, 14 * fee, 2, candidates(&mut rng)),
+            Err(TxBuilderError::InsufficientFunds(found, needed))
+                if found == 14 * fee && needed == 15 * fee
+        );
+        assert!(transaction_builder.input_materials.is_empty());
+    }
 }
@@ -0,0 +1,333 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Network-dependent rules that a new transaction must satisfy.
+
+use crate::TxBuilderError;
+use mc_transaction_core::{
+    constants::{MAX_INPUTS, MAX_OUTPUTS, MAX_TOMBSTONE_BLOCKS},
+    tokens::Mob,
+    Amount, BlockVersion, FeeMap, Token, TokenId,
+};
+
+/// The rules the consensus network currently applies to new transactions.
+///
+/// Clients should build this from what the network reports at runtime (for
+/// instance the last block info returned by a consensus node), rather than
+/// hard-coding fees, block versions or tombstone windows which drift when the
+/// network is reconfigured.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TxPolicy {
+    /// The block version new transactions should target.
+    block_version: BlockVersion,
+    /// The minimum fee for each token id.
+    fee_map: FeeMap,
+    /// The index of the last block in the ledger.
+    last_block_index: u64,
+    /// How many blocks past the next block a tombstone block may be.
+    max_tombstone_blocks: u64,
+}
+
+impl TxPolicy {
+    /// Create a new policy, using the default tombstone window.
+    ///
+    /// # Arguments
+    /// * `block_version` - The block version new transactions should target
+    /// * `fee_map` - The minimum fee map consensus is configured with
+    /// * `last_block_index` - The index of the last block in the ledger
+    pub fn new(block_version: BlockVersion, fee_map: FeeMap, last_block_index: u64) -> Self {
+        Self {
+            block_version,
+            fee_map,
+            last_block_index,
+            max_tombstone_blocks: MAX_TOMBSTONE_BLOCKS,
+        }
+    }
+
+    /// Replace the tombstone window, for networks which configure one smaller
+    /// than the protocol maximum.
+    #[must_use]
+    pub fn with_max_tombstone_blocks(mut self, max_tombstone_blocks: u64) -> Self {
+        self.max_tombstone_blocks = max_tombstone_blocks;
+        self
+    }
+
+    /// The block version new transactions should target.
+    pub fn block_version(&self) -> BlockVersion {
+        self.block_version
+    }
+
+    /// The minimum fee map consensus is configured with.
+    pub fn fee_map(&self) -> &FeeMap {
+        &self.fee_map
+    }
+
+    /// The index of the last block in the ledger.
+    pub fn last_block_index(&self) -> u64 {
+        self.last_block_index
+    }
+
+    /// How many blocks past the next block a tombstone block may be.
+    pub fn max_tombstone_blocks(&self) -> u64 {
+        self.max_tombstone_blocks
+    }
+
+    /// The minimum fee for a token id, or None if the token cannot be used to
+    /// pay fees.
+    pub fn minimum_fee(&self, token_id: TokenId) -> Option<u64> {
+        self.fee_map.get_fee_for_token(&token_id)
+    }
+
+    /// The fee to pay in a token id.
+    ///
+    /// # Arguments
+    /// * `token_id` - The token id the fee is paid in
+    /// * `opt_fee` - A fee chosen by the caller. If zero, the minimum fee is
+    ///   used.
+    pub fn fee(&self, token_id: TokenId, opt_fee: u64) -> Result<Amount, TxBuilderError> {
+        let value = if opt_fee != 0 {
+            opt_fee
+        } else {
+            self.minimum_fee(token_id)
+                .ok_or(TxBuilderError::FeeNotSupportedForToken(token_id))?
+        };
+        Ok(Amount::new(value, token_id))
+    }
+
+    /// Estimate the fee consensus will require for a transaction of the given
+    /// shape, checking that the shape is one consensus accepts at all.
+    ///
+    /// Consensus currently charges a flat minimum fee per token, so the
+    /// estimate is the minimum fee from the fee map whatever the number of
+    /// inputs and outputs, their ring sizes or their memos. What the shape
+    /// does affect is whether the transaction is valid, so the limits on
+    /// inputs and outputs are checked here, before any ring is fetched or
+    /// signed, rather than when the transaction is rejected.
+    ///
+    /// # Arguments
+    /// * `num_inputs` - The number of inputs the transaction will spend
+    /// * `num_outputs` - The number of outputs the transaction will create,
+    ///   including any change output
+    /// * `token_id` - The token id the fee is paid in
+    /// * `block_version` - The block version the transaction will target,
+    ///   normally [TxPolicy::block_version]
+    pub fn estimate_fee(
+        &self,
+        num_inputs: usize,
+        num_outputs: usize,
+        token_id: TokenId,
+        block_version: BlockVersion,
+    ) -> Result<Amount, TxBuilderError> {
+        if block_version > BlockVersion::MAX {
+            return Err(TxBuilderError::BlockVersionTooNew(
+                *block_version,
+                *BlockVersion::MAX,
+            ));
+        }
+        if !block_version.masked_token_id_feature_is_supported() && token_id != Mob::ID {
+            return Err(TxBuilderError::FeatureNotSupportedAtBlockVersion(
+                *block_version,
+                "nonzero token id",
+            ));
+        }
+
+        if num_inputs == 0 {
+            return Err(TxBuilderError::NoInputs);
+        }
+        if num_inputs as u64 > MAX_INPUTS {
+            return Err(TxBuilderError::TooManyInputs(num_inputs, MAX_INPUTS));
+        }
+        if num_outputs == 0 {
+            return Err(TxBuilderError::NoOutputs);
+        }
+        if num_outputs as u64 > MAX_OUTPUTS {
+            return Err(TxBuilderError::TooManyOutputs(num_outputs, MAX_OUTPUTS));
+        }
+
+        self.fee(token_id, 0)
+    }
+
+    /// The furthest tombstone block a transaction may have.
+    pub fn max_tombstone_block(&self) -> u64 {
+        self.next_block_index()
+            .saturating_add(self.max_tombstone_blocks)
+    }
+
+    /// The tombstone block to use, given one chosen by the caller.
+    ///
+    /// # Arguments
+    /// * `opt_tombstone` - A tombstone block chosen by the caller. If zero, the
+    ///   furthest allowed tombstone block is used.
+    pub fn tombstone_block(&self, opt_tombstone: u64) -> Result<u64, TxBuilderError> {
+        if opt_tombstone == 0 {
+            return Ok(self.max_tombstone_block());
+        }
+        self.check_tombstone_block(opt_tombstone)?;
+        Ok(opt_tombstone)
+    }
+
+    /// Check that a tombstone block would be accepted by consensus.
+    pub fn check_tombstone_block(&self, tombstone_block: u64) -> Result<(), TxBuilderError> {
+        let next_block_index = self.next_block_index();
+        if tombstone_block <= next_block_index {
+            return Err(TxBuilderError::TombstoneBlockExceeded(
+                tombstone_block,
+                next_block_index,
+            ));
+        }
+        let limit = self.max_tombstone_block();
+        if tombstone_block > limit {
+            return Err(TxBuilderError::TombstoneBlockTooFar(tombstone_block, limit));
+        }
+        Ok(())
+    }
+
+    /// Check that a feature is available at the policy's block version.
+    ///
+    /// # Arguments
+    /// * `is_supported` - A feature gate such as
+    ///   [BlockVersion::mixed_transactions_are_supported]
+    /// * `feature` - The feature's name, for the error message
+    pub fn check_feature(
+        &self,
+        is_supported: impl Fn(&BlockVersion) -> bool,
+        feature: &'static str,
+    ) -> Result<(), TxBuilderError> {
+        if is_supported(&self.block_version) {
+            Ok(())
+        } else {
+            Err(TxBuilderError::FeatureNotSupportedAtBlockVersion(
+                *self.block_version,
+                feature,
+            ))
+        }
+    }
+
+    fn next_block_index(&self) -> u64 {
+        self.last_block_index.saturating_add(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    fn test_policy(block_version: BlockVersion) -> TxPolicy {
+        let fee_map =
+            FeeMap::try_from_iter([(Mob::ID, Mob::MINIMUM_FEE), (TokenId::from(2), 1024)]).unwrap();
+        TxPolicy::new(block_version, fee_map, 100)
+    }
+
+    #[test]
+    fn fee_defaults_to_network_minimum() {
+        let policy = test_policy(BlockVersion::MAX);
+
+        assert_eq!(
+            policy.fee(Mob::ID, 0).unwrap(),
+            Amount::new(Mob::MINIMUM_FEE, Mob::ID)
+        );
+        assert_eq!(policy.fee(Mob::ID, 7).unwrap(), Amount::new(7, Mob::ID));
+        assert_eq!(
+            policy.fee(TokenId::from(2), 0).unwrap(),
+            Amount::new(1024, TokenId::from(2))
+        );
+        assert_matches!(
+            policy.fee(TokenId::from(3), 0),
+            Err(TxBuilderError::FeeNotSupportedForToken(token_id)) if token_id == TokenId::from(3)
+        );
+    }
+
+    #[test]
+    fn estimate_fee_checks_transaction_shape() {
+        let policy = test_policy(BlockVersion::MAX);
+        let block_version = policy.block_version();
+
+        assert_eq!(
+            policy.estimate_fee(1, 2, Mob::ID, block_version).unwrap(),
+            Amount::new(Mob::MINIMUM_FEE, Mob::ID)
+        );
+        assert_eq!(
+            policy
+                .estimate_fee(
+                    MAX_INPUTS as usize,
+                    MAX_OUTPUTS as usize,
+                    Mob::ID,
+                    block_version
+                )
+                .unwrap(),
+            Amount::new(Mob::MINIMUM_FEE, Mob::ID)
+        );
+        assert_eq!(
+            policy
+                .estimate_fee(3, 1, TokenId::from(2), block_version)
+                .unwrap(),
+            Amount::new(1024, TokenId::from(2))
+        );
+
+        assert_matches!(
+            policy.estimate_fee(0, 1, Mob::ID, block_version),
+            Err(TxBuilderError::NoInputs)
+        );
+        assert_matches!(
+            policy.estimate_fee(MAX_INPUTS as usize + 1, 1, Mob::ID, block_version),
+            Err(TxBuilderError::TooManyInputs(_, MAX_INPUTS))
+        );
+        assert_matches!(
+            policy.estimate_fee(1, 0, Mob::ID, block_version),
+            Err(TxBuilderError::NoOutputs)
+        );
+        assert_matches!(
+            policy.estimate_fee(1, MAX_OUTPUTS as usize + 1, Mob::ID, block_version),
+            Err(TxBuilderError::TooManyOutputs(_, MAX_OUTPUTS))
+        );
+        assert_matches!(
+            policy.estimate_fee(1, 1, TokenId::from(3), block_version),
+            Err(TxBuilderError::FeeNotSupportedForToken(_))
+        );
+        assert_matches!(
+            policy.estimate_fee(1, 1, TokenId::from(2), BlockVersion::ONE),
+            Err(TxBuilderError::FeatureNotSupportedAtBlockVersion(
+                1,
+                "nonzero token id"
+            ))
+        );
+    }
+
+    #[test]
+    fn tombstone_block_must_be_within_window() {
+        let policy = test_policy(BlockVersion::MAX).with_max_tombstone_blocks(10);
+
+        assert_eq!(policy.max_tombstone_block(), 111);
+        assert_eq!(policy.tombstone_block(0).unwrap(), 111);
+        assert_eq!(policy.tombstone_block(102).unwrap(), 102);
+        assert_matches!(
+            policy.tombstone_block(101),
+            Err(TxBuilderError::TombstoneBlockExceeded(101, 101))
+        );
+        assert_matches!(
+            policy.tombstone_block(112),
+            Err(TxBuilderError::TombstoneBlockTooFar(112, 111))
+        );
+    }
+
+    #[test]
+    fn features_are_gated_on_block_version() {
+        let policy = test_policy(BlockVersion::TWO);
+        assert!(policy
+            .check_feature(
+                BlockVersion::masked_token_id_feature_is_supported,
+                "masked token id"
+            )
+            .is_ok());
+        assert_matches!(
+            policy.check_feature(
+                BlockVersion::mixed_transactions_are_supported,
+                "mixed transactions"
+            ),
+            Err(TxBuilderError::FeatureNotSupportedAtBlockVersion(
+                2,
+                "mixed transactions"
+            ))
+        );
+    }
+}
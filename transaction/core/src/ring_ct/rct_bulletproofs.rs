@@ -18,7 +18,7 @@ use curve25519_dalek::{
 use mc_common::HashSet;
 use mc_crypto_digestible::Digestible;
 use mc_crypto_ring_signature::{
-    Commitment, CompressedCommitment, KeyImage, ReducedTxOut, RingMLSAG, Scalar,
+    Commitment, CompressedCommitment, KeyImage, MlsagBatchItem, ReducedTxOut, RingMLSAG, Scalar,
 };
 use mc_crypto_ring_signature_signer::{RingSigner, SignableInputRing};
 use mc_util_serial::prost::Message;
@@ -846,21 +846,32 @@ impl SignatureRctBulletproofs {
                 &self.range_proofs,
             )?;
 
-        // Each MLSAG must be valid.
-        for (i, ring) in rings.iter().enumerate() {
-            // Normally, the ring signature is made over the entire extended messages
-            // digest. If there are input rules, then the signature is over a
-            // reduced digest. See MCIP #31 for rationale
-            let this_was_signed: &[u8] = if let Some(signed_digest) = ring.signed_digest.as_ref() {
-                &signed_digest[..]
-            } else {
-                &mlsag_signing_digest.0
-            };
+        // Each MLSAG must be valid. They are verified as a batch, which decompresses
+        // ring members that appear in several rings only once.
+        let batch = rings
+            .iter()
+            .zip(&self.ring_signatures)
+            .zip(&self.pseudo_output_commitments)
+            .map(|((ring, ring_signature), pseudo_output)| {
+                // Normally, the ring signature is made over the entire extended messages
+                // digest. If there are input rules, then the signature is over a
+                // reduced digest. See MCIP #31 for rationale
+                let this_was_signed: &[u8] =
+                    if let Some(signed_digest) = ring.signed_digest.as_ref() {
+                        &signed_digest[..]
+                    } else {
+                        &mlsag_signing_digest.0
+                    };
 
-            let ring_signature = &self.ring_signatures[i];
-            let pseudo_output = self.pseudo_output_commitments[i];
-            ring_signature.verify(this_was_signed, &ring.members, &pseudo_output)?;
-        }
+                MlsagBatchItem {
+                    signature: ring_signature,
+                    message: this_was_signed,
+                    ring: &ring.members,
+                    output_commitment: pseudo_output,
+                }
+            })
+            .collect::<Vec<_>>();
+        RingMLSAG::verify_batch(&batch).map_err(|err| err.error)?;
 
         // Signature is valid.
         Ok(())
@@ -13,6 +13,7 @@
 extern crate alloc;
 
 mod memo;
+mod multisig;
 mod signed_contingent_input;
 mod tx_out_confirmation_number;
 mod tx_out_gift_code;
@@ -26,6 +27,10 @@ pub use memo::{
     GiftCodeCancellationMemo, GiftCodeFundingMemo, GiftCodeSenderMemo, MemoDecodingError, MemoType,
     RegisteredMemoType, SenderMemoCredential, UnusedMemo,
 };
+pub use multisig::{
+    lagrange_coefficient, CosignChallenge, CosignRequest, CosignerCommitment, LocalCosigner,
+    MultisigAccount, MultisigCosigner, MultisigError, MultisigKeyShare, MultisigRingSigner,
+};
 pub use signed_contingent_input::{
     SignedContingentInput, SignedContingentInputAmounts, SignedContingentInputError,
 };
@@ -0,0 +1,767 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Threshold (M-of-N) multisignature accounts.
+//!
+//! The spend private key `b` of a multisig account is split between N parties
+//! with Shamir secret sharing, so that any M of them can spend together, while
+//! fewer learn nothing about it. A coordinator holds the view private key: it
+//! finds the account's outputs and builds transactions as usual, signing them
+//! with a [MultisigRingSigner], which runs the signing protocol with the
+//! cosigners.
+//!
+//! The onetime private key of an output is `x = k + b`, where the coordinator
+//! can compute `k` from the view private key. So each MLSAG only needs from
+//! the cosigners their shares of the key image `b * Hp(P)` and of the response
+//! for the real input. Signing an input takes two rounds with each cosigner:
+//!
+//! 1. [MultisigCosigner::commit]: the cosigner picks a fresh nonce, and returns
+//!    its key image share and commitments to the nonce.
+//! 2. [MultisigCosigner::respond]: given the input's challenge, together with
+//!    the rest of the signature and every cosigner's commitments, the cosigner
+//!    recomputes the challenge for the message it was asked to sign in round 1,
+//!    and only returns its response share if it matches.
+//!
+//! So a coordinator can't get a share of a signature over any other message.
+//! The coordinator checks every share against the cosigner's verification key,
+//! so that a cosigner which misbehaves is identified.
+//!
+//! A cosigner must use each nonce for only one response, and should only have
+//! one signing session open at a time. Answering several open sessions at once
+//! allows a malicious coordinator to forge signatures (the ROS attack on
+//! two-round Schnorr multisignatures). [LocalCosigner] enforces both.
+
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::cell::RefCell;
+use curve25519_dalek::ristretto::RistrettoPoint;
+use displaydoc::Display;
+use mc_account_keys::{AccountKey, PublicAddress, ViewAccountKey};
+use mc_crypto_keys::{KeyError, RistrettoPrivate, RistrettoPublic};
+use mc_crypto_ring_signature::{
+    generators, hash_to_point, onetime_keys::recover_onetime_private_key, CompressedCommitment,
+    CurveScalar, Error as RingSignatureError, KeyImage, KeyNonceCommitments, PartialRingMLSAG,
+    ReducedTxOut, RingMLSAG, Scalar, B_BLINDING,
+};
+use mc_crypto_ring_signature_signer::{
+    Error as SignerError, OneTimeKeyDeriveData, RingSigner, SignableInputRing,
+};
+use rand_core::CryptoRngCore;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+/// The public side of a multisig account, held by its coordinator: the view
+/// private key, the spend public key, and the verification key of each share
+/// of the spend private key.
+#[derive(Clone, Debug)]
+pub struct MultisigAccount {
+    view_private_key: RistrettoPrivate,
+    spend_public_key: RistrettoPublic,
+    threshold: u32,
+    /// `b_i * G` for the share with index `i`, at position `i - 1`
+    verification_keys: Vec<RistrettoPublic>,
+}
+
+impl MultisigAccount {
+    /// Split the spend private key of `account_key` into `num_shares` shares,
+    /// any `threshold` of which can sign together.
+    ///
+    /// This is for a trusted dealer, which must erase `account_key` once the
+    /// shares are distributed. Fog info is not carried over, since signing
+    /// fog subaddresses needs the whole spend private key.
+    pub fn split(
+        account_key: &AccountKey,
+        threshold: u32,
+        num_shares: u32,
+        rng: &mut dyn CryptoRngCore,
+    ) -> Result<(Self, Vec<MultisigKeyShare>), MultisigError> {
+        if threshold == 0 || threshold > num_shares {
+            return Err(MultisigError::InvalidThreshold(threshold, num_shares));
+        }
+
+        // The coefficients of a random polynomial whose value at zero is `b`.
+        let mut rng = rng;
+        let mut coefficients: Vec<Scalar> = (0..threshold)
+            .map(|degree| match degree {
+                0 => *account_key.spend_private_key().as_ref(),
+                _ => Scalar::random(&mut rng),
+            })
+            .collect();
+
+        let shares: Vec<MultisigKeyShare> = (1..=num_shares)
+            .map(|index| {
+                let x = Scalar::from(index);
+                let secret = coefficients
+                    .iter()
+                    .rev()
+                    .fold(Scalar::ZERO, |acc, coefficient| acc * x + coefficient);
+                MultisigKeyShare {
+                    index,
+                    secret: RistrettoPrivate::from(secret),
+                }
+            })
+            .collect();
+        coefficients.zeroize();
+
+        let account = Self {
+            view_private_key: *account_key.view_private_key(),
+            spend_public_key: RistrettoPublic::from(account_key.spend_private_key()),
+            threshold,
+            verification_keys: shares
+                .iter()
+                .map(|share| RistrettoPublic::from(&share.secret))
+                .collect(),
+        };
+        Ok((account, shares))
+    }
+
+    /// The number of shares needed to sign.
+    pub fn threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    /// The number of shares the spend private key was split into.
+    pub fn num_shares(&self) -> u32 {
+        self.verification_keys.len() as u32
+    }
+
+    /// The public key of the share with this index, if there is one.
+    pub fn verification_key(&self, index: u32) -> Option<&RistrettoPublic> {
+        let position = index.checked_sub(1)?;
+        self.verification_keys.get(position as usize)
+    }
+
+    /// The keys needed to find the account's outputs.
+    pub fn view_account_key(&self) -> ViewAccountKey {
+        ViewAccountKey::new(self.view_private_key, self.spend_public_key)
+    }
+
+    /// Get the account's i^th subaddress.
+    pub fn subaddress(&self, index: u64) -> PublicAddress {
+        self.view_account_key().subaddress(index)
+    }
+}
+
+/// One party's share of a multisig account's spend private key.
+#[derive(Clone, Debug, Zeroize)]
+#[zeroize(drop)]
+pub struct MultisigKeyShare {
+    /// The index of the share, from 1 to the number of shares
+    index: u32,
+    /// The value of the secret sharing polynomial at `index`
+    secret: RistrettoPrivate,
+}
+
+impl MultisigKeyShare {
+    /// A share of a spend private key, as distributed by the dealer.
+    pub fn new(index: u32, secret: RistrettoPrivate) -> Self {
+        Self { index, secret }
+    }
+
+    /// The index of the share.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// The share's secret.
+    pub fn secret(&self) -> &RistrettoPrivate {
+        &self.secret
+    }
+
+    /// Check that this is the share of `account` it claims to be.
+    pub fn verify(&self, account: &MultisigAccount) -> Result<(), MultisigError> {
+        match account.verification_key(self.index) {
+            Some(key) if *key == RistrettoPublic::from(&self.secret) => Ok(()),
+            _ => Err(MultisigError::InvalidShare(self.index)),
+        }
+    }
+}
+
+/// The Lagrange coefficient of the share `index`, for reconstructing the
+/// secret at zero from the shares `signers`.
+pub fn lagrange_coefficient(index: u32, signers: &[u32]) -> Result<Scalar, MultisigError> {
+    check_signers(signers)?;
+    if !signers.contains(&index) {
+        return Err(MultisigError::NotASigner(index));
+    }
+    let x_i = Scalar::from(index);
+    let (numerator, denominator) = signers
+        .iter()
+        .filter(|signer| **signer != index)
+        .map(|signer| Scalar::from(*signer))
+        .fold((Scalar::ONE, Scalar::ONE), |(num, den), x_m| {
+            (num * x_m, den * (x_m - x_i))
+        });
+    Ok(numerator * denominator.invert())
+}
+
+fn check_signers(signers: &[u32]) -> Result<(), MultisigError> {
+    for (position, signer) in signers.iter().enumerate() {
+        if *signer == 0 {
+            return Err(MultisigError::InvalidShare(0));
+        }
+        if signers[..position].contains(signer) {
+            return Err(MultisigError::DuplicateShare(*signer));
+        }
+    }
+    Ok(())
+}
+
+/// What a cosigner is asked to sign: an MLSAG over `message` for the real
+/// input of `ring`, together with the shares `signers`.
+///
+/// `message` is the digest the transaction is signed over, so cosigners which
+/// apply their own spending policy need the transaction from elsewhere to
+/// check it against.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CosignRequest {
+    /// The digest of the transaction being signed
+    pub message: Vec<u8>,
+    /// The ring being signed
+    pub ring: Vec<ReducedTxOut>,
+    /// The index of the real input among the ring members
+    pub real_input_index: usize,
+    /// The indices of the shares which sign together
+    pub signers: Vec<u32>,
+}
+
+impl CosignRequest {
+    /// The onetime public key `P` of the real input.
+    pub fn real_input_target_key(&self) -> Result<RistrettoPublic, MultisigError> {
+        let real_input = self
+            .ring
+            .get(self.real_input_index)
+            .ok_or(MultisigError::RealInputIndexOutOfBounds)?;
+        Ok(RistrettoPublic::try_from(&real_input.target_key)?)
+    }
+}
+
+/// A cosigner's first round message.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CosignerCommitment {
+    /// `b_i * Hp(P)`, the cosigner's share of the key image
+    pub key_image_share: RistrettoPublic,
+    /// `alpha_i * G`
+    pub nonce_commitment: RistrettoPublic,
+    /// `alpha_i * Hp(P)`
+    pub nonce_key_image_commitment: RistrettoPublic,
+}
+
+/// A cosigner's second round input: the challenge to respond to, and what the
+/// cosigner needs to recompute it.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CosignChallenge {
+    /// The challenge `c` for the real input
+    pub challenge: CurveScalar,
+    /// The signature, without the response for the real input's onetime
+    /// private key
+    pub partial_signature: PartialRingMLSAG,
+    /// The pseudo-output amount commitment the signature is for
+    pub output_commitment: CompressedCommitment,
+    /// Every cosigner's first round message, in the order of
+    /// [CosignRequest::signers]
+    pub commitments: Vec<CosignerCommitment>,
+}
+
+/// A holder of a key share, which takes part in signing. This may be local,
+/// or forward the calls to a remote device or service.
+pub trait MultisigCosigner {
+    /// The index of the cosigner's key share.
+    fn share_index(&self) -> u32;
+
+    /// Start signing `request`, discarding any session which is still open.
+    ///
+    /// `rng` is for choosing the nonce. A remote cosigner should ignore it and
+    /// use its own.
+    fn commit(
+        &mut self,
+        request: &CosignRequest,
+        rng: &mut dyn CryptoRngCore,
+    ) -> Result<CosignerCommitment, MultisigError>;
+
+    /// Finish signing the open session, returning `alpha_i - c * l_i * b_i`,
+    /// where `l_i` is the share's Lagrange coefficient.
+    ///
+    /// The cosigner must recompute `c` from the request it committed to, with
+    /// [PartialRingMLSAG::real_input_challenge], and refuse to respond if it
+    /// doesn't match, or if its own commitments are missing.
+    fn respond(&mut self, challenge: &CosignChallenge) -> Result<CurveScalar, MultisigError>;
+}
+
+/// A cosigner holding its key share in memory.
+pub struct LocalCosigner {
+    share: MultisigKeyShare,
+    session: Option<CosignerSession>,
+}
+
+#[derive(Zeroize)]
+#[zeroize(drop)]
+struct CosignerSession {
+    nonce: Scalar,
+    lagrange_coefficient: Scalar,
+    #[zeroize(skip)]
+    request: CosignRequest,
+    #[zeroize(skip)]
+    commitment: CosignerCommitment,
+}
+
+impl LocalCosigner {
+    /// A cosigner for `share`.
+    pub fn new(share: MultisigKeyShare) -> Self {
+        Self {
+            share,
+            session: None,
+        }
+    }
+}
+
+impl MultisigCosigner for LocalCosigner {
+    fn share_index(&self) -> u32 {
+        self.share.index
+    }
+
+    fn commit(
+        &mut self,
+        request: &CosignRequest,
+        rng: &mut dyn CryptoRngCore,
+    ) -> Result<CosignerCommitment, MultisigError> {
+        self.session = None;
+        let target_key = request.real_input_target_key()?;
+        let lagrange_coefficient = lagrange_coefficient(self.share.index, &request.signers)?;
+
+        let mut rng = rng;
+        let nonce = Scalar::random(&mut rng);
+        let hp = hash_to_point(&target_key);
+        let commitment = CosignerCommitment {
+            key_image_share: RistrettoPublic::from(self.share.secret.as_ref() * hp),
+            nonce_commitment: RistrettoPublic::from(nonce * B_BLINDING),
+            nonce_key_image_commitment: RistrettoPublic::from(nonce * hp),
+        };
+        self.session = Some(CosignerSession {
+            nonce,
+            lagrange_coefficient,
+            request: request.clone(),
+            commitment: commitment.clone(),
+        });
+        Ok(commitment)
+    }
+
+    fn respond(&mut self, challenge: &CosignChallenge) -> Result<CurveScalar, MultisigError> {
+        // Taking the session ensures the nonce is used only once.
+        let session = self.session.take().ok_or(MultisigError::NoSession)?;
+        let request = &session.request;
+
+        // The nonce commitments the challenge was computed with must include ours.
+        let position = request
+            .signers
+            .iter()
+            .position(|index| *index == self.share.index)
+            .ok_or(MultisigError::NotASigner(self.share.index))?;
+        if challenge.commitments.len() != request.signers.len()
+            || challenge.commitments[position] != session.commitment
+        {
+            return Err(MultisigError::CommitmentMismatch);
+        }
+        let key_nonce_commitments = KeyNonceCommitments {
+            L0: challenge
+                .commitments
+                .iter()
+                .map(|commitment| commitment.nonce_commitment.as_ref())
+                .sum(),
+            R0: challenge
+                .commitments
+                .iter()
+                .map(|commitment| commitment.nonce_key_image_commitment.as_ref())
+                .sum(),
+        };
+
+        let c = challenge.partial_signature.real_input_challenge(
+            &request.message,
+            &request.ring,
+            request.real_input_index,
+            &key_nonce_commitments,
+            &challenge.output_commitment,
+        )?;
+        if c != challenge.challenge.scalar {
+            return Err(MultisigError::ChallengeMismatch);
+        }
+        Ok(CurveScalar::from(
+            session.nonce - c * session.lagrange_coefficient * self.share.secret.as_ref(),
+        ))
+    }
+}
+
+/// A [RingSigner] for a multisig account, which signs with its cosigners.
+///
+/// Inputs whose onetime private key is given, e.g. from a gift code, are
+/// signed without the cosigners.
+pub struct MultisigRingSigner {
+    account: MultisigAccount,
+    cosigners: RefCell<Vec<Box<dyn MultisigCosigner>>>,
+}
+
+impl MultisigRingSigner {
+    /// Sign for `account` with `cosigners`, which must be at least as many as
+    /// its threshold, and hold distinct shares of it.
+    pub fn new(
+        account: MultisigAccount,
+        cosigners: Vec<Box<dyn MultisigCosigner>>,
+    ) -> Result<Self, MultisigError> {
+        let signers: Vec<u32> = cosigners.iter().map(|c| c.share_index()).collect();
+        check_signers(&signers)?;
+        if let Some(index) = signers
+            .iter()
+            .find(|index| account.verification_key(**index).is_none())
+        {
+            return Err(MultisigError::InvalidShare(*index));
+        }
+        if signers.len() < account.threshold as usize {
+            return Err(MultisigError::NotEnoughCosigners(
+                signers.len(),
+                account.threshold,
+            ));
+        }
+        Ok(Self {
+            account,
+            cosigners: RefCell::new(cosigners),
+        })
+    }
+
+    fn sign_with_cosigners(
+        &self,
+        message: &[u8],
+        signable_ring: &SignableInputRing,
+        subaddress_index: u64,
+        output_blinding: Scalar,
+        rng: &mut dyn CryptoRngCore,
+    ) -> Result<RingMLSAG, MultisigError> {
+        let real_input = signable_ring
+            .members
+            .get(signable_ring.real_input_index)
+            .ok_or(MultisigError::RealInputIndexOutOfBounds)?;
+        let target_key = RistrettoPublic::try_from(&real_input.target_key)?;
+
+        // The part `k` of the onetime private key which doesn't depend on the spend
+        // private key: the key derivation is linear in it, so derive with zero.
+        let view_only_key = AccountKey::new(
+            &RistrettoPrivate::from(Scalar::ZERO),
+            &self.account.view_private_key,
+        );
+        let k = *recover_onetime_private_key(
+            &RistrettoPublic::try_from(&real_input.public_key)?,
+            &self.account.view_private_key,
+            &view_only_key.subaddress_spend_private(subaddress_index),
+        )
+        .as_ref();
+        if k * B_BLINDING + self.account.spend_public_key.as_ref() != *target_key.as_ref() {
+            return Err(MultisigError::TrueInputNotOwned);
+        }
+
+        let mut cosigners = self.cosigners.borrow_mut();
+        let signers: Vec<u32> = cosigners.iter().map(|c| c.share_index()).collect();
+        let request = CosignRequest {
+            message: message.to_vec(),
+            ring: signable_ring.members.clone(),
+            real_input_index: signable_ring.real_input_index,
+            signers: signers.clone(),
+        };
+
+        // Round 1: collect key image shares and nonce commitments.
+        let hp = hash_to_point(&target_key);
+        let mut key_image_point = k * hp;
+        let mut key_nonce_commitments = KeyNonceCommitments {
+            L0: RistrettoPoint::default(),
+            R0: RistrettoPoint::default(),
+        };
+        let mut commitments = Vec::with_capacity(cosigners.len());
+        for cosigner in cosigners.iter_mut() {
+            let index = cosigner.share_index();
+            let commitment = cosigner.commit(&request, rng)?;
+            let lagrange_coefficient = lagrange_coefficient(index, &signers)?;
+            key_image_point += lagrange_coefficient * commitment.key_image_share.as_ref();
+            key_nonce_commitments.L0 += commitment.nonce_commitment.as_ref();
+            key_nonce_commitments.R0 += commitment.nonce_key_image_commitment.as_ref();
+            commitments.push((index, lagrange_coefficient, commitment));
+        }
+        let key_image = KeyImage {
+            point: key_image_point.compress(),
+        };
+
+        // Round 2: collect and check response shares.
+        let output_commitment = CompressedCommitment::new(
+            signable_ring.input_secret.amount.value,
+            output_blinding,
+            &generators(*signable_ring.input_secret.amount.token_id),
+        );
+        let mut cosigner_error = None;
+        let result = RingMLSAG::sign_with_external_key(
+            message,
+            &signable_ring.members,
+            signable_ring.real_input_index,
+            &key_image,
+            &key_nonce_commitments,
+            signable_ring.input_secret.amount.value,
+            &signable_ring.input_secret.blinding,
+            &output_blinding,
+            &generators(*signable_ring.input_secret.amount.token_id),
+            rng,
+            |c, partial_signature| {
+                let challenge = CosignChallenge {
+                    challenge: CurveScalar::from(*c),
+                    partial_signature: partial_signature.clone(),
+                    output_commitment,
+                    commitments: commitments
+                        .iter()
+                        .map(|(_, _, commitment)| commitment.clone())
+                        .collect(),
+                };
+                let mut response = -(c * k);
+                for (cosigner, (index, lagrange_coefficient, commitment)) in
+                    cosigners.iter_mut().zip(&commitments)
+                {
+                    let share = cosigner
+                        .respond(&challenge)
+                        .and_then(|share| {
+                            check_response_share(
+                                &self.account,
+                                *index,
+                                lagrange_coefficient,
+                                commitment,
+                                &hp,
+                                c,
+                                &share.scalar,
+                            )
+                            .map(|()| share.scalar)
+                        })
+                        .map_err(|err| {
+                            cosigner_error = Some(err);
+                            RingSignatureError::InvalidState
+                        })?;
+                    response += share;
+                }
+                Ok(response)
+            },
+        );
+        match (result, cosigner_error) {
+            (_, Some(err)) => Err(err),
+            (result, None) => Ok(result?),
+        }
+    }
+}
+
+/// Check a cosigner's response share `s_i` against its commitments, i.e. that
+/// `s_i * G + c * l_i * B_i = alpha_i * G` and
+/// `s_i * Hp(P) + c * l_i * (b_i * Hp(P)) = alpha_i * Hp(P)`.
+fn check_response_share(
+    account: &MultisigAccount,
+    index: u32,
+    lagrange_coefficient: &Scalar,
+    commitment: &CosignerCommitment,
+    hp: &RistrettoPoint,
+    challenge: &Scalar,
+    share: &Scalar,
+) -> Result<(), MultisigError> {
+    let verification_key = account
+        .verification_key(index)
+        .ok_or(MultisigError::InvalidShare(index))?;
+    let weight = challenge * lagrange_coefficient;
+    let nonce_matches = share * B_BLINDING + weight * verification_key.as_ref()
+        == *commitment.nonce_commitment.as_ref();
+    let key_image_matches = share * hp + weight * commitment.key_image_share.as_ref()
+        == *commitment.nonce_key_image_commitment.as_ref();
+    if nonce_matches && key_image_matches {
+        Ok(())
+    } else {
+        Err(MultisigError::InvalidResponseShare(index))
+    }
+}
+
+impl RingSigner for MultisigRingSigner {
+    fn sign(
+        &self,
+        message: &[u8],
+        signable_ring: &SignableInputRing,
+        output_blinding: Scalar,
+        rng: &mut dyn CryptoRngCore,
+    ) -> Result<RingMLSAG, SignerError> {
+        match signable_ring.input_secret.onetime_key_derive_data {
+            OneTimeKeyDeriveData::OneTimeKey(onetime_private_key) => Ok(RingMLSAG::sign(
+                message,
+                &signable_ring.members,
+                signable_ring.real_input_index,
+                &onetime_private_key,
+                signable_ring.input_secret.amount.value,
+                &signable_ring.input_secret.blinding,
+                &output_blinding,
+                &generators(*signable_ring.input_secret.amount.token_id),
+                rng,
+            )?),
+            OneTimeKeyDeriveData::SubaddressIndex(subaddress_index) => Ok(self
+                .sign_with_cosigners(
+                    message,
+                    signable_ring,
+                    subaddress_index,
+                    output_blinding,
+                    rng,
+                )?),
+        }
+    }
+}
+
+/// An error which can occur when setting up or signing for a multisig account
+#[derive(Clone, Debug, Display, Eq, PartialEq)]
+pub enum MultisigError {
+    /// Invalid threshold: {0} of {1} shares
+    InvalidThreshold(u32, u32),
+    /// Share {0} is not a share of this account
+    InvalidShare(u32),
+    /// Share {0} appears more than once
+    DuplicateShare(u32),
+    /// Share {0} is not among the signers
+    NotASigner(u32),
+    /// Only {0} cosigners, but {1} are needed
+    NotEnoughCosigners(usize, u32),
+    /// The cosigner has no open signing session
+    NoSession,
+    /// Share {0} returned a response which doesn't match its commitments
+    InvalidResponseShare(u32),
+    /// The nonce commitments to sign with don't include the cosigner's own
+    CommitmentMismatch,
+    /// The challenge doesn't match the request the cosigner committed to
+    ChallengeMismatch,
+    /// Real input index out of bounds
+    RealInputIndexOutOfBounds,
+    /// True input not owned by this account
+    TrueInputNotOwned,
+    /// Invalid Ristretto key: {0}
+    Keys(KeyError),
+    /// Ring Signature: {0}
+    RingSignature(RingSignatureError),
+    /// Connection to a cosigner failed: {0}
+    ConnectionFailed(String),
+}
+
+impl From<KeyError> for MultisigError {
+    fn from(src: KeyError) -> Self {
+        Self::Keys(src)
+    }
+}
+
+impl From<RingSignatureError> for MultisigError {
+    fn from(src: RingSignatureError) -> Self {
+        Self::RingSignature(src)
+    }
+}
+
+impl From<MultisigError> for SignerError {
+    fn from(src: MultisigError) -> Self {
+        match src {
+            MultisigError::RealInputIndexOutOfBounds => Self::RealInputIndexOutOfBounds,
+            MultisigError::TrueInputNotOwned => Self::TrueInputNotOwned,
+            MultisigError::Keys(err) => Self::Keys(err),
+            MultisigError::RingSignature(err) => Self::RingSignature(err),
+            MultisigError::ConnectionFailed(err) => Self::ConnectionFailed(err),
+            err => Self::Multisig(err.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use mc_util_test_helper::{run_with_several_seeds, RngType, SeedableRng};
+
+    #[test]
+    // Any `threshold` shares should reconstruct the spend private key, and each
+    // share should match its verification key.
+    fn shares_reconstruct_the_spend_private_key() {
+        run_with_several_seeds(|mut rng| {
+            let account_key = AccountKey::random(&mut rng);
+            let (account, shares) = MultisigAccount::split(&account_key, 2, 3, &mut rng).unwrap();
+            assert_eq!(account.threshold(), 2);
+            assert_eq!(account.num_shares(), 3);
+            assert_eq!(account.subaddress(0), account_key.default_subaddress());
+
+            for share in &shares {
+                share.verify(&account).unwrap();
+            }
+
+            for signers in [[1, 2], [1, 3], [3, 2]] {
+                let secret = signers
+                    .iter()
+                    .map(|index| {
+                        let share = &shares[*index as usize - 1];
+                        lagrange_coefficient(*index, &signers).unwrap() * share.secret().as_ref()
+                    })
+                    .sum::<Scalar>();
+                assert_eq!(secret, *account_key.spend_private_key().as_ref());
+            }
+
+            // One share isn't enough.
+            assert_ne!(
+                *shares[0].secret().as_ref(),
+                *account_key.spend_private_key().as_ref()
+            );
+            let wrong_share = MultisigKeyShare::new(1, *shares[1].secret());
+            assert_eq!(
+                wrong_share.verify(&account),
+                Err(MultisigError::InvalidShare(1))
+            );
+        });
+    }
+
+    #[test]
+    fn invalid_parameters_are_rejected() {
+        let mut rng = RngType::from_seed([7u8; 32]);
+        let account_key = AccountKey::random(&mut rng);
+        assert_eq!(
+            MultisigAccount::split(&account_key, 0, 3, &mut rng).unwrap_err(),
+            MultisigError::InvalidThreshold(0, 3)
+        );
+        assert_eq!(
+            MultisigAccount::split(&account_key, 4, 3, &mut rng).unwrap_err(),
+            MultisigError::InvalidThreshold(4, 3)
+        );
+        assert_eq!(
+            lagrange_coefficient(1, &[1, 2, 1]),
+            Err(MultisigError::DuplicateShare(1))
+        );
+        assert_eq!(
+            lagrange_coefficient(3, &[1, 2]),
+            Err(MultisigError::NotASigner(3))
+        );
+
+        let (account, shares) = MultisigAccount::split(&account_key, 2, 3, &mut rng).unwrap();
+        let cosigner = |index: usize| -> Box<dyn MultisigCosigner> {
+            Box::new(LocalCosigner::new(shares[index].clone()))
+        };
+        assert_eq!(
+            MultisigRingSigner::new(account.clone(), vec![cosigner(0)]).err(),
+            Some(MultisigError::NotEnoughCosigners(1, 2))
+        );
+        assert_eq!(
+            MultisigRingSigner::new(account.clone(), vec![cosigner(0), cosigner(0)]).err(),
+            Some(MultisigError::DuplicateShare(1))
+        );
+        assert!(MultisigRingSigner::new(account, vec![cosigner(0), cosigner(2)]).is_ok());
+
+        let mut local = LocalCosigner::new(shares[0].clone());
+        let challenge = CosignChallenge {
+            challenge: CurveScalar::from(Scalar::ONE),
+            partial_signature: PartialRingMLSAG {
+                c_zero: CurveScalar::from(Scalar::ONE),
+                responses: vec![],
+                key_image: KeyImage::default(),
+            },
+            output_commitment: CompressedCommitment::default(),
+            commitments: vec![],
+        };
+        assert_eq!(
+            local.respond(&challenge).unwrap_err(),
+            MultisigError::NoSession
+        );
+    }
+}
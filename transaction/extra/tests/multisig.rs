@@ -0,0 +1,249 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Tests of signing transactions for multisig accounts
+
+use mc_account_keys::{AccountKey, DEFAULT_SUBADDRESS_INDEX};
+use mc_crypto_ring_signature_signer::{
+    Error as SignerError, NoKeysRingSigner, OneTimeKeyDeriveData,
+};
+use mc_fog_report_validation_test_utils::MockFogResolver;
+use mc_transaction_builder::test_utils::get_unsigned_transaction;
+use mc_transaction_core::{
+    ring_ct::{Error as RingCtError, InputRing},
+    ring_signature::{CurveScalar, Error as RingSignatureError, Scalar},
+    tokens::Mob,
+    tx::Tx,
+    validation::validate_signature,
+    BlockVersion, Token,
+};
+use mc_transaction_extra::{
+    CosignChallenge, CosignRequest, CosignerCommitment, LocalCosigner, MultisigAccount,
+    MultisigCosigner, MultisigError, MultisigKeyShare, MultisigRingSigner, UnsignedTx,
+};
+use rand::{rngs::StdRng, SeedableRng};
+use rand_core::CryptoRngCore;
+
+// A 2-of-3 multisig account, and an unsigned transaction spending from it whose
+// inputs need the spend private key to sign.
+fn get_multisig_transaction(
+    rng: &mut StdRng,
+) -> (
+    MultisigAccount,
+    Vec<MultisigKeyShare>,
+    UnsignedTx,
+    UnsignedTx,
+) {
+    let sender = AccountKey::random(rng);
+    let recipient = AccountKey::random(rng);
+    let (account, shares) = MultisigAccount::split(&sender, 2, 3, rng).unwrap();
+
+    let unsigned_tx = get_unsigned_transaction(
+        BlockVersion::MAX,
+        Mob::ID,
+        2,
+        1,
+        &sender,
+        &recipient,
+        MockFogResolver::default(),
+        rng,
+    )
+    .unwrap();
+
+    let mut multisig_unsigned_tx = unsigned_tx.clone();
+    for ring in multisig_unsigned_tx.rings.iter_mut() {
+        match ring {
+            InputRing::Signable(ring) => {
+                ring.input_secret.onetime_key_derive_data =
+                    OneTimeKeyDeriveData::SubaddressIndex(DEFAULT_SUBADDRESS_INDEX)
+            }
+            InputRing::Presigned(_) => panic!("unexpected presigned input"),
+        }
+    }
+
+    (account, shares, unsigned_tx, multisig_unsigned_tx)
+}
+
+fn local_cosigners(shares: &[MultisigKeyShare]) -> Vec<Box<dyn MultisigCosigner>> {
+    shares
+        .iter()
+        .map(|share| Box::new(LocalCosigner::new(share.clone())) as Box<dyn MultisigCosigner>)
+        .collect()
+}
+
+#[test]
+// Any two of the three shares should sign a valid transaction, with the same
+// key images as signing with the whole spend private key.
+fn test_multisig_signing() {
+    let mut rng: StdRng = SeedableRng::from_seed([1u8; 32]);
+    let (account, shares, unsigned_tx, multisig_unsigned_tx) = get_multisig_transaction(&mut rng);
+
+    let expected_key_images = unsigned_tx
+        .sign(&NoKeysRingSigner {}, None, &mut rng)
+        .unwrap()
+        .key_images();
+
+    for signers in [[0, 1], [0, 2], [2, 1]] {
+        let cosigners = local_cosigners(&[shares[signers[0]].clone(), shares[signers[1]].clone()]);
+        let signer = MultisigRingSigner::new(account.clone(), cosigners).unwrap();
+        let tx = multisig_unsigned_tx.sign(&signer, None, &mut rng).unwrap();
+
+        validate_signature(BlockVersion::MAX, &tx, &mut rng).unwrap();
+        assert_eq!(tx.key_images(), expected_key_images);
+    }
+
+    // Signing with all of the shares works too.
+    let signer = MultisigRingSigner::new(account, local_cosigners(&shares)).unwrap();
+    let tx = multisig_unsigned_tx.sign(&signer, None, &mut rng).unwrap();
+    validate_signature(BlockVersion::MAX, &tx, &mut rng).unwrap();
+    assert_eq!(tx.key_images(), expected_key_images);
+}
+
+// A cosigner which adds one to its responses.
+struct BadCosigner(LocalCosigner);
+
+impl MultisigCosigner for BadCosigner {
+    fn share_index(&self) -> u32 {
+        self.0.share_index()
+    }
+
+    fn commit(
+        &mut self,
+        request: &CosignRequest,
+        rng: &mut dyn CryptoRngCore,
+    ) -> Result<CosignerCommitment, MultisigError> {
+        self.0.commit(request, rng)
+    }
+
+    fn respond(&mut self, challenge: &CosignChallenge) -> Result<CurveScalar, MultisigError> {
+        let response = self.0.respond(challenge)?;
+        Ok(CurveScalar::from(response.scalar + Scalar::ONE))
+    }
+}
+
+#[test]
+// A cosigner whose response doesn't match its commitments should be
+// identified.
+fn test_multisig_bad_cosigner_is_identified() {
+    let mut rng: StdRng = SeedableRng::from_seed([2u8; 32]);
+    let (account, shares, _unsigned_tx, multisig_unsigned_tx) = get_multisig_transaction(&mut rng);
+
+    let cosigners: Vec<Box<dyn MultisigCosigner>> = vec![
+        Box::new(LocalCosigner::new(shares[0].clone())),
+        Box::new(BadCosigner(LocalCosigner::new(shares[2].clone()))),
+    ];
+    let signer = MultisigRingSigner::new(account, cosigners).unwrap();
+
+    match multisig_unsigned_tx.sign(&signer, None, &mut rng) {
+        Err(RingCtError::Signer(SignerError::Multisig(message))) => {
+            assert_eq!(message, MultisigError::InvalidResponseShare(3).to_string())
+        }
+        other => panic!("unexpected result: {other:?}"),
+    }
+}
+
+// A cosigner whose coordinator tampers with what it asks the cosigner to
+// sign, either in the first round or the second.
+struct TamperedCosigner {
+    cosigner: LocalCosigner,
+    tamper_request: fn(&mut CosignRequest),
+    tamper_challenge: fn(&mut CosignChallenge),
+}
+
+impl MultisigCosigner for TamperedCosigner {
+    fn share_index(&self) -> u32 {
+        self.cosigner.share_index()
+    }
+
+    fn commit(
+        &mut self,
+        request: &CosignRequest,
+        rng: &mut dyn CryptoRngCore,
+    ) -> Result<CosignerCommitment, MultisigError> {
+        let mut request = request.clone();
+        (self.tamper_request)(&mut request);
+        self.cosigner.commit(&request, rng)
+    }
+
+    fn respond(&mut self, challenge: &CosignChallenge) -> Result<CurveScalar, MultisigError> {
+        let mut challenge = challenge.clone();
+        (self.tamper_challenge)(&mut challenge);
+        self.cosigner.respond(&challenge)
+    }
+}
+
+fn assert_multisig_error(result: Result<Tx, RingCtError>, expected: MultisigError) {
+    match result {
+        Err(RingCtError::Signer(SignerError::Multisig(message))) => {
+            assert_eq!(message, expected.to_string())
+        }
+        other => panic!("unexpected result: {other:?}"),
+    }
+}
+
+#[test]
+// A cosigner should refuse to respond to a challenge which isn't for the
+// message it committed to sign, so a coordinator can't get a share of a
+// signature for another transaction.
+fn test_multisig_tampered_challenge_is_rejected() {
+    let mut rng: StdRng = SeedableRng::from_seed([3u8; 32]);
+    let (account, shares, _unsigned_tx, multisig_unsigned_tx) = get_multisig_transaction(&mut rng);
+
+    let sign = |tamper_request: fn(&mut CosignRequest),
+                tamper_challenge: fn(&mut CosignChallenge),
+                rng: &mut StdRng| {
+        let cosigners: Vec<Box<dyn MultisigCosigner>> = vec![
+            Box::new(LocalCosigner::new(shares[0].clone())),
+            Box::new(TamperedCosigner {
+                cosigner: LocalCosigner::new(shares[1].clone()),
+                tamper_request,
+                tamper_challenge,
+            }),
+        ];
+        let signer = MultisigRingSigner::new(account.clone(), cosigners).unwrap();
+        multisig_unsigned_tx.sign(&signer, None, rng)
+    };
+
+    // Nothing tampered with.
+    let tx = sign(|_| {}, |_| {}, &mut rng).unwrap();
+    validate_signature(BlockVersion::MAX, &tx, &mut rng).unwrap();
+
+    // A challenge which doesn't match the rest of the signature.
+    assert_multisig_error(
+        sign(
+            |_| {},
+            |challenge| challenge.challenge.scalar += Scalar::ONE,
+            &mut rng,
+        ),
+        MultisigError::ChallengeMismatch,
+    );
+
+    // A challenge for another message than the one committed to.
+    assert_multisig_error(
+        sign(|request| request.message[0] ^= 1, |_| {}, &mut rng),
+        MultisigError::ChallengeMismatch,
+    );
+
+    // A partial signature which doesn't close the ring.
+    match sign(
+        |_| {},
+        |challenge| challenge.partial_signature.c_zero.scalar += Scalar::ONE,
+        &mut rng,
+    ) {
+        Err(RingCtError::Signer(SignerError::RingSignature(
+            RingSignatureError::InvalidSignature,
+        ))) => {}
+        other => panic!("unexpected result: {other:?}"),
+    }
+
+    // Nonce commitments which leave out the cosigner's own.
+    assert_multisig_error(
+        sign(
+            |_| {},
+            |challenge| {
+                challenge.commitments.pop();
+            },
+            &mut rng,
+        ),
+        MultisigError::CommitmentMismatch,
+    );
+}
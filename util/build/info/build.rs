@@ -110,6 +110,7 @@ fn main() {
     let target_feature = env_with_fallback("CARGO_CFG_TARGET_FEATURE", "?");
     let rustflags = env_with_fallback("RUSTFLAGS", "?");
     let sgx_mode = env_with_fallback("SGX_MODE", "?");
+    let curve25519_dalek_backend = env_with_fallback("CARGO_CFG_CURVE25519_DALEK_BACKEND", "?");
 
     // Format the contents
     let gen_contents = format!(
@@ -126,6 +127,7 @@ pub fn target_os() -> &'static str {{ "{target_os}" }}
 pub fn target_feature() -> &'static str {{ "{target_feature}" }}
 pub fn rustflags() -> &'static str {{ "{rustflags}" }}
 pub fn sgx_mode() -> &'static str {{ "{sgx_mode}" }}
+pub fn curve25519_dalek_backend() -> &'static str {{ "{curve25519_dalek_backend}" }}
 // Note: Please update `build-info/src/lib.rs` if you add more stuff
 "###,
     );
@@ -2,6 +2,9 @@
 
 #![no_std]
 
+#[cfg(feature = "std")]
+extern crate std;
+
 include!(concat!(env!("OUT_DIR"), "/build_info_generated.rs"));
 
 // Write a report as a json blob containing all the info
@@ -11,7 +14,7 @@ use core::fmt::{Result, Write};
 pub fn write_report(output: &mut dyn Write) -> Result {
     write!(
         output,
-        r##"{{ "GIT_COMMIT": "{}", "MOBILECOIN_GIT_COMMIT": "{}", "PROFILE": "{}", "DEBUG": "{}", "OPT_LEVEL": "{}", "DEBUG_ASSERTIONS": "{}", "TARGET_ARCH": "{}", "TARGET_OS": "{}", "TARGET_FEATURE": "{}", "RUSTFLAGS": "{}", "SGX_MODE": "{}" }}"##,
+        r##"{{ "GIT_COMMIT": "{}", "MOBILECOIN_GIT_COMMIT": "{}", "PROFILE": "{}", "DEBUG": "{}", "OPT_LEVEL": "{}", "DEBUG_ASSERTIONS": "{}", "TARGET_ARCH": "{}", "TARGET_OS": "{}", "TARGET_FEATURE": "{}", "RUSTFLAGS": "{}", "SGX_MODE": "{}", "DALEK_BACKEND": "{}" }}"##,
         git_commit(),
         mobilecoin_git_commit(),
         profile(),
@@ -23,5 +26,38 @@ pub fn write_report(output: &mut dyn Write) -> Result {
         target_feature(),
         rustflags(),
         sgx_mode(),
+        dalek_backend(),
     )
 }
+
+/// The backend curve25519-dalek uses for field arithmetic on this CPU.
+///
+/// The `simd` backend, which is the default on x86_64, uses AVX2 if the
+/// build targets it, e.g. with `-C target-cpu=skylake`. Otherwise it detects
+/// AVX2 at runtime and falls back to the serial backend on CPUs without it,
+/// which this can only tell apart with the `std` feature.
+pub fn dalek_backend() -> &'static str {
+    match curve25519_dalek_backend() {
+        "serial" | "fiat" => return curve25519_dalek_backend(),
+        _ if target_arch() != "x86_64" => return "serial",
+        _ => {}
+    }
+    if target_feature().split(',').any(|feature| feature == "avx2") {
+        return "simd-avx2";
+    }
+    detect_avx2()
+}
+
+#[cfg(all(feature = "std", target_arch = "x86_64"))]
+fn detect_avx2() -> &'static str {
+    if std::is_x86_feature_detected!("avx2") {
+        "simd-avx2"
+    } else {
+        "serial"
+    }
+}
+
+#[cfg(not(all(feature = "std", target_arch = "x86_64")))]
+fn detect_avx2() -> &'static str {
+    "simd"
+}
@@ -0,0 +1,385 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Layered loading of server configs.
+//!
+//! Each value of a config is taken from the first of these which provides it:
+//! 1. the command line,
+//! 2. the environment variable declared with `#[clap(env = ...)]`,
+//! 3. a TOML or JSON file passed with `--config-file`,
+//! 4. the default declared on the config struct.
+//!
+//! The file holds a flat table whose keys are the names of the command line
+//! options, with either dashes or underscores, e.g. `client-listen-uri` or
+//! `client_listen_uri`. Options of flattened structs go in the same table.
+//! Values may be strings, numbers, booleans, or arrays of those for options
+//! which take several values.
+//!
+//! Secret options, like shared secrets, must be marked with
+//! `#[serde(serialize_with = "mc_util_cli::redact_secret")]`, so that they
+//! don't appear in the output of `--print-effective-config`, or anywhere else
+//! the config is serialized.
+
+use crate::ParserWithBuildInfo;
+use clap::{error::ErrorKind, Arg, ArgAction, Command, FromArgMatches};
+use serde::{Serialize, Serializer};
+use serde_json::{Map, Value};
+use std::{
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+
+const CONFIG_FILE_ARG: &str = "config_file";
+const PRINT_EFFECTIVE_CONFIG_ARG: &str = "print_effective_config";
+
+/// What secret options are serialized as when they are set.
+pub const REDACTED: &str = "<redacted>";
+
+/// Serialize a secret option as [REDACTED], or as nothing when it is unset, so
+/// that operators can see whether it is set without the config leaking it.
+pub fn redact_secret<T, S>(secret: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match secret {
+        Some(_) => serializer.serialize_some(REDACTED),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// A config which can be loaded from the command line, the environment and a
+/// config file, in that order of precedence.
+///
+/// The file holds a flat table whose keys are the names of the command line
+/// options (including those of flattened structs), with either dashes or
+/// underscores.
+///
+/// This adds two options to the command line:
+/// * `--config-file <PATH>` (or `MC_CONFIG_FILE`), the file to read values
+///   from,
+/// * `--print-effective-config`, which prints the resulting config as JSON and
+///   exits, so that operators can check what a server would run with. Secret
+///   options are redacted, see [redact_secret].
+pub trait LayeredConfig: ParserWithBuildInfo + Serialize {
+    /// Load the config from the process arguments, environment and config
+    /// file, exiting with an error message if any of them is invalid.
+    fn load() -> Self {
+        match load_from::<Self, _, _>(std::env::args_os()) {
+            Ok((config, false)) => config,
+            Ok((config, true)) => {
+                let json = serde_json::to_string_pretty(&config)
+                    .expect("Could not serialize the effective config");
+                println!("{json}");
+                std::process::exit(0);
+            }
+            Err(err) => err.exit(),
+        }
+    }
+
+    /// Load the config from the given arguments, the environment and the
+    /// config file they name. `--print-effective-config` is ignored.
+    fn try_load_from<I, T>(args: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString>,
+    {
+        load_from::<Self, _, _>(args).map(|(config, _print_effective_config)| config)
+    }
+}
+
+impl<T> LayeredConfig for T where T: ParserWithBuildInfo + Serialize {}
+
+/// Load a config, and whether `--print-effective-config` was passed.
+fn load_from<C, I, T>(args: I) -> Result<(C, bool), clap::Error>
+where
+    C: ParserWithBuildInfo,
+    I: IntoIterator<Item = T>,
+    T: Into<OsString>,
+{
+    let args = args.into_iter().map(Into::into).collect::<Vec<OsString>>();
+    let mut command = with_layering_args(C::command_with_build_info());
+
+    // Find the config file first, since its values become the defaults of the
+    // command which parses everything else.
+    let config_file = command
+        .clone()
+        .ignore_errors(true)
+        .try_get_matches_from(&args)?
+        .get_one::<PathBuf>(CONFIG_FILE_ARG)
+        .cloned();
+    if let Some(path) = config_file {
+        let values = read_config_file(&mut command, &path)?;
+        command = apply_config_values(command, &path, values)?;
+    }
+
+    let mut matches = command.try_get_matches_from_mut(&args)?;
+    let print_effective_config = matches.get_flag(PRINT_EFFECTIVE_CONFIG_ARG);
+    let config = C::from_arg_matches_mut(&mut matches).map_err(|err| err.format(&mut command))?;
+    Ok((config, print_effective_config))
+}
+
+fn with_layering_args(command: Command) -> Command {
+    command
+        .arg(
+            Arg::new(CONFIG_FILE_ARG)
+                .long("config-file")
+                .env("MC_CONFIG_FILE")
+                .value_name("PATH")
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("A TOML or JSON file with values for any of the other options"),
+        )
+        .arg(
+            Arg::new(PRINT_EFFECTIVE_CONFIG_ARG)
+                .long("print-effective-config")
+                .action(ArgAction::SetTrue)
+                .help("Print the config resulting from all sources as JSON, and exit"),
+        )
+}
+
+/// Read the top-level table of a config file, choosing the format from the
+/// file extension.
+fn read_config_file(command: &mut Command, path: &Path) -> Result<Map<String, Value>, clap::Error> {
+    let contents = fs::read_to_string(path).map_err(|err| {
+        command.error(
+            ErrorKind::Io,
+            format!("Could not read config file {}: {err}", path.display()),
+        )
+    })?;
+    let parsed = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => toml::from_str::<Value>(&contents).map_err(|err| err.to_string()),
+        Some("json") => serde_json::from_str::<Value>(&contents).map_err(|err| err.to_string()),
+        _ => {
+            return Err(command.error(
+                ErrorKind::InvalidValue,
+                format!(
+                    "Config file {} must have a .toml or .json extension",
+                    path.display()
+                ),
+            ))
+        }
+    };
+    match parsed {
+        Ok(Value::Object(table)) => Ok(table),
+        Ok(_) => Err(command.error(
+            ErrorKind::Format,
+            format!(
+                "Config file {} must contain a table of option names to values",
+                path.display()
+            ),
+        )),
+        Err(err) => Err(command.error(
+            ErrorKind::Format,
+            format!("Could not parse config file {}: {err}", path.display()),
+        )),
+    }
+}
+
+/// Make the values from a config file the defaults of the matching options.
+///
+/// Options given a value this way are no longer required on the command line.
+/// Since clap prefers explicit and environment values over defaults, this gives
+/// the file lower precedence than both.
+fn apply_config_values(
+    mut command: Command,
+    path: &Path,
+    values: Map<String, Value>,
+) -> Result<Command, clap::Error> {
+    for (key, value) in values {
+        let Some(id) = find_arg_id(&command, &key) else {
+            return Err(command.error(
+                ErrorKind::UnknownArgument,
+                format!(
+                    "Unknown key `{key}` in config file {}; keys must be the names of \
+                     command line options, see --help",
+                    path.display()
+                ),
+            ));
+        };
+        let Some(defaults) = to_arg_values(&value) else {
+            return Err(command.error(
+                ErrorKind::InvalidValue,
+                format!(
+                    "Invalid value for `{key}` in config file {}: expected a string, number, \
+                     boolean, or an array of those",
+                    path.display()
+                ),
+            ));
+        };
+        command = command.mut_arg(id, |arg| arg.required(false).default_values(defaults));
+    }
+    Ok(command)
+}
+
+/// Find the option a config file key refers to, by id or long name.
+fn find_arg_id(command: &Command, key: &str) -> Option<String> {
+    let id = key.replace('-', "_");
+    let long = key.replace('_', "-");
+    command
+        .get_arguments()
+        .filter(|arg| !arg.is_positional())
+        .filter(|arg| {
+            let arg_id = arg.get_id().as_str();
+            arg_id != CONFIG_FILE_ARG && arg_id != PRINT_EFFECTIVE_CONFIG_ARG
+        })
+        .find(|arg| arg.get_id().as_str() == id || arg.get_long() == Some(long.as_str()))
+        .map(|arg| arg.get_id().to_string())
+}
+
+/// Convert a config file value to the strings clap would have been given on
+/// the command line.
+fn to_arg_values(value: &Value) -> Option<Vec<String>> {
+    fn scalar(value: &Value) -> Option<String> {
+        match value {
+            Value::String(s) => Some(s.clone()),
+            Value::Number(n) => Some(n.to_string()),
+            Value::Bool(b) => Some(b.to_string()),
+            Value::Null | Value::Array(_) | Value::Object(_) => None,
+        }
+    }
+
+    match value {
+        Value::Array(items) => items.iter().map(scalar).collect(),
+        value => scalar(value).map(|s| vec![s]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[derive(Clone, Debug, Parser, Serialize)]
+    struct TestConfig {
+        /// A required option
+        #[clap(long)]
+        listen_port: u16,
+
+        /// An option with a default
+        #[clap(long, default_value = "default")]
+        name: String,
+
+        /// An option with an environment variable which only one test sets
+        #[clap(long, env = "MC_LAYERED_TEST_REGION", default_value = "default")]
+        region: String,
+
+        /// A flag
+        #[clap(long)]
+        verbose: bool,
+
+        /// An option taking several values
+        #[clap(long, use_value_delimiter = true)]
+        peers: Vec<String>,
+
+        /// A secret option
+        #[clap(long)]
+        #[serde(serialize_with = "redact_secret")]
+        secret: Option<String>,
+    }
+
+    fn config_file(extension: &str, contents: &str) -> NamedTempFile {
+        let mut file = tempfile::Builder::new()
+            .suffix(extension)
+            .tempfile()
+            .unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+        file
+    }
+
+    fn load(file: &NamedTempFile, extra_args: &[&str]) -> Result<TestConfig, clap::Error> {
+        let config_file_arg = format!("--config-file={}", file.path().display());
+        let args = ["test", config_file_arg.as_str()]
+            .into_iter()
+            .chain(extra_args.iter().copied());
+        TestConfig::try_load_from(args)
+    }
+
+    #[test]
+    fn without_config_file() {
+        let config = TestConfig::try_load_from(["test", "--listen-port=1234"]).unwrap();
+        assert_eq!(config.listen_port, 1234);
+        assert_eq!(config.name, "default");
+        assert!(!config.verbose);
+
+        let err = TestConfig::try_load_from(["test"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn toml_and_json_files() {
+        let file = config_file(
+            ".toml",
+            "listen_port = 1234\nverbose = true\npeers = [\"a\", \"b\"]\n",
+        );
+        let config = load(&file, &[]).unwrap();
+        assert_eq!(config.listen_port, 1234);
+        assert_eq!(config.name, "default");
+        assert!(config.verbose);
+        assert_eq!(config.peers, ["a", "b"]);
+
+        let file = config_file(".json", r#"{"listen-port": 4321, "name": "from file"}"#);
+        let config = load(&file, &[]).unwrap();
+        assert_eq!(config.listen_port, 4321);
+        assert_eq!(config.name, "from file");
+        assert!(!config.verbose);
+        assert!(config.peers.is_empty());
+    }
+
+    #[test]
+    fn precedence() {
+        let file = config_file(".toml", "listen_port = 1234\nregion = \"from file\"\n");
+        assert_eq!(load(&file, &[]).unwrap().region, "from file");
+
+        std::env::set_var("MC_LAYERED_TEST_REGION", "from env");
+        assert_eq!(load(&file, &[]).unwrap().region, "from env");
+
+        let config = load(&file, &["--region=from args", "--listen-port=1"]).unwrap();
+        std::env::remove_var("MC_LAYERED_TEST_REGION");
+        assert_eq!(config.region, "from args");
+        assert_eq!(config.listen_port, 1);
+    }
+
+    #[test]
+    fn secrets_are_redacted() {
+        let config = TestConfig::try_load_from(["test", "--listen-port=1"]).unwrap();
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(json["secret"], Value::Null);
+
+        let config =
+            TestConfig::try_load_from(["test", "--listen-port=1", "--secret=hunter2"]).unwrap();
+        assert_eq!(config.secret.as_deref(), Some("hunter2"));
+        let json = serde_json::to_string(&config).unwrap();
+        assert!(json.contains(r#""secret":"<redacted>""#));
+        assert!(!json.contains("hunter2"));
+    }
+
+    #[test]
+    fn invalid_files() {
+        let file = config_file(".toml", "listen_port = 1234\nlisten_prot = 1\n");
+        let err = load(&file, &[]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnknownArgument);
+        assert!(err.to_string().contains("`listen_prot`"));
+
+        let file = config_file(".toml", "listen_port = \"not a port\"\n");
+        assert_eq!(
+            load(&file, &[]).unwrap_err().kind(),
+            ErrorKind::ValueValidation
+        );
+
+        let file = config_file(".toml", "[listen_port]\nvalue = 1\n");
+        assert_eq!(
+            load(&file, &[]).unwrap_err().kind(),
+            ErrorKind::InvalidValue
+        );
+
+        let file = config_file(".toml", "listen_port = \n");
+        assert_eq!(load(&file, &[]).unwrap_err().kind(), ErrorKind::Format);
+
+        let file = config_file(".yaml", "listen_port: 1234\n");
+        assert_eq!(
+            load(&file, &[]).unwrap_err().kind(),
+            ErrorKind::InvalidValue
+        );
+    }
+}
@@ -1,5 +1,9 @@
 // Copyright (c) 2018-2022 The MobileCoin Foundation
 
+mod layered;
+
+pub use layered::{redact_secret, LayeredConfig, REDACTED};
+
 use clap::{CommandFactory, FromArgMatches, Parser};
 
 /// Command line parser trait that provides build information into the version
@@ -5,6 +5,7 @@
 
 use clap::Parser;
 use mc_common::logger::create_root_logger;
+use mc_util_generate_sample_ledger::genesis_spec::GenesisSpec;
 use std::path::PathBuf;
 
 /// Configuration.
@@ -42,6 +43,13 @@ struct Config {
     /// of confidential token ids.
     #[clap(long, default_value = "0", env = "MC_MAX_TOKEN_ID")]
     pub max_token_id: u64,
+
+    /// TOML file describing the tokens and distributions of the origin block.
+    /// When set, `--txs`, `--blocks`, `--key-images` and `--max-token-id` are
+    /// ignored, and a matching consensus tokens config is written to
+    /// tokens.json.
+    #[clap(long, env = "MC_GENESIS_SPEC")]
+    pub genesis_spec: Option<PathBuf>,
 }
 
 fn main() {
@@ -60,6 +68,24 @@ fn main() {
         });
     assert!(!pub_addrs.is_empty());
 
+    if let Some(spec_path) = &config.genesis_spec {
+        let spec = GenesisSpec::load_from_path(spec_path)
+            .unwrap_or_else(|err| panic!("Could not load genesis spec {spec_path:?}: {err}"));
+        mc_util_generate_sample_ledger::bootstrap_ledger_from_spec(
+            &config.output_dir,
+            &pub_addrs,
+            &spec,
+            config.seed,
+            logger,
+        )
+        .unwrap_or_else(|err| panic!("Could not bootstrap ledger from {spec_path:?}: {err}"));
+
+        let tokens_config = serde_json::to_string_pretty(&spec.tokens_config())
+            .expect("Could not serialize tokens config");
+        std::fs::write("tokens.json", tokens_config).expect("Could not write tokens.json");
+        return;
+    }
+
     // Bootstrap the ledger db
     mc_util_generate_sample_ledger::bootstrap_ledger(
         &config.output_dir,
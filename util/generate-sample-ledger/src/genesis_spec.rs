@@ -0,0 +1,413 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! A TOML description of an origin block with several tokens.
+//!
+//! A spec lists the tokens to create. Each token can be split equally between
+//! all recipients, given to specific recipients, or both, and can name the
+//! governors allowed to sign its minting configurations. For example:
+//!
+//! ```toml
+//! [[tokens]]
+//! token_id = 0
+//! outputs_per_recipient = 10
+//! amount_per_output = 1_000_000_000_000
+//!
+//! [[tokens]]
+//! token_id = 1
+//! minimum_fee = 1024
+//! allocations = [{ recipient = 0, num_outputs = 2, amount_per_output = 500 }]
+//! governors = { threshold = 1, signers = ["""
+//! -----BEGIN PUBLIC KEY-----
+//! ...
+//! -----END PUBLIC KEY-----
+//! """] }
+//! ```
+//!
+//! Recipients are referred to by their position in the list of public
+//! addresses the ledger is generated for.
+
+use displaydoc::Display;
+use mc_account_keys::PublicAddress;
+use mc_crypto_keys::{DistinguishedEncoding, Ed25519Public};
+use mc_transaction_core::{Amount, BlockVersion, TokenId};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{collections::HashSet, fs, path::Path};
+
+/// An error in a genesis spec.
+#[derive(Debug, Display)]
+pub enum GenesisSpecError {
+    /// Could not read the spec: {0}
+    Io(std::io::Error),
+
+    /// Could not parse the spec: {0}
+    Toml(toml::de::Error),
+
+    /// Block version {0} is not supported
+    InvalidBlockVersion(u32),
+
+    /// Block version {0} does not support more than one token
+    MixedTokensNotSupported(BlockVersion),
+
+    /// Token {0} is listed more than once
+    DuplicateToken(TokenId),
+
+    /// No outputs are created for token {0}
+    NoOutputs(TokenId),
+
+    /// Token {0} is allocated to recipient {1}, but there are only {2}
+    UnknownRecipient(TokenId, usize, usize),
+
+    /// The supply of token {0} does not fit in a u64
+    SupplyOverflow(TokenId),
+
+    /// Token {0} cannot have governors
+    GovernorsNotAllowed(TokenId),
+
+    /// Token {0} has a governor threshold of {1} with {2} signers
+    InvalidThreshold(TokenId, u32, usize),
+
+    /// Token {0} has an invalid governor key: {1}
+    InvalidGovernorKey(TokenId, String),
+}
+
+impl From<std::io::Error> for GenesisSpecError {
+    fn from(src: std::io::Error) -> Self {
+        Self::Io(src)
+    }
+}
+
+impl From<toml::de::Error> for GenesisSpecError {
+    fn from(src: toml::de::Error) -> Self {
+        Self::Toml(src)
+    }
+}
+
+/// The tokens to create in the origin block.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct GenesisSpec {
+    /// The block version to create TxOuts at. Defaults to the first version
+    /// which supports multiple tokens.
+    #[serde(default = "default_block_version")]
+    pub block_version: u32,
+
+    /// The tokens to create.
+    pub tokens: Vec<TokenSpec>,
+}
+
+fn default_block_version() -> u32 {
+    *BlockVersion::THREE
+}
+
+/// The initial distribution and governance of one token.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct TokenSpec {
+    /// The token id.
+    pub token_id: u64,
+
+    /// The minimum fee to configure consensus with, if not the default for
+    /// this token.
+    #[serde(default)]
+    pub minimum_fee: Option<u64>,
+
+    /// The number of outputs every recipient gets.
+    #[serde(default)]
+    pub outputs_per_recipient: usize,
+
+    /// The value of each of the outputs every recipient gets.
+    #[serde(default)]
+    pub amount_per_output: u64,
+
+    /// Outputs for specific recipients, on top of the ones every recipient
+    /// gets.
+    #[serde(default)]
+    pub allocations: Vec<AllocationSpec>,
+
+    /// The keys allowed to sign minting configurations for this token.
+    #[serde(default)]
+    pub governors: Option<GovernorsSpec>,
+}
+
+/// Outputs for a single recipient.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AllocationSpec {
+    /// The index of the recipient.
+    pub recipient: usize,
+
+    /// The number of outputs.
+    pub num_outputs: usize,
+
+    /// The value of each output.
+    pub amount_per_output: u64,
+}
+
+/// A threshold of Ed25519 signers.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct GovernorsSpec {
+    /// The number of signatures required.
+    pub threshold: u32,
+
+    /// The PEM-encoded public keys of the signers.
+    pub signers: Vec<String>,
+}
+
+impl GenesisSpec {
+    /// Read a spec from a TOML file.
+    pub fn load_from_path(path: impl AsRef<Path>) -> Result<Self, GenesisSpecError> {
+        let data = fs::read_to_string(path)?;
+        Ok(toml::from_str(&data)?)
+    }
+
+    /// The block version to create TxOuts at.
+    pub fn block_version(&self) -> Result<BlockVersion, GenesisSpecError> {
+        BlockVersion::try_from(self.block_version)
+            .map_err(|_| GenesisSpecError::InvalidBlockVersion(self.block_version))
+    }
+
+    /// Check the spec against the number of recipients.
+    pub fn validate(&self, num_recipients: usize) -> Result<(), GenesisSpecError> {
+        let block_version = self.block_version()?;
+        if self.tokens.len() > 1 && !block_version.mixed_transactions_are_supported() {
+            return Err(GenesisSpecError::MixedTokensNotSupported(block_version));
+        }
+
+        let mut token_ids = HashSet::new();
+        for token in &self.tokens {
+            let token_id = TokenId::from(token.token_id);
+            if !token_ids.insert(token_id) {
+                return Err(GenesisSpecError::DuplicateToken(token_id));
+            }
+            token.validate(num_recipients)?;
+        }
+        Ok(())
+    }
+
+    /// The recipient and amount of every output, in the order they appear in
+    /// the origin block.
+    pub fn outputs(&self, recipients: &[PublicAddress]) -> Vec<(PublicAddress, Amount)> {
+        self.tokens
+            .iter()
+            .flat_map(|token| token.outputs(recipients))
+            .collect()
+    }
+
+    /// A tokens configuration for consensus, in its JSON format, with the
+    /// fees and governors in this spec.
+    pub fn tokens_config(&self) -> Value {
+        let mut tokens = self
+            .tokens
+            .iter()
+            .map(TokenSpec::token_config)
+            .collect::<Vec<_>>();
+        // Consensus requires MOB to be configured.
+        if !self
+            .tokens
+            .iter()
+            .any(|token| token.token_id == *TokenId::MOB)
+        {
+            tokens.insert(0, json!({ "token_id": *TokenId::MOB }));
+        }
+        json!({ "tokens": tokens })
+    }
+}
+
+impl TokenSpec {
+    fn validate(&self, num_recipients: usize) -> Result<(), GenesisSpecError> {
+        let token_id = TokenId::from(self.token_id);
+
+        let mut supply = 0u64;
+        let mut add = |num_outputs: usize, amount: u64| -> Result<(), GenesisSpecError> {
+            supply = (num_outputs as u64)
+                .checked_mul(amount)
+                .and_then(|value| supply.checked_add(value))
+                .ok_or(GenesisSpecError::SupplyOverflow(token_id))?;
+            Ok(())
+        };
+        for _ in 0..num_recipients {
+            add(self.outputs_per_recipient, self.amount_per_output)?;
+        }
+        for allocation in &self.allocations {
+            if allocation.recipient >= num_recipients {
+                return Err(GenesisSpecError::UnknownRecipient(
+                    token_id,
+                    allocation.recipient,
+                    num_recipients,
+                ));
+            }
+            add(allocation.num_outputs, allocation.amount_per_output)?;
+        }
+        if supply == 0 {
+            return Err(GenesisSpecError::NoOutputs(token_id));
+        }
+
+        if let Some(governors) = &self.governors {
+            if token_id == TokenId::MOB {
+                return Err(GenesisSpecError::GovernorsNotAllowed(token_id));
+            }
+            if governors.threshold == 0 || governors.threshold as usize > governors.signers.len() {
+                return Err(GenesisSpecError::InvalidThreshold(
+                    token_id,
+                    governors.threshold,
+                    governors.signers.len(),
+                ));
+            }
+            for signer in &governors.signers {
+                parse_public_key(signer)
+                    .map_err(|err| GenesisSpecError::InvalidGovernorKey(token_id, err))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn outputs(&self, recipients: &[PublicAddress]) -> Vec<(PublicAddress, Amount)> {
+        let token_id = TokenId::from(self.token_id);
+        let mut outputs = Vec::new();
+        // Zero-valued outputs are skipped, so that an allocation-only token
+        // doesn't give every recipient empty outputs.
+        if self.amount_per_output > 0 {
+            for recipient in recipients {
+                for _ in 0..self.outputs_per_recipient {
+                    outputs.push((
+                        recipient.clone(),
+                        Amount::new(self.amount_per_output, token_id),
+                    ));
+                }
+            }
+        }
+        for allocation in &self.allocations {
+            if allocation.amount_per_output == 0 {
+                continue;
+            }
+            for _ in 0..allocation.num_outputs {
+                outputs.push((
+                    recipients[allocation.recipient].clone(),
+                    Amount::new(allocation.amount_per_output, token_id),
+                ));
+            }
+        }
+        outputs
+    }
+
+    fn token_config(&self) -> Value {
+        let mut config = json!({ "token_id": self.token_id });
+        if let Some(minimum_fee) = self.minimum_fee {
+            config["minimum_fee"] = json!(minimum_fee);
+        }
+        if let Some(governors) = &self.governors {
+            let signers = governors
+                .signers
+                .iter()
+                .map(|pub_key| json!({ "type": "Single", "pub_key": pub_key.trim() }))
+                .collect::<Vec<_>>();
+            config["governors"] = json!({
+                "type": "MultiSig",
+                "threshold": governors.threshold,
+                "signers": signers,
+            });
+        }
+        config
+    }
+}
+
+fn parse_public_key(pem_str: &str) -> Result<Ed25519Public, String> {
+    let pem = pem::parse(pem_str.trim()).map_err(|err| err.to_string())?;
+    Ed25519Public::try_from_der(pem.contents()).map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mc_account_keys::AccountKey;
+    use mc_crypto_keys::Ed25519Pair;
+    use mc_util_from_random::FromRandom;
+    use rand::SeedableRng;
+    use rand_hc::Hc128Rng;
+
+    fn governor_pem(rng: &mut Hc128Rng) -> String {
+        let public_key = Ed25519Pair::from_random(rng).public_key();
+        pem::encode(&pem::Pem::new("PUBLIC KEY", public_key.to_der()))
+    }
+
+    fn spec(governor: &str) -> GenesisSpec {
+        toml::from_str(&format!(
+            r#"
+            [[tokens]]
+            token_id = 0
+            outputs_per_recipient = 2
+            amount_per_output = 1000
+
+            [[tokens]]
+            token_id = 1
+            minimum_fee = 1024
+            allocations = [{{ recipient = 1, num_outputs = 3, amount_per_output = 7 }}]
+            governors = {{ threshold = 1, signers = ["""{governor}"""] }}
+            "#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn outputs_follow_the_spec() {
+        let mut rng = Hc128Rng::from_seed([1u8; 32]);
+        let recipients = (0..3)
+            .map(|_| AccountKey::random(&mut rng).default_subaddress())
+            .collect::<Vec<_>>();
+        let spec = spec(&governor_pem(&mut rng));
+        spec.validate(recipients.len()).unwrap();
+        assert_eq!(spec.block_version().unwrap(), BlockVersion::THREE);
+
+        let outputs = spec.outputs(&recipients);
+        assert_eq!(outputs.len(), 9);
+        assert!(outputs[..6]
+            .iter()
+            .all(|(_, amount)| *amount == Amount::new(1000, TokenId::MOB)));
+        assert!(outputs[6..]
+            .iter()
+            .all(|output| *output == (recipients[1].clone(), Amount::new(7, 1.into()))));
+
+        let tokens_config = spec.tokens_config();
+        assert_eq!(tokens_config["tokens"][1]["minimum_fee"], 1024);
+        assert_eq!(tokens_config["tokens"][1]["governors"]["threshold"], 1);
+    }
+
+    #[test]
+    fn invalid_specs_are_rejected() {
+        let mut rng = Hc128Rng::from_seed([1u8; 32]);
+
+        let spec = spec(&governor_pem(&mut rng));
+        assert!(matches!(
+            spec.validate(1),
+            Err(GenesisSpecError::UnknownRecipient(_, 1, 1))
+        ));
+
+        let mut mob_governors = spec.clone();
+        mob_governors.tokens[0].governors = spec.tokens[1].governors.clone();
+        assert!(matches!(
+            mob_governors.validate(2),
+            Err(GenesisSpecError::GovernorsNotAllowed(_))
+        ));
+
+        let mut old_version = spec.clone();
+        old_version.block_version = 0;
+        assert!(matches!(
+            old_version.validate(2),
+            Err(GenesisSpecError::MixedTokensNotSupported(_))
+        ));
+
+        assert!(matches!(
+            self::spec("not a key").validate(2),
+            Err(GenesisSpecError::InvalidGovernorKey(..))
+        ));
+
+        let mut overflow = spec;
+        overflow.tokens[0].amount_per_output = u64::MAX;
+        assert!(matches!(
+            overflow.validate(2),
+            Err(GenesisSpecError::SupplyOverflow(_))
+        ));
+    }
+}
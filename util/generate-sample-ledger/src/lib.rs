@@ -4,11 +4,18 @@
 
 #![deny(missing_docs)]
 
+pub mod genesis_spec;
+
+use crate::genesis_spec::{GenesisSpec, GenesisSpecError};
 use mc_account_keys::PublicAddress;
-use mc_blockchain_test_utils::get_blocks_with_recipients;
+use mc_blockchain_test_utils::{
+    get_blocks_with_recipients, make_block_metadata, make_block_signature,
+};
+use mc_blockchain_types::{Block, BlockContents, BlockData};
 use mc_common::logger::{log, Logger};
 use mc_ledger_db::{Ledger, LedgerDB};
 use mc_transaction_core::{constants::TOTAL_MOB, BlockVersion};
+use mc_transaction_core_test_utils::get_outputs;
 use rand::SeedableRng;
 use rand_hc::Hc128Rng as FixedRng;
 use std::path::Path;
@@ -108,3 +115,52 @@ pub fn bootstrap_ledger(
            mc_util_build_info::git_commit(),
     ).expect("File I/O");
 }
+
+/// Deterministically creates a ledger whose origin block follows a
+/// [GenesisSpec].
+///
+/// # Arguments
+/// * `path` - Opens a LedgerDB instance at the given path.
+/// * `recipients` - The recipients the spec refers to, by index.
+/// * `spec` - The tokens and distributions to create.
+/// * `seed` - Seed for the TxOut keys, so that the same spec and seed always
+///   produce the same ledger.
+pub fn bootstrap_ledger_from_spec(
+    path: &Path,
+    recipients: &[PublicAddress],
+    spec: &GenesisSpec,
+    seed: Option<[u8; 32]>,
+    logger: Logger,
+) -> Result<(), GenesisSpecError> {
+    spec.validate(recipients.len())?;
+    let block_version = spec.block_version()?;
+
+    let mut rng = FixedRng::from_seed(seed.unwrap_or([33u8; 32]));
+    let recipient_and_amount = spec.outputs(recipients);
+    log::info!(
+        logger,
+        "Making {} outputs across {} recipients ({} tokens).",
+        recipient_and_amount.len(),
+        recipients.len(),
+        spec.tokens.len(),
+    );
+    let outputs = get_outputs(block_version, &recipient_and_amount, &mut rng);
+
+    let block = Block::new_origin_block(&outputs);
+    let block_contents = BlockContents {
+        outputs,
+        ..Default::default()
+    };
+    let signature = make_block_signature(&block, &mut rng);
+    let metadata = make_block_metadata(block.id.clone(), &mut rng);
+    let block_data = BlockData::new(block, block_contents, signature, Some(metadata));
+
+    std::fs::create_dir_all(path)?;
+    LedgerDB::create(path).expect("Could not create ledger_db");
+    let mut db = LedgerDB::open(path).expect("Could not open ledger_db");
+    db.append_block_data(&block_data)
+        .expect("Failed to add origin block");
+
+    log::info!(logger, "Wrote LedgerDB to {:?}", path);
+    Ok(())
+}
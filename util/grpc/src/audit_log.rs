@@ -0,0 +1,375 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! An append-only audit log of administrative and attestation events.
+//!
+//! Compliance teams need evidence of operational changes to a server, such as
+//! shards being added or TLS certificates being reloaded, which is kept
+//! separately from (and for longer than) the debug logs. When configured, each
+//! event is appended to the audit log file as one JSON object per line, and
+//! synced to disk before the call returns. The file is rotated once it reaches
+//! a configured size, keeping a configured number of rotated files, named
+//! `<path>.1` (the newest) to `<path>.<max files>` (the oldest).
+//!
+//! The audit log is global to the process, so that code far from the server
+//! setup (like the [ServerCertReloader](crate::ServerCertReloader)) can record
+//! events. Recording an event when no audit log is configured does nothing.
+
+use clap::Parser;
+use mc_common::logger::{log, Logger};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+static AUDIT_LOG: OnceLock<AuditLog> = OnceLock::new();
+
+/// Operator-facing configuration of the audit log of a server.
+#[derive(Clone, Debug, Eq, PartialEq, Parser, Serialize)]
+pub struct AuditLogConfig {
+    /// File to append audit events to, as JSON lines. Audit events are not
+    /// recorded if this is unset.
+    #[clap(long, env = "MC_AUDIT_LOG_PATH")]
+    pub audit_log_path: Option<PathBuf>,
+
+    /// Size, in bytes, at which the audit log file is rotated.
+    #[clap(long, default_value = "104857600", env = "MC_AUDIT_LOG_MAX_BYTES")]
+    pub audit_log_max_bytes: u64,
+
+    /// Number of rotated audit log files to keep.
+    #[clap(long, default_value = "10", env = "MC_AUDIT_LOG_MAX_FILES")]
+    pub audit_log_max_files: usize,
+}
+
+impl Default for AuditLogConfig {
+    fn default() -> Self {
+        Self {
+            audit_log_path: None,
+            audit_log_max_bytes: 100 * 1024 * 1024,
+            audit_log_max_files: 10,
+        }
+    }
+}
+
+impl AuditLogConfig {
+    /// Open the configured audit log, and use it for the events recorded by
+    /// this process, which are attributed to `service`.
+    ///
+    /// Does nothing if no audit log path is configured, or if the process
+    /// already has an audit log.
+    pub fn init(&self, service: &str) -> io::Result<()> {
+        let Some(path) = self.audit_log_path.as_ref() else {
+            return Ok(());
+        };
+        let audit_log = AuditLog::open(
+            path,
+            service,
+            self.audit_log_max_bytes,
+            self.audit_log_max_files,
+        )?;
+        let _ = AUDIT_LOG.set(audit_log);
+        Ok(())
+    }
+}
+
+/// An event recorded in the audit log.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AuditEvent {
+    /// A shard was added through the admin API.
+    ShardAdded {
+        /// The (redacted) URI of the shard.
+        shard_uri: String,
+    },
+    /// An enclave attested with a store, either as the server started or
+    /// after the store lost its session.
+    StoreAttested {
+        /// The (redacted) URI of the store.
+        store_uri: String,
+    },
+    /// An enclave failed to attest with a store.
+    StoreAttestationFailed {
+        /// The (redacted) URI of the store.
+        store_uri: String,
+        /// Why the attestation failed.
+        error: String,
+    },
+    /// The server loaded a client auth token secret. A different fingerprint
+    /// from the previous start shows that the secret was rotated.
+    AuthTokenSecretLoaded {
+        /// The hex-encoded first 8 bytes of the SHA-256 hash of the secret.
+        fingerprint: String,
+    },
+    /// The server loaded its TLS certificate and key, as it started or after
+    /// a SIGHUP.
+    TlsCertReloaded {
+        /// The certificate file.
+        cert_file: PathBuf,
+    },
+    /// The server failed to load its TLS certificate and key, and kept using
+    /// the ones it had.
+    TlsCertReloadFailed {
+        /// The certificate file.
+        cert_file: PathBuf,
+        /// Why loading failed.
+        error: String,
+    },
+    /// The list of blocked store enclave measurements was set, as the server
+    /// started or through the admin API.
+    StoreMeasurementsBlocked {
+        /// The hex-encoded MRENCLAVE values which are now blocked.
+        mr_enclaves: Vec<String>,
+        /// The responder ids of the stores whose sessions were closed.
+        closed_stores: Vec<String>,
+    },
+    /// A shard handoff was started through the admin API.
+    ShardHandoffStarted {
+        /// The (redacted) URIs of the shards to be retired.
+        retiring_shard_uris: Vec<String>,
+        /// The (redacted) URIs of the shards taking over.
+        incoming_shard_uris: Vec<String>,
+    },
+    /// A shard handoff completed, and the incoming shards replaced the
+    /// retiring shards.
+    ShardHandoffCompleted {
+        /// The (redacted) URIs of the retired shards.
+        retiring_shard_uris: Vec<String>,
+        /// The (redacted) URIs of the shards which took over.
+        incoming_shard_uris: Vec<String>,
+        /// Whether the handoff was forced before the incoming shards were
+        /// ready.
+        forced: bool,
+    },
+    /// A shard handoff was cancelled, leaving the shards unchanged.
+    ShardHandoffCancelled {
+        /// The (redacted) URIs of the shards which were to take over.
+        incoming_shard_uris: Vec<String>,
+    },
+    /// A client stream was cancelled through the admin API.
+    QueryCancelled {
+        /// The gRPC method of the stream.
+        method: String,
+        /// The client's address.
+        peer: String,
+    },
+}
+
+impl AuditEvent {
+    /// The event for loading a client auth token secret, which records a
+    /// fingerprint of it rather than the secret itself.
+    pub fn auth_token_secret_loaded(secret: &[u8]) -> Self {
+        let hash = Sha256::digest(secret);
+        Self::AuthTokenSecretLoaded {
+            fingerprint: hex::encode(&hash[..8]),
+        }
+    }
+}
+
+/// Record an event in the audit log of this process, if it has one.
+///
+/// Failing to write the event is logged rather than returned, since the
+/// action being audited has already happened.
+pub fn record_audit_event(event: AuditEvent, logger: &Logger) {
+    if let Some(audit_log) = AUDIT_LOG.get() {
+        if let Err(err) = audit_log.record(&event) {
+            log::error!(
+                logger,
+                "Could not write {:?} to the audit log: {}",
+                event,
+                err
+            );
+        }
+    }
+}
+
+/// One line of the audit log.
+#[derive(Serialize)]
+struct AuditRecord<'a> {
+    /// Milliseconds since the Unix epoch.
+    timestamp_ms: u64,
+    service: &'a str,
+    #[serde(flatten)]
+    event: &'a AuditEvent,
+}
+
+/// An audit log file, rotated by size.
+pub struct AuditLog {
+    service: String,
+    file: Mutex<RotatingFile>,
+}
+
+impl AuditLog {
+    /// Open (or create) the audit log at `path`, for events from `service`.
+    pub fn open(
+        path: &impl AsRef<Path>,
+        service: &str,
+        max_bytes: u64,
+        max_files: usize,
+    ) -> io::Result<Self> {
+        Ok(Self {
+            service: service.to_owned(),
+            file: Mutex::new(RotatingFile::open(
+                path.as_ref().to_path_buf(),
+                max_bytes,
+                max_files,
+            )?),
+        })
+    }
+
+    /// Append an event to the log.
+    pub fn record(&self, event: &AuditEvent) -> io::Result<()> {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or_default();
+        let mut line = serde_json::to_vec(&AuditRecord {
+            timestamp_ms,
+            service: &self.service,
+            event,
+        })?;
+        line.push(b'\n');
+
+        self.file.lock().expect("mutex poisoned").write_line(&line)
+    }
+}
+
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_bytes: u64,
+    max_files: usize,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf, max_bytes: u64, max_files: usize) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            file,
+            size,
+            max_bytes,
+            max_files,
+        })
+    }
+
+    fn write_line(&mut self, line: &[u8]) -> io::Result<()> {
+        if self.size > 0 && self.size + line.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        self.file.write_all(line)?;
+        self.file.sync_data()?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.max_files == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            // Renaming over the oldest file drops it.
+            for index in (1..self.max_files).rev() {
+                match fs::rename(self.rotated_path(index), self.rotated_path(index + 1)) {
+                    Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                    _ => {}
+                }
+            }
+            fs::rename(&self.path, self.rotated_path(1))?;
+        }
+
+        *self = Self::open(self.path.clone(), self.max_bytes, self.max_files)?;
+        Ok(())
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{index}"));
+        path.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_lines(path: &Path) -> Vec<serde_json::Value> {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn records_events_as_json_lines() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("audit.log");
+        let audit_log = AuditLog::open(&path, "fog_view_router", 1024 * 1024, 2).unwrap();
+
+        audit_log
+            .record(&AuditEvent::ShardAdded {
+                shard_uri: "insecure-fog-view-store://store1:3225".to_owned(),
+            })
+            .unwrap();
+        audit_log
+            .record(&AuditEvent::auth_token_secret_loaded(&[1; 32]))
+            .unwrap();
+
+        let lines = read_lines(&path);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["service"], "fog_view_router");
+        assert_eq!(lines[0]["event"], "shard_added");
+        assert_eq!(
+            lines[0]["shard_uri"],
+            "insecure-fog-view-store://store1:3225"
+        );
+        assert!(lines[0]["timestamp_ms"].as_u64().unwrap() > 0);
+        assert_eq!(lines[1]["event"], "auth_token_secret_loaded");
+        assert_eq!(lines[1]["fingerprint"].as_str().unwrap().len(), 16);
+    }
+
+    #[test]
+    fn rotates_by_size() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("audit.log");
+        let event = AuditEvent::StoreAttested {
+            store_uri: "insecure-key-image-store://store1:3228".to_owned(),
+        };
+        // Each line is under 200 bytes, so this fits two lines per file.
+        let audit_log = AuditLog::open(&path, "ledger_router", 300, 2).unwrap();
+
+        for _ in 0..7 {
+            audit_log.record(&event).unwrap();
+        }
+
+        assert_eq!(read_lines(&path).len(), 1);
+        assert_eq!(read_lines(&temp_dir.path().join("audit.log.1")).len(), 2);
+        assert_eq!(read_lines(&temp_dir.path().join("audit.log.2")).len(), 2);
+        assert!(!temp_dir.path().join("audit.log.3").exists());
+    }
+
+    #[test]
+    fn appends_to_existing_log() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("audit.log");
+        let event = AuditEvent::TlsCertReloaded {
+            cert_file: "/etc/tls/server.crt".into(),
+        };
+
+        AuditLog::open(&path, "fog_view_router", 1024, 1)
+            .unwrap()
+            .record(&event)
+            .unwrap();
+        AuditLog::open(&path, "fog_view_router", 1024, 1)
+            .unwrap()
+            .record(&event)
+            .unwrap();
+
+        assert_eq!(read_lines(&path).len(), 2);
+    }
+}
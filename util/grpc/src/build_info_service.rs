@@ -42,6 +42,7 @@ pub fn get_build_info() -> BuildInfo {
     build_info.set_target_feature(::mc_util_build_info::target_feature().to_owned());
     build_info.set_rustflags(::mc_util_build_info::rustflags().to_owned());
     build_info.set_sgx_mode(::mc_util_build_info::sgx_mode().to_owned());
+    build_info.set_dalek_backend(::mc_util_build_info::dalek_backend().to_owned());
     build_info
 }
 
@@ -1,4 +1,4 @@
-use grpcio::{RpcContext, RpcStatus, RpcStatusCode};
+use grpcio::{Metadata, RpcContext, RpcStatus, RpcStatusCode};
 
 /// The string used for the chain id GRPC header
 /// Note that a corresponding HTTP header is defined by the go-grpc-gateway
@@ -11,7 +11,14 @@ pub const CHAIN_ID_MISMATCH_ERR_MSG: &str = "chain-id mismatch:";
 /// Test the chain id of a request against the value on the server side.
 /// This does nothing if the client does not supply a chain-id header.
 pub fn check_request_chain_id(server_chain_id: &str, ctx: &RpcContext) -> Result<(), RpcStatus> {
-    for (header, value) in ctx.request_headers().iter() {
+    check_chain_id_metadata(server_chain_id, ctx.request_headers())
+}
+
+/// Test the chain id found in a set of request headers against the value on
+/// the server side.
+/// This does nothing if the headers do not contain a chain-id header.
+pub fn check_chain_id_metadata(server_chain_id: &str, headers: &Metadata) -> Result<(), RpcStatus> {
+    for (header, value) in headers.iter() {
         if header == CHAIN_ID_GRPC_HEADER && server_chain_id.as_bytes() != value {
             return Err(RpcStatus::with_message(
                 RpcStatusCode::FAILED_PRECONDITION,
@@ -60,8 +60,21 @@ impl ConnectionUriGrpcioChannel for ChannelBuilder {
 pub trait ConnectionUriGrpcioServer {
     /// Build a Server from a ServerBuilder using information from a URI and
     /// enable support for hot-reloading certificates when TLS is used.
+    ///
+    /// The server binds exactly the host of the URI, so `0.0.0.0` only
+    /// accepts IPv4 connections. `[::]` binds a dual-stack socket, which
+    /// accepts both IPv4 and IPv6 connections where the host supports it.
     fn build_using_uri(self, uri: &impl ConnectionUri, logger: Logger) -> Result<Server>;
 
+    /// Build a Server from a ServerBuilder which listens on each of several
+    /// URIs, serving the same services on all of them. Each URI gets its own
+    /// credentials, so that e.g. a TLS URI and an insecure one can be mixed.
+    fn build_using_uris<'a, U: ConnectionUri + 'a>(
+        self,
+        uris: impl IntoIterator<Item = &'a U>,
+        logger: Logger,
+    ) -> Result<Server>;
+
     /// Create the default channel settings for server
     fn default_channel_builder(env: Arc<Environment>) -> ChannelBuilder {
         ChannelBuilder::new(env)
@@ -100,26 +113,27 @@ pub trait ConnectionUriGrpcioServer {
 
 impl ConnectionUriGrpcioServer for ServerBuilder {
     fn build_using_uri(self, uri: &impl ConnectionUri, logger: Logger) -> Result<Server> {
-        let server_creds = Self::server_credentials_from_uri(uri, &logger);
-
-        if uri.use_tls() {
-            log::debug!(
-                logger,
-                "Binding secure gRPC server to {}:{}",
-                uri.host(),
-                uri.port(),
-            );
-        } else {
-            log::warn!(
-                logger,
-                "Binding insecure gRPC server to {}:{}",
-                uri.host(),
-                uri.port(),
-            );
-        }
+        self.build_using_uris(core::iter::once(uri), logger)
+    }
 
+    fn build_using_uris<'a, U: ConnectionUri + 'a>(
+        self,
+        uris: impl IntoIterator<Item = &'a U>,
+        logger: Logger,
+    ) -> Result<Server> {
         let mut server = self.build()?;
-        server.add_listening_port(uri.addr(), server_creds)?;
+        for uri in uris {
+            let server_creds = Self::server_credentials_from_uri(uri, &logger);
+
+            let listen_addr = uri.addr();
+            if uri.use_tls() {
+                log::debug!(logger, "Binding secure gRPC server to {}", listen_addr);
+            } else {
+                log::warn!(logger, "Binding insecure gRPC server to {}", listen_addr);
+            }
+
+            server.add_listening_port(listen_addr, server_creds)?;
+        }
         Ok(server)
     }
 
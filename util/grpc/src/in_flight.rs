@@ -0,0 +1,237 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Tracks the client streams a server is handling, so that an operator can
+//! see what each of them is doing, and cancel one which is stuck without
+//! restarting the server and dropping every other client.
+
+use futures::future::{AbortHandle, AbortRegistration};
+use grpcio::RpcContext;
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// What an in-flight client stream is doing.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InFlightRequestInfo {
+    /// The id to cancel the stream with.
+    pub id: u64,
+    /// The gRPC method of the stream.
+    pub method: String,
+    /// The client's address.
+    pub peer: String,
+    /// How long ago the stream was opened.
+    pub age: Duration,
+    /// How many requests the client sent on the stream.
+    pub num_requests: u64,
+    /// The total size of the requests the client sent.
+    pub bytes_received: u64,
+    /// The total size of the responses sent to the client.
+    pub bytes_sent: u64,
+    /// How many shards the stream's queries are sent to.
+    pub num_shards: u64,
+    /// What the stream is waiting for, e.g. "idle" or "querying shards".
+    pub state: String,
+}
+
+struct InFlightState {
+    method: String,
+    peer: String,
+    started_at: Instant,
+    num_requests: AtomicU64,
+    bytes_received: AtomicU64,
+    bytes_sent: AtomicU64,
+    num_shards: AtomicU64,
+    state: Mutex<&'static str>,
+    abort_handle: AbortHandle,
+}
+
+/// The client streams a server is handling.
+#[derive(Default)]
+pub struct InFlightRequests {
+    next_id: AtomicU64,
+    requests: Mutex<BTreeMap<u64, Arc<InFlightState>>>,
+}
+
+impl InFlightRequests {
+    /// Start tracking the stream of a call.
+    ///
+    /// The stream is tracked until the returned [InFlightRequest] is dropped,
+    /// and cancelling it aborts the future made abortable with the returned
+    /// registration, e.g. with [futures::future::Abortable].
+    pub fn track(
+        self: &Arc<Self>,
+        ctx: &RpcContext,
+        num_shards: usize,
+    ) -> (InFlightRequest, AbortRegistration) {
+        let method = String::from_utf8_lossy(ctx.method()).into_owned();
+        self.start(method, ctx.peer(), num_shards)
+    }
+
+    fn start(
+        self: &Arc<Self>,
+        method: String,
+        peer: String,
+        num_shards: usize,
+    ) -> (InFlightRequest, AbortRegistration) {
+        let (abort_handle, abort_registration) = AbortHandle::new_pair();
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let state = Arc::new(InFlightState {
+            method,
+            peer,
+            started_at: Instant::now(),
+            num_requests: Default::default(),
+            bytes_received: Default::default(),
+            bytes_sent: Default::default(),
+            num_shards: AtomicU64::new(num_shards as u64),
+            state: Mutex::new("idle"),
+            abort_handle,
+        });
+        self.requests
+            .lock()
+            .expect("mutex poisoned")
+            .insert(id, state.clone());
+        let request = InFlightRequest {
+            id,
+            state,
+            requests: self.clone(),
+        };
+        (request, abort_registration)
+    }
+
+    /// The streams being handled, oldest first.
+    pub fn list(&self) -> Vec<InFlightRequestInfo> {
+        let now = Instant::now();
+        self.requests
+            .lock()
+            .expect("mutex poisoned")
+            .iter()
+            .map(|(id, state)| InFlightRequestInfo {
+                id: *id,
+                method: state.method.clone(),
+                peer: state.peer.clone(),
+                age: now.saturating_duration_since(state.started_at),
+                num_requests: state.num_requests.load(Ordering::SeqCst),
+                bytes_received: state.bytes_received.load(Ordering::SeqCst),
+                bytes_sent: state.bytes_sent.load(Ordering::SeqCst),
+                num_shards: state.num_shards.load(Ordering::SeqCst),
+                state: state.state.lock().expect("mutex poisoned").to_string(),
+            })
+            .collect()
+    }
+
+    /// Cancel the stream with the given id, returning what it was doing, or
+    /// None if there is no such stream, e.g. because it already ended.
+    pub fn cancel(&self, id: u64) -> Option<InFlightRequestInfo> {
+        let info = self.list().into_iter().find(|info| info.id == id)?;
+        let state = self.requests.lock().expect("mutex poisoned").remove(&id)?;
+        state.abort_handle.abort();
+        Some(info)
+    }
+}
+
+/// A stream being handled, which is no longer tracked once this is dropped.
+pub struct InFlightRequest {
+    id: u64,
+    state: Arc<InFlightState>,
+    requests: Arc<InFlightRequests>,
+}
+
+impl InFlightRequest {
+    /// The id to cancel the stream with.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Count a request the client sent, of `bytes` bytes.
+    pub fn request_received(&self, bytes: u32) {
+        self.state.num_requests.fetch_add(1, Ordering::SeqCst);
+        self.state
+            .bytes_received
+            .fetch_add(bytes as u64, Ordering::SeqCst);
+    }
+
+    /// Count a response sent to the client, of `bytes` bytes.
+    pub fn response_sent(&self, bytes: u32) {
+        self.state
+            .bytes_sent
+            .fetch_add(bytes as u64, Ordering::SeqCst);
+    }
+
+    /// Set how many shards the stream's queries are sent to.
+    pub fn set_num_shards(&self, num_shards: usize) {
+        self.state
+            .num_shards
+            .store(num_shards as u64, Ordering::SeqCst);
+    }
+
+    /// Set what the stream is waiting for.
+    pub fn set_state(&self, state: &'static str) {
+        *self.state.state.lock().expect("mutex poisoned") = state;
+    }
+}
+
+impl Drop for InFlightRequest {
+    fn drop(&mut self) {
+        self.requests
+            .requests
+            .lock()
+            .expect("mutex poisoned")
+            .remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{executor::block_on, future::Abortable};
+
+    #[test]
+    fn streams_are_listed_until_dropped() {
+        let requests = Arc::new(InFlightRequests::default());
+        let (first, _) = requests.start("/a".to_owned(), "peer-a".to_owned(), 2);
+        let (second, _) = requests.start("/b".to_owned(), "peer-b".to_owned(), 3);
+
+        first.request_received(10);
+        first.request_received(5);
+        first.response_sent(7);
+        first.set_state("querying shards");
+        second.set_num_shards(1);
+
+        let list = requests.list();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].id, first.id());
+        assert_eq!(list[0].method, "/a");
+        assert_eq!(list[0].peer, "peer-a");
+        assert_eq!(list[0].num_requests, 2);
+        assert_eq!(list[0].bytes_received, 15);
+        assert_eq!(list[0].bytes_sent, 7);
+        assert_eq!(list[0].num_shards, 2);
+        assert_eq!(list[0].state, "querying shards");
+        assert_eq!(list[1].num_shards, 1);
+        assert_eq!(list[1].state, "idle");
+
+        drop(first);
+        let list = requests.list();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].id, second.id());
+    }
+
+    #[test]
+    fn cancel_aborts_the_stream() {
+        let requests = Arc::new(InFlightRequests::default());
+        let (request, registration) = requests.start("/a".to_owned(), "peer".to_owned(), 1);
+        let stream = Abortable::new(futures::future::pending::<()>(), registration);
+
+        assert!(requests.cancel(request.id() + 1).is_none());
+        let cancelled = requests.cancel(request.id()).unwrap();
+        assert_eq!(cancelled.method, "/a");
+        assert!(requests.list().is_empty());
+        assert!(block_on(stream).is_err());
+        assert!(requests.cancel(request.id()).is_none());
+    }
+}
@@ -0,0 +1,447 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! A chain of request interceptors which run before a service handler.
+//!
+//! Services hold an [InterceptorChain] and call [InterceptorChain::check] at
+//! the top of each handler, instead of hand-wiring chain-id checks,
+//! authentication, admission control and similar concerns in every method.
+//! Which layers are present is decided when the chain is built, usually from
+//! an [InterceptorConfig].
+
+use crate::{check_chain_id_metadata, Authenticator};
+use clap::Parser;
+use grpcio::{Metadata, RpcContext, RpcStatus, RpcStatusCode};
+use mc_common::logger::{log, Logger};
+use mc_util_metrics::{OpMetrics, ServiceMetrics};
+use serde::Serialize;
+use std::{
+    fmt,
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+lazy_static::lazy_static! {
+    /// Counts requests rejected by each interceptor layer.
+    static ref INTERCEPTOR_COUNTERS: OpMetrics = OpMetrics::new_and_registered("grpc_interceptor");
+}
+
+/// The parts of an incoming call which interceptors see.
+///
+/// Interceptors only see the method name, the caller's address and the
+/// request headers, which keeps them independent of the request message type
+/// and easy to test.
+pub struct InterceptedCall<'a> {
+    /// The full name of the called method
+    pub method: &'a str,
+    /// The caller's address, as reported by gRPC, e.g. `ipv4:10.0.0.1:5123`
+    /// or `ipv6:[::1]:5123`. When the server is behind a proxy, this is the
+    /// proxy's address, and the client's address may be in the headers.
+    pub peer: &'a str,
+    /// The request headers
+    pub headers: &'a Metadata,
+}
+
+impl InterceptedCall<'_> {
+    /// The caller's IP address, unless it connected some other way, e.g. over
+    /// a unix socket. IPv4 addresses mapped into IPv6 are returned as IPv4.
+    pub fn peer_ip(&self) -> Option<IpAddr> {
+        let addr = self
+            .peer
+            .strip_prefix("ipv4:")
+            .or_else(|| self.peer.strip_prefix("ipv6:"))?;
+        let ip = SocketAddr::from_str(addr).ok()?.ip();
+        Some(match ip {
+            IpAddr::V6(ipv6) => ipv6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+            IpAddr::V4(_) => ip,
+        })
+    }
+}
+
+/// A single layer in an [InterceptorChain].
+pub trait Interceptor: Send + Sync {
+    /// A short name for this layer, used in logs and metrics.
+    fn name(&self) -> &'static str;
+
+    /// Inspect a call, returning an error status if it should be rejected.
+    /// The status is sent to the client as is.
+    fn intercept(&self, call: &InterceptedCall) -> Result<(), RpcStatus>;
+}
+
+/// Rejects requests whose chain-id header does not match the server's.
+pub struct ChainIdInterceptor {
+    chain_id: String,
+}
+
+impl ChainIdInterceptor {
+    /// Create a new chain id interceptor for the given server chain id.
+    pub fn new(chain_id: impl Into<String>) -> Self {
+        Self {
+            chain_id: chain_id.into(),
+        }
+    }
+}
+
+impl Interceptor for ChainIdInterceptor {
+    fn name(&self) -> &'static str {
+        "chain_id"
+    }
+
+    fn intercept(&self, call: &InterceptedCall) -> Result<(), RpcStatus> {
+        check_chain_id_metadata(&self.chain_id, call.headers)
+    }
+}
+
+/// Rejects requests which fail authentication.
+pub struct AuthInterceptor {
+    authenticator: Arc<dyn Authenticator + Send + Sync>,
+}
+
+impl AuthInterceptor {
+    /// Create a new auth interceptor around an authenticator.
+    pub fn new(authenticator: Arc<dyn Authenticator + Send + Sync>) -> Self {
+        Self { authenticator }
+    }
+}
+
+impl Interceptor for AuthInterceptor {
+    fn name(&self) -> &'static str {
+        "auth"
+    }
+
+    fn intercept(&self, call: &InterceptedCall) -> Result<(), RpcStatus> {
+        self.authenticator
+            .authenticate_metadata(call.headers)
+            .map(|_| ())
+            .or_else(|err| err.into())
+    }
+}
+
+/// Logs every request reaching this layer at debug level.
+pub struct LoggingInterceptor {
+    logger: Logger,
+}
+
+impl LoggingInterceptor {
+    /// Create a new logging interceptor.
+    pub fn new(logger: Logger) -> Self {
+        Self { logger }
+    }
+}
+
+impl Interceptor for LoggingInterceptor {
+    fn name(&self) -> &'static str {
+        "logging"
+    }
+
+    fn intercept(&self, call: &InterceptedCall) -> Result<(), RpcStatus> {
+        log::debug!(
+            self.logger,
+            "Incoming request: {} from {}",
+            call.method,
+            call.peer
+        );
+        Ok(())
+    }
+}
+
+/// A token bucket limiting the rate of requests accepted by a server.
+///
+/// The limit is global to the chain it is installed in, not per-client.
+pub struct RateLimitInterceptor {
+    max_per_second: u32,
+    state: Mutex<TokenBucket>,
+}
+
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimitInterceptor {
+    /// Create a new rate limiter allowing up to `max_per_second` requests per
+    /// second, with bursts of the same size.
+    pub fn new(max_per_second: u32) -> Self {
+        Self {
+            max_per_second,
+            state: Mutex::new(TokenBucket {
+                tokens: max_per_second as f64,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    fn try_acquire(&self, now: Instant) -> bool {
+        let mut bucket = self.state.lock().expect("mutex poisoned");
+        let elapsed = now.saturating_duration_since(bucket.last_refill);
+        let capacity = self.max_per_second as f64;
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * capacity).min(capacity);
+        bucket.last_refill = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl Interceptor for RateLimitInterceptor {
+    fn name(&self) -> &'static str {
+        "rate_limit"
+    }
+
+    fn intercept(&self, _call: &InterceptedCall) -> Result<(), RpcStatus> {
+        if self.try_acquire(Instant::now()) {
+            Ok(())
+        } else {
+            Err(RpcStatus::with_message(
+                RpcStatusCode::RESOURCE_EXHAUSTED,
+                format!(
+                    "Rate limit of {} requests per second exceeded",
+                    self.max_per_second
+                ),
+            ))
+        }
+    }
+}
+
+/// Operator-facing configuration of the optional interceptor layers.
+///
+/// Authentication is not configured here: it is enabled whenever the server
+/// is given a client auth token secret.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Parser, Serialize)]
+pub struct InterceptorConfig {
+    /// Disable rejecting requests whose chain-id header does not match this
+    /// server's chain id.
+    #[clap(long, env = "MC_GRPC_DISABLE_CHAIN_ID_CHECK")]
+    pub grpc_disable_chain_id_check: bool,
+
+    /// Log every incoming request at debug level.
+    #[clap(long, env = "MC_GRPC_REQUEST_LOGGING")]
+    pub grpc_request_logging: bool,
+
+    /// Maximum number of requests per second accepted by the server's
+    /// services. Unlimited when not set.
+    #[clap(long, env = "MC_GRPC_RATE_LIMIT")]
+    pub grpc_rate_limit: Option<u32>,
+}
+
+/// An ordered list of interceptors, run until one rejects the request.
+#[derive(Clone, Default)]
+pub struct InterceptorChain {
+    layers: Vec<Arc<dyn Interceptor>>,
+}
+
+impl InterceptorChain {
+    /// Create an empty chain, which accepts every request.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build the standard chain used by fog servers.
+    ///
+    /// Layers run in the order: logging, rate limit, chain id, auth, so that
+    /// cheap checks happen before authentication.
+    pub fn from_config(
+        config: &InterceptorConfig,
+        chain_id: &str,
+        authenticator: Arc<dyn Authenticator + Send + Sync>,
+        logger: Logger,
+    ) -> Self {
+        let mut chain = Self::new();
+        if config.grpc_request_logging {
+            chain = chain.with(LoggingInterceptor::new(logger));
+        }
+        if let Some(max_per_second) = config.grpc_rate_limit {
+            chain = chain.with(RateLimitInterceptor::new(max_per_second));
+        }
+        if !config.grpc_disable_chain_id_check {
+            chain = chain.with(ChainIdInterceptor::new(chain_id));
+        }
+        chain.with(AuthInterceptor::new(authenticator))
+    }
+
+    /// Append a layer to the end of the chain.
+    #[must_use]
+    pub fn with(mut self, interceptor: impl Interceptor + 'static) -> Self {
+        self.layers.push(Arc::new(interceptor));
+        self
+    }
+
+    /// Append the layers of another chain to the end of this one.
+    #[must_use]
+    pub fn then(mut self, rest: InterceptorChain) -> Self {
+        self.layers.extend(rest.layers);
+        self
+    }
+
+    /// The names of the layers in this chain, in order.
+    pub fn layer_names(&self) -> Vec<&'static str> {
+        self.layers.iter().map(|layer| layer.name()).collect()
+    }
+
+    /// Run the chain against an incoming call.
+    pub fn check(&self, ctx: &RpcContext) -> Result<(), RpcStatus> {
+        self.check_call(&InterceptedCall {
+            method: &ServiceMetrics::get_method_name(ctx),
+            peer: &ctx.peer(),
+            headers: ctx.request_headers(),
+        })
+    }
+
+    /// Run the chain against the parts of a call interceptors see.
+    pub fn check_call(&self, call: &InterceptedCall) -> Result<(), RpcStatus> {
+        for layer in self.layers.iter() {
+            if let Err(status) = layer.intercept(call) {
+                INTERCEPTOR_COUNTERS.inc(layer.name());
+                return Err(status);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for InterceptorChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.layer_names()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        AnonymousAuthenticator, TokenAuthenticator, TokenBasicCredentialsGenerator,
+        CHAIN_ID_GRPC_HEADER,
+    };
+    use grpcio::MetadataBuilder;
+    use mc_common::{logger::test_with_logger, time::SystemTimeProvider};
+    use std::time::Duration;
+
+    fn check(chain: &InterceptorChain, headers: &Metadata) -> Result<(), RpcStatus> {
+        chain.check_call(&InterceptedCall {
+            method: "test.method",
+            peer: "ipv4:127.0.0.1:5123",
+            headers,
+        })
+    }
+
+    fn headers_with_chain_id(chain_id: &str) -> Metadata {
+        let mut builder = MetadataBuilder::new();
+        builder.add_str(CHAIN_ID_GRPC_HEADER, chain_id).unwrap();
+        builder.build()
+    }
+
+    #[test_with_logger]
+    fn default_chain_checks_chain_id_and_auth(logger: Logger) {
+        let chain = InterceptorChain::from_config(
+            &InterceptorConfig::default(),
+            "local",
+            Arc::new(AnonymousAuthenticator),
+            logger,
+        );
+        assert_eq!(chain.layer_names(), vec!["chain_id", "auth"]);
+
+        assert!(check(&chain, &headers_with_chain_id("local")).is_ok());
+        let err = check(&chain, &headers_with_chain_id("main")).unwrap_err();
+        assert_eq!(err.code(), RpcStatusCode::FAILED_PRECONDITION);
+    }
+
+    #[test_with_logger]
+    fn disabled_chain_id_check_is_skipped(logger: Logger) {
+        let config = InterceptorConfig {
+            grpc_disable_chain_id_check: true,
+            grpc_request_logging: true,
+            grpc_rate_limit: Some(10),
+        };
+        let chain = InterceptorChain::from_config(
+            &config,
+            "local",
+            Arc::new(AnonymousAuthenticator),
+            logger,
+        );
+        assert_eq!(chain.layer_names(), vec!["logging", "rate_limit", "auth"]);
+
+        assert!(check(&chain, &headers_with_chain_id("main")).is_ok());
+    }
+
+    #[test_with_logger]
+    fn auth_layer_rejects_missing_credentials(logger: Logger) {
+        let shared_secret = [7; 32];
+        let authenticator = Arc::new(TokenAuthenticator::new(
+            shared_secret,
+            Duration::from_secs(60),
+            SystemTimeProvider,
+        ));
+        let chain = InterceptorChain::from_config(
+            &InterceptorConfig::default(),
+            "local",
+            authenticator,
+            logger,
+        );
+
+        let err = check(&chain, &headers_with_chain_id("local")).unwrap_err();
+        assert_eq!(err.code(), RpcStatusCode::UNAUTHENTICATED);
+
+        let creds = TokenBasicCredentialsGenerator::new(shared_secret, SystemTimeProvider)
+            .generate_for("user")
+            .unwrap();
+        let mut builder = MetadataBuilder::new();
+        builder
+            .add_str("authorization", &creds.authorization_header())
+            .unwrap();
+        assert!(check(&chain, &builder.build()).is_ok());
+    }
+
+    #[test]
+    fn joined_chains_keep_their_order() {
+        let chain = InterceptorChain::new()
+            .with(RateLimitInterceptor::new(1))
+            .then(InterceptorChain::new().with(ChainIdInterceptor::new("local")));
+        assert_eq!(chain.layer_names(), vec!["rate_limit", "chain_id"]);
+
+        let headers = headers_with_chain_id("local");
+        assert!(check(&chain, &headers).is_ok());
+        let err = check(&chain, &headers).unwrap_err();
+        assert_eq!(err.code(), RpcStatusCode::RESOURCE_EXHAUSTED);
+    }
+
+    #[test]
+    fn peers_are_parsed() {
+        let headers = MetadataBuilder::new().build();
+        let peer_ip = |peer| {
+            InterceptedCall {
+                method: "",
+                peer,
+                headers: &headers,
+            }
+            .peer_ip()
+        };
+        assert_eq!(
+            peer_ip("ipv4:10.0.0.1:5123"),
+            Some("10.0.0.1".parse().unwrap())
+        );
+        assert_eq!(
+            peer_ip("ipv6:[2001:db8::1]:443"),
+            Some("2001:db8::1".parse().unwrap())
+        );
+        assert_eq!(
+            peer_ip("ipv6:[::ffff:10.0.0.1]:443"),
+            Some("10.0.0.1".parse().unwrap())
+        );
+        assert_eq!(peer_ip("unix:/tmp/router.sock"), None);
+    }
+
+    #[test]
+    fn rate_limit_refills_over_time() {
+        let limiter = RateLimitInterceptor::new(2);
+        let start = Instant::now();
+        assert!(limiter.try_acquire(start));
+        assert!(limiter.try_acquire(start));
+        assert!(!limiter.try_acquire(start));
+        assert!(limiter.try_acquire(start + Duration::from_millis(500)));
+        assert!(!limiter.try_acquire(start + Duration::from_millis(500)));
+    }
+}
@@ -22,18 +22,23 @@ pub use autogenerated_code::*;
 
 mod admin_server;
 mod admin_service;
+mod audit_log;
 mod auth;
 mod build_info_service;
 mod chain_id;
 mod cookie_helper;
 mod grpcio_extensions;
 mod health_service;
+mod in_flight;
+mod interceptor;
+mod message_size;
 mod retry_config;
 mod server_cert_reloader;
 
 pub use crate::{
     admin_server::AdminServer,
     admin_service::{AdminService, GetConfigJsonFn},
+    audit_log::{record_audit_event, AuditEvent, AuditLog, AuditLogConfig},
     auth::{
         AnonymousAuthenticator, Authenticator, AuthenticatorError, AuthorizationHeaderError,
         BasicCredentials, TokenAuthenticator, TokenBasicCredentialsGenerator,
@@ -41,10 +46,19 @@ pub use crate::{
     },
     autogenerated_code::*,
     build_info_service::BuildInfoService,
-    chain_id::{check_request_chain_id, CHAIN_ID_GRPC_HEADER, CHAIN_ID_MISMATCH_ERR_MSG},
+    chain_id::{
+        check_chain_id_metadata, check_request_chain_id, CHAIN_ID_GRPC_HEADER,
+        CHAIN_ID_MISMATCH_ERR_MSG,
+    },
     cookie_helper::{Error as CookieError, GrpcCookieStore},
     grpcio_extensions::{ConnectionUriGrpcioChannel, ConnectionUriGrpcioServer},
     health_service::{HealthCheckStatus, HealthService, ReadinessIndicator},
+    in_flight::{InFlightRequest, InFlightRequestInfo, InFlightRequests},
+    interceptor::{
+        AuthInterceptor, ChainIdInterceptor, InterceptedCall, Interceptor, InterceptorChain,
+        InterceptorConfig, LoggingInterceptor, RateLimitInterceptor,
+    },
+    message_size::{MessageDirection, MessageSizeConfig, MessageTooLarge},
     retry_config::GrpcRetryConfig,
     server_cert_reloader::{ServerCertReloader, ServerCertReloaderError},
 };
@@ -291,17 +305,17 @@ pub fn decode_to_rpc_err(error: mc_util_serial::DecodeError, logger: &Logger) ->
 /// Handles a bunch of grpc boilerplate that was being copy pasted
 use grpcio::{Server, Service};
 
-/// Build and start a server composed of several services
+/// Build and start a server composed of several services, listening on the
+/// host and port of `uri` exactly as given.
 #[inline]
 pub fn run_server(
     env: std::sync::Arc<grpcio::Environment>,
     services: Vec<Service>,
-    port: u16,
+    uri: &impl mc_util_uri::ConnectionUri,
     logger: &Logger,
 ) -> Server {
     use grpcio::ServerBuilder;
 
-    // FIXME: This should default to localhost and you should have to provide the IP
     let mut server_builder = ServerBuilder::new(env);
 
     for service in services {
@@ -310,12 +324,13 @@ pub fn run_server(
 
     let mut server = server_builder.build().expect("Could not build server");
 
-    let port = server
-        .add_listening_port(format!("0.0.0.0:{port}"), ServerCredentials::insecure())
+    let listen_addr = uri.addr();
+    server
+        .add_listening_port(&listen_addr, ServerCredentials::insecure())
         .expect("Could not create anonymous bind");
     server.start();
 
-    log::info!(logger, "API listening on 0.0.0.0:{port}");
+    log::info!(logger, "API listening on {}", listen_addr);
 
     server
 }
@@ -0,0 +1,176 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Limits on the size of gRPC messages, and a typed error for exceeding them.
+//!
+//! grpc core enforces the limits and reports a violation as a
+//! `RESOURCE_EXHAUSTED` status, with a message giving the size of the message
+//! and the limit. [MessageTooLarge] parses that message, so that clients can
+//! tell an oversized request or response apart from other resource
+//! exhaustion, such as rate limiting, and report both numbers.
+
+use clap::Parser;
+use displaydoc::Display;
+use grpcio::{ChannelBuilder, Environment, RpcStatus, RpcStatusCode, ServerBuilder};
+use serde::Serialize;
+use std::sync::Arc;
+
+/// Operator-facing configuration of the gRPC message size limits of a server.
+///
+/// Unset limits keep the grpc core defaults: 4 MiB for received messages and
+/// unlimited for sent messages.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Parser, Serialize)]
+pub struct MessageSizeConfig {
+    /// Largest request, in bytes, that the server accepts.
+    #[clap(long, env = "MC_GRPC_MAX_RECEIVE_MESSAGE_BYTES")]
+    pub grpc_max_receive_message_bytes: Option<i32>,
+
+    /// Largest response, in bytes, that the server sends.
+    #[clap(long, env = "MC_GRPC_MAX_SEND_MESSAGE_BYTES")]
+    pub grpc_max_send_message_bytes: Option<i32>,
+}
+
+impl MessageSizeConfig {
+    /// Apply the limits to a channel builder.
+    pub fn apply_to_channel(&self, mut builder: ChannelBuilder) -> ChannelBuilder {
+        if let Some(limit) = self.grpc_max_receive_message_bytes {
+            builder = builder.max_receive_message_len(limit);
+        }
+        if let Some(limit) = self.grpc_max_send_message_bytes {
+            builder = builder.max_send_message_len(limit);
+        }
+        builder
+    }
+
+    /// Apply the limits to a server builder.
+    ///
+    /// This replaces any channel args set on the builder before.
+    #[must_use]
+    pub fn apply_to_server(&self, builder: ServerBuilder, env: Arc<Environment>) -> ServerBuilder {
+        builder.channel_args(self.apply_to_channel(ChannelBuilder::new(env)).build_args())
+    }
+}
+
+/// Whether an oversized message was being received or sent.
+#[derive(Clone, Copy, Debug, Display, Eq, PartialEq)]
+pub enum MessageDirection {
+    /// Received
+    Received,
+    /// Sent
+    Sent,
+}
+
+/// {direction} message of {size} bytes exceeds the limit of {limit} bytes
+#[derive(Clone, Copy, Debug, Display, Eq, PartialEq)]
+pub struct MessageTooLarge {
+    /// Whether the message was being received or sent, by whichever side
+    /// rejected it.
+    pub direction: MessageDirection,
+
+    /// The size of the message, in bytes.
+    pub size: u64,
+
+    /// The limit it exceeded, in bytes.
+    pub limit: u64,
+}
+
+impl MessageTooLarge {
+    /// Recognize a message size violation in a status returned by grpc.
+    pub fn from_rpc_status(status: &RpcStatus) -> Option<Self> {
+        if status.code() != RpcStatusCode::RESOURCE_EXHAUSTED {
+            return None;
+        }
+        Self::parse(status.message())
+    }
+
+    /// Recognize a message size violation in an error returned by grpc.
+    pub fn from_grpc_error(err: &grpcio::Error) -> Option<Self> {
+        match err {
+            grpcio::Error::RpcFailure(status) => Self::from_rpc_status(status),
+            _ => None,
+        }
+    }
+
+    /// A status reporting this violation, in the same form as grpc core.
+    pub fn to_rpc_status(&self) -> RpcStatus {
+        RpcStatus::with_message(
+            RpcStatusCode::RESOURCE_EXHAUSTED,
+            format!(
+                "{} message larger than max ({} vs. {})",
+                self.direction, self.size, self.limit
+            ),
+        )
+    }
+
+    /// Parse grpc core's "Received message larger than max (size vs. limit)"
+    /// message, or the "Sent" equivalent.
+    fn parse(message: &str) -> Option<Self> {
+        let (direction, rest) = if let Some(rest) = message.strip_prefix("Received ") {
+            (MessageDirection::Received, rest)
+        } else if let Some(rest) = message.strip_prefix("Sent ") {
+            (MessageDirection::Sent, rest)
+        } else {
+            return None;
+        };
+        let numbers = rest
+            .strip_prefix("message larger than max (")?
+            .strip_suffix(')')?;
+        let (size, limit) = numbers.split_once(" vs. ")?;
+        Some(Self {
+            direction,
+            size: size.parse().ok()?,
+            limit: limit.parse().ok()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_grpc_core_statuses() {
+        let status = RpcStatus::with_message(
+            RpcStatusCode::RESOURCE_EXHAUSTED,
+            "Received message larger than max (5242880 vs. 4194304)".to_owned(),
+        );
+        let expected = MessageTooLarge {
+            direction: MessageDirection::Received,
+            size: 5242880,
+            limit: 4194304,
+        };
+        assert_eq!(MessageTooLarge::from_rpc_status(&status), Some(expected));
+        assert_eq!(
+            MessageTooLarge::from_grpc_error(&grpcio::Error::RpcFailure(status)),
+            Some(expected)
+        );
+
+        let sent = MessageTooLarge {
+            direction: MessageDirection::Sent,
+            size: 10,
+            limit: 5,
+        };
+        assert_eq!(
+            MessageTooLarge::from_rpc_status(&sent.to_rpc_status()),
+            Some(sent)
+        );
+        assert_eq!(
+            sent.to_string(),
+            "Sent message of 10 bytes exceeds the limit of 5 bytes"
+        );
+    }
+
+    #[test]
+    fn ignores_other_statuses() {
+        let rate_limited = RpcStatus::with_message(
+            RpcStatusCode::RESOURCE_EXHAUSTED,
+            "Rate limit of 10 requests per second exceeded".to_owned(),
+        );
+        assert_eq!(MessageTooLarge::from_rpc_status(&rate_limited), None);
+
+        let wrong_code = RpcStatus::with_message(
+            RpcStatusCode::INTERNAL,
+            "Received message larger than max (5 vs. 4)".to_owned(),
+        );
+        assert_eq!(MessageTooLarge::from_rpc_status(&wrong_code), None);
+    }
+}
@@ -45,4 +45,14 @@ impl GrpcRetryConfig {
     {
         retry::retry(self.get_retry_iterator(), operation)
     }
+
+    /// Retry an operation using this retry config, passing it the number of
+    /// the attempt, starting from 1
+    pub fn retry_with_index<O, R, E, OR>(&self, operation: O) -> Result<R, retry::Error<E>>
+    where
+        O: FnMut(u64) -> OR,
+        OR: Into<retry::OperationResult<R, E>>,
+    {
+        retry::retry_with_index(self.get_retry_iterator(), operation)
+    }
 }
@@ -1,8 +1,16 @@
 // Copyright (c) 2018-2022 The MobileCoin Foundation
 
 //! A `grpcio::ServerCredentialsFetcher` implementation that reloads a GRPC's
-//! server TLS certificate/key when a SIGHUP is received.
-
+//! server TLS certificate/key when a SIGHUP is received, or when the files
+//! change.
+//!
+//! grpc asks the fetcher for credentials whenever it starts a TLS handshake,
+//! so new connections use the new certificate while existing connections are
+//! left alone, and certificate renewals don't need a restart. The files are
+//! checked for changes by their modification time and size, so whatever renews
+//! the certificate doesn't need to signal the server.
+
+use crate::{record_audit_event, AuditEvent};
 use displaydoc::Display;
 use grpcio::{CertificateRequestType, ServerCredentialsBuilder, ServerCredentialsFetcher};
 use mc_common::logger::{log, Logger};
@@ -12,8 +20,9 @@ use std::{
     path::{Path, PathBuf},
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc,
+        Arc, Mutex,
     },
+    time::SystemTime,
 };
 
 /// The `grpcio::ServerCredentialsFetcher` demands a root certificate for
@@ -73,8 +82,26 @@ impl From<io::Error> for ServerCertReloaderError {
     }
 }
 
+/// The modification time and size of a file, used to notice that it changed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct FileStamp {
+    modified: SystemTime,
+    len: u64,
+}
+
+impl FileStamp {
+    fn read(path: &Path) -> io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        Ok(Self {
+            modified: metadata.modified()?,
+            len: metadata.len(),
+        })
+    }
+}
+
 /// A `grpcio::ServerCredentialsFetcher` implementation that reloads a GRPC's
-/// server TLS certificate/key when a SIGHUP is received.
+/// server TLS certificate/key when a SIGHUP is received, or when the files
+/// change.
 pub struct ServerCertReloader {
     /// Certificate file to watch.
     cert_file: PathBuf,
@@ -85,6 +112,9 @@ pub struct ServerCertReloader {
     /// Signal that we need to re-load the certificate/key files.
     load_needed: Arc<AtomicBool>,
 
+    /// The stamps of the certificate/key files when they were last loaded.
+    loaded_stamps: Mutex<Option<(FileStamp, FileStamp)>>,
+
     /// Logger.
     logger: Logger,
 }
@@ -104,21 +134,47 @@ impl ServerCertReloader {
             cert_file: cert_file.as_ref().to_path_buf(),
             key_file: key_file.as_ref().to_path_buf(),
             load_needed,
+            loaded_stamps: Mutex::new(None),
             logger,
         })
     }
+
+    /// Read the current stamps of the certificate/key files, and whether they
+    /// differ from the ones last loaded.
+    fn files_changed(&self) -> (Option<(FileStamp, FileStamp)>, bool) {
+        let stamps = FileStamp::read(&self.cert_file)
+            .and_then(|cert| Ok((cert, FileStamp::read(&self.key_file)?)))
+            .ok();
+        let changed =
+            stamps.is_some() && stamps != *self.loaded_stamps.lock().expect("mutex poisoned");
+        (stamps, changed)
+    }
 }
 
 impl ServerCredentialsFetcher for ServerCertReloader {
     fn fetch(&self) -> Result<Option<ServerCredentialsBuilder>, Box<dyn std::error::Error>> {
-        if !self.load_needed.load(Ordering::SeqCst) {
+        let (stamps, files_changed) = self.files_changed();
+        if !self.load_needed.load(Ordering::SeqCst) && !files_changed {
             return Ok(None);
         }
 
         log::info!(self.logger, "Loading certificates");
 
-        let crt = fs::read_to_string(&self.cert_file)?;
-        let key = fs::read_to_string(&self.key_file)?;
+        let (crt, key) = match fs::read_to_string(&self.cert_file)
+            .and_then(|crt| Ok((crt, fs::read_to_string(&self.key_file)?)))
+        {
+            Ok(crt_and_key) => crt_and_key,
+            Err(err) => {
+                record_audit_event(
+                    AuditEvent::TlsCertReloadFailed {
+                        cert_file: self.cert_file.clone(),
+                        error: err.to_string(),
+                    },
+                    &self.logger,
+                );
+                return Err(err.into());
+            }
+        };
 
         let new_cred = ServerCredentialsBuilder::new()
             // This sets the client root certificate to verify client's identity.
@@ -132,6 +188,13 @@ impl ServerCredentialsFetcher for ServerCertReloader {
             .add_cert(crt.into(), key.into());
 
         self.load_needed.store(false, Ordering::SeqCst);
+        *self.loaded_stamps.lock().expect("mutex poisoned") = stamps;
+        record_audit_event(
+            AuditEvent::TlsCertReloaded {
+                cert_file: self.cert_file.clone(),
+            },
+            &self.logger,
+        );
         Ok(Some(new_cred))
     }
 }
@@ -269,6 +332,46 @@ mod tests {
         assert_eq!(reply.get_data(), vec![1, 2, 3]);
     }
 
+    #[test_with_logger]
+    fn test_reload_on_file_change(logger: Logger) {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let cert_file = temp_dir.path().join("server.crt");
+        let key_file = temp_dir.path().join("server.key");
+
+        // Load test certs and keys
+        let (server1_cert, server1_key) = ok_self_signed_1();
+        let (server2_cert, server2_key) = ok_self_signed_2();
+
+        // Write server1's cert files into the temp dir.
+        std::fs::write(&cert_file, &server1_cert).unwrap();
+        std::fs::write(&key_file, server1_key).unwrap();
+
+        // Start the GRPC server.
+        let (_server, port) = create_test_server(&cert_file, &key_file, logger);
+
+        let client1 = create_test_client(&server1_cert, "www.server1.com", port);
+        let mut req = PingRequest::default();
+        req.set_data(vec![1, 2, 3]);
+        let reply = client1.ping(&req).expect("rpc");
+        assert_eq!(reply.get_data(), vec![1, 2, 3]);
+
+        // Replace server1 certificates with server2, without sending a SIGHUP.
+        std::fs::write(&cert_file, &server2_cert).unwrap();
+        std::fs::write(&key_file, server2_key).unwrap();
+
+        // A new connection should pick up the new certificate.
+        let client2 = create_test_client(&server2_cert, "www.server2.com", port);
+        let mut req = PingRequest::default();
+        req.set_data(vec![1, 2, 3]);
+        let reply = client2.ping(&req).expect("rpc");
+        assert_eq!(reply.get_data(), vec![1, 2, 3]);
+
+        // The existing connection should be left alone.
+        req.set_data(vec![5, 6, 7]);
+        let reply = client1.ping(&req).expect("rpc");
+        assert_eq!(reply.get_data(), vec![5, 6, 7]);
+    }
+
     #[test_with_logger]
     fn test_reload_invalid_data(logger: Logger) {
         let temp_dir = tempfile::TempDir::new().unwrap();
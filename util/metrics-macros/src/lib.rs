@@ -0,0 +1,110 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Macros which record `mc_util_metrics::ServiceMetrics` for the methods of a
+//! gRPC service.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{
+    parse_macro_input, parse_quote, visit_mut::VisitMut, Expr, FnArg, Ident, ImplItem, ItemImpl,
+    Pat, Signature, Type,
+};
+
+/// Record the rate, errors and duration of each method of a gRPC service
+/// impl, in the given `ServiceMetrics`.
+///
+/// This goes on the `impl` of a grpcio service trait:
+///
+/// ```ignore
+/// #[service_metrics(SVC_COUNTERS)]
+/// impl FogBlockApi for BlockService {
+///     fn get_blocks(&mut self, ctx: RpcContext, ...) {
+///         ...
+///         send_result(ctx, sink, result, logger)
+///     }
+/// }
+/// ```
+///
+/// For each method taking an `RpcContext`, the request is counted and timed
+/// for as long as the method runs, and the result passed to each
+/// `send_result` call in the method is counted by its status code.
+/// Methods which send their results some other way only get the request
+/// counts and durations.
+#[proc_macro_attribute]
+pub fn service_metrics(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let metrics = parse_macro_input!(attr as Expr);
+    let mut item_impl = parse_macro_input!(item as ItemImpl);
+
+    for item in item_impl.items.iter_mut() {
+        let ImplItem::Fn(method) = item else {
+            continue;
+        };
+        let Some(ctx) = rpc_context_arg(&method.sig) else {
+            continue;
+        };
+
+        SendResultMetrics { metrics: &metrics }.visit_block_mut(&mut method.block);
+
+        let block = &method.block;
+        method.block = parse_quote! {{
+            let _timer = #metrics.req(&#ctx);
+            #block
+        }};
+    }
+
+    quote!(#item_impl).into()
+}
+
+/// The name of the `RpcContext` argument of a method, if it has one.
+fn rpc_context_arg(sig: &Signature) -> Option<Ident> {
+    sig.inputs.iter().find_map(|arg| {
+        let FnArg::Typed(arg) = arg else {
+            return None;
+        };
+        let (Pat::Ident(pat), Type::Path(ty)) = (&*arg.pat, &*arg.ty) else {
+            return None;
+        };
+        let segment = ty.path.segments.last()?;
+        (segment.ident == "RpcContext").then(|| pat.ident.clone())
+    })
+}
+
+/// Rewrites `send_result(ctx, sink, resp, logger)` calls to record `resp` in
+/// the metrics before sending it.
+struct SendResultMetrics<'a> {
+    metrics: &'a Expr,
+}
+
+impl VisitMut for SendResultMetrics<'_> {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        syn::visit_mut::visit_expr_mut(self, expr);
+
+        let Expr::Call(call) = expr else {
+            return;
+        };
+        let Expr::Path(func) = &*call.func else {
+            return;
+        };
+        let is_send_result = func
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "send_result");
+        if !is_send_result || call.args.len() != 4 {
+            return;
+        }
+
+        let metrics = self.metrics;
+        let args = call.args.iter();
+        *expr = parse_quote! {
+            match (#(#args),*) {
+                (__ctx, __sink, __resp, __logger) => {
+                    #metrics.result(&__ctx, &__resp);
+                    #func(__ctx, __sink, __resp, __logger)
+                }
+            }
+        };
+    }
+}
@@ -13,6 +13,8 @@ mod op_counters;
 mod service_metrics;
 
 pub use json_encoder::JsonEncoder as MetricsJsonEncoder;
+#[cfg(feature = "service_metrics")]
+pub use mc_util_metrics_macros::service_metrics;
 pub use op_counters::OpMetrics;
 pub use prometheus::{
     core::{Collector, Desc},
@@ -26,9 +26,20 @@ fn sample_service_method(ctx: RpcContext, params: Params) {
   // do business logic
   metrics.resp(&ctx, success_flag);
 }
+
+The `service_metrics` attribute does this for each method of a service impl,
+recording the results passed to `send_result`:
+
+#[service_metrics(SVC_COUNTERS)]
+impl SampleApi for SampleService {
+  fn sample_service_method(&mut self, ctx: RpcContext, params: Params, sink: UnarySink<Response>) {
+    // do business logic
+    send_result(ctx, sink, result, &logger)
+  }
+}
 */
 
-use grpcio::{RpcContext, RpcStatusCode};
+use grpcio::{RpcContext, RpcStatus, RpcStatusCode};
 use mc_common::logger::global_log;
 use prometheus::{
     core::{Collector, Desc},
@@ -198,6 +209,19 @@ impl ServiceMetrics {
             .inc();
     }
 
+    /// Takes the RpcContext used during a gRPC method call to get the method
+    /// name, and increments the error and status code counters for the result
+    /// the method is about to send
+    pub fn result<T>(&self, ctx: &RpcContext, resp: &Result<T, RpcStatus>) {
+        let method_name = Self::get_method_name(ctx);
+        let code = match resp {
+            Ok(_) => RpcStatusCode::OK,
+            Err(status) => status.code(),
+        };
+        self.resp_impl(&method_name, resp.is_ok());
+        self.status_code_impl(&method_name, code);
+    }
+
     /// Tracks gRPC message name and size for aggregation into a Prometheus
     /// histogram
     pub fn message<M: Message>(&self, message: &M) {
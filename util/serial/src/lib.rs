@@ -3,7 +3,7 @@
 #![no_std]
 
 extern crate alloc;
-use alloc::vec::Vec;
+use alloc::{vec, vec::Vec};
 
 pub extern crate prost;
 
@@ -81,6 +81,46 @@ pub fn decode<T: Message + Default>(buf: &[u8]) -> Result<T, DecodeError> {
     T::decode(buf)
 }
 
+/// The field number of the padding [pad_encoded] appends. This is the largest
+/// valid protobuf field number, which no message should use.
+pub const PADDING_FIELD_TAG: u32 = (1 << 29) - 1;
+
+/// Append an unknown bytes field of zeroes to an encoded protobuf message, so
+/// that its length is the smallest multiple of `block_size` which can be
+/// reached given the overhead of encoding the field. Decoders skip unknown
+/// fields, so the result decodes to the same message, and the padding is only
+/// visible as length. A block size of zero disables padding.
+pub fn pad_encoded(bytes: &mut Vec<u8>, block_size: usize) {
+    if block_size == 0 {
+        return;
+    }
+    let unpadded_len = bytes.len();
+    let key_len = prost::encoding::key_len(PADDING_FIELD_TAG);
+    let mut target = unpadded_len.next_multiple_of(block_size);
+    if target == unpadded_len {
+        return;
+    }
+    let padding_len = loop {
+        if let Some(len) = target
+            .checked_sub(unpadded_len + key_len)
+            .and_then(padding_field_len)
+        {
+            break len;
+        }
+        target += block_size;
+    };
+    prost::encoding::bytes::encode(PADDING_FIELD_TAG, &vec![0u8; padding_len], bytes);
+}
+
+/// Find the length of a bytes field whose contents and length prefix together
+/// take exactly `available` bytes, if there is one. Empty fields are not
+/// encoded, so the length must be nonzero.
+pub fn padding_field_len(available: usize) -> Option<usize> {
+    (available.saturating_sub(10).max(1)..=available)
+        .rev()
+        .find(|len| len + prost::encoding::encoded_len_varint(*len as u64) == available)
+}
+
 #[cfg(feature = "serde_with")]
 mod json_u64 {
     use super::*;
@@ -210,4 +250,23 @@ mod test {
         let deserialized: TestStruct = deserialize(&serialized).unwrap();
         assert_eq!(deserialized, the_struct);
     }
+
+    #[derive(Clone, PartialEq, Message)]
+    struct TestMessage {
+        #[prost(bytes, tag = "1")]
+        data: Vec<u8>,
+    }
+
+    #[test]
+    fn test_pad_encoded() {
+        for data_len in 0..300 {
+            let message = TestMessage {
+                data: vec![7u8; data_len],
+            };
+            let mut bytes = encode(&message);
+            pad_encoded(&mut bytes, 64);
+            assert_eq!(bytes.len() % 64, 0, "data_len = {data_len}");
+            assert_eq!(decode::<TestMessage>(&bytes).unwrap(), message);
+        }
+    }
 }
@@ -254,6 +254,36 @@ mod consensus_client_uri_tests {
         assert_eq!(uri.password(), "def:1:2:3");
     }
 
+    #[test]
+    fn test_redacted() {
+        let uri = ClientUri::from_str(
+            "mc://abc:def@node1.test.mobilecoin.com/?responder-id=node1.test.mobilecoin.com:443&token=s3cret",
+        )
+        .unwrap();
+        let redacted = uri.redacted();
+        assert_eq!(
+            redacted,
+            "mc://REDACTED:REDACTED@node1.test.mobilecoin.com/?responder-id=node1.test.mobilecoin.com%3A443&token=REDACTED"
+        );
+        assert!(!format!("{uri:?}").contains("s3cret"));
+        assert!(!format!("{uri:?}").contains("def"));
+        assert!(!uri.to_string().contains("s3cret"));
+        assert!(!uri.to_string().contains("def"));
+
+        // Credentials are redacted even when only one of them is set.
+        let uri =
+            ClientUri::from_str("insecure-mc://:passw0rd@node1.test.mobilecoin.com:666/").unwrap();
+        assert_eq!(
+            uri.redacted(),
+            "insecure-mc://:REDACTED@node1.test.mobilecoin.com:666/"
+        );
+
+        // URIs without credentials are unchanged.
+        let uri =
+            ClientUri::from_str("mc://node1.test.mobilecoin.com/?tls-hostname=lol.com").unwrap();
+        assert_eq!(uri.redacted(), uri.url().as_str());
+    }
+
     #[test]
     fn test_client_from_peer_should_fail() {
         assert!(ClientUri::from_str("mcp://localhost:3223/").is_err());
@@ -386,6 +416,7 @@ mod fog_uri_tests {
     use super::{ConnectionUri, FogUri};
     use core::str::FromStr;
     use mc_common::ResponderId;
+    use std::net::{Ipv4Addr, Ipv6Addr};
 
     #[test]
     fn test_valid_client_uris() {
@@ -455,7 +486,47 @@ mod fog_uri_tests {
                 .unwrap()
                 .subdomain(),
             Some("node")
-        )
+        );
+        assert_eq!(
+            FogUri::from_str("fog://10.0.0.1/").unwrap().subdomain(),
+            None
+        );
+        assert_eq!(FogUri::from_str("fog://[::1]/").unwrap().subdomain(), None);
+    }
+
+    #[test]
+    fn test_ipv6_uris() {
+        let uri = FogUri::from_str("insecure-fog://[::1]:3223/").unwrap();
+        assert_eq!(uri.host(), "[::1]");
+        assert_eq!(uri.port(), 3223);
+        assert_eq!(uri.addr(), "[::1]:3223");
+        assert_eq!(uri.ip_addr(), Some(Ipv6Addr::LOCALHOST.into()));
+        assert_eq!(uri.to_string(), "insecure-fog://[::1]:3223/");
+        assert_eq!(
+            uri.responder_id().unwrap(),
+            ResponderId::from_str("[::1]:3223").unwrap()
+        );
+        assert_eq!(
+            FogUri::try_from_responder_id(uri.responder_id().unwrap(), false)
+                .unwrap()
+                .addr(),
+            "[::1]:3223"
+        );
+
+        // Addresses are normalized by the URL parser.
+        let uri = FogUri::from_str("fog://[fd00:0::0:1]/").unwrap();
+        assert_eq!(uri.addr(), "[fd00::1]:443");
+
+        let uri = FogUri::from_str("insecure-fog://127.0.0.1/").unwrap();
+        assert_eq!(uri.ip_addr(), Some(Ipv4Addr::LOCALHOST.into()));
+        let uri = FogUri::from_str("insecure-fog://0.0.0.0:3223/").unwrap();
+        assert_eq!(uri.addr(), "0.0.0.0:3223");
+        let uri = FogUri::from_str("insecure-fog://[::]:3223/").unwrap();
+        assert_eq!(uri.addr(), "[::]:3223");
+        let uri = FogUri::from_str("insecure-fog://localhost/").unwrap();
+        assert_eq!(uri.ip_addr(), None);
+
+        assert!(FogUri::from_str("insecure-fog://[::1/").is_err());
     }
 
     #[test]
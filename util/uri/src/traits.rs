@@ -9,8 +9,8 @@ use core::{
 use displaydoc::Display;
 use mc_common::{NodeID, ResponderId, ResponderIdParseError};
 use mc_crypto_keys::{DistinguishedEncoding, Ed25519Public, KeyError, SignatureError};
-use std::{path::PathBuf, str::FromStr};
-use url::Url;
+use std::{net::IpAddr, path::PathBuf, str::FromStr};
+use url::{Host, Url};
 
 /// Wrapper for errors that can occur during conversion to/from `Uri`
 #[derive(Debug, Display, Ord, PartialOrd, Eq, PartialEq, Clone)]
@@ -60,6 +60,23 @@ impl From<ResponderIdParseError> for UriConversionError {
     }
 }
 
+/// What credentials are replaced with by [ConnectionUri::redacted].
+const REDACTED: &str = "REDACTED";
+
+/// The query parameters that [ConnectionUri::redacted] leaves the values of
+/// in place, because they are known not to be secret.
+const NON_SECRET_PARAMS: &[&str] = &[
+    "broadcast-consensus-msgs",
+    "ca-bundle",
+    "consensus-msg-key",
+    "consensus-relay-incoming-txs",
+    "responder-id",
+    "sharding_strategy",
+    "tls-chain",
+    "tls-hostname",
+    "tls-key",
+];
+
 /// A base URI trait.
 pub trait ConnectionUri:
     Clone + Display + Eq + Hash + Ord + PartialEq + PartialOrd + Send + Sync
@@ -73,9 +90,22 @@ pub trait ConnectionUri:
     /// Retreive the port part of the URI.
     fn port(&self) -> u16;
 
-    /// Retrieve the host:port string for this connection.
+    /// Retrieve the host:port string for this connection. IPv6 hosts are
+    /// enclosed in brackets, e.g. `[::1]:3223`.
     fn addr(&self) -> String;
 
+    /// Retrieve the IP address of the host, if the host is an IP address
+    /// rather than a domain name.
+    fn ip_addr(&self) -> Option<IpAddr> {
+        match self.url().host()? {
+            Host::Ipv4(addr) => Some(IpAddr::V4(addr)),
+            Host::Ipv6(addr) => Some(IpAddr::V6(addr)),
+            // Our schemes are not special schemes as far as the URL parser is
+            // concerned, so it leaves IPv4 addresses as opaque hosts.
+            Host::Domain(domain) => domain.parse().ok(),
+        }
+    }
+
     /// Whether TLS should be used for this connection.
     fn use_tls(&self) -> bool;
 
@@ -87,6 +117,36 @@ pub trait ConnectionUri:
     /// available.
     fn password(&self) -> String;
 
+    /// Retrieve the URI in a form that is safe to log, with the username,
+    /// password, and the values of any query parameters that aren't known
+    /// to be safe replaced with `REDACTED`.
+    fn redacted(&self) -> String {
+        let mut url = self.url().clone();
+        // These only fail for URLs without a host, which we never construct.
+        if !url.username().is_empty() {
+            let _ = url.set_username(REDACTED);
+        }
+        if url.password().is_some() {
+            let _ = url.set_password(Some(REDACTED));
+        }
+        if url.query().is_some() {
+            let pairs = url.query_pairs().into_owned().collect::<Vec<_>>();
+            url.query_pairs_mut()
+                .clear()
+                .extend_pairs(pairs.iter().map(|(name, value)| {
+                    if NON_SECRET_PARAMS.contains(&name.as_str()) {
+                        (name.as_str(), value.as_str())
+                    } else {
+                        (name.as_str(), REDACTED)
+                    }
+                }));
+        }
+        if url.fragment().is_some() {
+            url.set_fragment(Some(REDACTED));
+        }
+        url.to_string()
+    }
+
     /// Retrieve the responder id for this connection.
     fn responder_id(&self) -> StdResult<ResponderId, UriConversionError> {
         let responder_id_string = self
@@ -169,7 +229,7 @@ pub trait ConnectionUri:
     /// Retrieve the TLS chain file path to use for this connection.
     fn tls_chain_path(&self) -> StdResult<String, String> {
         self.get_param("tls-chain")
-            .ok_or_else(|| format!("Missing tls-chain query parameter for {}", self.url()))
+            .ok_or_else(|| format!("Missing tls-chain query parameter for {}", self.redacted()))
     }
 
     /// Retrieve the TLS chain to use for this connection.
@@ -182,7 +242,7 @@ pub trait ConnectionUri:
     /// Retrieve the TLS key file path to use for this connection.
     fn tls_key_path(&self) -> StdResult<String, String> {
         self.get_param("tls-key")
-            .ok_or_else(|| format!("Missing tls-key query parameter for {}", self.url()))
+            .ok_or_else(|| format!("Missing tls-key query parameter for {}", self.redacted()))
     }
 
     /// Retrieve the TLS key to use for this connection.
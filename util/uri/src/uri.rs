@@ -5,7 +5,7 @@ use displaydoc::Display;
 use mc_common::ResponderId;
 use percent_encoding::percent_decode_str;
 use std::{
-    fmt::{Display, Formatter, Result as FmtResult},
+    fmt::{Debug, Display, Formatter, Result as FmtResult},
     marker::PhantomData,
     str::FromStr,
 };
@@ -27,12 +27,12 @@ pub enum UriParseError {
 impl std::error::Error for UriParseError {}
 
 /// Represents a URI with custom scheme validation and other helpers.
-#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct Uri<Scheme: UriScheme> {
     /// The original Url object used to construct this object.
     url: Url,
 
-    /// Hostname.
+    /// Hostname, with IPv6 addresses enclosed in brackets.
     host: String,
 
     /// Consensus port.
@@ -60,8 +60,11 @@ impl<Scheme: UriScheme> Uri<Scheme> {
         self.port = port;
     }
 
-    /// Extract the subdomain from url
+    /// Extract the subdomain from url. IP addresses have no subdomain.
     pub fn subdomain(&self) -> Option<&str> {
+        if self.ip_addr().is_some() {
+            return None;
+        }
         let host_str = self.url.host_str()?;
         host_str.split_once('.').map(|(first, _)| first)
     }
@@ -113,6 +116,8 @@ impl<Scheme: UriScheme> Uri<Scheme> {
     }
 }
 
+/// Only the scheme, host and port are shown, never credentials or query
+/// parameters. Use [ConnectionUri::redacted] when the full url is needed.
 impl<Scheme: UriScheme> Display for Uri<Scheme> {
     fn fmt(&self, f: &mut Formatter) -> FmtResult {
         let scheme = if self.use_tls {
@@ -124,6 +129,13 @@ impl<Scheme: UriScheme> Display for Uri<Scheme> {
     }
 }
 
+/// Only the redacted URI is shown, since the url may hold credentials.
+impl<Scheme: UriScheme> Debug for Uri<Scheme> {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        f.debug_tuple("Uri").field(&self.redacted()).finish()
+    }
+}
+
 impl<Scheme: UriScheme> FromStr for Uri<Scheme> {
     type Err = UriParseError;
 
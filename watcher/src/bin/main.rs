@@ -7,9 +7,10 @@
 use displaydoc::Display;
 use mc_watcher::{
     attestation_evidence_collector::AttestationEvidenceCollector,
-    config::WatcherConfig,
+    config::{WatcherCommand, WatcherConfig},
+    tcb_policy::{audit_block, TcbPolicy},
     watcher::{SyncResult, Watcher},
-    watcher_db::create_or_open_rw_watcher_db,
+    watcher_db::{create_or_open_rw_watcher_db, WatcherDB},
 };
 
 use clap::Parser;
@@ -17,6 +18,7 @@ use futures::executor::block_on;
 use grpcio::{EnvBuilder, ServerBuilder};
 use mc_common::logger::{create_app_logger, log, o, Logger};
 use mc_util_grpc::{ConnectionUriGrpcioServer, HealthCheckStatus, HealthService};
+use mc_util_repr_bytes::ReprBytes;
 use mc_util_uri::ConnectionUri;
 use std::{
     io::Error as IOError,
@@ -25,6 +27,7 @@ use std::{
         Arc,
     },
     thread::{sleep, Builder as ThreadBuilder, JoinHandle},
+    time::{Duration, Instant},
 };
 
 fn main() {
@@ -41,19 +44,65 @@ fn main() {
         logger.clone(),
     )
     .expect("Could not create or open watcher db");
+
+    match &config.command {
+        Some(WatcherCommand::Prune) => {
+            let retention_policy = config
+                .retention_policy()
+                .expect("--retain-blocks is required for pruning");
+            let summary = watcher_db
+                .prune(&retention_policy)
+                .expect("Failed pruning watcher db");
+            log::info!(logger, "Pruning done: {:?}", summary);
+            return;
+        }
+        Some(WatcherCommand::Compact { dest }) => {
+            watcher_db
+                .compact_to(dest)
+                .expect("Failed compacting watcher db");
+            log::info!(logger, "Wrote compacted watcher db to {:?}", dest);
+            return;
+        }
+        Some(WatcherCommand::Audit {
+            tcb_policy,
+            block_index,
+        }) => {
+            let policy = TcbPolicy::load(tcb_policy).expect("Failed loading TCB policy");
+            let audits =
+                audit_block(&watcher_db, &policy, *block_index).expect("Failed auditing block");
+            if audits.is_empty() {
+                log::info!(logger, "No signatures found for block {}", block_index);
+            }
+            for audit in audits {
+                log::info!(
+                    logger,
+                    "Block {} signed by {} at {} ({}): {}",
+                    block_index,
+                    hex::encode(audit.signer.to_bytes()),
+                    audit.signed_at,
+                    audit.src_url,
+                    audit.verdict
+                );
+            }
+            return;
+        }
+        None => {}
+    }
+
     let watcher = Watcher::new(watcher_db.clone(), config.store_block_data, logger.clone())
         .expect("Failed creating watcher");
 
     let _verification_reports_collector = <AttestationEvidenceCollector>::new(
-        watcher_db,
+        watcher_db.clone(),
         sources_config.sources().to_vec(),
         config.poll_interval,
         logger.clone(),
     );
 
     // Start watcher sync thread.
-    let mut sync_thread = WatcherSyncThread::start(watcher, config.clone(), logger.clone())
-        .expect("Failed starting watcher sync thread.");
+    let mut sync_thread =
+        WatcherSyncThread::start(watcher, watcher_db, config.clone(), logger.clone())
+            .expect("Failed starting watcher sync thread.");
 
     // Start gRPC server.
     let health_check_callback: Arc<dyn Fn(&str) -> HealthCheckStatus + Sync + Send> =
@@ -118,14 +167,24 @@ pub struct WatcherSyncThread {
 
 const MAX_BLOCKS_PER_SYNC_ITERATION: usize = 1000;
 
+/// How often the sync thread prunes the watcher db, if pruning is enabled.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(600);
+
 impl WatcherSyncThread {
     /// Start the sync thread.
-    pub fn start(watcher: Watcher, config: WatcherConfig, logger: Logger) -> Result<Self, Error> {
+    pub fn start(
+        watcher: Watcher,
+        watcher_db: WatcherDB,
+        config: WatcherConfig,
+        logger: Logger,
+    ) -> Result<Self, Error> {
         let stop_requested = Arc::new(AtomicBool::new(false));
         let thread_stop_requested = stop_requested.clone();
 
         let join_handle = Some(ThreadBuilder::new().name("WatcherSync".to_string()).spawn(
-            move || Self::thread_entrypoint(watcher, config, thread_stop_requested, logger),
+            move || {
+                Self::thread_entrypoint(watcher, watcher_db, config, thread_stop_requested, logger)
+            },
         )?);
 
         Ok(Self {
@@ -146,12 +205,16 @@ impl WatcherSyncThread {
 
     fn thread_entrypoint(
         watcher: Watcher,
+        watcher_db: WatcherDB,
         config: WatcherConfig,
         stop_requested: Arc<AtomicBool>,
         logger: Logger,
     ) {
         log::debug!(logger, "Watcher sync thread started");
 
+        let retention_policy = config.retention_policy();
+        let mut last_pruned: Option<Instant> = None;
+
         loop {
             if stop_requested.load(Ordering::SeqCst) {
                 log::debug!(logger, "Watcher sync thread stop requested.");
@@ -170,6 +233,16 @@ impl WatcherSyncThread {
 
             watcher.collect_metrics(None);
 
+            if let Some(retention_policy) = retention_policy.as_ref() {
+                if last_pruned.map_or(true, |instant| instant.elapsed() >= PRUNE_INTERVAL) {
+                    // A failure to prune is not fatal, we will try again later.
+                    if let Err(err) = watcher_db.prune(retention_policy) {
+                        log::error!(logger, "Failed pruning watcher db: {}", err);
+                    }
+                    last_pruned = Some(Instant::now());
+                }
+            }
+
             // Decide next step before continuing based on sync result
             match sync_result {
                 SyncResult::AllBlocksSynced => {
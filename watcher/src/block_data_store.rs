@@ -10,7 +10,7 @@ use mc_blockchain_types::{
 };
 use mc_common::{
     logger::{log, Logger},
-    HashMap,
+    HashMap, HashSet,
 };
 use mc_crypto_digestible::{Digestible, MerlinTranscript};
 use mc_util_serial::{decode, encode};
@@ -237,6 +237,69 @@ impl BlockDataStore {
         Ok(())
     }
 
+    /// Remove all block data for blocks with an index lower than
+    /// `first_kept_block`, returning the number of BlockDatas removed.
+    /// Block/BlockContents are removed once no kept BlockData refers to them
+    /// anymore.
+    pub fn prune_before(
+        &self,
+        db_txn: &mut RwTransaction<'_>,
+        first_kept_block: BlockIndex,
+    ) -> Result<usize, WatcherDBError> {
+        let mut num_removed = 0;
+        let mut removed_block_hashes = HashSet::default();
+        let mut removed_block_contents_hashes = HashSet::default();
+
+        let mut cursor = db_txn.open_rw_cursor(self.block_datas_by_index)?;
+        for (key_bytes, value_bytes) in cursor.iter_start().filter_map(Result::ok) {
+            // The key is the block index, followed by the source url.
+            match key_to_block_index(key_bytes) {
+                Some(block_index) if block_index >= first_kept_block => break,
+                Some(_) => {}
+                None => continue,
+            }
+
+            let stored_block_data: StoredBlockData = decode(value_bytes)?;
+            removed_block_hashes.insert(stored_block_data.block_hash);
+            removed_block_contents_hashes.insert(stored_block_data.block_contents_hash);
+            cursor.del(WriteFlags::empty())?;
+            num_removed += 1;
+        }
+        drop(cursor);
+
+        if num_removed == 0 {
+            return Ok(0);
+        }
+
+        // Blocks and their contents are de-duplicated, so only remove the ones
+        // that none of the kept block datas point at.
+        let mut cursor = db_txn.open_ro_cursor(self.block_datas_by_index)?;
+        for (_key_bytes, value_bytes) in cursor
+            .iter_from(first_kept_block.to_be_bytes())
+            .filter_map(Result::ok)
+        {
+            let stored_block_data: StoredBlockData = decode(value_bytes)?;
+            removed_block_hashes.remove(&stored_block_data.block_hash);
+            removed_block_contents_hashes.remove(&stored_block_data.block_contents_hash);
+        }
+        drop(cursor);
+
+        for hash in removed_block_hashes {
+            match db_txn.del(self.blocks_by_hash, &hash, None) {
+                Ok(()) | Err(lmdb::Error::NotFound) => {}
+                Err(err) => Err(err)?,
+            }
+        }
+        for hash in removed_block_contents_hashes {
+            match db_txn.del(self.block_contents_by_hash, &hash, None) {
+                Ok(()) | Err(lmdb::Error::NotFound) => {}
+                Err(err) => Err(err)?,
+            }
+        }
+
+        Ok(num_removed)
+    }
+
     fn store_block(
         &self,
         db_txn: &mut RwTransaction<'_>,
@@ -292,6 +355,12 @@ impl BlockDataStore {
     }
 }
 
+/// Get the block index from a `block_datas_by_index` database key.
+fn key_to_block_index(key_bytes: &[u8]) -> Option<BlockIndex> {
+    let index_bytes = key_bytes.get(..core::mem::size_of::<BlockIndex>())?;
+    Some(BlockIndex::from_be_bytes(index_bytes.try_into().ok()?))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
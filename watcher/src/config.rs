@@ -3,7 +3,8 @@
 
 //! Configuration parameters for the watcher test utility.
 
-use clap::Parser;
+use crate::watcher_db::RetentionPolicy;
+use clap::{Parser, Subcommand};
 use mc_util_parse::parse_duration_in_seconds;
 use mc_util_uri::{ConsensusClientUri, WatcherUri};
 use serde::{Deserialize, Serialize};
@@ -44,9 +45,60 @@ pub struct WatcherConfig {
         env = "MC_CLIENT_LISTEN_URI"
     )]
     pub client_listen_uri: WatcherUri,
+
+    /// (Optional) Number of most recent blocks to keep block signatures and
+    /// block data for. Older blocks are pruned while syncing. By default
+    /// nothing is pruned.
+    #[clap(long, env = "MC_RETAIN_BLOCKS")]
+    pub retain_blocks: Option<u64>,
+
+    /// When pruning, also remove the attestation evidence of signers that only
+    /// signed pruned blocks.
+    #[clap(long, env = "MC_PRUNE_ATTESTATION_EVIDENCE")]
+    pub prune_attestation_evidence: bool,
+
+    /// (Optional) Run a maintenance command instead of syncing.
+    #[clap(subcommand)]
+    pub command: Option<WatcherCommand>,
+}
+
+/// Maintenance commands for the watcher db.
+#[derive(Clone, Debug, Subcommand)]
+pub enum WatcherCommand {
+    /// Prune the watcher db according to `--retain-blocks` and exit.
+    Prune,
+
+    /// Write a compacted copy of the watcher db and exit.
+    Compact {
+        /// Path to write the compacted watcher db to. Must not contain a
+        /// database already.
+        #[clap(long, env = "MC_COMPACTED_WATCHER_DB")]
+        dest: PathBuf,
+    },
+
+    /// Check whether the signers of a block were acceptable enclaves at the
+    /// time they signed it, and exit.
+    Audit {
+        /// Path to the TCB policy json file listing the identities which were
+        /// acceptable over time.
+        #[clap(long, env = "MC_TCB_POLICY")]
+        tcb_policy: PathBuf,
+
+        /// The index of the block to audit.
+        #[clap(long)]
+        block_index: u64,
+    },
 }
 
 impl WatcherConfig {
+    /// Get the retention policy, if pruning is enabled.
+    pub fn retention_policy(&self) -> Option<RetentionPolicy> {
+        self.retain_blocks.map(|keep_blocks| RetentionPolicy {
+            keep_blocks,
+            prune_attestation_evidence: self.prune_attestation_evidence,
+        })
+    }
+
     /// Load the sources configuration file.
     pub fn sources_config(&self) -> SourcesConfig {
         // Read configuration file.
@@ -9,6 +9,7 @@ pub mod block_data_store;
 pub mod config;
 pub mod error;
 pub mod metrics;
+pub mod tcb_policy;
 pub mod watcher;
 pub mod watcher_db;
 pub use url::Url;
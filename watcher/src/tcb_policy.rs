@@ -0,0 +1,398 @@
+// Copyright (c) 2018-2024 The MobileCoin Foundation
+
+//! Auditing block signatures against a historical TCB policy.
+//!
+//! The set of enclaves and TCB levels which are acceptable changes over time:
+//! new enclave releases are added, and once Intel publishes an advisory,
+//! evidence which doesn't mitigate it stops being acceptable. A [TcbPolicy]
+//! records this history as a list of epochs, each listing the identities
+//! (measurements and mitigated advisories) that were acceptable during it.
+//! Auditors can then check whether a block was signed by an enclave which was
+//! acceptable at the time the block was signed, using the attestation evidence
+//! the watcher collected for the block signer.
+
+use crate::{
+    attestation_evidence_collector::{ConsensusNodeClient, NodeClient},
+    error::WatcherDBError,
+    watcher_db::WatcherDB,
+};
+use der::DateTime;
+use displaydoc::Display;
+use mc_attest_core::EvidenceKind;
+use mc_attest_verifier::{DcapVerifier, Verifier, DEBUG_ENCLAVE};
+use mc_attest_verifier_types::DcapEvidence;
+use mc_attestation_verifier::{Evidence, TrustedIdentity, VerificationTreeDisplay};
+use mc_crypto_keys::Ed25519Public;
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path, time::Duration};
+use url::Url;
+
+/// A period of time during which a set of enclave identities was acceptable.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TcbPolicyEpoch {
+    /// A name for the epoch, e.g. the release it covers. This is only used
+    /// for display.
+    #[serde(default)]
+    pub name: String,
+
+    /// The start of the epoch, in seconds since the Unix epoch.
+    pub valid_from: u64,
+
+    /// (Optional) The end of the epoch, in seconds since the Unix epoch. The
+    /// epoch is open-ended if this is not set.
+    #[serde(default)]
+    pub valid_until: Option<u64>,
+
+    /// The identities which were acceptable during the epoch, in the same
+    /// format as a trusted identities file.
+    pub identities: Vec<TrustedIdentity>,
+}
+
+impl TcbPolicyEpoch {
+    /// Whether the epoch covers `timestamp`.
+    pub fn covers(&self, timestamp: u64) -> bool {
+        self.valid_from <= timestamp && self.valid_until.map_or(true, |until| timestamp < until)
+    }
+}
+
+/// A time-indexed database of the enclave identities and TCB levels which
+/// were acceptable.
+///
+/// Epochs may overlap, e.g. during an enclave upgrade, in which case the
+/// identities of every epoch covering a time are acceptable at that time.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct TcbPolicy {
+    epochs: Vec<TcbPolicyEpoch>,
+}
+
+impl TcbPolicy {
+    /// Create a policy from a list of epochs.
+    pub fn new(epochs: Vec<TcbPolicyEpoch>) -> Self {
+        Self { epochs }
+    }
+
+    /// Parse a policy from json bytes.
+    pub fn from_json(json: &[u8]) -> Result<Self, TcbPolicyError> {
+        let policy: Self = serde_json::from_slice(json)?;
+        for epoch in policy.epochs.iter() {
+            if epoch
+                .valid_until
+                .is_some_and(|until| until <= epoch.valid_from)
+            {
+                return Err(TcbPolicyError::EmptyEpoch(epoch.name.clone()));
+            }
+        }
+        Ok(policy)
+    }
+
+    /// Load a policy from a json file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, TcbPolicyError> {
+        Self::from_json(&fs::read(path)?)
+    }
+
+    /// The epochs of the policy.
+    pub fn epochs(&self) -> &[TcbPolicyEpoch] {
+        &self.epochs
+    }
+
+    /// The identities which were acceptable at `timestamp`.
+    pub fn identities_at(&self, timestamp: u64) -> Vec<TrustedIdentity> {
+        self.epochs
+            .iter()
+            .filter(|epoch| epoch.covers(timestamp))
+            .flat_map(|epoch| epoch.identities.iter().cloned())
+            .collect()
+    }
+
+    /// Check whether `evidence` shows an enclave which was acceptable at
+    /// `timestamp`, and which holds `signer`.
+    pub fn verify_evidence(
+        &self,
+        evidence: &EvidenceKind,
+        signer: &Ed25519Public,
+        timestamp: u64,
+    ) -> SignatureVerdict {
+        let identities = self.identities_at(timestamp);
+        if identities.is_empty() {
+            return SignatureVerdict::NoPolicy;
+        }
+
+        match ConsensusNodeClient::get_block_signer(evidence) {
+            Ok(evidence_signer) if &evidence_signer == signer => {}
+            Ok(_) => {
+                return SignatureVerdict::Unacceptable(
+                    "The evidence is for a different signer".to_owned(),
+                )
+            }
+            Err(err) => return SignatureVerdict::Unacceptable(err),
+        }
+
+        let result = match evidence {
+            EvidenceKind::Epid(report) => {
+                let mut verifier = Verifier::default();
+                verifier.identities(&identities).debug(DEBUG_ENCLAVE);
+                verifier
+                    .verify(report)
+                    .map(|_| ())
+                    .map_err(|err| err.to_string())
+            }
+            EvidenceKind::Dcap(evidence) => verify_dcap_evidence(evidence, &identities, timestamp),
+        };
+
+        match result {
+            Ok(()) => SignatureVerdict::Acceptable,
+            Err(err) => SignatureVerdict::Unacceptable(err),
+        }
+    }
+}
+
+// DCAP collateral expires, so it is checked against the time of the signature
+// rather than the current time.
+fn verify_dcap_evidence(
+    evidence: &mc_attest_verifier_types::prost::DcapEvidence,
+    identities: &[TrustedIdentity],
+    timestamp: u64,
+) -> Result<(), String> {
+    let DcapEvidence {
+        quote,
+        collateral,
+        report_data,
+    } = DcapEvidence::try_from(evidence).map_err(|err| err.to_string())?;
+    let time = DateTime::from_unix_duration(Duration::from_secs(timestamp))
+        .map_err(|err| format!("Signature time out of range: {err}"))?;
+
+    let verifier = DcapVerifier::new(identities, time, report_data);
+    let evidence = Evidence::new(quote, collateral).map_err(|err| err.to_string())?;
+    let verification_output = verifier.verify(&evidence);
+    if verification_output.is_success().into() {
+        Ok(())
+    } else {
+        Err(VerificationTreeDisplay::new(&verifier, verification_output).to_string())
+    }
+}
+
+/// The result of auditing a block signature.
+#[derive(Clone, Debug, Display, Eq, PartialEq)]
+pub enum SignatureVerdict {
+    /// The signer was an acceptable enclave at the time of the signature
+    Acceptable,
+
+    /// The policy doesn't cover the time of the signature
+    NoPolicy,
+
+    /// No attestation evidence was collected for the signer
+    NoEvidence,
+
+    /// The signer was not an acceptable enclave at the time of the signature:
+    /// {0}
+    Unacceptable(String),
+}
+
+impl SignatureVerdict {
+    /// Whether the signer was known to be acceptable.
+    pub fn is_acceptable(&self) -> bool {
+        self == &Self::Acceptable
+    }
+}
+
+/// The audit of one signature of a block.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SignatureAudit {
+    /// The source the signature was fetched from.
+    pub src_url: Url,
+
+    /// The block signer.
+    pub signer: Ed25519Public,
+
+    /// When the block was signed, in seconds since the Unix epoch.
+    pub signed_at: u64,
+
+    /// Whether the signer was acceptable at that time.
+    pub verdict: SignatureVerdict,
+}
+
+/// Check whether `signer` was an acceptable enclave at `timestamp`, according
+/// to the attestation evidence collected for it from `src_url`.
+///
+/// The signer is acceptable if any of the evidence collected for it
+/// verifies. Otherwise, the reason the first piece of evidence was rejected is
+/// returned.
+pub fn audit_signer(
+    watcher_db: &WatcherDB,
+    policy: &TcbPolicy,
+    signer: &Ed25519Public,
+    src_url: &Url,
+    timestamp: u64,
+) -> Result<SignatureVerdict, WatcherDBError> {
+    let evidence = watcher_db.attestation_evidence_for_signer_and_url(signer, src_url)?;
+
+    let mut verdict = SignatureVerdict::NoEvidence;
+    for evidence in evidence.iter().flatten() {
+        match policy.verify_evidence(evidence, signer, timestamp) {
+            SignatureVerdict::Acceptable => return Ok(SignatureVerdict::Acceptable),
+            rejected if verdict == SignatureVerdict::NoEvidence => verdict = rejected,
+            _ => {}
+        }
+    }
+    Ok(verdict)
+}
+
+/// Audit every signature the watcher has for a block.
+pub fn audit_block(
+    watcher_db: &WatcherDB,
+    policy: &TcbPolicy,
+    block_index: u64,
+) -> Result<Vec<SignatureAudit>, WatcherDBError> {
+    watcher_db
+        .get_block_signatures(block_index)?
+        .into_iter()
+        .map(|signature_data| {
+            let src_url = Url::parse(&signature_data.src_url)?;
+            let signer = *signature_data.block_signature.signer();
+            let signed_at = signature_data.block_signature.signed_at();
+            let verdict = audit_signer(watcher_db, policy, &signer, &src_url, signed_at)?;
+            Ok(SignatureAudit {
+                src_url,
+                signer,
+                signed_at,
+                verdict,
+            })
+        })
+        .collect()
+}
+
+/// Errors loading a TCB policy.
+#[derive(Debug, Display)]
+pub enum TcbPolicyError {
+    /// IO: {0}
+    IO(std::io::Error),
+
+    /// Invalid TCB policy json: {0}
+    Json(serde_json::Error),
+
+    /// The epoch "{0}" ends before it starts
+    EmptyEpoch(String),
+}
+
+impl From<std::io::Error> for TcbPolicyError {
+    fn from(src: std::io::Error) -> Self {
+        Self::IO(src)
+    }
+}
+
+impl From<serde_json::Error> for TcbPolicyError {
+    fn from(src: serde_json::Error) -> Self {
+        Self::Json(src)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::watcher_db::tests::{setup_blocks, setup_watcher_db};
+    use mc_attest_core::{VerificationReport, VerificationSignature};
+    use mc_blockchain_types::BlockSignature;
+    use mc_common::logger::{test_with_logger, Logger};
+    use mc_crypto_keys::Ed25519Pair;
+    use mc_util_from_random::FromRandom;
+    use rand_core::SeedableRng;
+    use rand_hc::Hc128Rng;
+
+    const POLICY_JSON: &str = r#"{
+        "epochs": [
+            {
+                "name": "v5",
+                "valid_from": 1000,
+                "valid_until": 2000,
+                "identities": [
+                    {
+                        "MRENCLAVE": "207c9705bf640fdb960034595433ee1ff914f9154fbe4bc7fc8a97e912961e5c",
+                        "mitigated_hardening_advisories": ["INTEL-SA-00334"]
+                    }
+                ]
+            },
+            {
+                "name": "v6",
+                "valid_from": 1500,
+                "identities": [
+                    {
+                        "MRENCLAVE": "e35bc15ee92775029a60a715dca05d310ad40993f56ad43bca7e649ccc9021b5",
+                        "mitigated_hardening_advisories": ["INTEL-SA-00334", "INTEL-SA-00615"]
+                    }
+                ]
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn identities_are_selected_by_time() {
+        let policy = TcbPolicy::from_json(POLICY_JSON.as_bytes()).unwrap();
+        assert_eq!(policy.epochs().len(), 2);
+
+        assert!(policy.identities_at(999).is_empty());
+        assert_eq!(policy.identities_at(1000).len(), 1);
+        // During the upgrade, both releases are acceptable.
+        assert_eq!(policy.identities_at(1500).len(), 2);
+        assert_eq!(policy.identities_at(2000).len(), 1);
+        assert_eq!(policy.identities_at(u64::MAX).len(), 1);
+    }
+
+    #[test]
+    fn epochs_must_not_be_empty() {
+        let json = r#"{"epochs": [{"name": "v5", "valid_from": 10, "valid_until": 10, "identities": []}]}"#;
+        assert!(matches!(
+            TcbPolicy::from_json(json.as_bytes()),
+            Err(TcbPolicyError::EmptyEpoch(name)) if name == "v5"
+        ));
+    }
+
+    #[test_with_logger]
+    fn audit_block_signatures(logger: Logger) {
+        let mut rng = Hc128Rng::from_seed([8u8; 32]);
+        let url = Url::parse("http://www.my_url1.com").unwrap();
+        let watcher_db = setup_watcher_db(&[url.clone()], logger);
+        let blocks = setup_blocks();
+        let policy = TcbPolicy::from_json(POLICY_JSON.as_bytes()).unwrap();
+
+        let signer = Ed25519Pair::from_random(&mut rng);
+        let mut signature = BlockSignature::from_block_and_keypair(blocks[1].block(), &signer)
+            .expect("Could not sign block");
+        signature.set_signed_at(1200);
+        watcher_db
+            .add_block_signature(&url, 1, signature, "00/01".to_string())
+            .unwrap();
+
+        // No evidence has been collected for the signer yet.
+        let audits = audit_block(&watcher_db, &policy, 1).unwrap();
+        assert_eq!(
+            audits,
+            vec![SignatureAudit {
+                src_url: url.clone(),
+                signer: signer.public_key(),
+                signed_at: 1200,
+                verdict: SignatureVerdict::NoEvidence,
+            }]
+        );
+
+        // Evidence which doesn't attest to the signer is rejected.
+        let report = VerificationReport {
+            sig: VerificationSignature::from(vec![1; 32]),
+            chain: vec![vec![1; 16]],
+            http_body: "".to_owned(),
+        };
+        watcher_db
+            .add_attestation_evidence(
+                &url,
+                &signer.public_key(),
+                &report.into(),
+                &[signer.public_key()],
+            )
+            .unwrap();
+        let verdict = audit_signer(&watcher_db, &policy, &signer.public_key(), &url, 1200).unwrap();
+        assert!(matches!(verdict, SignatureVerdict::Unacceptable(_)));
+
+        // Nothing is acceptable outside of the policy.
+        let verdict = audit_signer(&watcher_db, &policy, &signer.public_key(), &url, 10).unwrap();
+        assert_eq!(verdict, SignatureVerdict::NoPolicy);
+    }
+}
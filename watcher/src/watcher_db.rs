@@ -2,12 +2,18 @@
 
 //! The watcher database
 
-use crate::{block_data_store::BlockDataStore, error::WatcherDBError};
+use crate::{
+    block_data_store::{
+        BlockDataStore, BLOCKS_BY_HASH_DB_NAME, BLOCK_CONTENTS_BY_HASH_DB_NAME,
+        BLOCK_DATAS_BY_INDEX_DB_NAME,
+    },
+    error::WatcherDBError,
+};
 
 use mc_blockchain_types::{BlockData, BlockIndex, BlockSignature};
 use mc_common::{
     logger::{log, Logger},
-    HashMap,
+    HashMap, HashSet,
 };
 use mc_crypto_digestible::{Digestible, MerlinTranscript};
 use mc_crypto_keys::Ed25519Public;
@@ -35,6 +41,8 @@ use url::Url;
 const MAX_LMDB_FILE_SIZE: usize = 1 << 40; // 1 TB
 /// LMDB parameter: max number of databases.
 const MAX_DATABASES: u32 = 10;
+/// Number of entries copied per write transaction when compacting.
+const COMPACT_ENTRIES_PER_TXN: usize = 10_000;
 
 /// Metadata store settings that are used for version control.
 #[derive(Clone, Default, Debug)]
@@ -1048,6 +1056,225 @@ impl WatcherDB {
         db_txn.commit()?;
         Ok(())
     }
+
+    /// Remove block signatures and block data that fall outside of the
+    /// retention window of `retention_policy`. The window ends at the highest
+    /// block synced from any of the configured URLs.
+    ///
+    /// Last synced blocks and the configuration are never removed. Attestation
+    /// evidence is only removed if the policy asks for it, and then only for
+    /// signers which signed none of the kept blocks. Should such a signer show
+    /// up again, its attestation evidence is queued for polling as usual.
+    pub fn prune(
+        &self,
+        retention_policy: &RetentionPolicy,
+    ) -> Result<PruneSummary, WatcherDBError> {
+        if !self.write_allowed {
+            return Err(WatcherDBError::ReadOnly);
+        }
+
+        let mut db_txn = self.env.begin_rw_txn()?;
+
+        let highest_synced = self
+            .get_url_to_last_synced(&db_txn)?
+            .values()
+            .filter_map(|opt_block_index| *opt_block_index)
+            .max();
+        let first_kept_block = match highest_synced {
+            Some(block_index) => (block_index + 1).saturating_sub(retention_policy.keep_blocks),
+            None => return Ok(PruneSummary::default()),
+        };
+
+        let mut summary = PruneSummary {
+            first_kept_block,
+            ..Default::default()
+        };
+
+        // Remove block signatures, remembering who signed the removed blocks.
+        let mut pruned_signers = HashSet::default();
+        let mut cursor = db_txn.open_rw_cursor(self.block_signatures)?;
+        for (key_bytes, value_bytes) in cursor.iter_start().filter_map(Result::ok) {
+            if bytes_to_block_index(key_bytes)? >= first_kept_block {
+                break;
+            }
+
+            let signature_data: BlockSignatureData = decode(value_bytes)?;
+            pruned_signers.insert(*signature_data.block_signature.signer());
+            cursor.del(WriteFlags::empty())?;
+            summary.block_signatures_removed += 1;
+        }
+        drop(cursor);
+
+        // Remove block data.
+        summary.block_datas_removed = self
+            .block_data_store
+            .prune_before(&mut db_txn, first_kept_block)?;
+
+        // Remove attestation evidence of signers which only signed removed blocks.
+        if retention_policy.prune_attestation_evidence && !pruned_signers.is_empty() {
+            let mut cursor = db_txn.open_ro_cursor(self.block_signatures)?;
+            for (_key_bytes, value_bytes) in cursor
+                .iter_from(first_kept_block.to_be_bytes())
+                .filter_map(Result::ok)
+            {
+                let signature_data: BlockSignatureData = decode(value_bytes)?;
+                pruned_signers.remove(signature_data.block_signature.signer());
+            }
+            drop(cursor);
+
+            summary.attestation_evidence_removed =
+                self.remove_attestation_evidence_for_signers(&mut db_txn, &pruned_signers)?;
+        }
+
+        // Done
+        db_txn.commit()?;
+
+        log::info!(
+            self.logger,
+            "Pruned watcher db up to block {}: {:?}",
+            first_kept_block,
+            summary
+        );
+        Ok(summary)
+    }
+
+    /// Remove the attestation evidence of the given signers, for all source
+    /// urls. Attestation evidence contents are removed once no signer refers
+    /// to them anymore.
+    fn remove_attestation_evidence_for_signers(
+        &self,
+        db_txn: &mut RwTransaction<'_>,
+        signers: &HashSet<Ed25519Public>,
+    ) -> Result<usize, WatcherDBError> {
+        let signer_key_size = <Ed25519Public as ReprBytes>::Size::USIZE;
+        let mut num_removed = 0;
+        let mut removed_hashes = HashSet::default();
+
+        let mut cursor = db_txn.open_rw_cursor(self.attestation_evidence_by_signer)?;
+        for (key_bytes, value_bytes) in cursor.iter_start().filter_map(Result::ok) {
+            // The key format is 32 bytes signer public key followed by tx source url.
+            if key_bytes.len() < signer_key_size {
+                continue;
+            }
+
+            let signer = Ed25519Public::try_from(&key_bytes[..signer_key_size])?;
+            if signers.contains(&signer) {
+                removed_hashes.insert(value_bytes.to_vec());
+                cursor.del(WriteFlags::empty())?;
+                num_removed += 1;
+            }
+        }
+        drop(cursor);
+
+        // The same attestation evidence may be shared by several signers.
+        let mut cursor = db_txn.open_ro_cursor(self.attestation_evidence_by_signer)?;
+        for (_key_bytes, value_bytes) in cursor.iter_start().filter_map(Result::ok) {
+            removed_hashes.remove(value_bytes);
+        }
+        drop(cursor);
+
+        for hash in removed_hashes {
+            match db_txn.del(self.attestation_evidence_by_hash, &hash, None) {
+                Ok(()) | Err(lmdb::Error::NotFound) => {}
+                Err(err) => Err(err)?,
+            }
+        }
+
+        Ok(num_removed)
+    }
+
+    /// Write a compacted copy of the database into `dest_path`.
+    ///
+    /// LMDB never shrinks its data file, so space freed by [WatcherDB::prune]
+    /// is only reused for new data. Copying the contents into a fresh
+    /// database gets rid of it. The copy is made from a read transaction, so
+    /// the watcher can keep syncing in the meantime; anything written after
+    /// the copy started is not part of it. The copy can then replace the
+    /// original database while the watcher is stopped.
+    pub fn compact_to(&self, dest_path: &Path) -> Result<(), WatcherDBError> {
+        if dest_path.join("data.mdb").exists() {
+            return Err(WatcherDBError::AlreadyExists);
+        }
+        std::fs::create_dir_all(dest_path)?;
+        Self::create(dest_path)?;
+
+        let dest_env = Environment::new()
+            .set_max_dbs(MAX_DATABASES)
+            .set_map_size(MAX_LMDB_FILE_SIZE)
+            .open(dest_path)?;
+
+        let src_txn = self.env.begin_ro_txn()?;
+        for db_name in [
+            WatcherDbMetadataStoreSettings::DB_NAME,
+            BLOCK_SIGNATURES_DB_NAME,
+            ATTESTATION_EVIDENCE_BY_BLOCK_SIGNER_DB_NAME,
+            ATTESTATION_EVIDENCE_BY_HASH_DB_NAME,
+            ATTESTATION_EVIDENCE_POLL_QUEUE_DB_NAME,
+            LAST_SYNCED_DB_NAME,
+            CONFIG_DB_NAME,
+            BLOCK_DATAS_BY_INDEX_DB_NAME,
+            BLOCKS_BY_HASH_DB_NAME,
+            BLOCK_CONTENTS_BY_HASH_DB_NAME,
+        ] {
+            let src_db = self.env.open_db(Some(db_name))?;
+            let dest_db = dest_env.open_db(Some(db_name))?;
+
+            let mut cursor = src_txn.open_ro_cursor(src_db)?;
+            let mut dest_txn = dest_env.begin_rw_txn()?;
+            let mut num_copied = 0;
+            for result in cursor.iter_start() {
+                let (key_bytes, value_bytes) = result?;
+                dest_txn.put(dest_db, &key_bytes, &value_bytes, WriteFlags::empty())?;
+
+                // Avoid holding the whole database in a single write transaction.
+                num_copied += 1;
+                if num_copied % COMPACT_ENTRIES_PER_TXN == 0 {
+                    dest_txn.commit()?;
+                    dest_txn = dest_env.begin_rw_txn()?;
+                }
+            }
+            dest_txn.commit()?;
+
+            log::info!(
+                self.logger,
+                "Copied {} entries of {} into {:?}",
+                num_copied,
+                db_name,
+                dest_path
+            );
+        }
+
+        // Done
+        Ok(())
+    }
+}
+
+/// Retention policy for [WatcherDB::prune].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RetentionPolicy {
+    /// Number of most recent blocks to keep block signatures and block data
+    /// for.
+    pub keep_blocks: u64,
+
+    /// Whether to also remove the attestation evidence of signers that only
+    /// signed removed blocks.
+    pub prune_attestation_evidence: bool,
+}
+
+/// What a call to [WatcherDB::prune] removed.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct PruneSummary {
+    /// Data for blocks below this index was removed.
+    pub first_kept_block: u64,
+
+    /// Number of block signatures removed.
+    pub block_signatures_removed: usize,
+
+    /// Number of block datas removed.
+    pub block_datas_removed: usize,
+
+    /// Number of (signer, tx source url) attestation evidence entries removed.
+    pub attestation_evidence_removed: usize,
 }
 
 /// Open an existing WatcherDB or create a new one in read-write mode.
@@ -1086,6 +1313,13 @@ fn bytes_to_url(bytes: &[u8]) -> Result<Url, WatcherDBError> {
     Ok(Url::parse(str::from_utf8(bytes)?)?)
 }
 
+fn bytes_to_block_index(bytes: &[u8]) -> Result<u64, WatcherDBError> {
+    let bytes = bytes
+        .try_into()
+        .map_err(|_| WatcherDBError::Deserialization)?;
+    Ok(u64::from_be_bytes(bytes))
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
@@ -2054,4 +2288,218 @@ pub mod tests {
             );
         })
     }
+
+    /// Add block data, signatures and attestation evidence for all of
+    /// `blocks_data` from each of `urls`.
+    fn add_blocks_for_urls(
+        watcher_db: &WatcherDB,
+        urls: &[Url],
+        blocks_data: &[BlockData],
+        verification_report: &VerificationReport,
+    ) {
+        for block_data in blocks_data {
+            for url in urls {
+                watcher_db.add_block_data(url, block_data).unwrap();
+                watcher_db
+                    .add_block_signature(
+                        url,
+                        block_data.block().index,
+                        block_data.signature().cloned().unwrap(),
+                        String::from("00/00"),
+                    )
+                    .unwrap();
+                watcher_db
+                    .add_attestation_evidence(
+                        url,
+                        block_data.signature().unwrap().signer(),
+                        &verification_report.clone().into(),
+                        &[],
+                    )
+                    .unwrap();
+            }
+        }
+    }
+
+    #[test_with_logger]
+    fn test_prune(logger: Logger) {
+        let url1 = Url::parse("http://www.my_url1.com").unwrap();
+        let url2 = Url::parse("http://www.my_url2.com").unwrap();
+        let urls = vec![url1.clone(), url2.clone()];
+
+        let verification_report = VerificationReport {
+            sig: vec![1; 32].into(),
+            chain: vec![vec![2; 16], vec![3; 32]],
+            http_body: "test body a".to_owned(),
+        };
+
+        let blocks_data = setup_blocks();
+        let watcher_db = setup_watcher_db(&urls, logger);
+
+        let retention_policy = RetentionPolicy {
+            keep_blocks: 4,
+            prune_attestation_evidence: true,
+        };
+
+        // Pruning an empty database should work.
+        assert_eq!(
+            watcher_db.prune(&retention_policy).unwrap(),
+            PruneSummary::default()
+        );
+
+        add_blocks_for_urls(&watcher_db, &urls, &blocks_data, &verification_report);
+        let last_synced = watcher_db.last_synced_blocks().unwrap();
+
+        // Only the last 4 of the 10 blocks should be kept.
+        assert_eq!(
+            watcher_db.prune(&retention_policy).unwrap(),
+            PruneSummary {
+                first_kept_block: 6,
+                block_signatures_removed: 12,
+                block_datas_removed: 12,
+                attestation_evidence_removed: 12,
+            }
+        );
+
+        for block_data in blocks_data.iter() {
+            let block_index = block_data.block().index;
+            let signer = block_data.signature().unwrap().signer();
+            let block_sigs = watcher_db.get_block_signatures(block_index).unwrap();
+            let verification_reports = watcher_db.attestation_evidence_for_signer(signer).unwrap();
+
+            if block_index < 6 {
+                assert_eq!(block_sigs, vec![]);
+                assert_eq!(
+                    watcher_db.get_block_data(&url1, block_index),
+                    Err(WatcherDBError::NotFound)
+                );
+                assert_eq!(verification_reports, HashMap::default());
+            } else {
+                assert_eq!(block_sigs.len(), 2);
+                assert_eq!(
+                    watcher_db.get_block_data(&url1, block_index).unwrap(),
+                    *block_data
+                );
+                assert_eq!(
+                    watcher_db.get_block_data(&url2, block_index).unwrap(),
+                    *block_data
+                );
+                assert_eq!(
+                    verification_reports,
+                    HashMap::from_iter(vec![
+                        (url1.clone(), vec![Some(verification_report.clone().into())]),
+                        (url2.clone(), vec![Some(verification_report.clone().into())]),
+                    ])
+                );
+            }
+        }
+
+        // Last synced blocks are kept.
+        assert_eq!(watcher_db.last_synced_blocks().unwrap(), last_synced);
+
+        // Pruning again should not remove anything else.
+        assert_eq!(
+            watcher_db.prune(&retention_policy).unwrap(),
+            PruneSummary {
+                first_kept_block: 6,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test_with_logger]
+    fn test_prune_keeps_attestation_evidence_by_default(logger: Logger) {
+        let url1 = Url::parse("http://www.my_url1.com").unwrap();
+        let urls = vec![url1.clone()];
+
+        let verification_report = VerificationReport {
+            sig: vec![1; 32].into(),
+            chain: vec![vec![2; 16], vec![3; 32]],
+            http_body: "test body a".to_owned(),
+        };
+
+        let blocks_data = setup_blocks();
+        let watcher_db = setup_watcher_db(&urls, logger);
+        add_blocks_for_urls(&watcher_db, &urls, &blocks_data, &verification_report);
+
+        let summary = watcher_db
+            .prune(&RetentionPolicy {
+                keep_blocks: 1,
+                prune_attestation_evidence: false,
+            })
+            .unwrap();
+        assert_eq!(summary.block_signatures_removed, 9);
+        assert_eq!(summary.attestation_evidence_removed, 0);
+
+        for block_data in blocks_data.iter() {
+            assert_eq!(
+                watcher_db
+                    .attestation_evidence_for_signer(block_data.signature().unwrap().signer())
+                    .unwrap(),
+                HashMap::from_iter(vec![(
+                    url1.clone(),
+                    vec![Some(verification_report.clone().into())]
+                )])
+            );
+        }
+    }
+
+    #[test_with_logger]
+    fn test_compact_to(logger: Logger) {
+        let url1 = Url::parse("http://www.my_url1.com").unwrap();
+        let url2 = Url::parse("http://www.my_url2.com").unwrap();
+        let urls = vec![url1.clone(), url2.clone()];
+
+        let verification_report = VerificationReport {
+            sig: vec![1; 32].into(),
+            chain: vec![vec![2; 16], vec![3; 32]],
+            http_body: "test body a".to_owned(),
+        };
+
+        let blocks_data = setup_blocks();
+        let watcher_db = setup_watcher_db(&urls, logger.clone());
+        add_blocks_for_urls(&watcher_db, &urls, &blocks_data, &verification_report);
+        watcher_db
+            .prune(&RetentionPolicy {
+                keep_blocks: 5,
+                prune_attestation_evidence: true,
+            })
+            .unwrap();
+
+        let dest = TempDir::new().unwrap();
+        watcher_db.compact_to(dest.path()).unwrap();
+
+        // The copy refuses to overwrite an existing database.
+        assert_eq!(
+            watcher_db.compact_to(dest.path()),
+            Err(WatcherDBError::AlreadyExists)
+        );
+
+        let compacted_db = WatcherDB::open_ro(dest.path(), logger).unwrap();
+        assert_eq!(
+            compacted_db.get_config_urls().unwrap(),
+            watcher_db.get_config_urls().unwrap()
+        );
+        assert_eq!(
+            compacted_db.last_synced_blocks().unwrap(),
+            watcher_db.last_synced_blocks().unwrap()
+        );
+        for block_data in blocks_data.iter() {
+            let block_index = block_data.block().index;
+            let signer = block_data.signature().unwrap().signer();
+            assert_eq!(
+                compacted_db.get_block_signatures(block_index).unwrap(),
+                watcher_db.get_block_signatures(block_index).unwrap()
+            );
+            assert_eq!(
+                compacted_db.get_block_data_map(block_index).unwrap(),
+                watcher_db.get_block_data_map(block_index).unwrap()
+            );
+            assert_eq!(
+                compacted_db
+                    .attestation_evidence_for_signer(signer)
+                    .unwrap(),
+                watcher_db.attestation_evidence_for_signer(signer).unwrap()
+            );
+        }
+    }
 }